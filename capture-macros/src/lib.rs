@@ -0,0 +1,128 @@
+//! Procedural macros for [`tracing-capture`]. The macros are re-exported by `tracing-capture`
+//! if its `macros` feature is enabled; this crate is not meant to be used directly.
+//!
+//! [`tracing-capture`]: https://docs.rs/tracing-capture/
+
+// Documentation settings.
+#![doc(html_root_url = "https://docs.rs/tracing-capture-macros/0.1.0")]
+// Linter settings.
+#![warn(missing_debug_implementations, missing_docs, bare_trait_objects)]
+#![warn(clippy::all, clippy::pedantic)]
+#![allow(clippy::must_use_candidate, clippy::module_name_repetitions)]
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{quote, quote_spanned};
+use syn::{spanned::Spanned, FnArg, ItemFn, ReturnType, Type};
+
+/// Wraps a test function so that it captures tracing spans and events. See the `tracing-capture`
+/// docs for details.
+#[proc_macro_attribute]
+pub fn test(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let attr = TokenStream2::from(attr);
+    if !attr.is_empty() {
+        let msg = "`#[tracing_capture::test]` does not accept arguments";
+        return syn::Error::new(attr.span(), msg)
+            .into_compile_error()
+            .into();
+    }
+
+    let function = syn::parse_macro_input!(item as ItemFn);
+    match expand_test(function) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.into_compile_error().into(),
+    }
+}
+
+fn expand_test(mut function: ItemFn) -> syn::Result<TokenStream2> {
+    if let Some(asyncness) = &function.sig.asyncness {
+        let msg = "async tests are not supported; install a `CaptureLayer` manually";
+        return Err(syn::Error::new(asyncness.span(), msg));
+    }
+    if function.sig.inputs.len() > 1 {
+        let msg = "test function must have at most one argument, `SharedStorage` \
+                   or `&SharedStorage`";
+        return Err(syn::Error::new(function.sig.inputs.span(), msg));
+    }
+
+    let storage_binding = match function.sig.inputs.pop() {
+        None => quote!(),
+        Some(pair) => match pair.into_value() {
+            FnArg::Typed(arg) => {
+                let (pat, ty) = (&arg.pat, &arg.ty);
+                let value = if matches!(**ty, Type::Reference(_)) {
+                    quote_spanned!(ty.span()=> &__storage)
+                } else {
+                    quote_spanned!(ty.span()=> __storage)
+                };
+                quote!(let #pat: #ty = #value;)
+            }
+            FnArg::Receiver(receiver) => {
+                let msg = "test function cannot have a `self` argument";
+                return Err(syn::Error::new(receiver.span(), msg));
+            }
+        },
+    };
+
+    let return_type = match &function.sig.output {
+        ReturnType::Default => quote!(()),
+        ReturnType::Type(_, ty) => quote!(#ty),
+    };
+    let ItemFn {
+        attrs,
+        vis,
+        sig,
+        block,
+    } = function;
+    Ok(quote! {
+        #[::core::prelude::v1::test]
+        #(#attrs)*
+        #vis #sig {
+            ::tracing_capture::__private::capture_test(
+                |__storage: ::tracing_capture::SharedStorage| -> #return_type {
+                    #storage_binding
+                    #block
+                },
+            )
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{expand_test, quote, ItemFn, TokenStream2};
+
+    fn expand_err(function: TokenStream2) -> String {
+        let function: ItemFn = syn::parse2(function).unwrap();
+        expand_test(function).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn expanding_test_with_storage() {
+        let function: ItemFn = syn::parse_quote! {
+            fn some_test(storage: &SharedStorage) {
+                assert!(storage.lock().all_spans().len() == 0);
+            }
+        };
+        let expanded = expand_test(function).unwrap().to_string();
+        assert!(expanded.contains("capture_test"), "{expanded}");
+        assert!(
+            expanded.contains("let storage : & SharedStorage = & __storage"),
+            "{expanded}"
+        );
+        assert!(expanded.contains("fn some_test ()"), "{expanded}");
+    }
+
+    #[test]
+    fn invalid_test_functions() {
+        let err = expand_err(quote!(
+            async fn some_test() {}
+        ));
+        assert!(err.contains("async tests are not supported"), "{err}");
+
+        let err = expand_err(quote!(
+            fn some_test(storage: &SharedStorage, other: u32) {}
+        ));
+        assert!(err.contains("at most one argument"), "{err}");
+    }
+}
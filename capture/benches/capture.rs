@@ -0,0 +1,70 @@
+//! Benchmarks for capturing spans and events.
+
+use criterion::{criterion_group, criterion_main, BatchSize, Bencher, Criterion, Throughput};
+use tracing_core::Dispatch;
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+use tracing_capture::{CaptureLayer, SharedStorage};
+
+/// Number of operations performed in each benchmark iteration.
+const OPERATIONS: u64 = 100;
+
+fn emit_events() {
+    for i in 0..OPERATIONS {
+        tracing::info!(i, flag = true, ratio = 0.5, "event");
+    }
+}
+
+fn emit_spans() {
+    for i in 0..OPERATIONS {
+        tracing::info_span!("span", i).in_scope(|| {
+            tracing::debug!("entered");
+        });
+    }
+}
+
+fn emit_nested_events() {
+    let _entered = tracing::info_span!("outer").entered();
+    emit_events();
+}
+
+/// Creates a dispatch with (`capture == true`) or without a `CaptureLayer`.
+fn create_dispatch(capture: bool) -> (Dispatch, SharedStorage) {
+    let storage = SharedStorage::default();
+    let dispatch = if capture {
+        Dispatch::new(Registry::default().with(CaptureLayer::new(&storage)))
+    } else {
+        Dispatch::new(Registry::default())
+    };
+    (dispatch, storage)
+}
+
+fn bench_with(bencher: &mut Bencher<'_>, capture: bool, action: fn()) {
+    bencher.iter_batched(
+        || create_dispatch(capture),
+        |(dispatch, storage)| {
+            tracing::dispatcher::with_default(&dispatch, action);
+            (dispatch, storage) // dropped outside the measured code
+        },
+        BatchSize::SmallInput,
+    );
+}
+
+fn capture_benches(criterion: &mut Criterion) {
+    let benches: [(&str, fn()); 3] = [
+        ("events", emit_events),
+        ("nested_events", emit_nested_events),
+        ("spans", emit_spans),
+    ];
+
+    for (name, action) in benches {
+        let mut group = criterion.benchmark_group(name);
+        group.throughput(Throughput::Elements(OPERATIONS));
+        group.bench_function("baseline", |bencher| bench_with(bencher, false, action));
+        group.bench_function("capture", |bencher| bench_with(bencher, true, action));
+        group.finish();
+    }
+}
+
+criterion_group!(benches, capture_benches);
+criterion_main!(benches);
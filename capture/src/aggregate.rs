@@ -0,0 +1,222 @@
+//! Aggregate metrics (counts and latency percentiles) derived from captured spans.
+
+use std::{collections::BTreeMap, time::Duration};
+
+use crate::{CapturedSpan, Storage};
+
+/// Criterion to group spans by in [`Storage::aggregate()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum GroupBy {
+    /// Group spans by their [logical name](CapturedSpan::name()).
+    Name,
+    /// Group spans by their target.
+    Target,
+}
+
+impl GroupBy {
+    fn key<'a>(self, span: &CapturedSpan<'a>) -> &'a str {
+        match self {
+            Self::Name => span.name(),
+            Self::Target => span.metadata().target(),
+        }
+    }
+}
+
+/// Aggregated metrics for a group of spans. Returned by [`Storage::aggregate()`].
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+pub struct SpanAggregate {
+    /// Number of spans in the group, including spans that were not closed.
+    pub count: usize,
+    /// Number of closed spans in the group. Only closed spans contribute
+    /// to [`Self::durations`] and [`Self::busy_times`].
+    pub closed_count: usize,
+    /// Durations of closed spans, i.e., the time from span creation to its closure.
+    pub durations: DurationStats,
+    /// Total times during which closed spans were entered
+    /// (see [`SpanStats::busy_time`](crate::SpanStats::busy_time)).
+    pub busy_times: DurationStats,
+}
+
+/// Statistics for a collection of [`Duration`]s, such as [span durations](SpanAggregate::durations).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DurationStats {
+    /// Sorted durations.
+    sorted: Vec<Duration>,
+}
+
+impl FromIterator<Duration> for DurationStats {
+    fn from_iter<I: IntoIterator<Item = Duration>>(iter: I) -> Self {
+        let mut sorted: Vec<_> = iter.into_iter().collect();
+        sorted.sort_unstable();
+        Self { sorted }
+    }
+}
+
+impl DurationStats {
+    /// Returns the number of durations.
+    pub fn len(&self) -> usize {
+        self.sorted.len()
+    }
+
+    /// Checks whether there are no durations.
+    pub fn is_empty(&self) -> bool {
+        self.sorted.is_empty()
+    }
+
+    /// Returns the minimum duration, or `None` if there are no durations.
+    pub fn min(&self) -> Option<Duration> {
+        self.sorted.first().copied()
+    }
+
+    /// Returns the maximum duration, or `None` if there are no durations.
+    pub fn max(&self) -> Option<Duration> {
+        self.sorted.last().copied()
+    }
+
+    /// Returns the sum of all durations.
+    pub fn total(&self) -> Duration {
+        self.sorted.iter().sum()
+    }
+
+    /// Returns the mean duration, or `None` if there are no durations.
+    pub fn mean(&self) -> Option<Duration> {
+        let len = u32::try_from(self.sorted.len())
+            .ok()
+            .filter(|&len| len > 0)?;
+        Some(self.total() / len)
+    }
+
+    /// Returns the specified percentile of durations using the nearest-rank method,
+    /// or `None` if there are no durations. The 0th percentile is the minimum duration,
+    /// and the 100th percentile is the maximum one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `percentile` is not in the `0.0..=100.0` range.
+    pub fn percentile(&self, percentile: f64) -> Option<Duration> {
+        assert!(
+            (0.0..=100.0).contains(&percentile),
+            "percentile must be in 0.0..=100.0 range, got {percentile}"
+        );
+        if self.sorted.is_empty() {
+            return None;
+        }
+        #[allow(clippy::cast_precision_loss)] // lengths are not large enough to lose precision
+        let rank = (percentile / 100.0 * self.sorted.len() as f64).ceil();
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        // `rank` is non-negative and does not exceed the length
+        let idx = (rank as usize).saturating_sub(1);
+        Some(self.sorted[idx])
+    }
+
+    /// Returns the median (50th percentile) duration.
+    pub fn p50(&self) -> Option<Duration> {
+        self.percentile(50.0)
+    }
+
+    /// Returns the 95th percentile duration.
+    pub fn p95(&self) -> Option<Duration> {
+        self.percentile(95.0)
+    }
+
+    /// Returns the 99th percentile duration.
+    pub fn p99(&self) -> Option<Duration> {
+        self.percentile(99.0)
+    }
+
+    /// Returns sorted durations.
+    pub fn as_slice(&self) -> &[Duration] {
+        &self.sorted
+    }
+}
+
+impl Storage {
+    /// Aggregates captured spans grouped by the specified criterion, producing span counts
+    /// and duration statistics (percentiles, min / max etc.) for each group. This can be used
+    /// in performance regression tests to check latencies directly from the captured data.
+    ///
+    /// [Placeholder spans](CapturedSpan::is_placeholder()) are not aggregated, since they
+    /// are not captured by the layer.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+    /// # use tracing_capture::{CaptureLayer, GroupBy, SharedStorage};
+    /// # use std::time::Duration;
+    /// let storage = SharedStorage::default();
+    /// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    /// let _open_span = tracing::subscriber::with_default(subscriber, || {
+    ///     for i in 0..10 {
+    ///         tracing::info_span!("db.query", i).in_scope(|| { /* query */ });
+    ///     }
+    ///     tracing::info_span!("db.query")
+    /// });
+    ///
+    /// let aggregates = storage.lock().aggregate(GroupBy::Name);
+    /// let queries = &aggregates["db.query"];
+    /// assert_eq!(queries.count, 11);
+    /// assert_eq!(queries.closed_count, 10);
+    /// assert!(queries.durations.p95().unwrap() < Duration::from_secs(1));
+    /// ```
+    pub fn aggregate(&self, group_by: GroupBy) -> BTreeMap<String, SpanAggregate> {
+        let mut groups = BTreeMap::<_, (usize, Vec<_>, Vec<_>)>::new();
+        for span in self.all_spans() {
+            if span.is_placeholder() {
+                continue;
+            }
+            let (count, durations, busy_times) = groups.entry(group_by.key(&span)).or_default();
+            *count += 1;
+            if let Some(closed_at) = span.closed_at() {
+                durations.push(closed_at.saturating_duration_since(span.created_at()));
+                busy_times.push(span.stats().busy_time);
+            }
+        }
+
+        groups
+            .into_iter()
+            .map(|(key, (count, durations, busy_times))| {
+                let aggregate = SpanAggregate {
+                    count,
+                    closed_count: durations.len(),
+                    durations: durations.into_iter().collect(),
+                    busy_times: busy_times.into_iter().collect(),
+                };
+                (key.to_owned(), aggregate)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn duration_percentiles() {
+        let stats: DurationStats = (1..=20).rev().map(Duration::from_millis).collect();
+        assert_eq!(stats.len(), 20);
+        assert_eq!(stats.min(), Some(Duration::from_millis(1)));
+        assert_eq!(stats.max(), Some(Duration::from_millis(20)));
+        assert_eq!(stats.total(), Duration::from_millis(210));
+        assert_eq!(stats.mean(), Some(Duration::from_micros(10_500)));
+        assert_eq!(stats.percentile(0.0), Some(Duration::from_millis(1)));
+        assert_eq!(stats.p50(), Some(Duration::from_millis(10)));
+        assert_eq!(stats.p95(), Some(Duration::from_millis(19)));
+        assert_eq!(stats.p99(), Some(Duration::from_millis(20)));
+        assert_eq!(stats.percentile(100.0), Some(Duration::from_millis(20)));
+
+        let stats = DurationStats::default();
+        assert!(stats.is_empty());
+        assert_eq!(stats.mean(), None);
+        assert_eq!(stats.p50(), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "percentile must be in 0.0..=100.0 range")]
+    fn invalid_percentile() {
+        DurationStats::default().percentile(101.0);
+    }
+}
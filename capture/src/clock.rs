@@ -0,0 +1,120 @@
+//! Clock sources for captured timestamps.
+
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+/// Source of timestamps for captured spans and events. Set via [`CaptureLayer::with_clock()`].
+///
+/// By default, the layer uses [`Instant::now()`]. A custom clock allows getting deterministic
+/// [span timings](crate::SpanStats) in tests, e.g. with [`MockClock`] or a clock paused
+/// via `tokio::time::pause()`.
+///
+/// The trait is implemented for `Fn() -> Instant` closures.
+///
+/// [`CaptureLayer::with_clock()`]: crate::CaptureLayer::with_clock()
+///
+/// # Examples
+///
+/// ```
+/// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+/// # use tracing_capture::{CaptureLayer, SharedStorage};
+/// # use std::time::Instant;
+/// let storage = SharedStorage::default();
+/// // With a paused `tokio` clock, this could be `|| tokio::time::Instant::now().into_std()`.
+/// let layer = CaptureLayer::new(&storage).with_clock(Instant::now);
+/// let subscriber = Registry::default().with(layer);
+/// ```
+pub trait Clock: Send + Sync {
+    /// Returns the current instant.
+    fn now(&self) -> Instant;
+}
+
+impl<F> Clock for F
+where
+    F: Fn() -> Instant + Send + Sync,
+{
+    fn now(&self) -> Instant {
+        self()
+    }
+}
+
+/// Shared reference to a [`Clock`].
+#[derive(Clone)]
+pub(crate) struct SharedClock(pub(crate) Arc<dyn Clock>);
+
+impl fmt::Debug for SharedClock {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("Clock")
+    }
+}
+
+/// Manually advanced [`Clock`]. Clones of a clock share the current time.
+///
+/// The clock starts at the instant of its creation and only moves forward
+/// on [`advance()`](Self::advance()) calls.
+///
+/// # Examples
+///
+/// ```
+/// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+/// # use tracing_capture::{CaptureLayer, MockClock, SharedStorage};
+/// # use std::time::Duration;
+/// let storage = SharedStorage::default();
+/// let clock = MockClock::default();
+/// let layer = CaptureLayer::new(&storage).with_clock(clock.clone());
+/// let subscriber = Registry::default().with(layer);
+/// tracing::subscriber::with_default(subscriber, || {
+///     let span = tracing::info_span!("compute");
+///     span.in_scope(|| clock.advance(Duration::from_millis(10)));
+///     clock.advance(Duration::from_millis(20));
+/// });
+///
+/// let storage = storage.lock();
+/// let stats = storage.all_spans().next().unwrap().stats();
+/// assert_eq!(stats.busy_time, Duration::from_millis(10));
+/// assert_eq!(stats.idle_time, Duration::from_millis(20));
+/// ```
+#[derive(Debug, Clone)]
+pub struct MockClock {
+    start: Instant,
+    elapsed_nanos: Arc<AtomicU64>,
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        Self {
+            start: Instant::now(),
+            elapsed_nanos: Arc::default(),
+        }
+    }
+}
+
+impl MockClock {
+    /// Returns the duration elapsed since the clock creation.
+    pub fn elapsed(&self) -> Duration {
+        Duration::from_nanos(self.elapsed_nanos.load(Ordering::Acquire))
+    }
+
+    /// Advances the clock by the specified duration.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the total elapsed duration overflows `u64` nanoseconds.
+    pub fn advance(&self, duration: Duration) {
+        let nanos = u64::try_from(duration.as_nanos()).expect("duration is too large");
+        let prev = self.elapsed_nanos.fetch_add(nanos, Ordering::AcqRel);
+        assert!(prev.checked_add(nanos).is_some(), "clock overflow");
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        self.start + self.elapsed()
+    }
+}
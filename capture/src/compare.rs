@@ -0,0 +1,155 @@
+//! Structural comparison of captured spans.
+
+use std::collections::HashSet;
+
+use crate::{predicates::FieldsSubsetPredicate, CapturedSpan, TraceTree, TraceTreeEvent};
+use tracing_tunnel::{CallSiteData, CallSiteKind, TracedValues};
+
+/// Options for [`CapturedSpan::structurally_eq()`] and the [`similar_to()`] predicate.
+///
+/// By default, all aspects of span structure are compared: metadata (name, target, level),
+/// field values, events attached to the span and child spans (recursively). Source code
+/// locations and [span stats](crate::SpanStats) are never compared.
+///
+/// [`similar_to()`]: crate::predicates::similar_to()
+///
+/// # Examples
+///
+/// ```
+/// # use tracing_capture::StructuralEqOptions;
+/// let options = StructuralEqOptions::default()
+///     .ignore_field("attempt")
+///     .ignore_children();
+/// ```
+#[derive(Debug, Clone)]
+pub struct StructuralEqOptions {
+    ignored_fields: HashSet<String>,
+    values: bool,
+    events: bool,
+    children: bool,
+}
+
+impl Default for StructuralEqOptions {
+    fn default() -> Self {
+        Self {
+            ignored_fields: HashSet::new(),
+            values: true,
+            events: true,
+            children: true,
+        }
+    }
+}
+
+impl StructuralEqOptions {
+    /// Ignores the field with the specified name in all compared spans and events.
+    #[must_use]
+    pub fn ignore_field(mut self, name: impl Into<String>) -> Self {
+        self.ignored_fields.insert(name.into());
+        self
+    }
+
+    /// Ignores all field values in compared spans and events. Field names are still compared.
+    #[must_use]
+    pub fn ignore_values(mut self) -> Self {
+        self.values = false;
+        self
+    }
+
+    /// Ignores events attached to compared spans.
+    #[must_use]
+    pub fn ignore_events(mut self) -> Self {
+        self.events = false;
+        self
+    }
+
+    /// Ignores child spans of compared spans. Accordingly, only the top-level spans
+    /// are compared.
+    #[must_use]
+    pub fn ignore_children(mut self) -> Self {
+        self.children = false;
+        self
+    }
+
+    pub(crate) fn trees_eq(&self, lhs: &TraceTree, rhs: &TraceTree) -> bool {
+        if !metadata_eq(&lhs.metadata, &rhs.metadata) || !self.values_eq(&lhs.values, &rhs.values) {
+            return false;
+        }
+        if self.events && !self.events_eq(&lhs.events, &rhs.events) {
+            return false;
+        }
+        if self.children {
+            lhs.children.len() == rhs.children.len()
+                && lhs
+                    .children
+                    .iter()
+                    .zip(&rhs.children)
+                    .all(|(lhs, rhs)| self.trees_eq(lhs, rhs))
+        } else {
+            true
+        }
+    }
+
+    fn events_eq(&self, lhs: &[TraceTreeEvent], rhs: &[TraceTreeEvent]) -> bool {
+        lhs.len() == rhs.len()
+            && lhs.iter().zip(rhs).all(|(lhs, rhs)| {
+                metadata_eq(&lhs.metadata, &rhs.metadata)
+                    && self.values_eq(&lhs.values, &rhs.values)
+            })
+    }
+
+    fn values_eq(&self, lhs: &TracedValues<String>, rhs: &TracedValues<String>) -> bool {
+        let is_compared = |(name, _): &(&str, _)| !self.ignored_fields.contains(*name);
+        let lhs_count = lhs.iter().filter(is_compared).count();
+        let rhs_count = rhs.iter().filter(is_compared).count();
+        lhs_count == rhs_count
+            && lhs.iter().filter(is_compared).all(|(name, lhs_value)| {
+                rhs.get(name).is_some_and(|rhs_value| {
+                    !self.values || FieldsSubsetPredicate::values_eq(lhs_value, rhs_value)
+                })
+            })
+    }
+}
+
+fn metadata_eq(lhs: &CallSiteData, rhs: &CallSiteData) -> bool {
+    let kinds_eq = matches!(
+        (lhs.kind, rhs.kind),
+        (CallSiteKind::Span, CallSiteKind::Span) | (CallSiteKind::Event, CallSiteKind::Event)
+    );
+    kinds_eq && lhs.name == rhs.name && lhs.target == rhs.target && lhs.level == rhs.level
+}
+
+impl CapturedSpan<'_> {
+    /// Checks whether this span has the same structure as `other`: the same metadata
+    /// and field values, the same events (compared in the order of capture) and recursively
+    /// the same child spans. The compared aspects can be configured via `options`.
+    ///
+    /// Unlike the equality operator, which checks span identity, this method can be used
+    /// to compare different spans, e.g. to assert that retries of an operation produce
+    /// identical span shapes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+    /// # use tracing_capture::{CaptureLayer, SharedStorage, StructuralEqOptions};
+    /// let storage = SharedStorage::default();
+    /// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     for attempt in 0..2 {
+    ///         tracing::info_span!("request", attempt).in_scope(|| {
+    ///             tracing::info!(status = 503, "responded");
+    ///         });
+    ///     }
+    /// });
+    ///
+    /// let storage = storage.lock();
+    /// let attempts: Vec<_> = storage.root_spans().collect();
+    /// let options = StructuralEqOptions::default();
+    /// assert!(!attempts[0].structurally_eq(&attempts[1], &options));
+    /// let options = options.ignore_field("attempt");
+    /// assert!(attempts[0].structurally_eq(&attempts[1], &options));
+    /// ```
+    pub fn structurally_eq(&self, other: &CapturedSpan<'_>, options: &StructuralEqOptions) -> bool {
+        options.trees_eq(&self.to_owned_tree(), &other.to_owned_tree())
+    }
+}
@@ -0,0 +1,453 @@
+//! Export of captured tracing data in the Chrome tracing and OTLP formats.
+
+use tracing_core::Level;
+
+use std::{
+    fmt::{self, Write as _},
+    time::{Instant, SystemTime},
+};
+
+use crate::{CapturedEvent, CapturedSpan, SpanEntry, Storage};
+use tracing_tunnel::TracedValue;
+
+/// Process ID used for all exported trace events.
+const PID: u32 = 1;
+/// Name of the span [extension value](CapturedSpan::extension_value()) holding the trace ID
+/// for OTLP log records.
+const TRACE_ID_EXTENSION: &str = "trace_id";
+/// Name of the span [extension value](CapturedSpan::extension_value()) holding the span ID
+/// for OTLP log records.
+const SPAN_ID_EXTENSION: &str = "span_id";
+
+impl Storage {
+    /// Exports captured spans and events as a JSON document in the [Chrome tracing format].
+    /// The document can be viewed in the [Perfetto UI] or in `chrome://tracing`.
+    ///
+    /// Each [span entry](crate::SpanEntry) is exported as a complete (`X`) trace event,
+    /// or as a begin (`B`) event if the span was not exited. Captured events are exported
+    /// as instant (`i`) trace events. Timestamps are measured in microseconds since
+    /// the [creation of this storage](Self::created_at()); thread IDs correspond to
+    /// indices in [`Self::threads()`]. Recorded values are exported as trace event `args`.
+    ///
+    /// [Chrome tracing format]: https://docs.google.com/document/d/1CvAClvFfyA5R-PhYUmn5OOQtYMH4h6I0nSsKchNAySU
+    /// [Perfetto UI]: https://ui.perfetto.dev/
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_subscriber::layer::SubscriberExt;
+    /// # use tracing_capture::{CaptureLayer, SharedStorage};
+    /// let storage = SharedStorage::default();
+    /// let subscriber = tracing_subscriber::registry()
+    ///     .with(CaptureLayer::new(&storage));
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     let _entered = tracing::info_span!("compute", arg = 42).entered();
+    ///     tracing::info!(result = 5, "computed");
+    /// });
+    ///
+    /// let trace = storage.lock().to_chrome_trace();
+    /// assert!(trace.starts_with(r#"{"traceEvents":["#));
+    /// assert!(trace.contains(r#""ph":"X","name":"compute""#));
+    /// assert!(trace.contains(r#""args":{"arg":42}"#));
+    /// assert!(trace.contains(r#""ph":"i","s":"t","name":"computed""#));
+    /// ```
+    pub fn to_chrome_trace(&self) -> String {
+        let mut json = String::new();
+        // Writing to a `String` cannot fail.
+        self.write_chrome_trace(&mut json).ok();
+        json
+    }
+
+    /// Exports captured events as an [OTLP] logs export request encoded in JSON
+    /// (i.e., in the format accepted by the `/v1/logs` endpoint of an OTLP/HTTP collector).
+    ///
+    /// Each captured event is converted to a log record:
+    ///
+    /// - The event level is converted to the log severity.
+    /// - The event message (if any) is used as the log body.
+    /// - Other event values are converted to log attributes.
+    /// - If the nearest ancestor span with the `trace_id` [extension value] exists,
+    ///   the record is correlated with the trace via `traceId` and `spanId` (the latter taken
+    ///   from the `span_id` extension value of the same span, if present). Integer IDs
+    ///   are formatted as hex, and hex string IDs are lowercased. IDs that do not fit
+    ///   into 16 bytes (trace IDs) / 8 bytes (span IDs), hex strings of another length,
+    ///   and all-zero IDs are invalid in OTLP and are not exported. Extension values
+    ///   are populated by the [extension extractor](crate::CaptureLayer::with_extension_extractor()).
+    ///
+    /// [OTLP]: https://opentelemetry.io/docs/specs/otlp/
+    /// [extension value]: CapturedSpan::extension_value()
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_subscriber::layer::SubscriberExt;
+    /// # use tracing_capture::{CaptureLayer, SharedStorage};
+    /// let storage = SharedStorage::default();
+    /// let subscriber = tracing_subscriber::registry()
+    ///     .with(CaptureLayer::new(&storage));
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     tracing::warn!(result = 5, "computed");
+    /// });
+    ///
+    /// let logs = storage.lock().to_otlp_logs();
+    /// assert!(logs.starts_with(r#"{"resourceLogs":["#));
+    /// assert!(logs.contains(r#""severityNumber":13,"severityText":"WARN""#));
+    /// assert!(logs.contains(r#""body":{"stringValue":"computed"}"#));
+    /// assert!(logs.contains(r#"{"key":"result","value":{"intValue":"5"}}"#));
+    /// ```
+    pub fn to_otlp_logs(&self) -> String {
+        let mut json = String::new();
+        // Writing to a `String` cannot fail.
+        self.write_otlp_logs(&mut json).ok();
+        json
+    }
+
+    fn write_chrome_trace(&self, json: &mut String) -> fmt::Result {
+        let mut trace_events = vec![];
+        for span in self.all_spans() {
+            for entry in span.entries() {
+                let mut trace_event = String::new();
+                self.write_span_entry(&mut trace_event, &span, entry)?;
+                trace_events.push((entry.entered_at, trace_event));
+            }
+        }
+        for event in self.all_events() {
+            let mut trace_event = String::new();
+            self.write_event(&mut trace_event, &event)?;
+            trace_events.push((event.timestamp(), trace_event));
+        }
+        // The sort is stable, so spans entered at the same instant retain their hierarchy order.
+        trace_events.sort_by_key(|(timestamp, _)| *timestamp);
+
+        json.push_str(r#"{"traceEvents":["#);
+        for (i, (_, trace_event)) in trace_events.iter().enumerate() {
+            if i > 0 {
+                json.push(',');
+            }
+            json.push_str(trace_event);
+        }
+        json.push_str(r#"],"displayTimeUnit":"ms"}"#);
+        Ok(())
+    }
+
+    fn micros_since_creation(&self, instant: Instant) -> f64 {
+        instant
+            .saturating_duration_since(self.created_at())
+            .as_secs_f64()
+            * 1e6
+    }
+
+    fn write_span_entry(
+        &self,
+        json: &mut String,
+        span: &CapturedSpan<'_>,
+        entry: &SpanEntry,
+    ) -> fmt::Result {
+        let phase = if entry.exited_at.is_some() { "X" } else { "B" };
+        write!(json, r#"{{"ph":"{phase}","name":"#)?;
+        write_json_str(json, span.name())?;
+        json.push_str(r#","cat":"#);
+        write_json_str(json, span.metadata().target())?;
+        let ts = self.micros_since_creation(entry.entered_at);
+        write!(json, r#","pid":{PID},"tid":{},"ts":{ts:.3}"#, entry.thread)?;
+        if let Some(exited_at) = entry.exited_at {
+            let dur = exited_at
+                .saturating_duration_since(entry.entered_at)
+                .as_secs_f64()
+                * 1e6;
+            write!(json, r#","dur":{dur:.3}"#)?;
+        }
+        write_args(json, span.values())?;
+        json.push('}');
+        Ok(())
+    }
+
+    fn write_event(&self, json: &mut String, event: &CapturedEvent<'_>) -> fmt::Result {
+        json.push_str(r#"{"ph":"i","s":"t","name":"#);
+        write_json_str(json, event.message().unwrap_or(event.metadata().name()))?;
+        json.push_str(r#","cat":"#);
+        write_json_str(json, event.metadata().target())?;
+        let ts = self.micros_since_creation(event.timestamp());
+        write!(
+            json,
+            r#","pid":{PID},"tid":{},"ts":{ts:.3}"#,
+            event.thread()
+        )?;
+        let values = event.values().filter(|(name, _)| *name != "message");
+        write_args(json, values)?;
+        json.push('}');
+        Ok(())
+    }
+}
+
+impl Storage {
+    fn write_otlp_logs(&self, json: &mut String) -> fmt::Result {
+        json.push_str(r#"{"resourceLogs":[{"resource":{"attributes":[]},"scopeLogs":[{"#);
+        write!(
+            json,
+            r#""scope":{{"name":"{}","version":"{}"}},"logRecords":["#,
+            env!("CARGO_PKG_NAME"),
+            env!("CARGO_PKG_VERSION")
+        )?;
+        for (i, event) in self.all_events().enumerate() {
+            if i > 0 {
+                json.push(',');
+            }
+            self.write_log_record(json, &event)?;
+        }
+        json.push_str("]}]}]}");
+        Ok(())
+    }
+
+    fn write_log_record(&self, json: &mut String, event: &CapturedEvent<'_>) -> fmt::Result {
+        let timestamp = unix_nanos(self.system_time(event.timestamp()));
+        let level = *event.metadata().level();
+        write!(
+            json,
+            r#"{{"timeUnixNano":"{timestamp}","observedTimeUnixNano":"{timestamp}","#
+        )?;
+        write!(
+            json,
+            r#""severityNumber":{},"severityText":"{level}""#,
+            severity_number(level)
+        )?;
+        if let Some(message) = event.message() {
+            json.push_str(r#","body":{"stringValue":"#);
+            write_json_str(json, message)?;
+            json.push('}');
+        }
+
+        json.push_str(r#","attributes":["#);
+        let values = event.values().filter(|(name, _)| *name != "message");
+        for (i, (name, value)) in values.enumerate() {
+            if i > 0 {
+                json.push(',');
+            }
+            json.push_str(r#"{"key":"#);
+            write_json_str(json, name)?;
+            json.push_str(r#","value":"#);
+            write_otlp_value(json, value)?;
+            json.push('}');
+        }
+        json.push(']');
+
+        let trace_span = event
+            .ancestors()
+            .find(|span| span.extension_value(TRACE_ID_EXTENSION).is_some());
+        if let Some(span) = trace_span {
+            let trace_id = span.extension_value(TRACE_ID_EXTENSION);
+            if let Some(trace_id) = trace_id.and_then(|id| otlp_id(id, 16)) {
+                write!(json, r#","traceId":"{trace_id}""#)?;
+            }
+            let span_id = span.extension_value(SPAN_ID_EXTENSION);
+            if let Some(span_id) = span_id.and_then(|id| otlp_id(id, 8)) {
+                write!(json, r#","spanId":"{span_id}""#)?;
+            }
+        }
+        json.push('}');
+        Ok(())
+    }
+}
+
+/// Converts a level to the OTLP severity number (the lowest number in the corresponding range).
+fn severity_number(level: Level) -> u8 {
+    match level {
+        Level::TRACE => 1,
+        Level::DEBUG => 5,
+        Level::INFO => 9,
+        Level::WARN => 13,
+        Level::ERROR => 17,
+    }
+}
+
+fn unix_nanos(time: SystemTime) -> u128 {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_nanos())
+}
+
+/// Formats a trace / span ID with the specified byte length as hex. Returns `None` if the value
+/// cannot be represented as an ID of this length, or if it is all zeros (which is invalid in OTLP).
+fn otlp_id(value: &TracedValue, byte_len: usize) -> Option<String> {
+    let hex_len = byte_len * 2;
+    let format_uint = |id: u128| {
+        let bit_len = u128::BITS - id.leading_zeros();
+        (bit_len as usize <= 8 * byte_len).then(|| format!("{id:0hex_len$x}"))
+    };
+    let hex = match value {
+        TracedValue::UInt(id) => format_uint(*id),
+        TracedValue::Int(id) => u128::try_from(*id).ok().and_then(format_uint),
+        TracedValue::Bytes(bytes) if bytes.len() == byte_len => {
+            let mut hex = String::with_capacity(hex_len);
+            for byte in bytes {
+                write!(hex, "{byte:02x}").ok()?;
+            }
+            Some(hex)
+        }
+        TracedValue::String(id) => {
+            let is_valid = id.len() == hex_len && id.bytes().all(|byte| byte.is_ascii_hexdigit());
+            is_valid.then(|| id.to_ascii_lowercase())
+        }
+        _ => None,
+    };
+    hex.filter(|hex| hex.bytes().any(|byte| byte != b'0'))
+}
+
+/// Writes a value as an OTLP `AnyValue` in the JSON encoding.
+fn write_otlp_value(json: &mut String, value: &TracedValue) -> fmt::Result {
+    match value {
+        TracedValue::Bool(value) => write!(json, r#"{{"boolValue":{value}}}"#),
+        TracedValue::Int(value) if i64::try_from(*value).is_ok() => {
+            write!(json, r#"{{"intValue":"{value}"}}"#)
+        }
+        TracedValue::UInt(value) if i64::try_from(*value).is_ok() => {
+            write!(json, r#"{{"intValue":"{value}"}}"#)
+        }
+        TracedValue::Float(value) if value.is_finite() => {
+            write!(json, r#"{{"doubleValue":{value}}}"#)
+        }
+        TracedValue::Float(value) => {
+            let value = if value.is_nan() {
+                "NaN"
+            } else if value.is_sign_positive() {
+                "Infinity"
+            } else {
+                "-Infinity"
+            };
+            write!(json, r#"{{"doubleValue":"{value}"}}"#)
+        }
+        _ => {
+            json.push_str(r#"{"stringValue":"#);
+            match value {
+                TracedValue::String(value) => write_json_str(json, value)?,
+                TracedValue::Object(obj) => write_json_str(json, obj.as_ref())?,
+                TracedValue::Error(err) => write_json_str(json, &err.message)?,
+                TracedValue::Int(value) => write_json_str(json, &value.to_string())?,
+                TracedValue::UInt(value) => write_json_str(json, &value.to_string())?,
+                _ => write_json_str(json, &format!("{value:?}"))?,
+            }
+            json.push('}');
+            Ok(())
+        }
+    }
+}
+
+fn write_args<'a>(
+    json: &mut String,
+    values: impl IntoIterator<Item = (&'a str, &'a TracedValue)>,
+) -> fmt::Result {
+    json.push_str(r#","args":{"#);
+    for (i, (name, value)) in values.into_iter().enumerate() {
+        if i > 0 {
+            json.push(',');
+        }
+        write_json_str(json, name)?;
+        json.push(':');
+        write_json_value(json, value)?;
+    }
+    json.push('}');
+    Ok(())
+}
+
+fn write_json_value(json: &mut String, value: &TracedValue) -> fmt::Result {
+    match value {
+        TracedValue::Bool(value) => write!(json, "{value}"),
+        TracedValue::Int(value) => write!(json, "{value}"),
+        TracedValue::UInt(value) => write!(json, "{value}"),
+        TracedValue::Float(value) if value.is_finite() => write!(json, "{value}"),
+        TracedValue::Float(value) => write_json_str(json, &value.to_string()),
+        TracedValue::String(value) => write_json_str(json, value),
+        TracedValue::Object(obj) => write_json_str(json, obj.as_ref()),
+        TracedValue::Error(err) => write_json_str(json, &err.message),
+        _ => write_json_str(json, &format!("{value:?}")),
+    }
+}
+
+fn write_json_str(json: &mut String, s: &str) -> fmt::Result {
+    json.push('"');
+    for ch in s.chars() {
+        match ch {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            ch if ch.is_control() => write!(json, "\\u{:04x}", u32::from(ch))?,
+            ch => json.push(ch),
+        }
+    }
+    json.push('"');
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escaping_json_strings() {
+        let mut json = String::new();
+        write_json_str(&mut json, "test \"quoted\"\\\n\u{1}").unwrap();
+        assert_eq!(json, r#""test \"quoted\"\\\n\u0001""#);
+    }
+
+    #[test]
+    fn writing_json_values() {
+        let mut json = String::new();
+        write_json_value(&mut json, &TracedValue::Float(f64::NAN)).unwrap();
+        json.push(',');
+        write_json_value(&mut json, &TracedValue::Float(1.5)).unwrap();
+        json.push(',');
+        write_json_value(&mut json, &TracedValue::Int(-3)).unwrap();
+        assert_eq!(json, r#""NaN",1.5,-3"#);
+    }
+
+    #[test]
+    fn writing_otlp_values() {
+        let mut json = String::new();
+        write_otlp_value(&mut json, &TracedValue::UInt(u64::MAX.into())).unwrap();
+        json.push(',');
+        write_otlp_value(&mut json, &TracedValue::Float(f64::NEG_INFINITY)).unwrap();
+        json.push(',');
+        write_otlp_value(&mut json, &TracedValue::Bool(true)).unwrap();
+        assert_eq!(
+            json,
+            r#"{"stringValue":"18446744073709551615"},{"doubleValue":"-Infinity"},{"boolValue":true}"#
+        );
+    }
+
+    #[test]
+    fn formatting_otlp_ids() {
+        let id = otlp_id(&TracedValue::UInt(0x00c0_ffee), 8).unwrap();
+        assert_eq!(id, "0000000000c0ffee");
+        let id = otlp_id(&TracedValue::UInt(1), 16).unwrap();
+        assert_eq!(id, "00000000000000000000000000000001");
+        let id = otlp_id(&TracedValue::from("ABCDEF0123456789"), 8).unwrap();
+        assert_eq!(id, "abcdef0123456789");
+        assert!(otlp_id(&TracedValue::from("not hex"), 8).is_none());
+        let id = otlp_id(&TracedValue::Bytes(vec![0xab; 8]), 8).unwrap();
+        assert_eq!(id, "abababababababab");
+        assert!(otlp_id(&TracedValue::Int(-1), 8).is_none());
+        let id = otlp_id(&TracedValue::UInt(u128::MAX), 16).unwrap();
+        assert_eq!(id, "f".repeat(32));
+        let id = otlp_id(&TracedValue::Int(i128::from(u64::MAX)), 8).unwrap();
+        assert_eq!(id, "f".repeat(16));
+    }
+
+    #[test]
+    fn rejecting_invalid_otlp_ids() {
+        // Strings must have the exact length.
+        assert!(otlp_id(&TracedValue::from(""), 8).is_none());
+        assert!(otlp_id(&TracedValue::from("abcdef"), 8).is_none());
+        assert!(otlp_id(&TracedValue::from("0123456789abcdef0"), 8).is_none());
+        // Integers must fit into the ID length.
+        assert!(otlp_id(&TracedValue::UInt(1 << 64), 8).is_none());
+        assert!(otlp_id(&TracedValue::Int(1 << 64), 8).is_none());
+        // Bytes must have the exact length.
+        assert!(otlp_id(&TracedValue::Bytes(vec![0xab; 9]), 8).is_none());
+        // All-zero IDs are invalid.
+        assert!(otlp_id(&TracedValue::UInt(0), 8).is_none());
+        assert!(otlp_id(&TracedValue::Int(0), 16).is_none());
+        assert!(otlp_id(&TracedValue::from("0000000000000000"), 8).is_none());
+        assert!(otlp_id(&TracedValue::Bytes(vec![0; 16]), 16).is_none());
+    }
+}
@@ -0,0 +1,270 @@
+//! Fallible access to span / event fields: `FieldRef` and `FieldError`.
+
+use std::{any, error, fmt};
+
+use tracing_tunnel::{FromTracedValue, TracedValue};
+
+/// Reference to a (possibly missing) field of a [`CapturedSpan`](crate::CapturedSpan)
+/// or a [`CapturedEvent`](crate::CapturedEvent) returned by their `field()` methods.
+///
+/// Unlike indexing, which panics if the field is missing, a `FieldRef` allows choosing between
+/// fallible access (e.g., [`Self::get()`] and [`Self::get_as()`] returning a [`FieldError`])
+/// and panicking access with messages including the field name, its owner and the actual value
+/// (e.g., [`Self::expect_str()`] and [`Self::expect_u64()`]).
+///
+/// # Examples
+///
+/// ```
+/// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+/// # use tracing_capture::{CaptureLayer, SharedStorage};
+/// let storage = SharedStorage::default();
+/// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+/// tracing::subscriber::with_default(subscriber, || {
+///     tracing::info_span!("request", method = "GET", attempt = 2_u64).in_scope(|| {});
+/// });
+///
+/// let storage = storage.lock();
+/// let span = storage.all_spans().next().unwrap();
+/// assert_eq!(span.field("method").expect_str(), "GET");
+/// assert_eq!(span.field("attempt").expect_u64(), 2);
+///
+/// let err = span.field("status").get().unwrap_err();
+/// assert!(err.is_missing());
+/// assert_eq!(err.to_string(), "field `status` is not contained in span `request`");
+/// let err = span.field("method").get_as::<u64>().unwrap_err();
+/// assert_eq!(
+///     err.to_string(),
+///     "field `method` of span `request` has unexpected value String(\"GET\") (expected: u64)"
+/// );
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct FieldRef<'a> {
+    owner: FieldOwner,
+    name: &'a str,
+    value: Option<&'a TracedValue>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct FieldOwner {
+    kind: &'static str,
+    name: &'static str,
+}
+
+impl FieldOwner {
+    pub(crate) fn span(name: &'static str) -> Self {
+        Self { kind: "span", name }
+    }
+
+    pub(crate) fn event(name: &'static str) -> Self {
+        Self {
+            kind: "event",
+            name,
+        }
+    }
+
+    pub(crate) fn missing_field(self, name: &str) -> FieldError {
+        FieldError {
+            owner: self,
+            name: name.to_owned(),
+            kind: FieldErrorKind::Missing,
+        }
+    }
+}
+
+impl fmt::Display for FieldOwner {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "{} `{}`", self.kind, self.name)
+    }
+}
+
+impl<'a> FieldRef<'a> {
+    pub(crate) fn new(owner: FieldOwner, name: &'a str, value: Option<&'a TracedValue>) -> Self {
+        Self { owner, name, value }
+    }
+
+    /// Returns the name of the referenced field.
+    pub fn name(&self) -> &'a str {
+        self.name
+    }
+
+    /// Returns the field value, or `None` if the field is missing.
+    pub fn value(&self) -> Option<&'a TracedValue> {
+        self.value
+    }
+
+    /// Returns the field value.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the field is missing.
+    pub fn get(&self) -> Result<&'a TracedValue, FieldError> {
+        self.value
+            .ok_or_else(|| self.error(FieldErrorKind::Missing))
+    }
+
+    /// Returns the field value converted to the specified type.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the field is missing or cannot be converted to `T`.
+    pub fn get_as<T>(&self) -> Result<T::Output, FieldError>
+    where
+        T: FromTracedValue<'a> + ?Sized,
+    {
+        let value = self.get()?;
+        T::from_value(value).ok_or_else(|| {
+            self.error(FieldErrorKind::UnexpectedValue {
+                expected: any::type_name::<T>(),
+                actual: value.clone(),
+            })
+        })
+    }
+
+    fn error(&self, kind: FieldErrorKind) -> FieldError {
+        FieldError {
+            owner: self.owner,
+            name: self.name.to_owned(),
+            kind,
+        }
+    }
+
+    #[track_caller]
+    fn expect_as<T>(&self) -> T::Output
+    where
+        T: FromTracedValue<'a> + ?Sized,
+    {
+        self.get_as::<T>().unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Returns the field value.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the field is missing.
+    #[track_caller]
+    pub fn expect_value(&self) -> &'a TracedValue {
+        self.get().unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Returns the string value of the field.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the field is missing or is not a string.
+    #[track_caller]
+    pub fn expect_str(&self) -> &'a str {
+        self.expect_as::<str>()
+    }
+
+    /// Returns the [`Debug`](fmt::Debug) presentation of the field value as per
+    /// [`TracedValue::as_debug_str()`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the field is missing or has no string / debug presentation.
+    #[track_caller]
+    pub fn expect_debug_str(&self) -> &'a str {
+        let value = self.expect_value();
+        value.as_debug_str().unwrap_or_else(|| {
+            let err = self.error(FieldErrorKind::UnexpectedValue {
+                expected: "debug string",
+                actual: value.clone(),
+            });
+            panic!("{err}");
+        })
+    }
+
+    /// Returns the Boolean value of the field.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the field is missing or is not a Boolean.
+    #[track_caller]
+    pub fn expect_bool(&self) -> bool {
+        self.expect_as::<bool>()
+    }
+
+    /// Returns the signed integer value of the field.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the field is missing, is not a signed integer, or does not fit into `i64`.
+    #[track_caller]
+    pub fn expect_i64(&self) -> i64 {
+        self.expect_as::<i64>()
+    }
+
+    /// Returns the unsigned integer value of the field.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the field is missing, is not an unsigned integer, or does not fit into `u64`.
+    #[track_caller]
+    pub fn expect_u64(&self) -> u64 {
+        self.expect_as::<u64>()
+    }
+
+    /// Returns the floating-point value of the field.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the field is missing or is not a floating-point value.
+    #[track_caller]
+    pub fn expect_f64(&self) -> f64 {
+        self.expect_as::<f64>()
+    }
+}
+
+/// Error accessing a field via [`FieldRef`].
+#[derive(Debug, Clone)]
+pub struct FieldError {
+    owner: FieldOwner,
+    name: String,
+    kind: FieldErrorKind,
+}
+
+#[derive(Debug, Clone)]
+enum FieldErrorKind {
+    Missing,
+    UnexpectedValue {
+        expected: &'static str,
+        actual: TracedValue,
+    },
+}
+
+impl FieldError {
+    /// Returns the name of the field that caused this error.
+    pub fn field_name(&self) -> &str {
+        &self.name
+    }
+
+    /// Checks whether this error was caused by the field missing.
+    pub fn is_missing(&self) -> bool {
+        matches!(self.kind, FieldErrorKind::Missing)
+    }
+
+    /// Returns the actual field value if this error was caused by a value of an unexpected type.
+    pub fn actual_value(&self) -> Option<&TracedValue> {
+        match &self.kind {
+            FieldErrorKind::Missing => None,
+            FieldErrorKind::UnexpectedValue { actual, .. } => Some(actual),
+        }
+    }
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Self { owner, name, kind } = self;
+        match kind {
+            FieldErrorKind::Missing => {
+                write!(formatter, "field `{name}` is not contained in {owner}")
+            }
+            FieldErrorKind::UnexpectedValue { expected, actual } => write!(
+                formatter,
+                "field `{name}` of {owner} has unexpected value {actual:?} (expected: {expected})"
+            ),
+        }
+    }
+}
+
+impl error::Error for FieldError {}
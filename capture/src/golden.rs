@@ -0,0 +1,263 @@
+//! Golden files locking captured traces across versions of the instrumented code.
+
+use serde_json::Value;
+
+use std::{
+    collections::BTreeSet,
+    env,
+    fmt::{self, Write as _},
+    fs,
+    io::{self, ErrorKind},
+    path::Path,
+};
+
+use crate::Storage;
+
+/// Environment variable that, if set to a value other than `0`, makes
+/// [`Storage::assert_matches_golden()`] (re-)record golden files instead of checking them.
+const UPDATE_VAR: &str = "TRACING_CAPTURE_UPDATE_GOLDEN";
+
+/// Tolerance for comparing captured traces with golden files in
+/// [`Storage::assert_matches_golden()`].
+///
+/// By default, comparison is exact: the golden file must completely match
+/// the [snapshot](Storage::to_snapshot()) of the storage.
+///
+/// # Examples
+///
+/// ```
+/// # use tracing_capture::GoldenTolerance;
+/// let tolerance = GoldenTolerance::default()
+///     .ignore_field("request_id")
+///     .with_numeric_tolerance(0.1);
+/// ```
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(docsrs, doc(cfg(feature = "golden")))]
+pub struct GoldenTolerance {
+    ignored_fields: BTreeSet<String>,
+    numeric_tolerance: f64,
+}
+
+impl GoldenTolerance {
+    /// Ignores the field with the specified name in all spans and events, e.g. because
+    /// its values are random.
+    #[must_use]
+    pub fn ignore_field(mut self, name: impl Into<String>) -> Self {
+        self.ignored_fields.insert(name.into());
+        self
+    }
+
+    /// Sets the relative tolerance for numeric values, e.g. durations recorded
+    /// by the instrumented code. Numbers `a` and `b` are considered equal if
+    /// `|a - b| <= tolerance * max(|a|, |b|)`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `tolerance` is negative or NaN.
+    #[must_use]
+    pub fn with_numeric_tolerance(mut self, tolerance: f64) -> Self {
+        assert!(tolerance >= 0.0, "numeric tolerance must be non-negative");
+        self.numeric_tolerance = tolerance;
+        self
+    }
+
+    fn strip_ignored_fields(&self, value: &mut Value) {
+        let Value::Object(map) = value else {
+            return;
+        };
+        if let Some(Value::Object(fields)) = map.get_mut("fields") {
+            fields.retain(|name, _| !self.ignored_fields.contains(name));
+        }
+        for key in ["spans", "events"] {
+            if let Some(Value::Array(items)) = map.get_mut(key) {
+                for item in items {
+                    self.strip_ignored_fields(item);
+                }
+            }
+        }
+    }
+
+    fn numbers_eq(&self, expected: &serde_json::Number, actual: &serde_json::Number) -> bool {
+        if expected == actual {
+            return true;
+        }
+        let (Some(expected), Some(actual)) = (expected.as_f64(), actual.as_f64()) else {
+            return false;
+        };
+        let scale = expected.abs().max(actual.abs());
+        (expected - actual).abs() <= self.numeric_tolerance * scale
+    }
+
+    fn diff(&self, path: &mut String, expected: &Value, actual: &Value, diffs: &mut Vec<String>) {
+        match (expected, actual) {
+            (Value::Object(expected), Value::Object(actual)) => {
+                let keys: BTreeSet<_> = expected.keys().chain(actual.keys()).collect();
+                for key in keys {
+                    let prev_len = path.len();
+                    if !path.is_empty() {
+                        path.push('.');
+                    }
+                    path.push_str(key);
+                    match (expected.get(key), actual.get(key)) {
+                        (Some(expected), Some(actual)) => {
+                            self.diff(path, expected, actual, diffs);
+                        }
+                        (Some(expected), None) => diffs.push(format!("{path}: missing {expected}")),
+                        (None, Some(actual)) => diffs.push(format!("{path}: unexpected {actual}")),
+                        (None, None) => unreachable!(),
+                    }
+                    path.truncate(prev_len);
+                }
+            }
+            (Value::Array(expected), Value::Array(actual)) => {
+                for (i, (expected, actual)) in expected.iter().zip(actual).enumerate() {
+                    let prev_len = path.len();
+                    write!(path, "[{i}]").unwrap();
+                    self.diff(path, expected, actual, diffs);
+                    path.truncate(prev_len);
+                }
+                for (i, expected) in expected.iter().enumerate().skip(actual.len()) {
+                    diffs.push(format!("{path}[{i}]: missing {expected}"));
+                }
+                for (i, actual) in actual.iter().enumerate().skip(expected.len()) {
+                    diffs.push(format!("{path}[{i}]: unexpected {actual}"));
+                }
+            }
+            (Value::Number(expected_num), Value::Number(actual_num)) => {
+                if !self.numbers_eq(expected_num, actual_num) {
+                    diffs.push(format!("{path}: expected {expected}, got {actual}"));
+                }
+            }
+            _ => {
+                if expected != actual {
+                    diffs.push(format!("{path}: expected {expected}, got {actual}"));
+                }
+            }
+        }
+    }
+}
+
+/// Differences between a golden file and the captured trace.
+struct GoldenDiff<'a> {
+    path: &'a Path,
+    diffs: Vec<String>,
+}
+
+impl fmt::Display for GoldenDiff<'_> {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            formatter,
+            "captured trace does not match golden file `{}`:",
+            self.path.display()
+        )?;
+        for diff in &self.diffs {
+            writeln!(formatter, "  {diff}")?;
+        }
+        write!(
+            formatter,
+            "If the change is intended, re-record the golden file by setting \
+             the {UPDATE_VAR}=1 env variable"
+        )
+    }
+}
+
+fn should_update() -> bool {
+    env::var_os(UPDATE_VAR).is_some_and(|value| !value.is_empty() && value != "0")
+}
+
+impl Storage {
+    fn golden_value(&self) -> io::Result<Value> {
+        serde_json::to_value(self.to_snapshot()).map_err(Into::into)
+    }
+
+    /// Records the [snapshot](Self::to_snapshot()) of this storage into a JSON golden file
+    /// at the specified path, creating parent directories if necessary. The file can then
+    /// be checked into version control and checked via [`Self::assert_matches_golden()`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the snapshot cannot be serialized (e.g., because it contains
+    /// 128-bit integers not fitting into 64 bits) or if writing the file fails.
+    #[cfg_attr(docsrs, doc(cfg(feature = "golden")))]
+    pub fn record_golden(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut contents = serde_json::to_string_pretty(&self.golden_value()?)?;
+        contents.push('\n');
+        fs::write(path, contents)
+    }
+
+    /// Asserts that the [snapshot](Self::to_snapshot()) of this storage matches
+    /// the golden file at the specified path with the specified `tolerance`. This allows
+    /// library authors to "lock" their instrumentation output across releases.
+    ///
+    /// If the `TRACING_CAPTURE_UPDATE_GOLDEN` env variable is set to a value other than `0`,
+    /// the golden file is (re-)recorded via [`Self::record_golden()`] instead.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the golden file cannot be read or parsed, or if it doesn't match
+    /// the captured trace. The panic message lists all differences with paths to the differing
+    /// spans / events, e.g. `spans[0].events[1].fields.rows`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_subscriber::layer::SubscriberExt;
+    /// # use tracing_capture::{CaptureLayer, GoldenTolerance, SharedStorage};
+    /// fn capture(request_id: u64) -> SharedStorage {
+    ///     let storage = SharedStorage::default();
+    ///     let subscriber = tracing_subscriber::registry()
+    ///         .with(CaptureLayer::new(&storage));
+    ///     tracing::subscriber::with_default(subscriber, || {
+    ///         tracing::info_span!("compute", request_id, count = 5).in_scope(|| {
+    ///             tracing::debug!(result = 8, "computed");
+    ///         });
+    ///     });
+    ///     storage
+    /// }
+    ///
+    /// let path = std::env::temp_dir().join("tracing-capture-doc.golden.json");
+    /// capture(1).lock().record_golden(&path)?;
+    /// let tolerance = GoldenTolerance::default().ignore_field("request_id");
+    /// capture(2).lock().assert_matches_golden(&path, &tolerance);
+    /// # Ok::<_, std::io::Error>(())
+    /// ```
+    #[cfg_attr(docsrs, doc(cfg(feature = "golden")))]
+    #[track_caller]
+    pub fn assert_matches_golden(&self, path: impl AsRef<Path>, tolerance: &GoldenTolerance) {
+        let path = path.as_ref();
+        if should_update() {
+            self.record_golden(path).unwrap_or_else(|err| {
+                panic!("failed recording golden file `{}`: {err}", path.display());
+            });
+            return;
+        }
+
+        let contents = fs::read_to_string(path).unwrap_or_else(|err| {
+            let hint = if err.kind() == ErrorKind::NotFound {
+                format!("; record it by setting the {UPDATE_VAR}=1 env variable")
+            } else {
+                String::new()
+            };
+            panic!(
+                "failed reading golden file `{}`: {err}{hint}",
+                path.display()
+            );
+        });
+        let mut expected: Value = serde_json::from_str(&contents).unwrap_or_else(|err| {
+            panic!("failed parsing golden file `{}`: {err}", path.display());
+        });
+        let mut actual = self.golden_value().unwrap_or_else(|err| {
+            panic!("failed serializing captured trace: {err}");
+        });
+        tolerance.strip_ignored_fields(&mut expected);
+        tolerance.strip_ignored_fields(&mut actual);
+
+        let mut diffs = vec![];
+        tolerance.diff(&mut String::new(), &expected, &actual, &mut diffs);
+        assert!(diffs.is_empty(), "{}", GoldenDiff { path, diffs });
+    }
+}
@@ -231,3 +231,31 @@ impl<'a> Iterator for DescendantEvents<'a> {
         self.inner.size_hint()
     }
 }
+
+/// Collects spans into a snapshot, which is then iterated over in parallel. Spans are cheap
+/// to copy (they only reference the storage), so the snapshot is relatively lightweight
+/// even for large captures.
+#[cfg(feature = "rayon")]
+#[cfg_attr(docsrs, doc(cfg(feature = "rayon")))]
+impl<'a> rayon::iter::IntoParallelIterator for CapturedSpans<'a> {
+    type Iter = rayon::vec::IntoIter<CapturedSpan<'a>>;
+    type Item = CapturedSpan<'a>;
+
+    fn into_par_iter(self) -> Self::Iter {
+        self.collect::<Vec<_>>().into_par_iter()
+    }
+}
+
+/// Collects events into a snapshot, which is then iterated over in parallel. Events are cheap
+/// to copy (they only reference the storage), so the snapshot is relatively lightweight
+/// even for large captures.
+#[cfg(feature = "rayon")]
+#[cfg_attr(docsrs, doc(cfg(feature = "rayon")))]
+impl<'a> rayon::iter::IntoParallelIterator for CapturedEvents<'a> {
+    type Iter = rayon::vec::IntoIter<CapturedEvent<'a>>;
+    type Item = CapturedEvent<'a>;
+
+    fn into_par_iter(self) -> Self::Iter {
+        self.collect::<Vec<_>>().into_par_iter()
+    }
+}
@@ -1,68 +1,425 @@
 //! `CaptureLayer` and related types.
 
 use id_arena::Arena;
+use predicates::Predicate;
 use tracing_core::{
+    callsite,
     span::{Attributes, Id, Record},
-    Event, Metadata, Subscriber,
+    Dispatch, Event, Level, LevelFilter, Metadata, Subscriber,
 };
 use tracing_subscriber::{
     layer::{Context, Filter},
-    registry::LookupSpan,
+    registry::{Extensions, LookupSpan, SpanRef},
     Layer,
 };
 
 use std::{
-    fmt, ops,
-    sync::{Arc, RwLock},
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    fmt, iter,
+    marker::PhantomData,
+    mem, ops, ptr,
+    sync::{
+        atomic::{AtomicU64, AtomicU8, Ordering},
+        Arc, Mutex, OnceLock, RwLock, RwLockWriteGuard, Weak,
+    },
+    thread::{self, ThreadId},
+    time::{Duration, Instant, SystemTime},
 };
 
+#[cfg(feature = "perf")]
+use crate::PerfStats;
 use crate::{
-    CapturedEvent, CapturedEventId, CapturedEventInner, CapturedEvents, CapturedSpan,
-    CapturedSpanId, CapturedSpanInner, CapturedSpans, SpanStats,
+    clock::{Clock, SharedClock},
+    timeline::TimelineEntry,
+    values::{display_value, CapturedValues, StoredValue, StringPool},
+    CapturedEvent, CapturedEventId, CapturedEventInner, CapturedEvents, CapturedOperation,
+    CapturedSpan, CapturedSpanId, CapturedSpanInner, CapturedSpans, Query, SpanEntry, SpanStats,
 };
-use tracing_tunnel::TracedValues;
+use tracing_tunnel::{TracedValue, TracedValues};
+
+type RedactionFn = dyn Fn(&str, &mut TracedValue) + Send + Sync;
+type ExtensionExtractorFn = dyn Fn(&Extensions<'_>, &mut TracedValues<&'static str>) + Send + Sync;
+type CloseHookFn = dyn Fn(&ClosedSpan) + Send + Sync;
+type ParentInferenceFn =
+    dyn for<'s> Fn(&OrphanEvent<'_>, &'s Storage) -> Option<CapturedSpan<'s>> + Send + Sync;
+type PartitionFn = dyn for<'a> Fn(&RootSpan<'a>) -> Option<&'a TracedValue> + Send + Sync;
+
+/// Partitions of a [`SharedStorage`] keyed by the partition key.
+type Partitions = Mutex<HashMap<String, Partition>>;
+
+#[derive(Debug)]
+struct Partition {
+    storage: Arc<RwLock<Storage>>,
+    // Weak reference so that the open spans check is performed once all `SharedStorage`
+    // handles for the partition are dropped.
+    guard: Weak<StorageGuard>,
+}
+
+impl Default for Partition {
+    fn default() -> Self {
+        Self {
+            storage: Arc::new(RwLock::new(Storage::new())),
+            guard: Weak::new(),
+        }
+    }
+}
+
+fn lock_partitions(
+    partitions: &Partitions,
+) -> impl ops::DerefMut<Target = HashMap<String, Partition>> + '_ {
+    partitions
+        .lock()
+        .expect("failed accessing tracing data storage partitions")
+}
+
+/// Converts a value returned by the [partitioning function](CaptureLayer::partition_by())
+/// to the partition key.
+fn partition_key(value: &TracedValue) -> String {
+    display_value(value)
+}
+
+thread_local! {
+    static THREAD_STORAGE: RefCell<Option<Arc<RwLock<Storage>>>> = const { RefCell::new(None) };
+}
+
+fn lock_for_write(storage: &RwLock<Storage>) -> RwLockWriteGuard<'_, Storage> {
+    #[cfg(feature = "perf")]
+    let started_at = Instant::now();
+    #[allow(unused_mut)] // used with the `perf` feature
+    let mut lock = storage
+        .write()
+        .expect("failed locking shared tracing data storage for write");
+    #[cfg(feature = "perf")]
+    lock.perf.on_lock(started_at.elapsed());
+    lock
+}
 
 /// Storage of captured tracing information.
 ///
 /// `Storage` instances are not created directly; instead, they are wrapped in [`SharedStorage`]
-/// and can be accessed via [`lock()`](SharedStorage::lock()).
-#[derive(Debug)]
+/// and can be accessed via [`lock()`](SharedStorage::lock()) or copied
+/// via [`snapshot()`](SharedStorage::snapshot()).
+///
+/// [String values](TracedValue::String) of captured spans and events are interned, so that
+/// repeated strings (e.g., identifiers or statuses recorded in many events) are stored once,
+/// and snapshots share them with the original storage. Interned strings are retained
+/// until the storage is [cleared](Self::clear()).
+#[derive(Debug, Clone)]
 pub struct Storage {
     pub(crate) spans: Arena<CapturedSpanInner>,
     pub(crate) events: Arena<CapturedEventInner>,
     root_span_ids: Vec<CapturedSpanId>,
     root_event_ids: Vec<CapturedEventId>,
+    timeline: Vec<TimelineEntry>,
+    /// Values referenced by `TimelineEntry::ValueRecorded` entries.
+    recorded_values: Vec<StoredValue>,
+    call_sites: Vec<CallSiteStats>,
+    call_site_indices: HashMap<callsite::Identifier, usize>,
+    open_spans_check: Option<OpenSpansCheck>,
+    max_events_per_span: Option<usize>,
+    /// Default field overriding span names; see `CaptureLayer::with_name_field()`.
+    /// Spans captured by a layer use the field configured for the layer.
+    name_field: Option<&'static str>,
+    /// Spans exceeding `max_events_per_span`, in the order of exceeding the limit.
+    span_ids_exceeding_event_limit: Vec<CapturedSpanId>,
+    /// Pool of interned string values shared among captured spans and events.
+    strings: StringPool,
+    /// Number of times the storage was cleared.
+    generation: u64,
+    created_at: Instant,
+    /// Wall-clock time corresponding to `created_at`.
+    created_at_system: SystemTime,
+    /// Clock used for timestamps; `None` means using `Instant::now()`.
+    clock: Option<SharedClock>,
+    threads: Vec<ThreadId>,
+    dispatchers: Vec<DispatcherId>,
+    /// Index in `dispatchers` of the dispatcher currently capturing into this storage.
+    /// Set by `CaptureLayer` each time it captures a span or event.
+    active_dispatcher: Option<usize>,
+    /// Initial capacities for child span and event IDs in each captured span.
+    span_capacity: (usize, usize),
+    #[cfg(feature = "perf")]
+    perf: PerfStats,
 }
 
 impl Storage {
     pub(crate) fn new() -> Self {
+        Self::with_capacity(0, 0)
+    }
+
+    fn with_capacity(spans: usize, events: usize) -> Self {
         Self {
-            spans: Arena::new(),
-            events: Arena::new(),
-            root_span_ids: vec![],
-            root_event_ids: vec![],
+            spans: Arena::with_capacity(spans),
+            events: Arena::with_capacity(events),
+            root_span_ids: Vec::with_capacity(spans),
+            root_event_ids: Vec::with_capacity(events),
+            // Each span is created, closed and usually entered / exited once.
+            timeline: Vec::with_capacity(events + 4 * spans),
+            recorded_values: vec![],
+            call_sites: vec![],
+            call_site_indices: HashMap::new(),
+            open_spans_check: None,
+            max_events_per_span: None,
+            name_field: None,
+            span_ids_exceeding_event_limit: vec![],
+            strings: StringPool::default(),
+            generation: 0,
+            created_at: Instant::now(),
+            created_at_system: SystemTime::now(),
+            clock: None,
+            threads: vec![],
+            dispatchers: vec![],
+            active_dispatcher: None,
+            span_capacity: (0, 0),
+            #[cfg(feature = "perf")]
+            perf: PerfStats::default(),
+        }
+    }
+
+    /// Returns the index of the current thread in [`Self::threads()`], adding the thread
+    /// if necessary.
+    fn current_thread(&mut self) -> usize {
+        let thread_id = thread::current().id();
+        if let Some(idx) = self.threads.iter().position(|&id| id == thread_id) {
+            idx
+        } else {
+            self.threads.push(thread_id);
+            self.threads.len() - 1
+        }
+    }
+
+    fn now(&self) -> Instant {
+        self.clock
+            .as_ref()
+            .map_or_else(Instant::now, |clock| clock.0.now())
+    }
+
+    /// Sets the clock used for the following timestamps. If the clock differs from the current
+    /// one, the storage creation instant is reset according to the new clock.
+    pub(crate) fn set_clock(&mut self, clock: &SharedClock) {
+        let is_same = self.clock.as_ref().is_some_and(|current| {
+            ptr::eq(
+                Arc::as_ptr(&current.0).cast::<()>(),
+                Arc::as_ptr(&clock.0).cast::<()>(),
+            )
+        });
+        if !is_same {
+            self.created_at = clock.0.now();
+            self.clock = Some(clock.clone());
         }
     }
 
+    /// Sets the dispatcher capturing the following spans and events, adding it
+    /// to [`Self::dispatchers()`] if necessary.
+    fn set_active_dispatcher(&mut self, dispatcher: DispatcherId) {
+        let idx = if let Some(idx) = self.dispatchers.iter().position(|&id| id == dispatcher) {
+            idx
+        } else {
+            self.dispatchers.push(dispatcher);
+            self.dispatchers.len() - 1
+        };
+        self.active_dispatcher = Some(idx);
+    }
+
     pub(crate) fn span(&self, id: CapturedSpanId) -> CapturedSpan<'_> {
+        let inner = self
+            .spans
+            .get(id)
+            .unwrap_or_else(|| panic!("{}", self.stale_id_message("span", id.index())));
         CapturedSpan {
-            inner: &self.spans[id],
+            inner,
             storage: self,
         }
     }
 
     pub(crate) fn event(&self, id: CapturedEventId) -> CapturedEvent<'_> {
+        let inner = self
+            .events
+            .get(id)
+            .unwrap_or_else(|| panic!("{}", self.stale_id_message("event", id.index())));
         CapturedEvent {
-            inner: &self.events[id],
+            inner,
             storage: self,
         }
     }
 
+    pub(crate) fn recorded_value(&self, idx: usize) -> &TracedValue {
+        &self.recorded_values[idx]
+    }
+
+    fn stale_id_message(&self, kind: &str, index: usize) -> String {
+        format!(
+            "captured {kind} #{index} is not present in the storage (storage generation: {}); \
+             it was probably captured before the storage was cleared",
+            self.generation
+        )
+    }
+
+    /// Checks whether the span with the specified ID belongs to the current storage generation.
+    pub(crate) fn contains_span(&self, id: CapturedSpanId) -> bool {
+        self.spans.get(id).is_some()
+    }
+
+    /// Returns the generation of this storage, i.e., the number of times it was
+    /// [cleared](Self::clear()).
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Removes all captured spans and events from this storage and increments
+    /// its [generation](Self::generation()). Capture settings (e.g., the
+    /// [open spans check](CaptureLayer::with_open_spans_check())) are retained.
+    ///
+    /// Spans and events captured before clearing are tagged with the previous generation.
+    /// A [`CaptureLayer`] ignores operations on spans that were open when the storage
+    /// was cleared (e.g., entering such a span or recording values for it), and treats
+    /// events / spans with such a parent as root ones. Dereferencing a span / event
+    /// from a previous generation in any other way panics with an informative message.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+    /// # use tracing_capture::{CaptureLayer, SharedStorage};
+    /// let storage = SharedStorage::default();
+    /// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     let span = tracing::info_span!("setup");
+    ///     span.in_scope(|| tracing::info!("preparing"));
+    ///     storage.clear();
+    ///     span.in_scope(|| tracing::info!("prepared")); // captured as a root event
+    ///     tracing::info_span!("test").in_scope(|| tracing::info!("started"));
+    /// });
+    ///
+    /// let storage = storage.lock();
+    /// assert_eq!(storage.generation(), 1);
+    /// let span_names: Vec<_> = storage.all_spans().map(|span| span.metadata().name()).collect();
+    /// assert_eq!(span_names, ["test"]);
+    /// assert_eq!(storage.root_events().len(), 1);
+    /// ```
+    pub fn clear(&mut self) {
+        // Creating new arenas ensures that IDs from previous generations are not resolved.
+        self.spans = Arena::new();
+        self.events = Arena::new();
+        self.root_span_ids.clear();
+        self.root_event_ids.clear();
+        self.timeline.clear();
+        self.recorded_values.clear();
+        self.call_sites.clear();
+        self.call_site_indices.clear();
+        self.span_ids_exceeding_event_limit.clear();
+        self.strings.clear();
+        self.generation += 1;
+    }
+
     /// Iterates over captured spans in the order of capture.
     pub fn all_spans(&self) -> CapturedSpans<'_> {
         CapturedSpans::from_arena(self)
     }
 
+    /// Returns the instant when this storage was created. This can be used as the origin
+    /// for [span](CapturedSpan::created_at()) and [event](CapturedEvent::timestamp()) timestamps.
+    pub fn created_at(&self) -> Instant {
+        self.created_at
+    }
+
+    /// Converts an instant captured by this storage to the wall-clock time.
+    pub(crate) fn system_time(&self, instant: Instant) -> SystemTime {
+        self.created_at_system + instant.saturating_duration_since(self.created_at)
+    }
+
+    /// Returns IDs of threads on which spans were entered or events were captured, in the order
+    /// the threads were first encountered. Indices in this slice are used to refer to threads
+    /// in [`SpanEntry::thread`] and [`CapturedEvent::thread()`].
+    pub fn threads(&self) -> &[ThreadId] {
+        &self.threads
+    }
+
+    /// Returns IDs of dispatchers that captured spans or events into this storage, in the order
+    /// the dispatchers were first encountered. Indices in this slice are used to refer
+    /// to dispatchers in [`CapturedSpan::dispatcher()`] and [`CapturedEvent::dispatcher()`].
+    ///
+    /// Several dispatchers can capture into the same storage if [`CaptureLayer`]s sharing
+    /// the storage are installed in different subscribers, e.g., when tests intentionally nest
+    /// subscribers via [`with_default()`](tracing::subscriber::with_default()).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+    /// # use tracing_capture::{CaptureLayer, SharedStorage};
+    /// let storage = SharedStorage::default();
+    /// let outer = Registry::default().with(CaptureLayer::new(&storage));
+    /// let inner = Registry::default().with(CaptureLayer::new(&storage));
+    /// tracing::subscriber::with_default(outer, || {
+    ///     tracing::info!("outer");
+    ///     tracing::subscriber::with_default(inner, || tracing::info!("inner"));
+    /// });
+    ///
+    /// let storage = storage.lock();
+    /// assert_eq!(storage.dispatchers().len(), 2);
+    /// let inner_messages: Vec<_> = storage
+    ///     .all_events()
+    ///     .filter(|event| event.dispatcher() == Some(1))
+    ///     .filter_map(|event| event.message())
+    ///     .collect();
+    /// assert_eq!(inner_messages, ["inner"]);
+    /// ```
+    pub fn dispatchers(&self) -> &[DispatcherId] {
+        &self.dispatchers
+    }
+
+    /// Returns usage statistics for call sites of captured spans and events, in the order
+    /// the call sites were first encountered. This can be used to check that instrumentation
+    /// (e.g., with sampling or throttling) emits the expected volume of spans / events
+    /// per call site.
+    ///
+    /// Placeholder spans created for [missing ancestors](CaptureLayer::with_missing_ancestors())
+    /// are not counted.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+    /// # use tracing_capture::{CaptureLayer, SharedStorage};
+    /// let storage = SharedStorage::default();
+    /// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     for i in 0..10 {
+    ///         let _entered = tracing::info_span!("iteration", i).entered();
+    ///         if i % 3 == 0 {
+    ///             tracing::info!(i, "sampled");
+    ///         }
+    ///     }
+    /// });
+    ///
+    /// let storage = storage.lock();
+    /// let counts: Vec<_> = storage
+    ///     .call_sites()
+    ///     .iter()
+    ///     .map(|site| (site.metadata.name(), site.count))
+    ///     .collect();
+    /// assert_eq!(counts.len(), 2);
+    /// assert_eq!(counts[0], ("iteration", 10));
+    /// assert_eq!(counts[1].1, 4);
+    /// ```
+    pub fn call_sites(&self) -> &[CallSiteStats] {
+        &self.call_sites
+    }
+
+    fn count_call_site(&mut self, metadata: &'static Metadata<'static>) {
+        let next_idx = self.call_sites.len();
+        let idx = *self
+            .call_site_indices
+            .entry(metadata.callsite())
+            .or_insert(next_idx);
+        if idx == next_idx {
+            self.call_sites.push(CallSiteStats { metadata, count: 0 });
+        }
+        self.call_sites[idx].count += 1;
+    }
+
     /// Iterates over root spans (i.e., spans that do not have a captured parent span)
     /// in the order of capture.
     pub fn root_spans(&self) -> CapturedSpans<'_> {
@@ -74,27 +431,461 @@ impl Storage {
         CapturedEvents::from_arena(self)
     }
 
+    /// Iterates over spans [created](CapturedSpan::created_at()) in the specified time range
+    /// (inclusive on both ends), in the order of capture. Spans are not required to be closed
+    /// within the range; use the [`ended_before()`] predicate to check this.
+    ///
+    /// [`ended_before()`]: crate::predicates::ended_before()
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::time::Instant;
+    /// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+    /// # use tracing_capture::{CaptureLayer, SharedStorage};
+    /// let storage = SharedStorage::default();
+    /// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    /// let (start, end) = tracing::subscriber::with_default(subscriber, || {
+    ///     tracing::info_span!("setup").in_scope(|| tracing::info!("preparing"));
+    ///     let start = Instant::now();
+    ///     tracing::info_span!("failover").in_scope(|| tracing::warn!("switching"));
+    ///     (start, Instant::now())
+    /// });
+    ///
+    /// let storage = storage.lock();
+    /// let spans: Vec<_> = storage.spans_between(start, end).collect();
+    /// assert_eq!(spans.len(), 1);
+    /// assert_eq!(spans[0].metadata().name(), "failover");
+    /// let events: Vec<_> = storage.events_between(start, end).collect();
+    /// assert_eq!(events.len(), 1);
+    /// assert_eq!(events[0].message(), Some("switching"));
+    /// ```
+    pub fn spans_between(
+        &self,
+        start: Instant,
+        end: Instant,
+    ) -> impl Iterator<Item = CapturedSpan<'_>> + '_ {
+        self.all_spans()
+            .filter(move |span| (start..=end).contains(&span.created_at()))
+    }
+
+    /// Iterates over events [emitted](CapturedEvent::timestamp()) in the specified time range
+    /// (inclusive on both ends), in the order of capture. See [`Self::spans_between()`]
+    /// for an example of usage.
+    pub fn events_between(
+        &self,
+        start: Instant,
+        end: Instant,
+    ) -> impl Iterator<Item = CapturedEvent<'_>> + '_ {
+        self.all_events()
+            .filter(move |event| (start..=end).contains(&event.timestamp()))
+    }
+
+    /// Iterates over all captured events that have the specified level, in the order of capture.
+    /// See [`CapturedSpan::events_at()`] for the span-scoped version.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing::Level;
+    /// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+    /// # use tracing_capture::{CaptureLayer, SharedStorage};
+    /// let storage = SharedStorage::default();
+    /// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     tracing::error!("root failure");
+    ///     tracing::info_span!("compute").in_scope(|| tracing::error!("nested failure"));
+    ///     tracing::info!("done");
+    /// });
+    ///
+    /// let storage = storage.lock();
+    /// let errors: Vec<_> = storage
+    ///     .events_at(Level::ERROR)
+    ///     .filter_map(|event| event.message())
+    ///     .collect();
+    /// assert_eq!(errors, ["root failure", "nested failure"]);
+    /// assert_eq!(storage.event_count_at(Level::WARN), 0);
+    /// ```
+    pub fn events_at(&self, level: Level) -> impl Iterator<Item = CapturedEvent<'_>> + '_ {
+        self.all_events()
+            .filter(move |event| *event.metadata().level() == level)
+    }
+
+    /// Returns the number of captured events that have the specified level.
+    pub fn event_count_at(&self, level: Level) -> usize {
+        self.events_at(level).count()
+    }
+
     /// Iterates over root events (i.e., events that do not have a captured parent span)
     /// in the order of capture.
     pub fn root_events(&self) -> CapturedEvents<'_> {
         CapturedEvents::from_slice(self, &self.root_event_ids)
     }
 
+    /// Starts a lazily evaluated [`Query`] over spans or events in this storage.
+    /// See `Query` docs for an example of usage.
+    pub fn query(&self) -> Query<'_> {
+        Query::new(self)
+    }
+
+    /// Returns the approximate memory usage of this storage. See [`MemoryUsage`] for details.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+    /// # use tracing_capture::{CaptureLayer, SharedStorage};
+    /// let storage = SharedStorage::default();
+    /// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     tracing::info_span!("compute", arg = 42).in_scope(|| {
+    ///         for _ in 0..3 {
+    ///             tracing::info!(result = "ok", "computed");
+    ///         }
+    ///     });
+    /// });
+    ///
+    /// let usage = storage.lock().memory_usage();
+    /// assert_eq!((usage.spans, usage.events, usage.values), (1, 3, 7));
+    /// // The `result` string is interned, while messages are `Debug` objects stored per event.
+    /// assert_eq!(usage.interned_strings, 1);
+    /// assert_eq!(usage.string_bytes, "ok".len() + 3 * "computed".len());
+    /// assert!(usage.total_bytes > usage.string_bytes);
+    /// ```
+    pub fn memory_usage(&self) -> MemoryUsage {
+        let mut usage = MemoryUsage {
+            spans: self.spans.len(),
+            events: self.events.len(),
+            total_bytes: mem::size_of::<Self>()
+                + self.spans.len() * mem::size_of::<CapturedSpanInner>()
+                + self.events.len() * mem::size_of::<CapturedEventInner>()
+                + self.root_span_ids.len() * mem::size_of::<CapturedSpanId>()
+                + self.root_event_ids.len() * mem::size_of::<CapturedEventId>()
+                + self.timeline.len() * mem::size_of::<TimelineEntry>()
+                + self.call_sites.len()
+                    * (mem::size_of::<CallSiteStats>()
+                        + mem::size_of::<(callsite::Identifier, usize)>())
+                + self.threads.len() * mem::size_of::<ThreadId>(),
+            ..MemoryUsage::default()
+        };
+        for span in self.all_spans() {
+            usage.total_bytes += span.inner.child_ids.len() * mem::size_of::<CapturedSpanId>()
+                + span.inner.event_ids.len() * mem::size_of::<CapturedEventId>()
+                + span.inner.follows_from_ids.len() * mem::size_of::<CapturedSpanId>()
+                + span.inner.entries.len() * mem::size_of::<SpanEntry>();
+            usage.add_values(&span.inner.values);
+            usage.add_values(&span.inner.extension_values);
+        }
+        for event in self.all_events() {
+            usage.add_values(&event.inner.values);
+        }
+
+        usage.interned_strings = self.strings.len();
+        for s in self.strings.iter() {
+            usage.string_bytes += s.len();
+            // Each entry consists of a pointer in the pool, and an allocation with reference counters
+            // and the string value, which points to the string data.
+            usage.total_bytes += mem::size_of::<Arc<TracedValue>>()
+                + 2 * mem::size_of::<usize>()
+                + mem::size_of::<TracedValue>()
+                + s.len();
+        }
+        usage
+    }
+
+    /// Iterates over all captured operations (span creation, entering / exiting, closing,
+    /// recording values and "follows from" relations, and emitting events) in the chronological
+    /// order. This can be used to assert on the interleaving of operations, or to print
+    /// a linear narrative of the captured trace using the [`Display`](fmt::Display)
+    /// implementation of [`CapturedOperation`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+    /// # use tracing_capture::{CaptureLayer, SharedStorage};
+    /// let storage = SharedStorage::default();
+    /// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     let span = tracing::info_span!("compute", result = tracing::field::Empty);
+    ///     span.in_scope(|| tracing::info!("started"));
+    ///     span.record("result", 42);
+    /// });
+    ///
+    /// let storage = storage.lock();
+    /// let narrative: Vec<_> = storage.timeline().map(|op| op.to_string()).collect();
+    /// assert_eq!(
+    ///     narrative,
+    ///     [
+    ///         "created span `compute`",
+    ///         "entered span `compute`",
+    ///         "INFO event: started",
+    ///         "exited span `compute`",
+    ///         "recorded `result` in span `compute`",
+    ///         "closed span `compute`",
+    ///     ]
+    /// );
+    /// ```
+    pub fn timeline(&self) -> impl ExactSizeIterator<Item = CapturedOperation<'_>> + '_ {
+        self.timeline.iter().map(|entry| entry.resolve(self))
+    }
+
+    /// Returns performance counters for capturing into this storage.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+    /// # use tracing_capture::{CaptureLayer, SharedStorage};
+    /// let storage = SharedStorage::default();
+    /// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     tracing::info_span!("compute").in_scope(|| {
+    ///         tracing::info!(result = 42, "computed");
+    ///     });
+    /// });
+    ///
+    /// let stats = storage.lock().perf_stats();
+    /// assert_eq!(stats.events, 1);
+    /// assert!(stats.lock_acquisitions >= 5); // span creation, entry, exit, closing and event
+    /// assert!(stats.allocated_bytes > 0);
+    /// ```
+    #[cfg(feature = "perf")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "perf")))]
+    pub fn perf_stats(&self) -> PerfStats {
+        self.perf
+    }
+
+    /// Iterates over spans that were not closed, in the order of capture. If capturing
+    /// has finished, such spans usually indicate a span leak, e.g. a future that was dropped
+    /// mid-poll while holding a span. See also [`CaptureLayer::with_open_spans_check()`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+    /// # use tracing_capture::{CaptureLayer, SharedStorage};
+    /// let storage = SharedStorage::default();
+    /// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     tracing::info_span!("closed").in_scope(|| { /* do nothing */ });
+    ///     std::mem::forget(tracing::info_span!("leaked"));
+    /// });
+    ///
+    /// let storage = storage.lock();
+    /// let open_spans: Vec<_> = storage
+    ///     .open_spans()
+    ///     .map(|span| span.metadata().name())
+    ///     .collect();
+    /// assert_eq!(open_spans, ["leaked"]);
+    /// ```
+    pub fn open_spans(&self) -> impl Iterator<Item = CapturedSpan<'_>> + '_ {
+        self.all_spans().filter(|span| !span.stats().is_closed)
+    }
+
+    /// Iterates over spans that have more directly attached events than allowed by
+    /// [`CaptureLayer::with_max_events_per_span()`], in the order of exceeding the limit.
+    /// If the limit is not set, the returned iterator is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+    /// # use tracing_capture::{CaptureLayer, SharedStorage};
+    /// let storage = SharedStorage::default();
+    /// let layer = CaptureLayer::new(&storage).with_max_events_per_span(5);
+    /// let subscriber = Registry::default().with(layer);
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     tracing::info_span!("hot_loop").in_scope(|| {
+    ///         for i in 0..10 {
+    ///             tracing::debug!(i, "iteration");
+    ///         }
+    ///     });
+    ///     tracing::info_span!("cold").in_scope(|| tracing::info!("done"));
+    /// });
+    ///
+    /// let storage = storage.lock();
+    /// let spans: Vec<_> = storage
+    ///     .spans_exceeding_event_limit()
+    ///     .map(|span| span.metadata().name())
+    ///     .collect();
+    /// assert_eq!(spans, ["hot_loop"]);
+    /// ```
+    pub fn spans_exceeding_event_limit(&self) -> CapturedSpans<'_> {
+        CapturedSpans::from_slice(self, &self.span_ids_exceeding_event_limit)
+    }
+
+    /// Iterates over all events in the subtree rooted at the specified span in the order
+    /// of capture. Unlike [`CapturedSpan::descendant_events()`], the returned events include
+    /// the events directly attached to `span`.
+    ///
+    /// This is useful to get all events related to a certain operation, e.g., processing
+    /// an HTTP request.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `span` does not belong to this storage.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+    /// # use tracing_capture::{CaptureLayer, SharedStorage};
+    /// let storage = SharedStorage::default();
+    /// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     for id in 0..3 {
+    ///         let _entered = tracing::info_span!("request", id).entered();
+    ///         tracing::info!("received request");
+    ///         tracing::debug_span!("db_query").in_scope(|| {
+    ///             tracing::debug!(rows = 42, "queried database");
+    ///         });
+    ///     }
+    /// });
+    ///
+    /// let storage = storage.lock();
+    /// let request_span = storage.root_spans().nth(1).unwrap();
+    /// let messages: Vec<_> = storage
+    ///     .events_under(&request_span)
+    ///     .filter_map(|event| event.message())
+    ///     .collect();
+    /// assert_eq!(messages, ["received request", "queried database"]);
+    /// ```
+    pub fn events_under<'s>(
+        &'s self,
+        span: &CapturedSpan<'_>,
+    ) -> impl Iterator<Item = CapturedEvent<'s>> + 's {
+        assert!(
+            ptr::eq(span.storage, self),
+            "span belongs to a different storage"
+        );
+        let span_ids: HashSet<_> = iter::once(span.inner.id)
+            .chain(span.descendants().map(|span| span.inner.id))
+            .collect();
+        self.all_events().filter(move |event| {
+            event
+                .inner
+                .parent_id
+                .is_some_and(|parent_id| span_ids.contains(&parent_id))
+        })
+    }
+
+    /// Asserts that every span matching `child` has an [ancestor](CapturedSpan::ancestors())
+    /// matching `parent`. This allows encoding invariants like "all database queries are
+    /// performed within a request span" in a single call. If no spans match `child`,
+    /// the assertion trivially holds.
+    ///
+    /// # Panics
+    ///
+    /// Panics with an informative message if a span matching `child` has no ancestors
+    /// matching `parent`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use predicates::ord::eq;
+    /// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+    /// # use tracing_capture::{predicates::name, CaptureLayer, SharedStorage};
+    /// let storage = SharedStorage::default();
+    /// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     let _entered = tracing::info_span!("request").entered();
+    ///     tracing::debug_span!("handler").in_scope(|| {
+    ///         tracing::debug_span!("db_query").in_scope(|| { /* do nothing */ });
+    ///     });
+    /// });
+    ///
+    /// let storage = storage.lock();
+    /// storage.assert_relation(&name(eq("request")), &name(eq("db_query")));
+    /// storage.assert_contains_child(&name(eq("request")), &name(eq("db_query")));
+    /// ```
+    #[track_caller]
+    pub fn assert_relation<P, C>(&self, parent: &P, child: &C)
+    where
+        P: for<'a> Predicate<CapturedSpan<'a>> + ?Sized,
+        C: for<'a> Predicate<CapturedSpan<'a>> + ?Sized,
+    {
+        let orphan = self.all_spans().find(|span| {
+            child.eval(span) && !span.ancestors().any(|ancestor| parent.eval(&ancestor))
+        });
+        if let Some(span) = orphan {
+            panic!("span matching {child} has no ancestor matching {parent}: {span:#?}");
+        }
+    }
+
+    /// Asserts that every span matching `parent` has a [descendant](CapturedSpan::descendants())
+    /// matching `child`. This is the inverse of [`Self::assert_relation()`]. If no spans
+    /// match `parent`, the assertion trivially holds.
+    ///
+    /// # Panics
+    ///
+    /// Panics with an informative message if a span matching `parent` has no descendants
+    /// matching `child`.
+    ///
+    /// # Examples
+    ///
+    /// See [`Self::assert_relation()`].
+    #[track_caller]
+    pub fn assert_contains_child<P, C>(&self, parent: &P, child: &C)
+    where
+        P: for<'a> Predicate<CapturedSpan<'a>> + ?Sized,
+        C: for<'a> Predicate<CapturedSpan<'a>> + ?Sized,
+    {
+        let childless = self.all_spans().find(|span| {
+            parent.eval(span) && !span.descendants().any(|descendant| child.eval(&descendant))
+        });
+        if let Some(span) = childless {
+            panic!("span matching {parent} has no descendant matching {child}: {span:#?}");
+        }
+    }
+
     pub(crate) fn push_span(
         &mut self,
         metadata: &'static Metadata<'static>,
         values: TracedValues<&'static str>,
         parent_id: Option<CapturedSpanId>,
     ) -> CapturedSpanId {
+        self.count_call_site(metadata);
+        let mut values = CapturedValues::from(values);
+        self.strings.intern_values(&mut values);
+        self.alloc_span(metadata, values, parent_id)
+    }
+
+    fn alloc_span(
+        &mut self,
+        metadata: &'static Metadata<'static>,
+        values: CapturedValues,
+        parent_id: Option<CapturedSpanId>,
+    ) -> CapturedSpanId {
+        #[cfg(feature = "perf")]
+        self.perf.on_allocation(
+            mem::size_of::<CapturedSpanInner>() + MemoryUsage::values_bytes(values.iter()),
+        );
+        let parent_id = parent_id.filter(|&id| self.contains_span(id));
+        let (child_capacity, event_capacity) = self.span_capacity;
+        let created_at = self.now();
         let span_id = self.spans.alloc_with_id(|id| CapturedSpanInner {
             metadata,
             values,
             stats: SpanStats::default(),
+            created_at,
+            created_seq: self.timeline.len(),
+            closed_at: None,
+            closed_seq: None,
+            entries: vec![],
+            recorded_fields: vec![],
+            overwritten_values: CapturedValues::default(),
+            extension_values: CapturedValues::default(),
+            is_placeholder: false,
+            dispatcher: self.active_dispatcher,
+            name_field: self.name_field,
             id,
             parent_id,
-            child_ids: vec![],
-            event_ids: vec![],
+            child_ids: Vec::with_capacity(child_capacity),
+            event_ids: Vec::with_capacity(event_capacity),
+            follows_from_ids: vec![],
         });
+        self.timeline.push(TimelineEntry::SpanCreated(span_id));
         if let Some(parent_id) = parent_id {
             let span = self.spans.get_mut(parent_id).unwrap();
             span.child_ids.push(span_id);
@@ -104,41 +895,150 @@ impl Storage {
         span_id
     }
 
-    fn on_span_enter(&mut self, id: CapturedSpanId) {
-        let span = self.spans.get_mut(id).unwrap();
+    pub(crate) fn push_placeholder_span(
+        &mut self,
+        metadata: &'static Metadata<'static>,
+        parent_id: Option<CapturedSpanId>,
+    ) -> CapturedSpanId {
+        let span_id = self.alloc_span(metadata, CapturedValues::default(), parent_id);
+        self.spans.get_mut(span_id).unwrap().is_placeholder = true;
+        span_id
+    }
+
+    pub(crate) fn on_span_enter(&mut self, id: CapturedSpanId) {
+        let thread = self.current_thread();
+        let now = self.now();
+        let Some(span) = self.spans.get_mut(id) else {
+            return; // the span was captured before the storage was cleared
+        };
         span.stats.entered += 1;
+        span.entries.push(SpanEntry {
+            entered_at: now,
+            exited_at: None,
+            thread,
+            entered_seq: self.timeline.len(),
+            exited_seq: None,
+        });
+        self.timeline.push(TimelineEntry::SpanEntered(id));
     }
 
-    fn on_span_exit(&mut self, id: CapturedSpanId) {
-        let span = self.spans.get_mut(id).unwrap();
+    pub(crate) fn on_span_exit(&mut self, id: CapturedSpanId) {
+        let thread = self.current_thread();
+        let now = self.now();
+        let Some(span) = self.spans.get_mut(id) else {
+            return; // the span was captured before the storage was cleared
+        };
         span.stats.exited += 1;
+        let entry = span
+            .entries
+            .iter_mut()
+            .rev()
+            .find(|entry| entry.thread == thread && entry.exited_at.is_none());
+        if let Some(entry) = entry {
+            entry.exited_at = Some(now);
+            entry.exited_seq = Some(self.timeline.len());
+        }
+        self.timeline.push(TimelineEntry::SpanExited(id));
     }
 
-    fn on_span_closed(&mut self, id: CapturedSpanId) {
-        let span = self.spans.get_mut(id).unwrap();
+    pub(crate) fn on_span_closed(&mut self, id: CapturedSpanId) {
+        let now = self.now();
+        let Some(span) = self.spans.get_mut(id) else {
+            return; // the span was captured before the storage was cleared
+        };
         span.stats.is_closed = true;
+        span.closed_at = Some(now);
+        span.closed_seq = Some(self.timeline.len());
+        self.timeline.push(TimelineEntry::SpanClosed(id));
     }
 
-    fn on_record(&mut self, id: CapturedSpanId, values: TracedValues<&'static str>) {
+    pub(crate) fn on_record(&mut self, id: CapturedSpanId, values: TracedValues<&'static str>) {
+        let Some(span) = self.spans.get(id) else {
+            return; // the span was captured before the storage was cleared
+        };
+        if span.is_placeholder {
+            return;
+        }
+        #[cfg(feature = "perf")]
+        self.perf
+            .on_allocation(MemoryUsage::values_bytes(values.iter()));
+        let mut values = CapturedValues::from(values);
+        self.strings.intern_values(&mut values);
         let span = self.spans.get_mut(id).unwrap();
+        for (name, value) in values.iter_stored() {
+            if !span.recorded_fields.contains(&name) {
+                span.recorded_fields.push(name);
+                if let Some(initial_value) = span.values.get_stored(name) {
+                    let initial_value = initial_value.clone();
+                    span.overwritten_values.insert_stored(name, initial_value);
+                }
+            }
+            let value_idx = self.recorded_values.len();
+            self.recorded_values.push(value.clone());
+            self.timeline
+                .push(TimelineEntry::ValueRecorded(id, name, value_idx));
+        }
         span.values.extend(values);
     }
 
+    fn on_extensions_extracted(&mut self, id: CapturedSpanId, values: TracedValues<&'static str>) {
+        let mut values = CapturedValues::from(values);
+        self.strings.intern_values(&mut values);
+        let Some(span) = self.spans.get_mut(id) else {
+            return; // the span was captured before the storage was cleared
+        };
+        span.extension_values.extend(values);
+    }
+
+    pub(crate) fn on_follows_from(&mut self, id: CapturedSpanId, follows_from: CapturedSpanId) {
+        if !self.contains_span(follows_from) {
+            return;
+        }
+        let Some(span) = self.spans.get_mut(id) else {
+            return; // the span was captured before the storage was cleared
+        };
+        if !span.follows_from_ids.contains(&follows_from) {
+            span.follows_from_ids.push(follows_from);
+        }
+        self.timeline
+            .push(TimelineEntry::FollowsFrom(id, follows_from));
+    }
+
     pub(crate) fn push_event(
         &mut self,
         metadata: &'static Metadata<'static>,
-        values: TracedValues<&'static str>,
+        mut values: CapturedValues,
         parent_id: Option<CapturedSpanId>,
     ) -> CapturedEventId {
+        self.strings.intern_values(&mut values);
+        #[cfg(feature = "perf")]
+        self.perf.on_allocation(
+            mem::size_of::<CapturedEventInner>() + MemoryUsage::values_bytes(values.iter()),
+        );
+        self.count_call_site(metadata);
+        let thread = self.current_thread();
+        let timestamp = self.now();
+        let parent_id = parent_id.filter(|&id| self.contains_span(id));
         let event_id = self.events.alloc_with_id(|id| CapturedEventInner {
             metadata,
             values,
+            timestamp,
+            seq: self.timeline.len(),
+            thread,
+            dispatcher: self.active_dispatcher,
             id,
             parent_id,
         });
+        self.timeline.push(TimelineEntry::EventEmitted(event_id));
         if let Some(parent_id) = parent_id {
             let span = self.spans.get_mut(parent_id).unwrap();
             span.event_ids.push(event_id);
+            if self
+                .max_events_per_span
+                .is_some_and(|max| span.event_ids.len() == max + 1)
+            {
+                self.span_ids_exceeding_event_limit.push(parent_id);
+            }
         } else {
             self.root_event_ids.push(event_id);
         }
@@ -146,22 +1046,214 @@ impl Storage {
     }
 }
 
+/// Opaque identifier of a [`Dispatch`] that a [`CaptureLayer`] is registered with.
+/// Returned by [`Storage::dispatchers()`].
+///
+/// IDs are unique within the process. A layer obtains its ID when the subscriber containing it
+/// is converted into a [`Dispatch`] (e.g., in [`with_default()`](tracing::subscriber::with_default())).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DispatcherId(u64);
+
+impl DispatcherId {
+    fn new() -> Self {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+        Self(NEXT_ID.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+/// Usage statistics for a call site of captured spans or events.
+/// Returned by [`Storage::call_sites()`].
+#[derive(Debug, Clone, Copy)]
+#[non_exhaustive]
+pub struct CallSiteStats {
+    /// Call site metadata.
+    pub metadata: &'static Metadata<'static>,
+    /// Number of spans (for span call sites) or events (for event call sites) captured
+    /// from the call site.
+    pub count: usize,
+}
+
+/// Approximate memory usage of a [`Storage`]. Returned by [`Storage::memory_usage()`].
+///
+/// Memory usage is estimated based on the lengths of collections rather than their capacities,
+/// and does not include memory occupied by [`Metadata`] (which is static and is not duplicated
+/// across captured spans / events). [String values](TracedValue::String) are interned
+/// by the storage, i.e., equal strings in different values share the same allocation;
+/// such strings are counted once.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct MemoryUsage {
+    /// Number of captured spans.
+    pub spans: usize,
+    /// Number of captured events.
+    pub events: usize,
+    /// Total number of values recorded in spans and events.
+    pub values: usize,
+    /// Number of distinct interned [string values](TracedValue::String).
+    pub interned_strings: usize,
+    /// Bytes occupied by string data in values: interned strings, [`Debug`](fmt::Debug)
+    /// representations of objects, byte slices, and error messages.
+    pub string_bytes: usize,
+    /// Total bytes occupied by the storage, including [`Self::string_bytes`].
+    pub total_bytes: usize,
+}
+
+impl MemoryUsage {
+    #[cfg(feature = "perf")]
+    fn values_bytes<'a>(
+        values: impl ExactSizeIterator<Item = (&'a str, &'a TracedValue)>,
+    ) -> usize {
+        let mut usage = Self {
+            total_bytes: values.len() * mem::size_of::<(&str, StoredValue)>(),
+            ..Self::default()
+        };
+        for (_, value) in values {
+            usage.add_value(value);
+        }
+        usage.total_bytes
+    }
+
+    fn add_values(&mut self, values: &CapturedValues) {
+        self.values += values.len();
+        self.total_bytes += values.len() * mem::size_of::<(&str, StoredValue)>();
+        // Interned strings are accounted for separately, since they are shared among values.
+        for value in values.owned_values() {
+            self.add_value(value);
+        }
+    }
+
+    fn add_value(&mut self, value: &TracedValue) {
+        let string_bytes = match value {
+            TracedValue::String(s) => s.len(),
+            TracedValue::Object(obj) => obj.as_ref().len(),
+            TracedValue::Bytes(bytes) => bytes.len(),
+            TracedValue::Error(err) => {
+                let mut bytes = 0;
+                let mut err = Some(err);
+                while let Some(inner) = err {
+                    bytes += inner.message.len();
+                    err = inner.source.as_deref();
+                    if let Some(source) = err {
+                        // Error sources are boxed; the box contents are not string data.
+                        self.total_bytes += mem::size_of_val(source);
+                    }
+                }
+                bytes
+            }
+            _ => 0,
+        };
+        self.string_bytes += string_bytes;
+        self.total_bytes += string_bytes;
+    }
+}
+
+/// Action taken if the [`Storage`] contains [open spans](Storage::open_spans()) when
+/// the last [`SharedStorage`] handle is dropped. Set via [`CaptureLayer::with_open_spans_check()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum OpenSpansCheck {
+    /// Print the list of open spans to stderr.
+    Log,
+    /// Panic with the list of open spans. If the thread is already panicking,
+    /// the open spans are logged instead.
+    Panic,
+}
+
+/// Performs the open spans check once all [`SharedStorage`] handles are dropped.
+#[derive(Debug)]
+struct StorageGuard {
+    storage: Arc<RwLock<Storage>>,
+}
+
+impl Drop for StorageGuard {
+    fn drop(&mut self) {
+        let Ok(storage) = self.storage.read() else {
+            return; // The storage is poisoned; the panic is already reported.
+        };
+        let Some(check) = storage.open_spans_check else {
+            return;
+        };
+        let open_spans: Vec<_> = storage
+            .open_spans()
+            .map(|span| {
+                let metadata = span.metadata();
+                format!("{}::{}", metadata.target(), metadata.name())
+            })
+            .collect();
+        drop(storage);
+        if open_spans.is_empty() {
+            return;
+        }
+
+        let message = format!(
+            "{} captured span(s) are not closed: {}",
+            open_spans.len(),
+            open_spans.join(", ")
+        );
+        if check == OpenSpansCheck::Panic && !thread::panicking() {
+            panic!("{message}");
+        } else {
+            eprintln!("{message}");
+        }
+    }
+}
+
 /// Shared wrapper for tracing [`Storage`].
 #[derive(Debug, Clone)]
 pub struct SharedStorage {
     inner: Arc<RwLock<Storage>>,
+    partitions: Arc<Partitions>,
+    // Unlike `inner`, not shared with `CaptureLayer`s, so that leaked spans (which keep
+    // the subscriber and thus the layer alive) do not prevent the open spans check.
+    _guard: Arc<StorageGuard>,
 }
 
 impl Default for SharedStorage {
     fn default() -> Self {
-        Self {
-            inner: Arc::new(RwLock::new(Storage::new())),
-        }
+        Self::new(Storage::new())
     }
 }
 
 #[allow(clippy::missing_panics_doc)] // lock poisoning propagation
 impl SharedStorage {
+    fn new(storage: Storage) -> Self {
+        let inner = Arc::new(RwLock::new(storage));
+        Self {
+            _guard: Arc::new(StorageGuard {
+                storage: Arc::clone(&inner),
+            }),
+            inner,
+            partitions: Arc::default(),
+        }
+    }
+
+    /// Creates a storage with pre-allocated space for the specified number of spans
+    /// and events. This avoids reallocations while capturing, which can be useful
+    /// if the amount of captured data is known in advance (e.g., in benchmarks).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_subscriber::layer::SubscriberExt;
+    /// # use tracing_capture::{CaptureLayer, SharedStorage};
+    /// let storage = SharedStorage::with_capacity(1_000, 1_000_000)
+    ///     .with_span_capacity(0, 1_000);
+    /// let subscriber = tracing_subscriber::registry()
+    ///     .with(CaptureLayer::new(&storage));
+    /// // Capture tracing data...
+    /// ```
+    pub fn with_capacity(spans: usize, events: usize) -> Self {
+        Self::new(Storage::with_capacity(spans, events))
+    }
+
+    /// Sets the initial capacity of child span and event lists for each captured span.
+    /// By default, these lists are allocated lazily.
+    #[must_use]
+    pub fn with_span_capacity(self, child_spans: usize, events: usize) -> Self {
+        lock_for_write(&self.inner).span_capacity = (child_spans, events);
+        self
+    }
+
     /// Locks the underlying [`Storage`] for exclusive access. While the lock is held,
     /// capturing cannot progress; beware of deadlocks!
     pub fn lock(&self) -> impl ops::Deref<Target = Storage> + '_ {
@@ -169,37 +1261,478 @@ impl SharedStorage {
             .read()
             .expect("failed accessing shared tracing data storage")
     }
-}
 
-/// Tracing [`Layer`] that captures (optionally filtered) spans and events.
-///
-/// The layer can optionally filter spans and events in addition to global [`Subscriber`] filtering.
-/// This could be used instead of per-layer filtering if it's not supported by the `Subscriber`.
-/// Keep in mind that without filtering, `CaptureLayer` can capture a lot of
-/// unnecessary spans / events.
-///
-/// Captured events are [tied](CapturedSpan::events()) to the nearest captured span
-/// in the span hierarchy. If no entered spans are captured when the event is emitted,
-/// the event will be captured in [`Storage::root_events()`].
-///
-/// # Examples
-///
-/// See [crate-level docs](index.html) for an example of usage.
-pub struct CaptureLayer<S> {
-    filter: Option<Box<dyn Filter<S> + Send + Sync>>,
-    storage: Arc<RwLock<Storage>>,
-}
+    /// Clears the underlying [`Storage`]. See [`Storage::clear()`] for details.
+    pub fn clear(&self) {
+        lock_for_write(&self.inner).clear();
+    }
+
+    /// Creates an immutable snapshot of the underlying [`Storage`]. Unlike with [`Self::lock()`],
+    /// the storage is locked only while copying captured data, so that the snapshot can be
+    /// inspected concurrently with ongoing capture, sent to other threads, or held across
+    /// `.await` points. Spans and events in the snapshot can be referenced by owned handles,
+    /// [`OwnedCapturedSpan`] and [`OwnedCapturedEvent`].
+    ///
+    /// Data captured after the snapshot is created is not reflected in it.
+    ///
+    /// [`OwnedCapturedSpan`]: crate::OwnedCapturedSpan
+    /// [`OwnedCapturedEvent`]: crate::OwnedCapturedEvent
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+    /// # use tracing_capture::{CaptureLayer, SharedStorage};
+    /// let storage = SharedStorage::default();
+    /// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    /// let _guard = tracing::subscriber::set_default(subscriber);
+    /// tracing::info!("before snapshot");
+    /// let snapshot = storage.snapshot();
+    /// tracing::info!("after snapshot"); // does not deadlock
+    ///
+    /// assert_eq!(snapshot.all_events().len(), 1);
+    /// assert_eq!(storage.lock().all_events().len(), 2);
+    /// ```
+    pub fn snapshot(&self) -> Arc<Storage> {
+        Arc::new(self.lock().clone())
+    }
+
+    /// Returns the partition of this storage with the specified key, creating it if necessary.
+    /// Spans and events are routed into partitions by a [partitioning function]
+    /// set for the capturing layer. Partitions are independent from this storage and each other;
+    /// e.g., [clearing](Self::clear()) this storage does not affect partitions.
+    ///
+    /// Partitions are shared among this storage and all its partitions; i.e., calling this method
+    /// on a partition returns a partition of the original storage.
+    ///
+    /// [partitioning function]: CaptureLayer::partition_by()
+    ///
+    /// # Examples
+    ///
+    /// See [`CaptureLayer::partition_by()`].
+    #[must_use]
+    pub fn partition(&self, key: &str) -> Self {
+        let mut partitions = lock_partitions(&self.partitions);
+        let partition = partitions.entry(key.to_owned()).or_default();
+        let guard = partition.guard.upgrade().unwrap_or_else(|| {
+            let guard = Arc::new(StorageGuard {
+                storage: Arc::clone(&partition.storage),
+            });
+            partition.guard = Arc::downgrade(&guard);
+            guard
+        });
+        Self {
+            inner: Arc::clone(&partition.storage),
+            partitions: Arc::clone(&self.partitions),
+            _guard: guard,
+        }
+    }
+
+    /// Returns keys of all partitions of this storage in no particular order. This includes
+    /// partitions created by the capturing layer and ones [accessed](Self::partition())
+    /// explicitly.
+    pub fn partition_keys(&self) -> Vec<String> {
+        lock_partitions(&self.partitions).keys().cloned().collect()
+    }
+
+    /// Binds this storage to the current thread, so that [per-thread](CaptureLayer::per_thread())
+    /// capture layers will capture root spans and events emitted on this thread into it.
+    /// The binding is active until the returned guard is dropped; after that,
+    /// the previously bound storage (if any) is restored.
+    ///
+    /// # Examples
+    ///
+    /// See [`CaptureLayer::per_thread()`].
+    pub fn bind_to_current_thread(&self) -> ThreadBinding {
+        let prev =
+            THREAD_STORAGE.with(|storage| storage.borrow_mut().replace(Arc::clone(&self.inner)));
+        ThreadBinding {
+            prev,
+            _not_send: PhantomData,
+        }
+    }
+}
+
+/// Guard binding a [`SharedStorage`] to the current thread. Returned by
+/// [`SharedStorage::bind_to_current_thread()`].
+#[derive(Debug)]
+#[must_use = "storage is unbound from the thread when the guard is dropped"]
+pub struct ThreadBinding {
+    prev: Option<Arc<RwLock<Storage>>>,
+    // The guard must be dropped on the thread it was created on.
+    _not_send: PhantomData<*const ()>,
+}
+
+impl Drop for ThreadBinding {
+    fn drop(&mut self) {
+        let prev = self.prev.take();
+        THREAD_STORAGE
+            .try_with(|storage| *storage.borrow_mut() = prev)
+            .ok(); // the thread-local may be destroyed if the guard is dropped on thread exit
+    }
+}
+
+/// Reference to a captured span stored in the span extensions.
+#[derive(Debug, Clone)]
+struct CapturedSpanRef {
+    id: CapturedSpanId,
+    storage: Arc<RwLock<Storage>>,
+    /// Storage of the layer that captured the span. Differs from `storage` if the span
+    /// is captured into a partition.
+    origin: Arc<RwLock<Storage>>,
+}
+
+/// Location of a new span or event in the captured data.
+struct CaptureTarget {
+    storage: Arc<RwLock<Storage>>,
+    origin: Arc<RwLock<Storage>>,
+    parent_id: Option<CapturedSpanId>,
+}
+
+/// Event without a captured parent span passed to the [parent inference hook].
+///
+/// [parent inference hook]: CaptureLayer::with_parent_inference()
+#[derive(Debug)]
+pub struct OrphanEvent<'a> {
+    metadata: &'static Metadata<'static>,
+    values: &'a CapturedValues,
+}
+
+impl<'a> OrphanEvent<'a> {
+    /// Provides a reference to the event metadata.
+    pub fn metadata(&self) -> &'static Metadata<'static> {
+        self.metadata
+    }
+
+    /// Iterates over values associated with the event. Values are already
+    /// [redacted](CaptureLayer::with_redaction()) and [truncated](CaptureLayer::with_max_value_len())
+    /// if necessary.
+    pub fn values(&self) -> impl Iterator<Item = (&'a str, &'a TracedValue)> + 'a {
+        self.values.iter()
+    }
+
+    /// Returns a value for the specified field, or `None` if the value is not defined.
+    pub fn value(&self, name: &str) -> Option<&'a TracedValue> {
+        self.values.get(name)
+    }
+}
+
+/// Root span of a captured subtree passed to the [partitioning function].
+///
+/// [partitioning function]: CaptureLayer::partition_by()
+#[derive(Debug)]
+pub struct RootSpan<'a> {
+    metadata: &'static Metadata<'static>,
+    values: &'a TracedValues<&'static str>,
+}
+
+impl<'a> RootSpan<'a> {
+    /// Provides a reference to the span metadata.
+    pub fn metadata(&self) -> &'static Metadata<'static> {
+        self.metadata
+    }
+
+    /// Iterates over values associated with the span. Values are already
+    /// [redacted](CaptureLayer::with_redaction()) and [truncated](CaptureLayer::with_max_value_len())
+    /// if necessary.
+    pub fn values(&self) -> impl Iterator<Item = (&'a str, &'a TracedValue)> + 'a {
+        self.values.iter()
+    }
+
+    /// Returns a value for the specified field, or `None` if the value is not defined.
+    pub fn value(&self, name: &str) -> Option<&'a TracedValue> {
+        self.values.get(name)
+    }
+}
+
+/// Owned snapshot of a captured span passed to [close hooks] once the span is closed.
+///
+/// [close hooks]: CaptureLayer::with_close_hook()
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct ClosedSpan {
+    /// Span metadata.
+    pub metadata: &'static Metadata<'static>,
+    /// Values that the span was created with, or which were recorded later.
+    pub values: TracedValues<&'static str>,
+    /// Span statistics at the time of closing.
+    pub stats: SpanStats,
+    /// Instant when the span was created.
+    pub created_at: Instant,
+    /// Instant when the span was closed.
+    pub closed_at: Instant,
+    /// Number of events directly attached to the span.
+    pub event_count: usize,
+    /// Metadata of the parent span, if any.
+    pub parent_metadata: Option<&'static Metadata<'static>>,
+}
+
+impl ClosedSpan {
+    fn new(span: CapturedSpan<'_>) -> Self {
+        Self {
+            metadata: span.metadata(),
+            values: span.inner.values.to_traced_values(),
+            stats: span.stats(),
+            created_at: span.created_at(),
+            closed_at: span.closed_at().expect("span is not closed"),
+            event_count: span.events().len(),
+            parent_metadata: span.parent().map(|parent| parent.metadata()),
+        }
+    }
+
+    /// Returns the span lifetime, i.e., the duration between its creation and closing.
+    pub fn duration(&self) -> Duration {
+        self.closed_at.duration_since(self.created_at)
+    }
+}
+
+/// Handle allowing to change the maximum level of spans and events captured by
+/// a [`CaptureLayer`] at runtime. Returned by [`CaptureLayer::handle()`].
+///
+/// The handle can also be used as a [per-layer filter](Layer::with_filter()) for the layer,
+/// in which case it reports the captured level as its [level hint](Filter::max_level_hint()).
+/// Changing the level [rebuilds](callsite::rebuild_interest_cache()) the interest cache
+/// for all call sites, so that the hint is re-evaluated by the subscriber.
+///
+/// # Examples
+///
+/// ```
+/// # use tracing_core::{Level, LevelFilter};
+/// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+/// # use tracing_capture::{CaptureLayer, SharedStorage};
+/// let storage = SharedStorage::default();
+/// let layer = CaptureLayer::new(&storage).with_max_level(Level::INFO);
+/// let handle = layer.handle();
+/// let subscriber = Registry::default().with(layer);
+/// tracing::subscriber::with_default(subscriber, || {
+///     tracing::debug!("not captured");
+///     // Capture debug events only during failure injection.
+///     handle.set_level(Level::DEBUG);
+///     tracing::debug!("injecting failure");
+///     handle.set_level(Level::INFO);
+///     tracing::debug!("not captured either");
+/// });
+///
+/// let storage = storage.lock();
+/// let messages: Vec<_> = storage.all_events().filter_map(|event| event.message()).collect();
+/// assert_eq!(messages, ["injecting failure"]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct CaptureHandle {
+    level: Arc<AtomicU8>,
+}
+
+impl CaptureHandle {
+    fn new(level: LevelFilter) -> Self {
+        Self {
+            level: Arc::new(AtomicU8::new(Self::encode_level(level))),
+        }
+    }
+
+    fn encode_level(level: LevelFilter) -> u8 {
+        match level.into_level() {
+            None => 0,
+            Some(Level::ERROR) => 1,
+            Some(Level::WARN) => 2,
+            Some(Level::INFO) => 3,
+            Some(Level::DEBUG) => 4,
+            Some(Level::TRACE) => 5,
+        }
+    }
+
+    /// Returns the current maximum captured level.
+    pub fn level(&self) -> LevelFilter {
+        match self.level.load(Ordering::Acquire) {
+            0 => LevelFilter::OFF,
+            1 => LevelFilter::ERROR,
+            2 => LevelFilter::WARN,
+            3 => LevelFilter::INFO,
+            4 => LevelFilter::DEBUG,
+            _ => LevelFilter::TRACE,
+        }
+    }
+
+    /// Sets the maximum captured level. Spans and events more verbose than `level`
+    /// will not be captured.
+    pub fn set_level(&self, level: impl Into<LevelFilter>) {
+        let level = Self::encode_level(level.into());
+        if self.level.swap(level, Ordering::AcqRel) != level {
+            callsite::rebuild_interest_cache();
+        }
+    }
+}
+
+/// Filters spans and events by the captured level. Unlike the level check performed
+/// by [`CaptureLayer`] itself, this filter reports the level as its hint.
+impl<S: Subscriber> Filter<S> for CaptureHandle {
+    fn enabled(&self, metadata: &Metadata<'_>, _ctx: &Context<'_, S>) -> bool {
+        *metadata.level() <= self.level()
+    }
+
+    fn max_level_hint(&self) -> Option<LevelFilter> {
+        Some(self.level())
+    }
+}
+
+/// Tracing [`Layer`] that captures (optionally filtered) spans and events.
+///
+/// The layer can optionally filter spans and events in addition to global [`Subscriber`] filtering.
+/// This could be used instead of per-layer filtering if it's not supported by the `Subscriber`.
+/// Keep in mind that without filtering, `CaptureLayer` can capture a lot of
+/// unnecessary spans / events.
+///
+/// Captured events are [tied](CapturedSpan::events()) to the nearest captured span
+/// in the span hierarchy. If no entered spans are captured when the event is emitted,
+/// the event will be captured in [`Storage::root_events()`], unless its parent is inferred
+/// using a [parent inference hook](Self::with_parent_inference()).
+///
+/// Captured values can be [redacted](Self::with_redaction()) and / or
+/// [truncated](Self::with_max_value_len()) before they are put into the storage, e.g.,
+/// to prevent secrets from leaking into CI logs or snapshot files.
+///
+/// # Dynamic level
+///
+/// The maximum captured level can be [set](Self::with_max_level()) for the layer and changed
+/// at runtime via a [`CaptureHandle`], e.g., to capture verbose events only during a certain
+/// phase of a test. The layer itself does not report a [level hint](Layer::max_level_hint()),
+/// since the hint would make the subscriber skip verbose spans and events for all layers,
+/// not just for `CaptureLayer`. To let the subscriber skip spans and events that
+/// are not captured, use the handle as a per-layer filter:
+///
+/// ```
+/// # use tracing_core::Level;
+/// # use tracing_subscriber::{layer::SubscriberExt, Layer, Registry};
+/// # use tracing_capture::{CaptureLayer, SharedStorage};
+/// let storage = SharedStorage::default();
+/// let layer = CaptureLayer::new(&storage).with_max_level(Level::INFO);
+/// let handle = layer.handle();
+/// let subscriber = Registry::default().with(Layer::with_filter(layer, handle));
+/// # drop(subscriber);
+/// ```
+///
+/// # Per-thread capturing
+///
+/// A layer created with [`Self::new()`] captures all spans and events into a single storage.
+/// This is problematic if the layer is installed globally (e.g., using
+/// [`set_global_default()`]) and tests are run in parallel, since spans and events from
+/// concurrently running tests would bleed into each other's storage. To isolate tests,
+/// use a layer created with [`Self::per_thread()`].
+///
+/// [`set_global_default()`]: tracing_core::dispatcher::set_global_default()
+///
+/// # Allocations
+///
+/// By default, capturing a span or event allocates heap memory, e.g. to store its values.
+/// This may interfere with tests asserting that a certain code path does not allocate.
+/// With the `no-alloc-hot-path` crate feature enabled, capturing an event
+/// does not allocate provided that:
+///
+/// - The event has no more than 8 values.
+/// - All event values are primitive (Booleans, integers or floating-point numbers).
+///   In particular, the event has no message, since messages are recorded
+///   as [`Debug`](fmt::Debug) objects.
+/// - The layer has no [redaction hook](Self::with_redaction()) that allocates.
+/// - The storage was [pre-allocated](SharedStorage::with_capacity()) with sufficient capacity,
+///   including the [capacity for events in the parent span](SharedStorage::with_span_capacity()).
+/// - An event from the same call site was captured on the current thread previously
+///   (e.g., as a warm-up), so that the call site and the thread are registered in the storage.
+///
+/// The feature increases the memory footprint of each captured event, since values
+/// are stored inline.
+///
+/// # Examples
+///
+/// See [crate-level docs](index.html) for an example of usage.
+pub struct CaptureLayer<S> {
+    filter: Option<Box<dyn Filter<S> + Send + Sync>>,
+    max_value_len: Option<usize>,
+    redaction: Option<Box<RedactionFn>>,
+    parent_inference: Option<Box<ParentInferenceFn>>,
+    extension_extractor: Option<Box<ExtensionExtractorFn>>,
+    close_hooks: Vec<Box<CloseHookFn>>,
+    open_spans_check: Option<OpenSpansCheck>,
+    max_events_per_span: Option<usize>,
+    name_field: Option<&'static str>,
+    clock: Option<SharedClock>,
+    missing_ancestors: bool,
+    capture_spans: bool,
+    capture_events: bool,
+    /// `None` means that the storage is determined per thread.
+    storage: Option<Arc<RwLock<Storage>>>,
+    /// `None` for per-thread layers.
+    partitions: Option<Arc<Partitions>>,
+    partition_by: Option<Box<PartitionFn>>,
+    dispatcher: OnceLock<DispatcherId>,
+    handle: CaptureHandle,
+}
 
 impl<S> fmt::Debug for CaptureLayer<S> {
     fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
         formatter
             .debug_struct("CaptureLayer")
             .field("filter", &self.filter.as_ref().map(|_| "Filter"))
+            .field("max_value_len", &self.max_value_len)
+            .field("redaction", &self.redaction.as_ref().map(|_| "Fn"))
+            .field(
+                "parent_inference",
+                &self.parent_inference.as_ref().map(|_| "Fn"),
+            )
+            .field(
+                "extension_extractor",
+                &self.extension_extractor.as_ref().map(|_| "Fn"),
+            )
+            .field("close_hooks", &self.close_hooks.len())
+            .field("open_spans_check", &self.open_spans_check)
+            .field("max_events_per_span", &self.max_events_per_span)
+            .field("name_field", &self.name_field)
+            .field("clock", &self.clock)
+            .field("missing_ancestors", &self.missing_ancestors)
+            .field("capture_spans", &self.capture_spans)
+            .field("capture_events", &self.capture_events)
             .field("storage", &self.storage)
+            .field("partitions", &self.partitions)
+            .field("partition_by", &self.partition_by.as_ref().map(|_| "Fn"))
+            .field("dispatcher", &self.dispatcher.get())
+            .field("level", &self.handle.level())
             .finish()
     }
 }
 
+/// Truncates `s` so that it's no longer than `max_len` bytes, and appends an ellipsis
+/// if the string was truncated.
+fn truncate_str(s: &mut String, max_len: usize) {
+    if s.len() > max_len {
+        let mut boundary = max_len;
+        while !s.is_char_boundary(boundary) {
+            boundary -= 1;
+        }
+        s.truncate(boundary);
+        s.push('…');
+    }
+}
+
+fn truncate_value(value: &mut TracedValue, max_len: usize) {
+    match value {
+        TracedValue::String(s) => truncate_str(s, max_len),
+        TracedValue::Object(obj) => {
+            let mut s = obj.as_ref().to_owned();
+            if s.len() > max_len {
+                truncate_str(&mut s, max_len);
+                *value = TracedValue::debug(&format_args!("{s}"));
+            }
+        }
+        TracedValue::Error(err) => {
+            let mut err = Some(err);
+            while let Some(inner) = err {
+                truncate_str(&mut inner.message, max_len);
+                err = inner.source.as_deref_mut();
+            }
+        }
+        TracedValue::Bytes(bytes) => bytes.truncate(max_len),
+        _ => { /* do nothing */ }
+    }
+}
+
 impl<S> CaptureLayer<S>
 where
     S: Subscriber + for<'a> LookupSpan<'a>,
@@ -208,9 +1741,77 @@ where
     /// Captured spans are not filtered; like any [`Layer`], filtering can be set up
     /// on the layer or subscriber level.
     pub fn new(storage: &SharedStorage) -> Self {
+        Self::with_storage(
+            Some(Arc::clone(&storage.inner)),
+            Some(Arc::clone(&storage.partitions)),
+        )
+    }
+
+    /// Creates a new layer that will capture data into the storage
+    /// [bound to the current thread](SharedStorage::bind_to_current_thread()).
+    ///
+    /// More precisely, a span or event is captured into the same storage as its nearest captured
+    /// ancestor span, which can be located on another thread (e.g., if a future instrumented
+    /// with a span is moved between threads). If there is no such ancestor, the span / event
+    /// is captured into the storage bound to the current thread. If no storage is bound,
+    /// the span / event is not captured.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+    /// # use tracing_capture::{CaptureLayer, SharedStorage};
+    /// # use std::thread;
+    /// // Usually, this will be done once, e.g., using `set_global_default()`.
+    /// let subscriber = Registry::default().with(CaptureLayer::per_thread());
+    /// let dispatch = tracing::Dispatch::new(subscriber);
+    ///
+    /// // In each test:
+    /// let test = |name: &'static str| {
+    ///     let storage = SharedStorage::default();
+    ///     tracing::dispatcher::with_default(&dispatch, || {
+    ///         let _binding = storage.bind_to_current_thread();
+    ///         tracing::info!(name, "test started");
+    ///     });
+    ///     tracing::info!("this event is not captured");
+    ///     let storage = storage.lock();
+    ///     assert_eq!(storage.all_events().len(), 1);
+    ///     assert_eq!(storage.all_events().next().unwrap()["name"], name);
+    /// };
+    /// thread::scope(|scope| {
+    ///     scope.spawn(|| test("first"));
+    ///     scope.spawn(|| test("second"));
+    /// });
+    /// ```
+    pub fn per_thread() -> Self {
+        Self::with_storage(None, None)
+    }
+
+    /// Creates a layer with the default configuration. If `storage` is `None`, the layer
+    /// captures data into the storage bound to the current thread.
+    fn with_storage(
+        storage: Option<Arc<RwLock<Storage>>>,
+        partitions: Option<Arc<Partitions>>,
+    ) -> Self {
         Self {
             filter: None,
-            storage: Arc::clone(&storage.inner),
+            max_value_len: None,
+            redaction: None,
+            parent_inference: None,
+            extension_extractor: None,
+            close_hooks: Vec::new(),
+            open_spans_check: None,
+            max_events_per_span: None,
+            name_field: None,
+            clock: None,
+            missing_ancestors: false,
+            capture_spans: true,
+            capture_events: true,
+            storage,
+            partitions,
+            partition_by: None,
+            dispatcher: OnceLock::new(),
+            handle: CaptureHandle::new(LevelFilter::TRACE),
         }
     }
 
@@ -227,16 +1828,693 @@ where
         self
     }
 
+    /// Sets the maximum level of captured spans and events. The level can be changed at runtime
+    /// using a [handle](Self::handle()). See [the type-level docs](#dynamic-level) for details.
+    #[must_use]
+    pub fn with_max_level(self, level: impl Into<LevelFilter>) -> Self {
+        self.handle.set_level(level);
+        self
+    }
+
+    /// Returns a handle allowing to change the maximum captured level at runtime.
+    /// See [`CaptureHandle`] for an example of usage.
+    pub fn handle(&self) -> CaptureHandle {
+        self.handle.clone()
+    }
+
+    /// Sets the maximum length (in bytes) of captured string values, [`Debug`](fmt::Debug)
+    /// representations of objects, and error messages. Longer values are truncated
+    /// (respecting UTF-8 char boundaries), and an ellipsis char `…` is appended to them.
+    /// [Byte values](TracedValue::Bytes) longer than `max_len` are truncated as well,
+    /// without any indication.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+    /// # use tracing_capture::{CaptureLayer, SharedStorage};
+    /// let storage = SharedStorage::default();
+    /// let layer = CaptureLayer::new(&storage).with_max_value_len(5);
+    /// let subscriber = Registry::default().with(layer);
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     tracing::info!(payload = "Hello, world!", "sent payload");
+    /// });
+    ///
+    /// let storage = storage.lock();
+    /// let event = storage.all_events().next().unwrap();
+    /// assert_eq!(event["payload"], "Hello…");
+    /// assert_eq!(event.message(), Some("sent …"));
+    /// ```
+    #[must_use]
+    pub fn with_max_value_len(mut self, max_len: usize) -> Self {
+        self.max_value_len = Some(max_len);
+        self
+    }
+
+    /// Specifies a redaction hook for this layer. The hook is called for each captured value
+    /// (including values recorded for spans after their creation) with the field name
+    /// and a mutable reference to the value, and may replace or modify the value.
+    /// Redaction is performed before [truncation](Self::with_max_value_len()).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+    /// # use tracing_capture::{CaptureLayer, SharedStorage};
+    /// # use tracing_tunnel::TracedValue;
+    /// let storage = SharedStorage::default();
+    /// let layer = CaptureLayer::new(&storage).with_redaction(|name, value| {
+    ///     if name == "password" {
+    ///         *value = TracedValue::from("[REDACTED]");
+    ///     }
+    /// });
+    /// let subscriber = Registry::default().with(layer);
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     tracing::info_span!("login", user = "alice", password = "123456").in_scope(|| {
+    ///         tracing::info!("logged in");
+    ///     });
+    /// });
+    ///
+    /// let storage = storage.lock();
+    /// let span = storage.all_spans().next().unwrap();
+    /// assert_eq!(span["user"], "alice");
+    /// assert_eq!(span["password"], "[REDACTED]");
+    /// ```
+    #[must_use]
+    pub fn with_redaction<F>(mut self, redaction: F) -> Self
+    where
+        F: Fn(&str, &mut TracedValue) + Send + Sync + 'static,
+    {
+        self.redaction = Some(Box::new(redaction));
+        self
+    }
+
+    /// Enables checking the [`Storage`] for [open spans](Storage::open_spans()) when the last
+    /// [`SharedStorage`] handle is dropped. This allows catching span leaks in tests,
+    /// e.g. ones caused by futures that were never completed or dropped. Layers do not count
+    /// as storage handles, since a leaked span keeps its subscriber (and thus the layer) alive.
+    ///
+    /// The check covers all spans in the storage, including ones captured by other layers
+    /// sharing the same storage. For [per-thread](Self::per_thread()) layers, the check
+    /// is enabled for each storage once a span is captured into it. By default, the check
+    /// is disabled.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the storage lock is poisoned.
+    ///
+    /// # Examples
+    ///
+    /// ```should_panic
+    /// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+    /// # use tracing_capture::{CaptureLayer, OpenSpansCheck, SharedStorage};
+    /// let storage = SharedStorage::default();
+    /// let layer = CaptureLayer::new(&storage).with_open_spans_check(OpenSpansCheck::Panic);
+    /// let subscriber = Registry::default().with(layer);
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     std::mem::forget(tracing::info_span!("leaked"));
+    /// });
+    /// drop(storage); // panics since the "leaked" span is not closed
+    /// ```
+    #[must_use]
+    pub fn with_open_spans_check(mut self, check: OpenSpansCheck) -> Self {
+        self.open_spans_check = Some(check);
+        if let Some(storage) = &self.storage {
+            lock_for_write(storage).open_spans_check = Some(check);
+        }
+        self
+    }
+
+    /// Sets the maximum number of events directly attached to a captured span. Spans exceeding
+    /// this limit are flagged and can be listed via [`Storage::spans_exceeding_event_limit()`].
+    /// This allows catching excessive logging in tests, e.g. logging on each iteration
+    /// of a hot loop. See also the [`event_count()`] predicate.
+    ///
+    /// [`event_count()`]: crate::predicates::event_count()
+    ///
+    /// # Examples
+    ///
+    /// See [`Storage::spans_exceeding_event_limit()`].
+    #[must_use]
+    pub fn with_max_events_per_span(mut self, max_events: usize) -> Self {
+        self.max_events_per_span = Some(max_events);
+        if let Some(storage) = &self.storage {
+            lock_for_write(storage).max_events_per_span = Some(max_events);
+        }
+        self
+    }
+
+    /// Sets the name of the field that overrides span names, e.g. `otel.name` used
+    /// by `tracing-opentelemetry` for dynamic naming. If a captured span has a string value
+    /// for this field (either provided on span creation or [recorded] later), the value is used
+    /// as the logical span name returned by [`CapturedSpan::name()`], and is matched
+    /// by the [`name()`] predicate and by the [`Query`] name filter. This allows assertions
+    /// to target the logical name rather than the static call site name.
+    ///
+    /// [recorded]: tracing::Span::record()
+    /// [`name()`]: crate::predicates::name()
+    /// [`Query`]: crate::Query
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use predicates::ord::eq;
+    /// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+    /// # use tracing_capture::{predicates::{name, ScanExt}, CaptureLayer, SharedStorage};
+    /// let storage = SharedStorage::default();
+    /// let layer = CaptureLayer::new(&storage).with_name_field("otel.name");
+    /// let subscriber = Registry::default().with(layer);
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     let span = tracing::info_span!("request", otel.name = tracing::field::Empty);
+    ///     span.record("otel.name", "GET /users");
+    /// });
+    ///
+    /// let storage = storage.lock();
+    /// let span = storage.scan_spans().single(&name(eq("GET /users")));
+    /// assert_eq!(span.name(), "GET /users");
+    /// assert_eq!(span.metadata().name(), "request");
+    /// ```
+    #[must_use]
+    pub fn with_name_field(mut self, field_name: &'static str) -> Self {
+        self.name_field = Some(field_name);
+        if let Some(storage) = &self.storage {
+            lock_for_write(storage).name_field = Some(field_name);
+        }
+        self
+    }
+
+    /// Sets the [`Clock`] used for timestamps of captured spans and events. By default,
+    /// [`Instant::now()`] is used. A custom clock allows getting deterministic
+    /// [span timings](SpanStats) in tests.
+    ///
+    /// Setting a clock resets the [creation instant](Storage::created_at()) of the storage
+    /// according to the clock; thus, the clock should be set before capturing anything.
+    ///
+    /// # Examples
+    ///
+    /// See [`MockClock`](crate::MockClock).
+    #[must_use]
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        let clock = SharedClock(Arc::new(clock));
+        if let Some(storage) = &self.storage {
+            lock_for_write(storage).set_clock(&clock);
+        }
+        self.clock = Some(clock);
+        self
+    }
+
+    /// Sets whether this layer captures spans. By default, spans are captured.
+    ///
+    /// If spans are not captured, the layer skips all span-related processing, which can be
+    /// used to reduce capture overhead in tests only concerned with events. Events are then
+    /// captured as [root events](Storage::root_events()) (unless their parent is inferred
+    /// with a [parent inference hook](Self::with_parent_inference())), and
+    /// [missing ancestors](Self::with_missing_ancestors()) are not recorded.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+    /// # use tracing_capture::{CaptureLayer, SharedStorage};
+    /// let storage = SharedStorage::default();
+    /// let layer = CaptureLayer::new(&storage).capture_spans(false);
+    /// let subscriber = Registry::default().with(layer);
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     tracing::info_span!("compute").in_scope(|| {
+    ///         tracing::info!(result = 42, "computed");
+    ///     });
+    /// });
+    ///
+    /// let storage = storage.lock();
+    /// assert_eq!(storage.all_spans().len(), 0);
+    /// assert_eq!(storage.root_events().len(), 1);
+    /// ```
+    #[must_use]
+    pub fn capture_spans(mut self, capture: bool) -> Self {
+        self.capture_spans = capture;
+        self
+    }
+
+    /// Sets whether this layer captures events. By default, events are captured.
+    ///
+    /// If events are not captured, the layer skips all event-related processing, which can be
+    /// used to reduce capture overhead in tests only concerned with the span structure.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+    /// # use tracing_capture::{CaptureLayer, SharedStorage};
+    /// let storage = SharedStorage::default();
+    /// let layer = CaptureLayer::new(&storage).capture_events(false);
+    /// let subscriber = Registry::default().with(layer);
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     tracing::info_span!("compute").in_scope(|| {
+    ///         tracing::info!(result = 42, "computed");
+    ///     });
+    /// });
+    ///
+    /// let storage = storage.lock();
+    /// let span = storage.all_spans().next().unwrap();
+    /// assert_eq!(span.metadata().name(), "compute");
+    /// assert_eq!(span.stats().entered, 1);
+    /// assert_eq!(storage.all_events().len(), 0);
+    /// ```
+    #[must_use]
+    pub fn capture_events(mut self, capture: bool) -> Self {
+        self.capture_events = capture;
+        self
+    }
+
+    /// Enables recording placeholders for ancestor spans that were not captured by this layer,
+    /// e.g., because they were rejected by the [layer filter](Self::with_filter()).
+    /// A placeholder retains the metadata of the original span, but not its values
+    /// (see [`CapturedSpan::is_placeholder()`]). As a result, [`CapturedSpan::parent()`],
+    /// [`CapturedSpan::ancestors()`] and structural predicates reflect the true span hierarchy.
+    /// Placeholders are created lazily, once a captured span or event has the corresponding
+    /// span as an ancestor. By default, placeholders are not recorded, and a captured span / event
+    /// is attached to its nearest captured ancestor.
+    ///
+    /// Only spans known to the [`Subscriber`] can be recorded as placeholders. In particular,
+    /// spans disabled globally and spans rejected by a [per-layer filter](Layer::with_filter())
+    /// wrapping this layer are invisible to the layer, and thus are not recorded.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_subscriber::{filter::filter_fn, layer::SubscriberExt, Registry};
+    /// # use tracing_capture::{CaptureLayer, SharedStorage};
+    /// let storage = SharedStorage::default();
+    /// let layer = CaptureLayer::new(&storage)
+    ///     .with_filter(filter_fn(|meta| meta.name() != "filtered"))
+    ///     .with_missing_ancestors();
+    /// let subscriber = Registry::default().with(layer);
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     let _outer = tracing::info_span!("outer").entered();
+    ///     let _filtered = tracing::info_span!("filtered", secret = 42).entered();
+    ///     tracing::info!("event");
+    /// });
+    ///
+    /// let storage = storage.lock();
+    /// let event = storage.all_events().next().unwrap();
+    /// let parent = event.parent().unwrap();
+    /// assert_eq!(parent.metadata().name(), "filtered");
+    /// assert!(parent.is_placeholder());
+    /// assert_eq!(parent.values().count(), 0);
+    /// assert_eq!(parent.parent().unwrap().metadata().name(), "outer");
+    /// ```
+    #[must_use]
+    pub fn with_missing_ancestors(mut self) -> Self {
+        self.missing_ancestors = true;
+        self
+    }
+
+    /// Sets a hook inferring the parent span for events that have no captured parent,
+    /// e.g. events emitted from spawned tasks that did not propagate the span context.
+    /// The hook is called with the event and the storage the event is captured into,
+    /// and may return a span from this storage that will be used as the parent of the event.
+    /// If the hook returns `None`, the event is captured as a [root event](Storage::root_events()).
+    ///
+    /// The hook is called while the storage is locked, so it should not emit tracing events
+    /// or access the [`SharedStorage`] itself.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+    /// # use tracing_capture::{CaptureLayer, SharedStorage};
+    /// # use tracing_tunnel::TracedValue;
+    /// let storage = SharedStorage::default();
+    /// let layer = CaptureLayer::new(&storage).with_parent_inference(|event, storage| {
+    ///     // Match events to request spans by the `request_id` field.
+    ///     let request_id = event.value("request_id")?.as_int()?;
+    ///     storage.all_spans().rev().find(|span| {
+    ///         span.metadata().name() == "request"
+    ///             && span.value("id").and_then(TracedValue::as_int) == Some(request_id)
+    ///     })
+    /// });
+    /// let subscriber = Registry::default().with(layer);
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     let _entered = tracing::info_span!("request", id = 42).entered();
+    ///     // Emulate a detached task that does not propagate the span context.
+    ///     tracing::info!(parent: None, request_id = 42, "processed in background");
+    /// });
+    ///
+    /// let storage = storage.lock();
+    /// let event = storage.all_events().next().unwrap();
+    /// assert_eq!(event.parent().unwrap().metadata().name(), "request");
+    /// assert_eq!(storage.root_events().len(), 0);
+    /// ```
+    #[must_use]
+    pub fn with_parent_inference<F>(mut self, infer: F) -> Self
+    where
+        F: for<'s> Fn(&OrphanEvent<'_>, &'s Storage) -> Option<CapturedSpan<'s>>
+            + Send
+            + Sync
+            + 'static,
+    {
+        self.parent_inference = Some(Box::new(infer));
+        self
+    }
+
+    /// Sets a hook extracting data from span [`Extensions`] stashed by other layers
+    /// (e.g., trace IDs set by the OpenTelemetry layer). The hook is called when a span
+    /// is created and when it is closed, and may insert values into the provided map.
+    /// Extracted values are accessible via [`CapturedSpan::extension_value()`]; they are
+    /// not mixed with the span fields.
+    ///
+    /// To have access to the data stashed by another layer on span creation,
+    /// that layer must be placed before the capturing layer in the layer stack.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_core::{span::{Attributes, Id}, Subscriber};
+    /// # use tracing_subscriber::{
+    /// #     layer::{Context, SubscriberExt}, registry::LookupSpan, Layer, Registry,
+    /// # };
+    /// # use tracing_capture::{CaptureLayer, SharedStorage};
+    /// /// Trace ID stashed in span extensions by a layer.
+    /// struct TraceId(u64);
+    ///
+    /// struct TraceIdLayer;
+    ///
+    /// impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for TraceIdLayer {
+    ///     fn on_new_span(&self, _: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+    ///         let span = ctx.span(id).unwrap();
+    ///         span.extensions_mut().insert(TraceId(0xc0ffee));
+    ///     }
+    /// }
+    ///
+    /// let storage = SharedStorage::default();
+    /// let layer = CaptureLayer::new(&storage).with_extension_extractor(|extensions, values| {
+    ///     if let Some(TraceId(trace_id)) = extensions.get::<TraceId>() {
+    ///         values.insert("trace_id", (*trace_id).into());
+    ///     }
+    /// });
+    /// let subscriber = Registry::default().with(TraceIdLayer).with(layer);
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     tracing::info_span!("request").in_scope(|| tracing::info!("received"));
+    /// });
+    ///
+    /// let storage = storage.lock();
+    /// let span = storage.all_spans().next().unwrap();
+    /// assert_eq!(*span.extension_value("trace_id").unwrap(), 0xc0ffee_u64);
+    /// assert!(span.value("trace_id").is_none());
+    /// ```
+    #[must_use]
+    pub fn with_extension_extractor<F>(mut self, extractor: F) -> Self
+    where
+        F: Fn(&Extensions<'_>, &mut TracedValues<&'static str>) + Send + Sync + 'static,
+    {
+        self.extension_extractor = Some(Box::new(extractor));
+        self
+    }
+
+    /// Adds a hook invoked each time a captured span is closed. The hook receives
+    /// an owned [snapshot](ClosedSpan) of the span data, which allows streaming aggregation
+    /// of span data (e.g., a histogram of span durations) in long-running tests.
+    /// Multiple hooks can be added; they are invoked in the order of addition.
+    ///
+    /// Hooks are invoked on the thread closing the span after the storage lock is released,
+    /// so a hook may access the [`SharedStorage`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+    /// # use tracing_capture::{CaptureLayer, SharedStorage};
+    /// # use std::{sync::{Arc, Mutex}, time::Duration};
+    /// let durations = Arc::new(Mutex::new(Vec::<Duration>::new()));
+    /// let durations_ = Arc::clone(&durations);
+    /// let storage = SharedStorage::default();
+    /// let layer = CaptureLayer::new(&storage).with_close_hook(move |span| {
+    ///     if span.metadata.name() == "request" {
+    ///         durations_.lock().unwrap().push(span.duration());
+    ///     }
+    /// });
+    /// let subscriber = Registry::default().with(layer);
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     for i in 0..5 {
+    ///         tracing::info_span!("request", i).in_scope(|| {
+    ///             tracing::info!("processing");
+    ///         });
+    ///     }
+    /// });
+    /// assert_eq!(durations.lock().unwrap().len(), 5);
+    /// ```
+    #[must_use]
+    pub fn with_close_hook<F>(mut self, hook: F) -> Self
+    where
+        F: Fn(&ClosedSpan) + Send + Sync + 'static,
+    {
+        self.close_hooks.push(Box::new(hook));
+        self
+    }
+
+    /// Sets a function partitioning captured data by root spans. The function is called
+    /// for each captured span without a captured parent; if it returns a value, the span
+    /// and all its descendant spans and events are captured into the [partition](SharedStorage::partition())
+    /// of the storage keyed by the string presentation of the value (e.g., the string itself
+    /// for string values, or the [`Debug`](fmt::Debug) output for debug objects). If the function
+    /// returns `None`, the span is captured into the storage itself. Events without
+    /// a captured parent span are always captured into the storage itself.
+    ///
+    /// This allows a single global subscriber to serve many concurrently running tests
+    /// as long as each test wraps its code into a span with a distinct field value.
+    ///
+    /// Partitioning has no effect for [per-thread](Self::per_thread()) layers.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+    /// # use tracing_capture::{CaptureLayer, SharedStorage};
+    /// # use std::thread;
+    /// let storage = SharedStorage::default();
+    /// let layer = CaptureLayer::new(&storage)
+    ///     .partition_by(|root_span| root_span.value("test_name"));
+    /// // Usually, this will be done once, e.g., using `set_global_default()`.
+    /// let dispatch = tracing::Dispatch::new(Registry::default().with(layer));
+    ///
+    /// let test = |name: &'static str| {
+    ///     tracing::dispatcher::with_default(&dispatch, || {
+    ///         tracing::info_span!("test", test_name = name).in_scope(|| {
+    ///             tracing::info!("test started");
+    ///         });
+    ///     });
+    /// };
+    /// thread::scope(|scope| {
+    ///     scope.spawn(|| test("first"));
+    ///     scope.spawn(|| test("second"));
+    /// });
+    ///
+    /// let partition = storage.partition("first");
+    /// let partition = partition.lock();
+    /// assert_eq!(partition.all_spans().len(), 1);
+    /// assert_eq!(partition.all_events().len(), 1);
+    /// // The storage itself has no captured data.
+    /// assert_eq!(storage.lock().all_spans().len(), 0);
+    /// ```
+    #[must_use]
+    pub fn partition_by<F>(mut self, partition_by: F) -> Self
+    where
+        F: for<'a> Fn(&RootSpan<'a>) -> Option<&'a TracedValue> + Send + Sync + 'static,
+    {
+        self.partition_by = Some(Box::new(partition_by));
+        self
+    }
+
+    fn extract_extensions(&self, span: &SpanRef<'_, S>) -> Option<TracedValues<&'static str>>
+    where
+        S: for<'a> LookupSpan<'a>,
+    {
+        let extractor = self.extension_extractor.as_ref()?;
+        let mut values = TracedValues::new();
+        extractor(&span.extensions(), &mut values);
+        Some(values)
+    }
+
+    fn infer_parent(
+        &self,
+        metadata: &'static Metadata<'static>,
+        values: &CapturedValues,
+        storage: &Storage,
+    ) -> Option<CapturedSpanId> {
+        let infer = self.parent_inference.as_ref()?;
+        let event = OrphanEvent { metadata, values };
+        let parent = infer(&event, storage)?;
+        // Spans from other storages cannot be used as parents.
+        ptr::eq(parent.storage, storage).then_some(parent.inner.id)
+    }
+
+    fn process_value(&self, name: &str, value: &mut TracedValue) {
+        if let Some(redaction) = &self.redaction {
+            redaction(name, value);
+        }
+        if let Some(max_len) = self.max_value_len {
+            truncate_value(value, max_len);
+        }
+    }
+
+    fn process_values(&self, values: TracedValues<&'static str>) -> TracedValues<&'static str> {
+        if self.redaction.is_none() && self.max_value_len.is_none() {
+            return values;
+        }
+
+        let values = values.into_iter().map(|(name, mut value)| {
+            self.process_value(name, &mut value);
+            (name, value)
+        });
+        values.collect()
+    }
+
+    fn process_event_values(&self, values: &mut CapturedValues) {
+        if self.redaction.is_none() && self.max_value_len.is_none() {
+            return;
+        }
+        for (name, value) in values.iter_mut() {
+            self.process_value(name, value);
+        }
+    }
+
     fn enabled(&self, metadata: &Metadata<'_>, ctx: &Context<'_, S>) -> bool {
+        if self.handle.level() < *metadata.level() {
+            return false;
+        }
         self.filter
             .as_deref()
             .map_or(true, |filter| filter.enabled(metadata, ctx))
     }
 
-    fn lock(&self) -> impl ops::DerefMut<Target = Storage> + '_ {
+    fn is_valid_parent(&self, span_ref: &CapturedSpanRef) -> bool {
         self.storage
-            .write()
-            .expect("failed locking shared tracing data storage for write")
+            .as_ref()
+            .map_or(true, |storage| Arc::ptr_eq(storage, &span_ref.origin))
+    }
+
+    /// Finds the nearest captured ancestor span that can be used as a parent.
+    fn find_parent<'a>(
+        &self,
+        mut ancestors: impl Iterator<Item = SpanRef<'a, S>>,
+    ) -> Option<CapturedSpanRef> {
+        ancestors.find_map(|span| {
+            let extensions = span.extensions();
+            let span_ref = extensions.get::<CapturedSpanRef>()?;
+            self.is_valid_parent(span_ref).then(|| span_ref.clone())
+        })
+    }
+
+    /// Determines the storage and the parent span for a new span or event, recording
+    /// placeholders for missing ancestors if necessary. `root_span` is the new span
+    /// (`None` for events); it is used for partitioning if the span has no captured parent.
+    fn target_for<'a>(
+        &self,
+        ancestors: impl Iterator<Item = SpanRef<'a, S>>,
+        root_span: Option<&RootSpan<'_>>,
+    ) -> Option<CaptureTarget> {
+        if !self.capture_spans {
+            return self.target(None, root_span);
+        }
+        if !self.missing_ancestors {
+            return self.target(self.find_parent(ancestors), root_span);
+        }
+
+        let mut missing_ancestors = vec![];
+        let mut parent = None;
+        for span in ancestors {
+            let extensions = span.extensions();
+            if let Some(span_ref) = extensions.get::<CapturedSpanRef>() {
+                if self.is_valid_parent(span_ref) {
+                    parent = Some(span_ref.clone());
+                    break;
+                }
+                // The span is captured into another storage; it cannot be replaced
+                // with a placeholder.
+            } else {
+                drop(extensions);
+                missing_ancestors.push(span);
+            }
+        }
+
+        let target = self.target(parent, root_span)?;
+        if missing_ancestors.is_empty() {
+            return Some(target);
+        }
+        let mut parent_id = target.parent_id;
+        let placeholder_ids: Vec<_> = {
+            let mut lock = lock_for_write(&target.storage);
+            lock.set_active_dispatcher(self.dispatcher_id());
+            missing_ancestors
+                .iter()
+                .rev()
+                .map(|span| {
+                    let id = lock.push_placeholder_span(span.metadata(), parent_id);
+                    parent_id = Some(id);
+                    id
+                })
+                .collect()
+        };
+        for (span, id) in missing_ancestors.iter().rev().zip(placeholder_ids) {
+            span.extensions_mut().insert(CapturedSpanRef {
+                id,
+                storage: Arc::clone(&target.storage),
+                origin: Arc::clone(&target.origin),
+            });
+        }
+        Some(CaptureTarget {
+            parent_id,
+            ..target
+        })
+    }
+
+    /// Determines the storage and the parent span for a new span or event.
+    fn target(
+        &self,
+        parent: Option<CapturedSpanRef>,
+        root_span: Option<&RootSpan<'_>>,
+    ) -> Option<CaptureTarget> {
+        if let Some(parent) = parent {
+            return Some(CaptureTarget {
+                storage: parent.storage,
+                origin: parent.origin,
+                parent_id: Some(parent.id),
+            });
+        }
+        let origin = if let Some(storage) = &self.storage {
+            Arc::clone(storage)
+        } else {
+            THREAD_STORAGE
+                .try_with(|storage| storage.borrow().clone())
+                .ok()??
+        };
+        let partition = root_span.and_then(|span| self.partition_storage(span));
+        Some(CaptureTarget {
+            storage: partition.unwrap_or_else(|| Arc::clone(&origin)),
+            origin,
+            parent_id: None,
+        })
+    }
+
+    fn partition_storage(&self, root_span: &RootSpan<'_>) -> Option<Arc<RwLock<Storage>>> {
+        let partition_by = self.partition_by.as_deref()?;
+        let partitions = self.partitions.as_deref()?;
+        let key = partition_key(partition_by(root_span)?);
+        let mut partitions = lock_partitions(partitions);
+        Some(Arc::clone(&partitions.entry(key).or_default().storage))
+    }
+
+    fn dispatcher_id(&self) -> DispatcherId {
+        *self.dispatcher.get_or_init(DispatcherId::new)
+    }
+
+    fn captured_span(&self, id: &Id, ctx: &Context<'_, S>) -> Option<CapturedSpanRef> {
+        if !self.capture_spans {
+            return None;
+        }
+        let span = ctx.span(id).unwrap();
+        let span_ref = span.extensions().get::<CapturedSpanRef>().cloned();
+        span_ref
     }
 }
 
@@ -244,60 +2522,150 @@ impl<S> Layer<S> for CaptureLayer<S>
 where
     S: Subscriber + for<'a> LookupSpan<'a>,
 {
+    fn on_register_dispatch(&self, _subscriber: &Dispatch) {
+        // Assigns the dispatcher ID in the registration order.
+        self.dispatcher_id();
+    }
+
     fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
-        if !self.enabled(attrs.metadata(), &ctx) {
+        if !self.capture_spans || !self.enabled(attrs.metadata(), &ctx) {
             return;
         }
 
-        let parent_id = if let Some(mut scope) = ctx.span_scope(id) {
-            scope.find_map(|span| span.extensions().get::<CapturedSpanId>().copied())
-        } else {
-            None
+        let values = self.process_values(TracedValues::from_values(attrs.values()));
+        let root_span = RootSpan {
+            metadata: attrs.metadata(),
+            values: &values,
+        };
+        // The first span in the scope is the created span itself.
+        let ancestors = ctx.span_scope(id).into_iter().flatten().skip(1);
+        let Some(target) = self.target_for(ancestors, Some(&root_span)) else {
+            return;
         };
-        let values = TracedValues::from_values(attrs.values());
-        let arena_id = self.lock().push_span(attrs.metadata(), values, parent_id);
-        ctx.span(id).unwrap().extensions_mut().insert(arena_id);
+        let span = ctx.span(id).unwrap();
+        let extension_values = self.extract_extensions(&span);
+        let arena_id = {
+            let mut lock = lock_for_write(&target.storage);
+            lock.set_active_dispatcher(self.dispatcher_id());
+            if self.storage.is_none() {
+                // Per-thread layers cannot configure bound storages in advance.
+                if let Some(check) = self.open_spans_check {
+                    lock.open_spans_check = Some(check);
+                }
+                if let Some(max_events) = self.max_events_per_span {
+                    lock.max_events_per_span = Some(max_events);
+                }
+            }
+            if let Some(clock) = &self.clock {
+                lock.set_clock(clock);
+            }
+            let arena_id = lock.push_span(attrs.metadata(), values, target.parent_id);
+            lock.spans[arena_id].name_field = self.name_field;
+            if let Some(extension_values) = extension_values {
+                lock.on_extensions_extracted(arena_id, extension_values);
+            }
+            arena_id
+        };
+        span.extensions_mut().insert(CapturedSpanRef {
+            id: arena_id,
+            storage: target.storage,
+            origin: target.origin,
+        });
     }
 
     fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
-        let span = ctx.span(id).unwrap();
-        if let Some(id) = span.extensions().get::<CapturedSpanId>().copied() {
-            self.lock().on_record(id, TracedValues::from_record(values));
-        };
+        if let Some(span) = self.captured_span(id, &ctx) {
+            let values = self.process_values(TracedValues::from_record(values));
+            lock_for_write(&span.storage).on_record(span.id, values);
+        }
     }
 
     fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
-        if !self.enabled(event.metadata(), &ctx) {
+        #[cfg(feature = "perf")]
+        let started_at = Instant::now();
+        if !self.capture_events || !self.enabled(event.metadata(), &ctx) {
             return;
         }
 
-        let parent_id = if let Some(mut scope) = ctx.event_scope(event) {
-            scope.find_map(|span| span.extensions().get::<CapturedSpanId>().copied())
-        } else {
-            None
+        let ancestors = ctx.event_scope(event).into_iter().flatten();
+        let Some(target) = self.target_for(ancestors, None) else {
+            return;
         };
-        self.lock()
-            .push_event(event.metadata(), TracedValues::from_event(event), parent_id);
+        let mut values = CapturedValues::from_event(event);
+        self.process_event_values(&mut values);
+        let mut lock = lock_for_write(&target.storage);
+        lock.set_active_dispatcher(self.dispatcher_id());
+        if let Some(clock) = &self.clock {
+            lock.set_clock(clock);
+        }
+        let parent_id = target
+            .parent_id
+            .or_else(|| self.infer_parent(event.metadata(), &values, &lock));
+        lock.push_event(event.metadata(), values, parent_id);
+        #[cfg(feature = "perf")]
+        lock.perf.on_event(started_at.elapsed());
     }
 
     fn on_enter(&self, id: &Id, ctx: Context<'_, S>) {
-        let span = ctx.span(id).unwrap();
-        if let Some(id) = span.extensions().get::<CapturedSpanId>().copied() {
-            self.lock().on_span_enter(id);
-        };
+        if let Some(span) = self.captured_span(id, &ctx) {
+            lock_for_write(&span.storage).on_span_enter(span.id);
+        }
     }
 
     fn on_exit(&self, id: &Id, ctx: Context<'_, S>) {
-        let span = ctx.span(id).unwrap();
-        if let Some(id) = span.extensions().get::<CapturedSpanId>().copied() {
-            self.lock().on_span_exit(id);
+        if let Some(span) = self.captured_span(id, &ctx) {
+            lock_for_write(&span.storage).on_span_exit(span.id);
+        }
+    }
+
+    fn on_follows_from(&self, id: &Id, follows_from: &Id, ctx: Context<'_, S>) {
+        let Some(span) = self.captured_span(id, &ctx) else {
+            return;
+        };
+        let Some(follows_from) = self.captured_span(follows_from, &ctx) else {
+            return;
         };
+        // Spans captured into different storages cannot be linked.
+        if Arc::ptr_eq(&span.storage, &follows_from.storage) {
+            lock_for_write(&span.storage).on_follows_from(span.id, follows_from.id);
+        }
     }
 
-    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
-        let span = ctx.span(&id).unwrap();
-        if let Some(id) = span.extensions().get::<CapturedSpanId>().copied() {
-            self.lock().on_span_closed(id);
+    fn on_id_change(&self, old: &Id, new: &Id, ctx: Context<'_, S>) {
+        if !self.capture_spans {
+            return;
+        }
+        let Some(span_ref) = ctx.span(old).and_then(|span| {
+            let span_ref = span.extensions().get::<CapturedSpanRef>().cloned();
+            span_ref
+        }) else {
+            return;
         };
+        if let Some(new_span) = ctx.span(new) {
+            let mut extensions = new_span.extensions_mut();
+            if extensions.get_mut::<CapturedSpanRef>().is_none() {
+                extensions.insert(span_ref);
+            }
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        if let Some(span) = self.captured_span(&id, &ctx) {
+            let extension_values = self.extract_extensions(&ctx.span(&id).unwrap());
+            let closed_span = {
+                let mut lock = lock_for_write(&span.storage);
+                if let Some(extension_values) = extension_values {
+                    lock.on_extensions_extracted(span.id, extension_values);
+                }
+                lock.on_span_closed(span.id);
+                let has_hooks = !self.close_hooks.is_empty() && lock.contains_span(span.id);
+                has_hooks.then(|| ClosedSpan::new(lock.span(span.id)))
+            };
+            if let Some(closed_span) = closed_span {
+                for hook in &self.close_hooks {
+                    hook(&closed_span);
+                }
+            }
+        }
     }
 }
@@ -50,39 +50,243 @@
 //! - [`tracing-fluent-assertions`] is more similar in its goals, but differs significantly
 //!   in the API design; e.g., the assertions need to be declared before the capture.
 //!
+//! # Crate features
+//!
+//! ## `trace-spec`
+//!
+//! *(Off by default)*
+//!
+//! Enables the [`spec`] module, which allows checking captured traces against declarative
+//! YAML / JSON specifications.
+//!
+//! ## `snapshot`
+//!
+//! *(Off by default)*
+//!
+//! Provides [`Storage::to_snapshot()`] returning a deterministic serializable representation
+//! of captured traces, e.g. for snapshot testing with [`insta`].
+//!
+//! ## `golden`
+//!
+//! *(Off by default)*
+//!
+//! Provides [`Storage::record_golden()`] and [`Storage::assert_matches_golden()`] recording
+//! [snapshots](Storage::to_snapshot()) of captured traces into JSON golden files and checking
+//! them, e.g. so that library authors can detect tracing regressions between versions.
+//! Enables the `snapshot` feature.
+//!
+//! ## `no-alloc-hot-path`
+//!
+//! *(Off by default)*
+//!
+//! Stores values of captured events inline for events with up to 8 values, so that
+//! capturing such events does not allocate on the heap. See
+//! [`CaptureLayer` docs](CaptureLayer#allocations) for details.
+//!
+//! ## `json`
+//!
+//! *(Off by default)*
+//!
+//! Provides [`CapturedSpan::values_json()`] and [`CapturedEvent::values_json()`] converting
+//! captured values to [`serde_json`] objects, e.g. to compare them with `serde_json::json!`
+//! literals or to validate them against a JSON schema. Enables the `json` feature
+//! in `tracing-tunnel`, which provides [`TracedValue::to_json()`].
+//!
+//! ## `tunnel`
+//!
+//! *(Off by default)*
+//!
+//! Provides [`CaptureReceiver`] capturing [`TracingEvent`]s produced by a [`TracingEventSender`]
+//! (e.g., in a WASM module) directly into a [`SharedStorage`]. Enables the `receiver` feature
+//! in `tracing-tunnel`.
+//!
+//! [`TracingEvent`]: tracing_tunnel::TracingEvent
+//! [`TracingEventSender`]: https://docs.rs/tracing-tunnel/0.1/tracing_tunnel/struct.TracingEventSender.html
+//!
+//! ## `rayon`
+//!
+//! *(Off by default)*
+//!
+//! Implements [`IntoParallelIterator`] for [`CapturedSpans`] and [`CapturedEvents`], so that
+//! analysis of large captures (e.g., millions of events from soak tests) can run in parallel
+//! using [`rayon`].
+//!
+//! [`IntoParallelIterator`]: https://docs.rs/rayon/1/rayon/iter/trait.IntoParallelIterator.html
+//!
+//! ## `macros`
+//!
+//! *(Off by default)*
+//!
+//! Provides the [`#[tracing_capture::test]`](macro@test) attribute macro, which wraps a test
+//! so that it captures tracing spans and events without subscriber setup boilerplate.
+//!
+//! ## `sqlite`
+//!
+//! *(Off by default)*
+//!
+//! Provides [`Storage::export_sqlite()`] writing captured spans, events and values
+//! into a SQLite file, so that large captures (e.g., from soak tests) can be queried with SQL,
+//! and [`Storage::load_sqlite()`] loading a read-only storage from such a file.
+//! Uses a bundled SQLite library via [`rusqlite`].
+//!
+//! [`rusqlite`]: https://docs.rs/rusqlite
+//!
+//! ## `perf`
+//!
+//! *(Off by default)*
+//!
+//! Enables performance counters queryable via [`Storage::perf_stats()`], e.g. to quantify
+//! capture overhead in benchmarks. This feature is internal; its API is not covered
+//! by semantic versioning guarantees.
+//!
+//! [`insta`]: https://docs.rs/insta
+//! [`rayon`]: https://docs.rs/rayon/1
+//! [`serde_json`]: https://docs.rs/serde_json/1/serde_json
 //! [`tracing-test`]: https://docs.rs/tracing-test
 //! [`tracing-fluent-assertions`]: https://docs.rs/tracing-fluent-assertions
 
 // Documentation settings.
 #![doc(html_root_url = "https://docs.rs/tracing-capture/0.1.0")]
+#![cfg_attr(docsrs, feature(doc_cfg))]
 // Linter settings.
 #![warn(missing_debug_implementations, missing_docs, bare_trait_objects)]
 #![warn(clippy::all, clippy::pedantic)]
 #![allow(clippy::must_use_candidate, clippy::module_name_repetitions)]
 
-use tracing_core::Metadata;
+use tracing_core::{Level, Metadata};
 
-use std::{cmp, fmt, ops, ptr};
+use std::{
+    cmp, fmt, ops, ptr,
+    time::{Duration, Instant},
+};
 
+mod aggregate;
+mod clock;
+mod compare;
+mod export;
+mod field_ref;
+#[cfg(feature = "golden")]
+mod golden;
 mod iter;
 mod layer;
+mod owned;
+#[cfg(feature = "perf")]
+mod perf;
 pub mod predicates;
+mod query;
+#[cfg(feature = "snapshot")]
+mod snapshot;
+#[cfg(feature = "trace-spec")]
+#[cfg_attr(docsrs, doc(cfg(feature = "trace-spec")))]
+pub mod spec;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+pub mod test_util;
+mod timeline;
+mod tree;
+#[cfg(feature = "tunnel")]
+mod tunnel;
+mod values;
 
+#[cfg(feature = "golden")]
+pub use crate::golden::GoldenTolerance;
+#[cfg(feature = "perf")]
+pub use crate::perf::PerfStats;
+#[cfg(feature = "snapshot")]
+pub use crate::snapshot::{EventSnapshot, SpanSnapshot, StorageSnapshot};
+#[cfg(feature = "sqlite")]
+pub use crate::sqlite::SqliteError;
+#[cfg(feature = "tunnel")]
+pub use crate::tunnel::CaptureReceiver;
 pub use crate::{
+    aggregate::{DurationStats, GroupBy, SpanAggregate},
+    clock::{Clock, MockClock},
+    compare::StructuralEqOptions,
+    field_ref::{FieldError, FieldRef},
     iter::{CapturedEvents, CapturedSpans, DescendantEvents, DescendantSpans},
-    layer::{CaptureLayer, SharedStorage, Storage},
+    layer::{
+        CallSiteStats, CaptureHandle, CaptureLayer, ClosedSpan, DispatcherId, MemoryUsage,
+        OpenSpansCheck, OrphanEvent, RootSpan, SharedStorage, Storage, ThreadBinding,
+    },
+    owned::{OwnedCapturedEvent, OwnedCapturedSpan},
+    query::{EventQuery, Query, QueryIter, SpanQuery},
+    timeline::CapturedOperation,
+    tree::{TraceTree, TraceTreeEvent},
 };
+/// Wraps a test function so that it captures tracing spans and events.
+///
+/// The wrapped function is marked as a test. It is run with the default subscriber set to
+/// a [`Registry`] with a [`CaptureLayer`] writing to a fresh [`SharedStorage`]. The storage
+/// can be injected into the function via an argument of type `SharedStorage` or `&SharedStorage`;
+/// the function may have no arguments if the storage is not needed. The test can return
+/// a value (e.g., a `Result`), and can be combined with other test attributes,
+/// such as `#[should_panic]`.
+///
+/// Async test functions are not supported.
+///
+/// [`Registry`]: tracing_subscriber::Registry
+///
+/// # Examples
+///
+/// ```
+/// use tracing_capture::{predicates::*, SharedStorage};
+///
+/// #[tracing_capture::test]
+/// fn capturing_spans(storage: &SharedStorage) {
+///     tracing::info_span!("compute", input = 42_i64).in_scope(|| {
+///         tracing::info!("computed");
+///     });
+///
+///     let storage = storage.lock();
+///     let span = storage.scan_spans().single(&name(eq("compute")));
+///     assert_eq!(span["input"], 42_i64);
+/// }
+/// ```
+#[cfg(feature = "macros")]
+#[cfg_attr(docsrs, doc(cfg(feature = "macros")))]
+pub use tracing_capture_macros::test;
 
-use tracing_tunnel::{TracedValue, TracedValues};
+use crate::{field_ref::FieldOwner, values::CapturedValues};
+use tracing_tunnel::TracedValue;
+
+#[cfg(feature = "json")]
+fn values_to_json<'a>(
+    values: impl Iterator<Item = (&'a str, &'a TracedValue)>,
+) -> serde_json::Map<String, serde_json::Value> {
+    values
+        .map(|(name, value)| (name.to_owned(), value.to_json()))
+        .collect()
+}
 
 mod sealed {
     pub trait Sealed {}
 }
 
-#[derive(Debug)]
+/// Implementation details of the [`test`](macro@test) macro.
+#[cfg(feature = "macros")]
+#[doc(hidden)]
+pub mod __private {
+    use tracing_core::{dispatcher, Dispatch};
+    use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+    use crate::{CaptureLayer, SharedStorage};
+
+    pub fn capture_test<R>(test_fn: impl FnOnce(SharedStorage) -> R) -> R {
+        let storage = SharedStorage::default();
+        let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+        dispatcher::with_default(&Dispatch::new(subscriber), || test_fn(storage))
+    }
+}
+
+#[derive(Debug, Clone)]
 struct CapturedEventInner {
     metadata: &'static Metadata<'static>,
-    values: TracedValues<&'static str>,
+    values: CapturedValues,
+    timestamp: Instant,
+    /// Index of the event in the storage timeline.
+    seq: usize,
+    thread: usize,
+    dispatcher: Option<usize>,
     id: CapturedEventId,
     parent_id: Option<CapturedSpanId>,
 }
@@ -98,7 +302,8 @@ type CapturedEventId = id_arena::Id<CapturedEventInner>;
 /// Two events from different [`Storage`]s are not ordered and are always non-equal.
 ///
 /// Values recorded with the event can be accessed by indexing or using [`Self::value()`],
-/// or iterated over using [`Self::values()`].
+/// or iterated over using [`Self::values()`]. Indexing panics if the field is missing;
+/// use [`Self::field()`] for fallible access.
 ///
 /// # Examples
 ///
@@ -121,6 +326,8 @@ type CapturedEventId = id_arena::Id<CapturedEventInner>;
 /// assert!(event
 ///     .ancestors()
 ///     .any(|span| span.metadata().name() == "test"));
+/// // Accessing the root span:
+/// assert_eq!(event.root().unwrap().metadata().name(), "request");
 /// # }
 /// ```
 #[derive(Clone, Copy)]
@@ -146,11 +353,37 @@ impl<'a> CapturedEvent<'a> {
         self.inner.values.iter()
     }
 
+    /// Converts values associated with the event to a JSON object. See
+    /// [`TracedValue::to_json()`] for details on the conversion.
+    #[cfg(feature = "json")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "json")))]
+    pub fn values_json(&self) -> serde_json::Map<String, serde_json::Value> {
+        values_to_json(self.values())
+    }
+
     /// Returns a value for the specified field, or `None` if the value is not defined.
     pub fn value(&self, name: &str) -> Option<&'a TracedValue> {
         self.inner.values.get(name)
     }
 
+    /// Returns a value for the specified field defined by this event or, if the event
+    /// does not define it, by the nearest [ancestor span](Self::ancestors()).
+    /// See [`CapturedSpan::inherited_value()`] for details.
+    pub fn inherited_value(&self, name: &str) -> Option<&'a TracedValue> {
+        self.value(name)
+            .or_else(|| self.ancestors().find_map(|span| span.value(name)))
+    }
+
+    /// Returns a reference to the specified field allowing fallible access to its value
+    /// or panicking access with detailed messages. See [`FieldRef`] for details.
+    pub fn field<'s>(&self, name: &'s str) -> FieldRef<'s>
+    where
+        'a: 's,
+    {
+        let owner = FieldOwner::event(self.metadata().name());
+        FieldRef::new(owner, name, self.value(name))
+    }
+
     /// Returns the message recorded in this event, i.e., the value of the `message` field
     /// if it has a string presentation.
     pub fn message(&self) -> Option<&'a str> {
@@ -162,6 +395,24 @@ impl<'a> CapturedEvent<'a> {
         })
     }
 
+    /// Returns the instant when this event was captured.
+    pub fn timestamp(&self) -> Instant {
+        self.inner.timestamp
+    }
+
+    /// Returns the [index of the thread](Storage::threads()) on which this event
+    /// was captured.
+    pub fn thread(&self) -> usize {
+        self.inner.thread
+    }
+
+    /// Returns the [index of the dispatcher](Storage::dispatchers()) that captured this event,
+    /// or `None` if the event was not captured by a [`CaptureLayer`]
+    /// (e.g., if it was created with [`test_util`] helpers).
+    pub fn dispatcher(&self) -> Option<usize> {
+        self.inner.dispatcher
+    }
+
     /// Returns the parent span for this event, or `None` if is not tied to a captured span.
     pub fn parent(&self) -> Option<CapturedSpan<'a>> {
         self.inner.parent_id.map(|id| self.storage.span(id))
@@ -172,6 +423,13 @@ impl<'a> CapturedEvent<'a> {
     pub fn ancestors(&self) -> impl Iterator<Item = CapturedSpan<'a>> + '_ {
         std::iter::successors(self.parent(), CapturedSpan::parent)
     }
+
+    /// Returns the root span for this event, i.e., the last of its [ancestors](Self::ancestors()).
+    /// If the event is directly attached to a root span, this span is returned. If the event
+    /// is [not tied](Storage::root_events()) to a captured span, returns `None`.
+    pub fn root(&self) -> Option<CapturedSpan<'a>> {
+        self.ancestors().last()
+    }
 }
 
 impl PartialEq for CapturedEvent<'_> {
@@ -196,8 +454,10 @@ impl ops::Index<&str> for CapturedEvent<'_> {
     type Output = TracedValue;
 
     fn index(&self, index: &str) -> &Self::Output {
-        self.value(index)
-            .unwrap_or_else(|| panic!("field `{index}` is not contained in event"))
+        self.value(index).unwrap_or_else(|| {
+            let owner = FieldOwner::event(self.metadata().name());
+            panic!("{}", owner.missing_field(index))
+        })
     }
 }
 
@@ -211,17 +471,78 @@ pub struct SpanStats {
     pub exited: usize,
     /// Is the span closed (dropped)?
     pub is_closed: bool,
+    /// Total time during which the span was entered (on at least one thread).
+    /// Similar to the `time.busy` field output by `tracing_subscriber::fmt` with
+    /// [span close events] enabled.
+    ///
+    /// [span close events]: tracing_subscriber::fmt::format::FmtSpan::CLOSE
+    pub busy_time: Duration,
+    /// Total time during which the span was alive, but not entered. For async code
+    /// instrumented with [`Instrument`], this is the time the future spent waiting
+    /// to be polled. Similar to the `time.idle` field output by `tracing_subscriber::fmt`.
+    ///
+    /// For a span that is not closed, idle time is counted until the last exit
+    /// from the span.
+    ///
+    /// [`Instrument`]: https://docs.rs/tracing/0.1/tracing/trait.Instrument.html
+    pub idle_time: Duration,
+}
+
+/// Information about a single entry into a [`CapturedSpan`].
+#[derive(Debug, Clone, Copy)]
+#[non_exhaustive]
+pub struct SpanEntry {
+    /// Instant when the span was entered.
+    pub entered_at: Instant,
+    /// Instant when the span was exited, or `None` if the span was not exited.
+    pub exited_at: Option<Instant>,
+    /// [Index of the thread](Storage::threads()) on which the span was entered.
+    pub thread: usize,
+    /// Indices of the entry and exit operations in the storage timeline.
+    pub(crate) entered_seq: usize,
+    pub(crate) exited_seq: Option<usize>,
 }
 
-#[derive(Debug)]
+/// Origin of a [`CapturedSpan`] value. Returned by [`CapturedSpan::value_origin()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ValueOrigin {
+    /// The value was provided on span creation.
+    New,
+    /// The value was recorded after span creation, e.g. using [`Span::record()`].
+    /// This includes values that were provided on span creation and then overwritten.
+    ///
+    /// [`Span::record()`]: https://docs.rs/tracing/0.1/tracing/struct.Span.html#method.record
+    Recorded,
+}
+
+#[derive(Debug, Clone)]
 struct CapturedSpanInner {
     metadata: &'static Metadata<'static>,
-    values: TracedValues<&'static str>,
+    values: CapturedValues,
     stats: SpanStats,
+    created_at: Instant,
+    /// Index of the creation operation in the storage timeline.
+    created_seq: usize,
+    closed_at: Option<Instant>,
+    /// Index of the closing operation in the storage timeline.
+    closed_seq: Option<usize>,
+    entries: Vec<SpanEntry>,
+    /// Names of fields recorded after span creation.
+    recorded_fields: Vec<&'static str>,
+    /// Values provided on span creation and overwritten by recording.
+    overwritten_values: CapturedValues,
+    /// Values extracted from span extensions.
+    extension_values: CapturedValues,
+    is_placeholder: bool,
+    dispatcher: Option<usize>,
+    /// Field overriding the span name, as configured for the capturing layer.
+    name_field: Option<&'static str>,
     id: CapturedSpanId,
     parent_id: Option<CapturedSpanId>,
     child_ids: Vec<CapturedSpanId>,
     event_ids: Vec<CapturedEventId>,
+    follows_from_ids: Vec<CapturedSpanId>,
 }
 
 type CapturedSpanId = id_arena::Id<CapturedSpanInner>;
@@ -235,7 +556,8 @@ type CapturedSpanId = id_arena::Id<CapturedSpanInner>;
 /// Two spans from different [`Storage`]s are not ordered and are always non-equal.
 ///
 /// Values recorded with the span can be accessed by indexing or using [`Self::value()`],
-/// or iterated over using [`Self::values()`].
+/// or iterated over using [`Self::values()`]. Indexing panics if the field is missing;
+/// use [`Self::field()`] for fallible access.
 ///
 /// # Examples
 ///
@@ -291,14 +613,292 @@ impl<'a> CapturedSpan<'a> {
         self.inner.values.iter()
     }
 
+    /// Iterates over values provided on span creation, including values subsequently
+    /// overwritten by recording.
+    #[cfg(feature = "sqlite")]
+    pub(crate) fn initial_values(&self) -> impl Iterator<Item = (&'a str, &'a TracedValue)> + 'a {
+        let recorded_fields = &self.inner.recorded_fields;
+        let unchanged_values = self
+            .inner
+            .values
+            .iter()
+            .filter(move |(name, _)| !recorded_fields.contains(name));
+        unchanged_values.chain(self.inner.overwritten_values.iter())
+    }
+
+    /// Converts values of this span to a JSON object. See [`TracedValue::to_json()`]
+    /// for details on the conversion.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use serde_json::json;
+    /// # use tracing_subscriber::layer::SubscriberExt;
+    /// # use tracing_capture::{CaptureLayer, SharedStorage};
+    /// let storage = SharedStorage::default();
+    /// let subscriber = tracing_subscriber::registry().with(CaptureLayer::new(&storage));
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     let _entered = tracing::info_span!("test", num = 42, flag = true).entered();
+    /// });
+    ///
+    /// let storage = storage.lock();
+    /// let span = storage.all_spans().next().unwrap();
+    /// let values = serde_json::Value::from(span.values_json());
+    /// assert_eq!(values, json!({ "num": 42, "flag": true }));
+    /// ```
+    #[cfg(feature = "json")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "json")))]
+    pub fn values_json(&self) -> serde_json::Map<String, serde_json::Value> {
+        values_to_json(self.values())
+    }
+
     /// Returns a value for the specified field, or `None` if the value is not defined.
     pub fn value(&self, name: &str) -> Option<&'a TracedValue> {
         self.inner.values.get(name)
     }
 
+    /// Returns a value for the specified field defined by this span or, if the span
+    /// does not define it, by the nearest [ancestor](Self::ancestors()). This mirrors
+    /// how contextual fields (e.g., a request ID set on the outermost span) are usually
+    /// interpreted by log formatters.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+    /// # use tracing_capture::{CaptureLayer, SharedStorage};
+    /// let storage = SharedStorage::default();
+    /// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     let _request = tracing::info_span!("request", request_id = 42_u64).entered();
+    ///     let _query = tracing::info_span!("query", table = "users").entered();
+    ///     tracing::info!("done");
+    /// });
+    ///
+    /// let storage = storage.lock();
+    /// let query = storage.all_spans().nth(1).unwrap();
+    /// assert!(query.value("request_id").is_none());
+    /// assert_eq!(*query.inherited_value("request_id").unwrap(), 42_u64);
+    /// let event = query.events().next().unwrap();
+    /// assert_eq!(*event.inherited_value("table").unwrap(), "users");
+    /// ```
+    pub fn inherited_value(&self, name: &str) -> Option<&'a TracedValue> {
+        self.value(name)
+            .or_else(|| self.ancestors().find_map(|span| span.value(name)))
+    }
+
+    /// Returns a reference to the specified field allowing fallible access to its value
+    /// or panicking access with detailed messages. See [`FieldRef`] for details.
+    pub fn field<'s>(&self, name: &'s str) -> FieldRef<'s>
+    where
+        'a: 's,
+    {
+        let owner = FieldOwner::span(self.metadata().name());
+        FieldRef::new(owner, name, self.value(name))
+    }
+
+    /// Iterates over values extracted from the span extensions by the
+    /// [extension extractor](CaptureLayer::with_extension_extractor()).
+    pub fn extension_values(&self) -> impl Iterator<Item = (&'a str, &'a TracedValue)> + 'a {
+        self.inner.extension_values.iter()
+    }
+
+    /// Returns a value extracted from the span extensions by the
+    /// [extension extractor](CaptureLayer::with_extension_extractor()), or `None`
+    /// if the value is not defined.
+    pub fn extension_value(&self, name: &str) -> Option<&'a TracedValue> {
+        self.inner.extension_values.get(name)
+    }
+
+    /// Returns the origin of the value for the specified field, or `None` if the value
+    /// is not defined.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+    /// # use tracing_capture::{CaptureLayer, SharedStorage, ValueOrigin};
+    /// let storage = SharedStorage::default();
+    /// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     let span = tracing::info_span!("compute", arg = 5, result = tracing::field::Empty);
+    ///     span.record("result", 25);
+    /// });
+    ///
+    /// let storage = storage.lock();
+    /// let span = storage.all_spans().next().unwrap();
+    /// assert_eq!(span.value_origin("arg"), Some(ValueOrigin::New));
+    /// assert_eq!(span.value_origin("result"), Some(ValueOrigin::Recorded));
+    /// assert_eq!(span.value_origin("other"), None);
+    /// ```
+    pub fn value_origin(&self, name: &str) -> Option<ValueOrigin> {
+        self.inner.values.get(name)?;
+        Some(if self.inner.recorded_fields.contains(&name) {
+            ValueOrigin::Recorded
+        } else {
+            ValueOrigin::New
+        })
+    }
+
     /// Returns statistics about span operations.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+    /// # use tracing_capture::{CaptureLayer, SharedStorage};
+    /// # use std::{thread, time::Duration};
+    /// let storage = SharedStorage::default();
+    /// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     let span = tracing::info_span!("compute");
+    ///     span.in_scope(|| thread::sleep(Duration::from_millis(10)));
+    ///     thread::sleep(Duration::from_millis(20));
+    ///     span.in_scope(|| thread::sleep(Duration::from_millis(10)));
+    /// });
+    ///
+    /// let storage = storage.lock();
+    /// let stats = storage.all_spans().next().unwrap().stats();
+    /// assert_eq!((stats.entered, stats.exited), (2, 2));
+    /// assert!(stats.is_closed);
+    /// assert!(stats.busy_time >= Duration::from_millis(20));
+    /// assert!(stats.idle_time >= Duration::from_millis(20));
+    /// ```
     pub fn stats(&self) -> SpanStats {
-        self.inner.stats
+        let (busy_time, idle_time) = self.timings();
+        SpanStats {
+            busy_time,
+            idle_time,
+            ..self.inner.stats
+        }
+    }
+
+    /// Computes busy and idle time based on span entries.
+    fn timings(&self) -> (Duration, Duration) {
+        let mut transitions: Vec<_> = self
+            .inner
+            .entries
+            .iter()
+            .flat_map(|entry| {
+                let exit = entry.exited_at.map(|exited_at| (exited_at, true));
+                std::iter::once((entry.entered_at, false)).chain(exit)
+            })
+            .collect();
+        // Entries are ordered before exits at the same instant.
+        transitions.sort_unstable();
+
+        let (mut busy_time, mut idle_time) = (Duration::ZERO, Duration::ZERO);
+        let mut last_transition = self.inner.created_at;
+        let mut depth = 0_usize;
+        for (instant, is_exit) in transitions {
+            let elapsed = instant.saturating_duration_since(last_transition);
+            if depth == 0 {
+                idle_time += elapsed;
+            } else {
+                busy_time += elapsed;
+            }
+            last_transition = instant;
+            depth = if is_exit { depth - 1 } else { depth + 1 };
+        }
+        if let Some(closed_at) = self.inner.closed_at {
+            let elapsed = closed_at.saturating_duration_since(last_transition);
+            if depth == 0 {
+                idle_time += elapsed;
+            } else {
+                busy_time += elapsed;
+            }
+        }
+        (busy_time, idle_time)
+    }
+
+    /// Returns the instant when this span was created.
+    pub fn created_at(&self) -> Instant {
+        self.inner.created_at
+    }
+
+    /// Returns the instant when this span was closed, or `None` if it is not closed.
+    pub fn closed_at(&self) -> Option<Instant> {
+        self.inner.closed_at
+    }
+
+    /// Checks whether this span was closed after its parent span has finished, which is
+    /// a common symptom of a detached task outliving the operation that spawned it.
+    ///
+    /// The parent is considered finished if it was closed, or if it was entered at least once
+    /// and all its entries were exited before this span was closed. The latter condition
+    /// is necessary because [`Registry`] keeps a parent span open until all its children
+    /// are closed.
+    ///
+    /// Returns `None` if this span is not closed or has no parent.
+    ///
+    /// [`Registry`]: tracing_subscriber::Registry
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+    /// # use tracing_capture::{CaptureLayer, SharedStorage};
+    /// let storage = SharedStorage::default();
+    /// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     let detached = tracing::info_span!("request").in_scope(|| {
+    ///         tracing::info_span!("attached").in_scope(|| { /* ... */ });
+    ///         tracing::info_span!("detached")
+    ///     });
+    ///     drop(detached); // closes the span after its parent has finished
+    /// });
+    ///
+    /// let storage = storage.lock();
+    /// let outlived: Vec<_> = storage
+    ///     .all_spans()
+    ///     .filter(|span| span.closed_after_parent() == Some(true))
+    ///     .map(|span| span.metadata().name())
+    ///     .collect();
+    /// assert_eq!(outlived, ["detached"]);
+    /// ```
+    pub fn closed_after_parent(&self) -> Option<bool> {
+        let closed_seq = self.inner.closed_seq?;
+        let parent = self.parent()?;
+        if parent.inner.closed_seq.is_some_and(|seq| seq < closed_seq) {
+            return Some(true);
+        }
+        let entries = &parent.inner.entries;
+        let is_finished = !entries.is_empty()
+            && entries
+                .iter()
+                .all(|entry| entry.exited_seq.is_some_and(|seq| seq < closed_seq));
+        Some(is_finished)
+    }
+
+    /// Checks whether this span is a placeholder for an ancestor span that was not captured
+    /// itself (e.g., because it was rejected by the layer filter). Placeholders are only
+    /// recorded if [`CaptureLayer::with_missing_ancestors()`] is enabled. A placeholder
+    /// has the metadata of the original span, but no values; its creation instant is
+    /// the instant its first captured descendant was created.
+    pub fn is_placeholder(&self) -> bool {
+        self.inner.is_placeholder
+    }
+
+    /// Returns the [index of the dispatcher](Storage::dispatchers()) that captured this span,
+    /// or `None` if the span was not captured by a [`CaptureLayer`]
+    /// (e.g., if it was created with [`test_util`] helpers).
+    pub fn dispatcher(&self) -> Option<usize> {
+        self.inner.dispatcher
+    }
+
+    /// Returns information about entries into this span in the order of their occurrence.
+    pub fn entries(&self) -> &'a [SpanEntry] {
+        &self.inner.entries
+    }
+
+    /// Returns the logical name of this span. This is the string value of the
+    /// [name field](CaptureLayer::with_name_field()) if it is configured and recorded
+    /// for the span, and the [metadata](Self::metadata()) name otherwise.
+    pub fn name(&self) -> &'a str {
+        self.inner
+            .name_field
+            .and_then(|field_name| self.value(field_name)?.as_str())
+            .unwrap_or_else(|| self.metadata().name())
     }
 
     /// Returns events attached to this span.
@@ -306,6 +906,43 @@ impl<'a> CapturedSpan<'a> {
         CapturedEvents::from_slice(self.storage, &self.inner.event_ids)
     }
 
+    /// Iterates over [events](Self::events()) attached to this span that have
+    /// the specified level.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing::Level;
+    /// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+    /// # use tracing_capture::{CaptureLayer, SharedStorage};
+    /// let storage = SharedStorage::default();
+    /// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     tracing::info_span!("compute").in_scope(|| {
+    ///         tracing::info!("started");
+    ///         tracing::warn!(retries = 2, "retrying");
+    ///         tracing::info!("finished");
+    ///     });
+    /// });
+    ///
+    /// let storage = storage.lock();
+    /// let span = storage.all_spans().next().unwrap();
+    /// let warnings: Vec<_> = span.events_at(Level::WARN).collect();
+    /// assert_eq!(warnings.len(), 1);
+    /// assert_eq!(warnings[0]["retries"], 2_i64);
+    /// assert_eq!(span.event_count_at(Level::INFO), 2);
+    /// ```
+    pub fn events_at(&self, level: Level) -> impl Iterator<Item = CapturedEvent<'a>> + 'a {
+        self.events()
+            .filter(move |event| *event.metadata().level() == level)
+    }
+
+    /// Returns the number of [events](Self::events()) attached to this span that have
+    /// the specified level.
+    pub fn event_count_at(&self, level: Level) -> usize {
+        self.events_at(level).count()
+    }
+
     /// Returns the reference to the parent span, if any.
     pub fn parent(&self) -> Option<Self> {
         self.inner.parent_id.map(|id| self.storage.span(id))
@@ -317,11 +954,57 @@ impl<'a> CapturedSpan<'a> {
         std::iter::successors(self.parent(), Self::parent)
     }
 
+    /// Returns the root span for this span, i.e., the last of its [ancestors](Self::ancestors()).
+    /// If this span is a [root span](Storage::root_spans()), returns the span itself.
+    #[must_use]
+    pub fn root(&self) -> Self {
+        self.ancestors().last().unwrap_or(*self)
+    }
+
+    /// Returns the depth of this span in the span tree, i.e., the number of its
+    /// [ancestors](Self::ancestors()). [Root spans](Storage::root_spans()) have depth 0.
+    pub fn depth(&self) -> usize {
+        self.ancestors().count()
+    }
+
     /// Iterates over the direct children of this span, in the order of their capture.
     pub fn children(&self) -> CapturedSpans<'a> {
         CapturedSpans::from_slice(self.storage, &self.inner.child_ids)
     }
 
+    /// Iterates over spans that this span follows from (i.e., spans that causally precede it
+    /// without being its parent), in the order the relations were recorded.
+    /// Spans captured in a different [`Storage`] are not included.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+    /// # use tracing_capture::{CaptureLayer, SharedStorage};
+    /// let storage = SharedStorage::default();
+    /// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     let producers: Vec<_> = (0..3)
+    ///         .map(|idx| tracing::info_span!("produce", idx))
+    ///         .collect();
+    ///     let consumer = tracing::info_span!("consume");
+    ///     for producer in &producers {
+    ///         consumer.follows_from(producer);
+    ///     }
+    /// });
+    ///
+    /// let storage = storage.lock();
+    /// let consumer = storage.all_spans().next_back().unwrap();
+    /// let producer_indices: Vec<_> = consumer
+    ///     .follows_from()
+    ///     .filter_map(|span| span["idx"].as_int())
+    ///     .collect();
+    /// assert_eq!(producer_indices, [0, 1, 2]);
+    /// ```
+    pub fn follows_from(&self) -> CapturedSpans<'a> {
+        CapturedSpans::from_slice(self.storage, &self.inner.follows_from_ids)
+    }
+
     /// Iterates over the descendants of this span.
     ///
     /// In the simplest case (spans are not re-entered, span parents are contextual), the iteration
@@ -361,8 +1044,10 @@ impl ops::Index<&str> for CapturedSpan<'_> {
     type Output = TracedValue;
 
     fn index(&self, index: &str) -> &Self::Output {
-        self.value(index)
-            .unwrap_or_else(|| panic!("field `{index}` is not contained in span"))
+        self.value(index).unwrap_or_else(|| {
+            let owner = FieldOwner::span(self.metadata().name());
+            panic!("{}", owner.missing_field(index))
+        })
     }
 }
 
@@ -371,6 +1056,10 @@ impl ops::Index<&str> for CapturedSpan<'_> {
 pub trait Captured<'a>: Eq + PartialOrd + sealed::Sealed {
     /// Provides a reference to the span / event metadata.
     fn metadata(&self) -> &'static Metadata<'static>;
+    /// Returns the logical name of the span / event. For spans, this takes
+    /// the [name field](CaptureLayer::with_name_field()) into account; see
+    /// [`CapturedSpan::name()`].
+    fn name(&self) -> &'a str;
     /// Returns a value for the specified field, or `None` if the value is not defined.
     fn value(&self, name: &str) -> Option<&'a TracedValue>;
     /// Returns the reference to the parent span, if any.
@@ -385,6 +1074,11 @@ impl<'a> Captured<'a> for CapturedSpan<'a> {
         self.metadata()
     }
 
+    #[inline]
+    fn name(&self) -> &'a str {
+        self.name()
+    }
+
     #[inline]
     fn value(&self, name: &str) -> Option<&'a TracedValue> {
         self.value(name)
@@ -404,6 +1098,11 @@ impl<'a> Captured<'a> for CapturedEvent<'a> {
         self.metadata()
     }
 
+    #[inline]
+    fn name(&self) -> &'a str {
+        self.metadata().name()
+    }
+
     #[inline]
     fn value(&self, name: &str) -> Option<&'a TracedValue> {
         self.value(name)
@@ -415,5 +1114,158 @@ impl<'a> Captured<'a> for CapturedEvent<'a> {
     }
 }
 
+impl<T: sealed::Sealed> sealed::Sealed for &T {}
+
+/// Allows evaluating generic predicates on references, e.g. in [`Iterator::filter()`].
+impl<'a, T: Captured<'a>> Captured<'a> for &T {
+    #[inline]
+    fn metadata(&self) -> &'static Metadata<'static> {
+        T::metadata(self)
+    }
+
+    #[inline]
+    fn name(&self) -> &'a str {
+        T::name(self)
+    }
+
+    #[inline]
+    fn value(&self, name: &str) -> Option<&'a TracedValue> {
+        T::value(self, name)
+    }
+
+    #[inline]
+    fn parent(&self) -> Option<CapturedSpan<'a>> {
+        T::parent(self)
+    }
+}
+
+/// Either a [`CapturedSpan`] or a [`CapturedEvent`]. Returned by [`Storage::scan_all()`].
+///
+/// Predicates generic over [`Captured`] items (e.g., [`field()`] and [`name()`]) can be evaluated
+/// on `CapturedItem`s directly. Span- or event-specific predicates can be applied using
+/// [`if_span()`] and [`if_event()`].
+///
+/// [`field()`]: crate::predicates::field()
+/// [`name()`]: crate::predicates::name()
+/// [`if_span()`]: crate::predicates::if_span()
+/// [`if_event()`]: crate::predicates::if_event()
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CapturedItem<'a> {
+    /// Captured span.
+    Span(CapturedSpan<'a>),
+    /// Captured event.
+    Event(CapturedEvent<'a>),
+}
+
+impl<'a> CapturedItem<'a> {
+    /// Returns the span if this item is a span.
+    pub fn as_span(&self) -> Option<CapturedSpan<'a>> {
+        match self {
+            Self::Span(span) => Some(*span),
+            Self::Event(_) => None,
+        }
+    }
+
+    /// Returns the event if this item is an event.
+    pub fn as_event(&self) -> Option<CapturedEvent<'a>> {
+        match self {
+            Self::Span(_) => None,
+            Self::Event(event) => Some(*event),
+        }
+    }
+
+    /// Returns the span contained in this item.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this item is an event.
+    #[track_caller]
+    pub fn expect_span(&self) -> CapturedSpan<'a> {
+        self.as_span()
+            .unwrap_or_else(|| panic!("expected a span, got {self:#?}"))
+    }
+
+    /// Returns the event contained in this item.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this item is a span.
+    #[track_caller]
+    pub fn expect_event(&self) -> CapturedEvent<'a> {
+        self.as_event()
+            .unwrap_or_else(|| panic!("expected an event, got {self:#?}"))
+    }
+}
+
+impl<'a> From<CapturedSpan<'a>> for CapturedItem<'a> {
+    fn from(span: CapturedSpan<'a>) -> Self {
+        Self::Span(span)
+    }
+}
+
+impl<'a> From<CapturedEvent<'a>> for CapturedItem<'a> {
+    fn from(event: CapturedEvent<'a>) -> Self {
+        Self::Event(event)
+    }
+}
+
+/// Items of the same kind are compared as spans or events; spans and events are incomparable.
+impl PartialOrd for CapturedItem<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+        match (self, other) {
+            (Self::Span(span), Self::Span(other)) => span.partial_cmp(other),
+            (Self::Event(event), Self::Event(other)) => event.partial_cmp(other),
+            _ => None,
+        }
+    }
+}
+
+impl ops::Index<&str> for CapturedItem<'_> {
+    type Output = TracedValue;
+
+    fn index(&self, index: &str) -> &Self::Output {
+        match self {
+            Self::Span(span) => &span[index],
+            Self::Event(event) => &event[index],
+        }
+    }
+}
+
+impl sealed::Sealed for CapturedItem<'_> {}
+
+impl<'a> Captured<'a> for CapturedItem<'a> {
+    #[inline]
+    fn metadata(&self) -> &'static Metadata<'static> {
+        match self {
+            Self::Span(span) => span.metadata(),
+            Self::Event(event) => event.metadata(),
+        }
+    }
+
+    #[inline]
+    fn name(&self) -> &'a str {
+        match self {
+            Self::Span(span) => span.name(),
+            Self::Event(event) => Captured::name(event),
+        }
+    }
+
+    #[inline]
+    fn value(&self, name: &str) -> Option<&'a TracedValue> {
+        match self {
+            Self::Span(span) => span.value(name),
+            Self::Event(event) => event.value(name),
+        }
+    }
+
+    #[inline]
+    fn parent(&self) -> Option<CapturedSpan<'a>> {
+        match self {
+            Self::Span(span) => span.parent(),
+            Self::Event(event) => event.parent(),
+        }
+    }
+}
+
 #[cfg(doctest)]
 doc_comment::doctest!("../README.md");
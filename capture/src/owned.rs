@@ -0,0 +1,135 @@
+//! Owned views of captured spans and events in storage snapshots.
+
+use std::{fmt, ptr, sync::Arc};
+
+use crate::{CapturedEvent, CapturedEventId, CapturedSpan, CapturedSpanId, Storage};
+
+/// Owned handle to a [`CapturedSpan`] in an immutable [`Storage`] snapshot returned by
+/// [`SharedStorage::snapshot()`](crate::SharedStorage::snapshot()).
+///
+/// Unlike `CapturedSpan`, the handle is not tied to a storage lock, so it can be sent to
+/// other threads or held across `.await` points. The span can be accessed via [`Self::get()`].
+///
+/// Handles are considered equal iff they point to the same span in the same snapshot.
+///
+/// # Examples
+///
+/// ```
+/// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+/// # use tracing_capture::{CaptureLayer, OwnedCapturedSpan, SharedStorage};
+/// let storage = SharedStorage::default();
+/// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+/// tracing::subscriber::with_default(subscriber, || {
+///     tracing::info_span!("compute", arg = 5_i32).in_scope(|| { /* do nothing */ });
+/// });
+///
+/// let snapshot = storage.snapshot();
+/// let spans: Vec<_> = snapshot
+///     .all_spans()
+///     .map(|span| OwnedCapturedSpan::new(&snapshot, span))
+///     .collect();
+/// std::thread::spawn(move || {
+///     assert_eq!(spans[0].get()["arg"], 5_i64);
+/// })
+/// .join()
+/// .unwrap();
+/// ```
+#[derive(Clone)]
+pub struct OwnedCapturedSpan {
+    storage: Arc<Storage>,
+    id: CapturedSpanId,
+}
+
+impl fmt::Debug for OwnedCapturedSpan {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.get(), formatter)
+    }
+}
+
+impl OwnedCapturedSpan {
+    /// Creates a handle for the `span` contained in the `storage` snapshot.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `span` does not belong to `storage`.
+    pub fn new(storage: &Arc<Storage>, span: CapturedSpan<'_>) -> Self {
+        assert!(
+            ptr::eq(span.storage, &**storage),
+            "span belongs to a different storage"
+        );
+        Self {
+            storage: Arc::clone(storage),
+            id: span.inner.id,
+        }
+    }
+
+    /// Returns the storage snapshot containing the span.
+    pub fn storage(&self) -> &Arc<Storage> {
+        &self.storage
+    }
+
+    /// Returns the referenced span.
+    pub fn get(&self) -> CapturedSpan<'_> {
+        self.storage.span(self.id)
+    }
+}
+
+impl PartialEq for OwnedCapturedSpan {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.storage, &other.storage) && self.id == other.id
+    }
+}
+
+impl Eq for OwnedCapturedSpan {}
+
+/// Owned handle to a [`CapturedEvent`] in an immutable [`Storage`] snapshot returned by
+/// [`SharedStorage::snapshot()`](crate::SharedStorage::snapshot()).
+///
+/// This is the event counterpart of [`OwnedCapturedSpan`]; see its docs for details.
+#[derive(Clone)]
+pub struct OwnedCapturedEvent {
+    storage: Arc<Storage>,
+    id: CapturedEventId,
+}
+
+impl fmt::Debug for OwnedCapturedEvent {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.get(), formatter)
+    }
+}
+
+impl OwnedCapturedEvent {
+    /// Creates a handle for the `event` contained in the `storage` snapshot.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `event` does not belong to `storage`.
+    pub fn new(storage: &Arc<Storage>, event: CapturedEvent<'_>) -> Self {
+        assert!(
+            ptr::eq(event.storage, &**storage),
+            "event belongs to a different storage"
+        );
+        Self {
+            storage: Arc::clone(storage),
+            id: event.inner.id,
+        }
+    }
+
+    /// Returns the storage snapshot containing the event.
+    pub fn storage(&self) -> &Arc<Storage> {
+        &self.storage
+    }
+
+    /// Returns the referenced event.
+    pub fn get(&self) -> CapturedEvent<'_> {
+        self.storage.event(self.id)
+    }
+}
+
+impl PartialEq for OwnedCapturedEvent {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.storage, &other.storage) && self.id == other.id
+    }
+}
+
+impl Eq for OwnedCapturedEvent {}
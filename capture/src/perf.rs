@@ -0,0 +1,45 @@
+//! Performance counters for `CaptureLayer` (enabled with the `perf` crate feature).
+
+use std::time::Duration;
+
+/// Performance counters for capturing spans and events into a [`Storage`](crate::Storage).
+/// Returned by [`Storage::perf_stats()`](crate::Storage::perf_stats()).
+///
+/// Counters are intended to quantify the overhead of capturing (e.g., in benchmarks
+/// or performance tests), and are only available with the `perf` crate feature.
+/// The feature is considered internal; the set of counters may change between minor releases.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct PerfStats {
+    /// Number of times the storage was locked for write by the layer.
+    pub lock_acquisitions: u64,
+    /// Total time spent waiting for the storage lock.
+    pub lock_wait_time: Duration,
+    /// Number of captured events.
+    pub events: u64,
+    /// Total time spent in [`Layer::on_event()`] for captured events, including waiting
+    /// for the storage lock.
+    ///
+    /// [`Layer::on_event()`]: tracing_subscriber::Layer::on_event()
+    pub on_event_time: Duration,
+    /// Estimated number of bytes allocated for captured data. Estimated in the same way
+    /// as [`MemoryUsage::total_bytes`](crate::MemoryUsage::total_bytes), but is cumulative;
+    /// e.g., it includes values overwritten by recording new span values.
+    pub allocated_bytes: u64,
+}
+
+impl PerfStats {
+    pub(crate) fn on_lock(&mut self, wait_time: Duration) {
+        self.lock_acquisitions += 1;
+        self.lock_wait_time += wait_time;
+    }
+
+    pub(crate) fn on_event(&mut self, elapsed: Duration) {
+        self.events += 1;
+        self.on_event_time += elapsed;
+    }
+
+    pub(crate) fn on_allocation(&mut self, bytes: usize) {
+        self.allocated_bytes += bytes as u64;
+    }
+}
@@ -0,0 +1,149 @@
+//! Predicates for byte `TracedValue`s: `bytes_len()` and `bytes_prefix()`.
+
+use predicates::{
+    reflection::{Case, Child, PredicateReflection, Product},
+    Predicate,
+};
+
+use std::fmt;
+
+use super::IntoFieldPredicate;
+use tracing_tunnel::TracedValue;
+
+fn non_bytes_case<'a, P: PredicateReflection>(
+    predicate: &'a P,
+    expected: bool,
+    variable: &TracedValue,
+) -> Option<Case<'a>> {
+    if expected {
+        None // was expecting bytes, but got another value
+    } else {
+        let product = Product::new("var", format!("{variable:?}"));
+        Some(Case::new(Some(predicate), expected).add_product(product))
+    }
+}
+
+/// Creates a predicate for the length of a [byte](TracedValue::Bytes) [`TracedValue`].
+/// Non-byte values never match.
+///
+/// Returned predicates can be supplied to the [`field()`](super::field()) function.
+///
+/// # Examples
+///
+/// ```
+/// # use predicates::ord::eq;
+/// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+/// # use tracing_capture::{predicates::{bytes_len, bytes_prefix, field, ScanExt}, CaptureLayer, SharedStorage};
+/// let storage = SharedStorage::default();
+/// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+/// tracing::subscriber::with_default(subscriber, || {
+///     let frame = b"\x01\x00\x04ping";
+///     tracing::info!(frame = &frame[..], "sent frame");
+/// });
+///
+/// let storage = storage.lock();
+/// let events = storage.scan_events();
+/// let _ = events.single(&field("frame", bytes_len(eq(7))));
+/// let _ = events.single(&field("frame", bytes_prefix([1, 0])));
+/// ```
+pub fn bytes_len<P: Predicate<usize>>(matches: P) -> BytesLenPredicate<P> {
+    BytesLenPredicate { matches }
+}
+
+/// Predicate for the length of a byte [`TracedValue`] returned by the [`bytes_len()`]
+/// function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BytesLenPredicate<P> {
+    matches: P,
+}
+
+impl<P: Predicate<usize>> fmt::Display for BytesLenPredicate<P> {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "bytes_len({})", self.matches)
+    }
+}
+
+impl<P: Predicate<usize>> PredicateReflection for BytesLenPredicate<P> {
+    fn children<'a>(&'a self) -> Box<dyn Iterator<Item = Child<'a>> + 'a> {
+        let children = vec![Child::new("len", &self.matches)];
+        Box::new(children.into_iter())
+    }
+}
+
+impl<P: Predicate<usize>> Predicate<TracedValue> for BytesLenPredicate<P> {
+    fn eval(&self, variable: &TracedValue) -> bool {
+        variable
+            .as_bytes()
+            .is_some_and(|bytes| self.matches.eval(&bytes.len()))
+    }
+
+    fn find_case(&self, expected: bool, variable: &TracedValue) -> Option<Case<'_>> {
+        let Some(bytes) = variable.as_bytes() else {
+            return non_bytes_case(self, expected, variable);
+        };
+        let child = self.matches.find_case(expected, &bytes.len())?;
+        Some(Case::new(Some(self), expected).add_child(child))
+    }
+}
+
+impl<P: Predicate<usize>> IntoFieldPredicate for BytesLenPredicate<P> {
+    type Predicate = Self;
+
+    fn into_predicate(self) -> Self::Predicate {
+        self
+    }
+}
+
+/// Creates a predicate checking that a [byte](TracedValue::Bytes) [`TracedValue`] starts
+/// with the specified `prefix`. Non-byte values never match.
+///
+/// Returned predicates can be supplied to the [`field()`](super::field()) function.
+/// See [`bytes_len()`] for an example of usage.
+pub fn bytes_prefix(prefix: impl AsRef<[u8]>) -> BytesPrefixPredicate {
+    BytesPrefixPredicate {
+        prefix: prefix.as_ref().to_vec(),
+    }
+}
+
+/// Predicate for the prefix of a byte [`TracedValue`] returned by the [`bytes_prefix()`]
+/// function.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BytesPrefixPredicate {
+    prefix: Vec<u8>,
+}
+
+impl fmt::Display for BytesPrefixPredicate {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "bytes_prefix({:?})", self.prefix)
+    }
+}
+
+impl PredicateReflection for BytesPrefixPredicate {}
+
+impl Predicate<TracedValue> for BytesPrefixPredicate {
+    fn eval(&self, variable: &TracedValue) -> bool {
+        variable
+            .as_bytes()
+            .is_some_and(|bytes| bytes.starts_with(&self.prefix))
+    }
+
+    fn find_case(&self, expected: bool, variable: &TracedValue) -> Option<Case<'_>> {
+        let Some(bytes) = variable.as_bytes() else {
+            return non_bytes_case(self, expected, variable);
+        };
+        if bytes.starts_with(&self.prefix) != expected {
+            return None;
+        }
+        let actual_prefix = &bytes[..bytes.len().min(self.prefix.len())];
+        let product = Product::new("prefix", format!("{actual_prefix:?}"));
+        Some(Case::new(Some(self), expected).add_product(product))
+    }
+}
+
+impl IntoFieldPredicate for BytesPrefixPredicate {
+    type Predicate = Self;
+
+    fn into_predicate(self) -> Self::Predicate {
+        self
+    }
+}
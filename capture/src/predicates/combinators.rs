@@ -1,7 +1,7 @@
 //! Helper combinators for predicates.
 
 use predicates::{
-    reflection::{Case, PredicateReflection},
+    reflection::{Case, Child, PredicateReflection},
     Predicate,
 };
 
@@ -27,7 +27,15 @@ impl<T: fmt::Display, U: fmt::Display> fmt::Display for And<T, U> {
     }
 }
 
-impl<T: PredicateReflection, U: PredicateReflection> PredicateReflection for And<T, U> {}
+impl<T: PredicateReflection, U: PredicateReflection> PredicateReflection for And<T, U> {
+    fn children<'a>(&'a self) -> Box<dyn Iterator<Item = Child<'a>> + 'a> {
+        let children = vec![
+            Child::new("left", &self.first),
+            Child::new("right", &self.second),
+        ];
+        Box::new(children.into_iter())
+    }
+}
 
 impl<T, U, Item: ?Sized> Predicate<Item> for And<T, U>
 where
@@ -80,7 +88,15 @@ impl<T: fmt::Display, U: fmt::Display> fmt::Display for Or<T, U> {
     }
 }
 
-impl<T: PredicateReflection, U: PredicateReflection> PredicateReflection for Or<T, U> {}
+impl<T: PredicateReflection, U: PredicateReflection> PredicateReflection for Or<T, U> {
+    fn children<'a>(&'a self) -> Box<dyn Iterator<Item = Child<'a>> + 'a> {
+        let children = vec![
+            Child::new("left", &self.first),
+            Child::new("right", &self.second),
+        ];
+        Box::new(children.into_iter())
+    }
+}
 
 impl<T, U, Item: ?Sized> Predicate<Item> for Or<T, U>
 where
@@ -114,6 +130,32 @@ where
 }
 
 macro_rules! impl_bool_ops {
+    ($name:ident) => {
+        impl<Rhs> core::ops::BitAnd<Rhs> for $name
+        where
+            Self: predicates::reflection::PredicateReflection,
+            Rhs: predicates::reflection::PredicateReflection,
+        {
+            type Output = $crate::predicates::And<Self, Rhs>;
+
+            fn bitand(self, rhs: Rhs) -> Self::Output {
+                $crate::predicates::And::new(self, rhs)
+            }
+        }
+
+        impl<Rhs> core::ops::BitOr<Rhs> for $name
+        where
+            Self: predicates::reflection::PredicateReflection,
+            Rhs: predicates::reflection::PredicateReflection,
+        {
+            type Output = $crate::predicates::Or<Self, Rhs>;
+
+            fn bitor(self, rhs: Rhs) -> Self::Output {
+                $crate::predicates::Or::new(self, rhs)
+            }
+        }
+    };
+
     ($name:ident <$($ty_var:ident),+>) => {
         impl<Rhs, $($ty_var,)+> core::ops::BitAnd<Rhs> for $name<$($ty_var,)+>
         where
@@ -141,5 +183,32 @@ macro_rules! impl_bool_ops {
     };
 }
 
+/// Implements `Predicate<&Item>` for a predicate by delegating to its `Predicate<Item>`
+/// implementation. Predicates generic over [`Captured`](crate::Captured) items do not need this,
+/// since `Captured` is implemented for references.
+macro_rules! impl_ref_predicate {
+    ($name:ident $(<$($ty_var:ident),+>)?: $item:ident) => {
+        impl<'r, 'a, $($($ty_var,)+)?> predicates::Predicate<&'r $crate::$item<'a>>
+            for $name $(<$($ty_var,)+>)?
+        where
+            Self: predicates::Predicate<$crate::$item<'a>>,
+        {
+            fn eval(&self, variable: &&'r $crate::$item<'a>) -> bool {
+                <Self as predicates::Predicate<$crate::$item<'a>>>::eval(self, variable)
+            }
+
+            fn find_case(
+                &self,
+                expected: bool,
+                variable: &&'r $crate::$item<'a>,
+            ) -> Option<predicates::reflection::Case<'_>> {
+                <Self as predicates::Predicate<$crate::$item<'a>>>::find_case(
+                    self, expected, variable,
+                )
+            }
+        }
+    };
+}
+
 impl_bool_ops!(And<T, U>);
 impl_bool_ops!(Or<T, U>);
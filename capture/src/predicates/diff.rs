@@ -0,0 +1,126 @@
+//! Human-readable diffs between expected and actual text values used in predicate cases.
+
+use std::fmt::Write as _;
+
+use tracing_tunnel::TracedValue;
+
+/// Minimum length of single-line texts to produce a diff for. Shorter values are easy
+/// to compare visually.
+const MIN_DIFF_LEN: usize = 32;
+/// Number of chars before the first difference displayed in a single-line diff.
+const CONTEXT_BEFORE: usize = 16;
+/// Number of chars starting from the first difference displayed in a single-line diff.
+const CONTEXT_AFTER: usize = 48;
+
+/// Expected values that may have a text representation to be diffed against actual values.
+pub trait DiffText {
+    fn diff_text(&self) -> Option<&str>;
+}
+
+macro_rules! impl_diff_text_for_primitives {
+    ($($ty:ty),+) => {
+        $(
+        impl DiffText for $ty {
+            fn diff_text(&self) -> Option<&str> {
+                None
+            }
+        }
+        )+
+    };
+}
+
+impl_diff_text_for_primitives!(bool, i64, i128, u64, u128, f64);
+
+impl DiffText for &str {
+    fn diff_text(&self) -> Option<&str> {
+        Some(self)
+    }
+}
+
+impl DiffText for TracedValue {
+    fn diff_text(&self) -> Option<&str> {
+        match self {
+            Self::String(s) => Some(s),
+            Self::Object(obj) => Some(obj.as_ref()),
+            Self::Error(err) => Some(&err.message),
+            _ => None,
+        }
+    }
+}
+
+/// Produces a diff between the text representations of the expected and actual values,
+/// or `None` if the values have no text representation, are equal, or are short enough
+/// to be compared without a diff.
+pub(crate) fn value_diff(expected: &impl DiffText, actual: &TracedValue) -> Option<String> {
+    text_diff(expected.diff_text()?, actual.diff_text()?)
+}
+
+pub(crate) fn text_diff(expected: &str, actual: &str) -> Option<String> {
+    if expected == actual {
+        return None;
+    }
+    if expected.contains('\n') || actual.contains('\n') {
+        Some(line_diff(expected, actual))
+    } else if expected.len().max(actual.len()) >= MIN_DIFF_LEN {
+        Some(char_diff(expected, actual))
+    } else {
+        None
+    }
+}
+
+/// Outputs differing lines after trimming the common prefix and suffix.
+fn line_diff(expected: &str, actual: &str) -> String {
+    let expected: Vec<_> = expected.lines().collect();
+    let actual: Vec<_> = actual.lines().collect();
+    let prefix_len = expected
+        .iter()
+        .zip(&actual)
+        .take_while(|(expected, actual)| expected == actual)
+        .count();
+    let max_suffix_len = expected.len().min(actual.len()) - prefix_len;
+    let suffix_len = expected
+        .iter()
+        .rev()
+        .zip(actual.iter().rev())
+        .take(max_suffix_len)
+        .take_while(|(expected, actual)| expected == actual)
+        .count();
+
+    let mut diff = format!("@@ line {} @@", prefix_len + 1);
+    for line in &expected[prefix_len..expected.len() - suffix_len] {
+        write!(diff, "\n- {line}").unwrap();
+    }
+    for line in &actual[prefix_len..actual.len() - suffix_len] {
+        write!(diff, "\n+ {line}").unwrap();
+    }
+    diff
+}
+
+/// Outputs the neighborhood of the first differing char.
+fn char_diff(expected: &str, actual: &str) -> String {
+    let diff_pos = expected
+        .chars()
+        .zip(actual.chars())
+        .take_while(|(expected, actual)| expected == actual)
+        .count();
+    let start = diff_pos.saturating_sub(CONTEXT_BEFORE);
+    format!(
+        "@@ char {diff_pos} @@\n- {}\n+ {}",
+        excerpt(expected, start),
+        excerpt(actual, start)
+    )
+}
+
+fn excerpt(s: &str, start: usize) -> String {
+    let char_count = s.chars().count();
+    let end = (start + CONTEXT_BEFORE + CONTEXT_AFTER).min(char_count);
+    let mut excerpt = String::new();
+    if start > 0 {
+        excerpt.push('…');
+    }
+    excerpt.extend(s.chars().skip(start).take(end.saturating_sub(start)));
+    if end < char_count {
+        excerpt.push('…');
+    }
+    excerpt
+}
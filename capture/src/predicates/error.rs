@@ -0,0 +1,196 @@
+//! Predicates for error `TracedValue`s: `error_message()` and `error_source_depth()`.
+
+use predicates::{
+    reflection::{Case, Child, PredicateReflection, Product},
+    Predicate,
+};
+
+use std::{fmt, iter};
+
+use super::IntoFieldPredicate;
+use tracing_tunnel::{TracedError, TracedValue};
+
+fn as_error(value: &TracedValue) -> Option<&TracedError> {
+    match value {
+        TracedValue::Error(err) => Some(err),
+        _ => None,
+    }
+}
+
+fn non_error_case<'a, P: PredicateReflection>(
+    predicate: &'a P,
+    expected: bool,
+    variable: &TracedValue,
+) -> Option<Case<'a>> {
+    if expected {
+        None // was expecting an error, but got another value
+    } else {
+        let product = Product::new("var", format!("{variable:?}"));
+        Some(Case::new(Some(predicate), expected).add_product(product))
+    }
+}
+
+/// Creates a predicate for the message of an error [`TracedValue`]. Only the message
+/// of the top-level error is checked; the messages of its [sources](TracedError::source)
+/// are not. Non-error values never match.
+///
+/// Returned predicates can be supplied to the [`field()`](super::field()) function.
+///
+/// # Examples
+///
+/// ```
+/// # use predicates::str::contains;
+/// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+/// # use tracing_capture::{predicates::{error_message, field, ScanExt}, CaptureLayer, SharedStorage};
+/// # use std::{error::Error, io};
+/// let storage = SharedStorage::default();
+/// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+/// tracing::subscriber::with_default(subscriber, || {
+///     let err = io::Error::new(io::ErrorKind::TimedOut, "connection timeout");
+///     tracing::warn!(error = &err as &dyn Error, "request failed");
+/// });
+///
+/// let storage = storage.lock();
+/// let _ = storage
+///     .scan_events()
+///     .single(&field("error", error_message(contains("timeout"))));
+/// ```
+pub fn error_message<P: Predicate<str>>(matches: P) -> ErrorMessagePredicate<P> {
+    ErrorMessagePredicate { matches }
+}
+
+/// Predicate for the message of an error [`TracedValue`] returned by the [`error_message()`]
+/// function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ErrorMessagePredicate<P> {
+    matches: P,
+}
+
+impl<P: Predicate<str>> fmt::Display for ErrorMessagePredicate<P> {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "error_message({})", self.matches)
+    }
+}
+
+impl<P: Predicate<str>> PredicateReflection for ErrorMessagePredicate<P> {
+    fn children<'a>(&'a self) -> Box<dyn Iterator<Item = Child<'a>> + 'a> {
+        let children = vec![Child::new("message", &self.matches)];
+        Box::new(children.into_iter())
+    }
+}
+
+impl<P: Predicate<str>> Predicate<TracedValue> for ErrorMessagePredicate<P> {
+    fn eval(&self, variable: &TracedValue) -> bool {
+        as_error(variable).is_some_and(|err| self.matches.eval(&err.message))
+    }
+
+    fn find_case(&self, expected: bool, variable: &TracedValue) -> Option<Case<'_>> {
+        let Some(err) = as_error(variable) else {
+            return non_error_case(self, expected, variable);
+        };
+        let child = self.matches.find_case(expected, &err.message)?;
+        Some(Case::new(Some(self), expected).add_child(child))
+    }
+}
+
+impl<P: Predicate<str>> IntoFieldPredicate for ErrorMessagePredicate<P> {
+    type Predicate = Self;
+
+    fn into_predicate(self) -> Self::Predicate {
+        self
+    }
+}
+
+/// Creates a predicate for the depth of an error [`TracedValue`], i.e., the number
+/// of [sources](TracedError::source) in its chain. An error without a source has depth 0.
+/// Non-error values never match.
+///
+/// This can be used to check that error context is preserved when errors are propagated
+/// through instrumented code. Returned predicates can be supplied to
+/// the [`field()`](super::field()) function.
+///
+/// # Examples
+///
+/// ```
+/// # use predicates::ord::{eq, ge};
+/// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+/// # use tracing_capture::{predicates::{error_source_depth, field, ScanExt}, CaptureLayer, SharedStorage};
+/// # use std::{error::Error, fmt, io};
+/// #[derive(Debug)]
+/// struct ContextError(io::Error);
+///
+/// impl fmt::Display for ContextError {
+///     fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+///         formatter.write_str("failed fetching data")
+///     }
+/// }
+///
+/// impl Error for ContextError {
+///     fn source(&self) -> Option<&(dyn Error + 'static)> {
+///         Some(&self.0)
+///     }
+/// }
+///
+/// let storage = SharedStorage::default();
+/// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+/// tracing::subscriber::with_default(subscriber, || {
+///     let err = ContextError(io::Error::new(io::ErrorKind::TimedOut, "timeout"));
+///     tracing::warn!(error = &err as &dyn Error, "request failed");
+/// });
+///
+/// let storage = storage.lock();
+/// let events = storage.scan_events();
+/// let _ = events.single(&field("error", error_source_depth(eq(1))));
+/// let _ = events.single(&field("error", error_source_depth(ge(1))));
+/// ```
+pub fn error_source_depth<P: Predicate<usize>>(matches: P) -> ErrorSourceDepthPredicate<P> {
+    ErrorSourceDepthPredicate { matches }
+}
+
+/// Predicate for the source chain depth of an error [`TracedValue`] returned by
+/// the [`error_source_depth()`] function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ErrorSourceDepthPredicate<P> {
+    matches: P,
+}
+
+impl<P: Predicate<usize>> ErrorSourceDepthPredicate<P> {
+    fn depth(err: &TracedError) -> usize {
+        iter::successors(err.source.as_deref(), |err| err.source.as_deref()).count()
+    }
+}
+
+impl<P: Predicate<usize>> fmt::Display for ErrorSourceDepthPredicate<P> {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "error_source_depth({})", self.matches)
+    }
+}
+
+impl<P: Predicate<usize>> PredicateReflection for ErrorSourceDepthPredicate<P> {
+    fn children<'a>(&'a self) -> Box<dyn Iterator<Item = Child<'a>> + 'a> {
+        let children = vec![Child::new("depth", &self.matches)];
+        Box::new(children.into_iter())
+    }
+}
+
+impl<P: Predicate<usize>> Predicate<TracedValue> for ErrorSourceDepthPredicate<P> {
+    fn eval(&self, variable: &TracedValue) -> bool {
+        as_error(variable).is_some_and(|err| self.matches.eval(&Self::depth(err)))
+    }
+
+    fn find_case(&self, expected: bool, variable: &TracedValue) -> Option<Case<'_>> {
+        let Some(err) = as_error(variable) else {
+            return non_error_case(self, expected, variable);
+        };
+        let child = self.matches.find_case(expected, &Self::depth(err))?;
+        Some(Case::new(Some(self), expected).add_child(child))
+    }
+}
+
+impl<P: Predicate<usize>> IntoFieldPredicate for ErrorSourceDepthPredicate<P> {
+    type Predicate = Self;
+
+    fn into_predicate(self) -> Self::Predicate {
+        self
+    }
+}
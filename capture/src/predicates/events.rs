@@ -0,0 +1,76 @@
+//! `event_count()` predicate factory.
+
+use predicates::{
+    reflection::{Case, Child, PredicateReflection},
+    Predicate,
+};
+
+use std::fmt;
+
+use crate::CapturedSpan;
+
+/// Creates a predicate for the number of [`CapturedEvent`]s directly attached to a [`CapturedSpan`].
+/// This can be used to catch excessive logging, e.g. logging on each iteration of a hot loop.
+/// See also [`CaptureLayer::with_max_events_per_span()`] for a similar check performed
+/// during capture.
+///
+/// [`CapturedEvent`]: crate::CapturedEvent
+/// [`CaptureLayer::with_max_events_per_span()`]: crate::CaptureLayer::with_max_events_per_span()
+///
+/// # Examples
+///
+/// ```
+/// # use predicates::ord::{eq, le};
+/// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+/// # use tracing_capture::{predicates::{event_count, name, ScanExt}, CaptureLayer, SharedStorage};
+/// let storage = SharedStorage::default();
+/// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+/// tracing::subscriber::with_default(subscriber, || {
+///     tracing::info_span!("compute").in_scope(|| {
+///         for i in 0..10 {
+///             tracing::debug!(i, "iteration");
+///         }
+///     });
+/// });
+///
+/// let storage = storage.lock();
+/// storage.scan_spans().all(&event_count(le(10)));
+/// let _ = storage.scan_spans().single(&(name(eq("compute")) & event_count(eq(10))));
+/// ```
+pub fn event_count<P: Predicate<usize>>(matches: P) -> EventCountPredicate<P> {
+    EventCountPredicate { matches }
+}
+
+/// Predicate for the number of events in a [`CapturedSpan`] returned by
+/// the [`event_count()`] function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EventCountPredicate<P> {
+    matches: P,
+}
+
+impl_bool_ops!(EventCountPredicate<P>);
+impl_ref_predicate!(EventCountPredicate<P>: CapturedSpan);
+
+impl<P: Predicate<usize>> fmt::Display for EventCountPredicate<P> {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "event_count({})", self.matches)
+    }
+}
+
+impl<P: Predicate<usize>> PredicateReflection for EventCountPredicate<P> {
+    fn children<'a>(&'a self) -> Box<dyn Iterator<Item = Child<'a>> + 'a> {
+        let children = vec![Child::new("count", &self.matches)];
+        Box::new(children.into_iter())
+    }
+}
+
+impl<P: Predicate<usize>> Predicate<CapturedSpan<'_>> for EventCountPredicate<P> {
+    fn eval(&self, variable: &CapturedSpan<'_>) -> bool {
+        self.matches.eval(&variable.events().len())
+    }
+
+    fn find_case(&self, expected: bool, variable: &CapturedSpan<'_>) -> Option<Case<'_>> {
+        let child = self.matches.find_case(expected, &variable.events().len())?;
+        Some(Case::new(Some(self), expected).add_child(child))
+    }
+}
@@ -2,9 +2,12 @@
 
 use predicates::Predicate;
 
-use std::fmt;
+use std::{fmt, iter};
 
-use crate::{CapturedEvent, CapturedEvents, CapturedSpan, CapturedSpans, DescendantSpans, Storage};
+use crate::{
+    CapturedEvent, CapturedEvents, CapturedItem, CapturedSpan, CapturedSpans, DescendantSpans,
+    Storage,
+};
 
 /// Helper to wrap holders of [`CapturedSpan`]s or [`CapturedEvent`]s
 /// (spans or the underlying [`Storage`]) so that they are more convenient to use with `Predicate`s.
@@ -34,6 +37,53 @@ impl<'a> ScanExt<'a> for &'a Storage {
     }
 }
 
+impl Storage {
+    /// Deeply scans all captured spans and events in the order of their capture. This allows
+    /// making assertions about spans and events at once, e.g., that there is exactly
+    /// one span or event mentioning a certain value. Like [`Self::all_spans()`], the scan
+    /// includes [placeholder spans](crate::CapturedSpan::is_placeholder()).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use predicates::str::contains;
+    /// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+    /// # use tracing_capture::{predicates::*, CaptureLayer, SharedStorage};
+    /// let storage = SharedStorage::default();
+    /// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     tracing::info_span!("request", id = 42_u64).in_scope(|| {
+    ///         tracing::info!("processing");
+    ///     });
+    ///     tracing::warn!(request_id = 23_u64, "request failed");
+    /// });
+    ///
+    /// let storage = storage.lock();
+    /// let item = storage
+    ///     .scan_all()
+    ///     .single(&(field("id", 42_u64) | field("request_id", 42_u64)));
+    /// assert_eq!(item.expect_span().metadata().name(), "request");
+    /// // Span- or event-specific predicates can be used as well.
+    /// let item = storage.scan_all().single(&if_event(message(contains("failed"))));
+    /// assert_eq!(item["request_id"], 23_u64);
+    /// ```
+    pub fn scan_all(&self) -> Scanner<&Self, impl Iterator<Item = CapturedItem<'_>> + '_> {
+        Scanner::new(self, |storage| {
+            // Spans and events are allocated in the capture order, so merging the arenas
+            // by the timeline index restores the overall capture order.
+            let mut spans = storage.all_spans().peekable();
+            let mut events = storage.all_events().peekable();
+            iter::from_fn(move || match (spans.peek(), events.peek()) {
+                (Some(span), Some(event)) if event.inner.seq < span.inner.created_seq => {
+                    events.next().map(CapturedItem::Event)
+                }
+                (Some(_), _) => spans.next().map(CapturedItem::Span),
+                (None, _) => events.next().map(CapturedItem::Event),
+            })
+        })
+    }
+}
+
 /// Scans for `CapturedSpan` are shallow, i.e. include only direct children spans / events.
 impl<'a> ScanExt<'a> for CapturedSpan<'a> {
     fn scan_spans(self) -> Scanner<Self, CapturedSpans<'a>> {
@@ -55,6 +105,68 @@ impl<'a> CapturedSpan<'a> {
     pub fn deep_scan_events(self) -> Scanner<Self, impl Iterator<Item = CapturedEvent<'a>> + 'a> {
         Scanner::new(self, |span| span.events().chain(span.descendant_events()))
     }
+
+    /// Scans all descendants of this span. This is equivalent to [`Self::deep_scan_spans()`]
+    /// and is provided for symmetry with [`Self::scan_ancestors()`].
+    pub fn scan_descendants(self) -> Scanner<Self, DescendantSpans<'a>> {
+        self.deep_scan_spans()
+    }
+
+    /// Scans all [ancestors](Self::ancestors()) of this span, starting from the direct parent.
+    pub fn scan_ancestors(self) -> Scanner<Self, impl Iterator<Item = CapturedSpan<'a>> + 'a> {
+        Scanner::new(self, |span| {
+            iter::successors(span.parent(), CapturedSpan::parent)
+        })
+    }
+
+    /// Returns the nearest [ancestor](Self::ancestors()) of this span matching the provided
+    /// predicate, or `None` if no ancestor matches.
+    pub fn ancestor_matching<P>(&self, predicate: &P) -> Option<CapturedSpan<'a>>
+    where
+        P: Predicate<CapturedSpan<'a>> + ?Sized,
+    {
+        self.ancestors().find(|span| predicate.eval(span))
+    }
+}
+
+impl<'a> CapturedEvent<'a> {
+    /// Scans all [ancestors](Self::ancestors()) of this event, starting from the direct parent.
+    pub fn scan_ancestors(self) -> Scanner<Self, impl Iterator<Item = CapturedSpan<'a>> + 'a> {
+        Scanner::new(self, |event| {
+            iter::successors(event.parent(), CapturedSpan::parent)
+        })
+    }
+
+    /// Returns the nearest [ancestor](Self::ancestors()) of this event matching the provided
+    /// predicate, or `None` if no ancestor matches.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_core::Level;
+    /// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+    /// # use predicates::ord::eq;
+    /// # use tracing_capture::{predicates::*, CaptureLayer, SharedStorage};
+    /// let storage = SharedStorage::default();
+    /// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     let _outer = tracing::info_span!("outer", id = 1).entered();
+    ///     let _inner = tracing::debug_span!("inner").entered();
+    ///     tracing::info!("test event");
+    /// });
+    ///
+    /// let storage = storage.lock();
+    /// let event = storage.all_events().next().unwrap();
+    /// let outer = event.ancestor_matching(&level(Level::INFO)).unwrap();
+    /// assert_eq!(outer.metadata().name(), "outer");
+    /// assert!(event.ancestor_matching(&name(eq("other"))).is_none());
+    /// ```
+    pub fn ancestor_matching<P>(&self, predicate: &P) -> Option<CapturedSpan<'a>>
+    where
+        P: Predicate<CapturedSpan<'a>> + ?Sized,
+    {
+        self.ancestors().find(|span| predicate.eval(span))
+    }
 }
 
 /// Helper that allows using `Predicate`s rather than closures to find matching elements,
@@ -146,6 +258,62 @@ where
             panic!("item matched predicate {predicate}: {item:#?}");
         }
     }
+
+    /// Checks that a (not necessarily contiguous) subsequence of items matches the provided
+    /// predicates in order, and returns the matched items. Each predicate is matched against
+    /// the first suitable item after the item matched by the previous predicate.
+    ///
+    /// This is useful for protocol-like assertions, e.g. that a request is received,
+    /// then processed, and then a response is sent, possibly with other events in between.
+    ///
+    /// # Panics
+    ///
+    /// Panics with an informative message naming the first unmatched predicate.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use predicates::ord::eq;
+    /// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+    /// # use tracing_capture::{predicates::*, CaptureLayer, SharedStorage};
+    /// let storage = SharedStorage::default();
+    /// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     tracing::info!("connected");
+    ///     tracing::debug!("sent handshake");
+    ///     tracing::info!(id = 1, "received request");
+    ///     tracing::info!(id = 1, "sent response");
+    ///     tracing::info!("disconnected");
+    /// });
+    ///
+    /// let storage = storage.lock();
+    /// let events = storage.scan_events();
+    /// let matched = events.subsequence(&[
+    ///     &message(eq("connected")),
+    ///     &(message(eq("received request")) & field("id", 1_i64)),
+    ///     &message(eq("disconnected")),
+    /// ]);
+    /// assert_eq!(matched.len(), 3);
+    /// ```
+    pub fn subsequence(self, predicates: &[&dyn Predicate<I::Item>]) -> Vec<I::Item> {
+        let mut iter = self.iter();
+        let mut matched = Vec::with_capacity(predicates.len());
+        for (idx, &predicate) in predicates.iter().enumerate() {
+            let item = iter.find(|item| predicate.eval(item));
+            if let Some(item) = item {
+                matched.push(item);
+            } else if let Some(prev_item) = matched.last() {
+                panic!(
+                    "no items have matched predicate #{idx} {predicate} in subsequence \
+                     after item matched by predicate #{}: {prev_item:#?}",
+                    idx - 1
+                );
+            } else {
+                panic!("no items have matched predicate #0 {predicate} in subsequence");
+            }
+        }
+        matched
+    }
 }
 
 impl<T, I> Scanner<T, I>
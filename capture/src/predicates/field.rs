@@ -1,13 +1,15 @@
-//! `field()` and `message()` predicate factories.
+//! `field()`, `inherited_field()`, `any_field()`, `fields_subset()`, `field_count()`
+//! and `message()` predicate factories.
 
 use predicates::{
-    reflection::{Case, PredicateReflection, Product},
+    reflection::{Case, Child, Parameter, PredicateReflection, Product},
     Predicate,
 };
 
 use std::{any::type_name, borrow::Borrow, fmt, marker::PhantomData};
 
-use crate::{Captured, CapturedEvent};
+use super::diff::{value_diff, DiffText};
+use crate::{Captured, CapturedEvent, CapturedSpan};
 use tracing_tunnel::{FromTracedValue, TracedValue};
 
 /// Conversion into a predicate for a [`TracedValue`] used in the [`field()`] function.
@@ -53,6 +55,9 @@ impl_into_field_predicate!(bool, i64, i128, u64, u128, f64, &str);
 /// - `bool`, `i64`, `i128`, `u64`, `u128`, `f64`, `&str`: will be compared to the `TracedValue`
 ///   using the corresponding [`PartialEq`] implementation.
 /// - A predicate produced by the [`value()`] function.
+/// - A numeric predicate produced by [`num_eq()`](super::num_eq()) and similar functions.
+/// - An error predicate produced by [`error_message()`](super::error_message())
+///   or [`error_source_depth()`](super::error_source_depth()).
 /// - Any `Predicate` for [`TracedValue`]. To bypass Rust orphaning rules, the predicate
 ///   must be enclosed in square brackets (i.e., a one-value array).
 ///
@@ -107,13 +112,23 @@ impl<P: Predicate<TracedValue>> fmt::Display for FieldPredicate<P> {
     }
 }
 
-impl<P: Predicate<TracedValue>> PredicateReflection for FieldPredicate<P> {}
+impl<P: Predicate<TracedValue>> PredicateReflection for FieldPredicate<P> {
+    fn parameters<'a>(&'a self) -> Box<dyn Iterator<Item = Parameter<'a>> + 'a> {
+        let params = vec![Parameter::new("name", &self.name)];
+        Box::new(params.into_iter())
+    }
+
+    fn children<'a>(&'a self) -> Box<dyn Iterator<Item = Child<'a>> + 'a> {
+        let children = vec![Child::new("value", &self.matches)];
+        Box::new(children.into_iter())
+    }
+}
 
 impl<'a, P: Predicate<TracedValue>, T: Captured<'a>> Predicate<T> for FieldPredicate<P> {
     fn eval(&self, variable: &T) -> bool {
         variable
             .value(self.name)
-            .map_or(false, |value| self.matches.eval(value))
+            .is_some_and(|value| self.matches.eval(value))
     }
 
     fn find_case(&self, expected: bool, variable: &T) -> Option<Case<'_>> {
@@ -131,6 +146,472 @@ impl<'a, P: Predicate<TracedValue>, T: Captured<'a>> Predicate<T> for FieldPredi
     }
 }
 
+/// Creates a predicate for a field of a [`CapturedSpan`] or [`CapturedEvent`] that may be
+/// inherited from ancestor spans. The field value is taken from the span / event itself or,
+/// if it is not defined there, from the nearest ancestor span defining it;
+/// see [`CapturedSpan::inherited_value()`].
+///
+/// The `matches` argument has the same semantics as in the [`field()`] function.
+///
+/// [`CapturedSpan`]: crate::CapturedSpan
+/// [`CapturedSpan::inherited_value()`]: crate::CapturedSpan::inherited_value()
+///
+/// # Examples
+///
+/// ```
+/// # use predicates::ord::eq;
+/// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+/// # use tracing_capture::{
+/// #     predicates::{field, inherited_field, level, ScanExt},
+/// #     CaptureLayer, SharedStorage,
+/// # };
+/// # use tracing_core::Level;
+/// let storage = SharedStorage::default();
+/// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+/// tracing::subscriber::with_default(subscriber, || {
+///     let _request = tracing::info_span!("request", request_id = 42_u64).entered();
+///     tracing::info_span!("query").in_scope(|| tracing::warn!("slow query"));
+/// });
+///
+/// let storage = storage.lock();
+/// let events = storage.scan_events();
+/// events.none(&field("request_id", 42_u64));
+/// let _ = events.single(&(level(Level::WARN) & inherited_field("request_id", 42_u64)));
+/// ```
+pub fn inherited_field<P: IntoFieldPredicate>(
+    name: &'static str,
+    matches: P,
+) -> InheritedFieldPredicate<P::Predicate> {
+    InheritedFieldPredicate {
+        name,
+        matches: matches.into_predicate(),
+    }
+}
+
+/// Predicate for a possibly inherited field of a [`CapturedSpan`] or [`CapturedEvent`]
+/// returned by the [`inherited_field()`] function.
+///
+/// [`CapturedSpan`]: crate::CapturedSpan
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InheritedFieldPredicate<P> {
+    name: &'static str,
+    matches: P,
+}
+
+impl_bool_ops!(InheritedFieldPredicate<P>);
+
+impl<P> InheritedFieldPredicate<P> {
+    fn inherited_value<'a, T: Captured<'a>>(&self, variable: &T) -> Option<&'a TracedValue> {
+        variable
+            .value(self.name)
+            .or_else(|| variable.parent()?.inherited_value(self.name))
+    }
+}
+
+impl<P: Predicate<TracedValue>> fmt::Display for InheritedFieldPredicate<P> {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "inherited.{}({})", self.name, self.matches)
+    }
+}
+
+impl<P: Predicate<TracedValue>> PredicateReflection for InheritedFieldPredicate<P> {
+    fn parameters<'a>(&'a self) -> Box<dyn Iterator<Item = Parameter<'a>> + 'a> {
+        let params = vec![Parameter::new("name", &self.name)];
+        Box::new(params.into_iter())
+    }
+
+    fn children<'a>(&'a self) -> Box<dyn Iterator<Item = Child<'a>> + 'a> {
+        let children = vec![Child::new("value", &self.matches)];
+        Box::new(children.into_iter())
+    }
+}
+
+impl<'a, P: Predicate<TracedValue>, T: Captured<'a>> Predicate<T> for InheritedFieldPredicate<P> {
+    fn eval(&self, variable: &T) -> bool {
+        self.inherited_value(variable)
+            .is_some_and(|value| self.matches.eval(value))
+    }
+
+    fn find_case(&self, expected: bool, variable: &T) -> Option<Case<'_>> {
+        let Some(value) = self.inherited_value(variable) else {
+            return if expected {
+                None // was expecting a variable, but there is none
+            } else {
+                let product = Product::new(format!("inherited.{}", self.name), "None");
+                Some(Case::new(Some(self), expected).add_product(product))
+            };
+        };
+
+        let child = self.matches.find_case(expected, value)?;
+        Some(Case::new(Some(self), expected).add_child(child))
+    }
+}
+
+/// Creates a predicate checking that a [`CapturedSpan`] or [`CapturedEvent`] has at least one
+/// field with the name matching `name` and the value matching `matches`. This is useful
+/// if field names are not known in advance, e.g. if instrumentation nests field names
+/// dynamically (`user.id`, `user.email`, etc.).
+///
+/// # Arguments
+///
+/// - `name` is a `str`ing predicate for the field name, e.g. one produced by
+///   the [`matching()`](super::matching()) function.
+/// - `matches` is a predicate for the field value; it has the same semantics
+///   as in the [`field()`] function.
+///
+/// [`CapturedSpan`]: crate::CapturedSpan
+///
+/// # Examples
+///
+/// ```
+/// # use predicates::str::is_empty;
+/// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+/// # use tracing_capture::{
+/// #     predicates::{any_field, matching, value, ScanExt}, CaptureLayer, SharedStorage,
+/// # };
+/// let storage = SharedStorage::default();
+/// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+/// tracing::subscriber::with_default(subscriber, || {
+///     tracing::info_span!("request", user.id = 42_u64, user.email = "me@example.com")
+///         .in_scope(|| tracing::info!("done"));
+/// });
+///
+/// let storage = storage.lock();
+/// let spans = storage.scan_spans();
+/// let _ = spans.single(&any_field(matching("user.*"), 42_u64));
+/// spans.none(&any_field(matching("user.*"), value(is_empty())));
+/// ```
+pub fn any_field<N, P>(name: N, matches: P) -> AnyFieldPredicate<N, P::Predicate>
+where
+    N: Predicate<str>,
+    P: IntoFieldPredicate,
+{
+    AnyFieldPredicate {
+        name,
+        matches: matches.into_predicate(),
+    }
+}
+
+/// Predicate for fields of a [`CapturedSpan`] or [`CapturedEvent`] selected by name returned by
+/// the [`any_field()`] function.
+///
+/// [`CapturedSpan`]: crate::CapturedSpan
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AnyFieldPredicate<N, P> {
+    name: N,
+    matches: P,
+}
+
+impl_bool_ops!(AnyFieldPredicate<N, P>);
+impl_ref_predicate!(AnyFieldPredicate<N, P>: CapturedSpan);
+impl_ref_predicate!(AnyFieldPredicate<N, P>: CapturedEvent);
+
+impl<N: Predicate<str>, P: Predicate<TracedValue>> fmt::Display for AnyFieldPredicate<N, P> {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "any_field({}, {})", self.name, self.matches)
+    }
+}
+
+impl<N: Predicate<str>, P: Predicate<TracedValue>> PredicateReflection for AnyFieldPredicate<N, P> {
+    fn children<'a>(&'a self) -> Box<dyn Iterator<Item = Child<'a>> + 'a> {
+        let children = vec![
+            Child::new("name", &self.name),
+            Child::new("value", &self.matches),
+        ];
+        Box::new(children.into_iter())
+    }
+}
+
+impl<N: Predicate<str>, P: Predicate<TracedValue>> AnyFieldPredicate<N, P> {
+    fn eval_values<'a>(
+        &self,
+        mut values: impl Iterator<Item = (&'a str, &'a TracedValue)>,
+    ) -> bool {
+        values.any(|(name, value)| self.name.eval(name) && self.matches.eval(value))
+    }
+
+    fn find_case_for_values<'a>(
+        &self,
+        expected: bool,
+        values: impl Iterator<Item = (&'a str, &'a TracedValue)>,
+    ) -> Option<Case<'_>> {
+        let mut values = values.filter(|(name, _)| self.name.eval(name)).peekable();
+        if expected {
+            let (name, child) = values.find_map(|(name, value)| {
+                let child = self.matches.find_case(true, value)?;
+                Some((name, child))
+            })?;
+            let product = Product::new("field", name.to_owned());
+            return Some(
+                Case::new(Some(self), expected)
+                    .add_product(product)
+                    .add_child(child),
+            );
+        }
+
+        if values.peek().is_none() {
+            let product = Product::new("fields", "None");
+            return Some(Case::new(Some(self), expected).add_product(product));
+        }
+        let mut case = Case::new(Some(self), expected);
+        for (name, value) in values {
+            if self.matches.eval(value) {
+                return None;
+            }
+            case = case.add_product(Product::new(format!("fields.{name}"), format!("{value:?}")));
+        }
+        Some(case)
+    }
+}
+
+impl<N: Predicate<str>, P: Predicate<TracedValue>> Predicate<CapturedSpan<'_>>
+    for AnyFieldPredicate<N, P>
+{
+    fn eval(&self, variable: &CapturedSpan<'_>) -> bool {
+        self.eval_values(variable.values())
+    }
+
+    fn find_case(&self, expected: bool, variable: &CapturedSpan<'_>) -> Option<Case<'_>> {
+        self.find_case_for_values(expected, variable.values())
+    }
+}
+
+impl<N: Predicate<str>, P: Predicate<TracedValue>> Predicate<CapturedEvent<'_>>
+    for AnyFieldPredicate<N, P>
+{
+    fn eval(&self, variable: &CapturedEvent<'_>) -> bool {
+        self.eval_values(variable.values())
+    }
+
+    fn find_case(&self, expected: bool, variable: &CapturedEvent<'_>) -> Option<Case<'_>> {
+        self.find_case_for_values(expected, variable.values())
+    }
+}
+
+/// Creates a predicate checking that a [`CapturedSpan`] or [`CapturedEvent`] contains all
+/// specified fields with the specified values. Other fields are ignored.
+///
+/// Values are compared in the same way as in the [`field()`] predicate with a primitive value;
+/// e.g., a signed integer value never equals an unsigned one. Unlike combining multiple
+/// [`field()`] predicates, all mismatched fields are reported at once on failure.
+///
+/// [`CapturedSpan`]: crate::CapturedSpan
+///
+/// # Examples
+///
+/// ```
+/// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+/// # use tracing_capture::{predicates::{fields_subset, ScanExt}, CaptureLayer, SharedStorage};
+/// let storage = SharedStorage::default();
+/// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+/// tracing::subscriber::with_default(subscriber, || {
+///     tracing::info_span!("compute", arg = 5_i32, name = "test", flag = true).in_scope(|| {
+///         tracing::info!("done");
+///     });
+/// });
+///
+/// let storage = storage.lock();
+/// let predicate = fields_subset([("arg", 5_i64.into()), ("name", "test".into())]);
+/// let _ = storage.scan_spans().single(&predicate);
+/// ```
+pub fn fields_subset<I>(fields: I) -> FieldsSubsetPredicate
+where
+    I: IntoIterator<Item = (&'static str, TracedValue)>,
+{
+    FieldsSubsetPredicate {
+        fields: fields.into_iter().collect(),
+    }
+}
+
+/// Predicate for a subset of fields of a [`CapturedSpan`] or [`CapturedEvent`] returned by
+/// the [`fields_subset()`] function.
+///
+/// [`CapturedSpan`]: crate::CapturedSpan
+#[derive(Debug, Clone)]
+pub struct FieldsSubsetPredicate {
+    fields: Vec<(&'static str, TracedValue)>,
+}
+
+impl_bool_ops!(FieldsSubsetPredicate);
+
+impl fmt::Display for FieldsSubsetPredicate {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("fields_subset(")?;
+        for (i, (name, value)) in self.fields.iter().enumerate() {
+            let separator = if i + 1 == self.fields.len() { "" } else { ", " };
+            write!(formatter, "{name} == {value:?}{separator}")?;
+        }
+        formatter.write_str(")")
+    }
+}
+
+impl PredicateReflection for FieldsSubsetPredicate {}
+
+impl FieldsSubsetPredicate {
+    pub(crate) fn values_eq(expected: &TracedValue, actual: &TracedValue) -> bool {
+        match expected {
+            TracedValue::Bool(value) => actual == value,
+            TracedValue::Int(value) => actual == value,
+            TracedValue::UInt(value) => actual == value,
+            TracedValue::Float(value) => actual == value,
+            TracedValue::String(value) => actual == value.as_str(),
+            TracedValue::Object(obj) => {
+                matches!(actual, TracedValue::Object(other) if obj.as_ref() == other.as_ref())
+            }
+            TracedValue::Error(err) => {
+                matches!(actual, TracedValue::Error(other) if err.message == other.message)
+            }
+            TracedValue::Bytes(bytes) => actual.as_bytes() == Some(bytes.as_slice()),
+            _ => false,
+        }
+    }
+
+    fn mismatched_fields<'a, 'p>(
+        &'p self,
+        variable: &impl Captured<'a>,
+    ) -> impl Iterator<Item = (&'static str, Option<&'a TracedValue>)> + 'p
+    where
+        'a: 'p,
+    {
+        let variable_values: Vec<_> = self
+            .fields
+            .iter()
+            .map(|(name, _)| variable.value(name))
+            .collect();
+        self.fields.iter().zip(variable_values).filter_map(
+            |((name, expected), actual)| match actual {
+                Some(actual) if Self::values_eq(expected, actual) => None,
+                _ => Some((*name, actual)),
+            },
+        )
+    }
+}
+
+impl<'a, T: Captured<'a>> Predicate<T> for FieldsSubsetPredicate {
+    fn eval(&self, variable: &T) -> bool {
+        self.mismatched_fields(variable).next().is_none()
+    }
+
+    fn find_case(&self, expected: bool, variable: &T) -> Option<Case<'_>> {
+        let mut mismatched_fields = self.mismatched_fields(variable).peekable();
+        if mismatched_fields.peek().is_none() != expected {
+            return None;
+        }
+
+        let mut case = Case::new(Some(self), expected);
+        if expected {
+            for (name, _) in &self.fields {
+                if let Some(value) = variable.value(name) {
+                    let product = Product::new(format!("fields.{name}"), format!("{value:?}"));
+                    case = case.add_product(product);
+                }
+            }
+        } else {
+            for (name, actual) in mismatched_fields {
+                let Some(actual) = actual else {
+                    case = case.add_product(Product::new(format!("fields.{name}"), "None"));
+                    continue;
+                };
+                case = case.add_product(Product::new(
+                    format!("fields.{name}"),
+                    format!("{actual:?}"),
+                ));
+                let expected = self
+                    .fields
+                    .iter()
+                    .find(|(field_name, _)| *field_name == name);
+                if let Some(diff) = expected.and_then(|(_, expected)| value_diff(expected, actual))
+                {
+                    case = case.add_product(Product::new(format!("fields.{name}.diff"), diff));
+                }
+            }
+        }
+        Some(case)
+    }
+}
+
+/// Creates a predicate for the number of fields in a [`CapturedSpan`] or [`CapturedEvent`].
+/// For events, the `message` field is counted as well.
+///
+/// # Arguments
+///
+/// The argument of this function is a `usize` predicate for the number of fields.
+///
+/// # Examples
+///
+/// ```
+/// # use predicates::ord::{eq, ge};
+/// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+/// # use tracing_capture::{predicates::{field_count, ScanExt}, CaptureLayer, SharedStorage};
+/// let storage = SharedStorage::default();
+/// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+/// tracing::subscriber::with_default(subscriber, || {
+///     tracing::info_span!("compute", arg = 5_i32, name = "test").in_scope(|| {
+///         tracing::info!(result = 42, "done");
+///     });
+/// });
+///
+/// let storage = storage.lock();
+/// let _ = storage.scan_spans().single(&field_count(eq(2)));
+/// let _ = storage.scan_events().single(&field_count(ge(2)));
+/// ```
+pub fn field_count<P: Predicate<usize>>(matches: P) -> FieldCountPredicate<P> {
+    FieldCountPredicate { matches }
+}
+
+/// Predicate for the number of fields in a [`CapturedSpan`] or [`CapturedEvent`] returned by
+/// the [`field_count()`] function.
+///
+/// [`CapturedSpan`]: crate::CapturedSpan
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FieldCountPredicate<P> {
+    matches: P,
+}
+
+impl_bool_ops!(FieldCountPredicate<P>);
+impl_ref_predicate!(FieldCountPredicate<P>: CapturedSpan);
+impl_ref_predicate!(FieldCountPredicate<P>: CapturedEvent);
+
+impl<P: Predicate<usize>> fmt::Display for FieldCountPredicate<P> {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "field_count({})", self.matches)
+    }
+}
+
+impl<P: Predicate<usize>> PredicateReflection for FieldCountPredicate<P> {
+    fn children<'a>(&'a self) -> Box<dyn Iterator<Item = Child<'a>> + 'a> {
+        let children = vec![Child::new("count", &self.matches)];
+        Box::new(children.into_iter())
+    }
+}
+
+impl<P: Predicate<usize>> FieldCountPredicate<P> {
+    fn find_case_for_count(&self, expected: bool, count: usize) -> Option<Case<'_>> {
+        let child = self.matches.find_case(expected, &count)?;
+        Some(Case::new(Some(self), expected).add_child(child))
+    }
+}
+
+impl<P: Predicate<usize>> Predicate<CapturedSpan<'_>> for FieldCountPredicate<P> {
+    fn eval(&self, variable: &CapturedSpan<'_>) -> bool {
+        self.matches.eval(&variable.values().count())
+    }
+
+    fn find_case(&self, expected: bool, variable: &CapturedSpan<'_>) -> Option<Case<'_>> {
+        self.find_case_for_count(expected, variable.values().count())
+    }
+}
+
+impl<P: Predicate<usize>> Predicate<CapturedEvent<'_>> for FieldCountPredicate<P> {
+    fn eval(&self, variable: &CapturedEvent<'_>) -> bool {
+        self.matches.eval(&variable.values().count())
+    }
+
+    fn find_case(&self, expected: bool, variable: &CapturedEvent<'_>) -> Option<Case<'_>> {
+        self.find_case_for_count(expected, variable.values().count())
+    }
+}
+
 #[doc(hidden)] // implementation detail (yet?)
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct EquivPredicate<V> {
@@ -145,18 +626,26 @@ impl<V: fmt::Debug> fmt::Display for EquivPredicate<V> {
 
 impl<V: fmt::Debug> PredicateReflection for EquivPredicate<V> {}
 
-impl<V: fmt::Debug + PartialEq<TracedValue>> Predicate<TracedValue> for EquivPredicate<V> {
+impl<V> Predicate<TracedValue> for EquivPredicate<V>
+where
+    V: fmt::Debug + PartialEq<TracedValue> + DiffText,
+{
     fn eval(&self, variable: &TracedValue) -> bool {
         self.value == *variable
     }
 
     fn find_case(&self, expected: bool, variable: &TracedValue) -> Option<Case<'_>> {
-        if self.eval(variable) == expected {
-            let product = Product::new("var", format!("{variable:?}"));
-            Some(Case::new(Some(self), expected).add_product(product))
-        } else {
-            None
+        if self.eval(variable) != expected {
+            return None;
+        }
+        let product = Product::new("var", format!("{variable:?}"));
+        let mut case = Case::new(Some(self), expected).add_product(product);
+        if !expected {
+            if let Some(diff) = value_diff(&self.value, variable) {
+                case = case.add_product(Product::new("diff", diff));
+            }
         }
+        Some(case)
     }
 }
 
@@ -232,6 +721,10 @@ where
     T: for<'a> FromTracedValue<'a> + ?Sized,
     P: Predicate<T>,
 {
+    fn children<'a>(&'a self) -> Box<dyn Iterator<Item = Child<'a>> + 'a> {
+        let children = vec![Child::new("value", &self.matches)];
+        Box::new(children.into_iter())
+    }
 }
 
 impl<T, P> Predicate<TracedValue> for ValuePredicate<T, P>
@@ -240,7 +733,7 @@ where
     P: Predicate<T>,
 {
     fn eval(&self, variable: &TracedValue) -> bool {
-        T::from_value(variable).map_or(false, |value| self.matches.eval(value.borrow()))
+        T::from_value(variable).is_some_and(|value| self.matches.eval(value.borrow()))
     }
 
     fn find_case(&self, expected: bool, variable: &TracedValue) -> Option<Case<'_>> {
@@ -310,6 +803,7 @@ pub struct MessagePredicate<P> {
 }
 
 impl_bool_ops!(MessagePredicate<P>);
+impl_ref_predicate!(MessagePredicate<P>: CapturedEvent);
 
 impl<P: Predicate<str>> fmt::Display for MessagePredicate<P> {
     fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -317,13 +811,18 @@ impl<P: Predicate<str>> fmt::Display for MessagePredicate<P> {
     }
 }
 
-impl<P: Predicate<str>> PredicateReflection for MessagePredicate<P> {}
+impl<P: Predicate<str>> PredicateReflection for MessagePredicate<P> {
+    fn children<'a>(&'a self) -> Box<dyn Iterator<Item = Child<'a>> + 'a> {
+        let children = vec![Child::new("message", &self.matches)];
+        Box::new(children.into_iter())
+    }
+}
 
 impl<P: Predicate<str>> Predicate<CapturedEvent<'_>> for MessagePredicate<P> {
     fn eval(&self, variable: &CapturedEvent<'_>) -> bool {
         variable
             .message()
-            .map_or(false, |value| self.matches.eval(value))
+            .is_some_and(|value| self.matches.eval(value))
     }
 
     fn find_case(&self, expected: bool, variable: &CapturedEvent<'_>) -> Option<Case<'_>> {
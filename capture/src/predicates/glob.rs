@@ -0,0 +1,98 @@
+//! `matching()` predicate factory.
+
+use predicates::{
+    reflection::{Case, Parameter, PredicateReflection, Product},
+    Predicate,
+};
+
+use std::fmt;
+
+/// Creates a `str`ing predicate checking whether a string matches the specified glob pattern.
+/// The pattern may contain `*` (matches any sequence of chars, including an empty one)
+/// and `?` (matches exactly one char) wildcards; other chars are matched literally.
+///
+/// The predicate can be used with [`any_field()`](super::any_field()) to select fields
+/// by name, or with any other function accepting a `str`ing predicate, such as [`name()`].
+///
+/// [`name()`]: super::name()
+///
+/// # Examples
+///
+/// ```
+/// # use tracing_capture::predicates::{matching, name};
+/// # use predicates::Predicate;
+/// let predicate = matching("user.*");
+/// assert!(predicate.eval("user.id"));
+/// assert!(!predicate.eval("username"));
+/// let _ = name(matching("handle_?"));
+/// ```
+pub fn matching(pattern: &str) -> GlobPredicate {
+    GlobPredicate {
+        pattern: pattern.to_owned(),
+    }
+}
+
+/// Glob predicate for `str`ings returned by the [`matching()`] function.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GlobPredicate {
+    pattern: String,
+}
+
+impl fmt::Display for GlobPredicate {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "var.matches({:?})", self.pattern)
+    }
+}
+
+impl PredicateReflection for GlobPredicate {
+    fn parameters<'a>(&'a self) -> Box<dyn Iterator<Item = Parameter<'a>> + 'a> {
+        let params = vec![Parameter::new("pattern", &self.pattern)];
+        Box::new(params.into_iter())
+    }
+}
+
+impl Predicate<str> for GlobPredicate {
+    fn eval(&self, variable: &str) -> bool {
+        glob_matches(&self.pattern, variable)
+    }
+
+    fn find_case(&self, expected: bool, variable: &str) -> Option<Case<'_>> {
+        if self.eval(variable) != expected {
+            return None;
+        }
+        let product = Product::new("var", format!("{variable:?}"));
+        Some(Case::new(Some(self), expected).add_product(product))
+    }
+}
+
+/// Checks whether `s` matches a `pattern` with `*` and `?` wildcards.
+pub(crate) fn glob_matches(pattern: &str, s: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let s: Vec<char> = s.chars().collect();
+    let (mut p_idx, mut s_idx) = (0, 0);
+    // Position of the last `*` in the pattern and the corresponding position in `s`.
+    let mut backtrack = None;
+
+    while s_idx < s.len() {
+        match pattern.get(p_idx) {
+            Some('*') => {
+                backtrack = Some((p_idx, s_idx));
+                p_idx += 1;
+            }
+            Some(&ch) if ch == '?' || ch == s[s_idx] => {
+                p_idx += 1;
+                s_idx += 1;
+            }
+            _ => {
+                let Some((star_idx, star_s_idx)) = backtrack else {
+                    return false;
+                };
+                // Make the last `*` consume one more char.
+                p_idx = star_idx + 1;
+                s_idx = star_s_idx + 1;
+                backtrack = Some((star_idx, s_idx));
+            }
+        }
+    }
+    pattern[p_idx..].iter().all(|&ch| ch == '*')
+}
@@ -0,0 +1,173 @@
+//! `if_span()` and `if_event()` predicate factories.
+
+use predicates::{
+    reflection::{Case, Child, PredicateReflection, Product},
+    Predicate,
+};
+
+use std::fmt;
+
+use crate::{CapturedEvent, CapturedItem, CapturedSpan};
+
+/// Creates a predicate for a [`CapturedItem`] that applies the provided span predicate
+/// if the item is a span, and is false for events. This allows using span-specific predicates
+/// (e.g., [`closed()`](super::closed())) in [`Storage::scan_all()`](crate::Storage::scan_all()).
+///
+/// # Examples
+///
+/// ```
+/// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+/// # use tracing_capture::{predicates::*, CaptureLayer, SharedStorage};
+/// let storage = SharedStorage::default();
+/// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+/// tracing::subscriber::with_default(subscriber, || {
+///     tracing::info_span!("compute").in_scope(|| {
+///         tracing::info!("computed");
+///     });
+/// });
+///
+/// let storage = storage.lock();
+/// let item = storage.scan_all().single(&if_span(closed()));
+/// assert_eq!(item.expect_span().metadata().name(), "compute");
+/// ```
+pub fn if_span<P>(matches: P) -> IfSpanPredicate<P>
+where
+    P: for<'a> Predicate<CapturedSpan<'a>>,
+{
+    IfSpanPredicate { matches }
+}
+
+/// Predicate for [`CapturedItem`]s returned by the [`if_span()`] function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IfSpanPredicate<P> {
+    matches: P,
+}
+
+impl_bool_ops!(IfSpanPredicate<P>);
+impl_ref_predicate!(IfSpanPredicate<P>: CapturedItem);
+
+impl<P> fmt::Display for IfSpanPredicate<P>
+where
+    P: for<'a> Predicate<CapturedSpan<'a>>,
+{
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "if_span({})", self.matches)
+    }
+}
+
+impl<P> PredicateReflection for IfSpanPredicate<P>
+where
+    P: for<'a> Predicate<CapturedSpan<'a>>,
+{
+    fn children<'a>(&'a self) -> Box<dyn Iterator<Item = Child<'a>> + 'a> {
+        let children = vec![Child::new("span", &self.matches)];
+        Box::new(children.into_iter())
+    }
+}
+
+impl<P> Predicate<CapturedItem<'_>> for IfSpanPredicate<P>
+where
+    P: for<'a> Predicate<CapturedSpan<'a>>,
+{
+    fn eval(&self, variable: &CapturedItem<'_>) -> bool {
+        variable
+            .as_span()
+            .is_some_and(|span| self.matches.eval(&span))
+    }
+
+    fn find_case(&self, expected: bool, variable: &CapturedItem<'_>) -> Option<Case<'_>> {
+        let Some(span) = variable.as_span() else {
+            return if expected {
+                None // was expecting a span, but got an event
+            } else {
+                let product = Product::new("kind", "event");
+                Some(Case::new(Some(self), expected).add_product(product))
+            };
+        };
+
+        let child = self.matches.find_case(expected, &span)?;
+        Some(Case::new(Some(self), expected).add_child(child))
+    }
+}
+
+/// Creates a predicate for a [`CapturedItem`] that applies the provided event predicate
+/// if the item is an event, and is false for spans. See [`if_span()`] for details.
+///
+/// # Examples
+///
+/// ```
+/// # use predicates::str::contains;
+/// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+/// # use tracing_capture::{predicates::*, CaptureLayer, SharedStorage};
+/// let storage = SharedStorage::default();
+/// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+/// tracing::subscriber::with_default(subscriber, || {
+///     tracing::info_span!("request", id = 42).in_scope(|| {
+///         tracing::info!("request 42 processed");
+///     });
+/// });
+///
+/// let storage = storage.lock();
+/// let predicate = field("id", 42_i64) | if_event(message(contains("42")));
+/// // Both the span and the event match the predicate.
+/// storage.scan_all().all(&predicate);
+/// ```
+pub fn if_event<P>(matches: P) -> IfEventPredicate<P>
+where
+    P: for<'a> Predicate<CapturedEvent<'a>>,
+{
+    IfEventPredicate { matches }
+}
+
+/// Predicate for [`CapturedItem`]s returned by the [`if_event()`] function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IfEventPredicate<P> {
+    matches: P,
+}
+
+impl_bool_ops!(IfEventPredicate<P>);
+impl_ref_predicate!(IfEventPredicate<P>: CapturedItem);
+
+impl<P> fmt::Display for IfEventPredicate<P>
+where
+    P: for<'a> Predicate<CapturedEvent<'a>>,
+{
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "if_event({})", self.matches)
+    }
+}
+
+impl<P> PredicateReflection for IfEventPredicate<P>
+where
+    P: for<'a> Predicate<CapturedEvent<'a>>,
+{
+    fn children<'a>(&'a self) -> Box<dyn Iterator<Item = Child<'a>> + 'a> {
+        let children = vec![Child::new("event", &self.matches)];
+        Box::new(children.into_iter())
+    }
+}
+
+impl<P> Predicate<CapturedItem<'_>> for IfEventPredicate<P>
+where
+    P: for<'a> Predicate<CapturedEvent<'a>>,
+{
+    fn eval(&self, variable: &CapturedItem<'_>) -> bool {
+        variable
+            .as_event()
+            .is_some_and(|event| self.matches.eval(&event))
+    }
+
+    fn find_case(&self, expected: bool, variable: &CapturedItem<'_>) -> Option<Case<'_>> {
+        let Some(event) = variable.as_event() else {
+            return if expected {
+                None // was expecting an event, but got a span
+            } else {
+                let product = Product::new("kind", "span");
+                Some(Case::new(Some(self), expected).add_product(product))
+            };
+        };
+
+        let child = self.matches.find_case(expected, &event)?;
+        Some(Case::new(Some(self), expected).add_child(child))
+    }
+}
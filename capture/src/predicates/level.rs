@@ -1,12 +1,12 @@
-//! `level()` predicate factory.
+//! `level()` predicate factory and its variations.
 
 use predicates::{
-    reflection::{Case, PredicateReflection},
+    reflection::{Case, Child, Parameter, PredicateReflection, Product},
     Predicate,
 };
 use tracing_core::{Level, LevelFilter};
 
-use std::fmt;
+use std::{fmt, ops};
 
 use crate::Captured;
 
@@ -44,6 +44,63 @@ impl IntoLevelPredicate for LevelFilter {
     }
 }
 
+/// Inclusive range of [`Level`]s. Bounds may be specified in any order; e.g.,
+/// `Level::DEBUG..=Level::ERROR` and `Level::ERROR..=Level::DEBUG` are equivalent.
+impl IntoLevelPredicate for ops::RangeInclusive<Level> {
+    type Predicate = LevelRangePredicate;
+
+    fn into_predicate(self) -> Self::Predicate {
+        let (start, end) = self.into_inner();
+        LevelRangePredicate {
+            most_severe: start.min(end),
+            least_severe: start.max(end),
+        }
+    }
+}
+
+#[doc(hidden)] // implementation detail (yet?)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LevelRangePredicate {
+    // Since more verbose levels are greater, the "most severe" bound is smaller.
+    most_severe: Level,
+    least_severe: Level,
+}
+
+impl fmt::Display for LevelRangePredicate {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            formatter,
+            "var in {}..={}",
+            self.least_severe, self.most_severe
+        )
+    }
+}
+
+impl PredicateReflection for LevelRangePredicate {
+    fn parameters<'a>(&'a self) -> Box<dyn Iterator<Item = Parameter<'a>> + 'a> {
+        let params = vec![
+            Parameter::new("most_severe", &self.most_severe),
+            Parameter::new("least_severe", &self.least_severe),
+        ];
+        Box::new(params.into_iter())
+    }
+}
+
+impl Predicate<Level> for LevelRangePredicate {
+    fn eval(&self, variable: &Level) -> bool {
+        (self.most_severe..=self.least_severe).contains(variable)
+    }
+
+    fn find_case(&self, expected: bool, variable: &Level) -> Option<Case<'_>> {
+        if self.eval(variable) == expected {
+            let product = Product::new("var", variable.to_string());
+            Some(Case::new(Some(self), expected).add_product(product))
+        } else {
+            None
+        }
+    }
+}
+
 /// Creates a predicate for the [`Level`] of a [`CapturedSpan`] or [`CapturedEvent`].
 ///
 /// # Arguments
@@ -52,6 +109,8 @@ impl IntoLevelPredicate for LevelFilter {
 ///
 /// - [`Level`]: will be compared exactly
 /// - [`LevelFilter`]: will be compared as per ordinary rules
+/// - Inclusive range of [`Level`]s, e.g. `Level::DEBUG..=Level::WARN`. The range bounds
+///   can be specified in any order. See also [`level_in()`].
 /// - Any `Predicate` for [`Level`]. To bypass Rust orphaning rules, the predicate
 ///   must be enclosed in square brackets (i.e., a one-value array).
 ///
@@ -104,7 +163,12 @@ impl<P: Predicate<Level>> fmt::Display for LevelPredicate<P> {
     }
 }
 
-impl<P: Predicate<Level>> PredicateReflection for LevelPredicate<P> {}
+impl<P: Predicate<Level>> PredicateReflection for LevelPredicate<P> {
+    fn children<'a>(&'a self) -> Box<dyn Iterator<Item = Child<'a>> + 'a> {
+        let children = vec![Child::new("level", &self.matches)];
+        Box::new(children.into_iter())
+    }
+}
 
 impl<'a, P: Predicate<Level>, T: Captured<'a>> Predicate<T> for LevelPredicate<P> {
     fn eval(&self, variable: &T) -> bool {
@@ -118,3 +182,64 @@ impl<'a, P: Predicate<Level>, T: Captured<'a>> Predicate<T> for LevelPredicate<P
         Some(Case::new(Some(self), expected).add_child(child))
     }
 }
+
+/// Creates a predicate checking that the [`Level`] of a [`CapturedSpan`] or [`CapturedEvent`]
+/// is at least as severe as the specified one. For example, `level_at_least(Level::WARN)`
+/// matches `WARN` and `ERROR` levels.
+///
+/// This is equivalent to `level(LevelFilter::from_level(min_level))`.
+///
+/// [`CapturedSpan`]: crate::CapturedSpan
+/// [`CapturedEvent`]: crate::CapturedEvent
+///
+/// # Examples
+///
+/// ```
+/// # use tracing_core::Level;
+/// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+/// # use tracing_capture::{predicates::{level_at_least, ScanExt}, CaptureLayer, SharedStorage};
+/// let storage = SharedStorage::default();
+/// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+/// tracing::subscriber::with_default(subscriber, || {
+///     tracing::info_span!("compute").in_scope(|| {
+///         tracing::debug!(answer = 42, "done");
+///     });
+/// });
+///
+/// let storage = storage.lock();
+/// // Check that there are no events above `DEBUG` level.
+/// storage.scan_events().none(&level_at_least(Level::INFO));
+/// ```
+pub fn level_at_least(min_level: Level) -> LevelPredicate<predicates::ord::OrdPredicate<Level>> {
+    level(LevelFilter::from_level(min_level))
+}
+
+/// Creates a predicate checking that the [`Level`] of a [`CapturedSpan`] or [`CapturedEvent`]
+/// is within the specified inclusive range. The range bounds can be specified in any order.
+///
+/// This is equivalent to `level(range)`.
+///
+/// [`CapturedSpan`]: crate::CapturedSpan
+/// [`CapturedEvent`]: crate::CapturedEvent
+///
+/// # Examples
+///
+/// ```
+/// # use tracing_core::Level;
+/// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+/// # use tracing_capture::{predicates::{level_in, ScanExt}, CaptureLayer, SharedStorage};
+/// let storage = SharedStorage::default();
+/// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+/// tracing::subscriber::with_default(subscriber, || {
+///     tracing::info_span!("compute").in_scope(|| {
+///         tracing::warn!(answer = 42, "done");
+///     });
+/// });
+///
+/// let storage = storage.lock();
+/// storage.scan_spans().all(&level_in(Level::DEBUG..=Level::WARN));
+/// storage.scan_events().single(&level_in(Level::INFO..=Level::ERROR));
+/// ```
+pub fn level_in(range: ops::RangeInclusive<Level>) -> LevelPredicate<LevelRangePredicate> {
+    level(range)
+}
@@ -0,0 +1,226 @@
+//! Predicates relating events to span lifecycle: `emitted_while_entered()` and `before_close()`.
+
+use predicates::{
+    reflection::{Case, Child, PredicateReflection, Product},
+    Predicate,
+};
+
+use std::fmt;
+
+use crate::{CapturedEvent, CapturedSpan};
+
+fn find_lifecycle_case<'p, P, F>(
+    predicate: &'p P,
+    expected: bool,
+    event: &CapturedEvent<'_>,
+    matches: F,
+) -> Option<Case<'p>>
+where
+    P: PredicateReflection,
+    F: Fn(&CapturedSpan<'_>) -> bool,
+{
+    let matching_span = event.storage.all_spans().find(|span| matches(span));
+    if matching_span.is_some() != expected {
+        return None;
+    }
+    let case = Case::new(Some(predicate), expected);
+    Some(if let Some(span) = matching_span {
+        case.add_product(Product::new("span", span.metadata().name()))
+    } else {
+        case
+    })
+}
+
+/// Creates a predicate checking that a [`CapturedEvent`] was emitted while a span
+/// matching the provided predicate was entered on the same thread. The span is not
+/// necessarily the parent of the event; e.g., the event may specify an explicit parent.
+///
+/// The predicate relies on the order of operations captured in the [`Storage`]
+/// (see [`Storage::timeline()`]) rather than on timestamps, so it is precise even if
+/// the operations have the same timestamp.
+///
+/// [`Storage`]: crate::Storage
+/// [`Storage::timeline()`]: crate::Storage::timeline()
+///
+/// # Examples
+///
+/// ```
+/// # use predicates::ord::eq;
+/// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+/// # use tracing_capture::{predicates::*, CaptureLayer, SharedStorage};
+/// let storage = SharedStorage::default();
+/// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+/// tracing::subscriber::with_default(subscriber, || {
+///     let span = tracing::info_span!("transaction");
+///     span.in_scope(|| tracing::info!("started"));
+///     tracing::info!(parent: &span, "committed");
+/// });
+///
+/// let storage = storage.lock();
+/// let in_transaction = emitted_while_entered(name(eq("transaction")));
+/// let events = storage.scan_events();
+/// let _ = events.single(&(message(eq("started")) & in_transaction));
+/// let _ = events.none(&(message(eq("committed")) & in_transaction));
+/// ```
+pub fn emitted_while_entered<P>(matches: P) -> EmittedWhileEnteredPredicate<P>
+where
+    P: for<'a> Predicate<CapturedSpan<'a>>,
+{
+    EmittedWhileEnteredPredicate { matches }
+}
+
+/// Predicate for [`CapturedEvent`]s emitted while a certain span was entered.
+/// Returned by the [`emitted_while_entered()`] function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EmittedWhileEnteredPredicate<P> {
+    matches: P,
+}
+
+impl_bool_ops!(EmittedWhileEnteredPredicate<P>);
+impl_ref_predicate!(EmittedWhileEnteredPredicate<P>: CapturedEvent);
+
+impl<P> EmittedWhileEnteredPredicate<P>
+where
+    P: for<'a> Predicate<CapturedSpan<'a>>,
+{
+    fn is_entered_at(span: &CapturedSpan<'_>, event: &CapturedEvent<'_>) -> bool {
+        let (thread, seq) = (event.inner.thread, event.inner.seq);
+        span.entries().iter().any(|entry| {
+            entry.thread == thread
+                && entry.entered_seq < seq
+                && entry.exited_seq.map_or(true, |exited_seq| seq < exited_seq)
+        })
+    }
+
+    fn matches_span(&self, span: &CapturedSpan<'_>, event: &CapturedEvent<'_>) -> bool {
+        Self::is_entered_at(span, event) && self.matches.eval(span)
+    }
+}
+
+impl<P> fmt::Display for EmittedWhileEnteredPredicate<P>
+where
+    P: for<'a> Predicate<CapturedSpan<'a>>,
+{
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "emitted_while_entered({})", self.matches)
+    }
+}
+
+impl<P> PredicateReflection for EmittedWhileEnteredPredicate<P>
+where
+    P: for<'a> Predicate<CapturedSpan<'a>>,
+{
+    fn children<'a>(&'a self) -> Box<dyn Iterator<Item = Child<'a>> + 'a> {
+        let children = vec![Child::new("span", &self.matches)];
+        Box::new(children.into_iter())
+    }
+}
+
+impl<P> Predicate<CapturedEvent<'_>> for EmittedWhileEnteredPredicate<P>
+where
+    P: for<'a> Predicate<CapturedSpan<'a>>,
+{
+    fn eval(&self, variable: &CapturedEvent<'_>) -> bool {
+        let mut spans = variable.storage.all_spans();
+        spans.any(|span| self.matches_span(&span, variable))
+    }
+
+    fn find_case(&self, expected: bool, variable: &CapturedEvent<'_>) -> Option<Case<'_>> {
+        find_lifecycle_case(self, expected, variable, |span| {
+            self.matches_span(span, variable)
+        })
+    }
+}
+
+/// Creates a predicate checking that a [`CapturedEvent`] was emitted before a span matching
+/// the provided predicate was closed. Spans that are not closed are not considered.
+///
+/// Like [`emitted_while_entered()`], the predicate relies on the order of captured operations
+/// rather than on timestamps.
+///
+/// # Examples
+///
+/// ```
+/// # use predicates::ord::eq;
+/// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+/// # use tracing_capture::{predicates::*, CaptureLayer, SharedStorage};
+/// let storage = SharedStorage::default();
+/// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+/// tracing::subscriber::with_default(subscriber, || {
+///     let span = tracing::info_span!("transaction");
+///     span.in_scope(|| tracing::info!("started"));
+///     tracing::info!("flushing");
+///     drop(span);
+///     tracing::info!("done");
+/// });
+///
+/// let storage = storage.lock();
+/// let before_commit = before_close(name(eq("transaction")));
+/// let events = storage.scan_events();
+/// let _ = events.single(&(message(eq("flushing")) & before_commit));
+/// let _ = events.none(&(message(eq("done")) & before_commit));
+/// ```
+pub fn before_close<P>(matches: P) -> BeforeClosePredicate<P>
+where
+    P: for<'a> Predicate<CapturedSpan<'a>>,
+{
+    BeforeClosePredicate { matches }
+}
+
+/// Predicate for [`CapturedEvent`]s emitted before a certain span was closed.
+/// Returned by the [`before_close()`] function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BeforeClosePredicate<P> {
+    matches: P,
+}
+
+impl_bool_ops!(BeforeClosePredicate<P>);
+impl_ref_predicate!(BeforeClosePredicate<P>: CapturedEvent);
+
+impl<P> BeforeClosePredicate<P>
+where
+    P: for<'a> Predicate<CapturedSpan<'a>>,
+{
+    fn matches_span(&self, span: &CapturedSpan<'_>, event: &CapturedEvent<'_>) -> bool {
+        let is_closed_after = span
+            .inner
+            .closed_seq
+            .is_some_and(|closed_seq| event.inner.seq < closed_seq);
+        is_closed_after && self.matches.eval(span)
+    }
+}
+
+impl<P> fmt::Display for BeforeClosePredicate<P>
+where
+    P: for<'a> Predicate<CapturedSpan<'a>>,
+{
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "before_close({})", self.matches)
+    }
+}
+
+impl<P> PredicateReflection for BeforeClosePredicate<P>
+where
+    P: for<'a> Predicate<CapturedSpan<'a>>,
+{
+    fn children<'a>(&'a self) -> Box<dyn Iterator<Item = Child<'a>> + 'a> {
+        let children = vec![Child::new("span", &self.matches)];
+        Box::new(children.into_iter())
+    }
+}
+
+impl<P> Predicate<CapturedEvent<'_>> for BeforeClosePredicate<P>
+where
+    P: for<'a> Predicate<CapturedSpan<'a>>,
+{
+    fn eval(&self, variable: &CapturedEvent<'_>) -> bool {
+        let mut spans = variable.storage.all_spans();
+        spans.any(|span| self.matches_span(&span, variable))
+    }
+
+    fn find_case(&self, expected: bool, variable: &CapturedEvent<'_>) -> Option<Case<'_>> {
+        find_lifecycle_case(self, expected, variable, |span| {
+            self.matches_span(span, variable)
+        })
+    }
+}
@@ -0,0 +1,177 @@
+//! `module()`, `is_span()` and `is_event()` predicate factories.
+
+use predicates::{
+    reflection::{Case, Child, Parameter, PredicateReflection, Product},
+    Predicate,
+};
+
+use std::fmt;
+
+use crate::Captured;
+
+/// Creates a predicate for the module path of a [`CapturedSpan`] or [`CapturedEvent`].
+/// If the module path is not recorded in the span / event metadata, the predicate is false.
+///
+/// # Arguments
+///
+/// The argument of this function can be any `str`ing predicate, e.g. `eq("my_crate::module")`
+/// for exact comparison.
+///
+/// [`CapturedSpan`]: crate::CapturedSpan
+/// [`CapturedEvent`]: crate::CapturedEvent
+///
+/// # Examples
+///
+/// ```
+/// # use predicates::{ord::eq, str::ends_with};
+/// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+/// # use tracing_capture::{predicates::{module, ScanExt}, CaptureLayer, SharedStorage};
+/// let storage = SharedStorage::default();
+/// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+/// tracing::subscriber::with_default(subscriber, || {
+///     tracing::info_span!("compute").in_scope(|| {
+///         tracing::info!(answer = 42, "done");
+///     });
+/// });
+///
+/// let storage = storage.lock();
+/// let _ = storage.scan_spans().single(&module(eq(module_path!())));
+/// storage.scan_events().none(&module(ends_with("::other")));
+/// ```
+pub fn module<P: Predicate<str>>(matches: P) -> ModulePredicate<P> {
+    ModulePredicate { matches }
+}
+
+/// Predicate for the module path of a [`CapturedSpan`] or [`CapturedEvent`] returned by
+/// the [`module()`] function.
+///
+/// [`CapturedSpan`]: crate::CapturedSpan
+/// [`CapturedEvent`]: crate::CapturedEvent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModulePredicate<P> {
+    matches: P,
+}
+
+impl_bool_ops!(ModulePredicate<P>);
+
+impl<P: Predicate<str>> fmt::Display for ModulePredicate<P> {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "module({})", self.matches)
+    }
+}
+
+impl<P: Predicate<str>> PredicateReflection for ModulePredicate<P> {
+    fn children<'a>(&'a self) -> Box<dyn Iterator<Item = Child<'a>> + 'a> {
+        let children = vec![Child::new("module", &self.matches)];
+        Box::new(children.into_iter())
+    }
+}
+
+impl<'a, P: Predicate<str>, T: Captured<'a>> Predicate<T> for ModulePredicate<P> {
+    fn eval(&self, variable: &T) -> bool {
+        variable
+            .metadata()
+            .module_path()
+            .is_some_and(|path| self.matches.eval(path))
+    }
+
+    fn find_case(&self, expected: bool, variable: &T) -> Option<Case<'_>> {
+        let Some(path) = variable.metadata().module_path() else {
+            return if expected {
+                None
+            } else {
+                let product = Product::new("module_path", "None");
+                Some(Case::new(Some(self), expected).add_product(product))
+            };
+        };
+        let child = self.matches.find_case(expected, path)?;
+        Some(Case::new(Some(self), expected).add_child(child))
+    }
+}
+
+/// Creates a predicate checking whether a [`CapturedSpan`] or [`CapturedEvent`] is a span
+/// according to its metadata. This is mostly useful in generic code, e.g. together with
+/// [`Captured`].
+///
+/// [`CapturedSpan`]: crate::CapturedSpan
+/// [`CapturedEvent`]: crate::CapturedEvent
+///
+/// # Examples
+///
+/// ```
+/// # use predicates::Predicate;
+/// # use tracing_capture::{predicates::{is_event, is_span}, Captured};
+/// fn describe<'a>(captured: &impl Captured<'a>) -> &'static str {
+///     if is_span().eval(captured) {
+///         "span"
+///     } else if is_event().eval(captured) {
+///         "event"
+///     } else {
+///         unreachable!()
+///     }
+/// }
+/// ```
+pub fn is_span() -> KindPredicate {
+    KindPredicate { is_span: true }
+}
+
+/// Creates a predicate checking whether a [`CapturedSpan`] or [`CapturedEvent`] is an event
+/// according to its metadata. See [`is_span()`] for details.
+///
+/// [`CapturedSpan`]: crate::CapturedSpan
+/// [`CapturedEvent`]: crate::CapturedEvent
+pub fn is_event() -> KindPredicate {
+    KindPredicate { is_span: false }
+}
+
+/// Predicate for the kind of a [`CapturedSpan`] or [`CapturedEvent`] returned by
+/// the [`is_span()`] and [`is_event()`] functions.
+///
+/// [`CapturedSpan`]: crate::CapturedSpan
+/// [`CapturedEvent`]: crate::CapturedEvent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KindPredicate {
+    is_span: bool,
+}
+
+impl_bool_ops!(KindPredicate);
+
+impl fmt::Display for KindPredicate {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str(if self.is_span {
+            "is_span()"
+        } else {
+            "is_event()"
+        })
+    }
+}
+
+impl PredicateReflection for KindPredicate {
+    fn parameters<'a>(&'a self) -> Box<dyn Iterator<Item = Parameter<'a>> + 'a> {
+        let params = vec![Parameter::new("is_span", &self.is_span)];
+        Box::new(params.into_iter())
+    }
+}
+
+impl<'a, T: Captured<'a>> Predicate<T> for KindPredicate {
+    fn eval(&self, variable: &T) -> bool {
+        let metadata = variable.metadata();
+        if self.is_span {
+            metadata.is_span()
+        } else {
+            metadata.is_event()
+        }
+    }
+
+    fn find_case(&self, expected: bool, variable: &T) -> Option<Case<'_>> {
+        if self.eval(variable) != expected {
+            return None;
+        }
+        let kind = if variable.metadata().is_span() {
+            "span"
+        } else {
+            "event"
+        };
+        Some(Case::new(Some(self), expected).add_product(Product::new("kind", kind)))
+    }
+}
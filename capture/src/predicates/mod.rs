@@ -4,20 +4,51 @@
 //!
 //! A predicate can be created with the functions from this module:
 //!
-//! - [`level()`] checks the span / event level
-//! - [`name()`] checks the span name
+//! - [`level()`] checks the span / event level; [`level_at_least()`] and [`level_in()`]
+//!   are its shortcuts for level ranges
+//! - [`name()`] checks the span / event name
 //! - [`target()`] checks the span / event target
+//! - [`module()`] checks the span / event module path
+//! - [`is_span()`] and [`is_event()`] check the kind of a span / event, which is useful
+//!   in generic code
 //! - [`field()`] checks a specific span / event field
-//! - [`message()`] checks the event message
+//! - [`inherited_field()`] checks a span / event field that may be inherited from ancestor spans
+//! - [`any_field()`] checks fields with names matching a predicate, such as a glob pattern
+//!   produced by [`matching()`]
+//! - [`fields_subset()`] checks multiple span / event fields at once, and [`field_count()`]
+//!   checks the number of fields
+//! - [`message()`] checks the event message, and [`message_with_fields()`] checks that
+//!   the message matches a template with field values substituted
+//! - [`error_message()`] and [`error_source_depth()`] check error values; they can be used
+//!   with [`field()`]
+//! - [`bytes_len()`] and [`bytes_prefix()`] check byte values; they can be used with [`field()`]
+//! - [`num_eq()`], [`num_gt()`], [`num_ge()`], [`num_lt()`] and [`num_le()`] compare
+//!   numeric field values regardless of their exact type; they can be used with [`field()`]
 //! - [`parent()`] checks the direct parent span of an event / span
 //! - [`ancestor()`] checks the ancestor spans of an event / span
+//! - [`depth()`] checks the number of ancestor spans of an event / span
+//! - [`event_count()`] checks the number of events directly attached to a span
+//! - [`entered()`], [`exited()`] and [`closed()`] check span [stats](crate::SpanStats)
+//! - [`outlived_parent()`] checks whether a span was closed after its parent has finished
+//! - [`emitted_while_entered()`] and [`before_close()`] check the event position relative
+//!   to the lifecycle of spans
+//! - [`if_span()`] and [`if_event()`] apply span- or event-specific predicates
+//!   to [`CapturedItem`]s, e.g. ones produced by [`Storage::scan_all()`]
 //!
 //! These predicates can be combined with bitwise operators, `&` and `|`.
 //! The [`ScanExt`] trait may be used to simplify assertions with predicates. The remaining
 //! traits and structs are lower-level plumbing and rarely need to be used directly.
 //!
+//! All predicates for spans / events can also be evaluated on references to them
+//! (e.g., `&CapturedSpan<'_>`), which is convenient with [`Iterator::filter()`].
+//! To store predicates in structs (e.g., test fixtures) or pass them between helper functions
+//! without spelling out their types, predicates can be boxed as [`BoxedSpanPredicate`]
+//! or [`BoxedEventPredicate`].
+//!
 //! [`CapturedSpan`]: crate::CapturedSpan
 //! [`CapturedEvent`]: crate::CapturedEvent
+//! [`CapturedItem`]: crate::CapturedItem
+//! [`Storage::scan_all()`]: crate::Storage::scan_all()
 //!
 //! # Examples
 //!
@@ -44,30 +75,99 @@
 
 use predicates::Predicate;
 
+use crate::{CapturedEvent, CapturedSpan};
+
+mod bytes;
 #[macro_use]
 mod combinators;
+mod diff;
+mod error;
+mod events;
 mod ext;
 mod field;
+mod glob;
+mod item;
 mod level;
+mod lifecycle;
+mod metadata;
 mod name;
+mod num;
 mod parent;
+mod similar;
+mod stats;
 mod target;
+mod template;
+mod time;
 
 #[cfg(test)]
 mod tests;
 
+#[cfg(feature = "trace-spec")]
+pub(crate) use self::glob::glob_matches;
+
 pub use self::{
+    bytes::{bytes_len, bytes_prefix, BytesLenPredicate, BytesPrefixPredicate},
     combinators::{And, Or},
+    error::{error_message, error_source_depth, ErrorMessagePredicate, ErrorSourceDepthPredicate},
+    events::{event_count, EventCountPredicate},
     ext::{ScanExt, Scanner},
     field::{
-        field, message, value, FieldPredicate, IntoFieldPredicate, MessagePredicate, ValuePredicate,
+        any_field, field, field_count, fields_subset, inherited_field, message, value,
+        AnyFieldPredicate, FieldCountPredicate, FieldPredicate, FieldsSubsetPredicate,
+        InheritedFieldPredicate, IntoFieldPredicate, MessagePredicate, ValuePredicate,
+    },
+    glob::{matching, GlobPredicate},
+    item::{if_event, if_span, IfEventPredicate, IfSpanPredicate},
+    level::{level, level_at_least, level_in, IntoLevelPredicate, LevelPredicate},
+    lifecycle::{
+        before_close, emitted_while_entered, BeforeClosePredicate, EmittedWhileEnteredPredicate,
     },
-    level::{level, IntoLevelPredicate, LevelPredicate},
+    metadata::{is_event, is_span, module, KindPredicate, ModulePredicate},
     name::{name, NamePredicate},
-    parent::{ancestor, parent, AncestorPredicate, ParentPredicate},
+    num::{num_eq, num_ge, num_gt, num_le, num_lt, NumPredicate, Numeric},
+    parent::{ancestor, depth, parent, AncestorPredicate, DepthPredicate, ParentPredicate},
+    similar::{similar_to, SimilarToPredicate},
+    stats::{
+        closed, entered, exited, outlived_parent, ClosedPredicate, OutlivedParentPredicate,
+        SpanCountPredicate,
+    },
     target::{target, IntoTargetPredicate, TargetPredicate},
+    template::{message_with_fields, MessageTemplatePredicate},
+    time::{ended_before, started_after, TimestampPredicate},
 };
 
+/// Boxed predicate for [`CapturedSpan`]s. Can be stored in structs and shared across threads.
+///
+/// # Examples
+///
+/// ```
+/// # use predicates::ord::eq;
+/// # use tracing_core::Level;
+/// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+/// # use tracing_capture::{predicates::*, CaptureLayer, SharedStorage};
+/// struct Fixture {
+///     request_span: BoxedSpanPredicate,
+/// }
+///
+/// let fixture = Fixture {
+///     request_span: Box::new(name(eq("request")) & level(Level::INFO)),
+/// };
+/// let storage = SharedStorage::default();
+/// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+/// tracing::subscriber::with_default(subscriber, || {
+///     tracing::info_span!("request").in_scope(|| tracing::info!("processing"));
+/// });
+///
+/// let storage = storage.lock();
+/// // Boxed predicates should be dereferenced to be used with `ScanExt`.
+/// let _ = storage.scan_spans().single(&*fixture.request_span);
+/// ```
+pub type BoxedSpanPredicate = Box<dyn for<'a> Predicate<CapturedSpan<'a>> + Send + Sync>;
+
+/// Boxed predicate for [`CapturedEvent`]s. Can be stored in structs and shared across threads.
+/// See [`BoxedSpanPredicate`] for an example of usage.
+pub type BoxedEventPredicate = Box<dyn for<'a> Predicate<CapturedEvent<'a>> + Send + Sync>;
+
 /// Converts a predicate into an `Fn(_) -> bool` closure.
 ///
 /// This can be used in APIs (e.g., [`Iterator`] combinators) that expect a closure
@@ -1,21 +1,29 @@
 //! `name()` predicate factory.
 
 use predicates::{
-    reflection::{Case, PredicateReflection},
+    reflection::{Case, Child, PredicateReflection},
     Predicate,
 };
 
 use std::fmt;
 
-use crate::CapturedSpan;
+use crate::Captured;
 
-/// Creates a predicate for the name of a [`CapturedSpan`].
+/// Creates a predicate for the name of a [`CapturedSpan`] or [`CapturedEvent`].
+///
+/// Event names are generated by `tracing` macros and are usually not meaningful
+/// (e.g., `event src/main.rs:42`), so this predicate is mostly useful for spans.
+/// For spans, the predicate takes the [name field](crate::CaptureLayer::with_name_field())
+/// into account, i.e., matches against [`CapturedSpan::name()`].
 ///
 /// # Arguments
 ///
 /// The argument of this function can be any `str`ing predicate, e.g. `eq("test")` for
 /// exact comparison.
 ///
+/// [`CapturedSpan`]: crate::CapturedSpan
+/// [`CapturedEvent`]: crate::CapturedEvent
+///
 /// # Examples
 ///
 /// ```
@@ -40,7 +48,11 @@ pub fn name<P: Predicate<str>>(matches: P) -> NamePredicate<P> {
     NamePredicate { matches }
 }
 
-/// Predicate for the name of a [`CapturedSpan`] returned by the [`name()`] function.
+/// Predicate for the name of a [`CapturedSpan`] or [`CapturedEvent`] returned by
+/// the [`name()`] function.
+///
+/// [`CapturedSpan`]: crate::CapturedSpan
+/// [`CapturedEvent`]: crate::CapturedEvent
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct NamePredicate<P> {
     matches: P,
@@ -54,17 +66,20 @@ impl<P: Predicate<str>> fmt::Display for NamePredicate<P> {
     }
 }
 
-impl<P: Predicate<str>> PredicateReflection for NamePredicate<P> {}
+impl<P: Predicate<str>> PredicateReflection for NamePredicate<P> {
+    fn children<'a>(&'a self) -> Box<dyn Iterator<Item = Child<'a>> + 'a> {
+        let children = vec![Child::new("name", &self.matches)];
+        Box::new(children.into_iter())
+    }
+}
 
-impl<P: Predicate<str>> Predicate<CapturedSpan<'_>> for NamePredicate<P> {
-    fn eval(&self, variable: &CapturedSpan<'_>) -> bool {
-        self.matches.eval(variable.metadata().name())
+impl<'a, P: Predicate<str>, T: Captured<'a>> Predicate<T> for NamePredicate<P> {
+    fn eval(&self, variable: &T) -> bool {
+        self.matches.eval(variable.name())
     }
 
-    fn find_case(&self, expected: bool, variable: &CapturedSpan<'_>) -> Option<Case<'_>> {
-        let child = self
-            .matches
-            .find_case(expected, variable.metadata().name())?;
+    fn find_case(&self, expected: bool, variable: &T) -> Option<Case<'_>> {
+        let child = self.matches.find_case(expected, variable.name())?;
         Some(Case::new(Some(self), expected).add_child(child))
     }
 }
@@ -0,0 +1,260 @@
+//! Numeric predicates for `TracedValue`s: `num_eq()`, `num_gt()` etc.
+
+use predicates::{
+    reflection::{Case, Parameter, PredicateReflection, Product},
+    Predicate,
+};
+
+use std::{cmp::Ordering, fmt};
+
+use super::IntoFieldPredicate;
+use tracing_tunnel::TracedValue;
+
+/// Number that can be compared with numeric [`TracedValue`]s by the predicates
+/// in this module.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Num {
+    Int(i128),
+    UInt(u128),
+    Float(f64),
+}
+
+impl Num {
+    fn from_value(value: &TracedValue) -> Option<Self> {
+        Some(match value {
+            TracedValue::Int(value) => Self::Int(*value),
+            TracedValue::UInt(value) => Self::UInt(*value),
+            TracedValue::Float(value) => Self::Float(*value),
+            _ => return None,
+        })
+    }
+
+    #[allow(clippy::cast_precision_loss)] // documented in `num_eq()`
+    fn as_float(self) -> f64 {
+        match self {
+            Self::Int(value) => value as f64,
+            Self::UInt(value) => value as f64,
+            Self::Float(value) => value,
+        }
+    }
+
+    fn compare(self, other: Self) -> Option<Ordering> {
+        match (self, other) {
+            (Self::Int(x), Self::Int(y)) => Some(x.cmp(&y)),
+            (Self::UInt(x), Self::UInt(y)) => Some(x.cmp(&y)),
+            (Self::Int(x), Self::UInt(y)) => Some(match u128::try_from(x) {
+                Ok(x) => x.cmp(&y),
+                Err(_) => Ordering::Less, // `x` is negative
+            }),
+            (Self::UInt(_), Self::Int(_)) => other.compare(self).map(Ordering::reverse),
+            _ => self.as_float().partial_cmp(&other.as_float()),
+        }
+    }
+}
+
+impl fmt::Display for Num {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Int(value) => fmt::Display::fmt(value, formatter),
+            Self::UInt(value) => fmt::Display::fmt(value, formatter),
+            Self::Float(value) => fmt::Debug::fmt(value, formatter),
+        }
+    }
+}
+
+/// Primitive numeric type that can be used as an argument of [`num_eq()`] and other
+/// numeric predicate factories. This trait is sealed.
+pub trait Numeric: Copy + crate::sealed::Sealed {
+    #[doc(hidden)] // implementation detail
+    fn into_num(self) -> NumArg;
+}
+
+/// Opaque numeric argument.
+#[doc(hidden)] // implementation detail
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NumArg(Num);
+
+macro_rules! impl_numeric {
+    ($variant:ident: $target:ty => $($ty:ty),+) => {
+        $(
+        impl crate::sealed::Sealed for $ty {}
+
+        impl Numeric for $ty {
+            fn into_num(self) -> NumArg {
+                NumArg(Num::$variant(<$target>::from(self)))
+            }
+        }
+        )+
+    };
+}
+
+impl_numeric!(Int: i128 => i8, i16, i32, i64, i128);
+impl_numeric!(UInt: u128 => u8, u16, u32, u64, u128);
+impl_numeric!(Float: f64 => f32, f64);
+
+impl crate::sealed::Sealed for isize {}
+
+impl Numeric for isize {
+    fn into_num(self) -> NumArg {
+        NumArg(Num::Int(self as i128))
+    }
+}
+
+impl crate::sealed::Sealed for usize {}
+
+impl Numeric for usize {
+    fn into_num(self) -> NumArg {
+        NumArg(Num::UInt(self as u128))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NumOp {
+    Eq,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+}
+
+impl NumOp {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Eq => "==",
+            Self::Gt => ">",
+            Self::Ge => ">=",
+            Self::Lt => "<",
+            Self::Le => "<=",
+        }
+    }
+
+    fn matches(self, ordering: Ordering) -> bool {
+        match self {
+            Self::Eq => ordering.is_eq(),
+            Self::Gt => ordering.is_gt(),
+            Self::Ge => ordering.is_ge(),
+            Self::Lt => ordering.is_lt(),
+            Self::Le => ordering.is_le(),
+        }
+    }
+}
+
+/// Creates a predicate checking that a numeric [`TracedValue`] is equal to the specified number.
+///
+/// Unlike comparisons via [`PartialEq`] (e.g., `field("count", 5_i64)`), numeric predicates
+/// compare values across `Int`, `UInt` and `Float` variants of `TracedValue`. This is useful
+/// if the exact type of the recorded value is not important or is unknown; e.g.,
+/// `tracing` records `usize` values as `u64`, and `i32` values as `i64`.
+/// Non-numeric values never match.
+///
+/// Returned predicates can be supplied to the [`field()`](super::field()) function.
+///
+/// # Widening rules
+///
+/// - Signed and unsigned integers are compared exactly, with negative signed integers
+///   being less than any unsigned integer.
+/// - If at least one of compared numbers is a floating-point number, both numbers are
+///   converted to `f64` (which may lose precision for integers exceeding 2<sup>53</sup>
+///   by the absolute value). `NaN` is not comparable with any number, so comparisons
+///   with it are always false.
+///
+/// # Examples
+///
+/// ```
+/// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+/// # use tracing_capture::{predicates::{field, num_eq, num_gt, num_le, ScanExt}, CaptureLayer, SharedStorage};
+/// let storage = SharedStorage::default();
+/// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+/// tracing::subscriber::with_default(subscriber, || {
+///     tracing::info!(count = 5_usize, ratio = 0.5, "processed");
+/// });
+///
+/// let storage = storage.lock();
+/// let events = storage.scan_events();
+/// // `count` is recorded as `u64`, so this wouldn't match with `field("count", 5_i64)`.
+/// let _ = events.single(&field("count", num_eq(5)));
+/// let _ = events.single(&field("count", num_eq(5.0)));
+/// let _ = events.single(&(field("count", num_gt(-1)) & field("ratio", num_le(1))));
+/// ```
+pub fn num_eq(value: impl Numeric) -> NumPredicate {
+    NumPredicate::new(NumOp::Eq, value)
+}
+
+/// Creates a predicate checking that a numeric [`TracedValue`] is greater than
+/// the specified number. See [`num_eq()`] for the details on how numbers are compared.
+pub fn num_gt(value: impl Numeric) -> NumPredicate {
+    NumPredicate::new(NumOp::Gt, value)
+}
+
+/// Creates a predicate checking that a numeric [`TracedValue`] is greater than or equal to
+/// the specified number. See [`num_eq()`] for the details on how numbers are compared.
+pub fn num_ge(value: impl Numeric) -> NumPredicate {
+    NumPredicate::new(NumOp::Ge, value)
+}
+
+/// Creates a predicate checking that a numeric [`TracedValue`] is less than
+/// the specified number. See [`num_eq()`] for the details on how numbers are compared.
+pub fn num_lt(value: impl Numeric) -> NumPredicate {
+    NumPredicate::new(NumOp::Lt, value)
+}
+
+/// Creates a predicate checking that a numeric [`TracedValue`] is less than or equal to
+/// the specified number. See [`num_eq()`] for the details on how numbers are compared.
+pub fn num_le(value: impl Numeric) -> NumPredicate {
+    NumPredicate::new(NumOp::Le, value)
+}
+
+/// Predicate for numeric [`TracedValue`]s returned by [`num_eq()`] and other
+/// numeric predicate factories.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NumPredicate {
+    op: NumOp,
+    value: Num,
+}
+
+impl NumPredicate {
+    fn new(op: NumOp, value: impl Numeric) -> Self {
+        Self {
+            op,
+            value: value.into_num().0,
+        }
+    }
+}
+
+impl fmt::Display for NumPredicate {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "num(var {} {})", self.op.as_str(), self.value)
+    }
+}
+
+impl PredicateReflection for NumPredicate {
+    fn parameters<'a>(&'a self) -> Box<dyn Iterator<Item = Parameter<'a>> + 'a> {
+        let params = vec![Parameter::new(self.op.as_str(), &self.value)];
+        Box::new(params.into_iter())
+    }
+}
+
+impl Predicate<TracedValue> for NumPredicate {
+    fn eval(&self, variable: &TracedValue) -> bool {
+        Num::from_value(variable)
+            .and_then(|num| num.compare(self.value))
+            .is_some_and(|ordering| self.op.matches(ordering))
+    }
+
+    fn find_case(&self, expected: bool, variable: &TracedValue) -> Option<Case<'_>> {
+        if self.eval(variable) == expected {
+            let product = Product::new("var", format!("{variable:?}"));
+            Some(Case::new(Some(self), expected).add_product(product))
+        } else {
+            None
+        }
+    }
+}
+
+impl IntoFieldPredicate for NumPredicate {
+    type Predicate = Self;
+
+    fn into_predicate(self) -> Self::Predicate {
+        self
+    }
+}
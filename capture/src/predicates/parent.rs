@@ -1,7 +1,7 @@
-//! `parent()` predicate factory.
+//! `parent()`, `ancestor()` and `depth()` predicate factories.
 
 use predicates::{
-    reflection::{Case, PredicateReflection, Product},
+    reflection::{Case, Child, PredicateReflection, Product},
     Predicate,
 };
 
@@ -59,7 +59,15 @@ where
     }
 }
 
-impl<P> PredicateReflection for ParentPredicate<P> where P: for<'a> Predicate<CapturedSpan<'a>> {}
+impl<P> PredicateReflection for ParentPredicate<P>
+where
+    P: for<'a> Predicate<CapturedSpan<'a>>,
+{
+    fn children<'a>(&'a self) -> Box<dyn Iterator<Item = Child<'a>> + 'a> {
+        let children = vec![Child::new("parent", &self.matches)];
+        Box::new(children.into_iter())
+    }
+}
 
 impl<'a, P, T> Predicate<T> for ParentPredicate<P>
 where
@@ -68,7 +76,7 @@ where
 {
     fn eval(&self, variable: &T) -> bool {
         let parent = variable.parent();
-        parent.map_or(false, |parent| self.matches.eval(&parent))
+        parent.is_some_and(|parent| self.matches.eval(&parent))
     }
 
     fn find_case(&self, expected: bool, variable: &T) -> Option<Case<'_>> {
@@ -138,7 +146,15 @@ where
     }
 }
 
-impl<P> PredicateReflection for AncestorPredicate<P> where P: for<'a> Predicate<CapturedSpan<'a>> {}
+impl<P> PredicateReflection for AncestorPredicate<P>
+where
+    P: for<'a> Predicate<CapturedSpan<'a>>,
+{
+    fn children<'a>(&'a self) -> Box<dyn Iterator<Item = Child<'a>> + 'a> {
+        let children = vec![Child::new("ancestor", &self.matches)];
+        Box::new(children.into_iter())
+    }
+}
 
 impl<'a, P, T> Predicate<T> for AncestorPredicate<P>
 where
@@ -166,3 +182,73 @@ where
         }
     }
 }
+
+/// Creates a predicate for the depth of a [`CapturedSpan`] or a [`CapturedEvent`] in the span tree,
+/// i.e., the number of its ancestor spans. Root spans and events not tied to a span
+/// have depth 0.
+///
+/// [`CapturedEvent`]: crate::CapturedEvent
+///
+/// # Examples
+///
+/// ```
+/// # use predicates::ord::eq;
+/// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+/// # use tracing_capture::{predicates::*, CaptureLayer, SharedStorage};
+/// let storage = SharedStorage::default();
+/// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+/// tracing::subscriber::with_default(subscriber, || {
+///     let _entered = tracing::info_span!("wrapper").entered();
+///     tracing::info_span!("compute").in_scope(|| {
+///         tracing::info!(answer = 42, "done");
+///     });
+/// });
+///
+/// let storage = storage.lock();
+/// let _ = storage.scan_spans().single(&depth(eq(1)));
+/// let _ = storage.scan_events().single(&depth(eq(2)));
+/// ```
+pub fn depth<P: Predicate<usize>>(matches: P) -> DepthPredicate<P> {
+    DepthPredicate { matches }
+}
+
+/// Predicate for the depth of a [`CapturedSpan`] or [`CapturedEvent`] returned
+/// by the [`depth()`] function.
+///
+/// [`CapturedEvent`]: crate::CapturedEvent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DepthPredicate<P> {
+    matches: P,
+}
+
+impl_bool_ops!(DepthPredicate<P>);
+
+impl<P: Predicate<usize>> fmt::Display for DepthPredicate<P> {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "depth({})", self.matches)
+    }
+}
+
+impl<P: Predicate<usize>> PredicateReflection for DepthPredicate<P> {
+    fn children<'a>(&'a self) -> Box<dyn Iterator<Item = Child<'a>> + 'a> {
+        let children = vec![Child::new("depth", &self.matches)];
+        Box::new(children.into_iter())
+    }
+}
+
+impl<'a, P, T> Predicate<T> for DepthPredicate<P>
+where
+    T: Captured<'a>,
+    P: Predicate<usize>,
+{
+    fn eval(&self, variable: &T) -> bool {
+        let depth = iter::successors(variable.parent(), CapturedSpan::parent).count();
+        self.matches.eval(&depth)
+    }
+
+    fn find_case(&self, expected: bool, variable: &T) -> Option<Case<'_>> {
+        let depth = iter::successors(variable.parent(), CapturedSpan::parent).count();
+        let child = self.matches.find_case(expected, &depth)?;
+        Some(Case::new(Some(self), expected).add_child(child))
+    }
+}
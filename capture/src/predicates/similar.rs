@@ -0,0 +1,94 @@
+//! `similar_to()` predicate factory.
+
+use predicates::{
+    reflection::{Case, Parameter, PredicateReflection, Product},
+    Predicate,
+};
+
+use std::fmt;
+
+use crate::{CapturedSpan, StructuralEqOptions, TraceTree};
+
+/// Creates a predicate checking that a [`CapturedSpan`] is
+/// [structurally equal](CapturedSpan::structurally_eq()) to the specified span.
+///
+/// The structure of the span is copied when the predicate is created, so the predicate
+/// does not borrow the [`Storage`](crate::Storage). By default, all aspects of the span
+/// structure are compared; use [`SimilarToPredicate::with_options()`] to configure this.
+///
+/// # Examples
+///
+/// ```
+/// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+/// # use tracing_capture::{
+/// #     predicates::{similar_to, ScanExt}, CaptureLayer, SharedStorage, StructuralEqOptions,
+/// # };
+/// let storage = SharedStorage::default();
+/// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+/// tracing::subscriber::with_default(subscriber, || {
+///     for attempt in 0..3 {
+///         tracing::info_span!("request", attempt).in_scope(|| {
+///             tracing::warn!("timed out");
+///         });
+///     }
+/// });
+///
+/// let storage = storage.lock();
+/// let first_attempt = storage.root_spans().next().unwrap();
+/// let options = StructuralEqOptions::default().ignore_field("attempt");
+/// let predicate = similar_to(&first_attempt).with_options(options);
+/// storage.scan_spans().all(&predicate);
+/// ```
+pub fn similar_to(span: &CapturedSpan<'_>) -> SimilarToPredicate {
+    SimilarToPredicate {
+        expected: span.to_owned_tree(),
+        options: StructuralEqOptions::default(),
+    }
+}
+
+/// Predicate for the structure of a [`CapturedSpan`] returned by the [`similar_to()`] function.
+#[derive(Debug, Clone)]
+pub struct SimilarToPredicate {
+    expected: TraceTree,
+    options: StructuralEqOptions,
+}
+
+impl_bool_ops!(SimilarToPredicate);
+impl_ref_predicate!(SimilarToPredicate: CapturedSpan);
+
+impl SimilarToPredicate {
+    /// Sets the options for comparing spans.
+    #[must_use]
+    pub fn with_options(mut self, options: StructuralEqOptions) -> Self {
+        self.options = options;
+        self
+    }
+}
+
+impl fmt::Display for SimilarToPredicate {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "similar_to({})", self.expected.metadata.name)
+    }
+}
+
+impl PredicateReflection for SimilarToPredicate {
+    fn parameters<'a>(&'a self) -> Box<dyn Iterator<Item = Parameter<'a>> + 'a> {
+        let params = vec![Parameter::new("name", &self.expected.metadata.name)];
+        Box::new(params.into_iter())
+    }
+}
+
+impl Predicate<CapturedSpan<'_>> for SimilarToPredicate {
+    fn eval(&self, variable: &CapturedSpan<'_>) -> bool {
+        self.options
+            .trees_eq(&self.expected, &variable.to_owned_tree())
+    }
+
+    fn find_case(&self, expected: bool, variable: &CapturedSpan<'_>) -> Option<Case<'_>> {
+        if self.eval(variable) != expected {
+            return None;
+        }
+        let product = Product::new("name", variable.metadata().name());
+        Some(Case::new(Some(self), expected).add_product(product))
+    }
+}
@@ -0,0 +1,206 @@
+//! `entered()`, `exited()`, `closed()` and `outlived_parent()` predicate factories.
+
+use predicates::{
+    reflection::{Case, Child, PredicateReflection, Product},
+    Predicate,
+};
+
+use std::fmt;
+
+use crate::{CapturedSpan, SpanStats};
+
+/// Creates a predicate for the number of times a [`CapturedSpan`] was entered,
+/// as per [`SpanStats::entered`]. This can be used to check, e.g., the number of attempts
+/// of a retried operation.
+///
+/// # Examples
+///
+/// ```
+/// # use predicates::ord::{eq, ge};
+/// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+/// # use tracing_capture::{predicates::{closed, entered, exited, name, ScanExt}, CaptureLayer, SharedStorage};
+/// let storage = SharedStorage::default();
+/// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+/// tracing::subscriber::with_default(subscriber, || {
+///     let span = tracing::info_span!("request");
+///     for attempt in 0..3 {
+///         span.in_scope(|| tracing::info!(attempt, "sending request"));
+///     }
+/// });
+///
+/// let storage = storage.lock();
+/// let predicate = name(eq("request")) & entered(eq(3)) & exited(ge(3)) & closed();
+/// let _ = storage.scan_spans().single(&predicate);
+/// ```
+pub fn entered<P: Predicate<usize>>(matches: P) -> SpanCountPredicate<P> {
+    SpanCountPredicate {
+        stat: SpanCountStat::Entered,
+        matches,
+    }
+}
+
+/// Creates a predicate for the number of times a [`CapturedSpan`] was exited,
+/// as per [`SpanStats::exited`]. See [`entered()`] for an example of usage.
+pub fn exited<P: Predicate<usize>>(matches: P) -> SpanCountPredicate<P> {
+    SpanCountPredicate {
+        stat: SpanCountStat::Exited,
+        matches,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SpanCountStat {
+    Entered,
+    Exited,
+}
+
+impl SpanCountStat {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Entered => "entered",
+            Self::Exited => "exited",
+        }
+    }
+
+    fn get(self, stats: &SpanStats) -> usize {
+        match self {
+            Self::Entered => stats.entered,
+            Self::Exited => stats.exited,
+        }
+    }
+}
+
+/// Predicate for the number of entries into or exits from a [`CapturedSpan`] returned by
+/// the [`entered()`] and [`exited()`] functions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpanCountPredicate<P> {
+    stat: SpanCountStat,
+    matches: P,
+}
+
+impl_bool_ops!(SpanCountPredicate<P>);
+impl_ref_predicate!(SpanCountPredicate<P>: CapturedSpan);
+
+impl<P: Predicate<usize>> fmt::Display for SpanCountPredicate<P> {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "{}({})", self.stat.as_str(), self.matches)
+    }
+}
+
+impl<P: Predicate<usize>> PredicateReflection for SpanCountPredicate<P> {
+    fn children<'a>(&'a self) -> Box<dyn Iterator<Item = Child<'a>> + 'a> {
+        let children = vec![Child::new(self.stat.as_str(), &self.matches)];
+        Box::new(children.into_iter())
+    }
+}
+
+impl<P: Predicate<usize>> Predicate<CapturedSpan<'_>> for SpanCountPredicate<P> {
+    fn eval(&self, variable: &CapturedSpan<'_>) -> bool {
+        self.matches.eval(&self.stat.get(&variable.stats()))
+    }
+
+    fn find_case(&self, expected: bool, variable: &CapturedSpan<'_>) -> Option<Case<'_>> {
+        let count = self.stat.get(&variable.stats());
+        let child = self.matches.find_case(expected, &count)?;
+        Some(
+            Case::new(Some(self), expected)
+                .add_product(Product::new(self.stat.as_str(), count))
+                .add_child(child),
+        )
+    }
+}
+
+/// Creates a predicate checking whether a [`CapturedSpan`] is closed, as per
+/// [`SpanStats::is_closed`]. See [`entered()`] for an example of usage.
+pub fn closed() -> ClosedPredicate {
+    ClosedPredicate { _private: () }
+}
+
+/// Predicate checking whether a [`CapturedSpan`] is closed. Returned by
+/// the [`closed()`] function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClosedPredicate {
+    _private: (),
+}
+
+impl_bool_ops!(ClosedPredicate);
+impl_ref_predicate!(ClosedPredicate: CapturedSpan);
+
+impl fmt::Display for ClosedPredicate {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("closed()")
+    }
+}
+
+impl PredicateReflection for ClosedPredicate {}
+
+impl Predicate<CapturedSpan<'_>> for ClosedPredicate {
+    fn eval(&self, variable: &CapturedSpan<'_>) -> bool {
+        variable.stats().is_closed
+    }
+
+    fn find_case(&self, expected: bool, variable: &CapturedSpan<'_>) -> Option<Case<'_>> {
+        let is_closed = variable.stats().is_closed;
+        if is_closed != expected {
+            return None;
+        }
+        Some(Case::new(Some(self), expected).add_product(Product::new("is_closed", is_closed)))
+    }
+}
+
+/// Creates a predicate checking whether a [`CapturedSpan`] was closed after its parent
+/// has finished, as per [`CapturedSpan::closed_after_parent()`]. This can be used to detect
+/// detached tasks outliving the operation that spawned them.
+///
+/// # Examples
+///
+/// ```
+/// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+/// # use tracing_capture::{predicates::{outlived_parent, ScanExt}, CaptureLayer, SharedStorage};
+/// let storage = SharedStorage::default();
+/// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+/// tracing::subscriber::with_default(subscriber, || {
+///     let task = tracing::info_span!("request").in_scope(|| tracing::info_span!("task"));
+///     task.in_scope(|| tracing::info!("still running"));
+/// });
+///
+/// let storage = storage.lock();
+/// let task = storage.scan_spans().single(&outlived_parent());
+/// assert_eq!(task.metadata().name(), "task");
+/// ```
+pub fn outlived_parent() -> OutlivedParentPredicate {
+    OutlivedParentPredicate { _private: () }
+}
+
+/// Predicate checking whether a [`CapturedSpan`] was closed after its parent has finished.
+/// Returned by the [`outlived_parent()`] function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutlivedParentPredicate {
+    _private: (),
+}
+
+impl_bool_ops!(OutlivedParentPredicate);
+impl_ref_predicate!(OutlivedParentPredicate: CapturedSpan);
+
+impl fmt::Display for OutlivedParentPredicate {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("outlived_parent()")
+    }
+}
+
+impl PredicateReflection for OutlivedParentPredicate {}
+
+impl Predicate<CapturedSpan<'_>> for OutlivedParentPredicate {
+    fn eval(&self, variable: &CapturedSpan<'_>) -> bool {
+        variable.closed_after_parent() == Some(true)
+    }
+
+    fn find_case(&self, expected: bool, variable: &CapturedSpan<'_>) -> Option<Case<'_>> {
+        let closed_after_parent = variable.closed_after_parent();
+        if (closed_after_parent == Some(true)) != expected {
+            return None;
+        }
+        let product = Product::new("closed_after_parent", format!("{closed_after_parent:?}"));
+        Some(Case::new(Some(self), expected).add_product(product))
+    }
+}
@@ -1,7 +1,7 @@
 //! `target()` predicate factory.
 
 use predicates::{
-    reflection::{Case, PredicateReflection, Product},
+    reflection::{Case, Child, Parameter, PredicateReflection, Product},
     Predicate,
 };
 
@@ -45,15 +45,18 @@ impl fmt::Display for TargetStrPredicate<'_> {
     }
 }
 
-impl PredicateReflection for TargetStrPredicate<'_> {}
+impl PredicateReflection for TargetStrPredicate<'_> {
+    fn parameters<'a>(&'a self) -> Box<dyn Iterator<Item = Parameter<'a>> + 'a> {
+        let params = vec![Parameter::new("prefix", &self.prefix)];
+        Box::new(params.into_iter())
+    }
+}
 
 impl Predicate<str> for TargetStrPredicate<'_> {
     fn eval(&self, variable: &str) -> bool {
         variable
             .strip_prefix(self.prefix)
-            .map_or(false, |stripped| {
-                stripped.is_empty() || stripped.starts_with("::")
-            })
+            .is_some_and(|stripped| stripped.is_empty() || stripped.starts_with("::"))
     }
 
     fn find_case(&self, expected: bool, variable: &str) -> Option<Case<'_>> {
@@ -124,7 +127,12 @@ impl<P: Predicate<str>> fmt::Display for TargetPredicate<P> {
     }
 }
 
-impl<P: Predicate<str>> PredicateReflection for TargetPredicate<P> {}
+impl<P: Predicate<str>> PredicateReflection for TargetPredicate<P> {
+    fn children<'a>(&'a self) -> Box<dyn Iterator<Item = Child<'a>> + 'a> {
+        let children = vec![Child::new("target", &self.matches)];
+        Box::new(children.into_iter())
+    }
+}
 
 impl<'a, P: Predicate<str>, T: Captured<'a>> Predicate<T> for TargetPredicate<P> {
     fn eval(&self, variable: &T) -> bool {
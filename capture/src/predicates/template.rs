@@ -0,0 +1,172 @@
+//! `message_with_fields()` predicate factory.
+
+use predicates::{
+    reflection::{Case, Parameter, PredicateReflection, Product},
+    Predicate,
+};
+
+use std::fmt;
+
+use crate::{values::display_value, CapturedEvent};
+
+/// Creates a predicate checking that the message of a [`CapturedEvent`] matches a template
+/// with `{field}` placeholders substituted from the event fields.
+///
+/// Field values are substituted as they would be presented by `Display`: strings and `Debug`
+/// objects as-is, and scalar values (numbers, bools) formatted. Literal braces
+/// can be escaped as `{{` and `}}`. If a field referenced by the template is missing
+/// from the event, the predicate does not match.
+///
+/// This is useful to check consistency between the message and the fields of an event,
+/// e.g. that the message interpolates the same values that are recorded as fields.
+///
+/// # Panics
+///
+/// Panics if the template is malformed, i.e., contains an unclosed placeholder,
+/// an empty placeholder, or an unescaped closing brace.
+///
+/// # Examples
+///
+/// ```
+/// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+/// # use tracing_capture::{predicates::{message_with_fields, ScanExt}, CaptureLayer, SharedStorage};
+/// let storage = SharedStorage::default();
+/// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+/// tracing::subscriber::with_default(subscriber, || {
+///     let (user, attempt) = ("alice", 3);
+///     tracing::info!(user, attempt, "user {user} logged in (attempt #{attempt})");
+/// });
+///
+/// let storage = storage.lock();
+/// let events = storage.scan_events();
+/// let _ = events.single(&message_with_fields("user {user} logged in (attempt #{attempt})"));
+/// // The predicate fails if a field has another value...
+/// events.none(&message_with_fields("user {user} logged in (attempt #1)"));
+/// // ...or is missing.
+/// events.none(&message_with_fields("user {name} logged in"));
+/// ```
+pub fn message_with_fields(template: impl Into<String>) -> MessageTemplatePredicate {
+    let template = template.into();
+    let parts = TemplatePart::parse(&template)
+        .unwrap_or_else(|err| panic!("invalid message template {template:?}: {err}"));
+    MessageTemplatePredicate { template, parts }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum TemplatePart {
+    Text(String),
+    Field(String),
+}
+
+impl TemplatePart {
+    fn parse(template: &str) -> Result<Vec<Self>, &'static str> {
+        let mut parts = vec![];
+        let mut text = String::new();
+        let mut chars = template.chars();
+        while let Some(ch) = chars.next() {
+            match ch {
+                '{' if chars.as_str().starts_with('{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.as_str().starts_with('}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let rest = chars.as_str();
+                    let end = rest.find('}').ok_or("unclosed placeholder")?;
+                    let field_name = &rest[..end];
+                    if field_name.is_empty() {
+                        return Err("empty placeholder");
+                    }
+                    if field_name.contains('{') {
+                        return Err("nested placeholder");
+                    }
+                    if !text.is_empty() {
+                        parts.push(Self::Text(std::mem::take(&mut text)));
+                    }
+                    parts.push(Self::Field(field_name.to_owned()));
+                    chars = rest[end + 1..].chars();
+                }
+                '}' => return Err("unescaped closing brace"),
+                _ => text.push(ch),
+            }
+        }
+        if !text.is_empty() {
+            parts.push(Self::Text(text));
+        }
+        Ok(parts)
+    }
+}
+
+/// Predicate for the message of a [`CapturedEvent`] returned by
+/// the [`message_with_fields()`] function.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MessageTemplatePredicate {
+    template: String,
+    parts: Vec<TemplatePart>,
+}
+
+impl_bool_ops!(MessageTemplatePredicate);
+impl_ref_predicate!(MessageTemplatePredicate: CapturedEvent);
+
+impl MessageTemplatePredicate {
+    /// Substitutes field values into the template. Returns the name of the first missing field
+    /// on error.
+    fn expand(&self, event: &CapturedEvent<'_>) -> Result<String, &str> {
+        let mut expanded = String::new();
+        for part in &self.parts {
+            match part {
+                TemplatePart::Text(text) => expanded.push_str(text),
+                TemplatePart::Field(name) => {
+                    let value = event.value(name).ok_or(name.as_str())?;
+                    expanded.push_str(&display_value(value));
+                }
+            }
+        }
+        Ok(expanded)
+    }
+}
+
+impl fmt::Display for MessageTemplatePredicate {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "message_with_fields({:?})", self.template)
+    }
+}
+
+impl PredicateReflection for MessageTemplatePredicate {
+    fn parameters<'a>(&'a self) -> Box<dyn Iterator<Item = Parameter<'a>> + 'a> {
+        let params = vec![Parameter::new("template", &self.template)];
+        Box::new(params.into_iter())
+    }
+}
+
+impl Predicate<CapturedEvent<'_>> for MessageTemplatePredicate {
+    fn eval(&self, variable: &CapturedEvent<'_>) -> bool {
+        let Some(message) = variable.message() else {
+            return false;
+        };
+        self.expand(variable)
+            .is_ok_and(|expected_message| expected_message == message)
+    }
+
+    fn find_case(&self, expected: bool, variable: &CapturedEvent<'_>) -> Option<Case<'_>> {
+        if self.eval(variable) != expected {
+            return None;
+        }
+
+        let message = variable
+            .message()
+            .map_or_else(|| "None".to_owned(), |message| format!("{message:?}"));
+        let case = Case::new(Some(self), expected).add_product(Product::new("message", message));
+        Some(match self.expand(variable) {
+            Ok(expanded) => {
+                case.add_product(Product::new("expected_message", format!("{expanded:?}")))
+            }
+            Err(field_name) => {
+                case.add_product(Product::new("missing_field", field_name.to_owned()))
+            }
+        })
+    }
+}
@@ -2,7 +2,7 @@
 
 use predicates::{
     constant::always,
-    ord::{eq, gt},
+    ord::{eq, gt, lt},
     prelude::*,
     reflection::{Case, Product},
     str::{ends_with, starts_with},
@@ -11,8 +11,10 @@ use tracing_core::{
     callsite::DefaultCallsite, field::FieldSet, Kind, Level, LevelFilter, Metadata,
 };
 
+use std::{fmt, panic};
+
 use super::*;
-use crate::Storage;
+use crate::{values::CapturedValues, Storage};
 use tracing_tunnel::{TracedValue, TracedValues};
 
 static SITE: DefaultCallsite = DefaultCallsite::new(METADATA);
@@ -60,6 +62,29 @@ fn level_predicates() {
     assert!(!predicate.eval(&span));
 }
 
+#[test]
+fn level_range_predicates() {
+    let mut storage = Storage::new();
+    let span_id = storage.push_span(METADATA, TracedValues::new(), None);
+    let span = storage.span(span_id);
+
+    assert!(level_at_least(Level::INFO).eval(&span));
+    assert!(level_at_least(Level::TRACE).eval(&span));
+    assert!(!level_at_least(Level::WARN).eval(&span));
+
+    assert!(level_in(Level::DEBUG..=Level::ERROR).eval(&span));
+    assert!(level_in(Level::ERROR..=Level::DEBUG).eval(&span));
+    assert!(level_in(Level::INFO..=Level::INFO).eval(&span));
+    assert!(!level_in(Level::WARN..=Level::ERROR).eval(&span));
+    assert!(!level_in(Level::TRACE..=Level::DEBUG).eval(&span));
+
+    let predicate = level_in(Level::WARN..=Level::ERROR);
+    assert_eq!(predicate.to_string(), "level(var in WARN..=ERROR)");
+    let case = predicate.find_case(false, &span).unwrap();
+    let products = collect_products(&case);
+    assert_eq!(products[0].value().to_string(), "INFO");
+}
+
 #[test]
 fn target_predicates() {
     let mut storage = Storage::new();
@@ -90,6 +115,35 @@ fn name_predicates() {
     assert!(!predicate.eval(&span));
 }
 
+#[test]
+fn metadata_predicates() {
+    let mut storage = Storage::new();
+    let span_id = storage.push_span(METADATA, TracedValues::new(), None);
+    let event_id = storage.push_event(EVENT_METADATA, TracedValues::new().into(), Some(span_id));
+    let span = storage.span(span_id);
+    let event = storage.event(event_id);
+
+    assert!(name(starts_with("event")).eval(&event));
+    assert!(module(eq("predicate")).eval(&span));
+    assert!(module(eq("predicate")).eval(&event));
+    let predicate = module(eq("other"));
+    assert_eq!(predicate.to_string(), "module(var == \"other\")");
+    assert!(!predicate.eval(&span));
+
+    assert!(is_span().eval(&span));
+    assert!(!is_span().eval(&event));
+    assert!(is_event().eval(&event));
+    assert_eq!(
+        (is_span() | is_event()).to_string(),
+        "(is_span() || is_event())"
+    );
+    let predicate = is_span();
+    let case = predicate.find_case(false, &event).unwrap();
+    let products = collect_products(&case);
+    assert_eq!(products[0].name(), "kind");
+    assert_eq!(products[0].value().to_string(), "event");
+}
+
 #[test]
 fn compound_predicates() {
     let mut storage = Storage::new();
@@ -108,7 +162,7 @@ fn compound_predicates() {
     assert_eq!(products[0].name(), "fields.val");
     assert_eq!(products[0].value().to_string(), "None");
 
-    storage.spans[span_id].values = TracedValues::from_iter([("val", 23_u64.into())]);
+    storage.spans[span_id].values = CapturedValues::from_iter([("val", 23_u64.into())]);
     let span = storage.span(span_id);
     let case = predicate.find_case(false, &span).unwrap();
     let products = collect_products(&case);
@@ -116,7 +170,7 @@ fn compound_predicates() {
     assert_eq!(products[0].name(), "var");
     assert_eq!(products[0].value().to_string(), "UInt(23)");
 
-    storage.spans[span_id].values = TracedValues::from_iter([("val", 42_u64.into())]);
+    storage.spans[span_id].values = CapturedValues::from_iter([("val", 42_u64.into())]);
     let span = storage.span(span_id);
     let eval = predicate.eval(&span);
     assert!(eval);
@@ -173,12 +227,12 @@ fn message_predicates() {
             TracedValue::debug(&format_args!("completed computations")),
         ),
     ]);
-    let event_id = storage.push_event(EVENT_METADATA, values, None);
+    let event_id = storage.push_event(EVENT_METADATA, values.into(), None);
     let event = storage.event(event_id);
     let predicate = message(eq("completed computations"));
     assert!(predicate.eval(&event));
 
-    storage.events[event_id].values = TracedValues::from_iter([("val", 42_i64.into())]);
+    storage.events[event_id].values = TracedValues::from_iter([("val", 42_i64.into())]).into();
     assert!(!predicate.eval(&storage.event(event_id)));
     storage.events[event_id]
         .values
@@ -195,6 +249,301 @@ fn message_predicates() {
     assert!(predicate.eval(&event));
 }
 
+#[test]
+fn numeric_predicates() {
+    assert!(num_eq(5).eval(&TracedValue::from(5_u64)));
+    assert!(num_eq(5_u8).eval(&TracedValue::from(5_i64)));
+    assert!(num_eq(5.0).eval(&TracedValue::from(5_u64)));
+    assert!(num_eq(-3).eval(&TracedValue::from(-3.0)));
+    assert!(!num_eq(5).eval(&TracedValue::from(6_u64)));
+    assert!(!num_eq(5).eval(&TracedValue::from("5")));
+    assert!(!num_eq(5).eval(&TracedValue::from(f64::NAN)));
+
+    assert!(num_gt(-1).eval(&TracedValue::from(0_u64)));
+    assert!(num_lt(0_u64).eval(&TracedValue::from(-1_i64)));
+    assert!(num_lt(u128::MAX).eval(&TracedValue::from(i128::MAX)));
+    assert!(num_ge(1.5).eval(&TracedValue::from(2_i64)));
+    assert!(num_le(2_usize).eval(&TracedValue::from(2.0)));
+    assert!(!num_le(2_usize).eval(&TracedValue::from(2.5)));
+    assert!(!num_gt(f64::NAN).eval(&TracedValue::from(2.5)));
+
+    let mut storage = Storage::new();
+    let values = TracedValues::from_iter([("val", 42_u64.into())]);
+    let span_id = storage.push_span(METADATA, values, None);
+    let span = storage.span(span_id);
+    let predicate = field("val", num_eq(42_i32));
+    assert!(predicate.eval(&span));
+    let predicate = field("val", num_gt(42));
+    assert_eq!(predicate.to_string(), "fields.val(num(var > 42))");
+    let case = predicate.find_case(false, &span).unwrap();
+    let products = collect_products(&case);
+    assert_eq!(products.len(), 1);
+    assert_eq!(products[0].value().to_string(), "UInt(42)");
+}
+
+#[derive(Debug)]
+struct ChainedError {
+    message: &'static str,
+    source: Option<Box<ChainedError>>,
+}
+
+impl ChainedError {
+    fn new(messages: &[&'static str]) -> Self {
+        let (message, rest) = messages.split_first().unwrap();
+        Self {
+            message,
+            source: if rest.is_empty() {
+                None
+            } else {
+                Some(Box::new(Self::new(rest)))
+            },
+        }
+    }
+}
+
+impl fmt::Display for ChainedError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str(self.message)
+    }
+}
+
+impl std::error::Error for ChainedError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.source
+            .as_deref()
+            .map(|err| err as &(dyn std::error::Error + 'static))
+    }
+}
+
+#[test]
+fn bytes_predicates() {
+    let value = TracedValue::from(&b"\x01\x00ping"[..]);
+    assert!(bytes_len(eq(6)).eval(&value));
+    assert!(bytes_prefix([1, 0]).eval(&value));
+    assert!(bytes_prefix(b"\x01\x00ping").eval(&value));
+    assert!(!bytes_prefix(b"\x01\x00pings").eval(&value));
+    let non_bytes = TracedValue::from("\x01\x00ping");
+    assert!(!bytes_len(always()).eval(&non_bytes));
+    assert!(!bytes_prefix([]).eval(&non_bytes));
+
+    let predicate = bytes_prefix([1, 1]);
+    assert_eq!(predicate.to_string(), "bytes_prefix([1, 1])");
+    let case = predicate.find_case(false, &value).unwrap();
+    let products = collect_products(&case);
+    assert_eq!(products.len(), 1);
+    assert_eq!(products[0].name(), "prefix");
+    assert_eq!(products[0].value().to_string(), "[1, 0]");
+
+    let mut storage = Storage::new();
+    let values = TracedValues::from_iter([("val", value)]);
+    let span_id = storage.push_span(METADATA, values, None);
+    let span = storage.span(span_id);
+    let predicate = field("val", bytes_len(lt(5)));
+    assert_eq!(predicate.to_string(), "fields.val(bytes_len(var < 5))");
+    let case = predicate.find_case(false, &span).unwrap();
+    let products = collect_products(&case);
+    assert_eq!(products.len(), 1);
+    assert_eq!(products[0].value().to_string(), "6");
+}
+
+#[test]
+fn error_predicates() {
+    let err = ChainedError::new(&["request failed", "connection lost", "timeout"]);
+    let value = TracedValue::error(&err);
+    assert!(error_message(eq("request failed")).eval(&value));
+    assert!(!error_message(ends_with("timeout")).eval(&value));
+    assert!(error_source_depth(eq(2)).eval(&value));
+    assert!(!error_source_depth(gt(2)).eval(&value));
+    let leaf_value = TracedValue::error(&ChainedError::new(&["timeout"]));
+    assert!(error_source_depth(eq(0)).eval(&leaf_value));
+
+    let non_error = TracedValue::from("request failed");
+    assert!(!error_message(always()).eval(&non_error));
+    assert!(!error_source_depth(always()).eval(&non_error));
+
+    let mut storage = Storage::new();
+    let values = TracedValues::from_iter([("val", value)]);
+    let span_id = storage.push_span(METADATA, values, None);
+    let span = storage.span(span_id);
+    let predicate = field("val", error_message(starts_with("request")))
+        & field("val", error_source_depth(gt(1)));
+    assert!(predicate.eval(&span));
+
+    let predicate = field("val", error_source_depth(gt(5)));
+    assert_eq!(
+        predicate.to_string(),
+        "fields.val(error_source_depth(var > 5))"
+    );
+    let case = predicate.find_case(false, &span).unwrap();
+    let products = collect_products(&case);
+    assert_eq!(products.len(), 1);
+    assert_eq!(products[0].value().to_string(), "2");
+}
+
+#[test]
+fn fields_subset_predicates() {
+    let mut storage = Storage::new();
+    let values = TracedValues::from_iter([
+        ("val", 42_i64.into()),
+        ("name", "test".into()),
+        ("flag", true.into()),
+    ]);
+    let span_id = storage.push_span(METADATA, values, None);
+    let span = storage.span(span_id);
+
+    let predicate = fields_subset([("val", 42_i64.into()), ("flag", true.into())]);
+    assert_eq!(
+        predicate.to_string(),
+        "fields_subset(val == Int(42), flag == Bool(true))"
+    );
+    assert!(predicate.eval(&span));
+    assert!(fields_subset([]).eval(&span));
+    assert!(!fields_subset([("val", 42_u64.into())]).eval(&span));
+
+    let predicate = fields_subset([
+        ("val", 42_i64.into()),
+        ("name", "other".into()),
+        ("missing", 1.5.into()),
+    ]);
+    assert!(!predicate.eval(&span));
+    let case = predicate.find_case(false, &span).unwrap();
+    let products = collect_products(&case);
+    assert_eq!(products.len(), 2);
+    assert_eq!(products[0].name(), "fields.name");
+    assert_eq!(products[0].value().to_string(), "String(\"test\")");
+    assert_eq!(products[1].name(), "fields.missing");
+    assert_eq!(products[1].value().to_string(), "None");
+    assert!(predicate.find_case(true, &span).is_none());
+}
+
+#[test]
+fn field_count_predicates() {
+    let mut storage = Storage::new();
+    let values = TracedValues::from_iter([("val", 42_i64.into()), ("message", "test".into())]);
+    let event_id = storage.push_event(EVENT_METADATA, values.into(), None);
+    let event = storage.event(event_id);
+
+    let predicate = field_count(eq(2));
+    assert_eq!(predicate.to_string(), "field_count(var == 2)");
+    assert!(predicate.eval(&event));
+    assert!(!field_count(gt(2)).eval(&event));
+
+    let span_id = storage.push_span(METADATA, TracedValues::new(), None);
+    let span = storage.span(span_id);
+    assert!(field_count(eq(0)).eval(&span));
+    let predicate = field_count(eq(1)) & name(eq("test_span"));
+    let case = predicate.find_case(false, &span).unwrap();
+    let products = collect_products(&case);
+    assert_eq!(products.len(), 1);
+    assert_eq!(products[0].value().to_string(), "0");
+}
+
+#[test]
+fn glob_matching() {
+    assert!(glob::glob_matches("test", "test"));
+    assert!(!glob::glob_matches("test", "tests"));
+    assert!(glob::glob_matches("test*", "tests"));
+    assert!(glob::glob_matches("*", ""));
+    assert!(glob::glob_matches("t?st", "tëst"));
+    assert!(glob::glob_matches("*ab*c", "xaabxbc"));
+    assert!(!glob::glob_matches("*ab*c", "xaabxb"));
+    assert!(glob::glob_matches("a*b*c*", "abc"));
+    assert!(!glob::glob_matches("a?c", "ac"));
+}
+
+#[test]
+fn any_field_predicates() {
+    let mut storage = Storage::new();
+    let values = TracedValues::from_iter([
+        ("user.id", 42_u64.into()),
+        ("user.email", "me@example.com".into()),
+        ("val", 23_i64.into()),
+    ]);
+    let span_id = storage.push_span(METADATA, values, None);
+    let span = storage.span(span_id);
+
+    let predicate = any_field(matching("user.*"), 42_u64);
+    assert_eq!(
+        predicate.to_string(),
+        "any_field(var.matches(\"user.*\"), var == 42)"
+    );
+    assert!(predicate.eval(&span));
+    assert!(any_field(matching("user.?mail"), "me@example.com").eval(&span));
+    assert!(!any_field(matching("user.*"), 23_i64).eval(&span));
+    let case = predicate.find_case(true, &span).unwrap();
+    let products = collect_products(&case);
+    assert_eq!(products[0].name(), "field");
+    assert_eq!(products[0].value().to_string(), "user.id");
+
+    let predicate = any_field(matching("user.*"), value(gt(50_u64)));
+    let case = predicate.find_case(false, &span).unwrap();
+    let products = collect_products(&case);
+    assert_eq!(products.len(), 2);
+    assert_eq!(products[0].name(), "fields.user.id");
+    assert_eq!(products[1].name(), "fields.user.email");
+    assert!(predicate.find_case(true, &span).is_none());
+
+    let predicate = any_field(matching("request.*"), [always()]);
+    assert!(!predicate.eval(&span));
+    let case = predicate.find_case(false, &span).unwrap();
+    let products = collect_products(&case);
+    assert_eq!(products[0].name(), "fields");
+    assert_eq!(products[0].value().to_string(), "None");
+}
+
+#[test]
+fn event_count_predicates() {
+    let mut storage = Storage::new();
+    let span_id = storage.push_span(METADATA, TracedValues::new(), None);
+    for val in 0_i64..3 {
+        let values = TracedValues::from_iter([("val", val.into())]);
+        storage.push_event(EVENT_METADATA, values.into(), Some(span_id));
+    }
+    let span = storage.span(span_id);
+
+    let predicate = event_count(eq(3));
+    assert_eq!(predicate.to_string(), "event_count(var == 3)");
+    assert!(predicate.eval(&span));
+    let predicate = event_count(lt(3));
+    let case = predicate.find_case(false, &span).unwrap();
+    let products = collect_products(&case);
+    assert_eq!(products.len(), 1);
+    assert_eq!(products[0].value().to_string(), "3");
+}
+
+#[test]
+fn span_stats_predicates() {
+    let mut storage = Storage::new();
+    let span_id = storage.push_span(METADATA, TracedValues::new(), None);
+    let stats = &mut storage.spans.get_mut(span_id).unwrap().stats;
+    stats.entered = 3;
+    stats.exited = 2;
+    let span = storage.span(span_id);
+
+    let predicate = entered(eq(3)) & exited(lt(3));
+    assert_eq!(
+        predicate.to_string(),
+        "(entered(var == 3) && exited(var < 3))"
+    );
+    assert!(predicate.eval(&span));
+    let predicate = entered(gt(3));
+    let case = predicate.find_case(false, &span).unwrap();
+    let products = collect_products(&case);
+    assert_eq!(products.len(), 2);
+    assert_eq!(products[0].name(), "entered");
+    assert_eq!(products[0].value().to_string(), "3");
+
+    let predicate = closed();
+    assert_eq!(predicate.to_string(), "closed()");
+    assert!(!predicate.eval(&span));
+    let case = predicate.find_case(false, &span).unwrap();
+    let products = collect_products(&case);
+    assert_eq!(products.len(), 1);
+    assert_eq!(products[0].name(), "is_closed");
+    assert_eq!(products[0].value().to_string(), "false");
+    assert!(predicate.find_case(true, &span).is_none());
+}
+
 #[test]
 fn using_extensions() {
     let mut storage = Storage::new();
@@ -206,7 +555,7 @@ fn using_extensions() {
                 TracedValue::debug(&format_args!("completed computations")),
             ),
         ]);
-        storage.push_event(EVENT_METADATA, values, None);
+        storage.push_event(EVENT_METADATA, values.into(), None);
     }
     let scanner = storage.scan_events();
 
@@ -225,3 +574,314 @@ fn using_extensions() {
     let event = scanner.first(&field("val", value(gt(2_i64))));
     assert_eq!(event["val"], 3_i64);
 }
+
+#[test]
+fn matching_subsequence() {
+    let mut storage = Storage::new();
+    for val in 0_i64..5 {
+        let values = TracedValues::from_iter([("val", val.into())]);
+        storage.push_event(EVENT_METADATA, values.into(), None);
+    }
+    let scanner = storage.scan_events();
+
+    let matched = scanner.subsequence(&[&field("val", 1_i64), &field("val", value(gt(2_i64)))]);
+    let matched: Vec<_> = matched.iter().map(|event| event["val"].clone()).collect();
+    assert_eq!(matched, [1_i64, 3_i64]);
+    assert!(scanner.subsequence(&[]).is_empty());
+
+    let panic_message = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+        scanner.subsequence(&[&field("val", 3_i64), &field("val", 1_i64)]);
+    }))
+    .unwrap_err();
+    let panic_message = panic_message.downcast_ref::<String>().unwrap();
+    assert!(
+        panic_message.starts_with("no items have matched predicate #1 fields.val(var == 1)"),
+        "{panic_message}"
+    );
+}
+
+#[test]
+fn diffs_for_mismatched_text_values() {
+    let mut storage = Storage::new();
+    let actual = "The quick brown fox jumps over the lazy dog near the river bank";
+    let values = TracedValues::from_iter([("val", actual.into())]);
+    let span_id = storage.push_span(METADATA, values, None);
+    let span = storage.span(span_id);
+
+    let expected = "The quick brown fox jumps over the lazy cat near the river bank";
+    let predicate = field("val", expected);
+    let case = predicate.find_case(false, &span).unwrap();
+    let products = collect_products(&case);
+    assert_eq!(products.len(), 2);
+    assert_eq!(products[1].name(), "diff");
+    assert_eq!(
+        products[1].value().to_string(),
+        "@@ char 40 @@\n\
+         - …s over the lazy cat near the river bank\n\
+         + …s over the lazy dog near the river bank"
+    );
+
+    // Non-text values are not diffed.
+    let predicate = field("val", 42_i64);
+    let case = predicate.find_case(false, &span).unwrap();
+    assert_eq!(collect_products(&case).len(), 1);
+
+    let actual = "first line\nsecond line\nthird line";
+    let values = TracedValues::from_iter([("val", actual.into())]);
+    let span_id = storage.push_span(METADATA, values, None);
+    let span = storage.span(span_id);
+
+    let expected = "first line\nline 2\nthird line";
+    let predicate = fields_subset([("val", expected.into())]);
+    let case = predicate.find_case(false, &span).unwrap();
+    let products = collect_products(&case);
+    assert_eq!(products.len(), 2);
+    assert_eq!(products[1].name(), "fields.val.diff");
+    assert_eq!(
+        products[1].value().to_string(),
+        "@@ line 2 @@\n- line 2\n+ second line"
+    );
+}
+
+#[test]
+fn message_template_predicates() {
+    let mut storage = Storage::new();
+    let values = TracedValues::from_iter([
+        ("val", 42_i64.into()),
+        (
+            "message",
+            TracedValue::debug(&format_args!("computed {{42}} in 0.5s")),
+        ),
+    ]);
+    let event_id = storage.push_event(EVENT_METADATA, values.into(), None);
+    let event = storage.event(event_id);
+
+    let predicate = message_with_fields("computed {{{val}}} in 0.5s");
+    assert!(predicate.eval(&event));
+    assert_eq!(
+        predicate.to_string(),
+        "message_with_fields(\"computed {{{val}}} in 0.5s\")"
+    );
+    let case = predicate.find_case(true, &event).unwrap();
+    let products: Vec<_> = case.products().collect();
+    assert_eq!(products.len(), 2);
+    assert_eq!(products[1].name(), "expected_message");
+    assert_eq!(products[1].value().to_string(), "\"computed {42} in 0.5s\"");
+
+    let predicate = message_with_fields("computed {{{other}}} in 0.5s");
+    assert!(!predicate.eval(&event));
+    let case = predicate.find_case(false, &event).unwrap();
+    let products: Vec<_> = case.products().collect();
+    assert_eq!(products[1].name(), "missing_field");
+    assert_eq!(products[1].value().to_string(), "other");
+
+    storage.events[event_id].values = TracedValues::from_iter([("val", 42_i64.into())]).into();
+    let predicate = message_with_fields("{val}");
+    assert!(!predicate.eval(&storage.event(event_id)));
+}
+
+#[test]
+fn invalid_message_templates() {
+    for (template, expected_err) in [
+        ("computed {val", "unclosed placeholder"),
+        ("computed {} in 0.5s", "empty placeholder"),
+        ("computed {va{l}", "nested placeholder"),
+        ("computed val}", "unescaped closing brace"),
+    ] {
+        let err = panic::catch_unwind(|| message_with_fields(template)).unwrap_err();
+        let err = err.downcast_ref::<String>().unwrap();
+        assert!(err.contains(expected_err), "{err}");
+    }
+}
+
+#[test]
+fn timestamp_predicates() {
+    use std::time::{Duration, Instant};
+
+    let mut storage = Storage::new();
+    let start = Instant::now();
+    let span_id = storage.push_span(METADATA, TracedValues::new(), None);
+    let event_id = storage.push_event(EVENT_METADATA, TracedValues::new().into(), Some(span_id));
+    let end = Instant::now();
+    let span = storage.span(span_id);
+    let event = storage.event(event_id);
+
+    assert!(started_after(start).eval(&span));
+    assert!(started_after(start).eval(&event));
+    assert!(!started_after(end + Duration::from_millis(1)).eval(&event));
+    assert!(ended_before(end).eval(&event));
+    // The span is not closed.
+    assert!(!ended_before(end).eval(&span));
+
+    let predicate = ended_before(end);
+    assert!(predicate.to_string().starts_with("ended_before("));
+    let case = predicate.find_case(false, &span).unwrap();
+    let products = collect_products(&case);
+    assert_eq!(products[0].name(), "closed_at");
+    assert_eq!(products[0].value().to_string(), "None");
+}
+
+#[test]
+fn reflecting_predicate_structure() {
+    use predicates::reflection::PredicateReflection;
+
+    fn collect_tree(predicate: &dyn PredicateReflection, prefix: &str, tree: &mut Vec<String>) {
+        for param in predicate.parameters() {
+            tree.push(format!("{prefix}{}={}", param.name(), param.value()));
+        }
+        for child in predicate.children() {
+            let name = format!("{prefix}{}", child.name());
+            tree.push(name.clone());
+            collect_tree(child.value(), &format!("{name}."), tree);
+        }
+    }
+
+    let predicate = (name(eq("test")) & field("val", 42_u64))
+        | (level_in(Level::WARN..=Level::ERROR) & num_gt(5));
+    let mut tree = vec![];
+    collect_tree(&predicate, "", &mut tree);
+    assert_eq!(
+        tree,
+        [
+            "left",
+            "left.left",
+            "left.left.name",
+            "left.right",
+            "left.right.name=val",
+            "left.right.value",
+            "right",
+            "right.left",
+            "right.left.level",
+            "right.left.level.most_severe=ERROR",
+            "right.left.level.least_severe=WARN",
+            "right.right",
+            "right.right.>=5",
+        ]
+    );
+}
+
+#[test]
+fn similar_to_predicate() {
+    use crate::StructuralEqOptions;
+
+    let mut storage = Storage::new();
+    let mut span_ids = vec![];
+    for val in [1_u64, 1, 2] {
+        let values = TracedValues::from_iter([("val", val.into())]);
+        let span_id = storage.push_span(METADATA, values, None);
+        storage.push_event(EVENT_METADATA, TracedValues::new().into(), Some(span_id));
+        span_ids.push(span_id);
+    }
+    let extra_event_span_id = span_ids[2];
+    storage.push_event(
+        EVENT_METADATA,
+        TracedValues::new().into(),
+        Some(extra_event_span_id),
+    );
+    let spans: Vec<_> = span_ids.iter().map(|&id| storage.span(id)).collect();
+
+    let predicate = similar_to(&spans[0]);
+    assert_eq!(predicate.to_string(), "similar_to(test_span)");
+    assert!(predicate.eval(&spans[0]));
+    assert!(predicate.eval(&spans[1]));
+    assert!(!predicate.eval(&spans[2]));
+    let case = predicate.find_case(false, &spans[2]).unwrap();
+    let products = collect_products(&case);
+    assert_eq!(products[0].name(), "name");
+    assert_eq!(products[0].value().to_string(), "test_span");
+
+    let options = StructuralEqOptions::default().ignore_field("val");
+    assert!(!spans[0].structurally_eq(&spans[2], &options));
+    let options = options.ignore_events();
+    assert!(spans[0].structurally_eq(&spans[2], &options));
+    let predicate = similar_to(&spans[0]).with_options(options);
+    assert!(predicate.eval(&spans[2]));
+    let options = StructuralEqOptions::default()
+        .ignore_values()
+        .ignore_events();
+    assert!(spans[0].structurally_eq(&spans[2], &options));
+}
+
+#[test]
+fn predicates_on_references_and_boxed_predicates() {
+    fn assert_send_sync<T: Send + Sync>(_: &T) {}
+
+    let mut storage = Storage::new();
+    let values = TracedValues::from_iter([("val", 42_u64.into())]);
+    let span_id = storage.push_span(METADATA, values, None);
+    let event_id = storage.push_event(EVENT_METADATA, TracedValues::new().into(), Some(span_id));
+    let span = storage.span(span_id);
+    let event = storage.event(event_id);
+
+    let span_predicate = name(eq("test_span")) & field("val", 42_u64) & event_count(eq(1));
+    assert!(span_predicate.eval(&&span));
+    let spans = [span];
+    assert_eq!(
+        spans
+            .iter()
+            .filter(|span| span_predicate.eval(span))
+            .count(),
+        1
+    );
+    let event_predicate = level(Level::DEBUG) & parent(name(eq("test_span"))) & is_event();
+    assert!(event_predicate.eval(&&event));
+    assert!(!(event_predicate & emitted_while_entered(always())).eval(&&event));
+
+    let boxed_span: BoxedSpanPredicate = Box::new(span_predicate);
+    assert!(boxed_span.eval(&span));
+    let boxed_event: BoxedEventPredicate = Box::new(event_predicate);
+    assert!(boxed_event.eval(&event));
+    let case = boxed_event.find_case(true, &event).unwrap();
+    assert!(!collect_products(&case).is_empty());
+
+    assert_send_sync(&boxed_span);
+    assert_send_sync(&boxed_event);
+}
+
+#[test]
+fn inherited_field_predicate() {
+    let mut storage = Storage::new();
+    let values = TracedValues::from_iter([("val", 42_u64.into())]);
+    let root_id = storage.push_span(METADATA, values, None);
+    let child_id = storage.push_span(METADATA, TracedValues::new(), Some(root_id));
+    let values = TracedValues::from_iter([("val", 5_u64.into())]);
+    let overriding_id = storage.push_span(METADATA, values, Some(child_id));
+    let event_id = storage.push_event(EVENT_METADATA, TracedValues::new().into(), Some(child_id));
+    let orphan_id = storage.push_event(EVENT_METADATA, TracedValues::new().into(), None);
+
+    let child = storage.span(child_id);
+    assert_eq!(
+        child.inherited_value("val"),
+        Some(&TracedValue::from(42_u64))
+    );
+    let overriding = storage.span(overriding_id);
+    assert_eq!(
+        overriding.inherited_value("val"),
+        Some(&TracedValue::from(5_u64))
+    );
+    let event = storage.event(event_id);
+    assert_eq!(
+        event.inherited_value("val"),
+        Some(&TracedValue::from(42_u64))
+    );
+    let orphan = storage.event(orphan_id);
+    assert_eq!(orphan.inherited_value("val"), None);
+
+    let predicate = inherited_field("val", 42_u64);
+    assert_eq!(predicate.to_string(), "inherited.val(var == 42)");
+    assert!(!field("val", 42_u64).eval(&child));
+    assert!(predicate.eval(&child));
+    assert!(predicate.eval(&event));
+    assert!(!predicate.eval(&overriding));
+    assert!(!predicate.eval(&orphan));
+
+    let case = predicate.find_case(false, &orphan).unwrap();
+    let products = collect_products(&case);
+    assert_eq!(products.len(), 1);
+    assert_eq!(products[0].name(), "inherited.val");
+    assert_eq!(products[0].value().to_string(), "None");
+    let case = predicate.find_case(false, &overriding).unwrap();
+    let products = collect_products(&case);
+    assert_eq!(products.len(), 1);
+    assert_eq!(products[0].value().to_string(), "UInt(5)");
+}
@@ -0,0 +1,146 @@
+//! `started_after()` and `ended_before()` predicate factories.
+
+use predicates::{
+    reflection::{Case, PredicateReflection, Product},
+    Predicate,
+};
+
+use std::{fmt, time::Instant};
+
+use crate::{CapturedEvent, CapturedSpan};
+
+/// Creates a predicate checking that a [`CapturedSpan`] was [created](CapturedSpan::created_at())
+/// or a [`CapturedEvent`] was [emitted](CapturedEvent::timestamp()) at or after
+/// the specified instant.
+///
+/// Together with [`ended_before()`], this can be used to scope assertions to a certain phase
+/// of a test.
+///
+/// # Examples
+///
+/// ```
+/// # use std::time::Instant;
+/// # use predicates::ord::eq;
+/// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+/// # use tracing_capture::{predicates::*, CaptureLayer, SharedStorage};
+/// let storage = SharedStorage::default();
+/// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+/// let (start, end) = tracing::subscriber::with_default(subscriber, || {
+///     tracing::info_span!("request").in_scope(|| tracing::info!("ok"));
+///     let start = Instant::now();
+///     tracing::info_span!("failover").in_scope(|| tracing::warn!("switching"));
+///     let end = Instant::now();
+///     tracing::info_span!("request").in_scope(|| tracing::info!("ok"));
+///     (start, end)
+/// });
+///
+/// let storage = storage.lock();
+/// let during_failover = started_after(start) & ended_before(end);
+/// let span = storage.scan_spans().single(&during_failover);
+/// assert_eq!(span.metadata().name(), "failover");
+/// storage.scan_events().single(&during_failover);
+/// storage.scan_spans().none(&(during_failover & name(eq("request"))));
+/// ```
+pub fn started_after(instant: Instant) -> TimestampPredicate {
+    TimestampPredicate {
+        bound: TimeBound::StartedAfter,
+        instant,
+    }
+}
+
+/// Creates a predicate checking that a [`CapturedSpan`] was [closed](CapturedSpan::closed_at())
+/// or a [`CapturedEvent`] was [emitted](CapturedEvent::timestamp()) at or before
+/// the specified instant. Spans that are not closed do not match the predicate.
+///
+/// See [`started_after()`] for an example of usage.
+pub fn ended_before(instant: Instant) -> TimestampPredicate {
+    TimestampPredicate {
+        bound: TimeBound::EndedBefore,
+        instant,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TimeBound {
+    StartedAfter,
+    EndedBefore,
+}
+
+impl TimeBound {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::StartedAfter => "started_after",
+            Self::EndedBefore => "ended_before",
+        }
+    }
+}
+
+/// Predicate for timestamps of [`CapturedSpan`]s and [`CapturedEvent`]s returned by
+/// the [`started_after()`] and [`ended_before()`] functions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimestampPredicate {
+    bound: TimeBound,
+    instant: Instant,
+}
+
+impl_bool_ops!(TimestampPredicate);
+impl_ref_predicate!(TimestampPredicate: CapturedSpan);
+impl_ref_predicate!(TimestampPredicate: CapturedEvent);
+
+impl TimestampPredicate {
+    fn matches(&self, timestamp: Option<Instant>) -> bool {
+        timestamp.is_some_and(|timestamp| match self.bound {
+            TimeBound::StartedAfter => timestamp >= self.instant,
+            TimeBound::EndedBefore => timestamp <= self.instant,
+        })
+    }
+
+    fn timestamp_case(
+        &self,
+        expected: bool,
+        name: &'static str,
+        timestamp: Option<Instant>,
+    ) -> Option<Case<'_>> {
+        if self.matches(timestamp) != expected {
+            return None;
+        }
+        let product = Product::new(name, format!("{timestamp:?}"));
+        Some(Case::new(Some(self), expected).add_product(product))
+    }
+
+    fn span_timestamp(&self, span: &CapturedSpan<'_>) -> (&'static str, Option<Instant>) {
+        match self.bound {
+            TimeBound::StartedAfter => ("created_at", Some(span.created_at())),
+            TimeBound::EndedBefore => ("closed_at", span.closed_at()),
+        }
+    }
+}
+
+impl fmt::Display for TimestampPredicate {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "{}({:?})", self.bound.as_str(), self.instant)
+    }
+}
+
+impl PredicateReflection for TimestampPredicate {}
+
+impl Predicate<CapturedSpan<'_>> for TimestampPredicate {
+    fn eval(&self, variable: &CapturedSpan<'_>) -> bool {
+        self.matches(self.span_timestamp(variable).1)
+    }
+
+    fn find_case(&self, expected: bool, variable: &CapturedSpan<'_>) -> Option<Case<'_>> {
+        let (name, timestamp) = self.span_timestamp(variable);
+        self.timestamp_case(expected, name, timestamp)
+    }
+}
+
+impl Predicate<CapturedEvent<'_>> for TimestampPredicate {
+    fn eval(&self, variable: &CapturedEvent<'_>) -> bool {
+        self.matches(Some(variable.timestamp()))
+    }
+
+    fn find_case(&self, expected: bool, variable: &CapturedEvent<'_>) -> Option<Case<'_>> {
+        self.timestamp_case(expected, "timestamp", Some(variable.timestamp()))
+    }
+}
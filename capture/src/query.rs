@@ -0,0 +1,245 @@
+//! Chained query API for `Storage`.
+
+use tracing_core::Level;
+
+use std::iter;
+
+use crate::{
+    predicates::FieldsSubsetPredicate, Captured, CapturedEvents, CapturedSpan, CapturedSpans,
+    Storage,
+};
+use tracing_tunnel::TracedValue;
+
+/// Entry point for querying a [`Storage`] returned by [`Storage::query()`].
+///
+/// A query is built by choosing the queried items ([`Self::spans()`] or [`Self::events()`])
+/// and chaining filters. Filters are evaluated lazily when the query is iterated.
+/// For more complex conditions, use [predicates](crate::predicates).
+///
+/// # Examples
+///
+/// ```
+/// # use tracing_core::Level;
+/// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+/// # use tracing_capture::{CaptureLayer, SharedStorage};
+/// let storage = SharedStorage::default();
+/// let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+/// tracing::subscriber::with_default(subscriber, || {
+///     let _entered = tracing::info_span!("request").entered();
+///     for count in 0..3 {
+///         tracing::info_span!("compute", count).in_scope(|| {
+///             tracing::debug!("computing");
+///             tracing::warn!(count, "computed");
+///         });
+///     }
+///     tracing::debug_span!("compute").in_scope(|| { /* ... */ });
+/// });
+///
+/// let storage = storage.lock();
+/// let root = storage.root_spans().next().unwrap();
+/// let spans = storage
+///     .query()
+///     .spans()
+///     .name("compute")
+///     .min_level(Level::INFO)
+///     .with_field("count")
+///     .descendants_of(root);
+/// assert_eq!(spans.count(), 3);
+///
+/// let events = storage.query().events().min_level(Level::WARN).field("count", 1_i64);
+/// let events: Vec<_> = events.collect();
+/// assert_eq!(events.len(), 1);
+/// assert_eq!(events[0].message(), Some("computed"));
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct Query<'a> {
+    storage: &'a Storage,
+}
+
+impl<'a> Query<'a> {
+    pub(crate) fn new(storage: &'a Storage) -> Self {
+        Self { storage }
+    }
+
+    /// Queries spans in the order of capture.
+    pub fn spans(self) -> SpanQuery<'a> {
+        QueryIter::new(self.storage.all_spans())
+    }
+
+    /// Queries events in the order of capture.
+    pub fn events(self) -> EventQuery<'a> {
+        QueryIter::new(self.storage.all_events())
+    }
+}
+
+#[derive(Debug, Default)]
+struct Filters<'a> {
+    name: Option<String>,
+    target: Option<String>,
+    level: Option<Level>,
+    min_level: Option<Level>,
+    fields: Vec<(String, Option<TracedValue>)>,
+    parent: Option<CapturedSpan<'a>>,
+    ancestor: Option<CapturedSpan<'a>>,
+}
+
+impl<'a> Filters<'a> {
+    fn matches<T: Captured<'a>>(&self, item: &T) -> bool {
+        let metadata = item.metadata();
+        if self.name.as_ref().is_some_and(|name| name != item.name()) {
+            return false;
+        }
+        if self
+            .target
+            .as_ref()
+            .is_some_and(|target| target != metadata.target())
+        {
+            return false;
+        }
+        if self.level.is_some_and(|level| level != *metadata.level()) {
+            return false;
+        }
+        // More verbose levels are greater, e.g. `Level::TRACE > Level::INFO`.
+        if self
+            .min_level
+            .is_some_and(|min_level| *metadata.level() > min_level)
+        {
+            return false;
+        }
+
+        let fields_match = self.fields.iter().all(|(name, expected)| {
+            let value = item.value(name);
+            match expected {
+                Some(expected) => {
+                    value.is_some_and(|value| FieldsSubsetPredicate::values_eq(expected, value))
+                }
+                None => value.is_some(),
+            }
+        });
+        if !fields_match {
+            return false;
+        }
+
+        if let Some(expected_parent) = &self.parent {
+            if item.parent().as_ref() != Some(expected_parent) {
+                return false;
+            }
+        }
+        if let Some(expected_ancestor) = &self.ancestor {
+            let mut ancestors = iter::successors(item.parent(), CapturedSpan::parent);
+            if !ancestors.any(|span| span == *expected_ancestor) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// Lazily evaluated query over [`CapturedSpan`]s or [`CapturedEvent`]s. Created using [`Query`].
+///
+/// Queries are iterators yielding matching items in the order of capture;
+/// filters can be chained using the methods of this type.
+///
+/// [`CapturedEvent`]: crate::CapturedEvent
+#[derive(Debug)]
+pub struct QueryIter<'a, I> {
+    items: I,
+    filters: Filters<'a>,
+}
+
+/// Query over [`CapturedSpan`]s returned by [`Query::spans()`].
+pub type SpanQuery<'a> = QueryIter<'a, CapturedSpans<'a>>;
+/// Query over [`CapturedEvent`](crate::CapturedEvent)s returned by [`Query::events()`].
+pub type EventQuery<'a> = QueryIter<'a, CapturedEvents<'a>>;
+
+impl<'a, I, T> QueryIter<'a, I>
+where
+    I: Iterator<Item = T>,
+    T: Captured<'a>,
+{
+    fn new(items: I) -> Self {
+        Self {
+            items,
+            filters: Filters::default(),
+        }
+    }
+
+    /// Retains items with the specified name. For spans, the name takes
+    /// the [name field](crate::CaptureLayer::with_name_field()) into account.
+    #[must_use]
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.filters.name = Some(name.into());
+        self
+    }
+
+    /// Retains items with the specified target.
+    #[must_use]
+    pub fn target(mut self, target: impl Into<String>) -> Self {
+        self.filters.target = Some(target.into());
+        self
+    }
+
+    /// Retains items with the specified level.
+    #[must_use]
+    pub fn level(mut self, level: Level) -> Self {
+        self.filters.level = Some(level);
+        self
+    }
+
+    /// Retains items with the level at least as severe as `min_level`. E.g., `min_level(Level::WARN)`
+    /// retains items with `WARN` and `ERROR` levels.
+    #[must_use]
+    pub fn min_level(mut self, min_level: Level) -> Self {
+        self.filters.min_level = Some(min_level);
+        self
+    }
+
+    /// Retains items having the specified field, regardless of its value.
+    #[must_use]
+    pub fn with_field(mut self, name: impl Into<String>) -> Self {
+        self.filters.fields.push((name.into(), None));
+        self
+    }
+
+    /// Retains items having the specified field equal to `value`. Values are compared
+    /// in the same way as in [`fields_subset()`](crate::predicates::fields_subset()), i.e.,
+    /// variants of [`TracedValue`] must match exactly; use [predicates](crate::predicates)
+    /// for more flexible comparisons.
+    #[must_use]
+    pub fn field(mut self, name: impl Into<String>, value: impl Into<TracedValue>) -> Self {
+        self.filters.fields.push((name.into(), Some(value.into())));
+        self
+    }
+
+    /// Retains items with the specified direct parent span.
+    #[must_use]
+    pub fn children_of(mut self, parent: CapturedSpan<'a>) -> Self {
+        self.filters.parent = Some(parent);
+        self
+    }
+
+    /// Retains items that are (direct or indirect) descendants of the specified span.
+    /// The span itself is not retained.
+    #[must_use]
+    pub fn descendants_of(mut self, ancestor: CapturedSpan<'a>) -> Self {
+        self.filters.ancestor = Some(ancestor);
+        self
+    }
+}
+
+impl<'a, I, T> Iterator for QueryIter<'a, I>
+where
+    I: Iterator<Item = T>,
+    T: Captured<'a>,
+{
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let filters = &self.filters;
+        self.items.find(|item| filters.matches(item))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.items.size_hint().1)
+    }
+}
@@ -0,0 +1,235 @@
+//! Deterministic snapshots of captured traces.
+
+use serde::Serialize;
+
+use std::collections::BTreeMap;
+
+use crate::{CapturedEvent, CapturedSpan, Storage};
+use tracing_tunnel::TracedValue;
+
+/// Value substituted for redacted fields by [`StorageSnapshot::redact()`].
+const REDACTED: &str = "[REDACTED]";
+
+/// Deterministic serializable representation of a [`Storage`] returned by
+/// [`Storage::to_snapshot()`].
+///
+/// A snapshot is intended for snapshot testing, e.g. with `insta::assert_yaml_snapshot!`.
+/// Its shape is similar to the trace spec format from the `spec` module: root spans and root events
+/// are listed in the order of capture, with child spans and events nested in their parents.
+/// Snapshots only contain information that does not change between runs or due to minor
+/// refactoring; timestamps, thread indices, source files and line numbers are omitted,
+/// and fields are sorted by name.
+///
+/// Field values are serialized as [`TracedValue`]s, i.e., with the value type, e.g. `int: 42`.
+/// Values that are still not deterministic (e.g., IDs or durations recorded
+/// by the instrumented code) can be [redacted](Self::redact()) or
+/// [normalized](Self::with_normalizer()).
+///
+/// # Examples
+///
+/// ```
+/// # use tracing_subscriber::layer::SubscriberExt;
+/// # use tracing_capture::{CaptureLayer, SharedStorage};
+/// let storage = SharedStorage::default();
+/// let subscriber = tracing_subscriber::registry()
+///     .with(CaptureLayer::new(&storage));
+/// tracing::subscriber::with_default(subscriber, || {
+///     tracing::info_span!("compute", request_id = 12345, count = 5).in_scope(|| {
+///         tracing::debug!(result = 8, "computed");
+///     });
+/// });
+///
+/// let snapshot = storage.lock().to_snapshot().redact("request_id");
+/// insta::assert_yaml_snapshot!(snapshot, @r###"
+/// ---
+/// spans:
+///   - name: compute
+///     target: rust_out
+///     level: INFO
+///     fields:
+///       count:
+///         int: 5
+///       request_id:
+///         string: "[REDACTED]"
+///     events:
+///       - target: rust_out
+///         level: DEBUG
+///         message: computed
+///         fields:
+///           result:
+///             int: 8
+/// "###);
+/// ```
+#[derive(Debug, Clone, Default, Serialize)]
+#[cfg_attr(docsrs, doc(cfg(feature = "snapshot")))]
+#[non_exhaustive]
+pub struct StorageSnapshot {
+    /// Root spans in the order of creation.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub spans: Vec<SpanSnapshot>,
+    /// Root events (i.e., events without a parent span) in the order of capture.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub events: Vec<EventSnapshot>,
+}
+
+impl StorageSnapshot {
+    pub(crate) fn new(storage: &Storage) -> Self {
+        Self {
+            spans: storage.root_spans().map(SpanSnapshot::new).collect(),
+            events: storage.root_events().map(EventSnapshot::new).collect(),
+        }
+    }
+
+    /// Replaces values of the field with the specified name in all spans and events
+    /// with the `"[REDACTED]"` string.
+    #[must_use]
+    pub fn redact(self, field_name: &str) -> Self {
+        self.with_normalizer(field_name, |_| TracedValue::from(REDACTED))
+    }
+
+    /// Replaces values of the field with the specified name in all spans and events
+    /// with the output of `normalizer`. Unlike [redaction](Self::redact()), this allows
+    /// to retain the value type or a deterministic part of the value (e.g., rounding durations
+    /// or replacing random IDs with placeholders), so that the snapshot remains informative.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_subscriber::layer::SubscriberExt;
+    /// # use tracing_capture::{CaptureLayer, SharedStorage};
+    /// # use tracing_tunnel::TracedValue;
+    /// let storage = SharedStorage::default();
+    /// let subscriber = tracing_subscriber::registry()
+    ///     .with(CaptureLayer::new(&storage));
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     tracing::info!(duration_ms = 123_u64, "request completed");
+    /// });
+    ///
+    /// let snapshot = storage
+    ///     .lock()
+    ///     .to_snapshot()
+    ///     .with_normalizer("duration_ms", |_| TracedValue::from(0_u64));
+    /// assert_eq!(snapshot.events[0].fields["duration_ms"], 0_u64);
+    /// ```
+    #[must_use]
+    pub fn with_normalizer(
+        mut self,
+        field_name: &str,
+        normalizer: impl Fn(&TracedValue) -> TracedValue,
+    ) -> Self {
+        for span in &mut self.spans {
+            span.normalize(field_name, &normalizer);
+        }
+        for event in &mut self.events {
+            event.normalize(field_name, &normalizer);
+        }
+        self
+    }
+}
+
+/// Snapshot of a [`CapturedSpan`]. Part of a [`StorageSnapshot`].
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(docsrs, doc(cfg(feature = "snapshot")))]
+#[non_exhaustive]
+pub struct SpanSnapshot {
+    /// [Logical name](CapturedSpan::name()) of the span.
+    pub name: String,
+    /// Span target.
+    pub target: &'static str,
+    /// Span level, e.g. `INFO`.
+    pub level: &'static str,
+    /// Span fields sorted by name.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub fields: BTreeMap<String, TracedValue>,
+    /// Child spans in the order of creation.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub spans: Vec<SpanSnapshot>,
+    /// Events directly attached to the span in the order of capture.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub events: Vec<EventSnapshot>,
+}
+
+impl SpanSnapshot {
+    fn new(span: CapturedSpan<'_>) -> Self {
+        let metadata = span.metadata();
+        Self {
+            name: span.name().to_owned(),
+            target: metadata.target(),
+            level: metadata.level().as_str(),
+            fields: sorted_fields(span.values()),
+            spans: span.children().map(Self::new).collect(),
+            events: span.events().map(EventSnapshot::new).collect(),
+        }
+    }
+
+    fn normalize(&mut self, field_name: &str, normalizer: &dyn Fn(&TracedValue) -> TracedValue) {
+        normalize_field(&mut self.fields, field_name, normalizer);
+        for child in &mut self.spans {
+            child.normalize(field_name, normalizer);
+        }
+        for event in &mut self.events {
+            event.normalize(field_name, normalizer);
+        }
+    }
+}
+
+/// Snapshot of a [`CapturedEvent`]. Part of a [`StorageSnapshot`].
+#[derive(Debug, Clone, Serialize)]
+#[cfg_attr(docsrs, doc(cfg(feature = "snapshot")))]
+#[non_exhaustive]
+pub struct EventSnapshot {
+    /// Event target.
+    pub target: &'static str,
+    /// Event level, e.g. `INFO`.
+    pub level: &'static str,
+    /// Event message, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// Event fields (excluding the message) sorted by name.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub fields: BTreeMap<String, TracedValue>,
+}
+
+impl EventSnapshot {
+    fn new(event: CapturedEvent<'_>) -> Self {
+        let metadata = event.metadata();
+        let values = event.values().filter(|(name, _)| *name != "message");
+        Self {
+            target: metadata.target(),
+            level: metadata.level().as_str(),
+            message: event.message().map(str::to_owned),
+            fields: sorted_fields(values),
+        }
+    }
+
+    fn normalize(&mut self, field_name: &str, normalizer: &dyn Fn(&TracedValue) -> TracedValue) {
+        normalize_field(&mut self.fields, field_name, normalizer);
+    }
+}
+
+fn sorted_fields<'a>(
+    values: impl Iterator<Item = (&'a str, &'a TracedValue)>,
+) -> BTreeMap<String, TracedValue> {
+    values
+        .map(|(name, value)| (name.to_owned(), value.clone()))
+        .collect()
+}
+
+fn normalize_field(
+    fields: &mut BTreeMap<String, TracedValue>,
+    field_name: &str,
+    normalizer: &dyn Fn(&TracedValue) -> TracedValue,
+) {
+    if let Some(value) = fields.get_mut(field_name) {
+        *value = normalizer(value);
+    }
+}
+
+impl Storage {
+    /// Returns a deterministic, serializable snapshot of this storage intended
+    /// for snapshot testing. See [`StorageSnapshot`] docs for details.
+    #[cfg_attr(docsrs, doc(cfg(feature = "snapshot")))]
+    pub fn to_snapshot(&self) -> StorageSnapshot {
+        StorageSnapshot::new(self)
+    }
+}
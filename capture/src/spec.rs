@@ -0,0 +1,464 @@
+//! Declarative specifications of the expected trace shape.
+//!
+//! The core type of this module is [`TraceSpec`]. It can be loaded from YAML
+//! or JSON and checked against a captured [`Storage`], producing a readable report
+//! on mismatch. This allows maintaining expected traces outside of Rust code,
+//! e.g. in acceptance tests.
+//!
+//! # Specification format
+//!
+//! A spec is a map with optional `spans` and `events` fields, which list the expected
+//! root spans and root events, respectively. Each span spec has the following fields:
+//!
+//! - `name` (required): [logical span name](crate::CapturedSpan::name())
+//! - `target`: span target
+//! - `level`: span level, e.g. `info` or `WARN`
+//! - `fields`: map of required span fields. A field value can be a Boolean,
+//!   a number, a string, or `null`; the latter means that any value is accepted.
+//! - `spans`: expected child spans (with the same format)
+//! - `events`: expected events directly attached to the span
+//!
+//! Each event spec has the `message`, `target`, `level` and `fields` fields,
+//! all of which are optional.
+//!
+//! Names, targets, messages and string field values may contain wildcards: `*` matches
+//! any sequence of chars (including an empty one), and `?` matches a single char.
+//! Numbers are compared by value across integer and floating-point variants,
+//! similar to [`num_eq()`](crate::predicates::num_eq). Strings are compared with
+//! [string](TracedValue::String) values, [`Debug`](std::fmt::Debug) representations
+//! of objects and error messages.
+//!
+//! Spans and events listed in the spec must be present in the captured trace in the specified
+//! order. The captured trace may contain spans, events and fields not mentioned in the spec.
+//!
+//! # Examples
+//!
+//! ```
+//! # use tracing_subscriber::layer::SubscriberExt;
+//! use tracing_capture::{spec::TraceSpec, CaptureLayer, SharedStorage};
+//!
+//! let storage = SharedStorage::default();
+//! let subscriber = tracing_subscriber::registry()
+//!     .with(CaptureLayer::new(&storage));
+//! tracing::subscriber::with_default(subscriber, || {
+//!     tracing::info_span!("compute", count = 5).in_scope(|| {
+//!         tracing::debug!(result = 8, "computed fibonacci number");
+//!     });
+//! });
+//!
+//! let spec = TraceSpec::from_yaml(r#"
+//! spans:
+//!   - name: compute
+//!     level: info
+//!     fields:
+//!       count: 5
+//!     events:
+//!       - message: computed *
+//!         fields:
+//!           result: null
+//! "#)?;
+//! spec.assert_matches(&storage.lock());
+//!
+//! let spec = TraceSpec::from_json(r#"{
+//!     "spans": [{ "name": "compute", "fields": { "count": 3 } }]
+//! }"#)?;
+//! let err = spec.check(&storage.lock()).unwrap_err();
+//! assert!(err.to_string().contains("field `count`: expected 3, got Int(5)"));
+//! # Ok::<_, Box<dyn std::error::Error>>(())
+//! ```
+
+use predicates::Predicate;
+use serde::{Deserialize, Deserializer};
+use tracing_core::{Level, Metadata};
+
+use std::{collections::BTreeMap, error, fmt};
+
+use crate::{
+    predicates::{glob_matches, num_eq},
+    CapturedEvent, CapturedEvents, CapturedSpan, CapturedSpans, Storage,
+};
+use tracing_tunnel::TracedValue;
+
+/// Error loading a [`TraceSpec`].
+#[derive(Debug)]
+pub struct SpecError(SpecErrorRepr);
+
+#[derive(Debug)]
+enum SpecErrorRepr {
+    Json(serde_json::Error),
+    Yaml(serde_yaml::Error),
+}
+
+impl fmt::Display for SpecError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.0 {
+            SpecErrorRepr::Json(err) => write!(formatter, "invalid JSON trace spec: {err}"),
+            SpecErrorRepr::Yaml(err) => write!(formatter, "invalid YAML trace spec: {err}"),
+        }
+    }
+}
+
+impl error::Error for SpecError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match &self.0 {
+            SpecErrorRepr::Json(err) => Some(err),
+            SpecErrorRepr::Yaml(err) => Some(err),
+        }
+    }
+}
+
+/// Report on mismatches between a [`TraceSpec`] and a captured trace returned
+/// from [`TraceSpec::check()`].
+#[derive(Debug)]
+pub struct SpecMismatch {
+    mismatches: Vec<Mismatch>,
+}
+
+impl fmt::Display for SpecMismatch {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "captured trace does not match spec:")?;
+        for mismatch in &self.mismatches {
+            mismatch.fmt_indented(formatter, 0)?;
+        }
+        Ok(())
+    }
+}
+
+impl error::Error for SpecMismatch {}
+
+#[derive(Debug)]
+struct Mismatch {
+    path: String,
+    message: String,
+    details: Vec<Mismatch>,
+}
+
+impl Mismatch {
+    fn new(path: &str, message: String) -> Self {
+        Self {
+            path: path.to_owned(),
+            message,
+            details: vec![],
+        }
+    }
+
+    fn fmt_indented(&self, formatter: &mut fmt::Formatter<'_>, indent: usize) -> fmt::Result {
+        write!(formatter, "\n{:indent$}- ", "")?;
+        if !self.path.is_empty() {
+            write!(formatter, "{}: ", self.path)?;
+        }
+        formatter.write_str(&self.message)?;
+        for detail in &self.details {
+            detail.fmt_indented(formatter, indent + 2)?;
+        }
+        Ok(())
+    }
+}
+
+/// Declarative specification of the expected trace shape.
+///
+/// See [module-level docs](self) for the specification format and an example of usage.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TraceSpec {
+    #[serde(default)]
+    spans: Vec<SpanSpec>,
+    #[serde(default)]
+    events: Vec<EventSpec>,
+}
+
+impl TraceSpec {
+    /// Loads a spec from YAML.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the YAML is malformed or does not correspond to the spec format.
+    pub fn from_yaml(yaml: &str) -> Result<Self, SpecError> {
+        serde_yaml::from_str(yaml).map_err(|err| SpecError(SpecErrorRepr::Yaml(err)))
+    }
+
+    /// Loads a spec from JSON.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the JSON is malformed or does not correspond to the spec format.
+    pub fn from_json(json: &str) -> Result<Self, SpecError> {
+        serde_json::from_str(json).map_err(|err| SpecError(SpecErrorRepr::Json(err)))
+    }
+
+    /// Checks whether the trace captured in `storage` matches this spec.
+    ///
+    /// # Errors
+    ///
+    /// Returns a mismatch report if the captured trace does not match the spec.
+    pub fn check(&self, storage: &Storage) -> Result<(), SpecMismatch> {
+        let mut mismatches = vec![];
+        check_spans(&self.spans, storage.root_spans(), "", &mut mismatches);
+        check_events(&self.events, storage.root_events(), "", &mut mismatches);
+        if mismatches.is_empty() {
+            Ok(())
+        } else {
+            Err(SpecMismatch { mismatches })
+        }
+    }
+
+    /// Asserts that the trace captured in `storage` matches this spec.
+    ///
+    /// # Panics
+    ///
+    /// Panics with a mismatch report if the captured trace does not match the spec.
+    #[track_caller]
+    pub fn assert_matches(&self, storage: &Storage) {
+        if let Err(err) = self.check(storage) {
+            panic!("{err}");
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SpanSpec {
+    name: String,
+    #[serde(default)]
+    target: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_level")]
+    level: Option<Level>,
+    #[serde(default)]
+    fields: BTreeMap<String, Option<ValueSpec>>,
+    #[serde(default)]
+    spans: Vec<SpanSpec>,
+    #[serde(default)]
+    events: Vec<EventSpec>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct EventSpec {
+    #[serde(default)]
+    message: Option<String>,
+    #[serde(default)]
+    target: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_level")]
+    level: Option<Level>,
+    #[serde(default)]
+    fields: BTreeMap<String, Option<ValueSpec>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum ValueSpec {
+    Bool(bool),
+    Int(i64),
+    UInt(u64),
+    Float(f64),
+    String(String),
+}
+
+impl fmt::Display for ValueSpec {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Bool(value) => fmt::Display::fmt(value, formatter),
+            Self::Int(value) => fmt::Display::fmt(value, formatter),
+            Self::UInt(value) => fmt::Display::fmt(value, formatter),
+            Self::Float(value) => fmt::Display::fmt(value, formatter),
+            Self::String(value) => fmt::Debug::fmt(value, formatter),
+        }
+    }
+}
+
+impl ValueSpec {
+    fn matches(&self, value: &TracedValue) -> bool {
+        match self {
+            Self::Bool(expected) => value.as_bool() == Some(*expected),
+            Self::Int(expected) => num_eq(*expected).eval(value),
+            Self::UInt(expected) => num_eq(*expected).eval(value),
+            Self::Float(expected) => num_eq(*expected).eval(value),
+            Self::String(pattern) => {
+                let s = match value {
+                    TracedValue::String(s) => s.as_str(),
+                    TracedValue::Object(obj) => obj.as_ref(),
+                    TracedValue::Error(err) => &err.message,
+                    _ => return false,
+                };
+                glob_matches(pattern, s)
+            }
+        }
+    }
+}
+
+fn deserialize_level<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Level>, D::Error> {
+    let level = Option::<String>::deserialize(deserializer)?;
+    level
+        .map(|level| level.parse().map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+fn child_path(path: &str, kind: &str, idx: usize) -> String {
+    if path.is_empty() {
+        format!("{kind}[{idx}]")
+    } else {
+        format!("{path}.{kind}[{idx}]")
+    }
+}
+
+fn check_metadata(
+    target: Option<&str>,
+    level: Option<Level>,
+    metadata: &Metadata<'_>,
+    path: &str,
+    mismatches: &mut Vec<Mismatch>,
+) {
+    if let Some(target) = target {
+        if !glob_matches(target, metadata.target()) {
+            let message = format!("target: expected {target:?}, got {:?}", metadata.target());
+            mismatches.push(Mismatch::new(path, message));
+        }
+    }
+    if let Some(level) = level {
+        if *metadata.level() != level {
+            let message = format!("level: expected {level}, got {}", metadata.level());
+            mismatches.push(Mismatch::new(path, message));
+        }
+    }
+}
+
+fn check_fields<'a>(
+    fields: &BTreeMap<String, Option<ValueSpec>>,
+    get_value: impl Fn(&str) -> Option<&'a TracedValue>,
+    path: &str,
+    mismatches: &mut Vec<Mismatch>,
+) {
+    for (name, expected) in fields {
+        let message = match (get_value(name), expected) {
+            (None, _) => format!("field `{name}` is missing"),
+            (Some(value), Some(expected)) if !expected.matches(value) => {
+                format!("field `{name}`: expected {expected}, got {value:?}")
+            }
+            _ => continue,
+        };
+        mismatches.push(Mismatch::new(path, message));
+    }
+}
+
+fn check_span(spec: &SpanSpec, span: CapturedSpan<'_>, path: &str, mismatches: &mut Vec<Mismatch>) {
+    let metadata = span.metadata();
+    if !glob_matches(&spec.name, span.name()) {
+        let message = format!("name: expected {:?}, got {:?}", spec.name, span.name());
+        mismatches.push(Mismatch::new(path, message));
+    }
+    check_metadata(
+        spec.target.as_deref(),
+        spec.level,
+        metadata,
+        path,
+        mismatches,
+    );
+    check_fields(&spec.fields, |name| span.value(name), path, mismatches);
+    check_spans(&spec.spans, span.children(), path, mismatches);
+    check_events(&spec.events, span.events(), path, mismatches);
+}
+
+fn check_event(
+    spec: &EventSpec,
+    event: CapturedEvent<'_>,
+    path: &str,
+    mismatches: &mut Vec<Mismatch>,
+) {
+    if let Some(expected) = &spec.message {
+        let message = event.message();
+        if !message.is_some_and(|message| glob_matches(expected, message)) {
+            let message = format!("message: expected {expected:?}, got {message:?}");
+            mismatches.push(Mismatch::new(path, message));
+        }
+    }
+    check_metadata(
+        spec.target.as_deref(),
+        spec.level,
+        event.metadata(),
+        path,
+        mismatches,
+    );
+    check_fields(&spec.fields, |name| event.value(name), path, mismatches);
+}
+
+/// Matches `specs` to a subsequence of `items` greedily, i.e., each spec is matched
+/// to the first remaining matching item.
+fn check_sequence<T: Copy>(
+    specs_len: usize,
+    items: &[T],
+    kind: &str,
+    path: &str,
+    mismatches: &mut Vec<Mismatch>,
+    mut check_item: impl FnMut(usize, T, &str, &mut Vec<Mismatch>),
+) {
+    let mut cursor = 0;
+    for spec_idx in 0..specs_len {
+        let item_path = child_path(path, kind, spec_idx);
+        let mut closest: Option<(usize, Vec<Mismatch>)> = None;
+        let mut matched = false;
+        for (offset, &item) in items[cursor..].iter().enumerate() {
+            let mut item_mismatches = vec![];
+            check_item(spec_idx, item, "", &mut item_mismatches);
+            if item_mismatches.is_empty() {
+                cursor += offset + 1;
+                matched = true;
+                break;
+            }
+            let is_closer = closest
+                .as_ref()
+                .map_or(true, |(_, closest)| item_mismatches.len() < closest.len());
+            if is_closer {
+                closest = Some((cursor + offset, item_mismatches));
+            }
+        }
+
+        if !matched {
+            let candidates = items.len() - cursor;
+            let noun = kind.trim_end_matches('s');
+            let message = format!("no matching {noun} found among {candidates} candidate(s)");
+            let mut mismatch = Mismatch::new(&item_path, message);
+            if let Some((idx, details)) = closest {
+                mismatch.message =
+                    format!("{}; closest candidate (#{idx}) differs:", mismatch.message);
+                mismatch.details = details;
+            }
+            mismatches.push(mismatch);
+        }
+    }
+}
+
+fn check_spans(
+    specs: &[SpanSpec],
+    spans: CapturedSpans<'_>,
+    path: &str,
+    mismatches: &mut Vec<Mismatch>,
+) {
+    let spans: Vec<_> = spans.collect();
+    check_sequence(
+        specs.len(),
+        &spans,
+        "spans",
+        path,
+        mismatches,
+        |idx, span, path, mismatches| check_span(&specs[idx], span, path, mismatches),
+    );
+}
+
+fn check_events(
+    specs: &[EventSpec],
+    events: CapturedEvents<'_>,
+    path: &str,
+    mismatches: &mut Vec<Mismatch>,
+) {
+    let events: Vec<_> = events.collect();
+    check_sequence(
+        specs.len(),
+        &events,
+        "events",
+        path,
+        mismatches,
+        |idx, event, path, mismatches| check_event(&specs[idx], event, path, mismatches),
+    );
+}
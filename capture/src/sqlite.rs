@@ -0,0 +1,696 @@
+//! Export of captured tracing data to SQLite and loading it back.
+
+use rusqlite::{params, types::Value, Connection, OptionalExtension};
+use tracing_core::{callsite, field::FieldSet, Callsite, Interest, Kind, Level, Metadata};
+
+use std::{
+    collections::HashMap,
+    error, fmt,
+    path::Path,
+    sync::{Arc, OnceLock},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    clock::SharedClock, CapturedEvent, CapturedOperation, CapturedSpan, CapturedSpanId, MockClock,
+    Storage,
+};
+use tracing_tunnel::{TracedValue, TracedValues};
+
+const SCHEMA: &str = "
+    CREATE TABLE call_sites (
+        id INTEGER PRIMARY KEY,
+        kind TEXT NOT NULL,
+        name TEXT NOT NULL,
+        target TEXT NOT NULL,
+        level TEXT NOT NULL,
+        module_path TEXT,
+        file TEXT,
+        line INTEGER,
+        fields TEXT NOT NULL
+    );
+    CREATE TABLE spans (
+        id INTEGER PRIMARY KEY,
+        parent_id INTEGER,
+        call_site_id INTEGER NOT NULL REFERENCES call_sites(id),
+        name TEXT NOT NULL,
+        created_at INTEGER NOT NULL,
+        closed_at INTEGER,
+        is_placeholder INTEGER NOT NULL
+    );
+    CREATE TABLE events (
+        id INTEGER PRIMARY KEY,
+        parent_id INTEGER,
+        call_site_id INTEGER NOT NULL REFERENCES call_sites(id),
+        level TEXT NOT NULL,
+        message TEXT,
+        timestamp INTEGER NOT NULL
+    );
+    CREATE TABLE field_values (
+        span_id INTEGER,
+        event_id INTEGER,
+        name TEXT NOT NULL,
+        value,
+        json TEXT NOT NULL
+    );
+    CREATE TABLE timeline (
+        seq INTEGER PRIMARY KEY,
+        operation TEXT NOT NULL,
+        span_id INTEGER,
+        event_id INTEGER,
+        follows_from_id INTEGER,
+        field TEXT,
+        value,
+        json TEXT,
+        timestamp INTEGER
+    );
+";
+
+/// Error exporting a [`Storage`] to SQLite or loading it back.
+#[cfg_attr(docsrs, doc(cfg(feature = "sqlite")))]
+#[derive(Debug)]
+pub struct SqliteError(SqliteErrorRepr);
+
+#[derive(Debug)]
+enum SqliteErrorRepr {
+    Sqlite(rusqlite::Error),
+    Json(serde_json::Error),
+    Format(String),
+}
+
+impl SqliteError {
+    fn format(message: impl Into<String>) -> Self {
+        Self(SqliteErrorRepr::Format(message.into()))
+    }
+}
+
+impl From<rusqlite::Error> for SqliteError {
+    fn from(err: rusqlite::Error) -> Self {
+        Self(SqliteErrorRepr::Sqlite(err))
+    }
+}
+
+impl From<serde_json::Error> for SqliteError {
+    fn from(err: serde_json::Error) -> Self {
+        Self(SqliteErrorRepr::Json(err))
+    }
+}
+
+impl fmt::Display for SqliteError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.0 {
+            SqliteErrorRepr::Sqlite(err) => write!(formatter, "SQLite error: {err}"),
+            SqliteErrorRepr::Json(err) => write!(formatter, "invalid serialized value: {err}"),
+            SqliteErrorRepr::Format(message) => write!(formatter, "invalid capture: {message}"),
+        }
+    }
+}
+
+impl error::Error for SqliteError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match &self.0 {
+            SqliteErrorRepr::Sqlite(err) => Some(err),
+            SqliteErrorRepr::Json(err) => Some(err),
+            SqliteErrorRepr::Format(_) => None,
+        }
+    }
+}
+
+fn nanos_since(origin: Instant, instant: Instant) -> i64 {
+    let nanos = instant.saturating_duration_since(origin).as_nanos();
+    i64::try_from(nanos).unwrap_or(i64::MAX)
+}
+
+fn index_to_sql(index: usize) -> i64 {
+    i64::try_from(index).expect("index overflow")
+}
+
+/// Converts a value to a plain SQL value suitable for querying.
+fn value_to_sql(value: &TracedValue) -> Value {
+    match value {
+        TracedValue::Bool(value) => Value::Integer((*value).into()),
+        TracedValue::Int(value) => {
+            i64::try_from(*value).map_or_else(|_| Value::Text(value.to_string()), Value::Integer)
+        }
+        TracedValue::UInt(value) => {
+            i64::try_from(*value).map_or_else(|_| Value::Text(value.to_string()), Value::Integer)
+        }
+        TracedValue::Float(value) => Value::Real(*value),
+        TracedValue::String(value) => Value::Text(value.clone()),
+        TracedValue::Object(value) => Value::Text(value.as_ref().to_owned()),
+        TracedValue::Error(err) => Value::Text(err.message.clone()),
+        TracedValue::Bytes(bytes) => Value::Blob(bytes.clone()),
+        _ => Value::Null,
+    }
+}
+
+struct Exporter<'a> {
+    storage: &'a Storage,
+    connection: &'a Connection,
+    call_site_ids: HashMap<callsite::Identifier, i64>,
+}
+
+impl Exporter<'_> {
+    fn timestamp(&self, instant: Instant) -> i64 {
+        nanos_since(self.storage.created_at(), instant)
+    }
+
+    fn call_site_id(&mut self, metadata: &'static Metadata<'static>) -> rusqlite::Result<i64> {
+        if let Some(&id) = self.call_site_ids.get(&metadata.callsite()) {
+            return Ok(id);
+        }
+        let id = index_to_sql(self.call_site_ids.len());
+        let kind = if metadata.is_span() { "span" } else { "event" };
+        let fields: Vec<_> = metadata.fields().iter().map(|field| field.name()).collect();
+        self.connection.execute(
+            "INSERT INTO call_sites VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            params![
+                id,
+                kind,
+                metadata.name(),
+                metadata.target(),
+                metadata.level().as_str(),
+                metadata.module_path(),
+                metadata.file(),
+                metadata.line(),
+                fields.join("\n"),
+            ],
+        )?;
+        self.call_site_ids.insert(metadata.callsite(), id);
+        Ok(id)
+    }
+
+    fn export_values<'v>(
+        &self,
+        owner: (Option<i64>, Option<i64>),
+        values: impl Iterator<Item = (&'v str, &'v TracedValue)>,
+    ) -> Result<(), SqliteError> {
+        let mut statement = self
+            .connection
+            .prepare_cached("INSERT INTO field_values VALUES (?1, ?2, ?3, ?4, ?5)")?;
+        for (name, value) in values {
+            let json = serde_json::to_string(value)?;
+            statement.execute(params![owner.0, owner.1, name, value_to_sql(value), json])?;
+        }
+        Ok(())
+    }
+
+    fn export_span(&mut self, span: CapturedSpan<'_>) -> Result<(), SqliteError> {
+        let id = index_to_sql(span.inner.id.index());
+        let parent_id = span
+            .parent()
+            .map(|parent| index_to_sql(parent.inner.id.index()));
+        let call_site_id = self.call_site_id(span.metadata())?;
+        self.connection.execute(
+            "INSERT INTO spans VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                id,
+                parent_id,
+                call_site_id,
+                span.name(),
+                self.timestamp(span.created_at()),
+                span.closed_at().map(|instant| self.timestamp(instant)),
+                span.is_placeholder(),
+            ],
+        )?;
+        self.export_values((Some(id), None), span.initial_values())
+    }
+
+    fn export_event(&mut self, event: CapturedEvent<'_>) -> Result<(), SqliteError> {
+        let id = index_to_sql(event.inner.id.index());
+        let parent_id = event
+            .parent()
+            .map(|parent| index_to_sql(parent.inner.id.index()));
+        let call_site_id = self.call_site_id(event.metadata())?;
+        self.connection.execute(
+            "INSERT INTO events VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                id,
+                parent_id,
+                call_site_id,
+                event.metadata().level().as_str(),
+                event.message(),
+                self.timestamp(event.timestamp()),
+            ],
+        )?;
+        self.export_values((None, Some(id)), event.values())
+    }
+
+    /// Exports all spans from the arena, including [placeholders](CapturedSpan::is_placeholder()).
+    fn export_spans(&mut self) -> Result<(), SqliteError> {
+        for span in self.storage.all_spans() {
+            self.export_span(span)?;
+        }
+        Ok(())
+    }
+
+    fn export_timeline(&mut self) -> Result<(), SqliteError> {
+        let span_id = |span: CapturedSpan<'_>| Some(index_to_sql(span.inner.id.index()));
+        let mut statement = self
+            .connection
+            .prepare("INSERT INTO timeline VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)")?;
+
+        // Entry and exit instants are not stored in spans, so we collect them beforehand.
+        let mut entry_instants = HashMap::new();
+        for span in self.storage.all_spans() {
+            for entry in span.entries() {
+                entry_instants.insert(entry.entered_seq, entry.entered_at);
+                if let (Some(seq), Some(instant)) = (entry.exited_seq, entry.exited_at) {
+                    entry_instants.insert(seq, instant);
+                }
+            }
+        }
+
+        for (seq, operation) in self.storage.timeline().enumerate() {
+            let mut recorded_value = None;
+            let (name, span, event, follows_from, field, instant) = match operation {
+                CapturedOperation::SpanCreated(span) => (
+                    "span_created",
+                    span_id(span),
+                    None,
+                    None,
+                    None,
+                    Some(span.created_at()),
+                ),
+                CapturedOperation::SpanEntered(span) => {
+                    let instant = entry_instants.get(&seq).copied();
+                    ("span_entered", span_id(span), None, None, None, instant)
+                }
+                CapturedOperation::SpanExited(span) => {
+                    let instant = entry_instants.get(&seq).copied();
+                    ("span_exited", span_id(span), None, None, None, instant)
+                }
+                CapturedOperation::SpanClosed(span) => (
+                    "span_closed",
+                    span_id(span),
+                    None,
+                    None,
+                    None,
+                    span.closed_at(),
+                ),
+                CapturedOperation::ValueRecorded { span, name, value } => {
+                    recorded_value = Some(value);
+                    (
+                        "value_recorded",
+                        span_id(span),
+                        None,
+                        None,
+                        Some(name),
+                        None,
+                    )
+                }
+                CapturedOperation::FollowsFrom { span, follows_from } => {
+                    let follows_from = span_id(follows_from);
+                    (
+                        "follows_from",
+                        span_id(span),
+                        None,
+                        follows_from,
+                        None,
+                        None,
+                    )
+                }
+                CapturedOperation::EventEmitted(event) => {
+                    self.export_event(event)?;
+                    let event_id = Some(index_to_sql(event.inner.id.index()));
+                    (
+                        "event_emitted",
+                        None,
+                        event_id,
+                        None,
+                        None,
+                        Some(event.timestamp()),
+                    )
+                }
+            };
+            let timestamp = instant.map(|instant| self.timestamp(instant));
+            let json = recorded_value.map(serde_json::to_string).transpose()?;
+            statement.execute(params![
+                index_to_sql(seq),
+                name,
+                span,
+                event,
+                follows_from,
+                field,
+                recorded_value.map_or(Value::Null, value_to_sql),
+                json,
+                timestamp
+            ])?;
+        }
+        Ok(())
+    }
+}
+
+/// Call site for metadata loaded from SQLite.
+#[derive(Debug, Default)]
+struct LoadedCallSite {
+    metadata: OnceLock<&'static Metadata<'static>>,
+}
+
+impl Callsite for LoadedCallSite {
+    fn set_interest(&self, _interest: Interest) {
+        // Does nothing
+    }
+
+    fn metadata(&self) -> &Metadata<'_> {
+        self.metadata.get().expect("metadata not initialized")
+    }
+}
+
+fn leak(s: String) -> &'static str {
+    Box::leak(s.into_boxed_str())
+}
+
+fn load_metadata(
+    connection: &Connection,
+) -> Result<HashMap<i64, &'static Metadata<'static>>, SqliteError> {
+    let mut statement = connection.prepare("SELECT * FROM call_sites")?;
+    let mut rows = statement.query([])?;
+    let mut metadata_by_id = HashMap::new();
+    while let Some(row) = rows.next()? {
+        let kind = match row.get::<_, String>("kind")?.as_str() {
+            "span" => Kind::SPAN,
+            "event" => Kind::EVENT,
+            other => {
+                return Err(SqliteError::format(format!(
+                    "unknown call site kind `{other}`"
+                )))
+            }
+        };
+        let level: String = row.get("level")?;
+        let level: Level = level
+            .parse()
+            .map_err(|_| SqliteError::format(format!("unknown level `{level}`")))?;
+        let fields: String = row.get("fields")?;
+        let fields: Box<[&'static str]> = fields
+            .split('\n')
+            .filter(|name| !name.is_empty())
+            .map(|name| leak(name.to_owned()))
+            .collect();
+
+        let call_site: &'static LoadedCallSite = Box::leak(Box::default());
+        let call_site_id = tracing_core::identify_callsite!(call_site);
+        let metadata = Metadata::new(
+            leak(row.get("name")?),
+            leak(row.get("target")?),
+            level,
+            row.get::<_, Option<String>>("file")?.map(leak),
+            row.get("line")?,
+            row.get::<_, Option<String>>("module_path")?.map(leak),
+            FieldSet::new(Box::leak(fields), call_site_id),
+            kind,
+        );
+        let metadata: &'static Metadata<'static> = Box::leak(Box::new(metadata));
+        call_site.metadata.set(metadata).ok();
+        metadata_by_id.insert(row.get("id")?, metadata);
+    }
+    Ok(metadata_by_id)
+}
+
+#[derive(Debug, Default)]
+struct LoadedValues {
+    spans: HashMap<i64, Vec<(String, TracedValue)>>,
+    events: HashMap<i64, Vec<(String, TracedValue)>>,
+}
+
+impl LoadedValues {
+    fn new(connection: &Connection) -> Result<Self, SqliteError> {
+        let mut this = Self::default();
+        let mut statement =
+            connection.prepare("SELECT span_id, event_id, name, json FROM field_values")?;
+        let mut rows = statement.query([])?;
+        while let Some(row) = rows.next()? {
+            let name: String = row.get(2)?;
+            let json: String = row.get(3)?;
+            let value: TracedValue = serde_json::from_str(&json)?;
+            let values = match (row.get::<_, Option<i64>>(0)?, row.get::<_, Option<i64>>(1)?) {
+                (Some(span_id), None) => this.spans.entry(span_id).or_default(),
+                (None, Some(event_id)) => this.events.entry(event_id).or_default(),
+                _ => return Err(SqliteError::format("value has no unique owner")),
+            };
+            values.push((name, value));
+        }
+        Ok(this)
+    }
+
+    fn take(
+        values: &mut HashMap<i64, Vec<(String, TracedValue)>>,
+        id: i64,
+        metadata: &'static Metadata<'static>,
+    ) -> TracedValues<&'static str> {
+        let values = values.remove(&id).unwrap_or_default();
+        values
+            .into_iter()
+            .map(|(name, value)| {
+                let name = metadata
+                    .fields()
+                    .field(&name)
+                    .map_or_else(|| leak(name), |field| field.name());
+                (name, value)
+            })
+            .collect()
+    }
+}
+
+struct Loader {
+    storage: Storage,
+    clock: MockClock,
+    metadata: HashMap<i64, &'static Metadata<'static>>,
+    values: LoadedValues,
+    span_ids: HashMap<i64, CapturedSpanId>,
+}
+
+impl Loader {
+    fn new(connection: &Connection) -> Result<Self, SqliteError> {
+        let clock = MockClock::default();
+        let mut storage = Storage::new();
+        storage.set_clock(&SharedClock(Arc::new(clock.clone())));
+        Ok(Self {
+            storage,
+            clock,
+            metadata: load_metadata(connection)?,
+            values: LoadedValues::new(connection)?,
+            span_ids: HashMap::new(),
+        })
+    }
+
+    fn advance_clock(&self, timestamp: Option<i64>) {
+        let Some(timestamp) = timestamp else {
+            return;
+        };
+        let timestamp = Duration::from_nanos(u64::try_from(timestamp).unwrap_or(0));
+        // Timestamps in the timeline are non-decreasing; we guard against invalid data regardless.
+        if let Some(diff) = timestamp.checked_sub(self.clock.elapsed()) {
+            self.clock.advance(diff);
+        }
+    }
+
+    fn metadata(&self, call_site_id: i64) -> Result<&'static Metadata<'static>, SqliteError> {
+        self.metadata
+            .get(&call_site_id)
+            .copied()
+            .ok_or_else(|| SqliteError::format(format!("unknown call site {call_site_id}")))
+    }
+
+    fn span_id(&self, id: Option<i64>) -> Result<CapturedSpanId, SqliteError> {
+        id.and_then(|id| self.span_ids.get(&id).copied())
+            .ok_or_else(|| SqliteError::format(format!("unknown span {id:?}")))
+    }
+
+    fn create_span(&mut self, connection: &Connection, id: i64) -> Result<(), SqliteError> {
+        let (parent_id, call_site_id, is_placeholder): (Option<i64>, i64, bool) = connection
+            .query_row(
+                "SELECT parent_id, call_site_id, is_placeholder FROM spans WHERE id = ?1",
+                [id],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .optional()?
+            .ok_or_else(|| SqliteError::format(format!("unknown span {id}")))?;
+        let metadata = self.metadata(call_site_id)?;
+        let parent_id = parent_id.map(|id| self.span_id(Some(id))).transpose()?;
+        let span_id = if is_placeholder {
+            self.storage.push_placeholder_span(metadata, parent_id)
+        } else {
+            let values = LoadedValues::take(&mut self.values.spans, id, metadata);
+            self.storage.push_span(metadata, values, parent_id)
+        };
+        self.span_ids.insert(id, span_id);
+        Ok(())
+    }
+
+    fn emit_event(&mut self, connection: &Connection, id: i64) -> Result<(), SqliteError> {
+        let (parent_id, call_site_id): (Option<i64>, i64) = connection
+            .query_row(
+                "SELECT parent_id, call_site_id FROM events WHERE id = ?1",
+                [id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?
+            .ok_or_else(|| SqliteError::format(format!("unknown event {id}")))?;
+        let metadata = self.metadata(call_site_id)?;
+        let values = LoadedValues::take(&mut self.values.events, id, metadata);
+        let parent_id = parent_id.map(|id| self.span_id(Some(id))).transpose()?;
+        self.storage.push_event(metadata, values.into(), parent_id);
+        Ok(())
+    }
+
+    fn record_value(
+        &mut self,
+        span_id: CapturedSpanId,
+        field: Option<String>,
+        json: Option<String>,
+    ) -> Result<(), SqliteError> {
+        let field = field.ok_or_else(|| SqliteError::format("recorded field is not specified"))?;
+        let json = json.ok_or_else(|| {
+            SqliteError::format(format!("recorded value for field `{field}` is missing"))
+        })?;
+        let value: TracedValue = serde_json::from_str(&json)?;
+        let name = self
+            .storage
+            .span(span_id)
+            .metadata()
+            .fields()
+            .field(&field)
+            .map_or_else(|| leak(field), |field| field.name());
+        let mut values = TracedValues::new();
+        values.insert(name, value);
+        self.storage.on_record(span_id, values);
+        Ok(())
+    }
+
+    fn load(mut self, connection: &Connection) -> Result<Storage, SqliteError> {
+        let mut statement = connection.prepare(
+            "SELECT operation, span_id, event_id, follows_from_id, field, json, timestamp \
+             FROM timeline ORDER BY seq",
+        )?;
+        let mut rows = statement.query([])?;
+        while let Some(row) = rows.next()? {
+            let operation: String = row.get(0)?;
+            let span_id: Option<i64> = row.get(1)?;
+            self.advance_clock(row.get(6)?);
+            match operation.as_str() {
+                "span_created" => {
+                    let id = span_id.ok_or_else(|| SqliteError::format("span is not specified"))?;
+                    self.create_span(connection, id)?;
+                }
+                "span_entered" => self.storage.on_span_enter(self.span_id(span_id)?),
+                "span_exited" => self.storage.on_span_exit(self.span_id(span_id)?),
+                "span_closed" => self.storage.on_span_closed(self.span_id(span_id)?),
+                "value_recorded" => {
+                    let span_id = self.span_id(span_id)?;
+                    self.record_value(span_id, row.get(4)?, row.get(5)?)?;
+                }
+                "follows_from" => {
+                    let span_id = self.span_id(span_id)?;
+                    let follows_from = self.span_id(row.get(3)?)?;
+                    self.storage.on_follows_from(span_id, follows_from);
+                }
+                "event_emitted" => {
+                    let id: Option<i64> = row.get(2)?;
+                    let id = id.ok_or_else(|| SqliteError::format("event is not specified"))?;
+                    self.emit_event(connection, id)?;
+                }
+                other => {
+                    return Err(SqliteError::format(format!("unknown operation `{other}`")));
+                }
+            }
+        }
+        drop(rows);
+        drop(statement);
+        Ok(self.storage)
+    }
+}
+
+impl Storage {
+    /// Exports captured spans and events to a SQLite database at the specified path,
+    /// e.g. to analyze large captures from soak tests with SQL afterwards.
+    ///
+    /// The database file is created if necessary; it must not contain tables with the names
+    /// used by the export. The following tables are created:
+    ///
+    /// - `call_sites`: metadata of spans and events. Field names are separated by `\n`
+    ///   in the `fields` column.
+    /// - `spans`: captured spans with their parent span IDs,
+    ///   [logical names](crate::CapturedSpan::name()), creation / closing timestamps
+    ///   and [placeholder](crate::CapturedSpan::is_placeholder()) flags.
+    /// - `events`: captured events with their parent span IDs, levels, messages and timestamps.
+    /// - `field_values`: values provided on creation of spans (`span_id` is set) and values
+    ///   of events (`event_id` is set). The `value` column contains a plain SQL value suitable
+    ///   for querying (e.g., integers are stored as `INTEGER`s if they fit), and the `json`
+    ///   column contains the exact serialized [`TracedValue`].
+    /// - `timeline`: captured [operations](crate::CapturedOperation) in the order of capture.
+    ///   Values recorded for spans after their creation are stored in the `value`
+    ///   and `json` columns of the corresponding operations, in the same format
+    ///   as in `field_values`.
+    ///
+    /// Span and event IDs are assigned in the order of capture. Timestamps are measured
+    /// in nanoseconds since the [creation of this storage](Self::created_at()).
+    /// [Extension values](crate::CapturedSpan::extension_values()) and thread information
+    /// are not exported.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database cannot be opened or written.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_subscriber::layer::SubscriberExt;
+    /// # use tracing_capture::{CaptureLayer, SharedStorage, Storage};
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let storage = SharedStorage::default();
+    /// let subscriber = tracing_subscriber::registry()
+    ///     .with(CaptureLayer::new(&storage));
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     let _entered = tracing::info_span!("compute", arg = 42).entered();
+    ///     tracing::info!(result = 5, "computed");
+    /// });
+    ///
+    /// let file_name = format!("tracing-capture-doc-{}.sqlite", std::process::id());
+    /// let path = std::env::temp_dir().join(file_name);
+    /// storage.lock().export_sqlite(&path)?;
+    ///
+    /// let loaded = Storage::load_sqlite(&path)?;
+    /// let span = loaded.all_spans().next().unwrap();
+    /// assert_eq!(span.metadata().name(), "compute");
+    /// assert_eq!(span["arg"], 42_i64);
+    /// assert_eq!(span.events().len(), 1);
+    /// # std::fs::remove_file(&path)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg_attr(docsrs, doc(cfg(feature = "sqlite")))]
+    pub fn export_sqlite(&self, path: impl AsRef<Path>) -> Result<(), SqliteError> {
+        let mut connection = Connection::open(path)?;
+        let transaction = connection.transaction()?;
+        transaction.execute_batch(SCHEMA)?;
+        let mut exporter = Exporter {
+            storage: self,
+            connection: &transaction,
+            call_site_ids: HashMap::new(),
+        };
+        exporter.export_spans()?;
+        exporter.export_timeline()?;
+        transaction.commit()?;
+        Ok(())
+    }
+
+    /// Loads a read-only storage from a SQLite database previously written by
+    /// [`Self::export_sqlite()`].
+    ///
+    /// The storage is reconstructed by replaying the exported timeline, so span
+    /// [stats](crate::CapturedSpan::stats()) and timestamps relative to the
+    /// [storage creation](Self::created_at()) are retained. All operations are attributed
+    /// to the current thread. Since spans and events must refer to `'static` [`Metadata`],
+    /// the loaded metadata is leaked; thus, this method should not be called repeatedly
+    /// in a long-running process.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the database cannot be read, or if its contents are invalid.
+    #[cfg_attr(docsrs, doc(cfg(feature = "sqlite")))]
+    pub fn load_sqlite(path: impl AsRef<Path>) -> Result<Self, SqliteError> {
+        let connection = Connection::open(path)?;
+        Loader::new(&connection)?.load(&connection)
+    }
+}
@@ -0,0 +1,313 @@
+//! Utilities for constructing synthetic [`CapturedSpan`]s and [`CapturedEvent`]s, e.g.
+//! to unit-test custom predicates.
+//!
+//! The core type of this module is [`StorageBuilder`], which allows building a [`Storage`]
+//! from synthetic spans and events without installing a [`CaptureLayer`] and emitting
+//! tracing data.
+//!
+//! # ⚠ Resource consumption
+//!
+//! Captured spans and events refer to `&'static` [`Metadata`]. To provide metadata with
+//! runtime-defined names, targets etc., builders leak the corresponding memory. Hence,
+//! this module should only be used in tests.
+//!
+//! [`CapturedSpan`]: crate::CapturedSpan
+//! [`CapturedEvent`]: crate::CapturedEvent
+//! [`CaptureLayer`]: crate::CaptureLayer
+//!
+//! # Examples
+//!
+//! ```
+//! # use predicates::ord::eq;
+//! # use tracing_core::Level;
+//! use tracing_capture::{predicates::*, test_util::StorageBuilder};
+//!
+//! let mut builder = StorageBuilder::new();
+//! let span_id = builder
+//!     .span("compute")
+//!     .target("app::compute")
+//!     .value("arg", 42_i64)
+//!     .build();
+//! builder
+//!     .event("computation finished")
+//!     .level(Level::WARN)
+//!     .parent(span_id)
+//!     .build();
+//! let storage = builder.build();
+//!
+//! let span = storage.scan_spans().single(&name(eq("compute")));
+//! assert_eq!(span["arg"], 42_i64);
+//! let predicate = level(Level::WARN) & parent(field("arg", 42_i64));
+//! let event = storage.scan_events().single(&predicate);
+//! assert_eq!(event.message(), Some("computation finished"));
+//! ```
+
+use tracing_core::{
+    callsite::{Callsite, Identifier},
+    field::FieldSet,
+    Interest, Kind, Level, Metadata,
+};
+
+use std::sync::OnceLock;
+
+use crate::{CapturedSpanId, SpanStats, Storage};
+use tracing_tunnel::{TracedValue, TracedValues};
+
+#[derive(Debug, Default)]
+struct SyntheticCallsite {
+    metadata: OnceLock<&'static Metadata<'static>>,
+}
+
+impl Callsite for SyntheticCallsite {
+    fn set_interest(&self, _interest: Interest) {
+        // Does nothing
+    }
+
+    fn metadata(&self) -> &Metadata<'_> {
+        self.metadata
+            .get()
+            .copied()
+            .expect("metadata not initialized")
+    }
+}
+
+fn leak_str(s: String) -> &'static str {
+    Box::leak(s.into_boxed_str())
+}
+
+/// Common data for span and event builders.
+#[derive(Debug)]
+struct ItemData {
+    name: String,
+    target: String,
+    level: Level,
+    module_path: Option<String>,
+    file: Option<String>,
+    line: Option<u32>,
+    values: Vec<(String, TracedValue)>,
+    parent_id: Option<CapturedSpanId>,
+}
+
+impl ItemData {
+    fn new(name: String) -> Self {
+        Self {
+            name,
+            target: "synthetic".to_owned(),
+            level: Level::INFO,
+            module_path: None,
+            file: None,
+            line: None,
+            values: vec![],
+            parent_id: None,
+        }
+    }
+
+    fn leak(self, kind: Kind) -> (&'static Metadata<'static>, TracedValues<&'static str>) {
+        let values: Vec<_> = self
+            .values
+            .into_iter()
+            .map(|(name, value)| (leak_str(name), value))
+            .collect();
+        let field_names: Box<[_]> = values.iter().map(|&(name, _)| name).collect();
+        let values = values.into_iter().collect();
+
+        let callsite: &'static SyntheticCallsite = Box::leak(Box::default());
+        let fields = FieldSet::new(Box::leak(field_names), Identifier(callsite));
+        let metadata = Metadata::new(
+            leak_str(self.name),
+            leak_str(self.target),
+            self.level,
+            self.file.map(leak_str),
+            self.line,
+            self.module_path.map(leak_str),
+            fields,
+            kind,
+        );
+        let metadata: &'static _ = Box::leak(Box::new(metadata));
+        callsite.metadata.set(metadata).unwrap();
+        (metadata, values)
+    }
+}
+
+macro_rules! impl_item_setters {
+    ($name:ident) => {
+        impl $name<'_> {
+            /// Sets the target. By default, the target is `synthetic`.
+            #[must_use]
+            pub fn target(mut self, target: impl Into<String>) -> Self {
+                self.data.target = target.into();
+                self
+            }
+
+            /// Sets the level. By default, the level is [`Level::INFO`].
+            #[must_use]
+            pub fn level(mut self, level: Level) -> Self {
+                self.data.level = level;
+                self
+            }
+
+            /// Sets the module path. By default, the module path is not set.
+            #[must_use]
+            pub fn module_path(mut self, module_path: impl Into<String>) -> Self {
+                self.data.module_path = Some(module_path.into());
+                self
+            }
+
+            /// Sets the file and the line. By default, neither is set.
+            #[must_use]
+            pub fn location(mut self, file: impl Into<String>, line: u32) -> Self {
+                self.data.file = Some(file.into());
+                self.data.line = Some(line);
+                self
+            }
+
+            /// Adds a value. The metadata field set will include the field with the specified
+            /// name.
+            #[must_use]
+            pub fn value(mut self, name: impl Into<String>, value: impl Into<TracedValue>) -> Self {
+                self.data.values.push((name.into(), value.into()));
+                self
+            }
+
+            /// Sets the parent span. By default, the built item is a root one.
+            #[must_use]
+            pub fn parent(mut self, parent_id: SyntheticSpanId) -> Self {
+                self.data.parent_id = Some(parent_id.0);
+                self
+            }
+        }
+    };
+}
+
+/// Builder of a synthetic [`Storage`].
+///
+/// See [module-level docs](self) for an example of usage.
+#[derive(Debug)]
+pub struct StorageBuilder {
+    storage: Storage,
+}
+
+impl Default for StorageBuilder {
+    fn default() -> Self {
+        Self {
+            storage: Storage::new(),
+        }
+    }
+}
+
+impl StorageBuilder {
+    /// Creates an empty builder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts building a span with the specified name.
+    pub fn span(&mut self, name: impl Into<String>) -> SpanBuilder<'_> {
+        SpanBuilder {
+            storage: &mut self.storage,
+            data: ItemData::new(name.into()),
+            stats: SpanStats::default(),
+            recorded_fields: vec![],
+        }
+    }
+
+    /// Starts building an event with the specified message. The message is recorded
+    /// as a string value of the `message` field.
+    pub fn event(&mut self, message: impl Into<String>) -> EventBuilder<'_> {
+        let mut data = ItemData::new("event".to_owned());
+        data.values
+            .push(("message".to_owned(), TracedValue::String(message.into())));
+        EventBuilder {
+            storage: &mut self.storage,
+            data,
+        }
+    }
+
+    /// Returns the built storage.
+    pub fn build(self) -> Storage {
+        self.storage
+    }
+}
+
+/// ID of a synthetic span returned by [`SpanBuilder::build()`]. Can be used to specify
+/// a parent for other spans / events.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SyntheticSpanId(CapturedSpanId);
+
+/// Builder of a synthetic [`CapturedSpan`](crate::CapturedSpan) returned from
+/// [`StorageBuilder::span()`].
+#[derive(Debug)]
+pub struct SpanBuilder<'a> {
+    storage: &'a mut Storage,
+    data: ItemData,
+    stats: SpanStats,
+    recorded_fields: Vec<String>,
+}
+
+impl_item_setters!(SpanBuilder);
+
+impl SpanBuilder<'_> {
+    /// Sets the span [stats](SpanStats). By default, the span is never entered
+    /// and is not closed.
+    #[must_use]
+    pub fn stats(mut self, entered: usize, exited: usize, is_closed: bool) -> Self {
+        self.stats = SpanStats {
+            entered,
+            exited,
+            is_closed,
+            ..SpanStats::default()
+        };
+        self
+    }
+
+    /// Adds a value [recorded](crate::ValueOrigin::Recorded) after span creation.
+    /// The metadata field set will include the field with the specified name.
+    #[must_use]
+    pub fn recorded_value(
+        mut self,
+        name: impl Into<String>,
+        value: impl Into<TracedValue>,
+    ) -> Self {
+        let name = name.into();
+        self.recorded_fields.push(name.clone());
+        self.value(name, value)
+    }
+
+    /// Adds the span to the storage and returns its ID.
+    pub fn build(self) -> SyntheticSpanId {
+        let parent_id = self.data.parent_id;
+        let (metadata, values) = self.data.leak(Kind::SPAN);
+        let id = self.storage.push_span(metadata, values, parent_id);
+        let span = &mut self.storage.spans[id];
+        span.stats = self.stats;
+        for name in metadata.fields() {
+            if self
+                .recorded_fields
+                .iter()
+                .any(|recorded| recorded == name.name())
+            {
+                span.recorded_fields.push(name.name());
+            }
+        }
+        SyntheticSpanId(id)
+    }
+}
+
+/// Builder of a synthetic [`CapturedEvent`](crate::CapturedEvent) returned from
+/// [`StorageBuilder::event()`].
+#[derive(Debug)]
+pub struct EventBuilder<'a> {
+    storage: &'a mut Storage,
+    data: ItemData,
+}
+
+impl_item_setters!(EventBuilder);
+
+impl EventBuilder<'_> {
+    /// Adds the event to the storage.
+    pub fn build(self) {
+        let parent_id = self.data.parent_id;
+        let (metadata, values) = self.data.leak(Kind::EVENT);
+        self.storage.push_event(metadata, values.into(), parent_id);
+    }
+}
@@ -0,0 +1,117 @@
+//! Chronological log of operations captured by `CaptureLayer`.
+
+use std::fmt;
+
+use tracing_tunnel::TracedValue;
+
+use crate::{CapturedEvent, CapturedEventId, CapturedSpan, CapturedSpanId, Storage};
+
+/// Raw operation stored in the [`Storage`] timeline.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum TimelineEntry {
+    SpanCreated(CapturedSpanId),
+    SpanEntered(CapturedSpanId),
+    SpanExited(CapturedSpanId),
+    SpanClosed(CapturedSpanId),
+    /// The last element is the index of the recorded value in the storage.
+    ValueRecorded(CapturedSpanId, &'static str, usize),
+    FollowsFrom(CapturedSpanId, CapturedSpanId),
+    EventEmitted(CapturedEventId),
+}
+
+impl TimelineEntry {
+    pub(crate) fn resolve(self, storage: &Storage) -> CapturedOperation<'_> {
+        match self {
+            Self::SpanCreated(id) => CapturedOperation::SpanCreated(storage.span(id)),
+            Self::SpanEntered(id) => CapturedOperation::SpanEntered(storage.span(id)),
+            Self::SpanExited(id) => CapturedOperation::SpanExited(storage.span(id)),
+            Self::SpanClosed(id) => CapturedOperation::SpanClosed(storage.span(id)),
+            Self::ValueRecorded(id, name, value_idx) => CapturedOperation::ValueRecorded {
+                span: storage.span(id),
+                name,
+                value: storage.recorded_value(value_idx),
+            },
+            Self::FollowsFrom(id, follows_from) => CapturedOperation::FollowsFrom {
+                span: storage.span(id),
+                follows_from: storage.span(follows_from),
+            },
+            Self::EventEmitted(id) => CapturedOperation::EventEmitted(storage.event(id)),
+        }
+    }
+}
+
+/// Operation captured by a [`CaptureLayer`](crate::CaptureLayer). Returned by
+/// [`Storage::timeline()`].
+///
+/// The [`Display`](fmt::Display) implementation outputs a short human-readable description
+/// of the operation, such as ``entered span `compute` ``.
+#[derive(Debug, Clone, Copy)]
+#[non_exhaustive]
+pub enum CapturedOperation<'a> {
+    /// Span was created.
+    SpanCreated(CapturedSpan<'a>),
+    /// Span was entered.
+    SpanEntered(CapturedSpan<'a>),
+    /// Span was exited.
+    SpanExited(CapturedSpan<'a>),
+    /// Span was closed.
+    SpanClosed(CapturedSpan<'a>),
+    /// Value was recorded for a span after its creation.
+    ValueRecorded {
+        /// Span for which the value was recorded.
+        span: CapturedSpan<'a>,
+        /// Name of the recorded field.
+        name: &'static str,
+        /// Recorded value. Since the value may be overwritten by subsequent recordings,
+        /// it may differ from the one accessible via [`CapturedSpan::value()`].
+        value: &'a TracedValue,
+    },
+    /// "Follows from" relation was recorded between spans.
+    FollowsFrom {
+        /// Span that follows from `follows_from`.
+        span: CapturedSpan<'a>,
+        /// Span that causally precedes `span`.
+        follows_from: CapturedSpan<'a>,
+    },
+    /// Event was emitted.
+    EventEmitted(CapturedEvent<'a>),
+}
+
+impl fmt::Display for CapturedOperation<'_> {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::SpanCreated(span) => {
+                write!(formatter, "created span `{}`", span.name())
+            }
+            Self::SpanEntered(span) => {
+                write!(formatter, "entered span `{}`", span.name())
+            }
+            Self::SpanExited(span) => {
+                write!(formatter, "exited span `{}`", span.name())
+            }
+            Self::SpanClosed(span) => {
+                write!(formatter, "closed span `{}`", span.name())
+            }
+            Self::ValueRecorded { span, name, .. } => {
+                let span_name = span.name();
+                write!(formatter, "recorded `{name}` in span `{span_name}`")
+            }
+            Self::FollowsFrom { span, follows_from } => {
+                let span_name = span.name();
+                let follows_from_name = follows_from.name();
+                write!(
+                    formatter,
+                    "span `{span_name}` follows from span `{follows_from_name}`"
+                )
+            }
+            Self::EventEmitted(event) => {
+                let level = event.metadata().level();
+                if let Some(message) = event.message() {
+                    write!(formatter, "{level} event: {message}")
+                } else {
+                    write!(formatter, "{level} event `{}`", event.metadata().name())
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,162 @@
+//! Owned trees of captured spans and events.
+
+use std::{iter, ops};
+
+use crate::{CapturedEvent, CapturedSpan, SpanStats};
+use tracing_tunnel::{CallSiteData, TracedValue, TracedValues};
+
+/// Owned tree of a captured span together with its descendant spans and events. Returned by
+/// [`CapturedSpan::to_owned_tree()`].
+///
+/// Unlike [`CapturedSpan`], a tree does not borrow the [`Storage`](crate::Storage),
+/// so it can be moved across threads, stored in fixtures or returned from helper functions.
+/// Span metadata is copied into a [`CallSiteData`].
+///
+/// # Examples
+///
+/// ```
+/// # use tracing_subscriber::{layer::SubscriberExt, Registry};
+/// # use tracing_capture::{CaptureLayer, SharedStorage, TraceTree};
+/// fn capture_request() -> TraceTree {
+///     let storage = SharedStorage::default();
+///     let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+///     tracing::subscriber::with_default(subscriber, || {
+///         tracing::info_span!("request", id = 42).in_scope(|| {
+///             tracing::debug_span!("query").in_scope(|| tracing::debug!("executed"));
+///             tracing::info!(status = 200, "responded");
+///         });
+///     });
+///     let storage = storage.lock();
+///     let span = storage.root_spans().next().unwrap();
+///     span.to_owned_tree()
+/// }
+///
+/// let tree = std::thread::spawn(capture_request).join().unwrap();
+/// assert_eq!(tree.metadata.name, "request");
+/// assert_eq!(tree["id"], 42_i64);
+/// assert_eq!(tree.children[0].metadata.name, "query");
+/// assert_eq!(tree.events[0].message(), Some("responded"));
+/// assert_eq!(tree.descendant_events().count(), 1);
+/// ```
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct TraceTree {
+    /// Copy of the span metadata.
+    pub metadata: CallSiteData,
+    /// Span values.
+    pub values: TracedValues<String>,
+    /// Span statistics at the time the tree was created.
+    pub stats: SpanStats,
+    /// Child spans in the order of creation.
+    pub children: Vec<TraceTree>,
+    /// Events directly attached to the span in the order of capture.
+    pub events: Vec<TraceTreeEvent>,
+}
+
+impl TraceTree {
+    fn new(span: &CapturedSpan<'_>) -> Self {
+        Self {
+            metadata: CallSiteData::from(span.metadata()),
+            values: owned_values(span.values()),
+            stats: span.stats(),
+            children: span.children().map(|child| Self::new(&child)).collect(),
+            events: span.events().map(TraceTreeEvent::new).collect(),
+        }
+    }
+
+    /// Returns the value of the specified span field.
+    pub fn value(&self, name: &str) -> Option<&TracedValue> {
+        self.values.get(name)
+    }
+
+    /// Iterates over all descendant spans of this span (excluding the span itself)
+    /// in the depth-first order.
+    pub fn descendants(&self) -> impl Iterator<Item = &Self> + '_ {
+        let children: Box<dyn Iterator<Item = &Self>> = Box::new(self.children.iter());
+        let mut stack = vec![children];
+        iter::from_fn(move || loop {
+            let child = stack.last_mut()?.next();
+            if let Some(child) = child {
+                stack.push(Box::new(child.children.iter()));
+                return Some(child);
+            }
+            stack.pop();
+        })
+    }
+
+    /// Iterates over the events of the [descendants](Self::descendants()) of this span.
+    /// Similar to [`CapturedSpan::descendant_events()`], the returned events do not include
+    /// the [events directly attached](Self::events) to this span.
+    pub fn descendant_events(&self) -> impl Iterator<Item = &TraceTreeEvent> + '_ {
+        self.descendants().flat_map(|span| &span.events)
+    }
+}
+
+impl ops::Index<&str> for TraceTree {
+    type Output = TracedValue;
+
+    fn index(&self, index: &str) -> &Self::Output {
+        self.value(index)
+            .unwrap_or_else(|| panic!("field `{index}` is not contained in span"))
+    }
+}
+
+/// Owned copy of a captured event, part of a [`TraceTree`].
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct TraceTreeEvent {
+    /// Copy of the event metadata.
+    pub metadata: CallSiteData,
+    /// Event values, including the message.
+    pub values: TracedValues<String>,
+}
+
+impl TraceTreeEvent {
+    fn new(event: CapturedEvent<'_>) -> Self {
+        Self {
+            metadata: CallSiteData::from(event.metadata()),
+            values: owned_values(event.values()),
+        }
+    }
+
+    /// Returns the value of the specified event field.
+    pub fn value(&self, name: &str) -> Option<&TracedValue> {
+        self.values.get(name)
+    }
+
+    /// Returns the message recorded in this event, i.e., the value of the `message` field
+    /// if it has a string presentation.
+    pub fn message(&self) -> Option<&str> {
+        self.value("message").and_then(|message| match message {
+            TracedValue::Object(obj) => Some(obj.as_ref()),
+            TracedValue::String(s) => Some(s),
+            TracedValue::Error(err) => Some(&err.message),
+            _ => None,
+        })
+    }
+}
+
+impl ops::Index<&str> for TraceTreeEvent {
+    type Output = TracedValue;
+
+    fn index(&self, index: &str) -> &Self::Output {
+        self.value(index)
+            .unwrap_or_else(|| panic!("field `{index}` is not contained in event"))
+    }
+}
+
+fn owned_values<'a>(
+    values: impl Iterator<Item = (&'a str, &'a TracedValue)>,
+) -> TracedValues<String> {
+    values
+        .map(|(name, value)| (name.to_owned(), value.clone()))
+        .collect()
+}
+
+impl CapturedSpan<'_> {
+    /// Copies this span together with its descendant spans and events into an owned
+    /// [`TraceTree`] that does not borrow the storage.
+    pub fn to_owned_tree(&self) -> TraceTree {
+        TraceTree::new(self)
+    }
+}
@@ -0,0 +1,96 @@
+//! Integration with `tracing-tunnel`.
+
+use tracing_core::Dispatch;
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+use tracing_tunnel::{ReceiveError, TracingEvent, TracingEventReceiver};
+
+use crate::{CaptureLayer, SharedStorage};
+
+/// Receiver of [`TracingEvent`]s capturing them into a [`SharedStorage`].
+///
+/// This allows asserting on traces produced by a [`TracingEventSender`] (e.g., in a WASM module
+/// or in another process) with the predicate API of this crate. Events are relayed via
+/// a [`TracingEventReceiver`] to a private [`Dispatch`] consisting of a [`Registry`]
+/// and a [`CaptureLayer`]; they never reach the host dispatcher.
+///
+/// [`TracingEventSender`]: https://docs.rs/tracing-tunnel/0.1/tracing_tunnel/struct.TracingEventSender.html
+///
+/// # Examples
+///
+/// ```
+/// # use predicates::ord::eq;
+/// # use tracing_capture::{predicates::{field, name, ScanExt}, CaptureReceiver, SharedStorage};
+/// # use tracing_tunnel::TracingEventSender;
+/// // Emulate a traced workflow executed elsewhere.
+/// let (events_sx, events_rx) = std::sync::mpsc::channel();
+/// let sender = TracingEventSender::new(move |event| {
+///     events_sx.send(event).ok();
+/// });
+/// tracing::subscriber::with_default(sender, || {
+///     tracing::info_span!("workflow", id = 42_u64).in_scope(|| tracing::info!("started"));
+/// });
+///
+/// let storage = SharedStorage::default();
+/// let mut receiver = CaptureReceiver::new(&storage);
+/// for event in events_rx.try_iter() {
+///     receiver.receive(event);
+/// }
+///
+/// let storage = storage.lock();
+/// let span = storage.scan_spans().single(&(name(eq("workflow")) & field("id", 42_u64)));
+/// assert_eq!(span.events().len(), 1);
+/// ```
+#[cfg_attr(docsrs, doc(cfg(feature = "tunnel")))]
+#[derive(Debug)]
+pub struct CaptureReceiver {
+    inner: TracingEventReceiver,
+}
+
+impl CaptureReceiver {
+    /// Creates a receiver capturing events into the specified storage.
+    pub fn new(storage: &SharedStorage) -> Self {
+        Self::with_layer(CaptureLayer::new(storage))
+    }
+
+    /// Creates a receiver capturing events with the specified layer, e.g. one configured
+    /// with a [filter](CaptureLayer::with_filter()) or [redaction](CaptureLayer::with_redaction()).
+    pub fn with_layer(layer: CaptureLayer<Registry>) -> Self {
+        let dispatch = Dispatch::new(Registry::default().with(layer));
+        Self {
+            inner: TracingEventReceiver::builder()
+                .with_dispatch(dispatch)
+                .build(),
+        }
+    }
+
+    /// Returns a reference to the underlying receiver, e.g. to inspect its
+    /// [counters](TracingEventReceiver::counters()).
+    pub fn receiver(&self) -> &TracingEventReceiver {
+        &self.inner
+    }
+
+    /// Returns an exclusive reference to the underlying receiver, e.g. to set
+    /// [ambient fields](TracingEventReceiver::set_ambient_field()).
+    pub fn receiver_mut(&mut self) -> &mut TracingEventReceiver {
+        &mut self.inner
+    }
+
+    /// Tries to capture the specified event.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error in the same cases as [`TracingEventReceiver::try_receive()`].
+    pub fn try_receive(&mut self, event: TracingEvent) -> Result<(), ReceiveError> {
+        self.inner.try_receive(event)
+    }
+
+    /// Captures the specified event. Errors are handled according to the receiver's
+    /// [error policy](tracing_tunnel::TracingEventReceiverBuilder::with_error_policy()).
+    ///
+    /// # Panics
+    ///
+    /// Panics in the same cases as [`TracingEventReceiver::receive()`].
+    pub fn receive(&mut self, event: TracingEvent) {
+        self.inner.receive(event);
+    }
+}
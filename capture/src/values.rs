@@ -0,0 +1,281 @@
+//! Storage for values of captured spans and events.
+
+use tracing_core::{
+    field::{Field, Visit},
+    Event,
+};
+
+use std::{borrow::Borrow, collections::HashSet, error, fmt, hash, ops, sync::Arc};
+
+use tracing_tunnel::{TracedValue, TracedValues};
+
+/// Maximum number of event values stored inline if the `no-alloc-hot-path` feature is enabled.
+#[cfg(feature = "no-alloc-hot-path")]
+const INLINE_EVENT_VALUES: usize = 8;
+
+#[cfg(feature = "no-alloc-hot-path")]
+type Inner = smallvec::SmallVec<[(&'static str, StoredValue); INLINE_EVENT_VALUES]>;
+#[cfg(not(feature = "no-alloc-hot-path"))]
+type Inner = Vec<(&'static str, StoredValue)>;
+
+/// Value of a captured span or event. [String values](TracedValue::String) are interned
+/// by the storage (see [`StringPool`]), so that equal strings are shared among values.
+#[derive(Debug, Clone)]
+pub(crate) enum StoredValue {
+    Owned(TracedValue),
+    /// Interned string value.
+    Interned(Arc<TracedValue>),
+}
+
+impl ops::Deref for StoredValue {
+    type Target = TracedValue;
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            Self::Owned(value) => value,
+            Self::Interned(value) => value,
+        }
+    }
+}
+
+impl StoredValue {
+    fn to_mut(&mut self) -> &mut TracedValue {
+        match self {
+            Self::Owned(value) => value,
+            Self::Interned(value) => Arc::make_mut(value),
+        }
+    }
+}
+
+/// String value in a [`StringPool`]. Hashed and compared by the string contents,
+/// so that the pool can be queried by `&str`.
+#[derive(Debug, Clone)]
+struct InternedString(Arc<TracedValue>);
+
+impl InternedString {
+    fn as_str(&self) -> &str {
+        match &*self.0 {
+            TracedValue::String(s) => s,
+            _ => unreachable!("only strings are interned"),
+        }
+    }
+}
+
+impl Borrow<str> for InternedString {
+    fn borrow(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl PartialEq for InternedString {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for InternedString {}
+
+impl hash::Hash for InternedString {
+    fn hash<H: hash::Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state);
+    }
+}
+
+/// Pool of interned [string values](TracedValue::String).
+#[derive(Debug, Clone, Default)]
+pub(crate) struct StringPool {
+    strings: HashSet<InternedString>,
+}
+
+impl StringPool {
+    /// Replaces a string value with an equal string from the pool, adding the string
+    /// to the pool if necessary.
+    fn intern(&mut self, value: &mut StoredValue) {
+        let StoredValue::Owned(TracedValue::String(s)) = value else {
+            return;
+        };
+        let interned = if let Some(interned) = self.strings.get(s.as_str()) {
+            Arc::clone(&interned.0)
+        } else {
+            let interned = Arc::new(TracedValue::String(std::mem::take(s)));
+            self.strings.insert(InternedString(Arc::clone(&interned)));
+            interned
+        };
+        *value = StoredValue::Interned(interned);
+    }
+
+    pub fn intern_values(&mut self, values: &mut CapturedValues) {
+        for (_, value) in &mut values.inner {
+            self.intern(value);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &str> + '_ {
+        self.strings.iter().map(InternedString::as_str)
+    }
+
+    pub fn clear(&mut self) {
+        self.strings.clear();
+    }
+}
+
+/// Values of a captured span or event. Unlike [`TracedValues`], values may be stored inline
+/// (i.e., without heap allocation) depending on crate features, and string values
+/// may be interned.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct CapturedValues {
+    inner: Inner,
+}
+
+impl CapturedValues {
+    pub fn from_event(event: &Event<'_>) -> Self {
+        let mut values = Self::default();
+        event.record(&mut values);
+        values
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&TracedValue> {
+        self.inner
+            .iter()
+            .find_map(|(existing_name, value)| (*existing_name == name).then_some(&**value))
+    }
+
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = (&str, &TracedValue)> + '_ {
+        self.inner.iter().map(|(name, value)| (*name, &**value))
+    }
+
+    /// Iterates over values that are not interned.
+    pub fn owned_values(&self) -> impl Iterator<Item = &TracedValue> + '_ {
+        self.inner.iter().filter_map(|(_, value)| match value {
+            StoredValue::Owned(value) => Some(value),
+            StoredValue::Interned(_) => None,
+        })
+    }
+
+    pub fn get_stored(&self, name: &str) -> Option<&StoredValue> {
+        self.inner
+            .iter()
+            .find_map(|(existing_name, value)| (*existing_name == name).then_some(value))
+    }
+
+    pub fn iter_stored(&self) -> impl Iterator<Item = (&'static str, &StoredValue)> + '_ {
+        self.inner.iter().map(|(name, value)| (*name, value))
+    }
+
+    pub fn to_traced_values(&self) -> TracedValues<&'static str> {
+        self.inner
+            .iter()
+            .map(|(name, value)| (*name, TracedValue::clone(value)))
+            .collect()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&'static str, &mut TracedValue)> + '_ {
+        self.inner
+            .iter_mut()
+            .map(|(name, value)| (*name, value.to_mut()))
+    }
+
+    pub fn insert(&mut self, name: &'static str, value: TracedValue) {
+        self.insert_stored(name, StoredValue::Owned(value));
+    }
+
+    pub fn insert_stored(&mut self, name: &'static str, value: StoredValue) {
+        let existing = self
+            .inner
+            .iter_mut()
+            .find(|(existing_name, _)| *existing_name == name);
+        if let Some((_, existing_value)) = existing {
+            *existing_value = value;
+        } else {
+            self.inner.push((name, value));
+        }
+    }
+
+    /// Inserts all `values`, replacing existing values with the same names.
+    pub fn extend(&mut self, values: Self) {
+        for (name, value) in values.inner {
+            self.insert_stored(name, value);
+        }
+    }
+}
+
+impl From<TracedValues<&'static str>> for CapturedValues {
+    fn from(values: TracedValues<&'static str>) -> Self {
+        values.into_iter().collect()
+    }
+}
+
+impl FromIterator<(&'static str, TracedValue)> for CapturedValues {
+    fn from_iter<I: IntoIterator<Item = (&'static str, TracedValue)>>(iter: I) -> Self {
+        let mut values = Self::default();
+        for (name, value) in iter {
+            values.insert(name, value);
+        }
+        values
+    }
+}
+
+impl Visit for CapturedValues {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.insert(field.name(), value.into());
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.insert(field.name(), value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.insert(field.name(), value.into());
+    }
+
+    fn record_i128(&mut self, field: &Field, value: i128) {
+        self.insert(field.name(), value.into());
+    }
+
+    fn record_u128(&mut self, field: &Field, value: u128) {
+        self.insert(field.name(), value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.insert(field.name(), value.into());
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.insert(field.name(), value.into());
+    }
+
+    fn record_error(&mut self, field: &Field, value: &(dyn error::Error + 'static)) {
+        self.insert(field.name(), TracedValue::error(value));
+    }
+
+    fn record_bytes(&mut self, field: &Field, value: &[u8]) {
+        self.insert(field.name(), value.into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.insert(field.name(), TracedValue::debug(value));
+    }
+}
+
+/// Converts a value to a string as it would be presented when interpolated into a message:
+/// strings and `Debug` objects are output as-is, scalars are formatted via `Display`.
+pub(crate) fn display_value(value: &TracedValue) -> String {
+    match value {
+        TracedValue::String(s) => s.clone(),
+        TracedValue::Object(obj) => obj.as_ref().to_owned(),
+        TracedValue::Bool(value) => value.to_string(),
+        TracedValue::Int(value) => value.to_string(),
+        TracedValue::UInt(value) => value.to_string(),
+        TracedValue::Float(value) => value.to_string(),
+        TracedValue::Error(err) => err.message.clone(),
+        _ => format!("{value:?}"),
+    }
+}
@@ -0,0 +1,93 @@
+//! Tests that capturing events does not allocate if the `no-alloc-hot-path` feature is enabled.
+
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+};
+
+use tracing_capture::{CaptureLayer, SharedStorage};
+
+thread_local! {
+    static ALLOCATIONS: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+/// Allocator counting allocations on the current thread if counting is enabled.
+struct CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS
+            .try_with(|count| {
+                if let Some(value) = count.get() {
+                    count.set(Some(value + 1));
+                }
+            })
+            .ok();
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn count_allocations(action: impl FnOnce()) -> usize {
+    ALLOCATIONS.with(|count| count.set(Some(0)));
+    action();
+    ALLOCATIONS.with(|count| count.take().unwrap())
+}
+
+fn emit_event(i: usize) {
+    tracing::info!(i, flag = true, ratio = 0.5);
+}
+
+#[test]
+fn capturing_events_does_not_allocate() {
+    const EVENT_COUNT: usize = 50;
+
+    // Events are only allocation-free as long as the storage capacity is not exceeded.
+    let storage =
+        SharedStorage::with_capacity(1, 2 * EVENT_COUNT + 2).with_span_capacity(0, EVENT_COUNT);
+    let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    tracing::subscriber::with_default(subscriber, || {
+        // Warm up: registers the call site and the current thread in the storage.
+        emit_event(0);
+        let root_allocations = count_allocations(|| {
+            for i in 0..EVENT_COUNT {
+                emit_event(i);
+            }
+        });
+        assert_eq!(root_allocations, 0);
+
+        let span = tracing::info_span!("test").entered();
+        let span_allocations = count_allocations(|| {
+            for i in 0..EVENT_COUNT {
+                emit_event(i);
+            }
+        });
+        assert_eq!(span_allocations, 0);
+        drop(span);
+
+        // Messages are recorded as `Debug` objects, which allocate.
+        let message_allocations = count_allocations(|| tracing::info!("event"));
+        assert!(message_allocations > 0);
+    });
+
+    let storage = storage.lock();
+    assert_eq!(storage.all_events().len(), 2 * EVENT_COUNT + 2);
+    let event = storage
+        .all_spans()
+        .next()
+        .unwrap()
+        .events()
+        .next_back()
+        .unwrap();
+    assert_eq!(event["i"], (EVENT_COUNT - 1) as u64);
+    assert_eq!(event["flag"], true);
+    assert_eq!(event["ratio"], 0.5);
+}
@@ -0,0 +1,81 @@
+//! Tests for per-thread capturing with a globally installed subscriber. Placed in a separate
+//! test binary since the global subscriber can only be set once.
+
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+use std::{sync::Once, thread};
+
+use tracing_capture::{
+    predicates::{field, ScanExt},
+    CaptureLayer, SharedStorage,
+};
+
+fn install_subscriber() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        let subscriber = Registry::default().with(CaptureLayer::per_thread());
+        tracing::subscriber::set_global_default(subscriber).unwrap();
+    });
+}
+
+fn run_test(idx: u64) {
+    install_subscriber();
+    let storage = SharedStorage::default();
+    let _binding = storage.bind_to_current_thread();
+
+    let span = tracing::info_span!("test", idx);
+    span.in_scope(|| {
+        for i in 0..10 {
+            tracing::info!(i, "iteration");
+        }
+    });
+    // Events in the span emitted on another thread should be captured into the same storage.
+    thread::spawn(move || {
+        span.in_scope(|| tracing::info!("moved to another thread"));
+    })
+    .join()
+    .unwrap();
+
+    let storage = storage.lock();
+    let span = storage.scan_spans().single(&field("idx", idx));
+    assert_eq!(span.events().len(), 11);
+    assert!(span.stats().is_closed);
+    assert_eq!(storage.all_spans().len(), 1);
+    assert_eq!(storage.all_events().len(), 11);
+}
+
+#[test]
+fn first_parallel_test() {
+    run_test(0);
+}
+
+#[test]
+fn second_parallel_test() {
+    run_test(1);
+}
+
+#[test]
+fn many_threads() {
+    thread::scope(|scope| {
+        for idx in 2..10 {
+            scope.spawn(move || run_test(idx));
+        }
+    });
+}
+
+#[test]
+fn unbound_thread_is_not_captured() {
+    install_subscriber();
+    let storage = SharedStorage::default();
+    {
+        let _binding = storage.bind_to_current_thread();
+        tracing::info!("captured");
+    }
+    tracing::info!("not captured");
+    thread::spawn(|| tracing::info!("not captured either"))
+        .join()
+        .unwrap();
+
+    let storage = storage.lock();
+    assert_eq!(storage.all_events().len(), 1);
+}
@@ -0,0 +1,115 @@
+//! Tests for recording and checking golden files.
+
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+use std::{fs, panic, path::PathBuf};
+
+use tracing_capture::{CaptureLayer, GoldenTolerance, SharedStorage};
+
+fn capture_trace(request_id: u64, rows: u64, latency: f64) -> SharedStorage {
+    let storage = SharedStorage::default();
+    let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::info_span!(target: "app::http", "request", request_id, method = "GET");
+        span.in_scope(|| {
+            tracing::debug_span!(target: "app::db", "query", table = "users").in_scope(|| {
+                tracing::debug!(target: "app::db", rows, "query completed");
+            });
+            tracing::warn!(target: "app::http", latency, "slow request");
+        });
+    });
+    storage
+}
+
+fn temp_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir()
+        .join("tracing-capture-golden")
+        .join(format!("{name}.json"));
+    fs::remove_file(&path).ok();
+    path
+}
+
+fn assert_mismatch(storage: &SharedStorage, path: &PathBuf, tolerance: &GoldenTolerance) -> String {
+    let err =
+        panic::catch_unwind(|| storage.lock().assert_matches_golden(path, tolerance)).unwrap_err();
+    err.downcast_ref::<String>().unwrap().clone()
+}
+
+#[test]
+fn recording_and_checking_golden_file() {
+    let path = temp_path("roundtrip");
+    capture_trace(1, 3, 1.5)
+        .lock()
+        .record_golden(&path)
+        .unwrap();
+
+    let contents = fs::read_to_string(&path).unwrap();
+    assert!(contents.contains("\"query completed\""), "{contents}");
+    assert!(contents.ends_with('\n'));
+    capture_trace(1, 3, 1.5)
+        .lock()
+        .assert_matches_golden(&path, &GoldenTolerance::default());
+}
+
+#[test]
+fn golden_file_mismatch_lists_differences() {
+    let path = temp_path("mismatch");
+    capture_trace(1, 3, 1.5)
+        .lock()
+        .record_golden(&path)
+        .unwrap();
+
+    let message = assert_mismatch(
+        &capture_trace(2, 4, 1.5),
+        &path,
+        &GoldenTolerance::default(),
+    );
+    assert!(message.contains("does not match golden file"), "{message}");
+    assert!(
+        message.contains("spans[0].fields.request_id.u_int: expected 1, got 2"),
+        "{message}"
+    );
+    assert!(
+        message.contains("spans[0].spans[0].events[0].fields.rows.u_int: expected 3, got 4"),
+        "{message}"
+    );
+    assert!(!message.contains("latency"), "{message}");
+}
+
+#[test]
+fn golden_file_tolerance() {
+    let path = temp_path("tolerance");
+    capture_trace(1, 3, 1.5)
+        .lock()
+        .record_golden(&path)
+        .unwrap();
+
+    let tolerance = GoldenTolerance::default()
+        .ignore_field("request_id")
+        .with_numeric_tolerance(0.1);
+    capture_trace(2, 3, 1.6)
+        .lock()
+        .assert_matches_golden(&path, &tolerance);
+
+    let message = assert_mismatch(&capture_trace(2, 3, 2.0), &path, &tolerance);
+    assert!(
+        message.contains("spans[0].events[0].fields.latency.float: expected 1.5, got 2.0"),
+        "{message}"
+    );
+    assert!(!message.contains("request_id"), "{message}");
+}
+
+#[test]
+fn missing_golden_file() {
+    let path = temp_path("missing");
+    let message = assert_mismatch(
+        &capture_trace(1, 3, 1.5),
+        &path,
+        &GoldenTolerance::default(),
+    );
+    assert!(message.contains("failed reading golden file"), "{message}");
+    assert!(
+        message.contains("TRACING_CAPTURE_UPDATE_GOLDEN=1"),
+        "{message}"
+    );
+}
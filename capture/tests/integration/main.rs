@@ -1,8 +1,11 @@
 //! Integration tests for tracing capture.
 
 use assert_matches::assert_matches;
-use predicates::ord::eq;
-use tracing_core::{Level, LevelFilter};
+use predicates::{
+    ord::{eq, ge},
+    Predicate,
+};
+use tracing_core::{Dispatch, Level, LevelFilter};
 use tracing_subscriber::{layer::SubscriberExt, Registry};
 
 use std::{borrow::Cow, panic};
@@ -10,8 +13,12 @@ use std::{borrow::Cow, panic};
 mod fib;
 
 use tracing_capture::{
-    predicates::{ancestor, field, level, message, name, parent, ScanExt},
-    CaptureLayer, SharedStorage, Storage,
+    predicates::{
+        ancestor, depth, field, if_event, if_span, level, message, name, parent, ScanExt,
+    },
+    test_util::StorageBuilder,
+    CaptureLayer, CapturedItem, CapturedOperation, GroupBy, OpenSpansCheck, OwnedCapturedEvent,
+    OwnedCapturedSpan, SharedStorage, Storage, ValueOrigin,
 };
 use tracing_tunnel::{
     CallSiteData, CallSiteKind, LocalSpans, TracedValue, TracedValues, TracingEvent,
@@ -41,8 +48,10 @@ fn replayed_spans_are_closed_if_entered_multiple_times() {
         TracingEvent::NewSpan {
             id: 0,
             parent_id: None,
+            is_root: false,
             metadata_id: 0,
             values: TracedValues::new(),
+            generation: None,
         },
         TracingEvent::SpanEntered { id: 0 },
         TracingEvent::SpanExited { id: 0 },
@@ -81,8 +90,10 @@ fn recorded_span_values_are_restored() {
         TracingEvent::NewSpan {
             id: 0,
             parent_id: None,
+            is_root: false,
             metadata_id: 0,
             values: TracedValues::from_iter([("i".to_owned(), TracedValue::from(42_i64))]),
+            generation: None,
         },
         TracingEvent::SpanEntered { id: 0 },
         TracingEvent::SpanExited { id: 0 },
@@ -108,6 +119,7 @@ fn recorded_span_values_are_restored() {
         TracingEvent::NewEvent {
             metadata_id: 1,
             parent: None,
+            is_root: false,
             values: TracedValues::from_iter([("message".to_owned(), TracedValue::from("test"))]),
         },
         TracingEvent::SpanExited { id: 0 },
@@ -145,8 +157,10 @@ fn spans_are_exited_on_receiver_drop() {
         TracingEvent::NewSpan {
             id: 0,
             parent_id: None,
+            is_root: false,
             metadata_id: 0,
             values: TracedValues::new(),
+            generation: None,
         },
         TracingEvent::SpanEntered { id: 0 },
     ];
@@ -174,8 +188,10 @@ fn spans_are_exited_on_receiver_drop() {
         TracingEvent::NewSpan {
             id: 1,
             parent_id: None,
+            is_root: false,
             metadata_id: 0,
             values: TracedValues::new(),
+            generation: None,
         },
         TracingEvent::SpanEntered { id: 1 },
         TracingEvent::SpanEntered { id: 0 },
@@ -207,6 +223,15 @@ fn capturing_spans_directly() {
     assert_captured_spans(&storage.lock());
 }
 
+#[test]
+fn capturing_spans_with_preallocated_storage() {
+    let storage = SharedStorage::with_capacity(16, 64).with_span_capacity(4, 8);
+    let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    tracing::subscriber::with_default(subscriber, || fib::fib(5));
+
+    assert_captured_spans(&storage.lock());
+}
+
 fn assert_captured_spans(storage: &Storage) {
     let fib_span = storage
         .all_spans()
@@ -225,7 +250,7 @@ fn assert_captured_spans(storage: &Storage) {
         assert_eq!(event.message(), Some("performing iteration"));
         assert_eq!(event["i"], i as u64);
     }
-    let return_event = fib_span.events().last().unwrap();
+    let return_event = fib_span.events().next_back().unwrap();
     assert_eq!(*return_event.metadata().level(), Level::INFO);
     assert!(return_event["return"].is_debug(&5));
 
@@ -254,6 +279,68 @@ fn capturing_spans_for_replayed_events() {
     assert_captured_spans(&storage.lock());
 }
 
+// This is also a `TracingEventReceiver` test.
+#[test]
+fn ambient_fields_are_attached_to_replayed_spans_and_events() {
+    let events = fib::record_events(5);
+    // Split events so that the first span is created, but not entered yet.
+    let split_pos = events
+        .iter()
+        .position(|event| matches!(event, TracingEvent::NewSpan { .. }))
+        .unwrap();
+    let (first_events, second_events) = events.split_at(split_pos + 1);
+
+    let storage = SharedStorage::default();
+    let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    tracing::subscriber::with_default(subscriber, || {
+        let mut receiver = TracingEventReceiver::default();
+        receiver.set_ambient_field("workflow_id", 42_u64);
+        for event in first_events {
+            receiver.receive(event.clone());
+        }
+        let metadata = receiver.persist_metadata();
+        let (spans, local_spans) = receiver.persist();
+
+        let mut receiver = TracingEventReceiver::new(metadata, spans, local_spans);
+        for event in second_events {
+            receiver.receive(event.clone());
+        }
+    });
+
+    let storage = storage.lock();
+    assert_captured_spans(&storage);
+    storage.scan_spans().all(&field("workflow_id", 42_u64));
+    storage.scan_events().all(&field("workflow_id", 42_u64));
+}
+
+// This is also a `TracingEventReceiver` test.
+#[test]
+fn replaying_events_to_multiple_dispatches() {
+    let events = fib::record_events(5);
+
+    let storages = [SharedStorage::default(), SharedStorage::default()];
+    let dispatches = storages.iter().map(|storage| {
+        let subscriber = Registry::default().with(CaptureLayer::new(storage));
+        Dispatch::new(subscriber)
+    });
+    let mut receiver = TracingEventReceiver::builder()
+        .with_dispatches(dispatches)
+        .build();
+    // Use a default subscriber to check that it is not used by the receiver.
+    let default_storage = SharedStorage::default();
+    let subscriber = Registry::default().with(CaptureLayer::new(&default_storage));
+    tracing::subscriber::with_default(subscriber, || {
+        for event in events {
+            receiver.receive(event);
+        }
+    });
+
+    for storage in &storages {
+        assert_captured_spans(&storage.lock());
+    }
+    assert_eq!(default_storage.lock().all_spans().len(), 0);
+}
+
 #[test]
 fn capturing_events_with_indirect_ancestor() {
     #[tracing::instrument(level = "debug", ret)]
@@ -331,11 +418,69 @@ fn capturing_span_hierarchy() {
         .filter_map(|span| span["value"].as_uint())
         .collect();
     assert_eq!(ancestor_values, [4, 5]);
+    assert_eq!(middle_span.depth(), 2);
+    assert_eq!(inner_span.depth(), 5);
+    let root_span = storage.root_spans().next().unwrap();
+    assert_eq!(middle_span.root(), root_span);
+    assert_eq!(root_span.root(), root_span);
+    assert_eq!(root_span.depth(), 0);
+
+    let deep_span = storage.scan_spans().single(&depth(eq(2)));
+    assert_eq!(deep_span, middle_span);
+    storage.scan_events().all(&depth(ge(1)));
+    storage
+        .scan_events()
+        .single(&(depth(eq(3)) & message(eq("doubled"))));
 
     let event_filter = parent(field("value", 3_u64)) & message(eq("doubled"));
     storage.scan_events().single(&event_filter);
     let event_filter = field("value", 2_u64) & ancestor(field("value", 3_u64));
     storage.scan_events().single(&event_filter);
+
+    let descendant = middle_span
+        .scan_descendants()
+        .single(&field("value", 1_u64));
+    let ancestor_span = descendant.scan_ancestors().single(&field("value", 4_u64));
+    assert_eq!(ancestor_span.parent(), storage.root_spans().next());
+    middle_span.scan_ancestors().none(&field("value", 2_u64));
+    let event = descendant.scan_events().single(&message(eq("doubled")));
+    event.scan_ancestors().all(&name(eq("factorial")));
+    let root = event.scan_ancestors().single(&field("value", 5_u64));
+    assert_eq!(storage.root_spans().next(), Some(root));
+    assert_eq!(event.root(), Some(root));
+    let nearest = event.ancestor_matching(&field("value", 3_u64)).unwrap();
+    assert_eq!(
+        event.ancestor_matching(&name(eq("factorial"))),
+        event.parent()
+    );
+    assert!(event.ancestor_matching(&field("value", 0_u64)).is_none());
+    assert_eq!(descendant.ancestor_matching(&depth(eq(0))), Some(root));
+    assert_eq!(
+        nearest.ancestor_matching(&field("value", 4_u64)),
+        Some(ancestor_span)
+    );
+
+    storage.assert_relation(&field("value", 5_u64), &depth(ge(1)));
+    storage.assert_contains_child(&field("value", 3_u64), &field("value", 0_u64));
+    let panic_result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+        storage.assert_relation(&field("value", 3_u64), &field("value", 4_u64));
+    }));
+    let panic_message = panic_result.unwrap_err();
+    let panic_message = panic_message.downcast_ref::<String>().unwrap();
+    assert!(
+        panic_message.starts_with("span matching fields.value(var == 4) has no ancestor matching"),
+        "{panic_message}"
+    );
+    assert_eq!(root.events().next().unwrap().root(), Some(root));
+
+    assert_eq!(storage.events_under(&root).count(), 12);
+    let subtree_values: Vec<_> = storage
+        .events_under(&middle_span)
+        .filter_map(|event| event.value("value")?.as_uint())
+        .collect();
+    assert_eq!(subtree_values, [3, 2, 1, 0]);
+    let subtree_events: Vec<_> = storage.events_under(&middle_span).collect();
+    assert!(subtree_events.windows(2).all(|pair| pair[0] < pair[1]));
 }
 
 #[test]
@@ -431,6 +576,68 @@ fn items_from_different_storages_are_not_comparable() {
     assert!(event.partial_cmp(&other_event).is_none());
 }
 
+#[test]
+fn capturing_missing_ancestors() {
+    #[tracing::instrument(level = "debug", ret)]
+    fn double(value: i32) -> i32 {
+        tracing::info!(value, "doubled");
+        value * 2
+    }
+
+    let storage = SharedStorage::default();
+    let layer = CaptureLayer::new(&storage)
+        .with_filter(LevelFilter::INFO)
+        .with_missing_ancestors();
+    let subscriber = Registry::default().with(layer);
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info_span!("wrapper").in_scope(|| double(5));
+        tracing::debug_span!("debug_wrapper").in_scope(|| {
+            double(-3);
+            double(4);
+        });
+        // No placeholders are recorded if there are no captured descendants.
+        tracing::debug_span!("empty_wrapper").in_scope(|| tracing::debug!("ignored"));
+    });
+
+    let storage = storage.lock();
+    let span_names: Vec<_> = storage
+        .all_spans()
+        .map(|span| (span.metadata().name(), span.is_placeholder()))
+        .collect();
+    assert_eq!(
+        span_names,
+        [
+            ("wrapper", false),
+            ("double", true),
+            ("debug_wrapper", true),
+            ("double", true),
+            ("double", true),
+        ]
+    );
+    assert_eq!(storage.all_events().len(), 3);
+    assert_eq!(storage.root_events().len(), 0);
+
+    for placeholder in storage.all_spans().filter(|span| span.is_placeholder()) {
+        assert_eq!(placeholder.values().count(), 0);
+        assert!(placeholder.stats().is_closed);
+    }
+
+    let predicate =
+        message(eq("doubled")) & parent(name(eq("double"))) & ancestor(name(eq("debug_wrapper")));
+    let events: Vec<_> = storage
+        .all_events()
+        .filter(|event| predicate.eval(event))
+        .collect();
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0]["value"], -3_i64);
+    assert_eq!(events[1]["value"], 4_i64);
+    let ancestors: Vec<_> = events[0]
+        .ancestors()
+        .map(|span| span.metadata().name())
+        .collect();
+    assert_eq!(ancestors, ["double", "debug_wrapper"]);
+}
+
 #[test]
 fn explicit_parent_is_correctly_handled() {
     let storage = SharedStorage::default();
@@ -457,6 +664,49 @@ fn explicit_parent_is_correctly_handled() {
     assert_eq!(child_span.parent(), Some(event_parent));
 }
 
+#[test]
+fn inferring_parents_for_orphan_events() {
+    let storage = SharedStorage::default();
+    let layer = CaptureLayer::new(&storage).with_parent_inference(|event, storage| {
+        assert!(event.metadata().is_event());
+        let request_id = event.value("request_id")?.as_uint()?;
+        storage.all_spans().find(|span| {
+            span.metadata().name() == "request"
+                && span.value("id").and_then(TracedValue::as_uint) == Some(request_id)
+        })
+    });
+    let subscriber = Registry::default().with(layer);
+    tracing::subscriber::with_default(subscriber, || {
+        let first = tracing::info_span!("request", id = 1_u64);
+        let second = tracing::info_span!("request", id = 2_u64);
+        tracing::info!(parent: None, request_id = 2_u64, "second");
+        tracing::info!(parent: None, request_id = 1_u64, "first");
+        tracing::info!(parent: None, request_id = 3_u64, "unknown");
+        tracing::info!("no ID");
+        // Events with a captured parent are not passed to the hook.
+        first.in_scope(|| tracing::info!(request_id = 2_u64, "contextual"));
+        drop((first, second));
+    });
+
+    let storage = storage.lock();
+    let spans: Vec<_> = storage.all_spans().collect();
+    let messages: Vec<_> = spans[0]
+        .events()
+        .filter_map(|event| event.message())
+        .collect();
+    assert_eq!(messages, ["first", "contextual"]);
+    let messages: Vec<_> = spans[1]
+        .events()
+        .filter_map(|event| event.message())
+        .collect();
+    assert_eq!(messages, ["second"]);
+    let root_messages: Vec<_> = storage
+        .root_events()
+        .filter_map(|event| event.message())
+        .collect();
+    assert_eq!(root_messages, ["unknown", "no ID"]);
+}
+
 #[test]
 fn failed_assertion_while_storage_is_locked() {
     let storage = SharedStorage::default();
@@ -478,3 +728,1515 @@ fn failed_assertion_while_storage_is_locked() {
     let storage = storage.lock();
     assert_eq!(storage.all_events().len(), 1);
 }
+
+#[test]
+fn redacting_and_truncating_values() {
+    let storage = SharedStorage::default();
+    let layer = CaptureLayer::new(&storage)
+        .with_redaction(|name, value| {
+            if name == "token" {
+                *value = TracedValue::from("[REDACTED]");
+            }
+        })
+        .with_max_value_len(10);
+    let subscriber = Registry::default().with(layer);
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::info_span!("request", token = "secret", body = tracing::field::Empty);
+        span.record("token", "other secret");
+        span.record("body", "Привет, мир!");
+        let err = std::io::Error::new(std::io::ErrorKind::Other, "connection reset by peer");
+        span.in_scope(|| {
+            tracing::warn!(err = &err as &dyn std::error::Error, "request failed");
+        });
+    });
+
+    let storage = storage.lock();
+    let span = storage.all_spans().next().unwrap();
+    assert_eq!(span["token"], "[REDACTED]");
+    assert_eq!(span["body"], "Приве…"); // Cyrillic chars take 2 bytes each
+    let event = span.events().next().unwrap();
+    assert_matches!(&event["err"], TracedValue::Error(err) if err.message == "connection…");
+    assert_eq!(event.message(), Some("request fa…"));
+}
+
+#[test]
+fn distinguishing_recorded_values() {
+    let storage = SharedStorage::default();
+    let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::info_span!(
+            "compute",
+            arg = 5,
+            attempt = 1,
+            result = tracing::field::Empty
+        );
+        span.record("attempt", 2);
+        span.record("result", 25);
+    });
+
+    let storage = storage.lock();
+    let span = storage.all_spans().next().unwrap();
+    assert_eq!(span.value_origin("arg"), Some(ValueOrigin::New));
+    assert_eq!(span.value_origin("attempt"), Some(ValueOrigin::Recorded));
+    assert_eq!(span["attempt"], 2_i64);
+    assert_eq!(span.value_origin("result"), Some(ValueOrigin::Recorded));
+    assert_eq!(span.value_origin("missing"), None);
+
+    let mut builder = StorageBuilder::new();
+    builder
+        .span("synthetic")
+        .value("arg", 5_i64)
+        .recorded_value("result", 25_i64)
+        .build();
+    let storage = builder.build();
+    let span = storage.all_spans().next().unwrap();
+    assert_eq!(span.value_origin("arg"), Some(ValueOrigin::New));
+    assert_eq!(span.value_origin("result"), Some(ValueOrigin::Recorded));
+}
+
+#[test]
+fn capturing_timeline() {
+    let storage = SharedStorage::default();
+    let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    tracing::subscriber::with_default(subscriber, || {
+        let outer = tracing::info_span!("outer", done = tracing::field::Empty);
+        let inner = outer.in_scope(|| tracing::debug_span!("inner"));
+        inner.in_scope(|| tracing::warn!(answer = 42, "answered"));
+        drop(inner);
+        outer.record("done", true);
+    });
+
+    let storage = storage.lock();
+    let timeline: Vec<_> = storage.timeline().collect();
+    assert_eq!(timeline.len(), 10);
+    assert_matches!(
+        timeline[0],
+        CapturedOperation::SpanCreated(span) if span.metadata().name() == "outer"
+    );
+    assert_matches!(
+        timeline[2],
+        CapturedOperation::SpanCreated(span) if span.parent().is_some()
+    );
+    assert_matches!(
+        timeline[5],
+        CapturedOperation::EventEmitted(event) if event["answer"] == 42_i64
+    );
+    assert_matches!(
+        timeline[8],
+        CapturedOperation::ValueRecorded { span, name: "done", value }
+            if span["done"] == true && *value == true
+    );
+
+    let narrative: Vec<_> = timeline.iter().map(ToString::to_string).collect();
+    assert_eq!(
+        narrative,
+        [
+            "created span `outer`",
+            "entered span `outer`",
+            "created span `inner`",
+            "exited span `outer`",
+            "entered span `inner`",
+            "WARN event: answered",
+            "exited span `inner`",
+            "closed span `inner`",
+            "recorded `done` in span `outer`",
+            "closed span `outer`",
+        ]
+        .map(str::to_owned)
+    );
+}
+
+#[test]
+fn capturing_follows_from_relations() {
+    let storage = SharedStorage::default();
+    let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    tracing::subscriber::with_default(subscriber, || {
+        let sources: Vec<_> = (0..3)
+            .map(|idx| tracing::info_span!("source", idx))
+            .collect();
+        let merged = tracing::info_span!("merge");
+        for source in sources.iter().rev() {
+            merged.follows_from(source);
+        }
+        // Duplicate relations are ignored.
+        merged.follows_from(&sources[0]);
+    });
+
+    let storage = storage.lock();
+    let merged = storage.scan_spans().single(&name(eq("merge")));
+    let source_indices: Vec<_> = merged
+        .follows_from()
+        .filter_map(|span| span["idx"].as_int())
+        .collect();
+    assert_eq!(source_indices, [2, 1, 0]);
+    for source in merged.follows_from() {
+        assert_eq!(source.follows_from().len(), 0);
+    }
+
+    let relations: Vec<_> = storage
+        .timeline()
+        .filter(|op| matches!(op, CapturedOperation::FollowsFrom { .. }))
+        .collect();
+    assert_eq!(relations.len(), 4);
+    assert_matches!(
+        relations[0],
+        CapturedOperation::FollowsFrom { span, follows_from }
+            if span == merged && follows_from["idx"] == 2_i64
+    );
+    assert_eq!(
+        relations[0].to_string(),
+        "span `merge` follows from span `source`"
+    );
+}
+
+#[test]
+fn querying_storage() {
+    let storage = SharedStorage::default();
+    let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    tracing::subscriber::with_default(subscriber, || {
+        let _entered = tracing::info_span!("request", id = 1).entered();
+        tracing::debug_span!(target: "db", "query", rows = tracing::field::Empty).in_scope(|| {
+            tracing::trace!(target: "db", "connected");
+            tracing::info_span!("retry").in_scope(|| tracing::warn!(attempt = 2, "retrying"));
+        });
+        tracing::error!("failed");
+    });
+
+    let storage = storage.lock();
+    let root = storage.query().spans().name("request").next().unwrap();
+    assert_eq!(root["id"], 1_i64);
+    let query_span = storage.query().spans().target("db").next().unwrap();
+    assert_eq!(query_span.metadata().name(), "query");
+    // `rows` is not recorded, so it is not present.
+    assert_eq!(storage.query().spans().with_field("rows").count(), 0);
+
+    let children: Vec<_> = storage.query().spans().children_of(root).collect();
+    assert_eq!(children, [query_span]);
+    let descendants = storage.query().spans().descendants_of(root);
+    assert_eq!(descendants.count(), 2);
+
+    let events = storage.query().events();
+    let messages: Vec<_> = events
+        .descendants_of(query_span)
+        .filter_map(|event| event.message())
+        .collect();
+    assert_eq!(messages, ["connected", "retrying"]);
+    let messages: Vec<_> = storage
+        .query()
+        .events()
+        .children_of(root)
+        .level(Level::ERROR)
+        .filter_map(|event| event.message())
+        .collect();
+    assert_eq!(messages, ["failed"]);
+
+    assert_eq!(storage.query().events().min_level(Level::DEBUG).count(), 2);
+    assert_eq!(storage.query().events().min_level(Level::TRACE).count(), 3);
+    let events = storage.query().events().field("attempt", 2_i64);
+    assert_eq!(events.count(), 1);
+    let events = storage.query().events().field("attempt", 2_u64);
+    assert_eq!(events.count(), 0);
+}
+
+#[test]
+fn detecting_open_spans() {
+    let storage = SharedStorage::default();
+    let layer = CaptureLayer::new(&storage).with_open_spans_check(OpenSpansCheck::Panic);
+    let subscriber = Registry::default().with(layer);
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info_span!("closed").in_scope(|| {
+            tracing::info!("test");
+        });
+        // If the span was created inside "closed", it would prevent the parent from closing.
+        std::mem::forget(tracing::info_span!(target: "leaks", "leaked"));
+    });
+
+    {
+        let storage = storage.lock();
+        assert_eq!(storage.all_spans().len(), 2);
+        let open_spans: Vec<_> = storage.open_spans().collect();
+        assert_eq!(open_spans.len(), 1);
+        let open_span = open_spans[0];
+        assert_eq!(open_span.metadata().name(), "leaked");
+        assert_eq!(open_span.stats().entered, 0);
+    }
+
+    let storage_copy = storage.clone();
+    drop(storage); // should not panic since there's another handle
+    let panic_result = panic::catch_unwind(panic::AssertUnwindSafe(|| drop(storage_copy)));
+    let panic_message = panic_result.unwrap_err();
+    let panic_message = panic_message.downcast_ref::<String>().unwrap();
+    assert_eq!(
+        panic_message,
+        "1 captured span(s) are not closed: leaks::leaked"
+    );
+}
+
+#[test]
+fn capturing_timings() {
+    let storage = SharedStorage::default();
+    let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::info_span!("compute", arg = 5);
+        for i in 0..2 {
+            let _entered = span.enter();
+            tracing::info!(i, "iteration");
+        }
+    });
+
+    let storage = storage.lock();
+    assert_eq!(storage.threads(), [std::thread::current().id()]);
+    let span = storage.all_spans().next().unwrap();
+    let closed_at = span.closed_at().unwrap();
+    assert!(span.created_at() >= storage.created_at());
+    let entries = span.entries();
+    assert_eq!(entries.len(), 2);
+    for (entry, event) in entries.iter().zip(span.events()) {
+        assert_eq!(entry.thread, 0);
+        assert_eq!(event.thread(), 0);
+        let exited_at = entry.exited_at.unwrap();
+        assert!(span.created_at() <= entry.entered_at);
+        assert!(entry.entered_at <= event.timestamp());
+        assert!(event.timestamp() <= exited_at);
+        assert!(exited_at <= closed_at);
+    }
+    assert!(entries[0].exited_at.unwrap() <= entries[1].entered_at);
+
+    let trace = storage.to_chrome_trace();
+    let span_pos = trace.find(r#"{"ph":"X","name":"compute","cat":"integration""#);
+    let span_pos = span_pos.unwrap();
+    let event_pos = trace.find(r#"{"ph":"i","s":"t","name":"iteration""#);
+    assert!(span_pos < event_pos.unwrap());
+    assert_eq!(trace.matches(r#""args":{"arg":5}"#).count(), 2);
+    assert!(trace.contains(r#""args":{"i":1}"#));
+    assert!(trace.ends_with(r#"],"displayTimeUnit":"ms"}"#));
+}
+
+#[test]
+fn capturing_busy_and_idle_time() {
+    use std::{thread, time::Duration};
+
+    const DELAY: Duration = Duration::from_millis(10);
+
+    let storage = SharedStorage::default();
+    let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::info_span!("poll");
+        // Emulate a future that is polled twice and spends most time waiting.
+        span.in_scope(|| {
+            // Re-entering the span must not be double-counted.
+            span.in_scope(|| thread::sleep(DELAY));
+        });
+        thread::sleep(DELAY * 5);
+        span.in_scope(|| thread::sleep(DELAY));
+
+        let open_span = tracing::info_span!("open");
+        open_span.in_scope(|| thread::sleep(DELAY));
+        thread::sleep(DELAY);
+        std::mem::forget(open_span);
+    });
+
+    let storage = storage.lock();
+    let mut spans = storage.all_spans();
+    let span = spans.next().unwrap();
+    let stats = span.stats();
+    assert!(stats.busy_time >= DELAY * 2);
+    assert!(stats.idle_time >= DELAY * 5);
+    assert!(stats.idle_time > stats.busy_time);
+    let lifetime = span.closed_at().unwrap() - span.created_at();
+    assert_eq!(stats.busy_time + stats.idle_time, lifetime);
+
+    // For open spans, idle time is counted until the last exit.
+    let open_span = spans.next().unwrap();
+    let stats = open_span.stats();
+    assert!(!stats.is_closed);
+    assert!(stats.busy_time >= DELAY);
+    assert!(stats.idle_time < DELAY);
+}
+
+#[test]
+fn extracting_owned_span_tree() {
+    fn assert_static_send<T: Send + Sync + 'static>(_: &T) {}
+
+    let storage = SharedStorage::default();
+    let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    tracing::subscriber::with_default(subscriber, || fib::fib(4));
+
+    let tree = {
+        let storage = storage.lock();
+        let root = storage.root_spans().next().unwrap();
+        let tree = root.to_owned_tree();
+        assert_eq!(tree.descendants().count(), root.descendants().count());
+        assert_eq!(
+            tree.descendant_events().count(),
+            root.descendant_events().count()
+        );
+        let names: Vec<_> = tree.descendants().map(|span| &span.metadata.name).collect();
+        let expected_names: Vec<_> = root
+            .descendants()
+            .map(|span| span.metadata().name())
+            .collect();
+        assert_eq!(names, expected_names);
+        tree
+    };
+    drop(storage);
+
+    assert_static_send(&tree);
+    assert_eq!(tree.metadata.name, "fib");
+    assert_eq!(tree.metadata.level, TracingLevel::Info);
+    assert!(tree.stats.is_closed);
+    let tree = std::thread::spawn(move || tree.clone()).join().unwrap();
+    assert_eq!(tree["approx"], 3.0);
+    assert_eq!(tree.children[0].metadata.name, "compute");
+    assert_eq!(tree.children[0]["count"], 4_u64);
+}
+
+#[test]
+fn predicates_for_span_lifecycle() {
+    use std::sync::mpsc;
+    use tracing_capture::predicates::{before_close, emitted_while_entered, into_fn};
+
+    let storage = SharedStorage::default();
+    let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::info_span!("transaction");
+        let _entered = span.enter();
+        let (sx, rx) = mpsc::channel();
+        let dispatch = tracing::dispatcher::get_default(Dispatch::clone);
+        std::thread::spawn(move || {
+            tracing::dispatcher::with_default(&dispatch, || {
+                // The span is entered on another thread.
+                tracing::info!("other thread");
+            });
+            sx.send(()).unwrap();
+        });
+        rx.recv().unwrap();
+        tracing::info!("same thread");
+    });
+
+    let storage = storage.lock();
+    let in_transaction = emitted_while_entered(name(eq("transaction")));
+    let before_commit = before_close(name(eq("transaction")));
+    let events: Vec<_> = storage.all_events().collect();
+    assert_eq!(events.len(), 2);
+    let messages: Vec<_> = events
+        .iter()
+        .copied()
+        .filter(into_fn(in_transaction))
+        .map(|event| event.message().unwrap())
+        .collect();
+    assert_eq!(messages, ["same thread"]);
+    assert!(events.iter().all(into_fn(before_commit)));
+
+    let other_event = events
+        .iter()
+        .find(|event| event.message() == Some("other thread"))
+        .unwrap();
+    assert_eq!(
+        in_transaction.to_string(),
+        r#"emitted_while_entered(name(var == "transaction"))"#
+    );
+    let case = in_transaction.find_case(false, other_event).unwrap();
+    assert_eq!(case.products().count(), 0);
+    let case = before_commit.find_case(true, other_event).unwrap();
+    let products: Vec<_> = case.products().map(ToString::to_string).collect();
+    assert_eq!(products, ["span: transaction"]);
+    assert!(before_commit.find_case(false, other_event).is_none());
+}
+
+#[test]
+fn counting_spans_and_events_per_call_site() {
+    let storage = SharedStorage::default();
+    let layer = CaptureLayer::new(&storage).with_missing_ancestors();
+    let subscriber = Registry::default().with(layer.with_filter(LevelFilter::INFO));
+    tracing::subscriber::with_default(subscriber, || {
+        let _outer = tracing::debug_span!("filtered").entered();
+        for i in 0..5_u64 {
+            tracing::info_span!("task", i).in_scope(|| {
+                tracing::info!(i, "started");
+                if i % 2 == 0 {
+                    tracing::warn!(i, "even");
+                }
+            });
+        }
+    });
+
+    let storage = storage.lock();
+    let counts: Vec<_> = storage
+        .call_sites()
+        .iter()
+        .map(|site| (site.metadata.name(), site.metadata.is_span(), site.count))
+        .collect();
+    // The placeholder `filtered` span is not counted.
+    assert_eq!(counts.len(), 3, "{counts:?}");
+    assert_eq!(counts[0], ("task", true, 5));
+    assert_eq!((counts[1].1, counts[1].2), (false, 5));
+    assert_eq!((counts[2].1, counts[2].2), (false, 3));
+    assert_eq!(storage.all_spans().len(), 6);
+}
+
+#[test]
+fn extracting_data_from_span_extensions() {
+    use tracing_core::{span, Event, Subscriber};
+    use tracing_subscriber::{layer::Context, registry::LookupSpan, Layer};
+
+    #[derive(Debug)]
+    struct EventCount(u64);
+
+    /// Layer counting events in the span extensions.
+    struct CountingLayer;
+
+    impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for CountingLayer {
+        fn on_new_span(&self, _: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+            ctx.span(id).unwrap().extensions_mut().insert(EventCount(0));
+        }
+
+        fn on_event(&self, _: &Event<'_>, ctx: Context<'_, S>) {
+            if let Some(span) = ctx.lookup_current() {
+                span.extensions_mut().get_mut::<EventCount>().unwrap().0 += 1;
+            }
+        }
+    }
+
+    let storage = SharedStorage::default();
+    let layer = CaptureLayer::new(&storage).with_extension_extractor(|extensions, values| {
+        if let Some(EventCount(count)) = extensions.get::<EventCount>() {
+            values.insert("event_count", (*count).into());
+        }
+    });
+    let subscriber = Registry::default().with(CountingLayer).with(layer);
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::info_span!("counted");
+        span.in_scope(|| {
+            tracing::info!("first");
+            tracing::info!("second");
+        });
+
+        let storage = storage.lock();
+        let captured = storage.all_spans().next().unwrap();
+        // Values are only extracted on span creation and closing.
+        assert_eq!(*captured.extension_value("event_count").unwrap(), 0_u64);
+        drop(storage);
+        drop(span);
+    });
+
+    let storage = storage.lock();
+    let span = storage.all_spans().next().unwrap();
+    let extension_values: Vec<_> = span.extension_values().collect();
+    assert_eq!(extension_values.len(), 1);
+    assert_eq!(*span.extension_value("event_count").unwrap(), 2_u64);
+    assert!(span.value("event_count").is_none());
+}
+
+#[test]
+fn invoking_close_hooks() {
+    use std::sync::{Arc, Mutex};
+
+    let closed_spans = Arc::new(Mutex::new(vec![]));
+    let closed_spans_ = Arc::clone(&closed_spans);
+    let captured_counts = Arc::new(Mutex::new(vec![]));
+    let captured_counts_ = Arc::clone(&captured_counts);
+    let storage = SharedStorage::default();
+    let storage_ = storage.clone();
+    let layer = CaptureLayer::new(&storage)
+        .with_close_hook(move |span| {
+            closed_spans_.lock().unwrap().push(span.clone());
+        })
+        .with_close_hook(move |_| {
+            // The storage is not locked when hooks are invoked.
+            let span_count = storage_.lock().all_spans().len();
+            captured_counts_.lock().unwrap().push(span_count);
+        });
+    let subscriber = Registry::default().with(layer);
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info_span!("outer", i = 1_i64).in_scope(|| {
+            tracing::info_span!("inner").in_scope(|| {
+                tracing::info!("first");
+                tracing::info!("second");
+            });
+        });
+    });
+
+    let closed_spans = closed_spans.lock().unwrap();
+    assert_eq!(closed_spans.len(), 2);
+    let inner = &closed_spans[0];
+    assert_eq!(inner.metadata.name(), "inner");
+    assert_eq!(inner.event_count, 2);
+    assert_eq!(inner.parent_metadata.unwrap().name(), "outer");
+    assert!(inner.stats.is_closed);
+    assert_eq!(inner.stats.entered, 1);
+    let outer = &closed_spans[1];
+    assert_eq!(outer.metadata.name(), "outer");
+    assert_eq!(outer.values["i"], 1_i64);
+    assert!(outer.parent_metadata.is_none());
+    assert!(outer.duration() >= inner.duration());
+
+    assert_eq!(*captured_counts.lock().unwrap(), [2, 2]);
+}
+
+#[test]
+fn inspecting_storage_snapshots() {
+    let storage = SharedStorage::default();
+    let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let span = tracing::info_span!("compute", arg = 5_i32);
+    span.in_scope(|| tracing::info!("started"));
+    let snapshot = storage.snapshot();
+    let span_handle = OwnedCapturedSpan::new(
+        &snapshot,
+        snapshot.scan_spans().single(&name(eq("compute"))),
+    );
+    let event_handle =
+        OwnedCapturedEvent::new(&snapshot, span_handle.get().events().next().unwrap());
+    assert_eq!(span_handle.clone(), span_handle);
+
+    // Capture continues while the snapshot is inspected on another thread.
+    let inspection = std::thread::spawn(move || {
+        let span = span_handle.get();
+        assert_eq!(span["arg"], 5_i64);
+        assert!(!span.stats().is_closed);
+        assert_eq!(event_handle.get().parent(), Some(span));
+        assert_eq!(span_handle.storage().all_events().len(), 1);
+    });
+    span.in_scope(|| tracing::info!("finished"));
+    drop(span);
+    inspection.join().unwrap();
+
+    let storage = storage.lock();
+    assert_eq!(storage.all_events().len(), 2);
+    assert!(storage.all_spans().next().unwrap().stats().is_closed);
+    assert!(!snapshot.all_spans().next().unwrap().stats().is_closed);
+}
+
+#[test]
+fn clearing_storage() {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    let storage = SharedStorage::default();
+    let hook_calls = Arc::new(AtomicUsize::new(0));
+    let hook_calls_ = Arc::clone(&hook_calls);
+    let layer = CaptureLayer::new(&storage).with_close_hook(move |_| {
+        hook_calls_.fetch_add(1, Ordering::Relaxed);
+    });
+    let subscriber = Registry::default().with(layer);
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let stale_span = tracing::info_span!("stale", value = tracing::field::Empty);
+    stale_span.in_scope(|| tracing::info!("before clearing"));
+    let snapshot = storage.snapshot();
+    let stale_handle = OwnedCapturedSpan::new(&snapshot, snapshot.all_spans().next().unwrap());
+    storage.clear();
+    assert_eq!(storage.lock().generation(), 1);
+    assert_eq!(storage.lock().all_spans().len(), 0);
+
+    // Operations on the stale span must not panic.
+    stale_span.record("value", 42_i64);
+    let fresh_span = tracing::info_span!("fresh");
+    fresh_span.follows_from(&stale_span);
+    stale_span.in_scope(|| {
+        tracing::info!("after clearing");
+        tracing::info_span!("child").in_scope(|| {});
+    });
+    drop(stale_span);
+    drop(fresh_span);
+    assert_eq!(hook_calls.load(Ordering::Relaxed), 2);
+
+    let storage = storage.lock();
+    let span_names: Vec<_> = storage
+        .all_spans()
+        .map(|span| span.metadata().name())
+        .collect();
+    assert_eq!(span_names, ["fresh", "child"]);
+    assert_eq!(storage.root_spans().len(), 2);
+    assert!(storage
+        .all_spans()
+        .all(|span| span.follows_from().len() == 0));
+    let event = storage.scan_events().single(&message(eq("after clearing")));
+    assert!(event.parent().is_none());
+    assert_eq!(storage.call_sites().len(), 3);
+
+    // The snapshot taken before clearing is unaffected.
+    let stale = stale_handle.get();
+    assert_eq!(stale.metadata().name(), "stale");
+    assert_eq!(stale.events().len(), 1);
+    assert_eq!(snapshot.generation(), 0);
+}
+
+#[test]
+fn partitioning_captured_data_by_dispatcher() {
+    let storage = SharedStorage::default();
+    let outer = Registry::default().with(CaptureLayer::new(&storage));
+    let inner = Registry::default().with(CaptureLayer::new(&storage));
+    let inner = Dispatch::new(inner);
+
+    tracing::subscriber::with_default(outer, || {
+        tracing::info_span!("outer").in_scope(|| {
+            tracing::info!("outer event");
+            tracing::dispatcher::with_default(&inner, || {
+                tracing::info_span!("inner").in_scope(|| tracing::info!("inner event"));
+            });
+        });
+        tracing::info!("another outer event");
+    });
+    // Reusing the dispatcher does not change its ID.
+    tracing::dispatcher::with_default(&inner, || tracing::info!("late inner event"));
+
+    let storage = storage.lock();
+    assert_eq!(storage.dispatchers().len(), 2);
+    let outer_span = storage.scan_spans().single(&name(eq("outer")));
+    assert_eq!(outer_span.dispatcher(), Some(0));
+    let inner_span = storage.scan_spans().single(&name(eq("inner")));
+    assert_eq!(inner_span.dispatcher(), Some(1));
+    // Spans from different dispatchers are not linked.
+    assert!(inner_span.parent().is_none());
+
+    let events_by_dispatcher = |idx| -> Vec<_> {
+        storage
+            .all_events()
+            .filter(|event| event.dispatcher() == Some(idx))
+            .filter_map(|event| event.message())
+            .collect()
+    };
+    assert_eq!(
+        events_by_dispatcher(0),
+        ["outer event", "another outer event"]
+    );
+    assert_eq!(events_by_dispatcher(1), ["inner event", "late inner event"]);
+
+    let mut builder = StorageBuilder::new();
+    builder.span("synthetic").build();
+    let storage = builder.build();
+    assert_eq!(storage.dispatchers().len(), 0);
+    assert_eq!(storage.all_spans().next().unwrap().dispatcher(), None);
+}
+
+#[cfg(feature = "tunnel")]
+#[test]
+fn capturing_tunnel_events_without_host_dispatcher() {
+    use tracing_capture::CaptureReceiver;
+
+    let events = [
+        TracingEvent::NewCallSite {
+            id: 0,
+            data: CALL_SITE_DATA,
+        },
+        TracingEvent::NewCallSite {
+            id: 1,
+            data: CallSiteData {
+                kind: CallSiteKind::Event,
+                name: Cow::Borrowed("event"),
+                level: TracingLevel::Info,
+                fields: vec!["message".into()],
+                ..CALL_SITE_DATA
+            },
+        },
+        TracingEvent::NewSpan {
+            id: 0,
+            parent_id: None,
+            is_root: false,
+            metadata_id: 0,
+            values: TracedValues::new(),
+            generation: None,
+        },
+        TracingEvent::SpanEntered { id: 0 },
+        TracingEvent::NewEvent {
+            metadata_id: 1,
+            parent: None,
+            is_root: false,
+            values: TracedValues::from_iter([("message".to_owned(), "hello".into())]),
+        },
+        TracingEvent::SpanExited { id: 0 },
+        TracingEvent::SpanDropped { id: 0 },
+    ];
+
+    let host_storage = SharedStorage::default();
+    let host_subscriber = Registry::default().with(CaptureLayer::new(&host_storage));
+    let storage = SharedStorage::default();
+    tracing::subscriber::with_default(host_subscriber, || {
+        let mut receiver = CaptureReceiver::new(&storage);
+        for event in events {
+            receiver.receive(event);
+        }
+        assert_eq!(receiver.receiver().counters().forwarded_spans, 1);
+    });
+
+    assert_eq!(host_storage.lock().all_spans().len(), 0);
+    assert_eq!(host_storage.lock().all_events().len(), 0);
+    let storage = storage.lock();
+    let span = storage.scan_spans().single(&name(eq("test")));
+    assert!(span.stats().is_closed);
+    let event = storage.scan_events().single(&message(eq("hello")));
+    assert_eq!(event.parent(), Some(span));
+}
+
+// This is also a `TracingEventReceiver` test.
+#[test]
+fn relaying_explicit_roots_from_tunnel() {
+    let event_call_site = CallSiteData {
+        kind: CallSiteKind::Event,
+        name: Cow::Borrowed("event"),
+        level: TracingLevel::Info,
+        fields: vec!["message".into()],
+        ..CALL_SITE_DATA
+    };
+    let new_event = |message: &str, is_root| TracingEvent::NewEvent {
+        metadata_id: 1,
+        parent: None,
+        is_root,
+        values: TracedValues::from_iter([("message".to_owned(), message.into())]),
+    };
+    let events = [
+        TracingEvent::NewCallSite {
+            id: 0,
+            data: CALL_SITE_DATA,
+        },
+        TracingEvent::NewCallSite {
+            id: 1,
+            data: event_call_site,
+        },
+        TracingEvent::NewSpan {
+            id: 0,
+            parent_id: None,
+            is_root: false,
+            metadata_id: 0,
+            values: TracedValues::new(),
+            generation: None,
+        },
+        TracingEvent::SpanEntered { id: 0 },
+        TracingEvent::NewSpan {
+            id: 1,
+            parent_id: None,
+            is_root: true,
+            metadata_id: 0,
+            values: TracedValues::new(),
+            generation: None,
+        },
+        new_event("root", true),
+        new_event("contextual", false),
+        TracingEvent::SpanExited { id: 0 },
+    ];
+
+    let storage = SharedStorage::default();
+    let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    tracing::subscriber::with_default(subscriber, || {
+        let mut receiver = TracingEventReceiver::default();
+        for event in events {
+            receiver.receive(event);
+        }
+    });
+
+    let storage = storage.lock();
+    let spans: Vec<_> = storage.all_spans().collect();
+    assert_eq!(spans.len(), 2);
+    assert_eq!(spans[1].parent(), None);
+    let root_event = storage.scan_events().single(&message(eq("root")));
+    assert_eq!(root_event.parent(), None);
+    let contextual_event = storage.scan_events().single(&message(eq("contextual")));
+    assert_eq!(contextual_event.parent(), Some(spans[0]));
+}
+
+#[test]
+fn capturing_byte_values() {
+    use tracing_capture::predicates::{bytes_len, bytes_prefix};
+
+    let storage = SharedStorage::default();
+    let layer = CaptureLayer::new(&storage).with_max_value_len(4);
+    let subscriber = Registry::default().with(layer);
+    tracing::subscriber::with_default(subscriber, || {
+        let frame = b"\x01\x00\x04ping";
+        tracing::info!(frame = &frame[..], "sent frame");
+        // Byte slices recorded via `Debug` are recorded as `Debug` objects.
+        tracing::info!(frame = ?&frame[..], "sent raw frame");
+    });
+
+    let storage = storage.lock();
+    let predicate = field("frame", bytes_len(eq(4))) & field("frame", bytes_prefix([1, 0]));
+    let event = storage.scan_events().single(&predicate);
+    assert_eq!(event["frame"].as_bytes(), Some(&b"\x01\x00\x04p"[..]));
+    let raw_event = storage.all_events().nth(1).unwrap();
+    assert!(raw_event["frame"].as_debug_str().is_some());
+}
+
+#[test]
+fn accessing_fields_fallibly() {
+    let storage = SharedStorage::default();
+    let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info_span!("request", method = "GET", retries = 2_u64).in_scope(|| {
+            tracing::warn!(elapsed = 0.5, ok = false, "request timed out");
+        });
+    });
+
+    let storage = storage.lock();
+    let span = storage.all_spans().next().unwrap();
+    assert_eq!(span.field("method").expect_str(), "GET");
+    assert_eq!(span.field("retries").expect_u64(), 2);
+    assert_eq!(span.field("retries").get_as::<u8>().unwrap(), 2);
+    let err = span.field("retries").get_as::<i64>().unwrap_err();
+    assert!(!err.is_missing());
+    assert_eq!(err.field_name(), "retries");
+    assert_eq!(*err.actual_value().unwrap(), 2_u64);
+
+    let event = span.events().next().unwrap();
+    assert_eq!(
+        event.field("message").expect_debug_str(),
+        "request timed out"
+    );
+    assert!(!event.field("ok").expect_bool());
+    assert!(event.field("status").value().is_none());
+    let err = event.field("status").get().unwrap_err();
+    assert!(err.is_missing());
+
+    let panic_result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+        event.field("elapsed").expect_str();
+    }));
+    let panic_message = panic_result.unwrap_err();
+    let panic_message = panic_message.downcast_ref::<String>().unwrap();
+    assert!(
+        panic_message.starts_with("field `elapsed` of event `event capture/tests")
+            && panic_message.ends_with("has unexpected value Float(0.5) (expected: str)"),
+        "{panic_message}"
+    );
+
+    let panic_result = panic::catch_unwind(panic::AssertUnwindSafe(|| &span["status"]));
+    let panic_message = panic_result.unwrap_err();
+    let panic_message = panic_message.downcast_ref::<String>().unwrap();
+    assert_eq!(
+        panic_message,
+        "field `status` is not contained in span `request`"
+    );
+}
+
+#[test]
+fn detecting_spans_outliving_parents() {
+    use tracing_capture::predicates::outlived_parent;
+
+    let storage = SharedStorage::default();
+    let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    tracing::subscriber::with_default(subscriber, || {
+        let parent = tracing::info_span!("parent");
+        let (detached, joined) = parent.in_scope(|| {
+            tracing::info_span!("attached").in_scope(|| {});
+            (
+                tracing::info_span!("detached"),
+                tracing::info_span!("joined"),
+            )
+        });
+        // Emulates an async parent awaiting a spawned task: the task finishes while the parent
+        // is not entered, after which the parent is polled again.
+        drop(joined);
+        parent.in_scope(|| {});
+        drop(parent);
+        drop(detached);
+
+        let unclosed = tracing::info_span!("root").in_scope(|| tracing::info_span!("unclosed"));
+        std::mem::forget(unclosed);
+    });
+
+    let storage = storage.lock();
+    let spans: Vec<_> = storage
+        .all_spans()
+        .map(|span| (span.metadata().name(), span.closed_after_parent()))
+        .collect();
+    assert_eq!(
+        spans,
+        [
+            ("parent", None),
+            ("attached", Some(false)),
+            ("detached", Some(true)),
+            ("joined", Some(false)),
+            ("root", None),
+            ("unclosed", None),
+        ]
+    );
+
+    let detached = storage.scan_spans().single(&outlived_parent());
+    assert_eq!(detached.metadata().name(), "detached");
+    storage
+        .scan_spans()
+        .none(&(outlived_parent() & name(eq("joined"))));
+}
+
+#[test]
+fn partitioning_captured_data_by_root_spans() {
+    let storage = SharedStorage::default();
+    let layer = CaptureLayer::new(&storage).partition_by(|root_span| root_span.value("test_id"));
+    let subscriber = Registry::default().with(layer);
+    tracing::subscriber::with_default(subscriber, || {
+        for test_id in [1_u64, 2, 1] {
+            tracing::info_span!("test", test_id).in_scope(|| {
+                tracing::info_span!("nested").in_scope(|| {
+                    tracing::info!(test_id, "nested event");
+                });
+                tracing::info!("event");
+            });
+        }
+        tracing::info_span!("unpartitioned").in_scope(|| tracing::info!("event"));
+        tracing::info!("root event");
+    });
+
+    let mut keys = storage.partition_keys();
+    keys.sort_unstable();
+    assert_eq!(keys, ["1", "2"]);
+
+    let partition = storage.partition("1");
+    let partition = partition.lock();
+    assert_eq!(partition.root_spans().len(), 2);
+    assert_eq!(partition.all_spans().len(), 4);
+    assert_eq!(partition.all_events().len(), 4);
+    for span in partition.root_spans() {
+        assert_eq!(span["test_id"], 1_u64);
+        let nested = span.scan_spans().single(&name(eq("nested")));
+        assert_eq!(nested.events().next().unwrap()["test_id"], 1_u64);
+    }
+
+    let partition = storage.partition("2");
+    assert_eq!(partition.lock().all_events().len(), 2);
+
+    let storage = storage.lock();
+    let span_names: Vec<_> = storage
+        .all_spans()
+        .map(|span| span.metadata().name())
+        .collect();
+    assert_eq!(span_names, ["unpartitioned"]);
+    assert_eq!(storage.all_events().len(), 2);
+    assert_eq!(storage.root_events().len(), 1);
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn analyzing_captured_data_in_parallel() {
+    use rayon::prelude::*;
+
+    let storage = SharedStorage::default();
+    let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    tracing::subscriber::with_default(subscriber, || {
+        for i in 0_u64..100 {
+            tracing::info_span!("task", i).in_scope(|| {
+                for j in 0_u64..10 {
+                    tracing::debug!(i, j, "step");
+                }
+            });
+        }
+    });
+
+    let storage = storage.lock();
+    let span_count = storage
+        .all_spans()
+        .into_par_iter()
+        .filter(|span| name(eq("task")).eval(span))
+        .count();
+    assert_eq!(span_count, 100);
+
+    let sum: u128 = storage
+        .all_events()
+        .into_par_iter()
+        .map(|event| event["j"].as_uint().unwrap())
+        .sum();
+    assert_eq!(sum, 100 * 45);
+
+    let spans_with_all_events = storage
+        .all_spans()
+        .into_par_iter()
+        .all(|span| span.events().into_par_iter().count() == 10);
+    assert!(spans_with_all_events);
+}
+
+#[test]
+fn capturing_only_spans_or_only_events() {
+    let emit = || {
+        let outer = tracing::info_span!("outer", id = 1_u64);
+        outer.in_scope(|| {
+            tracing::info_span!("inner").in_scope(|| {
+                tracing::info!("inner event");
+            });
+            tracing::warn!("outer event");
+        });
+        outer.record("id", 2_u64);
+    };
+
+    let storage = SharedStorage::default();
+    let layer = CaptureLayer::new(&storage)
+        .capture_events(false)
+        .with_missing_ancestors();
+    tracing::subscriber::with_default(Registry::default().with(layer), emit);
+    let storage = storage.lock();
+    assert_eq!(storage.all_events().len(), 0);
+    let outer = storage.scan_spans().single(&name(eq("outer")));
+    assert_eq!(outer["id"], 2_u64);
+    assert_eq!(outer.stats().entered, 1);
+    assert!(outer.stats().is_closed);
+    let inner = outer.children().next().unwrap();
+    assert_eq!(inner.metadata().name(), "inner");
+    assert_eq!(inner.events().len(), 0);
+
+    let storage = SharedStorage::default();
+    let layer = CaptureLayer::new(&storage)
+        .capture_spans(false)
+        .with_missing_ancestors();
+    tracing::subscriber::with_default(Registry::default().with(layer), emit);
+    let storage = storage.lock();
+    assert_eq!(storage.all_spans().len(), 0);
+    let messages: Vec<_> = storage
+        .root_events()
+        .map(|event| event.message().unwrap())
+        .collect();
+    assert_eq!(messages, ["inner event", "outer event"]);
+}
+
+#[test]
+fn memory_usage_for_error_values() {
+    use std::{error::Error, fmt, io};
+
+    #[derive(Debug)]
+    struct WrapperError(io::Error);
+
+    impl fmt::Display for WrapperError {
+        fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+            formatter.write_str("wrapper")
+        }
+    }
+
+    impl Error for WrapperError {
+        fn source(&self) -> Option<&(dyn Error + 'static)> {
+            Some(&self.0)
+        }
+    }
+
+    let storage = SharedStorage::default();
+    let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    tracing::subscriber::with_default(subscriber, || {
+        let err = WrapperError(io::Error::new(io::ErrorKind::Other, "source"));
+        tracing::warn!(err = &err as &(dyn Error + 'static));
+    });
+
+    let usage = storage.lock().memory_usage();
+    assert_eq!(usage.values, 1);
+    assert_eq!(usage.string_bytes, "wrapper".len() + "source".len());
+}
+
+#[test]
+fn interning_string_values() {
+    /// Checks whether two values share the same string data.
+    fn is_shared(lhs: Option<&TracedValue>, rhs: Option<&TracedValue>) -> bool {
+        let lhs = lhs.and_then(TracedValue::as_str).unwrap();
+        let rhs = rhs.and_then(TracedValue::as_str).unwrap();
+        assert_eq!(lhs, rhs);
+        lhs.as_ptr() == rhs.as_ptr()
+    }
+
+    let storage = SharedStorage::default();
+    let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::info_span!("request", status = "ok", user = tracing::field::Empty);
+        span.record("user", "alice");
+        span.in_scope(|| {
+            tracing::info!(status = "ok", user = "alice");
+            tracing::info!(status = "ok", user = "bob");
+        });
+    });
+
+    let snapshot = storage.snapshot();
+    let span = snapshot.all_spans().next().unwrap();
+    for event in span.events() {
+        assert!(is_shared(span.value("status"), event.value("status")));
+    }
+    let first_event = span.events().next().unwrap();
+    assert!(is_shared(span.value("user"), first_event.value("user")));
+
+    let usage = snapshot.memory_usage();
+    assert_eq!(usage.values, 6);
+    assert_eq!(usage.interned_strings, 3);
+    assert_eq!(usage.string_bytes, "ok".len() + "alice".len() + "bob".len());
+
+    // Snapshots share interned strings with the original storage.
+    {
+        let storage = storage.lock();
+        let original_span = storage.all_spans().next().unwrap();
+        assert!(is_shared(
+            span.value("status"),
+            original_span.value("status")
+        ));
+    }
+    storage.clear();
+    assert_eq!(storage.lock().memory_usage().interned_strings, 0);
+}
+
+#[test]
+fn scanning_spans_and_events_together() {
+    let storage = SharedStorage::default();
+    let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info_span!("request", id = 42_u64).in_scope(|| {
+            tracing::warn!(id = 42_u64, "request failed");
+        });
+        tracing::info!(id = 1_u64, "unrelated");
+    });
+
+    let storage = storage.lock();
+    let id_predicate = field("id", 42_u64);
+    let items = storage
+        .scan_all()
+        .subsequence(&[&id_predicate, &id_predicate]);
+    assert_matches!(items[0], CapturedItem::Span(span) if span.metadata().name() == "request");
+    assert_eq!(items[1].expect_event().message(), Some("request failed"));
+    assert_eq!(items[0], items[1].expect_event().parent().unwrap().into());
+
+    let span = storage.scan_all().single(&if_span(name(eq("request"))));
+    assert_eq!(span.expect_span()["id"], 42_u64);
+    let event = storage.scan_all().single(&if_event(level(Level::WARN)));
+    assert!(event.as_span().is_none());
+    storage
+        .scan_all()
+        .none(&(if_span(level(Level::WARN)) | if_event(name(eq("request")))));
+}
+
+#[test]
+fn scanning_all_items_with_placeholder_spans() {
+    let storage = SharedStorage::default();
+    let layer = CaptureLayer::new(&storage)
+        .with_filter(LevelFilter::INFO)
+        .with_missing_ancestors();
+    let subscriber = Registry::default().with(layer);
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info!("started");
+        tracing::debug_span!("wrapper").in_scope(|| {
+            tracing::info_span!("request").in_scope(|| tracing::info!("processing"));
+        });
+        tracing::info!("finished");
+    });
+
+    let storage = storage.lock();
+    let wrapper = storage.scan_all().single(&if_span(name(eq("wrapper"))));
+    assert!(wrapper.expect_span().is_placeholder());
+
+    let started = if_event(message(eq("started")));
+    let wrapper = if_span(name(eq("wrapper")));
+    let request = if_span(name(eq("request")));
+    let processing = if_event(message(eq("processing")));
+    let finished = if_event(message(eq("finished")));
+    let items =
+        storage
+            .scan_all()
+            .subsequence(&[&started, &wrapper, &request, &processing, &finished]);
+    assert_eq!(items.len(), 5);
+}
+
+#[test]
+fn exporting_events_as_otlp_logs() {
+    let storage = SharedStorage::default();
+    let layer = CaptureLayer::new(&storage).with_extension_extractor(|_, values| {
+        values.insert("trace_id", 0xc0ffee_u64.into());
+        values.insert("span_id", "00000000deadbeef".into());
+    });
+    let subscriber = Registry::default().with(layer);
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::debug!(flag = true, "orphan");
+        let _entered = tracing::info_span!("request").entered();
+        tracing::error!(code = 500_u64, reason = "oops", "failed");
+    });
+
+    let logs = storage.lock().to_otlp_logs();
+    assert!(logs.contains(r#""scope":{"name":"tracing-capture","#));
+    let records: Vec<_> = logs.split(r#"{"timeUnixNano":"#).skip(1).collect();
+    assert_eq!(records.len(), 2);
+
+    assert!(records[0].contains(r#""severityNumber":5,"severityText":"DEBUG""#));
+    assert!(records[0].contains(r#"{"key":"flag","value":{"boolValue":true}}"#));
+    assert!(!records[0].contains("traceId"));
+
+    assert!(records[1].contains(r#""severityNumber":17,"severityText":"ERROR""#));
+    assert!(records[1].contains(r#""body":{"stringValue":"failed"}"#));
+    assert!(records[1].contains(r#"{"key":"code","value":{"intValue":"500"}}"#));
+    assert!(records[1].contains(r#"{"key":"reason","value":{"stringValue":"oops"}}"#));
+    assert!(records[1].contains(r#""traceId":"00000000000000000000000000c0ffee""#));
+    assert!(records[1].contains(r#""spanId":"00000000deadbeef""#));
+    assert!(logs.ends_with("]}]}]}"));
+}
+
+#[test]
+fn changing_captured_level_at_runtime() {
+    use tracing_subscriber::Layer;
+
+    let storage = SharedStorage::default();
+    let layer = CaptureLayer::new(&storage).with_max_level(LevelFilter::WARN);
+    let handle = layer.handle();
+    assert_eq!(handle.level(), LevelFilter::WARN);
+    assert_eq!(Layer::<Registry>::max_level_hint(&layer), None);
+
+    let subscriber = Registry::default().with(layer);
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info_span!("setup").in_scope(|| tracing::warn!("starting"));
+        handle.set_level(Level::DEBUG);
+        tracing::info_span!("failover").in_scope(|| {
+            tracing::debug!("injecting failure");
+            tracing::trace!("not captured");
+        });
+        handle.set_level(LevelFilter::TRACE);
+        tracing::trace!("everything is captured");
+    });
+
+    let storage = storage.lock();
+    let span_names: Vec<_> = storage
+        .all_spans()
+        .map(|span| span.metadata().name())
+        .collect();
+    assert_eq!(span_names, ["failover"]);
+    let messages: Vec<_> = storage
+        .all_events()
+        .filter_map(|event| event.message())
+        .collect();
+    assert_eq!(
+        messages,
+        ["starting", "injecting failure", "everything is captured"]
+    );
+    let failover = storage.all_spans().next().unwrap();
+    assert_eq!(failover.events().len(), 1);
+}
+
+#[test]
+fn captured_level_does_not_affect_other_layers() {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+    use tracing_core::{Event, Subscriber};
+    use tracing_subscriber::{layer::Context, Layer};
+
+    struct CountingLayer(Arc<AtomicUsize>);
+
+    impl<S: Subscriber> Layer<S> for CountingLayer {
+        fn on_event(&self, _event: &Event<'_>, _ctx: Context<'_, S>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    let event_count = Arc::new(AtomicUsize::new(0));
+    let storage = SharedStorage::default();
+    let layer = CaptureLayer::new(&storage).with_max_level(Level::INFO);
+    let subscriber = Registry::default()
+        .with(layer)
+        .with(CountingLayer(Arc::clone(&event_count)));
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info!("captured");
+        tracing::debug!("not captured");
+    });
+
+    assert_eq!(event_count.load(Ordering::SeqCst), 2);
+    assert_eq!(storage.lock().all_events().len(), 1);
+}
+
+#[test]
+fn using_capture_handle_as_per_layer_filter() {
+    use tracing_core::Subscriber;
+    use tracing_subscriber::Layer;
+
+    let storage = SharedStorage::default();
+    let layer = CaptureLayer::new(&storage).with_max_level(Level::INFO);
+    let handle = layer.handle();
+    let subscriber = Registry::default().with(Layer::with_filter(layer, handle.clone()));
+    assert_eq!(subscriber.max_level_hint(), Some(LevelFilter::INFO));
+
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::debug!("not captured");
+        handle.set_level(Level::DEBUG);
+        tracing::debug!("captured");
+    });
+
+    let storage = storage.lock();
+    let messages: Vec<_> = storage
+        .all_events()
+        .filter_map(|event| event.message())
+        .collect();
+    assert_eq!(messages, ["captured"]);
+}
+
+#[test]
+fn comparing_span_structure() {
+    use tracing_capture::{predicates::similar_to, StructuralEqOptions};
+
+    let storage = SharedStorage::default();
+    let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    tracing::subscriber::with_default(subscriber, || {
+        for attempt in 0..3 {
+            tracing::info_span!("request", attempt).in_scope(|| {
+                tracing::debug_span!("connect").in_scope(|| tracing::debug!("connected"));
+                if attempt == 2 {
+                    tracing::debug_span!("fallback").in_scope(|| tracing::warn!("degraded"));
+                }
+                tracing::warn!(status = 503, "responded");
+            });
+        }
+    });
+
+    let storage = storage.lock();
+    let attempts: Vec<_> = storage.root_spans().collect();
+    let options = StructuralEqOptions::default().ignore_field("attempt");
+    assert!(attempts[0].structurally_eq(&attempts[1], &options));
+    assert!(!attempts[0].structurally_eq(&attempts[2], &options));
+    assert!(!attempts[0].structurally_eq(&attempts[1], &StructuralEqOptions::default()));
+    let shallow_options = options.clone().ignore_children();
+    assert!(attempts[0].structurally_eq(&attempts[2], &shallow_options));
+
+    let predicate = similar_to(&attempts[0]).with_options(options);
+    let similar_count = storage
+        .root_spans()
+        .filter(|span| predicate.eval(span))
+        .count();
+    assert_eq!(similar_count, 2);
+}
+
+#[test]
+fn capturing_with_mock_clock() {
+    use std::time::Duration;
+    use tracing_capture::MockClock;
+
+    let storage = SharedStorage::default();
+    let clock = MockClock::default();
+    let layer = CaptureLayer::new(&storage).with_clock(clock.clone());
+    let subscriber = Registry::default().with(layer);
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::info_span!("request");
+        clock.advance(Duration::from_millis(5));
+        span.in_scope(|| {
+            clock.advance(Duration::from_millis(10));
+            tracing::debug_span!("query").in_scope(|| {
+                clock.advance(Duration::from_millis(30));
+                tracing::info!("queried");
+            });
+        });
+        clock.advance(Duration::from_millis(1));
+    });
+    assert_eq!(clock.elapsed(), Duration::from_millis(46));
+
+    let storage = storage.lock();
+    let request = storage.scan_spans().single(&name(eq("request")));
+    let stats = request.stats();
+    assert_eq!(stats.busy_time, Duration::from_millis(40));
+    assert_eq!(stats.idle_time, Duration::from_millis(6));
+    assert_eq!(request.created_at(), storage.created_at());
+
+    let query = storage.scan_spans().single(&name(eq("query")));
+    assert_eq!(query.stats().busy_time, Duration::from_millis(30));
+    assert_eq!(query.stats().idle_time, Duration::ZERO);
+    let event = storage.all_events().next().unwrap();
+    let offset = event.timestamp() - storage.created_at();
+    assert_eq!(offset, Duration::from_millis(45));
+}
+
+#[test]
+fn filtering_events_by_level() {
+    let storage = SharedStorage::default();
+    let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::warn!("root warning");
+        tracing::info_span!("request").in_scope(|| {
+            tracing::debug!("received");
+            tracing::warn!(attempt = 1, "retrying");
+            tracing::debug_span!("query").in_scope(|| {
+                tracing::warn!("slow query");
+            });
+            tracing::warn!(attempt = 2, "retrying");
+        });
+    });
+
+    let storage = storage.lock();
+    let request = storage.scan_spans().single(&name(eq("request")));
+    // Only events directly attached to the span are returned.
+    let attempts: Vec<_> = request
+        .events_at(Level::WARN)
+        .map(|event| event["attempt"].as_int().unwrap())
+        .collect();
+    assert_eq!(attempts, [1, 2]);
+    assert_eq!(request.event_count_at(Level::DEBUG), 1);
+    assert_eq!(request.event_count_at(Level::ERROR), 0);
+
+    let warnings: Vec<_> = storage
+        .events_at(Level::WARN)
+        .filter_map(|event| event.message())
+        .collect();
+    assert_eq!(
+        warnings,
+        ["root warning", "retrying", "slow query", "retrying"]
+    );
+    assert_eq!(storage.event_count_at(Level::DEBUG), 1);
+}
+
+#[test]
+fn overriding_span_names_with_field() {
+    let storage = SharedStorage::default();
+    let layer = CaptureLayer::new(&storage).with_name_field("otel.name");
+    let subscriber = Registry::default().with(layer);
+    tracing::subscriber::with_default(subscriber, || {
+        for path in ["/users", "/orders"] {
+            let span = tracing::info_span!("request", otel.name = tracing::field::Empty);
+            span.record("otel.name", format!("GET {path}").as_str());
+            span.in_scope(|| tracing::info!("handled"));
+        }
+        tracing::info_span!("cleanup", otel.name = tracing::field::Empty).in_scope(|| {});
+        tracing::info_span!("static", otel.name = "background").in_scope(|| {});
+    });
+
+    let storage = storage.lock();
+    let span = storage.scan_spans().single(&name(eq("GET /orders")));
+    assert_eq!(span.name(), "GET /orders");
+    assert_eq!(span.metadata().name(), "request");
+    storage.scan_spans().none(&name(eq("request")));
+
+    let spans: Vec<_> = storage.query().spans().name("GET /users").collect();
+    assert_eq!(spans.len(), 1);
+    assert_eq!(spans[0]["otel.name"], "GET /users");
+    let events: Vec<_> = storage.query().events().children_of(spans[0]).collect();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].message(), Some("handled"));
+
+    // Spans without a recorded name field fall back to the static name.
+    let span = storage.scan_spans().single(&name(eq("cleanup")));
+    assert_eq!(span.name(), "cleanup");
+    let span = storage.scan_spans().single(&name(eq("background")));
+    assert_eq!(span.metadata().name(), "static");
+}
+
+#[test]
+fn aggregating_spans_by_overridden_names() {
+    let storage = SharedStorage::default();
+    let layer = CaptureLayer::new(&storage).with_name_field("otel.name");
+    let subscriber = Registry::default().with(layer);
+    tracing::subscriber::with_default(subscriber, || {
+        for path in ["/users", "/orders", "/users"] {
+            let span = tracing::info_span!("request", otel.name = tracing::field::Empty);
+            span.record("otel.name", format!("GET {path}").as_str());
+            span.in_scope(|| {});
+        }
+    });
+
+    let aggregates = storage.lock().aggregate(GroupBy::Name);
+    let names: Vec<_> = aggregates.keys().map(String::as_str).collect();
+    assert_eq!(names, ["GET /orders", "GET /users"]);
+    assert_eq!(aggregates["GET /users"].count, 2);
+    assert_eq!(aggregates["GET /orders"].closed_count, 1);
+}
+
+#[test]
+fn layers_with_different_name_fields_sharing_storage() {
+    let storage = SharedStorage::default();
+    let otel_layer = CaptureLayer::new(&storage).with_name_field("otel.name");
+    let otel_dispatch = Dispatch::new(Registry::default().with(otel_layer));
+    let custom_layer = CaptureLayer::new(&storage).with_name_field("custom.name");
+    let custom_dispatch = Dispatch::new(Registry::default().with(custom_layer));
+
+    for dispatch in [&otel_dispatch, &custom_dispatch, &otel_dispatch] {
+        tracing::dispatcher::with_default(dispatch, || {
+            tracing::info_span!("request", otel.name = "otel", custom.name = "custom")
+                .in_scope(|| {});
+        });
+    }
+
+    let storage = storage.lock();
+    let names: Vec<_> = storage.all_spans().map(|span| span.name()).collect();
+    assert_eq!(names, ["otel", "custom", "otel"]);
+}
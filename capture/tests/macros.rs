@@ -0,0 +1,63 @@
+//! Tests for the `#[tracing_capture::test]` attribute macro.
+
+use predicates::ord::eq;
+
+use std::num::ParseIntError;
+
+use tracing_capture::{
+    predicates::{level, message, name, ScanExt},
+    SharedStorage,
+};
+
+#[tracing_capture::test]
+fn injecting_storage_by_reference(storage: &SharedStorage) {
+    tracing::info_span!("compute", input = 42_i64).in_scope(|| {
+        tracing::warn!("computed");
+    });
+
+    let storage = storage.lock();
+    let span = storage.scan_spans().single(&name(eq("compute")));
+    assert_eq!(span["input"], 42_i64);
+    assert!(span.stats().is_closed);
+    let event = span.events().next().unwrap();
+    assert_eq!(event.message(), Some("computed"));
+}
+
+#[tracing_capture::test]
+fn injecting_owned_storage(storage: SharedStorage) {
+    tracing::info!(answer = 42_u64, "test");
+
+    let storage = storage.lock();
+    let event = storage.scan_events().single(&message(eq("test")));
+    assert_eq!(event["answer"], 42_u64);
+}
+
+#[tracing_capture::test]
+fn test_without_storage() {
+    tracing::info!("not inspected");
+}
+
+#[tracing_capture::test]
+fn test_returning_result(storage: &SharedStorage) -> Result<(), ParseIntError> {
+    let value: u64 = "42".parse()?;
+    tracing::info!(value, "parsed");
+    if value != 42 {
+        return Ok(()); // checks that early returns work
+    }
+
+    let storage = storage.lock();
+    assert_eq!(
+        storage.scan_events().single(&message(eq("parsed")))["value"],
+        42_u64
+    );
+    Ok(())
+}
+
+#[tracing_capture::test]
+#[should_panic(expected = "predicate")]
+fn test_with_should_panic(storage: &SharedStorage) {
+    tracing::info!("info");
+
+    let storage = storage.lock();
+    storage.scan_events().single(&level(tracing::Level::ERROR));
+}
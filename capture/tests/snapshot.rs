@@ -0,0 +1,71 @@
+//! Tests for deterministic storage snapshots.
+
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+use tracing_capture::{CaptureLayer, SharedStorage};
+use tracing_tunnel::TracedValue;
+
+fn capture_trace() -> SharedStorage {
+    let storage = SharedStorage::default();
+    let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::info_span!(
+            target: "app::http",
+            "request",
+            method = "GET",
+            id = 42,
+            status = tracing::field::Empty
+        );
+        span.in_scope(|| {
+            tracing::debug_span!(target: "app::db", "query", table = "users").in_scope(|| {
+                tracing::debug!(target: "app::db", rows = 3_u64, "query completed");
+            });
+            tracing::warn!(target: "app::http", latency = 1.5, "slow request");
+        });
+        span.record("status", 200);
+        drop(span);
+        tracing::info!(target: "app", ok = true, "shutting down");
+    });
+    storage
+}
+
+#[test]
+fn snapshot_of_captured_trace() {
+    let storage = capture_trace();
+    let snapshot = storage.lock().to_snapshot();
+    insta::assert_yaml_snapshot!("captured_trace", snapshot);
+}
+
+#[test]
+fn redacting_snapshot_fields() {
+    let storage = capture_trace();
+    let snapshot = storage.lock().to_snapshot().redact("id").redact("rows");
+    let span = &snapshot.spans[0];
+    assert_eq!(span.fields["id"], "[REDACTED]");
+    assert_eq!(span.fields["method"], "GET");
+    let event = &span.spans[0].events[0];
+    assert_eq!(event.fields["rows"], "[REDACTED]");
+    assert_eq!(snapshot.events[0].fields["ok"], true);
+}
+
+#[test]
+fn normalizing_snapshot_fields() {
+    let storage = capture_trace();
+    let snapshot = storage
+        .lock()
+        .to_snapshot()
+        .with_normalizer("latency", |_| TracedValue::from(0.0))
+        .with_normalizer("id", |value| {
+            TracedValue::from(if value.as_int().is_some() {
+                "<int>"
+            } else {
+                "<other>"
+            })
+        });
+    let span = &snapshot.spans[0];
+    assert_eq!(span.fields["id"], "<int>");
+    assert_eq!(span.events[0].fields["latency"], 0.0);
+    // Normalizers are not applied to other fields.
+    assert_eq!(span.fields["method"], "GET");
+    assert_eq!(span.spans[0].events[0].fields["rows"], 3_u64);
+}
@@ -0,0 +1,253 @@
+//! Tests for exporting captured traces to SQLite and loading them back.
+
+use rusqlite::Connection;
+use tracing_subscriber::{filter::filter_fn, layer::SubscriberExt, Registry};
+
+use std::{fs, path::PathBuf, time::Duration};
+
+use tracing_capture::{
+    CaptureLayer, CapturedOperation, MockClock, SharedStorage, Storage, StructuralEqOptions,
+    ValueOrigin,
+};
+use tracing_tunnel::TracedValue;
+
+fn capture_trace() -> SharedStorage {
+    let storage = SharedStorage::default();
+    let clock = MockClock::default();
+    let layer = CaptureLayer::new(&storage).with_clock(clock.clone());
+    let subscriber = Registry::default().with(layer);
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::info_span!("request", id = 42, status = tracing::field::Empty);
+        span.in_scope(|| {
+            clock.advance(Duration::from_millis(5));
+            let query = tracing::debug_span!("query", table = "users");
+            query.in_scope(|| {
+                clock.advance(Duration::from_millis(10));
+                tracing::debug!(rows = 3_u64, "query completed");
+            });
+            let retry = tracing::debug_span!("query", table = "users", retry = true);
+            retry.follows_from(&query);
+            retry.in_scope(|| tracing::warn!(latency = 1.5, "slow query"));
+        });
+        clock.advance(Duration::from_millis(20));
+        span.record("status", 200);
+        drop(span);
+        tracing::info!(big = u128::MAX, "shutting down");
+    });
+    storage
+}
+
+fn temp_path(name: &str) -> PathBuf {
+    let pid = std::process::id();
+    let path = std::env::temp_dir().join(format!("tracing-capture-{name}-{pid}.sqlite"));
+    fs::remove_file(&path).ok();
+    path
+}
+
+#[test]
+fn loading_exported_trace() {
+    let storage = capture_trace();
+    let storage = storage.lock();
+    let path = temp_path("roundtrip");
+    storage.export_sqlite(&path).unwrap();
+    let loaded = Storage::load_sqlite(&path).unwrap();
+    fs::remove_file(&path).ok();
+
+    let options = StructuralEqOptions::default();
+    let expected_roots: Vec<_> = storage.root_spans().collect();
+    let loaded_roots: Vec<_> = loaded.root_spans().collect();
+    assert_eq!(loaded_roots.len(), expected_roots.len());
+    for (loaded, expected) in loaded_roots.iter().zip(&expected_roots) {
+        assert!(loaded.structurally_eq(expected, &options));
+    }
+    assert_eq!(loaded.root_events().len(), 1);
+    let root_event = loaded.root_events().next().unwrap();
+    assert_eq!(root_event["big"], u128::MAX);
+
+    for (loaded, expected) in loaded.all_spans().zip(storage.all_spans()) {
+        let (loaded_stats, expected_stats) = (loaded.stats(), expected.stats());
+        assert_eq!(loaded_stats.busy_time, expected_stats.busy_time);
+        assert_eq!(loaded_stats.idle_time, expected_stats.idle_time);
+        assert_eq!(loaded_stats.entered, expected_stats.entered);
+        assert_eq!(loaded_stats.is_closed, expected_stats.is_closed);
+    }
+
+    let request = loaded.root_spans().next().unwrap();
+    assert_eq!(request.stats().busy_time, Duration::from_millis(15));
+    assert_eq!(request.stats().idle_time, Duration::from_millis(20));
+    assert_eq!(request.value_origin("status"), Some(ValueOrigin::Recorded));
+    assert_eq!(request.value_origin("id"), Some(ValueOrigin::New));
+    let retry = request.children().nth(1).unwrap();
+    assert_eq!(retry.follows_from().len(), 1);
+
+    let loaded_timeline: Vec<_> = loaded.timeline().map(|op| op.to_string()).collect();
+    let expected_timeline: Vec<_> = storage.timeline().map(|op| op.to_string()).collect();
+    assert_eq!(loaded_timeline, expected_timeline);
+}
+
+#[test]
+fn querying_exported_trace() {
+    let storage = capture_trace();
+    let path = temp_path("query");
+    storage.lock().export_sqlite(&path).unwrap();
+
+    let connection = Connection::open(&path).unwrap();
+    let span_count: i64 = connection
+        .query_row(
+            "SELECT COUNT(*) FROM spans WHERE name = 'query'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(span_count, 2);
+
+    let (message, latency): (String, f64) = connection
+        .query_row(
+            "SELECT e.message, v.value FROM events e \
+             JOIN field_values v ON v.event_id = e.id \
+             WHERE e.level = 'WARN' AND v.name = 'latency'",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .unwrap();
+    assert_eq!(message, "slow query");
+    assert!((latency - 1.5).abs() < f64::EPSILON);
+
+    let status: i64 = connection
+        .query_row(
+            "SELECT t.value FROM spans s JOIN timeline t ON t.span_id = s.id \
+             WHERE s.name = 'request' AND t.operation = 'value_recorded' AND t.field = 'status'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(status, 200);
+    let initial_value_count: i64 = connection
+        .query_row(
+            "SELECT COUNT(*) FROM field_values WHERE name = 'status'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(initial_value_count, 0);
+
+    let closed_at: i64 = connection
+        .query_row(
+            "SELECT closed_at FROM spans WHERE parent_id IS NULL",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(closed_at, 35_000_000);
+
+    drop(connection);
+    fs::remove_file(&path).ok();
+}
+
+#[test]
+fn loading_exported_trace_with_overwritten_values() {
+    let storage = SharedStorage::default();
+    let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::info_span!("retry", attempt = 1);
+        span.record("attempt", 2);
+        span.record("attempt", 3);
+    });
+
+    let storage = storage.lock();
+    let path = temp_path("overwritten");
+    storage.export_sqlite(&path).unwrap();
+    let loaded = Storage::load_sqlite(&path).unwrap();
+    fs::remove_file(&path).ok();
+
+    let span = loaded.all_spans().next().unwrap();
+    assert_eq!(span["attempt"], 3_i64);
+    assert_eq!(span.value_origin("attempt"), Some(ValueOrigin::Recorded));
+    let recorded_values: Vec<_> = loaded
+        .timeline()
+        .filter_map(|op| match op {
+            CapturedOperation::ValueRecorded { name, value, .. } => Some((name, value.clone())),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(
+        recorded_values,
+        [
+            ("attempt", TracedValue::from(2_i64)),
+            ("attempt", 3_i64.into())
+        ]
+    );
+
+    // Export the loaded storage once more to check that the initial value is retained.
+    let path = temp_path("overwritten-again");
+    loaded.export_sqlite(&path).unwrap();
+    let connection = Connection::open(&path).unwrap();
+    let initial_value: i64 = connection
+        .query_row(
+            "SELECT value FROM field_values WHERE name = 'attempt'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(initial_value, 1);
+    drop(connection);
+    fs::remove_file(&path).ok();
+}
+
+#[test]
+fn exporting_to_existing_database_fails() {
+    let storage = capture_trace();
+    let storage = storage.lock();
+    let path = temp_path("existing");
+    storage.export_sqlite(&path).unwrap();
+    let err = storage.export_sqlite(&path).unwrap_err().to_string();
+    assert!(err.starts_with("SQLite error"), "{err}");
+    fs::remove_file(&path).ok();
+}
+
+#[test]
+fn loading_exported_trace_with_placeholder_spans() {
+    let storage = SharedStorage::default();
+    let layer = CaptureLayer::new(&storage)
+        .with_filter(filter_fn(|meta| !meta.name().starts_with("filtered")))
+        .with_missing_ancestors();
+    let subscriber = Registry::default().with(layer);
+    tracing::subscriber::with_default(subscriber, || {
+        let _outer = tracing::info_span!("outer").entered();
+        let _filtered = tracing::info_span!("filtered", secret = 42).entered();
+        let _nested = tracing::info_span!("filtered_nested").entered();
+        tracing::info_span!("inner").in_scope(|| tracing::info!("event"));
+        tracing::info!("nested event");
+    });
+
+    let storage = storage.lock();
+    assert_eq!(
+        storage
+            .all_spans()
+            .filter(|span| span.is_placeholder())
+            .count(),
+        2
+    );
+    let path = temp_path("placeholders");
+    storage.export_sqlite(&path).unwrap();
+    let loaded = Storage::load_sqlite(&path).unwrap();
+    fs::remove_file(&path).ok();
+
+    let options = StructuralEqOptions::default();
+    let loaded_root = loaded.root_spans().next().unwrap();
+    let expected_root = storage.root_spans().next().unwrap();
+    assert!(loaded_root.structurally_eq(&expected_root, &options));
+
+    let loaded_spans: Vec<_> = loaded
+        .all_spans()
+        .map(|span| (span.metadata().name(), span.is_placeholder()))
+        .collect();
+    let expected_spans: Vec<_> = storage
+        .all_spans()
+        .map(|span| (span.metadata().name(), span.is_placeholder()))
+        .collect();
+    assert_eq!(loaded_spans, expected_spans);
+    let event = loaded.all_events().nth(1).unwrap();
+    assert_eq!(event.message(), Some("nested event"));
+    assert_eq!(event.parent().unwrap().metadata().name(), "filtered_nested");
+}
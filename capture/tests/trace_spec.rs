@@ -0,0 +1,164 @@
+//! Tests for declarative trace specs.
+
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+use tracing_capture::{spec::TraceSpec, CaptureLayer, SharedStorage};
+
+fn capture_trace() -> SharedStorage {
+    let storage = SharedStorage::default();
+    let subscriber = Registry::default().with(CaptureLayer::new(&storage));
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::info_span!(target: "app::http", "request", method = "GET", id = 42);
+        span.in_scope(|| {
+            tracing::debug_span!(target: "app::db", "query", table = "users").in_scope(|| {
+                tracing::debug!(target: "app::db", rows = 3_u64, "query completed");
+            });
+            tracing::warn!(target: "app::http", latency = 1.5, "slow request");
+        });
+        tracing::info!(target: "app", ok = true, "shutting down");
+    });
+    storage
+}
+
+const SPEC: &str = r#"
+spans:
+  - name: request
+    target: app::*
+    level: info
+    fields:
+      method: GET
+      id: 42.0
+    spans:
+      - name: query
+        level: DEBUG
+        fields:
+          table: us?rs
+        events:
+          - message: query *
+            fields:
+              rows: 3
+    events:
+      - level: warn
+        fields:
+          latency: 1.5
+events:
+  - message: shutting down
+    fields:
+      ok: true
+"#;
+
+#[test]
+fn matching_trace_against_spec() {
+    let storage = capture_trace();
+    let spec = TraceSpec::from_yaml(SPEC).unwrap();
+    spec.assert_matches(&storage.lock());
+
+    let empty_spec = TraceSpec::from_json("{}").unwrap();
+    empty_spec.assert_matches(&storage.lock());
+}
+
+#[test]
+fn matching_items_order() {
+    let storage = capture_trace();
+    let spec = TraceSpec::from_json(
+        r#"{
+            "spans": [{
+                "name": "request",
+                "events": [{ "message": "slow *" }],
+                "spans": [{ "name": "query" }]
+            }],
+            "events": [{ "message": "shutting down" }]
+        }"#,
+    )
+    .unwrap();
+    // Child spans and events are matched independently, so their relative order is irrelevant.
+    spec.assert_matches(&storage.lock());
+
+    let spec = TraceSpec::from_json(
+        r#"{ "spans": [{ "name": "request", "events": [{ "message": "slow *" }, { "level": "warn" }] }] }"#,
+    )
+    .unwrap();
+    let err = spec.check(&storage.lock()).unwrap_err().to_string();
+    assert!(
+        err.contains("events[1]: no matching event found among 0 candidate(s)"),
+        "{err}"
+    );
+}
+
+#[test]
+fn mismatch_report() {
+    let storage = capture_trace();
+    let spec = TraceSpec::from_yaml(
+        r#"
+spans:
+  - name: request
+    fields:
+      method: POST
+    spans:
+      - name: query
+        target: app::cache
+        events:
+          - message: "*failed"
+events:
+  - message: shutting down
+    fields:
+      code: null
+"#,
+    )
+    .unwrap();
+
+    let err = spec.check(&storage.lock()).unwrap_err().to_string();
+    let expected = r#"captured trace does not match spec:
+- spans[0]: no matching span found among 1 candidate(s); closest candidate (#0) differs:
+  - field `method`: expected "POST", got String("GET")
+  - spans[0]: no matching span found among 1 candidate(s); closest candidate (#0) differs:
+    - target: expected "app::cache", got "app::db"
+    - events[0]: no matching event found among 1 candidate(s); closest candidate (#0) differs:
+      - message: expected "*failed", got Some("query completed")
+- events[0]: no matching event found among 1 candidate(s); closest candidate (#0) differs:
+  - field `code` is missing"#;
+    assert_eq!(err, expected);
+}
+
+#[test]
+fn matching_overridden_span_names() {
+    let storage = SharedStorage::default();
+    let layer = CaptureLayer::new(&storage).with_name_field("otel.name");
+    let subscriber = Registry::default().with(layer);
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::info_span!("request", otel.name = tracing::field::Empty);
+        span.record("otel.name", "GET /users");
+        span.in_scope(|| tracing::info!("handled"));
+    });
+
+    let spec = TraceSpec::from_yaml(
+        r#"
+spans:
+  - name: GET /*
+    events:
+      - message: handled
+"#,
+    )
+    .unwrap();
+    spec.assert_matches(&storage.lock());
+
+    let spec = TraceSpec::from_json(r#"{ "spans": [{ "name": "request" }] }"#).unwrap();
+    let err = spec.check(&storage.lock()).unwrap_err().to_string();
+    assert!(
+        err.contains(r#"name: expected "request", got "GET /users""#),
+        "{err}"
+    );
+}
+
+#[test]
+fn invalid_specs() {
+    let err = TraceSpec::from_yaml("spans:\n  - target: app").unwrap_err();
+    assert!(err.to_string().contains("missing field `name`"), "{err}");
+
+    let err = TraceSpec::from_yaml("spans:\n  - name: test\n    nme: test").unwrap_err();
+    assert!(err.to_string().contains("unknown field `nme`"), "{err}");
+
+    let err = TraceSpec::from_json(r#"{ "events": [{ "level": "loud" }] }"#).unwrap_err();
+    let err = err.to_string();
+    assert!(err.starts_with("invalid JSON trace spec"), "{err}");
+}
@@ -0,0 +1,109 @@
+//! Umbrella crate for the tracing toolbox, re-exporting [`tracing-capture`] and [`tracing-tunnel`].
+//!
+//! Depending on this crate instead of the two halves separately ensures that their versions
+//! (and thus the shared types such as [`TracedValue`](tunnel::TracedValue)) always match.
+//! The most commonly used types are available from the [`prelude`].
+//!
+//! [`tracing-capture`]: https://docs.rs/tracing-capture/
+//! [`tracing-tunnel`]: https://docs.rs/tracing-tunnel/
+//!
+//! # Crate features
+//!
+//! ## `capture`
+//!
+//! *(On by default)*
+//!
+//! Re-exports `tracing-capture` as the [`capture`] module. Implies the `tunnel` and `std` features.
+//!
+//! ## `tunnel`
+//!
+//! *(On by default)*
+//!
+//! Re-exports `tracing-tunnel` as the [`tunnel`] module.
+//!
+//! ## `std`
+//!
+//! *(On by default)*
+//!
+//! Enables std-related functionality in `tracing-tunnel`. Similar to `tracing-tunnel`,
+//! this crate can be used in `no_std` environments if this feature and the `capture` feature
+//! are disabled.
+//!
+//! ## Forwarded features
+//!
+//! The following features are forwarded to the corresponding crate, enabling the crate
+//! if necessary:
+//!
+//! - `trace-spec`, `snapshot`, `golden`, `no-alloc-hot-path`, `sqlite`, `rayon`, `macros`
+//!   and `perf` are forwarded to `tracing-capture`. `capture-tunnel` is forwarded
+//!   to the `tunnel` feature of `tracing-capture`
+//! - `sender`, `receiver`, `file-store`, `wasm-web`, `compression`, `proptest`
+//!   and `arbitrary` are forwarded to `tracing-tunnel`
+//! - `json` is forwarded to both crates
+//!
+//! # Examples
+//!
+//! ```
+//! use tracing_subscriber::layer::SubscriberExt;
+//! use tracing_toolbox::prelude::*;
+//!
+//! let storage = SharedStorage::default();
+//! let subscriber = tracing_subscriber::registry().with(CaptureLayer::new(&storage));
+//! tracing::subscriber::with_default(subscriber, || {
+//!     tracing::info_span!("compute", arg = 5_i64).in_scope(|| {
+//!         tracing::info!(result = 42_i64, "computed");
+//!     });
+//! });
+//!
+//! let storage = storage.lock();
+//! let span = storage.scan_spans().single(&field("arg", 5_i64));
+//! assert_eq!(span["arg"], 5_i64);
+//! let event = storage.scan_events().single(&parent(field("arg", 5_i64)));
+//! let value: &TracedValue = &event["result"];
+//! assert_eq!(value.as_int(), Some(42));
+//! ```
+
+#![cfg_attr(not(feature = "std"), no_std)]
+// Documentation settings.
+#![cfg_attr(docsrs, feature(doc_cfg))]
+#![doc(html_root_url = "https://docs.rs/tracing-toolbox/0.1.0")]
+// Linter settings.
+#![warn(missing_debug_implementations, missing_docs, bare_trait_objects)]
+#![warn(clippy::all, clippy::pedantic)]
+#![allow(clippy::must_use_candidate, clippy::module_name_repetitions)]
+
+#[cfg(feature = "capture")]
+#[cfg_attr(docsrs, doc(cfg(feature = "capture")))]
+#[doc(inline)]
+pub use tracing_capture as capture;
+#[cfg(feature = "tunnel")]
+#[cfg_attr(docsrs, doc(cfg(feature = "tunnel")))]
+#[doc(inline)]
+pub use tracing_tunnel as tunnel;
+
+/// Commonly used types and functions.
+///
+/// The prelude is intended to be glob-imported: `use tracing_toolbox::prelude::*;`.
+/// It contains [`CaptureLayer`] and [`SharedStorage`] to capture tracing data,
+/// common [predicates](capture::predicates) for assertions on the captured data,
+/// and [`TracedValue`] together with the tunnel [sender](TracingEventSender) and
+/// [receiver](TracingEventReceiver) (if the corresponding features are enabled).
+pub mod prelude {
+    #[cfg(feature = "capture")]
+    pub use tracing_capture::{
+        predicates::{
+            ancestor, depth, field, fields_subset, into_fn, level, message, name, parent, target,
+            value, ScanExt,
+        },
+        CaptureLayer, Captured, CapturedEvent, CapturedSpan, SharedStorage, Storage,
+    };
+    #[cfg(feature = "sender")]
+    pub use tracing_tunnel::TracingEventSender;
+    #[cfg(feature = "tunnel")]
+    pub use tracing_tunnel::{TracedValue, TracedValues, TracingEvent};
+    #[cfg(feature = "receiver")]
+    pub use tracing_tunnel::{TracingEventReceiver, TracingEventReceiverBuilder};
+}
+
+#[cfg(doctest)]
+doc_comment::doctest!("../README.md");
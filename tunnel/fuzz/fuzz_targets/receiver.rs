@@ -0,0 +1,19 @@
+//! Fuzzes `TracingEventReceiver` with arbitrary (generally invalid) event sequences.
+//! The receiver must not panic on any input.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use tracing_core::Dispatch;
+use tracing_subscriber::Registry;
+use tracing_tunnel::{TracingEvent, TracingEventReceiver};
+
+fuzz_target!(|events: Vec<TracingEvent>| {
+    let dispatch = Dispatch::new(Registry::default());
+    let mut receiver = TracingEventReceiver::builder()
+        .with_dispatch(dispatch)
+        .build();
+    for event in events {
+        receiver.try_receive(event).ok();
+    }
+});
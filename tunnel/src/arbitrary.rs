@@ -0,0 +1,171 @@
+//! [`Arbitrary`] implementations for events and values, e.g. for fuzzing receivers with
+//! `cargo-fuzz`.
+//!
+//! Unlike `proptest` strategies, generated events are *not* guaranteed
+//! to be valid: they may reference undefined call sites or spans, have values not corresponding
+//! to call site fields, etc. This is intentional; a receiver must handle such events without
+//! panicking.
+
+use ::arbitrary::{Arbitrary, Result, Unstructured};
+
+use std::borrow::Cow;
+
+use crate::{
+    CallSiteData, CallSiteKind, DebugObject, SequencedEvent, TracedError, TracedValue,
+    TracedValues, TracingEvent, TracingLevel,
+};
+
+fn arbitrary_cow(u: &mut Unstructured<'_>) -> Result<Cow<'static, str>> {
+    String::arbitrary(u).map(Cow::Owned)
+}
+
+impl<'a> Arbitrary<'a> for TracingLevel {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        u.choose(&[
+            Self::Error,
+            Self::Warn,
+            Self::Info,
+            Self::Debug,
+            Self::Trace,
+        ])
+        .copied()
+    }
+}
+
+impl<'a> Arbitrary<'a> for CallSiteKind {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(if u.arbitrary()? {
+            Self::Span
+        } else {
+            Self::Event
+        })
+    }
+
+    fn size_hint(depth: usize) -> (usize, Option<usize>) {
+        bool::size_hint(depth)
+    }
+}
+
+impl<'a> Arbitrary<'a> for CallSiteData {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(Self {
+            kind: u.arbitrary()?,
+            name: arbitrary_cow(u)?,
+            target: arbitrary_cow(u)?,
+            level: u.arbitrary()?,
+            module_path: u.arbitrary::<Option<String>>()?.map(Cow::Owned),
+            file: u.arbitrary::<Option<String>>()?.map(Cow::Owned),
+            line: u.arbitrary()?,
+            fields: u
+                .arbitrary_iter::<String>()?
+                .map(|field| field.map(Cow::Owned))
+                .collect::<Result<_>>()?,
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for DebugObject {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        u.arbitrary().map(Self)
+    }
+
+    fn size_hint(depth: usize) -> (usize, Option<usize>) {
+        String::size_hint(depth)
+    }
+}
+
+impl<'a> Arbitrary<'a> for TracedError {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(Self {
+            message: u.arbitrary()?,
+            source: u.arbitrary()?,
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for TracedValue {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(match u.int_in_range(0_u8..=7)? {
+            0 => Self::Bool(u.arbitrary()?),
+            1 => Self::Int(u.arbitrary()?),
+            2 => Self::UInt(u.arbitrary()?),
+            3 => Self::Float(u.arbitrary()?),
+            4 => Self::String(u.arbitrary()?),
+            5 => Self::Object(u.arbitrary()?),
+            6 => Self::Bytes(u.arbitrary()?),
+            _ => Self::Error(u.arbitrary()?),
+        })
+    }
+}
+
+impl<'a, S> Arbitrary<'a> for TracedValues<S>
+where
+    S: AsRef<str> + Arbitrary<'a>,
+{
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        u.arbitrary_iter::<(S, TracedValue)>()?.collect()
+    }
+}
+
+impl<'a> Arbitrary<'a> for TracingEvent {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(match u.int_in_range(0_u8..=13)? {
+            0 => Self::NewCallSite {
+                id: u.arbitrary()?,
+                data: u.arbitrary()?,
+            },
+            1 => Self::NewSpan {
+                id: u.arbitrary()?,
+                parent_id: u.arbitrary()?,
+                is_root: u.arbitrary()?,
+                metadata_id: u.arbitrary()?,
+                values: u.arbitrary()?,
+                generation: u.arbitrary()?,
+            },
+            2 => Self::FollowsFrom {
+                id: u.arbitrary()?,
+                follows_from: u.arbitrary()?,
+            },
+            3 => Self::SpanEntered { id: u.arbitrary()? },
+            4 => Self::SpanExited { id: u.arbitrary()? },
+            5 => Self::SpanCloned { id: u.arbitrary()? },
+            6 => Self::SpanDropped { id: u.arbitrary()? },
+            7 => Self::ValuesRecorded {
+                id: u.arbitrary()?,
+                values: u.arbitrary()?,
+            },
+            8 => Self::NewEvent {
+                metadata_id: u.arbitrary()?,
+                parent: u.arbitrary()?,
+                is_root: u.arbitrary()?,
+                values: u.arbitrary()?,
+            },
+            9 => Self::SimpleEvent {
+                metadata_id: u.arbitrary()?,
+                message: u.arbitrary()?,
+            },
+            10 => Self::Heartbeat {
+                sequence: u.arbitrary()?,
+                dropped_events: u.arbitrary()?,
+            },
+            11 => Self::EventsDropped {
+                count: u.arbitrary()?,
+            },
+            12 => Self::Flush {
+                sequence: u.arbitrary()?,
+            },
+            _ => Self::Shutdown {
+                sequence: u.arbitrary()?,
+            },
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for SequencedEvent {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(Self {
+            sequence: u.arbitrary()?,
+            event: u.arbitrary()?,
+        })
+    }
+}
@@ -0,0 +1,47 @@
+//! Base64 serialization of byte values.
+
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use serde::{de::Error as DeError, Deserialize, Deserializer, Serializer};
+
+use crate::alloc::{String, Vec};
+
+pub(crate) fn encode(bytes: &[u8]) -> String {
+    STANDARD.encode(bytes)
+}
+
+pub(crate) fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&encode(bytes))
+}
+
+pub(crate) fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+    let s = String::deserialize(deserializer)?;
+    STANDARD.decode(s).map_err(D::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::TracedValue;
+
+    #[test]
+    fn base64_roundtrip() {
+        let samples: [(&[u8], &str); 5] = [
+            (b"", ""),
+            (b"f", "Zg=="),
+            (b"fo", "Zm8="),
+            (b"foo", "Zm9v"),
+            (&[0xfb, 0xff, 0x00, 0x01], "+/8AAQ=="),
+        ];
+        for (bytes, encoded) in samples {
+            let value = TracedValue::from(bytes);
+            let expected = format!(r#"{{"bytes":"{encoded}"}}"#);
+            assert_eq!(serde_json::to_string(&value).unwrap(), expected);
+            let restored: TracedValue = serde_json::from_str(&expected).unwrap();
+            assert_eq!(restored.as_bytes(), Some(bytes));
+        }
+
+        for invalid in ["Zg=", "Z===", "Zg!=", "Zg==Zg=="] {
+            let json = format!(r#"{{"bytes":"{invalid}"}}"#);
+            serde_json::from_str::<TracedValue>(&json).unwrap_err();
+        }
+    }
+}
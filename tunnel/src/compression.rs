@@ -0,0 +1,204 @@
+//! Optional compression of serialized tracing event payloads.
+
+use miniz_oxide::{deflate::compress_to_vec, inflate::decompress_to_vec_with_limit};
+
+use core::fmt;
+
+use crate::alloc::Vec;
+
+/// Tag of an uncompressed frame.
+const RAW_TAG: u8 = 0;
+/// Tag of a deflate-compressed frame. The tag is followed by the uncompressed payload length
+/// (4 bytes, little-endian) and the raw deflate stream.
+const DEFLATE_TAG: u8 = 1;
+
+/// Compressor of serialized [`TracingEvent`](crate::TracingEvent)s (e.g., JSON-encoded
+/// events or batches of events) sent over the tunnel.
+///
+/// Compressed payloads are framed: the first byte of a frame marks whether the payload
+/// is compressed. This allows leaving small payloads (for which compression is inefficient)
+/// uncompressed; see [`Self::with_min_len()`]. Frames should be decoded on the receiver side
+/// using [`decompress_payload()`].
+///
+/// The deflate algorithm is used for compression.
+///
+/// # Examples
+///
+/// ```
+/// # use tracing_tunnel::{decompress_payload, PayloadCompressor, TracingEvent};
+/// let events = vec![TracingEvent::SpanEntered { id: 1 }; 100];
+/// let payload = serde_json::to_vec(&events)?;
+///
+/// let compressor = PayloadCompressor::default();
+/// let frame = compressor.compress(&payload);
+/// assert!(frame.len() < payload.len() / 10);
+///
+/// // On the receiver side:
+/// let decompressed = decompress_payload(&frame)?;
+/// let restored: Vec<TracingEvent> = serde_json::from_slice(&decompressed)?;
+/// assert_eq!(restored.len(), 100);
+/// # Ok::<_, Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct PayloadCompressor {
+    level: u8,
+    min_len: usize,
+}
+
+impl Default for PayloadCompressor {
+    fn default() -> Self {
+        Self {
+            level: 6,
+            min_len: 256,
+        }
+    }
+}
+
+impl PayloadCompressor {
+    /// Creates a compressor with the default settings: compression level 6 and
+    /// the minimum compressed payload length of 256 bytes.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the compression level from 0 (no compression) to 10 (best compression).
+    /// Levels greater than 10 are treated as 10.
+    #[must_use]
+    pub fn with_level(mut self, level: u8) -> Self {
+        self.level = level.min(10);
+        self
+    }
+
+    /// Sets the minimum payload length to compress. Shorter payloads are framed uncompressed.
+    #[must_use]
+    pub fn with_min_len(mut self, min_len: usize) -> Self {
+        self.min_len = min_len;
+        self
+    }
+
+    /// Compresses the payload into a frame. If the payload is too short, or compression
+    /// does not decrease its size, the payload is framed uncompressed.
+    pub fn compress(&self, payload: &[u8]) -> Vec<u8> {
+        if payload.len() >= self.min_len {
+            if let Ok(len) = u32::try_from(payload.len()) {
+                let compressed = compress_to_vec(payload, self.level);
+                if compressed.len() + 4 < payload.len() {
+                    let mut frame = Vec::with_capacity(compressed.len() + 5);
+                    frame.push(DEFLATE_TAG);
+                    frame.extend_from_slice(&len.to_le_bytes());
+                    frame.extend_from_slice(&compressed);
+                    return frame;
+                }
+            }
+        }
+
+        let mut frame = Vec::with_capacity(payload.len() + 1);
+        frame.push(RAW_TAG);
+        frame.extend_from_slice(payload);
+        frame
+    }
+}
+
+/// Errors that can occur when decoding a frame produced by [`PayloadCompressor`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DecompressError {
+    /// The frame is empty or truncated.
+    Truncated,
+    /// The frame has an unknown tag, e.g. because it was produced by a newer version
+    /// of the crate.
+    UnknownTag(u8),
+    /// The compressed payload is malformed, or its length does not match
+    /// the length recorded in the frame.
+    Malformed,
+}
+
+impl fmt::Display for DecompressError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Truncated => formatter.write_str("payload frame is truncated"),
+            Self::UnknownTag(tag) => write!(formatter, "unknown payload frame tag: {tag}"),
+            Self::Malformed => formatter.write_str("compressed payload is malformed"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for DecompressError {}
+
+/// Decodes a frame produced by [`PayloadCompressor::compress()`], decompressing
+/// the payload if necessary.
+///
+/// # Errors
+///
+/// Returns an error if the frame is malformed.
+pub fn decompress_payload(frame: &[u8]) -> Result<Vec<u8>, DecompressError> {
+    let (&tag, data) = frame.split_first().ok_or(DecompressError::Truncated)?;
+    match tag {
+        RAW_TAG => Ok(data.to_vec()),
+        DEFLATE_TAG => {
+            if data.len() < 4 {
+                return Err(DecompressError::Truncated);
+            }
+            let (len, compressed) = data.split_at(4);
+            let len = u32::from_le_bytes([len[0], len[1], len[2], len[3]]) as usize;
+            // The recorded length is used as a limit to guard against decompression bombs.
+            let payload = decompress_to_vec_with_limit(compressed, len)
+                .map_err(|_| DecompressError::Malformed)?;
+            if payload.len() == len {
+                Ok(payload)
+            } else {
+                Err(DecompressError::Malformed)
+            }
+        }
+        _ => Err(DecompressError::UnknownTag(tag)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_payloads_are_not_compressed() {
+        let compressor = PayloadCompressor::new();
+        let frame = compressor.compress(b"short");
+        assert_eq!(frame, b"\0short");
+        assert_eq!(decompress_payload(&frame).unwrap(), b"short");
+    }
+
+    #[test]
+    fn compressing_payload() {
+        let payload = b"{\"span_entered\":{\"id\":1}}".repeat(50);
+        for level in [0, 1, 6, 10] {
+            let compressor = PayloadCompressor::new().with_level(level);
+            let frame = compressor.compress(&payload);
+            if level > 0 {
+                assert_eq!(frame[0], DEFLATE_TAG);
+                assert!(frame.len() < payload.len());
+            }
+            assert_eq!(decompress_payload(&frame).unwrap(), payload);
+        }
+    }
+
+    #[test]
+    fn decompression_errors() {
+        assert_eq!(decompress_payload(&[]), Err(DecompressError::Truncated));
+        assert_eq!(
+            decompress_payload(&[DEFLATE_TAG, 1]),
+            Err(DecompressError::Truncated)
+        );
+        assert_eq!(
+            decompress_payload(&[42, 1, 2]),
+            Err(DecompressError::UnknownTag(42))
+        );
+
+        let payload = b"test".repeat(100);
+        let mut frame = PayloadCompressor::new().compress(&payload);
+        // Understate the payload length.
+        frame[1] -= 1;
+        assert_eq!(decompress_payload(&frame), Err(DecompressError::Malformed));
+        frame[1] += 2;
+        assert_eq!(decompress_payload(&frame), Err(DecompressError::Malformed));
+    }
+}
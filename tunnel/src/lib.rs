@@ -71,7 +71,12 @@
 //!
 //! *(Off by default)*
 //!
-//! Provides [`TracingEventSender`].
+//! Provides [`TracingEventSender`]. If the `std` feature is enabled as well, also provides
+//! [`EventQueue`] for queueing events emitted by the sender.
+//!
+//! The sender does not require `std`, so it can be used on embedded or minimal WASI targets
+//! with just the `alloc` crate. In this case, span reference counts maintained by the sender
+//! are synchronized with a spin lock instead of `std::sync::Mutex`.
 //!
 //! ## `receiver`
 //!
@@ -79,7 +84,61 @@
 //!
 //! Provides [`TracingEventReceiver`] and related types.
 //!
+//! ## `file-store`
+//!
+//! *(Off by default; requires `receiver`)*
+//!
+//! Provides [`FileStore`], a file-backed implementation of [`MetadataStore`] and [`SpanStore`]
+//! storing changes in the receiver state as append-only JSON logs.
+//!
+//! ## `wasm-web`
+//!
+//! *(Off by default; requires `sender` and `std`)*
+//!
+//! Provides [`TracingEventSender`] constructors for browser environments
+//! on the `wasm32-unknown-unknown` target: [`TracingEventSender::js_callback()`]
+//! and [`TracingEventSender::post_message()`]. These constructors serialize each event to JSON
+//! and pass it to a JS function or post it to a `Worker`, respectively.
+//!
+//! ## `compression`
+//!
+//! *(Off by default)*
+//!
+//! Provides [`PayloadCompressor`] and [`decompress_payload()`] allowing to compress serialized
+//! events (e.g., batches of events) sent over the tunnel, which can significantly cut bandwidth
+//! for chatty senders. Compression uses the deflate algorithm implemented in pure Rust,
+//! so it is compatible with WASM and `no_std` environments.
+//!
+//! ## `proptest`
+//!
+//! *(Off by default; requires `std`)*
+//!
+//! Provides [`proptest`] strategies generating valid [`TracingEvent`] sequences
+//! in the [`strategies`] module, e.g. to fuzz [`TracingEventReceiver`] or wrappers around it.
+//! Note that the `proptest` dependency may have a higher MSRV than this crate.
+//!
+//! ## `json`
+//!
+//! *(Off by default; requires `std`)*
+//!
+//! Provides [`TracedValue::to_json()`] converting values to [`serde_json`] values,
+//! e.g. to compare them against `serde_json::json!` literals in tests. Also provides
+//! [`TracingEvent::json_schema()`] defining the JSON wire format of events for non-Rust
+//! consumers.
+//!
+//! ## `arbitrary`
+//!
+//! *(Off by default; requires `std`)*
+//!
+//! Implements [`Arbitrary`] for [`TracingEvent`], [`CallSiteData`], [`TracedValue`]
+//! and closely related types, e.g. to fuzz [`TracingEventReceiver`] with `cargo-fuzz`.
+//! Unlike [`proptest`] strategies, generated events are not guaranteed to be valid.
+//! Note that the `arbitrary` dependency may have a higher MSRV than this crate.
+//!
 //! [`tracing-core`]: https://docs.rs/tracing-core/0.1/tracing_core
+//! [`proptest`]: https://docs.rs/proptest/1/proptest
+//! [`Arbitrary`]: https://docs.rs/arbitrary/1/arbitrary/trait.Arbitrary.html
+//! [`serde_json`]: https://docs.rs/serde_json/1/serde_json
 //!
 //! # Examples
 //!
@@ -151,12 +210,23 @@
 #![warn(clippy::all, clippy::pedantic)]
 #![allow(clippy::must_use_candidate, clippy::module_name_repetitions)]
 
+#[cfg(feature = "arbitrary")]
+mod arbitrary;
+mod bytes;
+#[cfg(feature = "compression")]
+#[cfg_attr(docsrs, doc(cfg(feature = "compression")))]
+mod compression;
 #[cfg(feature = "receiver")]
 #[cfg_attr(docsrs, doc(cfg(feature = "receiver")))]
 mod receiver;
+#[cfg(feature = "json")]
+mod schema;
 #[cfg(feature = "sender")]
 #[cfg_attr(docsrs, doc(cfg(feature = "sender")))]
 mod sender;
+#[cfg(feature = "proptest")]
+#[cfg_attr(docsrs, doc(cfg(feature = "proptest")))]
+pub mod strategies;
 mod types;
 mod value;
 mod values;
@@ -176,18 +246,33 @@ mod alloc {
         string::String,
         vec::{self, Vec},
     };
+
+    #[cfg(feature = "sender")]
+    pub use alloc::boxed::Box;
 }
 
+#[cfg(feature = "compression")]
+pub use crate::compression::{decompress_payload, DecompressError, PayloadCompressor};
+#[cfg(feature = "file-store")]
+pub use crate::receiver::FileStore;
 #[cfg(feature = "receiver")]
 pub use crate::receiver::{
-    LocalSpans, PersistedMetadata, PersistedSpans, ReceiveError, TracingEventReceiver,
+    AliveSpan, DispatchMode, ErrorPolicy, EventInterceptor, FlushStatus, HeartbeatStatus,
+    LocalSpans, MetadataKeying, MetadataStore, PersistedMetadata, PersistedSpans, ReceiveError,
+    ReceiverCounters, ReceiverMetrics, SpanChanges, SpanStore, TracingEventReceiver,
+    TracingEventReceiverBuilder,
 };
+#[cfg(all(feature = "sender", feature = "std"))]
+pub use crate::sender::{DropPolicy, EventQueue};
 #[cfg(feature = "sender")]
-pub use crate::sender::TracingEventSender;
+pub use crate::sender::{LayeredSender, TargetRoutes, TracingEventSender, ValueLimits};
 #[cfg(feature = "std")]
 pub use crate::value::TracedError;
 pub use crate::{
-    types::{CallSiteData, CallSiteKind, MetadataId, RawSpanId, TracingEvent, TracingLevel},
+    types::{
+        CallSiteData, CallSiteKind, MetadataId, RawSpanId, SequencedEvent, TracingEvent,
+        TracingLevel,
+    },
     value::{DebugObject, FromTracedValue, TracedValue},
     values::{TracedValues, TracedValuesIter},
 };
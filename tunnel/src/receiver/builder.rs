@@ -0,0 +1,586 @@
+//! Builder for `TracingEventReceiver`.
+
+use tracing_core::{Dispatch, Metadata};
+
+use std::{collections::HashMap, mem, num::NonZeroU64, time::Duration};
+
+use super::{
+    interceptors::Interceptors, metrics::MetricsHook, store::ChangeTracker, CurrentExecution,
+    DispatchMode, Dispatches, ErrorPolicy, EventInterceptor, FlushHook, FlushStatus, Liveness,
+    LocalSpans, MetadataKeying, Parking, PersistedMetadata, PersistedSpans, ReceiverCounters,
+    ReceiverMetrics, Sequencing, TracingEventReceiver,
+};
+
+/// Builder for a [`TracingEventReceiver`] returned by [`TracingEventReceiver::builder()`].
+///
+/// The builder is the only way to configure a receiver. Configuring the receiver in one go
+/// is important for some options; for example, dispatches and static metadata must be set
+/// before the persisted call sites are registered. With the builder, call sites are registered
+/// only once, when the receiver is [built](Self::build()).
+///
+/// # Examples
+///
+/// ```
+/// # use std::num::NonZeroU64;
+/// # use tracing_core::Dispatch;
+/// # use tracing_tunnel::{LocalSpans, PersistedMetadata, PersistedSpans, TracingEventReceiver};
+/// let metadata = PersistedMetadata::default();
+/// let (spans, local_spans) = (PersistedSpans::default(), LocalSpans::default());
+/// let subscriber = tracing_subscriber::fmt().pretty().finish();
+/// let receiver = TracingEventReceiver::builder()
+///     .with_persisted_metadata(metadata)
+///     .with_persisted_spans(spans, local_spans)
+///     .with_dispatch(Dispatch::new(subscriber))
+///     .with_max_values(16)
+///     .with_idle_span_timeout(NonZeroU64::new(10_000).unwrap())
+///     .build();
+/// ```
+#[derive(Debug)]
+pub struct TracingEventReceiverBuilder {
+    metadata: PersistedMetadata,
+    spans: PersistedSpans,
+    local_spans: LocalSpans,
+    dispatches: Dispatches,
+    idle_span_timeout: Option<NonZeroU64>,
+    lazy_spans: bool,
+    reorder_capacity: usize,
+    reorder_timeout: Option<Duration>,
+    call_site_buffer: (usize, Duration),
+    metrics: MetricsHook,
+    flush_hook: FlushHook,
+    interceptors: Interceptors,
+    static_metadata: Vec<&'static Metadata<'static>>,
+    max_values: usize,
+    error_policy: ErrorPolicy,
+    metadata_keying: Option<MetadataKeying>,
+    dispatch_mode: DispatchMode,
+}
+
+impl Default for TracingEventReceiverBuilder {
+    fn default() -> Self {
+        Self {
+            metadata: PersistedMetadata::default(),
+            spans: PersistedSpans::default(),
+            local_spans: LocalSpans::default(),
+            dispatches: Dispatches::Default,
+            idle_span_timeout: None,
+            lazy_spans: false,
+            reorder_capacity: 0,
+            reorder_timeout: None,
+            call_site_buffer: (0, Duration::ZERO),
+            metrics: MetricsHook::default(),
+            flush_hook: FlushHook::default(),
+            interceptors: Interceptors::default(),
+            static_metadata: Vec::new(),
+            max_values: TracingEventReceiver::MAX_VALUES,
+            error_policy: ErrorPolicy::default(),
+            metadata_keying: None,
+            dispatch_mode: DispatchMode::default(),
+        }
+    }
+}
+
+impl TracingEventReceiverBuilder {
+    /// Sets persisted call site metadata to restore the receiver from.
+    /// See [`TracingEventReceiver::new()`] for details.
+    #[must_use]
+    pub fn with_persisted_metadata(mut self, metadata: PersistedMetadata) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    /// Sets persisted spans and local spans to restore the receiver from.
+    /// See [`TracingEventReceiver::new()`] for details.
+    #[must_use]
+    pub fn with_persisted_spans(mut self, spans: PersistedSpans, local_spans: LocalSpans) -> Self {
+        self.spans = spans;
+        self.local_spans = local_spans;
+        self
+    }
+
+    /// Binds the receiver to the specified dispatch. This is a shortcut
+    /// for [`Self::with_dispatches()`] with a single dispatch.
+    #[must_use]
+    pub fn with_dispatch(self, dispatch: Dispatch) -> Self {
+        self.with_dispatches([dispatch])
+    }
+
+    /// Binds the receiver to the specified dispatches, instead of the default dispatch
+    /// for the current thread. All received events will be relayed to each of the dispatches
+    /// (i.e., fanned out). Call sites known to the receiver are registered with
+    /// the dispatches when the receiver is [built](Self::build()).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_core::Dispatch;
+    /// # use tracing_tunnel::{TracingEvent, TracingEventReceiver};
+    /// let fmt_subscriber = tracing_subscriber::fmt().pretty().finish();
+    /// let other_subscriber = tracing_subscriber::fmt().compact().finish();
+    /// let mut receiver = TracingEventReceiver::builder()
+    ///     .with_dispatches([Dispatch::new(fmt_subscriber), Dispatch::new(other_subscriber)])
+    ///     .build();
+    /// let events: Vec<TracingEvent> = // ...
+    /// #   vec![];
+    /// for event in events {
+    ///     receiver.receive(event);
+    /// }
+    /// ```
+    #[must_use]
+    pub fn with_dispatches(mut self, dispatches: impl IntoIterator<Item = Dispatch>) -> Self {
+        self.dispatches = Dispatches::Explicit(dispatches.into_iter().collect());
+        self
+    }
+
+    /// Sets the mode of relaying span entries and exits. By default,
+    /// relayed spans are [entered](DispatchMode::Entered) on the thread calling the receiver.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_tunnel::{DispatchMode, TracingEventReceiver};
+    /// // The receiver can run on a shared executor thread without polluting its context.
+    /// let receiver = TracingEventReceiver::builder()
+    ///     .with_dispatch_mode(DispatchMode::Detached)
+    ///     .build();
+    /// ```
+    #[must_use]
+    pub fn with_dispatch_mode(mut self, mode: DispatchMode) -> Self {
+        self.dispatch_mode = mode;
+        self
+    }
+
+    /// Sets the maximum number of values in a received span or event. Spans and events
+    /// with more values are rejected with a [`ReceiveError::TooManyValues`] error. Also,
+    /// no more than `max_values` values (including ambient fields) are relayed
+    /// to the underlying [`Subscriber`].
+    ///
+    /// By default, and at most, 32 values are supported.
+    ///
+    /// [`ReceiveError::TooManyValues`]: crate::ReceiveError::TooManyValues
+    /// [`Subscriber`]: tracing_core::Subscriber
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_values` exceeds 32.
+    #[must_use]
+    pub fn with_max_values(mut self, max_values: usize) -> Self {
+        assert!(
+            max_values <= TracingEventReceiver::MAX_VALUES,
+            "at most {} values are supported",
+            TracingEventReceiver::MAX_VALUES
+        );
+        self.max_values = max_values;
+        self
+    }
+
+    /// Sets the idle timeout for spans measured in received (non-heartbeat) events. If a span
+    /// is not referenced by any event for `events` consecutive events, is not entered
+    /// and has no alive children, it is [forcefully dropped](TracingEventReceiver::force_drop()).
+    /// Creating or dropping a child span counts as activity for the parent.
+    ///
+    /// Idle spans are checked periodically rather than after each event, so a span may
+    /// be dropped after up to `events + events / 4` idle events.
+    ///
+    /// By default, spans are never dropped because of inactivity.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::num::NonZeroU64;
+    /// # use tracing_tunnel::TracingEventReceiver;
+    /// let timeout = NonZeroU64::new(10_000).unwrap();
+    /// let receiver = TracingEventReceiver::builder()
+    ///     .with_idle_span_timeout(timeout)
+    ///     .build();
+    /// ```
+    #[must_use]
+    pub fn with_idle_span_timeout(mut self, events: NonZeroU64) -> Self {
+        self.idle_span_timeout = Some(events);
+        self
+    }
+
+    /// Enables lazy creation of spans in the underlying [`Subscriber`]. By default, a span
+    /// is created in the subscriber as soon as it is received. In the lazy mode, a span
+    /// is only created when it is first entered, or when a descendant event
+    /// [enabled](Dispatch::enabled()) in one of the dispatches is received. Ancestors
+    /// of a created span are created as well, so that the span hierarchy in the subscriber
+    /// is preserved.
+    /// Events are only relayed to dispatches that enable them.
+    ///
+    /// This reduces overhead for verbose traced executions if the subscriber discards
+    /// most of the emitted spans and events (e.g., because of level filtering).
+    /// As a downside, spans that are never entered and have no enabled descendants
+    /// are not relayed to the subscriber at all, and [`TracingEvent::FollowsFrom`] relations
+    /// involving such spans are ignored.
+    ///
+    /// [`Subscriber`]: tracing_core::Subscriber
+    /// [`TracingEvent::FollowsFrom`]: crate::TracingEvent::FollowsFrom
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_core::{Dispatch, LevelFilter};
+    /// # use tracing_tunnel::{TracingEvent, TracingEventReceiver};
+    /// let subscriber = tracing_subscriber::fmt()
+    ///     .with_max_level(LevelFilter::WARN)
+    ///     .finish();
+    /// let mut receiver = TracingEventReceiver::builder()
+    ///     .with_dispatch(Dispatch::new(subscriber))
+    ///     .with_lazy_spans()
+    ///     .build();
+    /// let events: Vec<TracingEvent> = // ...
+    /// #   vec![];
+    /// for event in events {
+    ///     receiver.receive(event);
+    /// }
+    /// // Only spans with warnings / errors (and their ancestors) are created.
+    /// println!("{} spans forwarded", receiver.counters().forwarded_spans);
+    /// ```
+    #[must_use]
+    pub fn with_lazy_spans(mut self) -> Self {
+        self.lazy_spans = true;
+        self
+    }
+
+    /// Sets the capacity of the reorder buffer used by
+    /// [`TracingEventReceiver::try_receive_sequenced()`]. With a non-zero capacity, events
+    /// arriving ahead of the expected sequence number by at most `capacity` are buffered
+    /// until the missing events arrive, instead of being relayed immediately. By default,
+    /// the capacity is 0, i.e., events are not reordered.
+    ///
+    /// The receiver gives up waiting for missing events if a [flush](crate::TracingEvent::Flush)
+    /// or [shutdown](crate::TracingEvent::Shutdown) marker is buffered, or after
+    /// the [reorder timeout](Self::with_reorder_timeout()).
+    #[must_use]
+    pub fn with_reorder_buffer(mut self, capacity: usize) -> Self {
+        self.reorder_capacity = capacity;
+        self
+    }
+
+    /// Sets the maximum time to wait for missing events
+    /// if [reordering](Self::with_reorder_buffer()) is enabled. Once the oldest buffered event
+    /// is buffered for longer than `timeout`, the missing events are considered lost:
+    /// all buffered events are relayed in order, and a [`ReceiveError::OutOfOrder`] error
+    /// is returned. Since the timeout is only checked when receiving sequenced events,
+    /// a buffered [flush](crate::TracingEvent::Flush) marker should be used to bound waiting
+    /// at the end of the event stream.
+    ///
+    /// By default, the receiver waits for missing events indefinitely (or until the reorder
+    /// buffer overflows).
+    ///
+    /// [`ReceiveError::OutOfOrder`]: crate::ReceiveError::OutOfOrder
+    #[must_use]
+    pub fn with_reorder_timeout(mut self, timeout: Duration) -> Self {
+        self.reorder_timeout = Some(timeout);
+        self
+    }
+
+    /// Enables parking of events referencing unknown call sites, e.g., because the transport
+    /// delivered a [`NewSpan`] or [`NewEvent`] before the corresponding [`NewCallSite`].
+    /// By default, such events immediately result in a [`ReceiveError::UnknownMetadataId`] error.
+    ///
+    /// With parking enabled, an event referencing an unknown call site is parked until
+    /// the call site arrives. To preserve ordering, subsequent events (other than call sites
+    /// and reporting meta events, such as [heartbeats](crate::TracingEvent::Heartbeat))
+    /// are parked as well while there are parked events. In particular,
+    /// [flush](crate::TracingEvent::Flush) and [shutdown](crate::TracingEvent::Shutdown) markers
+    /// are handled only after the preceding parked events are relayed. Parked events are relayed once their call sites are known,
+    /// or regardless of that (potentially resulting in errors) if more than `capacity` events
+    /// are parked, or if the oldest parked event is parked for longer than `timeout`.
+    ///
+    /// Parked events are not persisted. The number of parked events and the deadline
+    /// for relaying them can be obtained via [`TracingEventReceiver::parked_event_count()`]
+    /// and [`TracingEventReceiver::parking_deadline()`]; parked events can be relayed forcefully
+    /// via [`TracingEventReceiver::flush_parked()`], e.g. before
+    /// [persisting](TracingEventReceiver::persist()) the receiver.
+    ///
+    /// [`NewSpan`]: crate::TracingEvent::NewSpan
+    /// [`NewEvent`]: crate::TracingEvent::NewEvent
+    /// [`NewCallSite`]: crate::TracingEvent::NewCallSite
+    /// [`ReceiveError::UnknownMetadataId`]: crate::ReceiveError::UnknownMetadataId
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::time::Duration;
+    /// # use tracing_tunnel::{TracingEvent, TracingEventReceiver, TracedValues};
+    /// let mut receiver = TracingEventReceiver::builder()
+    ///     .with_call_site_buffer(100, Duration::from_secs(5))
+    ///     .build();
+    /// let event = TracingEvent::NewEvent {
+    ///     metadata_id: 0,
+    ///     parent: None,
+    ///     is_root: false,
+    ///     values: TracedValues::new(),
+    /// };
+    /// receiver.try_receive(event)?; // the call site is unknown, so the event is parked
+    /// assert_eq!(receiver.parked_event_count(), 1);
+    /// assert!(receiver.parking_deadline().is_some());
+    /// // Once the call site arrives, the event is relayed.
+    /// # let data = tracing_tunnel::CallSiteData {
+    /// #     kind: tracing_tunnel::CallSiteKind::Event,
+    /// #     name: "test".into(),
+    /// #     target: "test".into(),
+    /// #     level: tracing_tunnel::TracingLevel::Info,
+    /// #     module_path: None,
+    /// #     file: None,
+    /// #     line: None,
+    /// #     fields: vec![],
+    /// # };
+    /// receiver.try_receive(TracingEvent::NewCallSite { id: 0, data })?;
+    /// assert_eq!(receiver.parked_event_count(), 0);
+    /// # Ok::<_, tracing_tunnel::ReceiveError>(())
+    /// ```
+    #[must_use]
+    pub fn with_call_site_buffer(mut self, capacity: usize, timeout: Duration) -> Self {
+        self.call_site_buffer = (capacity, timeout);
+        self
+    }
+
+    /// Sets the policy of handling errors in [`TracingEventReceiver::receive()`]. By default,
+    /// errors result in a panic. A lenient policy allows monitoring tunnel corruption
+    /// without switching all call sites to [`TracingEventReceiver::try_receive()`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_tunnel::{ErrorPolicy, ReceiveError, TracingEvent, TracingEventReceiver};
+    /// let mut receiver = TracingEventReceiver::builder()
+    ///     .with_error_policy(ErrorPolicy::Collect)
+    ///     .build();
+    /// receiver.receive(TracingEvent::SpanEntered { id: 42 }); // doesn't panic
+    /// let errors = receiver.take_errors();
+    /// assert!(matches!(errors.as_slice(), [ReceiveError::UnknownSpanId(42)]));
+    /// assert!(receiver.take_errors().is_empty());
+    /// ```
+    #[must_use]
+    pub fn with_error_policy(mut self, policy: ErrorPolicy) -> Self {
+        self.error_policy = policy;
+        self
+    }
+
+    /// Sets the keying of call sites in [`PersistedMetadata`] produced by
+    /// [`TracingEventReceiver::persist_metadata()`]. If not set, the keying of the metadata
+    /// [the receiver is restored from](Self::with_persisted_metadata()) is used.
+    ///
+    /// Setting [content-addressed](MetadataKeying::ContentHash) keying when restoring
+    /// the receiver from [numeric](MetadataKeying::Numeric) metadata migrates persisted
+    /// metadata and spans to content-addressed keying.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_tunnel::{MetadataKeying, PersistedMetadata, TracingEventReceiver};
+    /// let old_metadata = PersistedMetadata::default();
+    /// // ...restore `old_metadata` and spans
+    /// let receiver = TracingEventReceiver::builder()
+    ///     .with_persisted_metadata(old_metadata)
+    ///     .with_metadata_keying(MetadataKeying::ContentHash)
+    ///     .build();
+    /// // ...receive events
+    /// let metadata = receiver.persist_metadata();
+    /// assert_eq!(metadata.keying(), MetadataKeying::ContentHash);
+    /// ```
+    #[must_use]
+    pub fn with_metadata_keying(mut self, keying: MetadataKeying) -> Self {
+        self.metadata_keying = Some(keying);
+        self
+    }
+
+    /// Sets static [`Metadata`] registered in the host process that should be reused
+    /// for matching call sites instead of dynamically created metadata. A call site matches
+    /// metadata if they have the same kind (span or event), name, target, level, and fields
+    /// (in the same order); other properties (e.g., the source code location) are not compared.
+    ///
+    /// Reusing static metadata ensures that [`Subscriber`]s and layers relying on call site
+    /// identity (e.g., sampling or filtering by call site) treat relayed spans and events
+    /// in the same way as the ones produced by the host itself. Static metadata can be collected,
+    /// e.g., by a layer in [`Layer::register_callsite()`].
+    ///
+    /// Since static metadata cannot be extended,
+    /// [ambient fields](TracingEventReceiver::set_ambient_field()) are not attached to spans
+    /// and events with reused metadata (they are counted
+    /// as [dropped values](ReceiverCounters::dropped_values)).
+    ///
+    /// [`Subscriber`]: tracing_core::Subscriber
+    /// [`Layer::register_callsite()`]: https://docs.rs/tracing-subscriber/0.3/tracing_subscriber/layer/trait.Layer.html#method.register_callsite
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_core::Metadata;
+    /// # use tracing_tunnel::TracingEventReceiver;
+    /// let host_metadata: Vec<&'static Metadata<'static>> =
+    ///     // e.g., collected by a `Layer`...
+    /// #   vec![];
+    /// let receiver = TracingEventReceiver::builder()
+    ///     .with_static_metadata(host_metadata)
+    ///     .build();
+    /// ```
+    #[must_use]
+    pub fn with_static_metadata(
+        mut self,
+        metadata: impl IntoIterator<Item = &'static Metadata<'static>>,
+    ) -> Self {
+        self.static_metadata.extend(metadata);
+        self
+    }
+
+    /// Sets a metrics hook for the receiver. The hook is invoked after each received event
+    /// with the updated [counters](ReceiverCounters).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::sync::{atomic::{AtomicU64, Ordering}, Arc};
+    /// # use tracing_tunnel::{ReceiverCounters, TracingEvent, TracingEventReceiver};
+    /// let errors = Arc::new(AtomicU64::new(0));
+    /// let errors_gauge = Arc::clone(&errors);
+    /// let mut receiver = TracingEventReceiver::builder()
+    ///     .with_metrics(move |counters: &ReceiverCounters| {
+    ///         // In real code, counters can be exported to `metrics` / Prometheus etc.
+    ///         errors_gauge.store(counters.errors, Ordering::Relaxed);
+    ///     })
+    ///     .build();
+    /// receiver.try_receive(TracingEvent::SpanEntered { id: 1 }).unwrap_err();
+    /// assert_eq!(errors.load(Ordering::Relaxed), 1);
+    /// ```
+    #[must_use]
+    pub fn with_metrics(mut self, metrics: impl ReceiverMetrics + 'static) -> Self {
+        self.metrics = MetricsHook(Some(Box::new(metrics)));
+        self
+    }
+
+    /// Sets a hook invoked each time a [flush](crate::TracingEvent::Flush) or
+    /// [shutdown](crate::TracingEvent::Shutdown) marker is received.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::sync::{atomic::{AtomicBool, Ordering}, Arc};
+    /// # use tracing_tunnel::{FlushStatus, TracingEvent, TracingEventReceiver};
+    /// let is_complete = Arc::new(AtomicBool::new(false));
+    /// let is_complete_flag = Arc::clone(&is_complete);
+    /// let mut receiver = TracingEventReceiver::builder()
+    ///     .with_flush_hook(move |status: &FlushStatus| {
+    ///         if status.is_final {
+    ///             is_complete_flag.store(true, Ordering::Relaxed);
+    ///         }
+    ///     })
+    ///     .build();
+    /// receiver.receive(TracingEvent::Shutdown { sequence: 0 });
+    /// if is_complete.load(Ordering::Relaxed) {
+    ///     // Finalize the persisted receiver state.
+    ///     let (spans, _) = receiver.persist();
+    ///     assert!(spans.is_empty());
+    /// }
+    /// ```
+    #[must_use]
+    pub fn with_flush_hook(
+        mut self,
+        hook: impl FnMut(&FlushStatus) + Send + Sync + 'static,
+    ) -> Self {
+        self.flush_hook = FlushHook(Some(Box::new(hook)));
+        self
+    }
+
+    /// Adds an [interceptor](EventInterceptor) for received events. Interceptors are applied
+    /// in the order they were added to each event passed to [`TracingEventReceiver::try_receive()`]
+    /// (or released by [`TracingEventReceiver::try_receive_sequenced()`]) before the event
+    /// is relayed. If an interceptor discards the event, the following interceptors
+    /// are not invoked, and the event is counted as
+    /// [intercepted](ReceiverCounters::intercepted_events).
+    ///
+    /// Interceptors should be careful discarding call site and span lifecycle events;
+    /// this will result in errors for the following events referencing the call site / span,
+    /// or in span leakage in the underlying [`Subscriber`].
+    ///
+    /// [`Subscriber`]: tracing_core::Subscriber
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_tunnel::{TracedValue, TracingEvent, TracingEventReceiver};
+    /// let mut receiver = TracingEventReceiver::builder()
+    ///     // Redact passwords in events.
+    ///     .with_interceptor(|event: &mut TracingEvent| {
+    ///         if let TracingEvent::NewEvent { values, .. } = event {
+    ///             if values.get("password").is_some() {
+    ///                 values.insert("password".to_owned(), TracedValue::from("[redacted]"));
+    ///             }
+    ///         }
+    ///         true
+    ///     })
+    ///     // Discard heartbeats.
+    ///     .with_interceptor(|event: &mut TracingEvent| {
+    ///         !matches!(event, TracingEvent::Heartbeat { .. })
+    ///     })
+    ///     .build();
+    /// receiver.receive(TracingEvent::Heartbeat { sequence: 0, dropped_events: 0 });
+    /// assert_eq!(receiver.counters().intercepted_events, 1);
+    /// assert!(receiver.last_heartbeat().is_none());
+    /// ```
+    #[must_use]
+    pub fn with_interceptor(mut self, interceptor: impl EventInterceptor + 'static) -> Self {
+        self.interceptors.0.push(Box::new(interceptor));
+        self
+    }
+
+    /// Builds the receiver, registering persisted call sites with the configured dispatches.
+    pub fn build(self) -> TracingEventReceiver {
+        let mut receiver = TracingEventReceiver {
+            metadata: HashMap::new(),
+            spans: self.spans,
+            local_spans: self.local_spans,
+            current_execution: CurrentExecution::default(),
+            dispatches: self.dispatches,
+            liveness: Liveness::default(),
+            event_counter: 0,
+            idle_span_timeout: self.idle_span_timeout,
+            next_idle_check: 0,
+            lazy_spans: self.lazy_spans,
+            sequencing: Sequencing {
+                reorder_capacity: self.reorder_capacity,
+                reorder_timeout: self.reorder_timeout,
+                ..Sequencing::default()
+            },
+            parking: Parking {
+                capacity: self.call_site_buffer.0,
+                timeout: self.call_site_buffer.1,
+                ..Parking::default()
+            },
+            counters: ReceiverCounters::default(),
+            metrics: self.metrics,
+            flush_hook: self.flush_hook,
+            interceptors: self.interceptors,
+            generation: None,
+            static_metadata: self.static_metadata,
+            max_values: self.max_values,
+            error_policy: self.error_policy,
+            errors: Vec::new(),
+            metadata_keying: self.metadata_keying.unwrap_or(self.metadata.keying),
+            changes: ChangeTracker::default(),
+            root_parents: Vec::new(),
+            dispatch_mode: self.dispatch_mode,
+        };
+
+        match self.metadata.keying {
+            MetadataKeying::Numeric => {
+                receiver.spans.call_site_hashes.0.clear();
+                for (id, data) in self.metadata.inner {
+                    receiver.on_new_call_site(id, data);
+                }
+            }
+            MetadataKeying::ContentHash => {
+                let call_site_hashes = mem::take(&mut receiver.spans.call_site_hashes.0);
+                for (id, hash) in call_site_hashes {
+                    if let Some(data) = self.metadata.inner.get(&hash) {
+                        receiver.on_new_call_site(id, data.clone());
+                    }
+                }
+            }
+        }
+        receiver
+    }
+}
@@ -0,0 +1,68 @@
+//! Handling of errors in `TracingEventReceiver::receive()`.
+
+use tracing_core::{
+    callsite::DefaultCallsite,
+    dispatcher,
+    field::{FieldSet, Value},
+    Event, Kind, Level, Metadata,
+};
+
+use super::ReceiveError;
+
+/// Policy of handling errors in [`TracingEventReceiver::receive()`]. Set via
+/// [`TracingEventReceiverBuilder::with_error_policy()`].
+///
+/// The policy does not influence [`TracingEventReceiver::try_receive()`], which always returns
+/// errors to the caller. Regardless of the policy, errors are counted
+/// in [`ReceiverCounters::errors`](crate::ReceiverCounters::errors).
+///
+/// [`TracingEventReceiver::receive()`]: crate::TracingEventReceiver::receive()
+/// [`TracingEventReceiverBuilder::with_error_policy()`]: crate::TracingEventReceiverBuilder::with_error_policy()
+/// [`TracingEventReceiver::try_receive()`]: crate::TracingEventReceiver::try_receive()
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorPolicy {
+    /// Silently ignore errors.
+    Ignore,
+    /// Log errors as warnings to the default dispatcher of the host (i.e., the one active
+    /// for the current thread), with the `tracing_tunnel::receiver` target.
+    LogToHost,
+    /// Panic on errors. This is the default.
+    #[default]
+    Panic,
+    /// Collect errors; they can be retrieved using
+    /// [`TracingEventReceiver::take_errors()`](crate::TracingEventReceiver::take_errors()).
+    Collect,
+}
+
+static ERROR_CALL_SITE: DefaultCallsite = DefaultCallsite::new(&ERROR_METADATA);
+static ERROR_METADATA: Metadata<'static> = Metadata::new(
+    "receive error",
+    "tracing_tunnel::receiver",
+    Level::WARN,
+    Some(file!()),
+    Some(line!()),
+    Some(module_path!()),
+    FieldSet::new(
+        &["message"],
+        tracing_core::identify_callsite!(&ERROR_CALL_SITE),
+    ),
+    Kind::EVENT,
+);
+
+/// Logs a receive error to the default dispatcher for the current thread.
+pub(super) fn log_to_host(err: &ReceiveError) {
+    if ERROR_CALL_SITE.register().is_never() {
+        return;
+    }
+    dispatcher::get_default(|dispatch| {
+        if !dispatch.enabled(&ERROR_METADATA) {
+            return;
+        }
+        let fields = ERROR_METADATA.fields();
+        let message_field = fields.field("message").unwrap();
+        let message = format_args!("received bogus tracing event: {err}");
+        let values = [(&message_field, Some(&message as &dyn Value))];
+        dispatch.event(&Event::new(&ERROR_METADATA, &fields.value_set(&values)));
+    });
+}
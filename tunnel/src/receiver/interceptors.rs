@@ -0,0 +1,52 @@
+//! Event interceptors for `TracingEventReceiver`.
+
+use std::fmt;
+
+use crate::TracingEvent;
+
+/// Interceptor of events received by a [`TracingEventReceiver`] set via
+/// [`TracingEventReceiverBuilder::with_interceptor()`]. Interceptors can inspect, modify
+/// or discard events before they are relayed to the tracing infrastructure, e.g., to redact
+/// sensitive values or to filter out noisy events.
+///
+/// This trait is implemented for closures accepting a mutable [`TracingEvent`] reference
+/// and returning a `bool`.
+///
+/// [`TracingEventReceiver`]: crate::TracingEventReceiver
+/// [`TracingEventReceiverBuilder::with_interceptor()`]: crate::TracingEventReceiverBuilder::with_interceptor()
+pub trait EventInterceptor: Send + Sync {
+    /// Called for each received event before it is relayed. Returns `false` if the event
+    /// should be discarded.
+    fn intercept(&mut self, event: &mut TracingEvent) -> bool;
+}
+
+impl<F> EventInterceptor for F
+where
+    F: FnMut(&mut TracingEvent) -> bool + Send + Sync,
+{
+    fn intercept(&mut self, event: &mut TracingEvent) -> bool {
+        self(event)
+    }
+}
+
+/// Chain of [`EventInterceptor`]s applied in the order they were added.
+#[derive(Default)]
+pub(super) struct Interceptors(pub(super) Vec<Box<dyn EventInterceptor>>);
+
+impl fmt::Debug for Interceptors {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_struct("Interceptors")
+            .field("len", &self.0.len())
+            .finish()
+    }
+}
+
+impl Interceptors {
+    /// Applies interceptors to the event. Returns `false` if the event should be discarded.
+    pub(super) fn apply(&mut self, event: &mut TracingEvent) -> bool {
+        self.0
+            .iter_mut()
+            .all(|interceptor| interceptor.intercept(event))
+    }
+}
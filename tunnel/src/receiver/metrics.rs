@@ -0,0 +1,139 @@
+//! Metrics hooks for `TracingEventReceiver`.
+
+use std::fmt;
+
+use crate::TracingEvent;
+
+/// Counters maintained by a [`TracingEventReceiver`]. Returned by
+/// [`TracingEventReceiver::counters()`] and passed to [`ReceiverMetrics`] hooks.
+///
+/// Counters are not persisted; they are reset each time a receiver is created.
+///
+/// [`TracingEventReceiver`]: crate::TracingEventReceiver
+/// [`TracingEventReceiver::counters()`]: crate::TracingEventReceiver::counters()
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ReceiverCounters {
+    /// Number of received [`NewCallSite`](TracingEvent::NewCallSite) events.
+    pub new_call_sites: u64,
+    /// Number of received [`NewSpan`](TracingEvent::NewSpan) events.
+    pub new_spans: u64,
+    /// Number of received [`FollowsFrom`](TracingEvent::FollowsFrom) events.
+    pub follows_from: u64,
+    /// Number of received [`SpanEntered`](TracingEvent::SpanEntered) events.
+    pub spans_entered: u64,
+    /// Number of received [`SpanExited`](TracingEvent::SpanExited) events.
+    pub spans_exited: u64,
+    /// Number of received [`SpanCloned`](TracingEvent::SpanCloned) events.
+    pub spans_cloned: u64,
+    /// Number of received [`SpanDropped`](TracingEvent::SpanDropped) events.
+    pub spans_dropped: u64,
+    /// Number of received [`ValuesRecorded`](TracingEvent::ValuesRecorded) events.
+    pub values_recorded: u64,
+    /// Number of received [`NewEvent`](TracingEvent::NewEvent)
+    /// and [`SimpleEvent`](TracingEvent::SimpleEvent) events.
+    pub new_events: u64,
+    /// Number of received [`Heartbeat`](TracingEvent::Heartbeat) events.
+    pub heartbeats: u64,
+    /// Number of received [`EventsDropped`](TracingEvent::EventsDropped) events.
+    pub events_dropped: u64,
+    /// Total number of events reported as dropped by the sender
+    /// via [`EventsDropped`](TracingEvent::EventsDropped) events.
+    pub reported_dropped_events: u64,
+    /// Number of received [`Flush`](TracingEvent::Flush) events.
+    pub flushes: u64,
+    /// Number of received [`Shutdown`](TracingEvent::Shutdown) events.
+    pub shutdowns: u64,
+    /// Number of spans forwarded to the tracing infrastructure. A span is counted once
+    /// regardless of the number of dispatches the receiver relays events to.
+    pub forwarded_spans: u64,
+    /// Number of events that resulted in a [`ReceiveError`](crate::ReceiveError).
+    pub errors: u64,
+    /// Number of values that were not relayed because they do not correspond to any field
+    /// in the call site metadata, or because a span / event has too many values
+    /// together with ambient fields.
+    pub dropped_values: u64,
+    /// Number of events parked because they (or preceding events) reference unknown call sites.
+    /// See [`TracingEventReceiverBuilder::with_call_site_buffer()`] for details.
+    ///
+    /// [`TracingEventReceiverBuilder::with_call_site_buffer()`]: crate::TracingEventReceiverBuilder::with_call_site_buffer()
+    pub parked_events: u64,
+    /// Number of events discarded by [interceptors](crate::EventInterceptor).
+    pub intercepted_events: u64,
+}
+
+impl ReceiverCounters {
+    pub(super) fn on_event(&mut self, event: &TracingEvent) {
+        let counter = match event {
+            TracingEvent::NewCallSite { .. } => &mut self.new_call_sites,
+            TracingEvent::NewSpan { .. } => &mut self.new_spans,
+            TracingEvent::FollowsFrom { .. } => &mut self.follows_from,
+            TracingEvent::SpanEntered { .. } => &mut self.spans_entered,
+            TracingEvent::SpanExited { .. } => &mut self.spans_exited,
+            TracingEvent::SpanCloned { .. } => &mut self.spans_cloned,
+            TracingEvent::SpanDropped { .. } => &mut self.spans_dropped,
+            TracingEvent::ValuesRecorded { .. } => &mut self.values_recorded,
+            TracingEvent::NewEvent { .. } | TracingEvent::SimpleEvent { .. } => {
+                &mut self.new_events
+            }
+            TracingEvent::Heartbeat { .. } => &mut self.heartbeats,
+            TracingEvent::EventsDropped { .. } => &mut self.events_dropped,
+            TracingEvent::Flush { .. } => &mut self.flushes,
+            TracingEvent::Shutdown { .. } => &mut self.shutdowns,
+        };
+        *counter += 1;
+    }
+
+    /// Returns the total number of received events.
+    pub fn total_events(&self) -> u64 {
+        self.new_call_sites
+            + self.new_spans
+            + self.follows_from
+            + self.spans_entered
+            + self.spans_exited
+            + self.spans_cloned
+            + self.spans_dropped
+            + self.values_recorded
+            + self.new_events
+            + self.heartbeats
+            + self.events_dropped
+            + self.flushes
+            + self.shutdowns
+    }
+}
+
+/// Metrics hook for a [`TracingEventReceiver`] set via
+/// [`TracingEventReceiverBuilder::with_metrics()`]. Can be used to bridge receiver
+/// [counters](ReceiverCounters) to a metrics library in order to monitor tunnel health.
+///
+/// This trait is implemented for closures accepting a [`ReceiverCounters`] reference.
+///
+/// [`TracingEventReceiver`]: crate::TracingEventReceiver
+/// [`TracingEventReceiverBuilder::with_metrics()`]: crate::TracingEventReceiverBuilder::with_metrics()
+pub trait ReceiverMetrics: Send + Sync {
+    /// Called after each received event is processed (successfully or not) with
+    /// the updated counters.
+    fn on_event_received(&mut self, counters: &ReceiverCounters);
+}
+
+impl<F> ReceiverMetrics for F
+where
+    F: FnMut(&ReceiverCounters) + Send + Sync,
+{
+    fn on_event_received(&mut self, counters: &ReceiverCounters) {
+        self(counters);
+    }
+}
+
+/// Wrapper around an optional [`ReceiverMetrics`] hook.
+#[derive(Default)]
+pub(super) struct MetricsHook(pub(super) Option<Box<dyn ReceiverMetrics>>);
+
+impl fmt::Debug for MetricsHook {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_tuple("MetricsHook")
+            .field(&self.0.as_ref().map(|_| "ReceiverMetrics"))
+            .finish()
+    }
+}
@@ -1,24 +1,47 @@
 //! `TracingEvent` receiver.
 
-use serde::{Deserialize, Serialize};
+use serde::{de::MapAccess, ser::SerializeMap, Deserialize, Deserializer, Serialize, Serializer};
 use tracing_core::{
     dispatcher::{self, Dispatch},
     field::{self, FieldSet, Value, ValueSet},
     span::{Attributes, Id, Record},
-    Event, Field, Metadata,
+    Event, Field, Level, Metadata,
 };
 
 use std::{
-    collections::{HashMap, HashSet},
+    borrow::Cow,
+    collections::{HashMap, HashSet, VecDeque},
     error, fmt, mem,
+    num::NonZeroU64,
+    ptr,
+    time::{Duration, Instant},
 };
 
 mod arena;
+mod builder;
+mod errors;
+mod interceptors;
+mod metrics;
+mod persistence;
+mod store;
 #[cfg(test)]
 mod tests;
 
-use self::arena::ARENA;
-use crate::{CallSiteData, MetadataId, RawSpanId, TracedValue, TracedValues, TracingEvent};
+#[cfg(feature = "file-store")]
+pub use self::store::FileStore;
+pub use self::{
+    builder::TracingEventReceiverBuilder,
+    errors::ErrorPolicy,
+    interceptors::EventInterceptor,
+    metrics::{ReceiverCounters, ReceiverMetrics},
+    store::{MetadataStore, SpanChanges, SpanStore},
+};
+
+use self::{arena::ARENA, interceptors::Interceptors, metrics::MetricsHook, store::ChangeTracker};
+use crate::{
+    types::is_false, CallSiteData, CallSiteKind, DebugObject, MetadataId, RawSpanId,
+    SequencedEvent, TracedValue, TracedValues, TracingEvent,
+};
 
 enum CowValue<'a> {
     Borrowed(&'a dyn Value),
@@ -52,6 +75,7 @@ impl TracedValue {
             Self::Float(value) => value,
             Self::String(value) => value,
             Self::Object(value) => return CowValue::Owned(Box::new(field::debug(value))),
+            Self::Bytes(bytes) => return CowValue::Owned(Box::new(bytes.as_slice())),
             Self::Error(err) => {
                 let err = err as &(dyn error::Error + 'static);
                 return CowValue::Owned(Box::new(err));
@@ -60,13 +84,31 @@ impl TracedValue {
     }
 }
 
+#[allow(clippy::trivially_copy_pass_by_ref)] // required by `serde`
+fn is_zero(value: &usize) -> bool {
+    *value == 0
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct SpanData {
     metadata_id: MetadataId,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     parent_id: Option<RawSpanId>,
+    /// Whether the span is an explicit root; see [`TracingEvent::NewSpan`].
+    #[serde(default, skip_serializing_if = "is_false")]
+    is_root: bool,
     ref_count: usize,
+    /// Number of alive child spans. Children keep their parent alive, so that the parent
+    /// can be referenced when (re)creating a child span in the local subscriber.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    child_count: usize,
     values: TracedValues<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    generation: Option<u32>,
+    /// Value of the receiver event counter when the span was last referenced by an event.
+    /// Not persisted since the counter is specific to a receiver instance.
+    #[serde(skip)]
+    last_activity: u64,
 }
 
 /// Information about span / event [`Metadata`] that is [serializable] and thus
@@ -77,14 +119,50 @@ struct SpanData {
 /// Multiple executions of the same executable can (and optimally should)
 /// share `PersistedMetadata`.
 ///
+/// # Keying
+///
+/// By default, call sites are keyed by [`MetadataId`]s. These IDs are assigned by the sender
+/// per process, so metadata persisted for an older version of an executable may clash
+/// with the IDs of a recompiled executable. To avoid this, metadata can be
+/// [content-addressed](MetadataKeying::ContentHash), i.e., keyed by
+/// [hashes of call site data](CallSiteData::content_hash()). See [`MetadataKeying`] for details.
+///
+/// # Serialization format
+///
+/// Metadata is serialized as a map with the `version` field equal to [`Self::FORMAT_VERSION`]
+/// and the `call_sites` field containing call site data keyed by [`MetadataId`] or content hash.
+/// For content-addressed metadata, the `keying` field is set to `content_hash`. Unknown fields
+/// are ignored on deserialization. The legacy format (a map of call sites without a version)
+/// is supported as well, so that metadata persisted by older crate versions can be restored.
+///
+/// Restoring legacy metadata and skipping unknown fields require a self-describing format
+/// (e.g., JSON). Non-self-describing formats (e.g., `bincode` or `postcard`) can only
+/// round-trip metadata serialized in the current format by the same crate version.
+///
 /// [serializable]: https://docs.rs/serde/1/serde
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
-#[serde(transparent)]
+#[derive(Debug, Clone, Default)]
 pub struct PersistedMetadata {
     inner: HashMap<MetadataId, CallSiteData>,
+    keying: MetadataKeying,
+}
+
+impl Serialize for PersistedMetadata {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        persistence::serialize(serializer, "call_sites", &self.inner, &self.keying)
+    }
+}
+
+impl<'de> Deserialize<'de> for PersistedMetadata {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (inner, keying) = persistence::deserialize(deserializer, "call_sites")?;
+        Ok(Self { inner, keying })
+    }
 }
 
 impl PersistedMetadata {
+    /// Latest version of the serialization format.
+    pub const FORMAT_VERSION: u32 = persistence::FORMAT_VERSION;
+
     /// Returns the number of metadata entries.
     pub fn len(&self) -> usize {
         self.inner.len()
@@ -95,18 +173,117 @@ impl PersistedMetadata {
         self.inner.is_empty()
     }
 
-    /// Iterates over contained call site metadata together with the corresponding
-    /// [`MetadataId`]s.
-    pub fn iter(&self) -> impl Iterator<Item = (MetadataId, &CallSiteData)> + '_ {
+    /// Creates empty metadata with the specified keying.
+    pub fn with_keying(keying: MetadataKeying) -> Self {
+        Self {
+            inner: HashMap::new(),
+            keying,
+        }
+    }
+
+    /// Returns the keying of call sites in this metadata.
+    pub fn keying(&self) -> MetadataKeying {
+        self.keying
+    }
+
+    /// Iterates over contained call site metadata together with the corresponding keys:
+    /// [`MetadataId`]s or content hashes, depending on the [keying](Self::keying()).
+    pub fn iter(&self) -> impl Iterator<Item = (u64, &CallSiteData)> + '_ {
         self.inner.iter().map(|(id, data)| (*id, data))
     }
 
-    /// Merges entries from another `PersistedMetadata` instance.
+    /// Converts this metadata to be [content-addressed](MetadataKeying::ContentHash).
+    /// This is a no-op if the metadata is already content-addressed.
+    ///
+    /// Call sites with equal data are deduplicated during the conversion. Since [`MetadataId`]s
+    /// are lost, converted metadata can only restore spans persisted by a receiver with
+    /// content-addressed metadata. To migrate a receiver together with its spans, restore it
+    /// from the numeric metadata using [`TracingEventReceiverBuilder::with_metadata_keying()`].
+    #[must_use]
+    pub fn into_content_addressed(self) -> Self {
+        match self.keying {
+            MetadataKeying::Numeric => Self {
+                inner: self
+                    .inner
+                    .into_values()
+                    .map(|data| (data.content_hash(), data))
+                    .collect(),
+                keying: MetadataKeying::ContentHash,
+            },
+            MetadataKeying::ContentHash => self,
+        }
+    }
+
+    /// Merges entries from another `PersistedMetadata` instance. If either of the instances
+    /// is [content-addressed](MetadataKeying::ContentHash), the other one is
+    /// [converted](Self::into_content_addressed()) before merging.
     pub fn extend(&mut self, other: Self) {
+        let other = if self.keying == other.keying {
+            other
+        } else {
+            *self = mem::take(self).into_content_addressed();
+            other.into_content_addressed()
+        };
         self.inner.extend(other.inner);
     }
 }
 
+/// Keying of call sites in [`PersistedMetadata`].
+///
+/// With [content-addressed](Self::ContentHash) keying, [`PersistedSpans`] additionally record
+/// the mapping from sender-assigned [`MetadataId`]s to call site hashes, so that spans can be
+/// restored even if the metadata is shared among multiple versions of an executable.
+///
+/// # Migration
+///
+/// A receiver restored from [numeric](Self::Numeric) metadata and configured to use content
+/// hashes via [`TracingEventReceiverBuilder::with_metadata_keying()`] will persist
+/// content-addressed metadata and spans. Standalone metadata can be converted with
+/// [`PersistedMetadata::into_content_addressed()`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[non_exhaustive]
+pub enum MetadataKeying {
+    /// Call sites are keyed by [`MetadataId`]s assigned by the sender. This is the default.
+    #[default]
+    Numeric,
+    /// Call sites are keyed by their [content hash](CallSiteData::content_hash()).
+    ContentHash,
+}
+
+impl MetadataKeying {
+    const FIELD_NAME: &'static str = "keying";
+}
+
+impl persistence::SerializeExtras for MetadataKeying {
+    fn field_count(&self) -> usize {
+        usize::from(*self != Self::Numeric)
+    }
+
+    fn serialize_fields<M: SerializeMap>(&self, map: &mut M) -> Result<(), M::Error> {
+        if *self == Self::Numeric {
+            Ok(())
+        } else {
+            map.serialize_entry(Self::FIELD_NAME, self)
+        }
+    }
+}
+
+impl persistence::ExtraFields for MetadataKeying {
+    fn deserialize_field<'de, A: MapAccess<'de>>(
+        &mut self,
+        name: &str,
+        map: &mut A,
+    ) -> Result<bool, A::Error> {
+        if name == Self::FIELD_NAME {
+            *self = map.next_value()?;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+}
+
 /// Information about alive tracing spans for a particular execution that is (de)serializable and
 /// can be persisted across multiple [`TracingEventReceiver`] lifetimes.
 ///
@@ -114,14 +291,48 @@ impl PersistedMetadata {
 /// (e.g., a WASM module instance). Compared to [`LocalSpans`], `PersistedSpans` have
 /// the lifetime of the execution and not the host [`Subscriber`].
 ///
+/// # Serialization format
+///
+/// Spans are serialized as a map with the `version` field equal to [`Self::FORMAT_VERSION`]
+/// and the `spans` field containing span data keyed by [`RawSpanId`]. If
+/// [ambient fields](TracingEventReceiver::set_ambient_field()) are set, they are serialized
+/// in the `ambient_fields` field. If the receiver uses
+/// [content-addressed metadata](MetadataKeying::ContentHash), the mapping from [`MetadataId`]s
+/// to call site hashes is serialized in the `call_site_hashes` field.
+/// Similar to [`PersistedMetadata`], unknown fields are ignored, and the legacy unversioned
+/// format is supported on deserialization; both require a self-describing format.
+///
 /// [`Subscriber`]: tracing_core::Subscriber
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
-#[serde(transparent)]
+#[derive(Debug, Clone, Default)]
 pub struct PersistedSpans {
     inner: HashMap<RawSpanId, SpanData>,
+    ambient_fields: AmbientFields,
+    call_site_hashes: CallSiteHashes,
+}
+
+impl Serialize for PersistedSpans {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let extras = (&self.ambient_fields, &self.call_site_hashes);
+        persistence::serialize(serializer, "spans", &self.inner, &extras)
+    }
+}
+
+impl<'de> Deserialize<'de> for PersistedSpans {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (inner, (ambient_fields, call_site_hashes)) =
+            persistence::deserialize(deserializer, "spans")?;
+        Ok(Self {
+            inner,
+            ambient_fields,
+            call_site_hashes,
+        })
+    }
 }
 
 impl PersistedSpans {
+    /// Latest version of the serialization format.
+    pub const FORMAT_VERSION: u32 = persistence::FORMAT_VERSION;
+
     /// Returns the number of alive spans.
     pub fn len(&self) -> usize {
         self.inner.len()
@@ -131,6 +342,116 @@ impl PersistedSpans {
     pub fn is_empty(&self) -> bool {
         self.inner.is_empty()
     }
+
+    /// Returns [ambient fields](TracingEventReceiver::set_ambient_field()) persisted
+    /// together with spans.
+    pub fn ambient_fields(&self) -> &TracedValues<String> {
+        &self.ambient_fields.0
+    }
+}
+
+/// Fields attached to all spans and events relayed by a [`TracingEventReceiver`].
+#[derive(Debug, Clone, Default)]
+struct AmbientFields(TracedValues<String>);
+
+impl AmbientFields {
+    const FIELD_NAME: &'static str = "ambient_fields";
+}
+
+impl persistence::SerializeExtras for AmbientFields {
+    fn field_count(&self) -> usize {
+        usize::from(!self.0.is_empty())
+    }
+
+    fn serialize_fields<M: SerializeMap>(&self, map: &mut M) -> Result<(), M::Error> {
+        if self.0.is_empty() {
+            Ok(())
+        } else {
+            map.serialize_entry(Self::FIELD_NAME, &self.0)
+        }
+    }
+}
+
+impl persistence::ExtraFields for AmbientFields {
+    fn deserialize_field<'de, A: MapAccess<'de>>(
+        &mut self,
+        name: &str,
+        map: &mut A,
+    ) -> Result<bool, A::Error> {
+        if name == Self::FIELD_NAME {
+            self.0 = map.next_value()?;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+}
+
+/// Mapping from [`MetadataId`]s to [call site hashes](CallSiteData::content_hash()) used
+/// with content-addressed [`PersistedMetadata`].
+#[derive(Debug, Clone, Default)]
+struct CallSiteHashes(HashMap<MetadataId, u64>);
+
+impl CallSiteHashes {
+    const FIELD_NAME: &'static str = "call_site_hashes";
+}
+
+impl persistence::SerializeExtras for CallSiteHashes {
+    fn field_count(&self) -> usize {
+        usize::from(!self.0.is_empty())
+    }
+
+    fn serialize_fields<M: SerializeMap>(&self, map: &mut M) -> Result<(), M::Error> {
+        if self.0.is_empty() {
+            Ok(())
+        } else {
+            map.serialize_entry(Self::FIELD_NAME, &self.0)
+        }
+    }
+}
+
+impl persistence::ExtraFields for CallSiteHashes {
+    fn deserialize_field<'de, A: MapAccess<'de>>(
+        &mut self,
+        name: &str,
+        map: &mut A,
+    ) -> Result<bool, A::Error> {
+        if name == Self::FIELD_NAME {
+            self.0 = map.next_value()?;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+}
+
+/// Metadata of a call site together with the number of fields defined by the call site itself
+/// (i.e., not including ambient fields).
+#[derive(Debug, Clone, Copy)]
+struct ReceivedMetadata {
+    metadata: &'static Metadata<'static>,
+    own_field_count: usize,
+}
+
+impl ReceivedMetadata {
+    /// Checks whether static `metadata` registered by the host corresponds to the call site `data`.
+    fn matches_static(data: &CallSiteData, metadata: &Metadata<'_>) -> bool {
+        matches!(data.kind, CallSiteKind::Span) == metadata.is_span()
+            && Level::from(data.level) == *metadata.level()
+            && data.name == metadata.name()
+            && data.target == metadata.target()
+            && data
+                .fields
+                .iter()
+                .map(Cow::as_ref)
+                .eq(metadata.fields().iter().map(|field| field.name()))
+    }
+
+    fn call_site_data(&self) -> CallSiteData {
+        let mut data = CallSiteData::from(self.metadata);
+        data.fields.truncate(self.own_field_count);
+        data
+    }
 }
 
 /// [`Subscriber`]-specific information about tracing spans for a particular execution
@@ -140,10 +461,16 @@ impl PersistedSpans {
 /// the host [`Subscriber`]. It is intended to be placed in something like
 /// (an initially empty) `HashMap<K, LocalSpans>`, where `K` denotes the execution ID.
 ///
+/// If the receiver is
+/// [bound to explicit dispatches](TracingEventReceiverBuilder::with_dispatches()),
+/// local spans are specific to these dispatches (and their order), and should only be used
+/// with receivers bound to the same dispatches.
+///
 /// [`Subscriber`]: tracing_core::Subscriber
 #[derive(Debug, Default)]
 pub struct LocalSpans {
-    inner: HashMap<RawSpanId, Id>,
+    // Span IDs for each of the receiver dispatches.
+    inner: HashMap<RawSpanId, Vec<Id>>,
 }
 
 /// Error processing a [`TracingEvent`] by a [`TracingEventReceiver`].
@@ -161,6 +488,30 @@ pub enum ReceiveError {
         /// Actual number of values.
         actual: usize,
     },
+    /// The event references a span created by another generation of the sender
+    /// (e.g., before the sender was restarted), i.e., the span ID was reused.
+    ///
+    /// Apart from updating the current generation, the receiver state is not modified
+    /// in this case. The host can resolve the conflict
+    /// by [forcefully dropping](TracingEventReceiver::force_drop()) the stale span
+    /// and re-submitting the event.
+    StaleSpan {
+        /// ID of the stale span.
+        id: RawSpanId,
+        /// Generation of the sender that created the span.
+        span_generation: Option<u32>,
+        /// Current sender generation, as per the last received [`TracingEvent::NewSpan`].
+        current_generation: Option<u32>,
+    },
+    /// A [`SequencedEvent`] has an unexpected sequence number, e.g., because the transport
+    /// lost or reordered events. See [`TracingEventReceiver::try_receive_sequenced()`]
+    /// for details on how the receiver handles such events.
+    OutOfOrder {
+        /// Expected sequence number.
+        expected: u32,
+        /// Actual sequence number of the event.
+        actual: u32,
+    },
 }
 
 impl fmt::Display for ReceiveError {
@@ -172,6 +523,27 @@ impl fmt::Display for ReceiveError {
                 formatter,
                 "too many values provided ({actual}), should be no more than {max}"
             ),
+            Self::StaleSpan {
+                id,
+                span_generation,
+                current_generation,
+            } => {
+                write!(formatter, "span {id} is stale (span generation: ")?;
+                match span_generation {
+                    Some(generation) => write!(formatter, "{generation}")?,
+                    None => formatter.write_str("unknown")?,
+                }
+                formatter.write_str(", current generation: ")?;
+                match current_generation {
+                    Some(generation) => write!(formatter, "{generation}")?,
+                    None => formatter.write_str("unknown")?,
+                }
+                formatter.write_str(")")
+            }
+            Self::OutOfOrder { expected, actual } => write!(
+                formatter,
+                "event is out of order (expected sequence number: {expected}, actual: {actual})"
+            ),
         }
     }
 }
@@ -190,31 +562,391 @@ macro_rules! create_value_set {
     };
 }
 
+/// Mode of relaying span entries and exits by a [`TracingEventReceiver`]. Set via
+/// [`TracingEventReceiverBuilder::with_dispatch_mode()`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum DispatchMode {
+    /// Relayed spans are entered and exited on the thread calling the receiver, mirroring
+    /// [`SpanEntered`](TracingEvent::SpanEntered) and [`SpanExited`](TracingEvent::SpanExited)
+    /// events. Contextual parents of spans and events are resolved by the dispatch
+    /// based on the entered spans. This is the default.
+    #[default]
+    Entered,
+    /// Relayed spans are never entered on the thread calling the receiver, so that
+    /// the thread context is not affected. Instead, the receiver tracks entered spans itself
+    /// and resolves contextual parents of spans and events to the innermost entered span,
+    /// relaying them with explicit parents. Conversely, root spans and events are relayed
+    /// as explicit roots (unless a [root parent](TracingEventReceiver::set_root_parent())
+    /// is set), i.e., they do not depend on the current span of the calling thread.
+    ///
+    /// This mode is useful if the receiver runs on a thread shared with other operations,
+    /// e.g., an executor thread. Since relayed spans are not entered, subscribers will
+    /// not record their busy time.
+    Detached,
+}
+
+/// Dispatches that a [`TracingEventReceiver`] relays events to.
+#[derive(Debug, Clone, Default)]
+enum Dispatches {
+    /// The default dispatch for the current thread at the time of the call.
+    #[default]
+    Default,
+    /// Explicitly specified dispatches.
+    Explicit(Vec<Dispatch>),
+}
+
+impl Dispatches {
+    // Explicit dispatches are set as the default ones for the duration of the call
+    // since some subscribers (e.g., `Registry`) rely on this.
+    fn for_each(&self, mut action: impl FnMut(&Dispatch)) {
+        match self {
+            Self::Default => action(&dispatcher::get_default(Dispatch::clone)),
+            Self::Explicit(dispatches) => {
+                for dispatch in dispatches {
+                    dispatcher::with_default(dispatch, || action(dispatch));
+                }
+            }
+        }
+    }
+
+    /// Checks whether at least one of the dispatches is interested in the specified metadata.
+    fn is_enabled(&self, metadata: &Metadata<'_>) -> bool {
+        let mut is_enabled = false;
+        self.for_each(|dispatch| {
+            is_enabled = is_enabled || dispatch.enabled(metadata);
+        });
+        is_enabled
+    }
+
+    /// Performs an action for each dispatch and the corresponding local span ID.
+    fn for_each_span(&self, local_ids: &[Id], mut action: impl FnMut(&Dispatch, &Id)) {
+        match self {
+            Self::Default => {
+                if let Some(local_id) = local_ids.first() {
+                    action(&dispatcher::get_default(Dispatch::clone), local_id);
+                }
+            }
+            Self::Explicit(dispatches) => {
+                for (dispatch, local_id) in dispatches.iter().zip(local_ids) {
+                    dispatcher::with_default(dispatch, || action(dispatch, local_id));
+                }
+            }
+        }
+    }
+}
+
 /// Container for non-persisted information specific to a particular traced execution.
 #[derive(Debug, Default)]
 struct CurrentExecution {
     uncommitted_span_ids: HashSet<RawSpanId>,
     entered_span_ids: HashSet<RawSpanId>,
+    /// Stack of entered spans used to resolve contextual parents in the detached mode.
+    entered_stack: Vec<RawSpanId>,
 }
 
 impl CurrentExecution {
     fn remove_span(&mut self, id: RawSpanId) {
         self.entered_span_ids.remove(&id);
+        self.entered_stack.retain(|&entered_id| entered_id != id);
         self.uncommitted_span_ids.remove(&id);
     }
 
-    fn finalize(&mut self, local_spans: &LocalSpans) {
+    fn on_span_entered(&mut self, id: RawSpanId) {
+        self.entered_span_ids.insert(id);
+        self.entered_stack.push(id);
+    }
+
+    fn on_span_exited(&mut self, id: RawSpanId) {
+        self.entered_span_ids.remove(&id);
+        if let Some(pos) = self
+            .entered_stack
+            .iter()
+            .rposition(|&entered_id| entered_id == id)
+        {
+            self.entered_stack.remove(pos);
+        }
+    }
+
+    fn finalize(&mut self, dispatches: &Dispatches, local_spans: &LocalSpans, mode: DispatchMode) {
+        self.entered_stack.clear();
         for id in mem::take(&mut self.entered_span_ids) {
-            if let Some(local_id) = local_spans.inner.get(&id) {
-                TracingEventReceiver::dispatch(|dispatch| dispatch.exit(local_id));
+            if mode == DispatchMode::Detached {
+                continue; // the span was never entered in the dispatch
+            }
+            if let Some(local_ids) = local_spans.inner.get(&id) {
+                dispatches.for_each_span(local_ids, Dispatch::exit);
             }
         }
         for id in mem::take(&mut self.uncommitted_span_ids) {
-            if let Some(local_id) = local_spans.inner.get(&id) {
-                TracingEventReceiver::dispatch(|dispatch| dispatch.try_close(local_id.clone()));
+            if let Some(local_ids) = local_spans.inner.get(&id) {
+                dispatches.for_each_span(local_ids, |dispatch, local_id| {
+                    dispatch.try_close(local_id.clone());
+                });
+            }
+        }
+    }
+}
+
+/// Information about the last [heartbeat](TracingEvent::Heartbeat) received
+/// by a [`TracingEventReceiver`]. Returned by [`TracingEventReceiver::last_heartbeat()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct HeartbeatStatus {
+    /// Sequence number of the heartbeat, i.e., the number of events emitted by the sender
+    /// before the heartbeat.
+    pub sequence: u32,
+    /// Number of events reported as dropped by the sender.
+    pub dropped_events: u32,
+    /// Total number of events missing in the event stream, as detected by comparing
+    /// sequence numbers of consecutive heartbeats with the number of events received
+    /// between them. Gap detection assumes that events are received in the order
+    /// they were emitted.
+    pub missing_events: u32,
+    /// Number of (non-heartbeat) events received after the heartbeat.
+    pub events_since: u32,
+    /// Instant when the heartbeat was received.
+    pub received_at: Instant,
+}
+
+/// Information about the last [flush](TracingEvent::Flush) or [shutdown](TracingEvent::Shutdown)
+/// marker received by a [`TracingEventReceiver`]. Returned by [`TracingEventReceiver::last_flush()`]
+/// and passed to the [flush hook](TracingEventReceiverBuilder::with_flush_hook()).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct FlushStatus {
+    /// Sequence number of the marker, i.e., the number of events emitted by the sender
+    /// before the marker.
+    pub sequence: u32,
+    /// Whether the marker is a [shutdown marker](TracingEvent::Shutdown), i.e., the event stream
+    /// is complete.
+    pub is_final: bool,
+    /// Instant when the marker was received.
+    pub received_at: Instant,
+}
+
+type FlushHookFn = dyn FnMut(&FlushStatus) + Send + Sync;
+
+/// Wrapper around an optional flush hook.
+#[derive(Default)]
+struct FlushHook(Option<Box<FlushHookFn>>);
+
+impl fmt::Debug for FlushHook {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_tuple("FlushHook")
+            .field(&self.0.as_ref().map(|_| "Fn"))
+            .finish()
+    }
+}
+
+/// Information about a span alive in a [`TracingEventReceiver`]. Returned by
+/// [`TracingEventReceiver::alive_spans()`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct AliveSpan {
+    /// Span ID assigned by the sender.
+    pub id: RawSpanId,
+    /// ID of the span metadata.
+    pub metadata_id: MetadataId,
+    /// Number of span references held by the sender.
+    pub ref_count: usize,
+    /// Number of alive child spans. A span is kept alive while it has children,
+    /// even if the sender has dropped all references to it.
+    pub child_count: usize,
+    /// Whether the span is entered in the current execution.
+    pub is_entered: bool,
+    /// Number of (non-heartbeat) events received by the receiver since the last event
+    /// referencing the span. For spans restored from [`PersistedSpans`], counted since
+    /// the receiver creation.
+    pub idle_events: u64,
+    /// Generation of the sender that created the span, if specified.
+    pub generation: Option<u32>,
+}
+
+/// Verification of [`SequencedEvent`] numbers for a [`TracingEventReceiver`].
+#[derive(Debug, Default)]
+struct Sequencing {
+    next_sequence: u32,
+    reorder_capacity: usize,
+    /// Maximum time to wait for missing events; `None` means waiting indefinitely.
+    reorder_timeout: Option<Duration>,
+    /// Buffered events; the event at index `i` has sequence number `next_sequence + i`.
+    /// The first slot is always empty (otherwise, the event would be relayed).
+    reorder_buffer: VecDeque<Option<TracingEvent>>,
+    /// Time when the oldest currently buffered event was buffered.
+    buffered_since: Option<Instant>,
+}
+
+impl Sequencing {
+    /// Maximum difference between sequence numbers for which an event is considered
+    /// to be ahead of the expected one.
+    const MAX_OFFSET: u32 = u32::MAX / 2;
+
+    /// Returns events ready to be relayed (in order) together with a sequencing error, if any.
+    fn push(&mut self, event: SequencedEvent) -> (Vec<TracingEvent>, Option<ReceiveError>) {
+        let expected = self.next_sequence;
+        let actual = event.sequence;
+        let err = ReceiveError::OutOfOrder { expected, actual };
+        let offset = actual.wrapping_sub(expected);
+
+        if offset > Self::MAX_OFFSET {
+            // The event is late (e.g., a duplicate); it cannot be relayed in order.
+            return (vec![], Some(err));
+        }
+        if offset == 0 {
+            let mut ready_events = vec![event.event];
+            self.reorder_buffer.pop_front();
+            self.next_sequence = self.next_sequence.wrapping_add(1);
+            while let Some(Some(_)) = self.reorder_buffer.front() {
+                let buffered = self.reorder_buffer.pop_front().unwrap().unwrap();
+                ready_events.push(buffered);
+                self.next_sequence = self.next_sequence.wrapping_add(1);
+            }
+            if self.reorder_buffer.is_empty() {
+                self.buffered_since = None;
+            }
+            return (ready_events, None);
+        }
+
+        let offset = offset as usize;
+        if offset <= self.reorder_capacity {
+            if self.reorder_buffer.len() <= offset {
+                self.reorder_buffer.resize_with(offset + 1, || None);
+            }
+            let slot = &mut self.reorder_buffer[offset];
+            if slot.is_some() {
+                return (vec![], Some(err)); // duplicate event
+            }
+            // Flush and shutdown markers signal that all preceding events were emitted,
+            // so missing events are considered lost.
+            let is_marker = matches!(
+                event.event,
+                TracingEvent::Flush { .. } | TracingEvent::Shutdown { .. }
+            );
+            *slot = Some(event.event);
+
+            let now = Instant::now();
+            let buffered_since = *self.buffered_since.get_or_insert(now);
+            let is_overdue = self.reorder_timeout.is_some_and(|timeout| {
+                buffered_since
+                    .checked_add(timeout)
+                    .is_some_and(|deadline| deadline <= now)
+            });
+            if is_marker || is_overdue {
+                (self.drain(), Some(err))
+            } else {
+                (vec![], None)
             }
+        } else {
+            // The gap cannot be filled; relay all buffered events and resync.
+            let mut ready_events = self.drain();
+            ready_events.push(event.event);
+            self.next_sequence = actual.wrapping_add(1);
+            (ready_events, Some(err))
+        }
+    }
+
+    /// Gives up waiting for missing events, returning all buffered events in order
+    /// and resyncing to follow the last buffered event.
+    fn drain(&mut self) -> Vec<TracingEvent> {
+        // The buffer length is bounded by `MAX_OFFSET + 1`, so it fits into `u32`.
+        let buffer_len = u32::try_from(self.reorder_buffer.len()).unwrap();
+        self.next_sequence = self.next_sequence.wrapping_add(buffer_len);
+        self.buffered_since = None;
+        self.reorder_buffer.drain(..).flatten().collect()
+    }
+}
+
+/// Local parent of relayed root spans and events in a specific dispatch. Holds a reference
+/// to the parent span, so that it is not closed while it is used as the parent.
+#[derive(Debug)]
+struct RootParent {
+    dispatch: Dispatch,
+    id: Id,
+}
+
+impl RootParent {
+    fn new(dispatch: &Dispatch, id: &Id) -> Self {
+        Self {
+            dispatch: dispatch.clone(),
+            id: dispatch.clone_span(id),
+        }
+    }
+}
+
+impl Drop for RootParent {
+    fn drop(&mut self) {
+        dispatcher::with_default(&self.dispatch, || {
+            self.dispatch.try_close(self.id.clone());
+        });
+    }
+}
+
+/// Parking of events referencing unknown call sites for a [`TracingEventReceiver`].
+#[derive(Debug, Default)]
+struct Parking {
+    capacity: usize,
+    timeout: Duration,
+    /// Parked events in the order of receipt together with the time they were parked.
+    /// The first event always references an unknown call site.
+    events: VecDeque<(TracingEvent, Instant)>,
+}
+
+impl Parking {
+    fn is_enabled(&self) -> bool {
+        self.capacity > 0
+    }
+
+    /// Returns the time when the oldest parked event should be relayed regardless
+    /// of whether its call site is known.
+    fn deadline(&self) -> Option<Instant> {
+        let (_, parked_at) = self.events.front()?;
+        parked_at.checked_add(self.timeout)
+    }
+
+    fn is_overdue(&self, now: Instant) -> bool {
+        self.events.len() > self.capacity || self.deadline().is_some_and(|deadline| deadline <= now)
+    }
+}
+
+/// Liveness tracking for a [`TracingEventReceiver`].
+#[derive(Debug, Default)]
+struct Liveness {
+    last_heartbeat: Option<HeartbeatStatus>,
+    last_flush: Option<FlushStatus>,
+    events_since_start: u32,
+}
+
+impl Liveness {
+    fn on_event(&mut self) {
+        if let Some(heartbeat) = &mut self.last_heartbeat {
+            heartbeat.events_since = heartbeat.events_since.wrapping_add(1);
+        } else {
+            self.events_since_start = self.events_since_start.wrapping_add(1);
         }
     }
+
+    fn on_heartbeat(&mut self, sequence: u32, dropped_events: u32) {
+        let missing_events = if let Some(prev) = &self.last_heartbeat {
+            let expected_sequence = prev.sequence.wrapping_add(prev.events_since);
+            let gap = sequence.wrapping_sub(expected_sequence);
+            // A "negative" gap means duplicated or reordered events; we don't count it.
+            let gap = if gap <= u32::MAX / 2 { gap } else { 0 };
+            prev.missing_events.saturating_add(gap)
+        } else {
+            // The receiver may have been started in the middle of the event stream,
+            // so we cannot detect gaps before the first heartbeat.
+            0
+        };
+        self.last_heartbeat = Some(HeartbeatStatus {
+            sequence,
+            dropped_events,
+            missing_events,
+            events_since: 0,
+            received_at: Instant::now(),
+        });
+    }
 }
 
 /// Receiver of [`TracingEvent`]s produced by [`TracingEventSender`] that relays them
@@ -234,6 +966,78 @@ impl CurrentExecution {
 /// That is, persistence acts as a commitment of the execution results, while the default
 /// behavior is rollback.
 ///
+/// # Dispatching
+///
+/// By default, the receiver relays events to the [default dispatch](dispatcher::get_default())
+/// at the time an event is received. Alternatively, the receiver can be bound to one
+/// or more explicit [`Dispatch`]es using [`TracingEventReceiverBuilder::with_dispatches()`].
+///
+/// By default, relayed spans are entered and exited on the thread calling the receiver.
+/// If this thread is shared with other operations (e.g., it is an executor thread),
+/// the entered spans pollute its context. To avoid this, the receiver can be switched
+/// to the [detached mode](DispatchMode::Detached) via
+/// [`TracingEventReceiverBuilder::with_dispatch_mode()`].
+///
+/// # Liveness
+///
+/// If the sender emits [heartbeats](TracingEvent::Heartbeat), the receiver tracks them
+/// and allows detecting wedged / disconnected senders and gaps in the event stream
+/// via [`Self::last_heartbeat()`]. Liveness information is not persisted.
+///
+/// If the sender emits [flush](TracingEvent::Flush) or [shutdown](TracingEvent::Shutdown)
+/// markers, the receiver records the last marker (see [`Self::last_flush()`]) and invokes
+/// the [flush hook](TracingEventReceiverBuilder::with_flush_hook()). This allows hosts
+/// to finalize persisted state once the event stream is complete.
+///
+/// # Configuration
+///
+/// A receiver is configured with a [builder](TracingEventReceiverBuilder) created via
+/// [`Self::builder()`]. [`Self::new()`] and [`Self::default()`] are shortcuts for a builder
+/// with default configuration; a receiver cannot be reconfigured after it is built.
+/// Received events can be inspected, modified or discarded before they are relayed
+/// by [interceptors](TracingEventReceiverBuilder::with_interceptor()).
+///
+/// # Span cleanup
+///
+/// Spans are closed once the sender drops all references to them. To protect the host from
+/// misbehaving senders that never emit [`SpanDropped`](TracingEvent::SpanDropped) events,
+/// alive spans can be inspected via [`Self::alive_spans()`] and closed forcefully via
+/// [`Self::force_drop()`], or automatically after a period of inactivity configured with
+/// [`TracingEventReceiverBuilder::with_idle_span_timeout()`].
+///
+/// # Span ID reuse
+///
+/// If the sender is [assigned a generation](crate::TracingEventSender::with_generation()),
+/// the receiver records it for each created span and tracks the current sender generation
+/// based on received [`NewSpan`](TracingEvent::NewSpan) events. Events referencing a span
+/// created by another generation (e.g., after the sender was restarted and reused span IDs)
+/// are rejected with [`ReceiveError::StaleSpan`]. Span generations are persisted, but
+/// the current generation is not; it is restored once a new span is received.
+///
+/// # Metrics
+///
+/// The receiver maintains [counters](ReceiverCounters) for received events, forwarded spans,
+/// errors and dropped values, which can be accessed via [`Self::counters()`].
+/// A [metrics hook](ReceiverMetrics) set via [`TracingEventReceiverBuilder::with_metrics()`]
+/// is invoked after each received event, e.g., to export counters to a monitoring system.
+/// Counters are not persisted.
+///
+/// # Ambient fields
+///
+/// The receiver can attach [ambient fields](Self::set_ambient_field()) to all relayed
+/// spans and events, e.g., to identify the execution that produced them. Ambient fields
+/// are persisted together with spans, so that spans and events relayed after restoring
+/// the receiver carry the same fields.
+///
+/// # Root parent
+///
+/// Root spans and events (i.e., ones without a parent on the sender side) can be attached
+/// to a local span using [`Self::set_root_parent()`], or to a span in each of the
+/// [explicit dispatches](TracingEventReceiverBuilder::with_dispatches()) using
+/// [`Self::set_root_parents()`]. This can be used to place relayed traces into the correct
+/// distributed trace, e.g., by setting the OpenTelemetry context of an incoming request
+/// as the parent of the local span. The root parent is not persisted.
+///
 /// # ⚠ Resource consumption
 ///
 /// To fit the API of the [`tracing-core`] crate, the receiver leaks string parts
@@ -251,17 +1055,51 @@ impl CurrentExecution {
 /// [`TracingEventSender`]: crate::TracingEventSender
 /// [the Tardigrade runtime]: https://github.com/slowli/tardigrade
 /// [`tracing-core`]: https://docs.rs/tracing-core/
-#[derive(Debug, Default)]
+#[derive(Debug)]
 pub struct TracingEventReceiver {
-    metadata: HashMap<MetadataId, &'static Metadata<'static>>,
+    metadata: HashMap<MetadataId, ReceivedMetadata>,
     spans: PersistedSpans,
     local_spans: LocalSpans,
     current_execution: CurrentExecution,
+    dispatches: Dispatches,
+    liveness: Liveness,
+    event_counter: u64,
+    idle_span_timeout: Option<NonZeroU64>,
+    /// Value of the event counter at which idle spans are checked next.
+    next_idle_check: u64,
+    lazy_spans: bool,
+    sequencing: Sequencing,
+    parking: Parking,
+    counters: ReceiverCounters,
+    metrics: MetricsHook,
+    flush_hook: FlushHook,
+    interceptors: Interceptors,
+    generation: Option<u32>,
+    static_metadata: Vec<&'static Metadata<'static>>,
+    max_values: usize,
+    error_policy: ErrorPolicy,
+    errors: Vec<ReceiveError>,
+    metadata_keying: MetadataKeying,
+    changes: ChangeTracker,
+    /// Local parents for root spans and events, one per dispatch.
+    root_parents: Vec<Option<RootParent>>,
+    dispatch_mode: DispatchMode,
+}
+
+impl Default for TracingEventReceiver {
+    fn default() -> Self {
+        Self::builder().build()
+    }
 }
 
 impl TracingEventReceiver {
     /// Maximum supported number of values in a span or event.
-    const MAX_VALUES: usize = 32;
+    pub(crate) const MAX_VALUES: usize = 32;
+
+    /// Creates a [builder](TracingEventReceiverBuilder) for the receiver.
+    pub fn builder() -> TracingEventReceiverBuilder {
+        TracingEventReceiverBuilder::default()
+    }
 
     /// Restores the receiver from the persisted metadata and tracing spans.
     ///
@@ -271,55 +1109,472 @@ impl TracingEventReceiver {
     /// and the execution producing [`TracingEvent`]s is **bad**; it will most likely result
     /// in errors returned from [`Self::try_receive()`].
     ///
+    /// This is a shortcut for the corresponding [builder](Self::builder()) calls.
+    ///
     /// [`Subscriber`]: tracing_core::Subscriber
     pub fn new(
         metadata: PersistedMetadata,
         spans: PersistedSpans,
         local_spans: LocalSpans,
     ) -> Self {
-        let mut this = Self {
-            metadata: HashMap::new(),
-            spans,
-            local_spans,
-            current_execution: CurrentExecution::default(),
-        };
+        Self::builder()
+            .with_persisted_metadata(metadata)
+            .with_persisted_spans(spans, local_spans)
+            .build()
+    }
+
+    /// Returns the number of events currently parked because they (or preceding events)
+    /// reference unknown call sites. See [`TracingEventReceiverBuilder::with_call_site_buffer()`]
+    /// for details.
+    pub fn parked_event_count(&self) -> usize {
+        self.parking.events.len()
+    }
 
-        for (id, data) in metadata.inner {
-            this.on_new_call_site(id, data);
+    /// Returns the time after which the oldest parked event will be relayed regardless
+    /// of whether its call site is known, or `None` if there are no parked events
+    /// (or if the deadline is too far in the future to be represented).
+    /// Since overdue events are only relayed when receiving events, the host may want
+    /// to schedule a [`Self::flush_parked()`] call at this time if no events arrive.
+    pub fn parking_deadline(&self) -> Option<Instant> {
+        self.parking.deadline()
+    }
+
+    /// Relays all parked events regardless of whether their call sites are known.
+    /// See [`TracingEventReceiverBuilder::with_call_site_buffer()`] for details.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error relaying parked events; the remaining events are still relayed.
+    pub fn flush_parked(&mut self) -> Result<(), ReceiveError> {
+        let mut result = Ok(());
+        while let Some((event, _)) = self.parking.events.pop_front() {
+            let event_result = self.relay_event(event);
+            if result.is_ok() {
+                result = event_result;
+            }
         }
-        this
+        result
     }
 
-    fn dispatch<T>(dispatch_fn: impl FnOnce(&Dispatch) -> T) -> T {
-        dispatch_fn(&dispatcher::get_default(Dispatch::clone))
+    /// Returns errors collected by [`Self::receive()`] since the last call to this method.
+    /// Errors are only collected with the [`ErrorPolicy::Collect`] policy; collected errors
+    /// are kept until taken, so this method should be called periodically.
+    pub fn take_errors(&mut self) -> Vec<ReceiveError> {
+        mem::take(&mut self.errors)
     }
 
-    fn metadata(&self, id: MetadataId) -> Result<&'static Metadata<'static>, ReceiveError> {
-        self.metadata
-            .get(&id)
-            .copied()
-            .ok_or(ReceiveError::UnknownMetadataId(id))
+    /// Returns information about the last [flush](TracingEvent::Flush) or
+    /// [shutdown](TracingEvent::Shutdown) marker received by this receiver, or `None`
+    /// if no markers were received.
+    pub fn last_flush(&self) -> Option<FlushStatus> {
+        self.liveness.last_flush
     }
 
-    fn span(&self, id: RawSpanId) -> Result<&SpanData, ReceiveError> {
-        self.spans
-            .inner
-            .get(&id)
-            .ok_or(ReceiveError::UnknownSpanId(id))
+    /// Returns the current counters for this receiver.
+    pub fn counters(&self) -> ReceiverCounters {
+        self.counters
     }
 
-    fn span_mut(&mut self, id: RawSpanId) -> Result<&mut SpanData, ReceiveError> {
+    /// Iterates over spans alive in this receiver, i.e., spans created by the sender
+    /// that were not dropped yet. The iteration order is not specified.
+    pub fn alive_spans(&self) -> impl Iterator<Item = AliveSpan> + '_ {
+        self.spans.inner.iter().map(|(&id, span)| AliveSpan {
+            id,
+            metadata_id: span.metadata_id,
+            ref_count: span.ref_count,
+            child_count: span.child_count,
+            is_entered: self.current_execution.entered_span_ids.contains(&id),
+            idle_events: self.event_counter.saturating_sub(span.last_activity),
+            generation: span.generation,
+        })
+    }
+
+    /// Forcefully drops the span with the specified ID regardless of its reference count.
+    /// If the span is entered, it is exited first. The span is then closed in the underlying
+    /// [`Subscriber`](tracing_core::Subscriber). Child spans that were not yet relayed
+    /// to the subscriber lose their parent.
+    ///
+    /// This is an escape hatch for misbehaving senders; subsequent events referencing
+    /// the dropped span will result in a [`ReceiveError::UnknownSpanId`] error.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the span is not alive.
+    pub fn force_drop(&mut self, id: RawSpanId) -> Result<(), ReceiveError> {
+        let span = self
+            .spans
+            .inner
+            .remove(&id)
+            .ok_or(ReceiveError::UnknownSpanId(id))?;
+        self.changes.on_span(id);
+        for (&child_id, span) in &mut self.spans.inner {
+            if span.parent_id == Some(id) {
+                span.parent_id = None;
+                self.changes.on_span(child_id);
+            }
+        }
+
+        let is_entered = self.current_execution.entered_span_ids.contains(&id);
+        self.current_execution.remove_span(id);
+        if let Some(local_ids) = self.local_spans.inner.remove(&id) {
+            self.dispatches
+                .for_each_span(&local_ids, |dispatch, local_id| {
+                    if is_entered && self.dispatch_mode == DispatchMode::Entered {
+                        dispatch.exit(local_id);
+                    }
+                    dispatch.try_close(local_id.clone());
+                });
+        }
+        self.release_parent(span.parent_id);
+        Ok(())
+    }
+
+    /// Closes a span without references and children, and then its ancestors that
+    /// were only kept alive by the closed span.
+    fn close_span(&mut self, id: RawSpanId) {
+        let Some(span) = self.spans.inner.remove(&id) else {
+            return;
+        };
+        self.changes.on_span(id);
+        self.current_execution.remove_span(id);
+        if let Some(local_ids) = self.local_spans.inner.remove(&id) {
+            self.dispatches
+                .for_each_span(&local_ids, |dispatch, local_id| {
+                    dispatch.try_close(local_id.clone());
+                });
+        }
+        self.release_parent(span.parent_id);
+    }
+
+    fn release_parent(&mut self, parent_id: Option<RawSpanId>) {
+        let Some(parent_id) = parent_id else {
+            return;
+        };
+        let Some(parent) = self.spans.inner.get_mut(&parent_id) else {
+            return;
+        };
+        self.changes.on_span(parent_id);
+        // Saturating subtraction accounts for spans persisted before child counting was introduced.
+        parent.child_count = parent.child_count.saturating_sub(1);
+        parent.last_activity = self.event_counter;
+        if parent.ref_count == 0 && parent.child_count == 0 {
+            self.close_span(parent_id);
+        }
+    }
+
+    fn touch_span(&mut self, id: RawSpanId) {
+        if let Some(span) = self.spans.inner.get_mut(&id) {
+            span.last_activity = self.event_counter;
+        }
+    }
+
+    fn touch_referenced_spans(&mut self, event: &TracingEvent) {
+        match event {
+            TracingEvent::SpanEntered { id }
+            | TracingEvent::SpanExited { id }
+            | TracingEvent::SpanCloned { id }
+            | TracingEvent::SpanDropped { id }
+            | TracingEvent::ValuesRecorded { id, .. }
+            | TracingEvent::NewEvent {
+                parent: Some(id), ..
+            }
+            | TracingEvent::NewSpan {
+                parent_id: Some(id),
+                ..
+            } => {
+                self.touch_span(*id);
+            }
+            TracingEvent::FollowsFrom { id, follows_from } => {
+                self.touch_span(*id);
+                self.touch_span(*follows_from);
+            }
+            _ => { /* no spans are referenced */ }
+        }
+    }
+
+    /// Checks that the event does not reference spans created by another sender generation.
+    fn check_generations(&mut self, event: &TracingEvent) -> Result<(), ReceiveError> {
+        match event {
+            TracingEvent::NewSpan {
+                id,
+                parent_id,
+                generation,
+                ..
+            } => {
+                if generation.is_some() {
+                    self.generation = *generation;
+                }
+                self.check_generation(*id)?;
+                if let Some(parent_id) = parent_id {
+                    self.check_generation(*parent_id)?;
+                }
+            }
+            TracingEvent::SpanEntered { id }
+            | TracingEvent::SpanExited { id }
+            | TracingEvent::SpanCloned { id }
+            | TracingEvent::SpanDropped { id }
+            | TracingEvent::ValuesRecorded { id, .. }
+            | TracingEvent::NewEvent {
+                parent: Some(id), ..
+            } => {
+                self.check_generation(*id)?;
+            }
+            TracingEvent::FollowsFrom { id, follows_from } => {
+                self.check_generation(*id)?;
+                self.check_generation(*follows_from)?;
+            }
+            _ => { /* no spans are referenced */ }
+        }
+        Ok(())
+    }
+
+    fn check_generation(&self, id: RawSpanId) -> Result<(), ReceiveError> {
+        let Some(span) = self.spans.inner.get(&id) else {
+            return Ok(());
+        };
+        match (span.generation, self.generation) {
+            (Some(span_generation), Some(current)) if span_generation != current => {
+                Err(ReceiveError::StaleSpan {
+                    id,
+                    span_generation: span.generation,
+                    current_generation: self.generation,
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn drop_idle_spans(&mut self, timeout: NonZeroU64) {
+        if self.event_counter < self.next_idle_check {
+            return;
+        }
+        // Checking all spans is linear in their number, so it's amortized over several events.
+        self.next_idle_check = self.event_counter + (timeout.get() / 4).max(1);
+
+        let mut idle_ids: Vec<_> = self
+            .alive_spans()
+            .filter(|span| {
+                !span.is_entered && span.child_count == 0 && span.idle_events >= timeout.get()
+            })
+            .map(|span| span.id)
+            .collect();
+        idle_ids.sort_unstable();
+        for id in idle_ids {
+            self.force_drop(id).ok(); // cannot fail since the span is alive
+        }
+    }
+
+    /// Returns information about the last [heartbeat](TracingEvent::Heartbeat) received
+    /// by this receiver, or `None` if no heartbeats were received.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::time::Duration;
+    /// # use tracing_tunnel::{TracingEvent, TracingEventReceiver};
+    /// let mut receiver = TracingEventReceiver::default();
+    /// // Receive some events...
+    /// receiver.receive(TracingEvent::Heartbeat { sequence: 100, dropped_events: 0 });
+    ///
+    /// let heartbeat = receiver.last_heartbeat().unwrap();
+    /// assert_eq!(heartbeat.sequence, 100);
+    /// assert_eq!(heartbeat.missing_events, 0);
+    /// let is_stale = heartbeat.received_at.elapsed() > Duration::from_secs(60);
+    /// assert!(!is_stale);
+    /// ```
+    pub fn last_heartbeat(&self) -> Option<HeartbeatStatus> {
+        self.liveness.last_heartbeat
+    }
+
+    /// Sets an ambient field that will be attached to all spans and events relayed
+    /// by this receiver, unless a span / event defines a field with the same name itself.
+    /// Ambient fields are [persisted](Self::persist()) together with spans.
+    ///
+    /// Ambient fields are only attached to spans created in the underlying [`Subscriber`]
+    /// after the call (including spans restored from [`PersistedSpans`]); already created spans
+    /// are not affected. Since the number of values in a span / event is limited,
+    /// excessive ambient fields may be omitted.
+    ///
+    /// [`Subscriber`]: tracing_core::Subscriber
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_tunnel::{LocalSpans, PersistedSpans, TracingEventReceiver};
+    /// let mut receiver = TracingEventReceiver::default();
+    /// receiver.set_ambient_field("workflow_id", 42_u64);
+    /// // Receive some events...
+    /// let (spans, local_spans) = receiver.persist();
+    /// assert_eq!(spans.ambient_fields()["workflow_id"], 42_u64);
+    ///
+    /// // Later, possibly after restarting the host:
+    /// let metadata = // ...
+    /// #   Default::default();
+    /// let receiver = TracingEventReceiver::new(metadata, spans, local_spans);
+    /// assert_eq!(receiver.ambient_fields()["workflow_id"], 42_u64);
+    /// ```
+    pub fn set_ambient_field(&mut self, name: impl Into<String>, value: impl Into<TracedValue>) {
+        self.changes.on_span_extras();
+        let is_new = self
+            .spans
+            .ambient_fields
+            .0
+            .insert(name.into(), value.into())
+            .is_none();
+        if is_new {
+            // Call site metadata needs to be updated to include the new field.
+            self.update_call_sites();
+        }
+    }
+
+    fn update_call_sites(&mut self) {
+        let call_sites: Vec<_> = self
+            .metadata
+            .iter()
+            .map(|(&id, received)| (id, received.call_site_data()))
+            .collect();
+        for (id, data) in call_sites {
+            self.on_new_call_site(id, data);
+        }
+    }
+
+    /// Sets the local span that will be used as the parent for all relayed root spans and events
+    /// (i.e., ones that have neither an explicit parent nor an entered contextual parent
+    /// on the sender side), or unsets it if `parent` is `None`. This includes root spans
+    /// restored from [`PersistedSpans`].
+    ///
+    /// The parent applies to root spans and events relayed after the call. Since span IDs
+    /// are specific to a subscriber, the parent is only used for the default dispatch
+    /// (or the first of [explicit dispatches](TracingEventReceiverBuilder::with_dispatches()));
+    /// use [`Self::set_root_parents()`] to set parents for multiple dispatches.
+    ///
+    /// The receiver holds a reference to the parent span (as if it was cloned), so the span
+    /// stays alive until the parent is unset or replaced, or the receiver is dropped.
+    ///
+    /// # Examples
+    ///
+    /// With [`tracing-opentelemetry`], the OpenTelemetry context of an incoming request
+    /// can be attached to the parent span, so that relayed traces appear in the same
+    /// distributed trace:
+    ///
+    /// ```
+    /// # use tracing_tunnel::TracingEventReceiver;
+    /// let invocation_span = tracing::info_span!("invocation");
+    /// // With `tracing-opentelemetry`:
+    /// // invocation_span.set_parent(request_context);
+    /// let mut receiver = TracingEventReceiver::default();
+    /// receiver.set_root_parent(invocation_span.id());
+    /// // Receive events...
+    /// ```
+    ///
+    /// [`tracing-opentelemetry`]: https://docs.rs/tracing-opentelemetry/
+    pub fn set_root_parent(&mut self, parent: Option<Id>) {
+        self.set_root_parents([parent]);
+    }
+
+    /// Sets local parents for relayed root spans and events, one per dispatch in the order
+    /// the dispatches were [specified](TracingEventReceiverBuilder::with_dispatches()).
+    /// `None` entries and missing trailing entries mean that root spans and events
+    /// in the corresponding dispatch have no parent; excessive entries are ignored.
+    /// See [`Self::set_root_parent()`] for more details.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_core::Dispatch;
+    /// # use tracing_subscriber::Registry;
+    /// # use tracing_tunnel::TracingEventReceiver;
+    /// let dispatches = [Dispatch::new(Registry::default()), Dispatch::new(Registry::default())];
+    /// let parents: Vec<_> = dispatches
+    ///     .iter()
+    ///     .map(|dispatch| {
+    ///         tracing::dispatcher::with_default(dispatch, || tracing::info_span!("invocation"))
+    ///     })
+    ///     .collect();
+    /// let mut receiver = TracingEventReceiver::builder()
+    ///     .with_dispatches(dispatches)
+    ///     .build();
+    /// receiver.set_root_parents(parents.iter().map(tracing::Span::id));
+    /// // The parent spans are kept alive by the receiver, so their handles can be dropped.
+    /// drop(parents);
+    /// // Receive events...
+    /// ```
+    pub fn set_root_parents(&mut self, parents: impl IntoIterator<Item = Option<Id>>) {
+        let mut parents = parents.into_iter();
+        let mut root_parents = vec![];
+        self.dispatches.for_each(|dispatch| {
+            let parent = parents.next().flatten();
+            root_parents.push(parent.map(|id| RootParent::new(dispatch, &id)));
+        });
+        // Previous parents are released only after new ones are referenced, in case they coincide.
+        self.root_parents = root_parents;
+    }
+
+    /// Returns the root parent for the specified dispatch if the span / event being relayed
+    /// has no parent. Explicit roots are always attached to the root parent, while contextual
+    /// spans / events are only attached if no relayed span is entered.
+    fn root_parent(&self, dispatch_idx: usize, is_root: bool) -> Option<&Id> {
+        if is_root || self.current_execution.entered_span_ids.is_empty() {
+            let parent = self.root_parents.get(dispatch_idx)?.as_ref()?;
+            Some(&parent.id)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the parent of a new span or event, resolving the contextual parent
+    /// if necessary.
+    fn resolve_parent(&self, parent: Option<RawSpanId>, is_root: bool) -> Option<RawSpanId> {
+        if is_root {
+            parent
+        } else {
+            parent.or_else(|| self.contextual_parent())
+        }
+    }
+
+    /// Returns the innermost entered span if contextual parents are resolved
+    /// by the receiver, i.e., in the [detached mode](DispatchMode::Detached).
+    fn contextual_parent(&self) -> Option<RawSpanId> {
+        match self.dispatch_mode {
+            DispatchMode::Entered => None,
+            DispatchMode::Detached => self.current_execution.entered_stack.last().copied(),
+        }
+    }
+
+    /// Returns ambient fields [set](Self::set_ambient_field()) for this receiver.
+    pub fn ambient_fields(&self) -> &TracedValues<String> {
+        &self.spans.ambient_fields.0
+    }
+
+    fn metadata(&self, id: MetadataId) -> Result<&'static Metadata<'static>, ReceiveError> {
+        self.metadata
+            .get(&id)
+            .map(|received| received.metadata)
+            .ok_or(ReceiveError::UnknownMetadataId(id))
+    }
+
+    fn span(&self, id: RawSpanId) -> Result<&SpanData, ReceiveError> {
         self.spans
             .inner
-            .get_mut(&id)
+            .get(&id)
             .ok_or(ReceiveError::UnknownSpanId(id))
     }
 
+    fn span_mut(&mut self, id: RawSpanId) -> Result<&mut SpanData, ReceiveError> {
+        let span = self
+            .spans
+            .inner
+            .get_mut(&id)
+            .ok_or(ReceiveError::UnknownSpanId(id))?;
+        self.changes.on_span(id);
+        Ok(span)
+    }
+
     /// Returns `Ok(None)` if the local span ID is (validly) not set yet, and `Err(_)`
     /// if it must have been set by this point.
-    fn map_span_id(&self, remote_id: RawSpanId) -> Result<Option<&Id>, ReceiveError> {
+    fn map_span_id(&self, remote_id: RawSpanId) -> Result<Option<&[Id]>, ReceiveError> {
         match self.local_spans.inner.get(&remote_id) {
-            Some(local_id) => Ok(Some(local_id)),
+            Some(local_ids) => Ok(Some(local_ids)),
             None => {
                 // Check if the the referenced span is alive.
                 if self.spans.inner.contains_key(&remote_id) {
@@ -331,28 +1586,36 @@ impl TracingEventReceiver {
         }
     }
 
-    fn ensure_values_len(values: &TracedValues<String>) -> Result<(), ReceiveError> {
-        if values.len() > Self::MAX_VALUES {
+    fn ensure_values_len(&self, values: &TracedValues<String>) -> Result<(), ReceiveError> {
+        if values.len() > self.max_values {
             return Err(ReceiveError::TooManyValues {
                 actual: values.len(),
-                max: Self::MAX_VALUES,
+                max: self.max_values,
             });
         }
         Ok(())
     }
 
     fn generate_fields<'a>(
+        &self,
         metadata: &'static Metadata<'static>,
         values: &'a TracedValues<String>,
+        ambient_fields: Option<&'a AmbientFields>,
     ) -> Vec<(Field, CowValue<'a>)> {
         let fields = metadata.fields();
+        let ambient_fields = ambient_fields
+            .into_iter()
+            .flat_map(|ambient_fields| ambient_fields.0.iter())
+            .filter(|(field_name, _)| values.get(field_name).is_none());
         values
             .iter()
+            .chain(ambient_fields)
             .filter_map(|(field_name, value)| {
                 fields
                     .field(field_name)
                     .map(|field| (field, value.as_value()))
             })
+            .take(self.max_values)
             .collect()
     }
 
@@ -379,32 +1642,109 @@ impl TracingEventReceiver {
         )
     }
 
-    fn on_new_call_site(&mut self, id: MetadataId, data: CallSiteData) {
+    fn on_new_call_site(&mut self, id: MetadataId, mut data: CallSiteData) {
+        if self.metadata_keying == MetadataKeying::ContentHash {
+            let hash = data.content_hash();
+            self.spans.call_site_hashes.0.insert(id, hash);
+        }
+
+        let static_metadata = self
+            .static_metadata
+            .iter()
+            .copied()
+            .find(|metadata| ReceivedMetadata::matches_static(&data, metadata));
+        if let Some(metadata) = static_metadata {
+            let received = ReceivedMetadata {
+                metadata,
+                own_field_count: data.fields.len(),
+            };
+            let prev = self.metadata.insert(id, received);
+            if prev.map_or(true, |prev| !ptr::eq(prev.metadata, metadata)) {
+                self.dispatches.for_each(|dispatch| {
+                    dispatch.register_callsite(metadata);
+                });
+            }
+            return;
+        }
+
+        let own_field_count = data.fields.len();
+        for (name, _) in &self.spans.ambient_fields.0 {
+            if !data.fields.iter().any(|field| field == name) {
+                data.fields.push(Cow::Owned(name.to_owned()));
+            }
+        }
+
         let (metadata, is_new) = ARENA.alloc_metadata(data);
-        self.metadata.insert(id, metadata);
-        if is_new {
-            Self::dispatch(|dispatch| dispatch.register_callsite(metadata));
+        let received = ReceivedMetadata {
+            metadata,
+            own_field_count,
+        };
+        let prev = self.metadata.insert(id, received);
+        let should_register = match &self.dispatches {
+            Dispatches::Default => is_new,
+            // Explicit dispatches may be unaware of the call site even if it's not new.
+            Dispatches::Explicit(_) => prev.map_or(true, |prev| !ptr::eq(prev.metadata, metadata)),
+        };
+        if should_register {
+            self.dispatches.for_each(|dispatch| {
+                dispatch.register_callsite(metadata);
+            });
         }
     }
 
-    fn create_local_span(&self, data: &SpanData) -> Result<Id, ReceiveError> {
+    fn create_local_span(&self, data: &SpanData) -> Result<Vec<Id>, ReceiveError> {
         let metadata = self.metadata(data.metadata_id)?;
-        let local_parent_id = data
+        let local_parent_ids = data
             .parent_id
             .map(|parent_id| self.map_span_id(parent_id))
             .transpose()?
             .flatten();
 
-        let value_set = Self::generate_fields(metadata, &data.values);
+        let ambient_fields = Some(&self.spans.ambient_fields);
+        let value_set = self.generate_fields(metadata, &data.values, ambient_fields);
         let value_set = Self::expand_fields(&value_set);
         let value_set = Self::create_values(metadata.fields(), &value_set);
-        let attributes = if let Some(local_parent_id) = local_parent_id {
-            Attributes::child_of(local_parent_id.clone(), metadata, &value_set)
-        } else {
-            Attributes::new(metadata, &value_set)
-        };
 
-        Ok(Self::dispatch(|dispatch| dispatch.new_span(&attributes)))
+        let mut local_ids = vec![];
+        let mut dispatch_idx = 0;
+        self.dispatches.for_each(|dispatch| {
+            let local_parent_id = if data.parent_id.is_some() {
+                local_parent_ids.and_then(|ids| ids.get(dispatch_idx))
+            } else {
+                self.root_parent(dispatch_idx, data.is_root)
+            };
+            dispatch_idx += 1;
+            let attributes = if let Some(local_parent_id) = local_parent_id {
+                Attributes::child_of(local_parent_id.clone(), metadata, &value_set)
+            } else if data.is_root || self.dispatch_mode == DispatchMode::Detached {
+                Attributes::new_root(metadata, &value_set)
+            } else {
+                Attributes::new(metadata, &value_set)
+            };
+            local_ids.push(dispatch.new_span(&attributes));
+        });
+        Ok(local_ids)
+    }
+
+    /// Creates the specified span in the local subscriber together with its ancestors,
+    /// unless they are already created.
+    fn materialize_span(&mut self, id: RawSpanId) -> Result<(), ReceiveError> {
+        let mut pending_ids = vec![];
+        let mut next_id = Some(id);
+        while let Some(id) = next_id {
+            if self.map_span_id(id)?.is_some() {
+                break;
+            }
+            pending_ids.push(id);
+            next_id = self.span(id)?.parent_id;
+        }
+
+        for id in pending_ids.into_iter().rev() {
+            let local_ids = self.create_local_span(self.span(id)?)?;
+            self.local_spans.inner.insert(id, local_ids);
+            self.counters.forwarded_spans += 1;
+        }
+        Ok(())
     }
 
     /// Tries to consume an event and relays it to the tracing infrastructure.
@@ -417,33 +1757,250 @@ impl TracingEventReceiver {
     /// not a [`TracingEventSender`]).
     ///
     /// [`TracingEventSender`]: crate::TracingEventSender
-    #[allow(clippy::missing_panics_doc, clippy::map_entry)] // false positive
-    pub fn try_receive(&mut self, event: TracingEvent) -> Result<(), ReceiveError> {
+    pub fn try_receive(&mut self, mut event: TracingEvent) -> Result<(), ReceiveError> {
+        self.counters.on_event(&event);
+        let result = if !self.interceptors.apply(&mut event) {
+            self.counters.intercepted_events += 1;
+            Ok(())
+        } else if self.parking.is_enabled() {
+            self.receive_with_parking(event)
+        } else {
+            self.relay_event(event)
+        };
+        if let Some(metrics) = &mut self.metrics.0 {
+            metrics.on_event_received(&self.counters);
+        }
+        result
+    }
+
+    fn relay_event(&mut self, event: TracingEvent) -> Result<(), ReceiveError> {
+        self.counters.dropped_values += self.dropped_values_count(&event) as u64;
+        let result = self
+            .check_generations(&event)
+            .and_then(|()| self.handle_event(event));
+        if result.is_err() {
+            self.counters.errors += 1;
+        }
+        result
+    }
+
+    /// Checks whether the event references an unknown call site.
+    fn has_unknown_call_site(&self, event: &TracingEvent) -> bool {
+        match event {
+            TracingEvent::NewSpan { metadata_id, .. }
+            | TracingEvent::NewEvent { metadata_id, .. }
+            | TracingEvent::SimpleEvent { metadata_id, .. } => {
+                !self.metadata.contains_key(metadata_id)
+            }
+            _ => false,
+        }
+    }
+
+    fn receive_with_parking(&mut self, event: TracingEvent) -> Result<(), ReceiveError> {
+        let should_park = if self.parking.events.is_empty() {
+            self.has_unknown_call_site(&event)
+        } else {
+            // Flush and shutdown markers are parked so that they are handled only after
+            // all preceding events are relayed.
+            !matches!(event, TracingEvent::NewCallSite { .. })
+                && (!Self::is_meta_event(&event)
+                    || matches!(
+                        event,
+                        TracingEvent::Flush { .. } | TracingEvent::Shutdown { .. }
+                    ))
+        };
+        let mut result = if should_park {
+            self.counters.parked_events += 1;
+            self.parking.events.push_back((event, Instant::now()));
+            Ok(())
+        } else {
+            self.relay_event(event)
+        };
+
+        let now = Instant::now();
+        loop {
+            let is_overdue = self.parking.is_overdue(now);
+            let Some((front, _)) = self.parking.events.front() else {
+                break;
+            };
+            if !is_overdue && self.has_unknown_call_site(front) {
+                break;
+            }
+            let (event, _) = self.parking.events.pop_front().unwrap();
+            let event_result = self.relay_event(event);
+            if result.is_ok() {
+                result = event_result;
+            }
+        }
+        result
+    }
+
+    /// Tries to consume a [`SequencedEvent`] produced by a [sequenced sender], verifying
+    /// its sequence number. The first expected sequence number is 0.
+    ///
+    /// - If the event has the expected sequence number, it is relayed together with
+    ///   the following events from the
+    ///   [reorder buffer](TracingEventReceiverBuilder::with_reorder_buffer()).
+    /// - If the event is ahead of the expected one by no more than the reorder buffer capacity,
+    ///   it is buffered until the missing events arrive. If the event is a flush or shutdown
+    ///   marker, or the [reorder timeout](TracingEventReceiverBuilder::with_reorder_timeout())
+    ///   has expired, the missing events are considered lost: buffered events are relayed in order,
+    ///   the expected sequence number is reset to follow them, and
+    ///   a [`ReceiveError::OutOfOrder`] error is returned.
+    /// - If the event is further ahead, the missing events are considered lost. The buffered events
+    ///   and the event itself are relayed, the expected sequence number is reset to follow
+    ///   the event, and a [`ReceiveError::OutOfOrder`] error is returned.
+    /// - If the event is behind the expected one (e.g., it is a duplicate, or it has arrived
+    ///   after the receiver has given up waiting for it), the event is discarded, and
+    ///   a [`ReceiveError::OutOfOrder`] error is returned.
+    ///
+    /// The sequencing state is not persisted, so a restored receiver expects sequence numbers
+    /// to start from 0 (i.e., the sender is expected to be restarted as well). Otherwise,
+    /// the first received event will result in an error, after which the receiver resyncs.
+    ///
+    /// [sequenced sender]: crate::TracingEventSender::sequenced()
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the event is out of order, or if relaying any event fails
+    /// (see [`Self::try_receive()`]). In the latter case, the remaining ready events
+    /// are still relayed, and the first error is returned.
+    pub fn try_receive_sequenced(&mut self, event: SequencedEvent) -> Result<(), ReceiveError> {
+        let (ready_events, sequencing_err) = self.sequencing.push(event);
+        let mut result = Ok(());
+        for event in ready_events {
+            let event_result = self.try_receive(event);
+            if result.is_ok() {
+                result = event_result;
+            }
+        }
+        if let Some(err) = sequencing_err {
+            self.counters.errors += 1;
+            result = Err(err);
+        }
+        result
+    }
+
+    /// Counts values in the event that will not be relayed to the tracing infrastructure.
+    fn dropped_values_count(&self, event: &TracingEvent) -> usize {
+        let (metadata_id, values, has_ambient_fields) = match event {
+            TracingEvent::NewSpan {
+                metadata_id,
+                values,
+                ..
+            }
+            | TracingEvent::NewEvent {
+                metadata_id,
+                values,
+                ..
+            } => (*metadata_id, values, true),
+            TracingEvent::ValuesRecorded { id, values } => {
+                let Some(span) = self.spans.inner.get(id) else {
+                    return 0;
+                };
+                (span.metadata_id, values, false)
+            }
+            _ => return 0,
+        };
+        let Ok(metadata) = self.metadata(metadata_id) else {
+            return 0;
+        };
+        if values.len() > self.max_values {
+            return 0; // the event will be rejected as a whole
+        }
+
+        let fields = metadata.fields();
+        let known_count = values
+            .iter()
+            .filter(|(name, _)| fields.field(name).is_some())
+            .count();
+        // Ambient fields may be unknown if the call site uses static metadata.
+        let (ambient_count, unknown_ambient_count) = if has_ambient_fields {
+            let ambient_fields = self.spans.ambient_fields.0.iter();
+            ambient_fields
+                .filter(|(name, _)| values.get(name).is_none())
+                .fold((0, 0), |(known, unknown), (name, _)| {
+                    if fields.field(name).is_some() {
+                        (known + 1, unknown)
+                    } else {
+                        (known, unknown + 1)
+                    }
+                })
+        } else {
+            (0, 0)
+        };
+        let overflow = (known_count + ambient_count).saturating_sub(self.max_values);
+        values.len() - known_count + unknown_ambient_count + overflow
+    }
+
+    /// Checks whether the event is a meta event not counted in heartbeat sequence numbers.
+    fn is_meta_event(event: &TracingEvent) -> bool {
+        matches!(
+            event,
+            TracingEvent::Heartbeat { .. }
+                | TracingEvent::EventsDropped { .. }
+                | TracingEvent::Flush { .. }
+                | TracingEvent::Shutdown { .. }
+        )
+    }
+
+    fn on_event_received(
+        &mut self,
+        metadata_id: MetadataId,
+        parent: Option<RawSpanId>,
+        is_root: bool,
+        values: &TracedValues<String>,
+    ) -> Result<(), ReceiveError> {
+        let parent = self.resolve_parent(parent, is_root);
+        if let Some(parent_id) = parent {
+            if self.lazy_spans && self.dispatches.is_enabled(self.metadata(metadata_id)?) {
+                self.materialize_span(parent_id)?;
+            }
+        }
+        self.on_new_event(metadata_id, parent, is_root, values)
+    }
+
+    fn handle_event(&mut self, event: TracingEvent) -> Result<(), ReceiveError> {
+        if !Self::is_meta_event(&event) {
+            self.liveness.on_event();
+            self.event_counter += 1;
+            self.touch_referenced_spans(&event);
+        }
+
         match event {
             TracingEvent::NewCallSite { id, data } => {
+                self.changes.on_call_site(id, self.metadata_keying);
                 self.on_new_call_site(id, data);
             }
 
             TracingEvent::NewSpan {
                 id,
                 parent_id,
+                is_root,
                 metadata_id,
                 values,
+                generation,
             } => {
-                Self::ensure_values_len(&values)?;
+                self.ensure_values_len(&values)?;
 
+                let parent_id = self.resolve_parent(parent_id, is_root);
                 let data = SpanData {
                     metadata_id,
                     parent_id,
+                    is_root,
                     ref_count: 1,
+                    child_count: 0,
                     values,
+                    generation,
+                    last_activity: self.event_counter,
                 };
-                if !self.local_spans.inner.contains_key(&id) {
-                    let local_id = self.create_local_span(&data)?;
-                    self.local_spans.inner.insert(id, local_id);
+                self.on_new_span(id, data)?;
+                if let Some(parent_id) = parent_id {
+                    if let Some(parent) = self.spans.inner.get_mut(&parent_id) {
+                        parent.child_count += 1;
+                        self.changes.on_span(parent_id);
+                    }
                 }
-                self.spans.inner.insert(id, data);
-                self.current_execution.uncommitted_span_ids.insert(id);
             }
 
             TracingEvent::FollowsFrom { id, follows_from } => {
@@ -451,31 +2008,18 @@ impl TracingEventReceiver {
                 let local_follows_from = self.map_span_id(follows_from)?;
 
                 // TODO: properly handle remaining cases
-                if let (Some(id), Some(follows_from)) = (local_id, local_follows_from) {
-                    Self::dispatch(|dispatch| {
-                        dispatch.record_follows_from(id, follows_from);
+                if let (Some(ids), Some(follows_from)) = (local_id, local_follows_from) {
+                    let mut follows_from = follows_from.iter();
+                    self.dispatches.for_each_span(ids, |dispatch, id| {
+                        if let Some(follows_from) = follows_from.next() {
+                            dispatch.record_follows_from(id, follows_from);
+                        }
                     });
                 }
             }
 
-            TracingEvent::SpanEntered { id } => {
-                let local_id = if let Some(id) = self.map_span_id(id)? {
-                    id.clone()
-                } else {
-                    let data = self.span(id)?;
-                    let local_id = self.create_local_span(data)?;
-                    self.local_spans.inner.insert(id, local_id.clone());
-                    local_id
-                };
-                self.current_execution.entered_span_ids.insert(id);
-                Self::dispatch(|dispatch| dispatch.enter(&local_id));
-            }
-            TracingEvent::SpanExited { id } => {
-                if let Some(local_id) = self.map_span_id(id)? {
-                    Self::dispatch(|dispatch| dispatch.exit(local_id));
-                }
-                self.current_execution.entered_span_ids.remove(&id);
-            }
+            TracingEvent::SpanEntered { id } => self.on_span_entered(id)?,
+            TracingEvent::SpanExited { id } => self.on_span_exited(id)?,
 
             TracingEvent::SpanCloned { id } => {
                 let span = self.span_mut(id)?;
@@ -484,73 +2028,277 @@ impl TracingEventReceiver {
             }
             TracingEvent::SpanDropped { id } => {
                 let span = self.span_mut(id)?;
-                span.ref_count -= 1;
                 if span.ref_count == 0 {
-                    self.spans.inner.remove(&id);
-                    self.current_execution.remove_span(id);
-                    if let Some(local_id) = self.local_spans.inner.remove(&id) {
-                        Self::dispatch(|dispatch| dispatch.try_close(local_id.clone()));
-                    }
+                    // The sender has already dropped all references to the span;
+                    // it's only kept alive by its children.
+                    return Err(ReceiveError::UnknownSpanId(id));
+                }
+                span.ref_count -= 1;
+                if span.ref_count == 0 && span.child_count == 0 {
+                    self.close_span(id);
                 }
             }
 
             TracingEvent::ValuesRecorded { id, values } => {
-                Self::ensure_values_len(&values)?;
-
-                if let Some(local_id) = self.map_span_id(id)? {
-                    let metadata = self.metadata(self.spans.inner[&id].metadata_id)?;
-                    let values = Self::generate_fields(metadata, &values);
-                    let values = Self::expand_fields(&values);
-                    let values = Self::create_values(metadata.fields(), &values);
-                    let values = Record::new(&values);
-                    Self::dispatch(|dispatch| dispatch.record(local_id, &values));
-                }
-                let span = self.span_mut(id)?;
-                span.values.extend(values);
+                self.on_values_recorded(id, values)?;
             }
 
             TracingEvent::NewEvent {
                 metadata_id,
                 parent,
+                is_root,
                 values,
             } => {
-                Self::ensure_values_len(&values)?;
-
-                let metadata = self.metadata(metadata_id)?;
-                let values = Self::generate_fields(metadata, &values);
-                let values = Self::expand_fields(&values);
-                let values = Self::create_values(metadata.fields(), &values);
-                let parent = parent.map(|id| self.map_span_id(id)).transpose()?.flatten();
-                let event = if let Some(parent) = parent {
-                    Event::new_child_of(parent.clone(), metadata, &values)
-                } else {
-                    Event::new(metadata, &values)
+                self.on_event_received(metadata_id, parent, is_root, &values)?;
+            }
+            TracingEvent::SimpleEvent {
+                metadata_id,
+                message,
+            } => {
+                let mut values = TracedValues::new();
+                let message = TracedValue::Object(DebugObject(message));
+                values.insert("message".to_owned(), message);
+                self.on_event_received(metadata_id, None, false, &values)?;
+            }
+
+            TracingEvent::Heartbeat { .. }
+            | TracingEvent::EventsDropped { .. }
+            | TracingEvent::Flush { .. }
+            | TracingEvent::Shutdown { .. } => {
+                self.handle_meta_event(&event);
+            }
+        }
+
+        if let Some(timeout) = self.idle_span_timeout {
+            self.drop_idle_spans(timeout);
+        }
+        Ok(())
+    }
+
+    fn handle_meta_event(&mut self, event: &TracingEvent) {
+        match *event {
+            TracingEvent::Heartbeat {
+                sequence,
+                dropped_events,
+            } => {
+                self.liveness.on_heartbeat(sequence, dropped_events);
+            }
+            TracingEvent::EventsDropped { count } => {
+                self.counters.reported_dropped_events += u64::from(count);
+            }
+            TracingEvent::Flush { sequence } | TracingEvent::Shutdown { sequence } => {
+                let status = FlushStatus {
+                    sequence,
+                    is_final: matches!(event, TracingEvent::Shutdown { .. }),
+                    received_at: Instant::now(),
                 };
-                Self::dispatch(|dispatch| dispatch.event(&event));
+                self.liveness.last_flush = Some(status);
+                if let Some(hook) = &mut self.flush_hook.0 {
+                    hook(&status);
+                }
+            }
+            _ => unreachable!("not a meta event"),
+        }
+    }
+
+    #[allow(clippy::map_entry)] // false positive
+    fn on_new_span(&mut self, id: RawSpanId, data: SpanData) -> Result<(), ReceiveError> {
+        if !self.lazy_spans && !self.local_spans.inner.contains_key(&id) {
+            let local_id = self.create_local_span(&data)?;
+            self.local_spans.inner.insert(id, local_id);
+            self.counters.forwarded_spans += 1;
+        }
+        self.spans.inner.insert(id, data);
+        self.changes.on_span(id);
+        self.current_execution.uncommitted_span_ids.insert(id);
+        Ok(())
+    }
+
+    fn on_span_entered(&mut self, id: RawSpanId) -> Result<(), ReceiveError> {
+        self.materialize_span(id)?;
+        self.current_execution.on_span_entered(id);
+        if self.dispatch_mode == DispatchMode::Entered {
+            let local_ids = &self.local_spans.inner[&id];
+            self.dispatches.for_each_span(local_ids, Dispatch::enter);
+        }
+        Ok(())
+    }
+
+    fn on_span_exited(&mut self, id: RawSpanId) -> Result<(), ReceiveError> {
+        if let Some(local_ids) = self.map_span_id(id)? {
+            if self.dispatch_mode == DispatchMode::Entered {
+                self.dispatches.for_each_span(local_ids, Dispatch::exit);
             }
         }
+        self.current_execution.on_span_exited(id);
+        Ok(())
+    }
+
+    fn on_values_recorded(
+        &mut self,
+        id: RawSpanId,
+        values: TracedValues<String>,
+    ) -> Result<(), ReceiveError> {
+        self.ensure_values_len(&values)?;
+
+        if let Some(local_ids) = self.map_span_id(id)? {
+            let metadata = self.metadata(self.spans.inner[&id].metadata_id)?;
+            let values = self.generate_fields(metadata, &values, None);
+            let values = Self::expand_fields(&values);
+            let values = Self::create_values(metadata.fields(), &values);
+            let values = Record::new(&values);
+            self.dispatches
+                .for_each_span(local_ids, |dispatch, local_id| {
+                    dispatch.record(local_id, &values);
+                });
+        }
+        let span = self.span_mut(id)?;
+        span.values.extend(values);
         Ok(())
     }
 
-    /// Consumes an event and relays it to the tracing infrastructure.
+    fn on_new_event(
+        &self,
+        metadata_id: MetadataId,
+        parent: Option<RawSpanId>,
+        is_root: bool,
+        values: &TracedValues<String>,
+    ) -> Result<(), ReceiveError> {
+        self.ensure_values_len(values)?;
+
+        let metadata = self.metadata(metadata_id)?;
+        let ambient_fields = Some(&self.spans.ambient_fields);
+        let values = self.generate_fields(metadata, values, ambient_fields);
+        let values = Self::expand_fields(&values);
+        let values = Self::create_values(metadata.fields(), &values);
+        let parents = parent.map(|id| self.map_span_id(id)).transpose()?.flatten();
+        let mut dispatch_idx = 0;
+        self.dispatches.for_each(|dispatch| {
+            let parent = if parent.is_some() {
+                parents.and_then(|ids| ids.get(dispatch_idx))
+            } else {
+                self.root_parent(dispatch_idx, is_root)
+            };
+            dispatch_idx += 1;
+            if self.lazy_spans && !dispatch.enabled(metadata) {
+                // The parent span may not be created in the dispatch, so the event
+                // would be relayed with an incorrect parent.
+                return;
+            }
+            let event = if let Some(parent) = parent {
+                Event::new_child_of(parent.clone(), metadata, &values)
+            } else if is_root || self.dispatch_mode == DispatchMode::Detached {
+                Event::new_child_of(None, metadata, &values)
+            } else {
+                Event::new(metadata, &values)
+            };
+            dispatch.event(&event);
+        });
+        Ok(())
+    }
+
+    /// Consumes an event and relays it to the tracing infrastructure. Errors are handled
+    /// according to the [error policy](TracingEventReceiverBuilder::with_error_policy()).
     ///
     /// # Panics
     ///
-    /// Panics in the same cases when [`Self::try_receive()`] returns an error.
+    /// With the default [`ErrorPolicy::Panic`], panics in the same cases when
+    /// [`Self::try_receive()`] returns an error.
     pub fn receive(&mut self, event: TracingEvent) {
-        self.try_receive(event)
-            .expect("received bogus tracing event");
+        if let Err(err) = self.try_receive(event) {
+            match self.error_policy {
+                ErrorPolicy::Ignore => { /* do nothing */ }
+                ErrorPolicy::LogToHost => errors::log_to_host(&err),
+                ErrorPolicy::Panic => panic!("received bogus tracing event: {err:?}"),
+                ErrorPolicy::Collect => self.errors.push(err),
+            }
+        }
     }
 
     /// Persists [`Metadata`] produced by the previously consumed events. The returned
     /// metadata should be merged into the metadata provided to [`Self::new()`].
+    ///
+    /// Call sites in the returned metadata are keyed according to the
+    /// [configured keying](TracingEventReceiverBuilder::with_metadata_keying()).
     pub fn persist_metadata(&self) -> PersistedMetadata {
         let inner = self
             .metadata
             .iter()
-            .map(|(&id, &metadata)| (id, CallSiteData::from(metadata)))
+            .map(|(&id, received)| self.persisted_call_site(id, received))
             .collect();
-        PersistedMetadata { inner }
+        PersistedMetadata {
+            inner,
+            keying: self.metadata_keying,
+        }
+    }
+
+    fn persisted_call_site(
+        &self,
+        id: MetadataId,
+        received: &ReceivedMetadata,
+    ) -> (u64, CallSiteData) {
+        let data = received.call_site_data();
+        match self.metadata_keying {
+            MetadataKeying::Numeric => (id, data),
+            MetadataKeying::ContentHash => {
+                // Use the hash of the data received from the sender. The data reconstructed
+                // from metadata may differ from it if static metadata is reused.
+                let hash = self.spans.call_site_hashes.0.get(&id).copied();
+                (hash.unwrap_or_else(|| data.content_hash()), data)
+            }
+        }
+    }
+
+    /// Returns call sites received since the previous call to this method, so that they can be
+    /// saved to a [`MetadataStore`] without copying all metadata. On the first call,
+    /// returns the same metadata as [`Self::persist_metadata()`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_tunnel::{
+    /// #     LocalSpans, MetadataStore, PersistedMetadata, PersistedSpans, SpanStore,
+    /// #     TracingEventReceiver,
+    /// # };
+    /// // In-memory stores; in real apps, stores would be backed by a database or files.
+    /// let mut metadata_store = PersistedMetadata::default();
+    /// let mut span_store = PersistedSpans::default();
+    /// let mut receiver = TracingEventReceiver::default();
+    /// // Receive some events...
+    /// metadata_store.save_metadata(receiver.take_metadata_changes()).unwrap();
+    /// span_store.save_spans(receiver.take_span_changes()).unwrap();
+    /// // Receive more events; only changes since the last save will be saved.
+    /// metadata_store.save_metadata(receiver.take_metadata_changes()).unwrap();
+    /// span_store.save_spans(receiver.take_span_changes()).unwrap();
+    ///
+    /// // Later, possibly after restarting the host:
+    /// let metadata = metadata_store.load_metadata().unwrap();
+    /// let spans = span_store.load_spans().unwrap();
+    /// let receiver = TracingEventReceiver::new(metadata, spans, LocalSpans::default());
+    /// ```
+    pub fn take_metadata_changes(&mut self) -> PersistedMetadata {
+        let Some(ids) = self.changes.take_call_site_ids() else {
+            return self.persist_metadata();
+        };
+        let inner = ids
+            .into_iter()
+            .filter_map(|id| {
+                let received = self.metadata.get(&id)?;
+                Some(self.persisted_call_site(id, received))
+            })
+            .collect();
+        PersistedMetadata {
+            inner,
+            keying: self.metadata_keying,
+        }
+    }
+
+    /// Returns changes in [`PersistedSpans`] since the previous call to this method, so that
+    /// they can be saved to a [`SpanStore`] without copying all spans. On the first call,
+    /// returns a [snapshot](SpanChanges::is_snapshot()) of all spans.
+    /// See [`Self::take_metadata_changes()`] for an example.
+    pub fn take_span_changes(&mut self) -> SpanChanges {
+        self.changes.take_span_changes(&self.spans)
     }
 
     /// Returns persisted and local spans.
@@ -558,13 +2306,15 @@ impl TracingEventReceiver {
         self.current_execution.uncommitted_span_ids.clear();
         let spans = mem::take(&mut self.spans);
         let local_spans = mem::take(&mut self.local_spans);
-        self.current_execution.finalize(&local_spans);
+        self.current_execution
+            .finalize(&self.dispatches, &local_spans, self.dispatch_mode);
         (spans, local_spans)
     }
 }
 
 impl Drop for TracingEventReceiver {
     fn drop(&mut self) {
-        self.current_execution.finalize(&self.local_spans);
+        self.current_execution
+            .finalize(&self.dispatches, &self.local_spans, self.dispatch_mode);
     }
 }
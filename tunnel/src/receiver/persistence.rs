@@ -0,0 +1,245 @@
+//! Versioned (de)serialization for `PersistedMetadata` and `PersistedSpans`.
+
+use serde::{
+    de::{self, DeserializeOwned, IgnoredAny, MapAccess, Visitor},
+    ser::SerializeMap,
+    Deserializer, Serialize, Serializer,
+};
+
+use std::{collections::HashMap, fmt, marker::PhantomData};
+
+/// Current version of the persisted state format.
+pub(super) const FORMAT_VERSION: u32 = 1;
+const VERSION_FIELD: &str = "version";
+
+/// Optional top-level fields serialized alongside entries in the versioned format.
+pub(super) trait SerializeExtras {
+    /// Returns the number of fields serialized by [`Self::serialize_fields()`].
+    fn field_count(&self) -> usize;
+
+    /// Serializes non-default fields into the provided `map`.
+    fn serialize_fields<M: SerializeMap>(&self, map: &mut M) -> Result<(), M::Error>;
+}
+
+/// Optional top-level fields deserialized alongside entries in the versioned format.
+pub(super) trait ExtraFields: SerializeExtras + Default {
+    /// Deserializes the value of a field with the specified name. Returns `Ok(false)`
+    /// if the field is unknown.
+    fn deserialize_field<'de, A: MapAccess<'de>>(
+        &mut self,
+        name: &str,
+        map: &mut A,
+    ) -> Result<bool, A::Error>;
+}
+
+impl SerializeExtras for () {
+    fn field_count(&self) -> usize {
+        0
+    }
+
+    fn serialize_fields<M: SerializeMap>(&self, _map: &mut M) -> Result<(), M::Error> {
+        Ok(())
+    }
+}
+
+impl ExtraFields for () {
+    fn deserialize_field<'de, A: MapAccess<'de>>(
+        &mut self,
+        _name: &str,
+        _map: &mut A,
+    ) -> Result<bool, A::Error> {
+        Ok(false)
+    }
+}
+
+impl<T: SerializeExtras> SerializeExtras for &T {
+    fn field_count(&self) -> usize {
+        (**self).field_count()
+    }
+
+    fn serialize_fields<M: SerializeMap>(&self, map: &mut M) -> Result<(), M::Error> {
+        (**self).serialize_fields(map)
+    }
+}
+
+impl<T: SerializeExtras, U: SerializeExtras> SerializeExtras for (T, U) {
+    fn field_count(&self) -> usize {
+        self.0.field_count() + self.1.field_count()
+    }
+
+    fn serialize_fields<M: SerializeMap>(&self, map: &mut M) -> Result<(), M::Error> {
+        self.0.serialize_fields(map)?;
+        self.1.serialize_fields(map)
+    }
+}
+
+impl<T: ExtraFields, U: ExtraFields> ExtraFields for (T, U) {
+    fn deserialize_field<'de, A: MapAccess<'de>>(
+        &mut self,
+        name: &str,
+        map: &mut A,
+    ) -> Result<bool, A::Error> {
+        Ok(self.0.deserialize_field(name, map)? || self.1.deserialize_field(name, map)?)
+    }
+}
+
+/// Serializes `entries` in the versioned format, i.e., as a map with the `version` field,
+/// the `entries_field` containing the entries, and `extras`.
+pub(super) fn serialize<S, V, X>(
+    serializer: S,
+    entries_field: &'static str,
+    entries: &HashMap<u64, V>,
+    extras: &X,
+) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+    V: Serialize,
+    X: SerializeExtras,
+{
+    // The map length is known upfront so that the state can be serialized
+    // with formats that require it (e.g., `bincode`).
+    let mut map = serializer.serialize_map(Some(2 + extras.field_count()))?;
+    map.serialize_entry(VERSION_FIELD, &FORMAT_VERSION)?;
+    map.serialize_entry(entries_field, entries)?;
+    extras.serialize_fields(&mut map)?;
+    map.end()
+}
+
+/// Deserializes entries and extra fields either from the versioned format, or from
+/// the legacy format (version 0), in which entries were serialized as a map directly.
+pub(super) fn deserialize<'de, D, V, X>(
+    deserializer: D,
+    entries_field: &'static str,
+) -> Result<(HashMap<u64, V>, X), D::Error>
+where
+    D: Deserializer<'de>,
+    V: DeserializeOwned,
+    X: ExtraFields,
+{
+    deserializer.deserialize_map(VersionedVisitor {
+        entries_field,
+        _entries: PhantomData,
+    })
+}
+
+#[derive(Debug)]
+enum Key {
+    Version,
+    Entries,
+    LegacyId(u64),
+    Other(String),
+}
+
+struct KeySeed {
+    entries_field: &'static str,
+}
+
+impl<'de> de::DeserializeSeed<'de> for KeySeed {
+    type Value = Key;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_str(self)
+    }
+}
+
+impl Visitor<'_> for KeySeed {
+    type Value = Key;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            formatter,
+            "`{VERSION_FIELD}`, `{}` or a numeric ID",
+            self.entries_field
+        )
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<Self::Value, E> {
+        Ok(Key::LegacyId(value))
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+        Ok(if value == VERSION_FIELD {
+            Key::Version
+        } else if value == self.entries_field {
+            Key::Entries
+        } else if let Ok(id) = value.parse() {
+            Key::LegacyId(id)
+        } else {
+            Key::Other(value.to_owned())
+        })
+    }
+}
+
+struct VersionedVisitor<V, X> {
+    entries_field: &'static str,
+    _entries: PhantomData<fn() -> (V, X)>,
+}
+
+impl<'de, V: DeserializeOwned, X: ExtraFields> Visitor<'de> for VersionedVisitor<V, X> {
+    type Value = (HashMap<u64, V>, X);
+
+    fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            formatter,
+            "map with `{VERSION_FIELD}` and `{}` fields",
+            self.entries_field
+        )
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut version = None;
+        let mut entries = None;
+        let mut legacy_entries = HashMap::new();
+        let mut extras = X::default();
+
+        let key_seed = || KeySeed {
+            entries_field: self.entries_field,
+        };
+        while let Some(key) = map.next_key_seed(key_seed())? {
+            match key {
+                Key::Version => {
+                    if version.is_some() {
+                        return Err(de::Error::duplicate_field(VERSION_FIELD));
+                    }
+                    version = Some(map.next_value::<u32>()?);
+                }
+                Key::Entries => {
+                    if entries.is_some() {
+                        return Err(de::Error::duplicate_field(self.entries_field));
+                    }
+                    entries = Some(map.next_value()?);
+                }
+                Key::LegacyId(id) => {
+                    legacy_entries.insert(id, map.next_value()?);
+                }
+                Key::Other(name) => {
+                    if !extras.deserialize_field(&name, &mut map)? {
+                        // Unknown fields are ignored for forward compatibility.
+                        map.next_value::<IgnoredAny>()?;
+                    }
+                }
+            }
+        }
+
+        let Some(version) = version else {
+            // Legacy format: entries are stored directly in the map.
+            return if entries.is_some() {
+                Err(de::Error::missing_field(VERSION_FIELD))
+            } else {
+                Ok((legacy_entries, extras))
+            };
+        };
+        if version > FORMAT_VERSION {
+            let msg = format!(
+                "unsupported format version {version}; the latest supported version \
+                 is {FORMAT_VERSION}"
+            );
+            return Err(de::Error::custom(msg));
+        }
+        if !legacy_entries.is_empty() {
+            let msg = "numeric IDs are not allowed as top-level keys in the versioned format";
+            return Err(de::Error::custom(msg));
+        }
+        Ok((entries.unwrap_or_default(), extras))
+    }
+}
@@ -0,0 +1,463 @@
+//! Durable storage for the persisted `TracingEventReceiver` state.
+
+use serde::{Deserialize, Serialize};
+
+use std::{
+    collections::{HashMap, HashSet},
+    convert::Infallible,
+};
+
+use super::{MetadataKeying, PersistedMetadata, PersistedSpans, SpanData};
+use crate::{MetadataId, RawSpanId, TracedValues};
+
+/// Storage for [`PersistedMetadata`], e.g., backed by a database or a file.
+///
+/// Rather than serializing entire metadata on each flush, a store receives incremental changes
+/// obtained via [`TracingEventReceiver::take_metadata_changes()`].
+///
+/// The trait is implemented for `PersistedMetadata` itself, which can be used as an in-memory
+/// store (e.g., in tests).
+///
+/// # Synchronous design
+///
+/// `MetadataStore` and [`SpanStore`] are synchronous on purpose. The receiver itself
+/// is synchronous and does not depend on an async runtime, and async functions in traits
+/// are not supported by the minimum supported Rust version. Instead, changes are owned
+/// and `Send`, and the store does not borrow the receiver; thus, saving changes can be offloaded
+/// to a background thread, or to a blocking task of an async runtime (e.g.,
+/// `tokio::task::spawn_blocking`), without blocking event processing.
+///
+/// ```
+/// # use std::{sync::mpsc, thread};
+/// # use tracing_tunnel::{
+/// #     MetadataStore, PersistedMetadata, PersistedSpans, SpanChanges, SpanStore,
+/// #     TracingEventReceiver,
+/// # };
+/// let (changes_sx, changes_rx) = mpsc::channel::<(PersistedMetadata, SpanChanges)>();
+/// let saver = thread::spawn(move || {
+///     let (mut metadata_store, mut span_store) =
+///         (PersistedMetadata::default(), PersistedSpans::default());
+///     for (metadata_changes, span_changes) in changes_rx {
+///         metadata_store.save_metadata(metadata_changes).unwrap();
+///         span_store.save_spans(span_changes).unwrap();
+///     }
+///     (metadata_store, span_store)
+/// });
+///
+/// let mut receiver = TracingEventReceiver::default();
+/// // Receive events...
+/// let changes = (receiver.take_metadata_changes(), receiver.take_span_changes());
+/// changes_sx.send(changes).unwrap();
+/// drop(changes_sx);
+/// let (metadata, spans) = saver.join().unwrap();
+/// # assert!(metadata.is_empty() && spans.is_empty());
+/// ```
+///
+/// [`TracingEventReceiver::take_metadata_changes()`]: crate::TracingEventReceiver::take_metadata_changes()
+pub trait MetadataStore {
+    /// Error that can occur when loading or saving metadata.
+    type Error;
+
+    /// Loads all persisted metadata.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the metadata cannot be loaded.
+    fn load_metadata(&mut self) -> Result<PersistedMetadata, Self::Error>;
+
+    /// Saves new call sites. `changes` should be merged into the stored metadata
+    /// as per [`PersistedMetadata::extend()`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the changes cannot be saved.
+    fn save_metadata(&mut self, changes: PersistedMetadata) -> Result<(), Self::Error>;
+}
+
+impl MetadataStore for PersistedMetadata {
+    type Error = Infallible;
+
+    fn load_metadata(&mut self) -> Result<PersistedMetadata, Self::Error> {
+        Ok(self.clone())
+    }
+
+    fn save_metadata(&mut self, changes: PersistedMetadata) -> Result<(), Self::Error> {
+        self.extend(changes);
+        Ok(())
+    }
+}
+
+/// Storage for [`PersistedSpans`] of a particular execution, e.g., backed by a database or a file.
+///
+/// Similar to [`MetadataStore`], a store receives incremental [`SpanChanges`] obtained
+/// via [`TracingEventReceiver::take_span_changes()`]. See [`MetadataStore`] docs on why
+/// the trait is synchronous and how to save changes in the background.
+///
+/// The trait is implemented for `PersistedSpans` itself, which can be used as an in-memory store.
+///
+/// [`TracingEventReceiver::take_span_changes()`]: crate::TracingEventReceiver::take_span_changes()
+pub trait SpanStore {
+    /// Error that can occur when loading or saving spans.
+    type Error;
+
+    /// Loads persisted spans.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the spans cannot be loaded.
+    fn load_spans(&mut self) -> Result<PersistedSpans, Self::Error>;
+
+    /// Saves changes in spans. `changes` should be applied to the stored spans
+    /// as per [`PersistedSpans::apply()`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the changes cannot be saved.
+    fn save_spans(&mut self, changes: SpanChanges) -> Result<(), Self::Error>;
+}
+
+impl SpanStore for PersistedSpans {
+    type Error = Infallible;
+
+    fn load_spans(&mut self) -> Result<PersistedSpans, Self::Error> {
+        Ok(self.clone())
+    }
+
+    fn save_spans(&mut self, changes: SpanChanges) -> Result<(), Self::Error> {
+        self.apply(changes);
+        Ok(())
+    }
+}
+
+/// Changes in [`PersistedSpans`] since the previous call to
+/// [`TracingEventReceiver::take_span_changes()`].
+///
+/// Changes are (de)serializable, so they can be stored as is, e.g., in an append-only log
+/// that is replayed via [`PersistedSpans::apply()`] on load.
+///
+/// [`TracingEventReceiver::take_span_changes()`]: crate::TracingEventReceiver::take_span_changes()
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SpanChanges {
+    #[serde(default, skip_serializing_if = "is_false")]
+    is_snapshot: bool,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    updated: HashMap<RawSpanId, SpanData>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    removed: Vec<RawSpanId>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ambient_fields: Option<TracedValues<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    call_site_hashes: Option<HashMap<MetadataId, u64>>,
+}
+
+#[allow(clippy::trivially_copy_pass_by_ref)] // required by `serde`
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+impl SpanChanges {
+    fn snapshot(spans: &PersistedSpans) -> Self {
+        Self {
+            is_snapshot: true,
+            updated: spans.inner.clone(),
+            removed: Vec::new(),
+            ambient_fields: Some(spans.ambient_fields.0.clone()),
+            call_site_hashes: Some(spans.call_site_hashes.0.clone()),
+        }
+    }
+
+    fn diff(spans: &PersistedSpans, changed_ids: HashSet<RawSpanId>, with_extras: bool) -> Self {
+        let mut updated = HashMap::new();
+        let mut removed = Vec::new();
+        for id in changed_ids {
+            if let Some(span) = spans.inner.get(&id) {
+                updated.insert(id, span.clone());
+            } else {
+                removed.push(id);
+            }
+        }
+        removed.sort_unstable();
+
+        Self {
+            is_snapshot: false,
+            updated,
+            removed,
+            ambient_fields: with_extras.then(|| spans.ambient_fields.0.clone()),
+            call_site_hashes: with_extras.then(|| spans.call_site_hashes.0.clone()),
+        }
+    }
+
+    /// Checks whether these changes are a snapshot of all spans, i.e., spans not mentioned
+    /// in the changes should be removed from the store.
+    pub fn is_snapshot(&self) -> bool {
+        self.is_snapshot
+    }
+
+    /// Checks whether there are no changes.
+    pub fn is_empty(&self) -> bool {
+        !self.is_snapshot
+            && self.updated.is_empty()
+            && self.removed.is_empty()
+            && self.ambient_fields.is_none()
+            && self.call_site_hashes.is_none()
+    }
+
+    /// Iterates over IDs of spans that were created or updated.
+    pub fn updated_spans(&self) -> impl Iterator<Item = RawSpanId> + '_ {
+        self.updated.keys().copied()
+    }
+
+    /// Returns IDs of spans that were removed (e.g., closed).
+    pub fn removed_spans(&self) -> &[RawSpanId] {
+        &self.removed
+    }
+}
+
+impl PersistedSpans {
+    /// Applies changes obtained from [`TracingEventReceiver::take_span_changes()`].
+    ///
+    /// [`TracingEventReceiver::take_span_changes()`]: crate::TracingEventReceiver::take_span_changes()
+    pub fn apply(&mut self, changes: SpanChanges) {
+        if changes.is_snapshot {
+            self.inner.clear();
+        }
+        for id in changes.removed {
+            self.inner.remove(&id);
+        }
+        self.inner.extend(changes.updated);
+        if let Some(ambient_fields) = changes.ambient_fields {
+            self.ambient_fields.0 = ambient_fields;
+        }
+        if let Some(call_site_hashes) = changes.call_site_hashes {
+            self.call_site_hashes.0 = call_site_hashes;
+        }
+    }
+}
+
+/// Tracker of changes in the receiver state since the last call to
+/// `take_metadata_changes()` / `take_span_changes()`. Tracking starts on the first call;
+/// before that, the entire state is considered changed.
+#[derive(Debug, Default)]
+pub(super) struct ChangeTracker {
+    call_site_ids: Option<HashSet<MetadataId>>,
+    span_ids: Option<HashSet<RawSpanId>>,
+    span_extras_changed: bool,
+}
+
+impl ChangeTracker {
+    pub(super) fn on_call_site(&mut self, id: MetadataId, keying: MetadataKeying) {
+        if let Some(ids) = &mut self.call_site_ids {
+            ids.insert(id);
+        }
+        if keying == MetadataKeying::ContentHash {
+            // The call site hash is persisted together with spans.
+            self.span_extras_changed = true;
+        }
+    }
+
+    pub(super) fn on_span(&mut self, id: RawSpanId) {
+        if let Some(ids) = &mut self.span_ids {
+            ids.insert(id);
+        }
+    }
+
+    pub(super) fn on_span_extras(&mut self) {
+        self.span_extras_changed = true;
+    }
+
+    /// Returns `None` if all call sites should be considered changed.
+    pub(super) fn take_call_site_ids(&mut self) -> Option<HashSet<MetadataId>> {
+        self.call_site_ids.replace(HashSet::new())
+    }
+
+    pub(super) fn take_span_changes(&mut self, spans: &PersistedSpans) -> SpanChanges {
+        let with_extras = std::mem::take(&mut self.span_extras_changed);
+        match self.span_ids.replace(HashSet::new()) {
+            None => SpanChanges::snapshot(spans),
+            Some(ids) => SpanChanges::diff(spans, ids, with_extras),
+        }
+    }
+}
+
+#[cfg(feature = "file-store")]
+mod file {
+    use std::{
+        fs,
+        io::{self, BufRead, BufReader, Write},
+        path::{Path, PathBuf},
+    };
+
+    use super::{MetadataStore, SpanChanges, SpanStore};
+    use crate::{PersistedMetadata, PersistedSpans};
+
+    /// File-backed [`MetadataStore`] and [`SpanStore`].
+    ///
+    /// Metadata and spans are stored in separate files in the specified directory as append-only
+    /// logs of changes, one JSON object per line. Thus, saving changes does not require
+    /// rewriting the entire state. Logs are replayed on load; they can be compacted
+    /// via [`Self::compact()`]. If the process crashes while saving changes, the final line
+    /// of a log may be incomplete; such a line is discarded (and truncated from the log)
+    /// on load, i.e., the corresponding changes are lost.
+    ///
+    /// Since [`PersistedSpans`] are specific to an execution, each execution should use
+    /// a separate span store; metadata can be shared among executions of the same executable
+    /// by using the same directory for the metadata.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_tunnel::{
+    /// #     FileStore, LocalSpans, MetadataStore, SpanStore, TracingEvent, TracingEventReceiver,
+    /// # };
+    /// # fn main() -> std::io::Result<()> {
+    /// # let dir = std::env::temp_dir().join(format!("tracing-tunnel-doc-{}", std::process::id()));
+    /// let mut store = FileStore::new(&dir)?;
+    /// let metadata = store.load_metadata()?;
+    /// let spans = store.load_spans()?;
+    /// let mut receiver = TracingEventReceiver::new(metadata, spans, LocalSpans::default());
+    ///
+    /// let events: Vec<TracingEvent> = // ...
+    /// #   vec![];
+    /// for event in events {
+    ///     receiver.receive(event);
+    /// }
+    /// // Periodically save changes, e.g., on flush markers.
+    /// store.save_metadata(receiver.take_metadata_changes())?;
+    /// store.save_spans(receiver.take_span_changes())?;
+    /// # std::fs::remove_dir_all(&dir)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[derive(Debug, Clone)]
+    #[cfg_attr(docsrs, doc(cfg(feature = "file-store")))]
+    pub struct FileStore {
+        metadata_path: PathBuf,
+        spans_path: PathBuf,
+    }
+
+    impl FileStore {
+        const METADATA_FILE: &'static str = "metadata.jsonl";
+        const SPANS_FILE: &'static str = "spans.jsonl";
+
+        /// Creates a store in the specified directory. The directory is created if necessary.
+        ///
+        /// # Errors
+        ///
+        /// Returns an I/O error if the directory cannot be created.
+        pub fn new(dir: impl AsRef<Path>) -> io::Result<Self> {
+            let dir = dir.as_ref();
+            fs::create_dir_all(dir)?;
+            Ok(Self {
+                metadata_path: dir.join(Self::METADATA_FILE),
+                spans_path: dir.join(Self::SPANS_FILE),
+            })
+        }
+
+        /// Compacts the change logs, so that each contains a single entry with the current state.
+        ///
+        /// # Errors
+        ///
+        /// Returns an I/O error if the logs cannot be read or rewritten.
+        pub fn compact(&mut self) -> io::Result<()> {
+            let metadata = self.load_metadata()?;
+            rewrite_log(&self.metadata_path, &metadata)?;
+            let spans = self.load_spans()?;
+            rewrite_log(&self.spans_path, &SpanChanges::snapshot(&spans))
+        }
+    }
+
+    /// Reads a log, applying its entries in order. An entry is committed once its terminating
+    /// newline is written, so an unterminated final line (e.g., one torn by a crash during
+    /// appending) is discarded and truncated, so that subsequent entries are appended
+    /// after the last committed one.
+    fn read_log<T: serde::de::DeserializeOwned>(
+        path: &Path,
+        mut apply: impl FnMut(T),
+    ) -> io::Result<()> {
+        let file = match fs::File::open(path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(err),
+        };
+        let mut reader = BufReader::new(file);
+        let mut line = Vec::new();
+        let mut committed_len = 0_u64;
+        loop {
+            line.clear();
+            let line_len = reader.read_until(b'\n', &mut line)?;
+            if line_len == 0 {
+                return Ok(());
+            }
+            if line.last() != Some(&b'\n') {
+                break;
+            }
+            committed_len += line_len as u64;
+            if !line.iter().all(u8::is_ascii_whitespace) {
+                apply(serde_json::from_slice(&line)?);
+            }
+        }
+
+        fs::OpenOptions::new()
+            .write(true)
+            .open(path)?
+            .set_len(committed_len)
+    }
+
+    fn append_to_log<T: serde::Serialize>(path: &Path, entry: &T) -> io::Result<()> {
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        file.write_all(&line)?;
+        file.sync_data()
+    }
+
+    fn rewrite_log<T: serde::Serialize>(path: &Path, entry: &T) -> io::Result<()> {
+        let tmp_path = path.with_extension("jsonl.tmp");
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+        let mut file = fs::File::create(&tmp_path)?;
+        file.write_all(&line)?;
+        file.sync_data()?;
+        fs::rename(tmp_path, path)
+    }
+
+    impl MetadataStore for FileStore {
+        type Error = io::Error;
+
+        fn load_metadata(&mut self) -> io::Result<PersistedMetadata> {
+            let mut metadata = PersistedMetadata::default();
+            read_log(&self.metadata_path, |changes| metadata.extend(changes))?;
+            Ok(metadata)
+        }
+
+        fn save_metadata(&mut self, changes: PersistedMetadata) -> io::Result<()> {
+            if changes.is_empty() {
+                return Ok(());
+            }
+            append_to_log(&self.metadata_path, &changes)
+        }
+    }
+
+    impl SpanStore for FileStore {
+        type Error = io::Error;
+
+        fn load_spans(&mut self) -> io::Result<PersistedSpans> {
+            let mut spans = PersistedSpans::default();
+            read_log(&self.spans_path, |changes| spans.apply(changes))?;
+            Ok(spans)
+        }
+
+        fn save_spans(&mut self, changes: SpanChanges) -> io::Result<()> {
+            if changes.is_empty() {
+                return Ok(());
+            }
+            append_to_log(&self.spans_path, &changes)
+        }
+    }
+}
+
+#[cfg(feature = "file-store")]
+pub use self::file::FileStore;
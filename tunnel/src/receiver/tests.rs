@@ -2,9 +2,14 @@
 
 use assert_matches::assert_matches;
 
-use std::borrow::Cow;
+use std::{
+    borrow::Cow,
+    sync::{Arc, Mutex},
+};
 
 use super::*;
+use tracing_core::{callsite::DefaultCallsite, Kind};
+
 use crate::{CallSiteKind, TracingLevel};
 
 const CALL_SITE_DATA: CallSiteData = create_call_site(Vec::new());
@@ -49,8 +54,10 @@ fn unknown_metadata_error() {
     let event = TracingEvent::NewSpan {
         id: 0,
         parent_id: None,
+        is_root: false,
         metadata_id: 0,
         values: TracedValues::new(),
+        generation: None,
     };
     let mut receiver = TracingEventReceiver::default();
     let err = receiver.try_receive(event).unwrap_err();
@@ -66,12 +73,15 @@ fn unknown_span_errors() {
         TracingEvent::NewSpan {
             id: 42,
             parent_id: Some(1),
+            is_root: false,
             metadata_id: 0,
             values: TracedValues::new(),
+            generation: None,
         },
         TracingEvent::NewEvent {
             metadata_id: 0,
             parent: Some(1),
+            is_root: false,
             values: TracedValues::new(),
         },
         TracingEvent::ValuesRecorded {
@@ -91,6 +101,303 @@ fn unknown_span_errors() {
     }
 }
 
+fn new_span_event(id: RawSpanId, parent_id: Option<RawSpanId>) -> TracingEvent {
+    TracingEvent::NewSpan {
+        id,
+        parent_id,
+        is_root: false,
+        metadata_id: 0,
+        values: TracedValues::new(),
+        generation: None,
+    }
+}
+
+fn sorted_alive_spans(receiver: &TracingEventReceiver) -> Vec<AliveSpan> {
+    let mut spans: Vec<_> = receiver.alive_spans().collect();
+    spans.sort_unstable_by_key(|span| span.id);
+    spans
+}
+
+#[test]
+fn inspecting_and_force_dropping_spans() {
+    let mut receiver = TracingEventReceiver::default();
+    receiver.receive(TracingEvent::NewCallSite {
+        id: 0,
+        data: CALL_SITE_DATA,
+    });
+    receiver.receive(new_span_event(1, None));
+    receiver.receive(new_span_event(2, Some(1)));
+    receiver.receive(TracingEvent::SpanCloned { id: 1 });
+    receiver.receive(TracingEvent::SpanEntered { id: 1 });
+
+    let spans = sorted_alive_spans(&receiver);
+    assert_eq!(spans.len(), 2);
+    assert_eq!(spans[0].id, 1);
+    assert_eq!(spans[0].ref_count, 2);
+    assert!(spans[0].is_entered);
+    assert_eq!(spans[0].idle_events, 0);
+    assert_eq!(spans[1].id, 2);
+    assert_eq!(spans[1].ref_count, 1);
+    assert!(!spans[1].is_entered);
+    assert_eq!(spans[1].idle_events, 2);
+
+    receiver.force_drop(1).unwrap();
+    assert!(!receiver.local_spans.inner.contains_key(&1));
+    assert!(receiver.current_execution.entered_span_ids.is_empty());
+    let spans = sorted_alive_spans(&receiver);
+    assert_eq!(spans.len(), 1);
+    assert_eq!(spans[0].id, 2);
+    assert_eq!(receiver.spans.inner[&2].parent_id, None);
+
+    let err = receiver.force_drop(1).unwrap_err();
+    assert_matches!(err, ReceiveError::UnknownSpanId(1));
+    let err = receiver
+        .try_receive(TracingEvent::SpanExited { id: 1 })
+        .unwrap_err();
+    assert_matches!(err, ReceiveError::UnknownSpanId(1));
+
+    // The child span can still be used.
+    receiver.receive(TracingEvent::SpanEntered { id: 2 });
+    receiver.receive(TracingEvent::SpanExited { id: 2 });
+    receiver.receive(TracingEvent::SpanDropped { id: 2 });
+    assert_eq!(receiver.alive_spans().count(), 0);
+}
+
+#[test]
+fn child_spans_keep_parent_alive() {
+    let mut receiver = TracingEventReceiver::default();
+    receiver.receive(TracingEvent::NewCallSite {
+        id: 0,
+        data: CALL_SITE_DATA,
+    });
+    receiver.receive(new_span_event(1, None));
+    receiver.receive(new_span_event(2, Some(1)));
+    receiver.receive(TracingEvent::SpanCloned { id: 2 });
+    // The sender drops the parent span before its child (e.g., the child is moved into a task).
+    receiver.receive(TracingEvent::SpanDropped { id: 1 });
+
+    let spans = sorted_alive_spans(&receiver);
+    assert_eq!(spans.len(), 2);
+    assert_eq!(spans[0].ref_count, 0);
+    assert_eq!(spans[0].child_count, 1);
+    assert_eq!(spans[1].ref_count, 2);
+    let err = receiver
+        .try_receive(TracingEvent::SpanDropped { id: 1 })
+        .unwrap_err();
+    assert_matches!(err, ReceiveError::UnknownSpanId(1));
+
+    // Restore the receiver, so that the child span is lazily recreated with its parent.
+    let metadata = receiver.persist_metadata();
+    let (spans, _) = receiver.persist();
+    let spans_json = serde_json::to_value(&spans).unwrap();
+    assert_eq!(spans_json["spans"]["1"]["child_count"], 1);
+    assert!(spans_json["spans"]["2"].get("child_count").is_none());
+    let spans: PersistedSpans = serde_json::from_value(spans_json).unwrap();
+
+    let mut receiver = TracingEventReceiver::new(metadata, spans, LocalSpans::default());
+    receiver.receive(TracingEvent::SpanEntered { id: 2 });
+    receiver.receive(TracingEvent::SpanExited { id: 2 });
+    receiver.receive(TracingEvent::SpanDropped { id: 2 });
+    assert_eq!(receiver.alive_spans().count(), 2);
+    receiver.receive(TracingEvent::SpanDropped { id: 2 });
+    assert_eq!(receiver.alive_spans().count(), 0);
+    assert!(receiver.local_spans.inner.is_empty());
+}
+
+#[test]
+fn lazily_creating_local_spans() {
+    let subscriber = tracing_subscriber::fmt()
+        .with_max_level(tracing_core::LevelFilter::WARN)
+        .with_writer(std::io::sink)
+        .finish();
+    let mut receiver = TracingEventReceiver::builder()
+        .with_dispatch(Dispatch::new(subscriber))
+        .with_lazy_spans()
+        .build();
+    receiver.receive(TracingEvent::NewCallSite {
+        id: 0,
+        data: CALL_SITE_DATA,
+    });
+    for (id, level) in [(1, TracingLevel::Info), (2, TracingLevel::Error)] {
+        let data = CallSiteData {
+            kind: CallSiteKind::Event,
+            level,
+            ..CALL_SITE_DATA
+        };
+        receiver.receive(TracingEvent::NewCallSite { id, data });
+    }
+    let new_event = |metadata_id, parent| TracingEvent::NewEvent {
+        metadata_id,
+        parent,
+        is_root: false,
+        values: TracedValues::new(),
+    };
+
+    receiver.receive(new_span_event(1, None));
+    receiver.receive(new_span_event(2, Some(1)));
+    receiver.receive(new_span_event(3, None));
+    receiver.receive(new_event(1, Some(2)));
+    assert_eq!(receiver.counters().forwarded_spans, 0);
+    assert!(receiver.local_spans.inner.is_empty());
+
+    // A filtered-in event creates its parent span together with all ancestors.
+    receiver.receive(new_event(2, Some(2)));
+    assert_eq!(receiver.counters().forwarded_spans, 2);
+    assert!(receiver.local_spans.inner.contains_key(&1));
+    assert!(receiver.local_spans.inner.contains_key(&2));
+
+    receiver.receive(TracingEvent::SpanEntered { id: 3 });
+    receiver.receive(TracingEvent::SpanExited { id: 3 });
+    assert_eq!(receiver.counters().forwarded_spans, 3);
+
+    for id in [2, 1, 3] {
+        receiver.receive(TracingEvent::SpanDropped { id });
+    }
+    assert_eq!(receiver.alive_spans().count(), 0);
+    assert!(receiver.local_spans.inner.is_empty());
+}
+
+#[test]
+fn lazy_spans_do_not_relay_disabled_events() {
+    let buffer = SharedBuffer::default();
+    let buffer_clone = buffer.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .with_max_level(tracing_core::LevelFilter::WARN)
+        .with_writer(move || buffer_clone.clone())
+        .with_ansi(false)
+        .finish();
+    let mut receiver = TracingEventReceiver::builder()
+        .with_dispatch(Dispatch::new(subscriber))
+        .with_lazy_spans()
+        .build();
+    receiver.receive(TracingEvent::NewCallSite {
+        id: 0,
+        data: CALL_SITE_DATA,
+    });
+    for (id, level) in [(1, TracingLevel::Info), (2, TracingLevel::Error)] {
+        let data = CallSiteData {
+            kind: CallSiteKind::Event,
+            level,
+            ..CALL_SITE_DATA
+        };
+        receiver.receive(TracingEvent::NewCallSite { id, data });
+    }
+    let new_event = |metadata_id, parent| TracingEvent::NewEvent {
+        metadata_id,
+        parent,
+        is_root: false,
+        values: TracedValues::new(),
+    };
+
+    receiver.receive(new_span_event(1, None));
+    receiver.receive(new_span_event(2, None));
+    receiver.receive(TracingEvent::SpanEntered { id: 1 });
+    // The explicit parent is not created since the event is disabled, so the event
+    // must not be relayed in the context of the entered span.
+    receiver.receive(new_event(1, Some(2)));
+    assert!(!receiver.local_spans.inner.contains_key(&2));
+    assert!(buffer.0.lock().unwrap().is_empty());
+
+    receiver.receive(new_event(2, Some(2)));
+    assert!(receiver.local_spans.inner.contains_key(&2));
+    let logs = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+    let lines: Vec<_> = logs.lines().collect();
+    assert_eq!(lines.len(), 1, "{logs}");
+    assert!(lines[0].contains("ERROR"), "{logs}");
+}
+
+#[test]
+fn dropping_idle_spans() {
+    let timeout = NonZeroU64::new(3).unwrap();
+    let mut receiver = TracingEventReceiver::builder()
+        .with_idle_span_timeout(timeout)
+        .build();
+    receiver.receive(TracingEvent::NewCallSite {
+        id: 0,
+        data: CALL_SITE_DATA,
+    });
+    receiver.receive(new_span_event(1, None));
+    receiver.receive(new_span_event(2, None));
+    receiver.receive(TracingEvent::SpanEntered { id: 2 });
+    receiver.receive(TracingEvent::Heartbeat {
+        sequence: 4,
+        dropped_events: 0,
+    });
+    // Heartbeats do not count towards the timeout.
+    assert_eq!(receiver.alive_spans().count(), 2);
+
+    receiver.receive(TracingEvent::NewEvent {
+        metadata_id: 0,
+        parent: None,
+        is_root: false,
+        values: TracedValues::new(),
+    });
+    // Span 1 is idle for 3 events; span 2 is entered.
+    let spans = sorted_alive_spans(&receiver);
+    assert_eq!(spans.len(), 1);
+    assert_eq!(spans[0].id, 2);
+    assert_eq!(spans[0].idle_events, 1);
+
+    receiver.receive(TracingEvent::SpanExited { id: 2 });
+    for _ in 0..2 {
+        receiver.receive(TracingEvent::NewEvent {
+            metadata_id: 0,
+            parent: None,
+            is_root: false,
+            values: TracedValues::new(),
+        });
+    }
+    assert_eq!(receiver.alive_spans().count(), 1);
+    receiver.receive(TracingEvent::NewEvent {
+        metadata_id: 0,
+        parent: None,
+        is_root: false,
+        values: TracedValues::new(),
+    });
+    assert_eq!(receiver.alive_spans().count(), 0);
+    assert!(receiver.local_spans.inner.is_empty());
+}
+
+#[test]
+fn idle_timeout_keeps_parents_with_alive_children() {
+    let timeout = NonZeroU64::new(3).unwrap();
+    let mut receiver = TracingEventReceiver::builder()
+        .with_idle_span_timeout(timeout)
+        .build();
+    receiver.receive(TracingEvent::NewCallSite {
+        id: 0,
+        data: CALL_SITE_DATA,
+    });
+    // The parent span is never entered, e.g. because children are created with explicit `parent`.
+    receiver.receive(new_span_event(1, None));
+    receiver.receive(new_span_event(2, Some(1)));
+    receiver.receive(TracingEvent::SpanEntered { id: 2 });
+    for _ in 0..5 {
+        receiver.receive(TracingEvent::NewEvent {
+            metadata_id: 0,
+            parent: None,
+            is_root: false,
+            values: TracedValues::new(),
+        });
+    }
+    receiver.receive(TracingEvent::SpanExited { id: 2 });
+    receiver.receive(TracingEvent::SpanDropped { id: 2 });
+
+    // Short-lived children keep the parent alive as well.
+    for id in 3..6 {
+        receiver.receive(new_span_event(id, Some(1)));
+        receiver.receive(TracingEvent::SpanDropped { id });
+    }
+    let spans = sorted_alive_spans(&receiver);
+    assert_eq!(spans.len(), 1);
+    assert_eq!(spans[0].id, 1);
+    assert_eq!(spans[0].child_count, 0);
+    assert_eq!(spans[0].idle_events, 0);
+
+    receiver.receive(TracingEvent::SpanDropped { id: 1 });
+    assert_eq!(receiver.alive_spans().count(), 0);
+}
+
 #[test]
 fn spans_with_allowed_value_lengths() {
     for values_len in 0..=32 {
@@ -111,8 +418,10 @@ fn spans_with_allowed_value_lengths() {
         receiver.receive(TracingEvent::NewSpan {
             id: 0,
             parent_id: None,
+            is_root: false,
             metadata_id: 0,
             values,
+            generation: None,
         });
         receiver.receive(TracingEvent::SpanDropped { id: 0 });
     }
@@ -132,8 +441,10 @@ fn too_many_values_error() {
     let bogus_event = TracingEvent::NewSpan {
         id: 0,
         parent_id: None,
+        is_root: false,
         metadata_id: 0,
         values,
+        generation: None,
     };
     let err = receiver.try_receive(bogus_event).unwrap_err();
     assert_matches!(
@@ -145,6 +456,72 @@ fn too_many_values_error() {
     );
 }
 
+#[test]
+fn configuring_max_values_via_builder() {
+    let mut receiver = TracingEventReceiver::builder().with_max_values(2).build();
+    let fields = (0..3).map(|i| Cow::Owned(format!("field{i}"))).collect();
+    receiver.receive(TracingEvent::NewCallSite {
+        id: 0,
+        data: create_call_site(fields),
+    });
+    receiver.set_ambient_field("field2", 42_u64);
+
+    let values: TracedValues<_> = (0..3)
+        .map(|i| (format!("field{i}"), TracedValue::Int(i.into())))
+        .collect();
+    let err = receiver
+        .try_receive(TracingEvent::NewSpan {
+            id: 0,
+            parent_id: None,
+            is_root: false,
+            metadata_id: 0,
+            values: values.clone(),
+            generation: None,
+        })
+        .unwrap_err();
+    assert_matches!(err, ReceiveError::TooManyValues { actual: 3, max: 2 });
+
+    let values = values.into_iter().take(2).collect();
+    receiver.receive(TracingEvent::NewSpan {
+        id: 0,
+        parent_id: None,
+        is_root: false,
+        metadata_id: 0,
+        values,
+        generation: None,
+    });
+    // The ambient field does not fit into the values limit.
+    assert_eq!(receiver.counters().dropped_values, 1);
+}
+
+#[test]
+#[should_panic(expected = "at most 32 values are supported")]
+fn too_large_max_values_in_builder() {
+    let _ = TracingEventReceiver::builder().with_max_values(33);
+}
+
+#[test]
+fn restoring_receiver_via_builder() {
+    let mut receiver = TracingEventReceiver::default();
+    receiver.receive(TracingEvent::NewCallSite {
+        id: 0,
+        data: create_call_site(vec!["i".into()]),
+    });
+    receiver.receive(new_span_event(0, None));
+    let metadata = receiver.persist_metadata();
+    let (spans, local_spans) = receiver.persist();
+
+    let receiver = TracingEventReceiver::builder()
+        .with_persisted_metadata(metadata)
+        .with_persisted_spans(spans, local_spans)
+        .with_static_metadata([HOST_METADATA])
+        .build();
+    assert!(ptr::eq(receiver.metadata(0).unwrap(), HOST_METADATA));
+    let alive_spans = sorted_alive_spans(&receiver);
+    assert_eq!(alive_spans.len(), 1);
+    assert_eq!(alive_spans[0].id, 0);
+}
+
 #[test]
 fn receiver_does_not_panic_on_bogus_field() {
     let events = [
@@ -155,8 +532,10 @@ fn receiver_does_not_panic_on_bogus_field() {
         TracingEvent::NewSpan {
             id: 0,
             parent_id: None,
+            is_root: false,
             metadata_id: 0,
             values: TracedValues::from_iter([("i".to_owned(), TracedValue::from(42_i64))]),
+            generation: None,
         },
     ];
 
@@ -166,10 +545,190 @@ fn receiver_does_not_panic_on_bogus_field() {
     }
 }
 
+#[test]
+fn receiver_counters_and_metrics() {
+    let reported = Arc::new(Mutex::new(vec![]));
+    let reported_clone = Arc::clone(&reported);
+    let mut receiver = TracingEventReceiver::builder()
+        .with_metrics(move |counters: &ReceiverCounters| {
+            reported_clone.lock().unwrap().push(*counters);
+        })
+        .build();
+
+    let call_site = create_call_site(vec!["i".into()]);
+    receiver.receive(TracingEvent::NewCallSite {
+        id: 0,
+        data: call_site,
+    });
+    receiver.receive(TracingEvent::NewSpan {
+        id: 0,
+        parent_id: None,
+        is_root: false,
+        metadata_id: 0,
+        values: TracedValues::from_iter([
+            ("i".to_owned(), TracedValue::from(42_i64)),
+            ("bogus".to_owned(), TracedValue::from(true)),
+        ]),
+        generation: None,
+    });
+    receiver.receive(TracingEvent::SpanEntered { id: 0 });
+    receiver.receive(TracingEvent::ValuesRecorded {
+        id: 0,
+        values: TracedValues::from_iter([("other".to_owned(), TracedValue::from("?"))]),
+    });
+    receiver.receive(TracingEvent::SpanExited { id: 0 });
+    receiver
+        .try_receive(new_span_event(1, Some(5)))
+        .unwrap_err();
+    receiver.receive(TracingEvent::Heartbeat {
+        sequence: 6,
+        dropped_events: 0,
+    });
+
+    let counters = receiver.counters();
+    assert_eq!(counters.total_events(), 7);
+    assert_eq!(counters.new_call_sites, 1);
+    assert_eq!(counters.new_spans, 2);
+    assert_eq!(counters.spans_entered, 1);
+    assert_eq!(counters.spans_exited, 1);
+    assert_eq!(counters.values_recorded, 1);
+    assert_eq!(counters.heartbeats, 1);
+    assert_eq!(counters.forwarded_spans, 1);
+    assert_eq!(counters.errors, 1);
+    assert_eq!(counters.dropped_values, 2);
+
+    let reported = reported.lock().unwrap();
+    assert_eq!(reported.len(), 7);
+    assert_eq!(*reported.last().unwrap(), counters);
+    assert_eq!(reported[1].forwarded_spans, 1);
+    assert_eq!(reported[1].dropped_values, 1);
+}
+
+#[test]
+fn interceptors_modify_and_discard_events() {
+    let intercepted = Arc::new(Mutex::new(vec![]));
+    let intercepted_clone = Arc::clone(&intercepted);
+    let mut receiver = TracingEventReceiver::builder()
+        .with_interceptor(|event: &mut TracingEvent| {
+            if let TracingEvent::NewSpan { values, .. } = event {
+                values.insert("bogus".to_owned(), TracedValue::from(true));
+            }
+            !matches!(event, TracingEvent::Heartbeat { .. })
+        })
+        .with_interceptor(move |event: &mut TracingEvent| {
+            intercepted_clone.lock().unwrap().push(event.clone());
+            true
+        })
+        .build();
+
+    receiver.receive(TracingEvent::NewCallSite {
+        id: 0,
+        data: create_call_site(vec!["i".into()]),
+    });
+    receiver.receive(new_span_event(1, None));
+    receiver.receive(TracingEvent::Heartbeat {
+        sequence: 0,
+        dropped_events: 0,
+    });
+
+    let counters = receiver.counters();
+    assert_eq!(counters.total_events(), 3);
+    assert_eq!(counters.heartbeats, 1);
+    assert_eq!(counters.intercepted_events, 1);
+    assert_eq!(counters.dropped_values, 1);
+    assert!(receiver.last_heartbeat().is_none());
+
+    let intercepted = intercepted.lock().unwrap();
+    assert_eq!(intercepted.len(), 2);
+    assert_matches!(
+        &intercepted[1],
+        TracingEvent::NewSpan { values, .. } if values.get("bogus").is_some()
+    );
+}
+
+#[test]
+fn detecting_stale_spans() {
+    let new_span = |id, parent_id, generation| TracingEvent::NewSpan {
+        id,
+        parent_id,
+        is_root: false,
+        metadata_id: 0,
+        values: TracedValues::new(),
+        generation: Some(generation),
+    };
+
+    let mut receiver = TracingEventReceiver::default();
+    receiver.receive(TracingEvent::NewCallSite {
+        id: 0,
+        data: CALL_SITE_DATA,
+    });
+    receiver.receive(new_span(0, None, 1));
+    receiver.receive(new_span(1, Some(0), 1));
+    receiver.receive(TracingEvent::SpanEntered { id: 1 });
+    receiver.receive(TracingEvent::SpanExited { id: 1 });
+
+    // Emulate sender restart reusing span IDs.
+    let err = receiver.try_receive(new_span(0, None, 2)).unwrap_err();
+    assert_matches!(
+        err,
+        ReceiveError::StaleSpan {
+            id: 0,
+            span_generation: Some(1),
+            current_generation: Some(2),
+        }
+    );
+    assert!(err.to_string().contains("generation: 1"), "{err}");
+    let err = receiver
+        .try_receive(TracingEvent::SpanEntered { id: 1 })
+        .unwrap_err();
+    assert_matches!(err, ReceiveError::StaleSpan { id: 1, .. });
+
+    receiver.force_drop(0).unwrap();
+    receiver.receive(new_span(0, None, 2));
+    let err = receiver.try_receive(new_span(2, Some(1), 2)).unwrap_err();
+    assert_matches!(err, ReceiveError::StaleSpan { id: 1, .. });
+
+    let mut alive_spans: Vec<_> = receiver.alive_spans().collect();
+    alive_spans.sort_unstable_by_key(|span| span.id);
+    let generations: Vec<_> = alive_spans
+        .iter()
+        .map(|span| (span.id, span.generation))
+        .collect();
+    assert_eq!(generations, [(0, Some(2)), (1, Some(1))]);
+}
+
+#[test]
+fn spans_without_generation_are_never_stale() {
+    let mut receiver = TracingEventReceiver::default();
+    receiver.receive(TracingEvent::NewCallSite {
+        id: 0,
+        data: CALL_SITE_DATA,
+    });
+    receiver.receive(TracingEvent::NewSpan {
+        id: 0,
+        parent_id: None,
+        is_root: false,
+        metadata_id: 0,
+        values: TracedValues::new(),
+        generation: None,
+    });
+    receiver.receive(TracingEvent::NewSpan {
+        id: 1,
+        parent_id: Some(0),
+        is_root: false,
+        metadata_id: 0,
+        values: TracedValues::new(),
+        generation: Some(1),
+    });
+    receiver.receive(TracingEvent::SpanEntered { id: 0 });
+    receiver.receive(TracingEvent::SpanExited { id: 0 });
+}
+
 #[test]
 fn restoring_spans() {
     let metadata = PersistedMetadata {
         inner: HashMap::from_iter([(0, CALL_SITE_DATA)]),
+        keying: MetadataKeying::Numeric,
     };
     let spans = PersistedSpans {
         inner: HashMap::from_iter([(
@@ -177,10 +736,16 @@ fn restoring_spans() {
             SpanData {
                 metadata_id: 0,
                 parent_id: None,
+                is_root: false,
                 ref_count: 1,
+                child_count: 0,
                 values: TracedValues::new(),
+                generation: None,
+                last_activity: 0,
             },
         )]),
+        ambient_fields: AmbientFields::default(),
+        call_site_hashes: CallSiteHashes::default(),
     };
     let local_spans = LocalSpans::default();
 
@@ -203,6 +768,7 @@ fn restoring_span_after_recording_values() {
     let call_site = create_call_site(vec!["i".into()]);
     let metadata = PersistedMetadata {
         inner: HashMap::from_iter([(0, call_site)]),
+        keying: MetadataKeying::Numeric,
     };
     let spans = PersistedSpans {
         inner: HashMap::from_iter([(
@@ -210,10 +776,16 @@ fn restoring_span_after_recording_values() {
             SpanData {
                 metadata_id: 0,
                 parent_id: None,
+                is_root: false,
                 ref_count: 1,
+                child_count: 0,
                 values: TracedValues::new(),
+                generation: None,
+                last_activity: 0,
             },
         )]),
+        ambient_fields: AmbientFields::default(),
+        call_site_hashes: CallSiteHashes::default(),
     };
     let local_spans = LocalSpans::default();
 
@@ -227,3 +799,902 @@ fn restoring_span_after_recording_values() {
 
     visit_and_drop_span(&mut receiver);
 }
+
+fn create_persisted_spans() -> PersistedSpans {
+    PersistedSpans {
+        inner: HashMap::from_iter([(
+            1,
+            SpanData {
+                metadata_id: 0,
+                parent_id: None,
+                is_root: false,
+                ref_count: 1,
+                child_count: 0,
+                values: TracedValues::from_iter([("i".to_owned(), TracedValue::from(42_i64))]),
+                generation: None,
+                last_activity: 0,
+            },
+        )]),
+        ambient_fields: AmbientFields::default(),
+        call_site_hashes: CallSiteHashes::default(),
+    }
+}
+
+#[test]
+fn persisted_state_serialization_is_versioned() {
+    let metadata = PersistedMetadata {
+        inner: HashMap::from_iter([(0, CALL_SITE_DATA)]),
+        keying: MetadataKeying::Numeric,
+    };
+    let metadata_json = serde_json::to_value(&metadata).unwrap();
+    assert_eq!(metadata_json["version"], PersistedMetadata::FORMAT_VERSION);
+    assert_eq!(metadata_json["call_sites"]["0"]["name"], "test");
+    let restored: PersistedMetadata = serde_json::from_value(metadata_json).unwrap();
+    assert_eq!(restored.inner[&0].name, "test");
+
+    let spans_json = serde_json::to_value(create_persisted_spans()).unwrap();
+    assert_eq!(spans_json["version"], PersistedSpans::FORMAT_VERSION);
+    assert_eq!(spans_json["spans"]["1"]["ref_count"], 1);
+    let restored: PersistedSpans = serde_json::from_value(spans_json).unwrap();
+    assert_eq!(restored.inner[&1].values["i"], 42_i64);
+}
+
+#[test]
+fn restoring_persisted_state_from_legacy_format() {
+    let metadata_json = serde_json::json!({
+        "0": serde_json::to_value(CALL_SITE_DATA).unwrap(),
+    });
+    let metadata: PersistedMetadata = serde_json::from_value(metadata_json).unwrap();
+    assert_eq!(metadata.len(), 1);
+    assert_eq!(metadata.inner[&0].name, "test");
+
+    let spans_json = serde_json::json!({
+        "1": serde_json::to_value(&create_persisted_spans().inner[&1]).unwrap(),
+    });
+    let spans: PersistedSpans = serde_json::from_value(spans_json).unwrap();
+    assert_eq!(spans.len(), 1);
+    assert_eq!(spans.inner[&1].values["i"], 42_i64);
+
+    let mut receiver = TracingEventReceiver::new(metadata, spans, LocalSpans::default());
+    visit_and_drop_span(&mut receiver);
+}
+
+#[test]
+fn persisting_ambient_fields() {
+    let mut spans = create_persisted_spans();
+    let spans_json = serde_json::to_value(&spans).unwrap();
+    assert!(spans_json.get("ambient_fields").is_none());
+
+    spans
+        .ambient_fields
+        .0
+        .insert("workflow_id".to_owned(), 42_u64.into());
+    let spans_json = serde_json::to_value(&spans).unwrap();
+    assert_eq!(spans_json["ambient_fields"]["workflow_id"]["u_int"], 42);
+    let restored: PersistedSpans = serde_json::from_value(spans_json).unwrap();
+    assert_eq!(restored.ambient_fields()["workflow_id"], 42_u64);
+    assert_eq!(restored.inner[&1].values["i"], 42_i64);
+}
+
+static HOST_CALL_SITE: DefaultCallsite = DefaultCallsite::new(HOST_METADATA);
+static HOST_METADATA: &Metadata<'static> = &Metadata::new(
+    "test",
+    "tracing_tunnel",
+    Level::ERROR,
+    Some("host.rs"),
+    Some(1),
+    Some("host"),
+    FieldSet::new(&["i"], tracing_core::identify_callsite!(&HOST_CALL_SITE)),
+    Kind::SPAN,
+);
+
+#[test]
+fn reusing_static_metadata() {
+    let mut receiver = TracingEventReceiver::builder()
+        .with_static_metadata([HOST_METADATA])
+        .build();
+    receiver.set_ambient_field("workflow_id", 42_u64);
+    receiver.receive(TracingEvent::NewCallSite {
+        id: 0,
+        data: create_call_site(vec!["i".into()]),
+    });
+    receiver.receive(TracingEvent::NewCallSite {
+        id: 1,
+        data: CALL_SITE_DATA,
+    });
+
+    assert!(ptr::eq(receiver.metadata(0).unwrap(), HOST_METADATA));
+    let dynamic_metadata = receiver.metadata(1).unwrap();
+    assert!(!ptr::eq(dynamic_metadata, HOST_METADATA));
+    assert!(dynamic_metadata.fields().field("workflow_id").is_some());
+
+    receiver.receive(TracingEvent::NewSpan {
+        id: 0,
+        parent_id: None,
+        is_root: false,
+        metadata_id: 0,
+        values: TracedValues::from_iter([("i".to_owned(), TracedValue::from(1_i64))]),
+        generation: None,
+    });
+    // The ambient field cannot be attached to the span.
+    assert_eq!(receiver.counters().dropped_values, 1);
+
+    let persisted = receiver.persist_metadata();
+    assert_eq!(persisted.inner[&0].fields, ["i"]);
+    let receiver = TracingEventReceiver::new(
+        persisted.clone(),
+        PersistedSpans::default(),
+        LocalSpans::default(),
+    );
+    assert!(!ptr::eq(receiver.metadata(0).unwrap(), HOST_METADATA));
+    let receiver = TracingEventReceiver::builder()
+        .with_persisted_metadata(persisted)
+        .with_static_metadata([HOST_METADATA])
+        .build();
+    assert!(ptr::eq(receiver.metadata(0).unwrap(), HOST_METADATA));
+}
+
+#[test]
+fn ambient_fields_are_not_persisted_in_metadata() {
+    let call_site = create_call_site(vec!["i".into()]);
+    let metadata = PersistedMetadata {
+        inner: HashMap::from_iter([(0, call_site)]),
+        keying: MetadataKeying::Numeric,
+    };
+    let mut receiver =
+        TracingEventReceiver::new(metadata, PersistedSpans::default(), LocalSpans::default());
+    receiver.set_ambient_field("workflow_id", 42_u64);
+    receiver.set_ambient_field("i", 23_i64);
+
+    let fields = receiver.metadata(0).unwrap().fields();
+    let field_names: Vec<_> = fields.iter().map(|field| field.name()).collect();
+    assert_eq!(field_names, ["i", "workflow_id"]);
+    let persisted = receiver.persist_metadata();
+    assert_eq!(persisted.inner[&0].fields, ["i"]);
+
+    let (spans, local_spans) = receiver.persist();
+    assert_eq!(spans.ambient_fields().len(), 2);
+    let receiver = TracingEventReceiver::new(persisted, spans, local_spans);
+    let fields = receiver.metadata(0).unwrap().fields();
+    let field_names: Vec<_> = fields.iter().map(|field| field.name()).collect();
+    assert_eq!(field_names, ["i", "workflow_id"]);
+}
+
+#[test]
+fn unknown_fields_in_persisted_state_are_ignored() {
+    let spans_json = serde_json::json!({
+        "version": 1,
+        "spans": {
+            "1": {
+                "metadata_id": 0,
+                "ref_count": 1,
+                "values": {},
+                "unknown": "?",
+            },
+        },
+        "extra": [1, 2, 3],
+    });
+    let spans: PersistedSpans = serde_json::from_value(spans_json).unwrap();
+    assert_eq!(spans.inner[&1].ref_count, 1);
+}
+
+#[test]
+fn unsupported_persisted_state_version() {
+    let spans_json = serde_json::json!({ "version": 999, "spans": {} });
+    let err = serde_json::from_value::<PersistedSpans>(spans_json)
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("unsupported format version 999"), "{err}");
+}
+
+#[test]
+fn call_site_content_hash_is_stable() {
+    let hash = CALL_SITE_DATA.content_hash();
+    assert_eq!(hash, CALL_SITE_DATA.clone().content_hash());
+    // The hash must not change across crate versions; otherwise, persisted metadata
+    // would become unusable.
+    assert_eq!(hash, 0x00d7_cd67_8940_aeae);
+
+    let other_call_site = create_call_site(vec!["i".into()]);
+    assert_ne!(other_call_site.content_hash(), hash);
+    let other_call_site = CallSiteData {
+        line: Some(43),
+        ..CALL_SITE_DATA
+    };
+    assert_ne!(other_call_site.content_hash(), hash);
+}
+
+/// Emulates an execution of an executable version in which the metadata ID 0 corresponds
+/// to `call_site`.
+fn run_execution(
+    metadata: PersistedMetadata,
+    call_site: CallSiteData,
+) -> (PersistedMetadata, PersistedSpans) {
+    let mut receiver = TracingEventReceiver::builder()
+        .with_persisted_metadata(metadata)
+        .with_metadata_keying(MetadataKeying::ContentHash)
+        .build();
+    receiver.receive(TracingEvent::NewCallSite {
+        id: 0,
+        data: call_site,
+    });
+    receiver.receive(TracingEvent::NewSpan {
+        id: 1,
+        parent_id: None,
+        is_root: false,
+        metadata_id: 0,
+        values: TracedValues::new(),
+        generation: None,
+    });
+    let metadata = receiver.persist_metadata();
+    let (spans, _) = receiver.persist();
+    (metadata, spans)
+}
+
+#[test]
+fn content_addressed_metadata() {
+    let (mut metadata, spans) = run_execution(PersistedMetadata::default(), CALL_SITE_DATA);
+    assert_eq!(metadata.keying(), MetadataKeying::ContentHash);
+    let hash = CALL_SITE_DATA.content_hash();
+    assert_eq!(metadata.inner[&hash].name, "test");
+    assert_eq!(spans.call_site_hashes.0[&0], hash);
+
+    // Emulate a recompiled executable, in which metadata ID 0 corresponds to another call site.
+    let new_call_site = CallSiteData {
+        name: "new".into(),
+        ..CALL_SITE_DATA
+    };
+    let (new_metadata, _) = run_execution(metadata.clone(), new_call_site);
+    metadata.extend(new_metadata);
+    assert_eq!(metadata.len(), 2);
+
+    let metadata_json = serde_json::to_value(&metadata).unwrap();
+    assert_eq!(metadata_json["keying"], "content_hash");
+    let spans_json = serde_json::to_value(&spans).unwrap();
+    assert_eq!(spans_json["call_site_hashes"]["0"], hash);
+    let metadata: PersistedMetadata = serde_json::from_value(metadata_json).unwrap();
+    assert_eq!(metadata.keying(), MetadataKeying::ContentHash);
+    let spans: PersistedSpans = serde_json::from_value(spans_json).unwrap();
+
+    // Spans from the original execution must be restored with the original call site.
+    let mut receiver = TracingEventReceiver::new(metadata, spans, LocalSpans::default());
+    assert_eq!(receiver.metadata(0).unwrap().name(), "test");
+    visit_and_drop_span(&mut receiver);
+}
+
+#[test]
+fn migrating_to_content_addressed_metadata() {
+    let metadata = PersistedMetadata {
+        inner: HashMap::from_iter([(0, CALL_SITE_DATA), (1, CALL_SITE_DATA)]),
+        keying: MetadataKeying::Numeric,
+    };
+    let metadata_json = serde_json::to_value(&metadata).unwrap();
+    assert!(metadata_json.get("keying").is_none());
+
+    let migrated = metadata.clone().into_content_addressed();
+    assert_eq!(migrated.keying(), MetadataKeying::ContentHash);
+    assert_eq!(migrated.len(), 1);
+    let (hash, data) = migrated.iter().next().unwrap();
+    assert_eq!(hash, CALL_SITE_DATA.content_hash());
+    assert_eq!(data.name, "test");
+
+    let receiver = TracingEventReceiver::builder()
+        .with_persisted_metadata(metadata)
+        .with_persisted_spans(create_persisted_spans(), LocalSpans::default())
+        .with_metadata_keying(MetadataKeying::ContentHash)
+        .build();
+    let metadata = receiver.persist_metadata();
+    assert_eq!(metadata.keying(), MetadataKeying::ContentHash);
+    assert_eq!(metadata.len(), 1);
+    let (spans, local_spans) = receiver.persist();
+    assert_eq!(spans.call_site_hashes.0.len(), 2);
+
+    let mut receiver = TracingEventReceiver::new(metadata, spans, local_spans);
+    visit_and_drop_span(&mut receiver);
+}
+
+#[test]
+fn detecting_gaps_between_heartbeats() {
+    let mut receiver = TracingEventReceiver::default();
+    assert!(receiver.last_heartbeat().is_none());
+
+    receiver.receive(TracingEvent::NewCallSite {
+        id: 0,
+        data: CALL_SITE_DATA,
+    });
+    receiver.receive(TracingEvent::Heartbeat {
+        sequence: 10,
+        dropped_events: 0,
+    });
+    let heartbeat = receiver.last_heartbeat().unwrap();
+    assert_eq!(heartbeat.sequence, 10);
+    assert_eq!(heartbeat.missing_events, 0);
+    assert_eq!(heartbeat.events_since, 0);
+
+    for _ in 0..3 {
+        receiver.receive(TracingEvent::NewCallSite {
+            id: 0,
+            data: CALL_SITE_DATA,
+        });
+    }
+    assert_eq!(receiver.last_heartbeat().unwrap().events_since, 3);
+    // 2 events are missing.
+    receiver.receive(TracingEvent::Heartbeat {
+        sequence: 15,
+        dropped_events: 2,
+    });
+    let heartbeat = receiver.last_heartbeat().unwrap();
+    assert_eq!(heartbeat.sequence, 15);
+    assert_eq!(heartbeat.dropped_events, 2);
+    assert_eq!(heartbeat.missing_events, 2);
+    assert_eq!(heartbeat.events_since, 0);
+
+    // "Negative" gaps are ignored.
+    receiver.receive(TracingEvent::Heartbeat {
+        sequence: u32::MAX - 4,
+        dropped_events: 2,
+    });
+    assert_eq!(receiver.last_heartbeat().unwrap().missing_events, 2);
+    // Sequence numbers wrap around: 6 events are missing between the heartbeats.
+    receiver.receive(TracingEvent::NewCallSite {
+        id: 0,
+        data: CALL_SITE_DATA,
+    });
+    receiver.receive(TracingEvent::Heartbeat {
+        sequence: 2,
+        dropped_events: 2,
+    });
+    let heartbeat = receiver.last_heartbeat().unwrap();
+    assert_eq!(heartbeat.missing_events, 2 + 6);
+}
+
+#[cfg(feature = "proptest")]
+mod fuzz {
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::strategies::TraceFixture;
+
+    proptest! {
+        #[test]
+        fn receiver_accepts_generated_traces(fixture in any::<TraceFixture>()) {
+            let mut receiver = TracingEventReceiver::default();
+            for event in fixture.into_events() {
+                receiver.try_receive(event).unwrap();
+            }
+            prop_assert_eq!(receiver.alive_spans().count(), 0);
+            prop_assert_eq!(receiver.counters().errors, 0);
+        }
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+#[test]
+fn receiver_does_not_panic_on_arbitrary_events() {
+    use arbitrary::{Arbitrary, Unstructured};
+    use tracing_subscriber::Registry;
+
+    let dispatch = Dispatch::new(Registry::default());
+    // Xorshift PRNG to generate deterministic input bytes.
+    let mut state = 0x2545_f491_u32;
+    let mut next_byte = move || {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        state.to_le_bytes()[0]
+    };
+
+    for _ in 0..256 {
+        let data: Vec<u8> = (0..1_024).map(|_| next_byte()).collect();
+        let mut data = Unstructured::new(&data);
+        let events = Vec::<TracingEvent>::arbitrary(&mut data).unwrap();
+        let mut receiver = TracingEventReceiver::builder()
+            .with_dispatch(dispatch.clone())
+            .build();
+        for event in events {
+            receiver.try_receive(event).ok();
+        }
+    }
+}
+
+fn sequenced_receiver(reorder_capacity: usize) -> (TracingEventReceiver, Arc<Mutex<Vec<u32>>>) {
+    let flushes = Arc::<Mutex<Vec<u32>>>::default();
+    let flushes_clone = Arc::clone(&flushes);
+    let receiver = TracingEventReceiver::builder()
+        .with_reorder_buffer(reorder_capacity)
+        .with_flush_hook(move |status| flushes_clone.lock().unwrap().push(status.sequence))
+        .build();
+    (receiver, flushes)
+}
+
+fn sequenced_flush(sequence: u32) -> SequencedEvent {
+    SequencedEvent {
+        sequence,
+        event: TracingEvent::Flush { sequence },
+    }
+}
+
+#[test]
+fn detecting_out_of_order_events_without_reordering() {
+    let (mut receiver, flushes) = sequenced_receiver(0);
+    receiver.try_receive_sequenced(sequenced_flush(0)).unwrap();
+    let err = receiver
+        .try_receive_sequenced(sequenced_flush(2))
+        .unwrap_err();
+    assert_matches!(
+        err,
+        ReceiveError::OutOfOrder {
+            expected: 1,
+            actual: 2
+        }
+    );
+    // The late event is discarded.
+    let err = receiver
+        .try_receive_sequenced(sequenced_flush(1))
+        .unwrap_err();
+    assert_matches!(
+        err,
+        ReceiveError::OutOfOrder {
+            expected: 3,
+            actual: 1
+        }
+    );
+    receiver.try_receive_sequenced(sequenced_flush(3)).unwrap();
+
+    assert_eq!(*flushes.lock().unwrap(), [0, 2, 3]);
+    assert_eq!(receiver.counters().errors, 2);
+}
+
+fn sequenced_heartbeat(sequence: u32) -> SequencedEvent {
+    SequencedEvent {
+        sequence,
+        event: TracingEvent::Heartbeat {
+            sequence,
+            dropped_events: 0,
+        },
+    }
+}
+
+fn last_heartbeat_sequence(receiver: &TracingEventReceiver) -> Option<u32> {
+    receiver
+        .last_heartbeat()
+        .map(|heartbeat| heartbeat.sequence)
+}
+
+#[test]
+fn reordering_sequenced_events() {
+    let (mut receiver, _) = sequenced_receiver(3);
+    for sequence in [0, 2, 3] {
+        receiver
+            .try_receive_sequenced(sequenced_heartbeat(sequence))
+            .unwrap();
+    }
+    assert_eq!(last_heartbeat_sequence(&receiver), Some(0));
+    receiver
+        .try_receive_sequenced(sequenced_heartbeat(1))
+        .unwrap();
+    assert_eq!(last_heartbeat_sequence(&receiver), Some(3));
+    assert_eq!(receiver.counters().heartbeats, 4);
+
+    receiver
+        .try_receive_sequenced(sequenced_heartbeat(6))
+        .unwrap();
+    let err = receiver
+        .try_receive_sequenced(sequenced_heartbeat(6))
+        .unwrap_err();
+    assert_matches!(
+        err,
+        ReceiveError::OutOfOrder {
+            expected: 4,
+            actual: 6
+        }
+    );
+    assert_eq!(receiver.counters().heartbeats, 4);
+
+    // The gap cannot be filled using the buffer; buffered events are relayed.
+    let err = receiver
+        .try_receive_sequenced(sequenced_heartbeat(8))
+        .unwrap_err();
+    assert_matches!(
+        err,
+        ReceiveError::OutOfOrder {
+            expected: 4,
+            actual: 8
+        }
+    );
+    assert_eq!(last_heartbeat_sequence(&receiver), Some(8));
+    assert_eq!(receiver.counters().heartbeats, 6);
+    receiver
+        .try_receive_sequenced(sequenced_heartbeat(9))
+        .unwrap();
+    assert_eq!(receiver.counters().errors, 2);
+}
+
+#[test]
+fn flush_marker_drains_reorder_buffer() {
+    let (mut receiver, flushes) = sequenced_receiver(3);
+    receiver
+        .try_receive_sequenced(sequenced_heartbeat(0))
+        .unwrap();
+    receiver
+        .try_receive_sequenced(sequenced_heartbeat(2))
+        .unwrap();
+    assert_eq!(last_heartbeat_sequence(&receiver), Some(0));
+
+    let err = receiver
+        .try_receive_sequenced(sequenced_flush(3))
+        .unwrap_err();
+    assert_matches!(
+        err,
+        ReceiveError::OutOfOrder {
+            expected: 1,
+            actual: 3
+        }
+    );
+    assert_eq!(last_heartbeat_sequence(&receiver), Some(2));
+    assert_eq!(*flushes.lock().unwrap(), [3]);
+
+    // The receiver is resynced after the flush.
+    receiver.try_receive_sequenced(sequenced_flush(4)).unwrap();
+    assert_eq!(*flushes.lock().unwrap(), [3, 4]);
+    let err = receiver
+        .try_receive_sequenced(sequenced_heartbeat(1))
+        .unwrap_err();
+    assert_matches!(
+        err,
+        ReceiveError::OutOfOrder {
+            expected: 5,
+            actual: 1
+        }
+    );
+}
+
+#[test]
+fn reorder_timeout() {
+    let mut receiver = TracingEventReceiver::builder()
+        .with_reorder_buffer(3)
+        .with_reorder_timeout(Duration::ZERO)
+        .build();
+    receiver
+        .try_receive_sequenced(sequenced_heartbeat(0))
+        .unwrap();
+    let err = receiver
+        .try_receive_sequenced(sequenced_heartbeat(2))
+        .unwrap_err();
+    assert_matches!(
+        err,
+        ReceiveError::OutOfOrder {
+            expected: 1,
+            actual: 2
+        }
+    );
+    assert_eq!(last_heartbeat_sequence(&receiver), Some(2));
+    receiver
+        .try_receive_sequenced(sequenced_heartbeat(3))
+        .unwrap();
+    assert_eq!(receiver.counters().errors, 1);
+}
+
+#[test]
+fn sequence_numbers_wrap_on_overflow() {
+    let (mut receiver, _) = sequenced_receiver(2);
+    receiver.sequencing.next_sequence = u32::MAX;
+    receiver
+        .try_receive_sequenced(sequenced_heartbeat(0))
+        .unwrap();
+    assert_eq!(last_heartbeat_sequence(&receiver), None);
+    for sequence in [u32::MAX, 1] {
+        receiver
+            .try_receive_sequenced(sequenced_heartbeat(sequence))
+            .unwrap();
+    }
+    assert_eq!(last_heartbeat_sequence(&receiver), Some(1));
+    assert_eq!(receiver.counters().heartbeats, 3);
+}
+
+#[test]
+fn error_policies() {
+    let mut receiver = TracingEventReceiver::builder()
+        .with_error_policy(ErrorPolicy::Ignore)
+        .build();
+    receiver.receive(TracingEvent::SpanEntered { id: 1 });
+    assert_eq!(receiver.counters().errors, 1);
+    assert!(receiver.take_errors().is_empty());
+
+    let mut receiver = TracingEventReceiver::builder()
+        .with_error_policy(ErrorPolicy::Collect)
+        .build();
+    receiver.receive(TracingEvent::SpanEntered { id: 1 });
+    receiver.receive(new_span_event(2, None));
+    receiver.receive(TracingEvent::SpanExited { id: 3 });
+    let errors = receiver.take_errors();
+    assert_matches!(
+        errors.as_slice(),
+        [
+            ReceiveError::UnknownSpanId(1),
+            ReceiveError::UnknownMetadataId(0),
+            ReceiveError::UnknownSpanId(3),
+        ]
+    );
+    assert!(receiver.take_errors().is_empty());
+    assert_eq!(receiver.counters().errors, 3);
+
+    let mut receiver = TracingEventReceiver::default();
+    let panic_result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        receiver.receive(TracingEvent::SpanEntered { id: 1 });
+    }));
+    let panic_message = panic_result.unwrap_err();
+    let panic_message = panic_message.downcast_ref::<String>().unwrap();
+    assert_eq!(
+        panic_message,
+        "received bogus tracing event: UnknownSpanId(1)"
+    );
+}
+
+#[derive(Debug, Clone, Default)]
+struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl std::io::Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn logging_errors_to_host() {
+    let buffer = SharedBuffer::default();
+    let buffer_clone = buffer.clone();
+    let host_subscriber = tracing_subscriber::fmt()
+        .with_writer(move || buffer_clone.clone())
+        .with_ansi(false)
+        .finish();
+    // Events are relayed to another dispatch, so that they don't mix with logged errors.
+    let mut receiver = TracingEventReceiver::builder()
+        .with_dispatch(Dispatch::new(tracing_subscriber::Registry::default()))
+        .with_error_policy(ErrorPolicy::LogToHost)
+        .build();
+
+    tracing_core::dispatcher::with_default(&Dispatch::new(host_subscriber), || {
+        receiver.receive(TracingEvent::NewCallSite {
+            id: 0,
+            data: CALL_SITE_DATA,
+        });
+        receiver.receive(new_span_event(1, None));
+        receiver.receive(TracingEvent::SpanEntered { id: 2 });
+    });
+
+    let logs = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+    let lines: Vec<_> = logs.lines().collect();
+    assert_eq!(lines.len(), 1, "{logs}");
+    assert!(lines[0].contains("WARN"), "{logs}");
+    assert!(
+        lines[0]
+            .contains("tracing_tunnel::receiver: received bogus tracing event: unknown span ID: 2"),
+        "{logs}"
+    );
+}
+
+#[test]
+fn taking_incremental_changes() {
+    let mut receiver = TracingEventReceiver::default();
+    receiver.receive(TracingEvent::NewCallSite {
+        id: 0,
+        data: CALL_SITE_DATA,
+    });
+    receiver.receive(new_span_event(1, None));
+
+    let mut metadata_store = PersistedMetadata::default();
+    let mut span_store = PersistedSpans::default();
+    let metadata_changes = receiver.take_metadata_changes();
+    assert_eq!(metadata_changes.len(), 1);
+    metadata_store.save_metadata(metadata_changes).unwrap();
+    let span_changes = receiver.take_span_changes();
+    assert!(span_changes.is_snapshot());
+    span_store.save_spans(span_changes).unwrap();
+    assert_eq!(span_store.len(), 1);
+
+    // No changes since the last call.
+    assert!(receiver.take_metadata_changes().is_empty());
+    assert!(receiver.take_span_changes().is_empty());
+
+    receiver.receive(new_span_event(2, Some(1)));
+    receiver.receive(new_span_event(3, None));
+    receiver.receive(TracingEvent::SpanDropped { id: 3 });
+    receiver.set_ambient_field("workflow_id", 42_u64);
+    assert!(receiver.take_metadata_changes().is_empty());
+    let span_changes = receiver.take_span_changes();
+    assert!(!span_changes.is_snapshot());
+    let mut updated: Vec<_> = span_changes.updated_spans().collect();
+    updated.sort_unstable();
+    assert_eq!(updated, [1, 2]); // span 1 is updated since it has a new child
+    assert_eq!(span_changes.removed_spans(), [3]);
+
+    let span_changes_json = serde_json::to_value(&span_changes).unwrap();
+    let span_changes: SpanChanges = serde_json::from_value(span_changes_json).unwrap();
+    span_store.save_spans(span_changes).unwrap();
+    assert_eq!(span_store.len(), 2);
+    assert_eq!(span_store.inner[&1].child_count, 1);
+    assert_eq!(span_store.ambient_fields()["workflow_id"], 42_u64);
+
+    let metadata = metadata_store.load_metadata().unwrap();
+    let spans = span_store.load_spans().unwrap();
+    let mut receiver = TracingEventReceiver::new(metadata, spans, LocalSpans::default());
+    receiver.receive(TracingEvent::SpanDropped { id: 2 });
+    visit_and_drop_span(&mut receiver);
+}
+
+#[cfg(feature = "file-store")]
+#[test]
+fn file_store() {
+    let dir = std::env::temp_dir().join(format!("tracing-tunnel-test-{}", std::process::id()));
+    let mut store = FileStore::new(&dir).unwrap();
+    assert!(store.load_metadata().unwrap().is_empty());
+    assert!(store.load_spans().unwrap().is_empty());
+
+    let mut receiver = TracingEventReceiver::default();
+    receiver.receive(TracingEvent::NewCallSite {
+        id: 0,
+        data: CALL_SITE_DATA,
+    });
+    receiver.receive(new_span_event(1, None));
+    receiver.receive(new_span_event(2, None));
+    store
+        .save_metadata(receiver.take_metadata_changes())
+        .unwrap();
+    store.save_spans(receiver.take_span_changes()).unwrap();
+    receiver.receive(TracingEvent::SpanDropped { id: 2 });
+    store
+        .save_metadata(receiver.take_metadata_changes())
+        .unwrap();
+    store.save_spans(receiver.take_span_changes()).unwrap();
+
+    let spans_log = std::fs::read_to_string(dir.join("spans.jsonl")).unwrap();
+    assert_eq!(spans_log.lines().count(), 2);
+    let metadata_log = std::fs::read_to_string(dir.join("metadata.jsonl")).unwrap();
+    assert_eq!(metadata_log.lines().count(), 1);
+
+    store.compact().unwrap();
+    let spans_log = std::fs::read_to_string(dir.join("spans.jsonl")).unwrap();
+    assert_eq!(spans_log.lines().count(), 1);
+
+    let metadata = store.load_metadata().unwrap();
+    assert_eq!(metadata.len(), 1);
+    let spans = store.load_spans().unwrap();
+    assert_eq!(spans.len(), 1);
+    let mut receiver = TracingEventReceiver::new(metadata, spans, LocalSpans::default());
+    visit_and_drop_span(&mut receiver);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(feature = "file-store")]
+#[test]
+fn file_store_with_torn_final_line() {
+    use std::io::Write as _;
+
+    let dir = std::env::temp_dir().join(format!("tracing-tunnel-torn-{}", std::process::id()));
+    let mut store = FileStore::new(&dir).unwrap();
+    let mut receiver = TracingEventReceiver::default();
+    receiver.receive(TracingEvent::NewCallSite {
+        id: 0,
+        data: CALL_SITE_DATA,
+    });
+    receiver.receive(new_span_event(1, None));
+    store.save_spans(receiver.take_span_changes()).unwrap();
+    let spans_path = dir.join("spans.jsonl");
+    let committed_log = std::fs::read_to_string(&spans_path).unwrap();
+
+    // Emulate a crash while appending changes.
+    let mut file = std::fs::OpenOptions::new()
+        .append(true)
+        .open(&spans_path)
+        .unwrap();
+    file.write_all(br#"{"updated":{"2":"#).unwrap();
+    drop(file);
+
+    let spans = store.load_spans().unwrap();
+    assert_eq!(spans.len(), 1);
+    assert_eq!(std::fs::read_to_string(&spans_path).unwrap(), committed_log);
+
+    receiver.receive(new_span_event(2, None));
+    store.save_spans(receiver.take_span_changes()).unwrap();
+    let spans = store.load_spans().unwrap();
+    assert_eq!(spans.len(), 2);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn parking_events_with_unknown_call_sites() {
+    let mut receiver = TracingEventReceiver::builder()
+        .with_call_site_buffer(10, Duration::from_secs(3_600))
+        .build();
+    receiver.try_receive(new_span_event(1, None)).unwrap();
+    receiver
+        .try_receive(TracingEvent::SpanEntered { id: 1 })
+        .unwrap();
+    // Reporting meta events are not parked...
+    receiver
+        .try_receive(TracingEvent::EventsDropped { count: 1 })
+        .unwrap();
+    assert_eq!(receiver.counters().reported_dropped_events, 1);
+    // ...but flush markers are parked behind the preceding events.
+    receiver
+        .try_receive(TracingEvent::Flush { sequence: 2 })
+        .unwrap();
+    assert!(receiver.last_flush().is_none());
+    assert_eq!(receiver.parked_event_count(), 3);
+    assert!(receiver.parking_deadline().is_some());
+    assert_eq!(receiver.alive_spans().count(), 0);
+
+    receiver
+        .try_receive(TracingEvent::NewCallSite {
+            id: 0,
+            data: CALL_SITE_DATA,
+        })
+        .unwrap();
+    assert_eq!(receiver.parked_event_count(), 0);
+    assert_eq!(receiver.parking_deadline(), None);
+    assert!(receiver.last_flush().is_some());
+    let alive_spans = sorted_alive_spans(&receiver);
+    assert_matches!(
+        alive_spans.as_slice(),
+        [AliveSpan {
+            id: 1,
+            is_entered: true,
+            ..
+        }]
+    );
+
+    let counters = receiver.counters();
+    assert_eq!(counters.parked_events, 3);
+    assert_eq!(counters.new_spans, 1);
+    assert_eq!(counters.errors, 0);
+}
+
+#[test]
+fn shutdown_marker_is_parked_behind_events() {
+    let mut receiver = TracingEventReceiver::builder()
+        .with_call_site_buffer(10, Duration::MAX)
+        .build();
+    receiver.try_receive(new_span_event(1, None)).unwrap();
+    receiver
+        .try_receive(TracingEvent::Shutdown { sequence: 1 })
+        .unwrap();
+    assert!(receiver.last_flush().is_none());
+    assert_eq!(receiver.parked_event_count(), 2);
+
+    let err = receiver.flush_parked().unwrap_err();
+    assert_matches!(err, ReceiveError::UnknownMetadataId(0));
+    assert!(receiver.last_flush().unwrap().is_final);
+}
+
+#[test]
+fn parked_events_are_relayed_on_overflow() {
+    let mut receiver = TracingEventReceiver::builder()
+        .with_call_site_buffer(1, Duration::MAX)
+        .build();
+    receiver.try_receive(new_span_event(1, None)).unwrap();
+    let err = receiver.try_receive(new_span_event(2, None)).unwrap_err();
+    assert_matches!(err, ReceiveError::UnknownMetadataId(0));
+    assert_eq!(receiver.parked_event_count(), 1);
+
+    let err = receiver.flush_parked().unwrap_err();
+    assert_matches!(err, ReceiveError::UnknownMetadataId(0));
+    assert_eq!(receiver.parked_event_count(), 0);
+    assert_eq!(receiver.counters().errors, 2);
+}
+
+#[test]
+fn parked_events_are_relayed_after_deadline() {
+    let mut receiver = TracingEventReceiver::builder()
+        .with_call_site_buffer(10, Duration::ZERO)
+        .build();
+    let err = receiver.try_receive(new_span_event(1, None)).unwrap_err();
+    assert_matches!(err, ReceiveError::UnknownMetadataId(0));
+    assert_eq!(receiver.parked_event_count(), 0);
+    assert_eq!(receiver.counters().parked_events, 1);
+}
@@ -0,0 +1,407 @@
+//! JSON Schema for the wire format of `TracingEvent`s.
+
+use serde_json::{json, Value as Json};
+
+use crate::TracingEvent;
+
+fn u32_schema() -> Json {
+    json!({ "type": "integer", "minimum": 0, "maximum": u32::MAX })
+}
+
+fn variant(tag: &str, properties: &Json, required: &[&str]) -> Json {
+    json!({
+        "type": "object",
+        "properties": {
+            tag: {
+                "type": "object",
+                "properties": properties,
+                "required": required,
+            },
+        },
+        "required": [tag],
+        "additionalProperties": false,
+    })
+}
+
+fn call_site_properties() -> Json {
+    json!({
+        "id": { "$ref": "#/$defs/MetadataId" },
+        "kind": { "$ref": "#/$defs/CallSiteKind" },
+        "name": { "type": "string" },
+        "target": { "type": "string" },
+        "level": { "$ref": "#/$defs/TracingLevel" },
+        "module_path": { "type": "string" },
+        "file": { "type": "string" },
+        "line": u32_schema(),
+        "fields": { "type": "array", "items": { "type": "string" } },
+    })
+}
+
+fn event_variants() -> Vec<Json> {
+    let span_id = json!({ "id": { "$ref": "#/$defs/RawSpanId" } });
+    let sequence = json!({ "sequence": u32_schema() });
+    vec![
+        variant(
+            "new_call_site",
+            &call_site_properties(),
+            &["id", "kind", "name", "target", "level", "fields"],
+        ),
+        variant(
+            "new_span",
+            &json!({
+                "id": { "$ref": "#/$defs/RawSpanId" },
+                "parent_id": { "$ref": "#/$defs/RawSpanId" },
+                "is_root": { "type": "boolean" },
+                "metadata_id": { "$ref": "#/$defs/MetadataId" },
+                "values": { "$ref": "#/$defs/TracedValues" },
+                "generation": u32_schema(),
+            }),
+            &["id", "metadata_id", "values"],
+        ),
+        variant(
+            "follows_from",
+            &json!({
+                "id": { "$ref": "#/$defs/RawSpanId" },
+                "follows_from": { "$ref": "#/$defs/RawSpanId" },
+            }),
+            &["id", "follows_from"],
+        ),
+        variant("span_entered", &span_id, &["id"]),
+        variant("span_exited", &span_id, &["id"]),
+        variant("span_cloned", &span_id, &["id"]),
+        variant("span_dropped", &span_id, &["id"]),
+        variant(
+            "values_recorded",
+            &json!({
+                "id": { "$ref": "#/$defs/RawSpanId" },
+                "values": { "$ref": "#/$defs/TracedValues" },
+            }),
+            &["id", "values"],
+        ),
+        variant(
+            "new_event",
+            &json!({
+                "metadata_id": { "$ref": "#/$defs/MetadataId" },
+                "parent": { "$ref": "#/$defs/RawSpanId" },
+                "is_root": { "type": "boolean" },
+                "values": { "$ref": "#/$defs/TracedValues" },
+            }),
+            &["metadata_id", "values"],
+        ),
+        variant(
+            "simple_event",
+            &json!({
+                "metadata_id": { "$ref": "#/$defs/MetadataId" },
+                "message": { "type": "string" },
+            }),
+            &["metadata_id", "message"],
+        ),
+        variant(
+            "heartbeat",
+            &json!({ "sequence": u32_schema(), "dropped_events": u32_schema() }),
+            &["sequence", "dropped_events"],
+        ),
+        variant(
+            "events_dropped",
+            &json!({ "count": u32_schema() }),
+            &["count"],
+        ),
+        variant("flush", &sequence, &["sequence"]),
+        variant("shutdown", &sequence, &["sequence"]),
+    ]
+}
+
+fn definitions() -> Json {
+    let value_variants: Vec<_> = [
+        ("bool", json!({ "type": "boolean" })),
+        ("int", json!({ "type": "integer" })),
+        ("u_int", json!({ "type": "integer", "minimum": 0 })),
+        // Non-finite floats are serialized as `null` by `serde_json`.
+        ("float", json!({ "type": ["number", "null"] })),
+        ("string", json!({ "type": "string" })),
+        ("object", json!({ "type": "string" })),
+        (
+            "bytes",
+            json!({ "type": "string", "contentEncoding": "base64" }),
+        ),
+        ("error", json!({ "$ref": "#/$defs/TracedError" })),
+    ]
+    .into_iter()
+    .map(|(tag, schema)| {
+        json!({
+            "type": "object",
+            "properties": { tag: schema },
+            "required": [tag],
+            "additionalProperties": false,
+        })
+    })
+    .collect();
+
+    json!({
+        "MetadataId": { "type": "integer", "minimum": 0 },
+        "RawSpanId": { "type": "integer", "minimum": 0 },
+        "TracingLevel": { "enum": ["error", "warn", "info", "debug", "trace"] },
+        "CallSiteKind": { "enum": ["span", "event"] },
+        "TracedValues": {
+            "type": "object",
+            "additionalProperties": { "$ref": "#/$defs/TracedValue" },
+        },
+        "TracedValue": { "oneOf": value_variants },
+        "TracedError": {
+            "type": "object",
+            "properties": {
+                "message": { "type": "string" },
+                "source": {
+                    "oneOf": [{ "$ref": "#/$defs/TracedError" }, { "type": "null" }],
+                },
+            },
+            "required": ["message"],
+        },
+    })
+}
+
+impl TracingEvent {
+    /// Returns the [JSON Schema] (draft 2020-12) for the JSON presentation of [`TracingEvent`]s,
+    /// i.e., the presentation produced by serializing events with `serde_json`. The schema
+    /// allows non-Rust consumers of the tunnel (e.g., a TypeScript host) to generate types
+    /// for events and to validate event payloads.
+    ///
+    /// The wire format version is specified in the `x-wire-format-version` schema field
+    /// and is equal to [`Self::WIRE_FORMAT_VERSION`]. The schema does not prohibit unknown
+    /// fields in events, similar to deserialization with `serde`.
+    ///
+    /// [JSON Schema]: https://json-schema.org/
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use tracing_tunnel::TracingEvent;
+    /// let schema = TracingEvent::json_schema();
+    /// assert_eq!(schema["x-wire-format-version"], TracingEvent::WIRE_FORMAT_VERSION);
+    /// // The schema can be saved to a file and used to generate TypeScript types
+    /// // with a tool like `json-schema-to-typescript`.
+    /// let schema_string = serde_json::to_string_pretty(&schema)?;
+    /// # Ok::<_, serde_json::Error>(())
+    /// ```
+    pub fn json_schema() -> Json {
+        json!({
+            "$schema": "https://json-schema.org/draft/2020-12/schema",
+            "title": "TracingEvent",
+            "description": "Tracing event passed through tracing-tunnel",
+            "x-wire-format-version": Self::WIRE_FORMAT_VERSION,
+            "oneOf": event_variants(),
+            "$defs": definitions(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use super::*;
+    use crate::{CallSiteData, CallSiteKind, TracedValue, TracedValues, TracingLevel};
+
+    /// Validates a value against the subset of JSON Schema used in `json_schema()`.
+    fn is_valid(root: &Json, schema: &Json, value: &Json) -> bool {
+        if let Some(reference) = schema.get("$ref").and_then(Json::as_str) {
+            let name = reference.strip_prefix("#/$defs/").unwrap();
+            return is_valid(root, &root["$defs"][name], value);
+        }
+        if let Some(variants) = schema.get("oneOf").and_then(Json::as_array) {
+            let matches = variants
+                .iter()
+                .filter(|variant| is_valid(root, variant, value));
+            if matches.count() != 1 {
+                return false;
+            }
+        }
+        if let Some(allowed) = schema.get("enum").and_then(Json::as_array) {
+            if !allowed.contains(value) {
+                return false;
+            }
+        }
+        if let Some(ty) = schema.get("type") {
+            let types: Vec<_> = match ty {
+                Json::String(ty) => vec![ty.as_str()],
+                Json::Array(types) => types.iter().filter_map(Json::as_str).collect(),
+                _ => unreachable!(),
+            };
+            if !types.into_iter().any(|ty| has_type(value, ty)) {
+                return false;
+            }
+        }
+        if let Some(min) = schema.get("minimum").and_then(Json::as_i64) {
+            if value.as_i64().is_some_and(|value| value < min) {
+                return false;
+            }
+        }
+        if let Some(max) = schema.get("maximum").and_then(Json::as_u64) {
+            if value.as_u64().map_or(true, |value| value > max) {
+                // Negative values are rejected by the `minimum` check.
+                return false;
+            }
+        }
+        if let Some(items) = schema.get("items") {
+            let array = value.as_array().unwrap();
+            if !array.iter().all(|item| is_valid(root, items, item)) {
+                return false;
+            }
+        }
+        if let Json::Object(map) = value {
+            is_valid_object(root, schema, map)
+        } else {
+            true
+        }
+    }
+
+    fn has_type(value: &Json, ty: &str) -> bool {
+        match ty {
+            "null" => value.is_null(),
+            "boolean" => value.is_boolean(),
+            // Large integers (e.g., `u128` values) are parsed as floats.
+            "integer" => value.as_f64().is_some_and(|value| value.fract() == 0.0),
+            "number" => value.is_number(),
+            "string" => value.is_string(),
+            "array" => value.is_array(),
+            "object" => value.is_object(),
+            _ => unreachable!(),
+        }
+    }
+
+    fn is_valid_object(root: &Json, schema: &Json, map: &serde_json::Map<String, Json>) -> bool {
+        let required = schema.get("required").and_then(Json::as_array);
+        let required = required.map_or(&[][..], Vec::as_slice);
+        if !required
+            .iter()
+            .all(|key| map.contains_key(key.as_str().unwrap()))
+        {
+            return false;
+        }
+        let properties = schema.get("properties").and_then(Json::as_object);
+        let additional = schema.get("additionalProperties");
+        map.iter().all(|(key, value)| {
+            if let Some(property) = properties.and_then(|props| props.get(key)) {
+                is_valid(root, property, value)
+            } else {
+                match additional {
+                    Some(Json::Bool(false)) => false,
+                    Some(additional) => is_valid(root, additional, value),
+                    None => true,
+                }
+            }
+        })
+    }
+
+    fn sample_values() -> TracedValues<String> {
+        #[derive(Debug)]
+        struct TestError;
+
+        impl std::fmt::Display for TestError {
+            fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                formatter.write_str("test error")
+            }
+        }
+
+        impl std::error::Error for TestError {}
+
+        let values = [
+            ("bool", TracedValue::from(true)),
+            ("int", TracedValue::from(-42_i64)),
+            ("uint", TracedValue::from(u128::MAX)),
+            ("float", TracedValue::from(0.5)),
+            ("nan", TracedValue::from(f64::NAN)),
+            ("string", TracedValue::from("test")),
+            ("object", TracedValue::debug(&Some(1))),
+            ("bytes", TracedValue::from(&b"\x00bytes"[..])),
+            ("error", TracedValue::error(&TestError)),
+        ];
+        values
+            .into_iter()
+            .map(|(name, value)| (name.to_owned(), value))
+            .collect()
+    }
+
+    fn sample_events() -> Vec<TracingEvent> {
+        let call_site = CallSiteData {
+            kind: CallSiteKind::Span,
+            name: Cow::Borrowed("test"),
+            target: Cow::Borrowed("tracing_tunnel"),
+            level: TracingLevel::Info,
+            module_path: Some(Cow::Borrowed("schema::tests")),
+            file: None,
+            line: Some(42),
+            fields: vec![Cow::Borrowed("bool")],
+        };
+        vec![
+            TracingEvent::NewCallSite {
+                id: 1,
+                data: call_site,
+            },
+            TracingEvent::NewSpan {
+                id: 1,
+                parent_id: None,
+                is_root: true,
+                metadata_id: 1,
+                values: sample_values(),
+                generation: Some(3),
+            },
+            TracingEvent::FollowsFrom {
+                id: 2,
+                follows_from: 1,
+            },
+            TracingEvent::SpanEntered { id: 1 },
+            TracingEvent::SpanExited { id: 1 },
+            TracingEvent::SpanCloned { id: 1 },
+            TracingEvent::SpanDropped { id: 1 },
+            TracingEvent::ValuesRecorded {
+                id: 1,
+                values: sample_values(),
+            },
+            TracingEvent::NewEvent {
+                metadata_id: 1,
+                parent: Some(1),
+                is_root: false,
+                values: TracedValues::new(),
+            },
+            TracingEvent::SimpleEvent {
+                metadata_id: 1,
+                message: "Hello, world!".to_owned(),
+            },
+            TracingEvent::Heartbeat {
+                sequence: 10,
+                dropped_events: 0,
+            },
+            TracingEvent::EventsDropped { count: 5 },
+            TracingEvent::Flush { sequence: 10 },
+            TracingEvent::Shutdown { sequence: 12 },
+        ]
+    }
+
+    #[test]
+    fn events_conform_to_schema() {
+        let schema = TracingEvent::json_schema();
+        for event in sample_events() {
+            let event = serde_json::to_string(&event).unwrap();
+            let event: Json = serde_json::from_str(&event).unwrap();
+            assert!(is_valid(&schema, &schema, &event), "{event:#}");
+        }
+    }
+
+    #[test]
+    fn schema_rejects_invalid_events() {
+        let schema = TracingEvent::json_schema();
+        let invalid_events = [
+            json!({ "span_entered": {} }),
+            json!({ "span_entered": { "id": -1 } }),
+            json!({ "unknown": { "id": 1 } }),
+            json!({ "span_entered": { "id": 1 }, "span_exited": { "id": 1 } }),
+            json!({ "heartbeat": { "sequence": 1_u64 << 32, "dropped_events": 0 } }),
+            json!({ "new_event": { "metadata_id": 1, "values": { "test": 1 } } }),
+            json!({ "new_event": { "metadata_id": 1, "values": { "test": { "bogus": 1 } } } }),
+        ];
+        for event in invalid_events {
+            assert!(!is_valid(&schema, &schema, &event), "{event:#}");
+        }
+    }
+}
@@ -0,0 +1,219 @@
+//! `LayeredSender` teeing tracing data to a local subscriber and a `TracingEventSender`.
+
+use tracing_core::{
+    span::{Attributes, Id, Record},
+    Dispatch, Event, Interest, Metadata, Subscriber,
+};
+
+use super::{
+    sync::{Mutex, ReentrancyGuard},
+    TracingEventSender,
+};
+use crate::{alloc::BTreeMap, RawSpanId, TracingEvent};
+
+/// Tracing [`Subscriber`] that tees tracing data to a local subscriber and
+/// a [`TracingEventSender`]. Created using [`TracingEventSender::layered()`]
+/// or [`Self::new()`].
+///
+/// This is useful to debug a traced guest locally (e.g., by writing traces to stdout using
+/// a `tracing_subscriber::fmt` subscriber) while still sending traces to the host, without
+/// depending on the [`Registry`] and layers from `tracing-subscriber`, which may be unavailable
+/// or too heavy in minimal WASM guests.
+///
+/// [`Registry`]: https://docs.rs/tracing-subscriber/0.3/tracing_subscriber/registry/struct.Registry.html
+///
+/// # Span IDs and filtering
+///
+/// The sender and the local subscriber allocate span IDs independently; spans exposed
+/// to the tracing infrastructure have IDs allocated by the sender, and `LayeredSender` maps
+/// them to local span IDs. The sender receives all spans and events, while the local subscriber
+/// only receives spans and events it is [enabled](Subscriber::enabled()) for.
+///
+/// Since a tracing [`Event`] cannot be re-targeted to another parent span, events
+/// with an explicitly specified parent (e.g., `tracing::info!(parent: &span, "test")`)
+/// are only sent to the sender.
+///
+/// # Threading
+///
+/// Spans closed by the local subscriber from within [`Subscriber::try_close()`] are detected
+/// using a reentrancy guard. With the `std` crate feature, the guard is tracked per thread.
+/// Without `std`, the guard is shared by all threads, so a span closed on one thread
+/// while another thread is closing a span would be misinterpreted as a local span;
+/// thus, without `std`, `LayeredSender` must only be used from a single thread.
+///
+/// # Examples
+///
+/// ```
+/// # use std::sync::mpsc;
+/// # use tracing_subscriber::FmtSubscriber;
+/// # use tracing_tunnel::{TracingEvent, TracingEventSender};
+/// let (events_sx, events_rx) = mpsc::channel();
+/// let local = FmtSubscriber::builder().with_test_writer().finish();
+/// let subscriber = TracingEventSender::layered(local, move |event| {
+///     events_sx.send(event).ok();
+/// });
+/// tracing::subscriber::with_default(subscriber, || {
+///     tracing::info_span!("compute").in_scope(|| tracing::info!("done"));
+/// });
+///
+/// let events: Vec<_> = events_rx.iter().collect();
+/// assert!(events
+///     .iter()
+///     .any(|event| matches!(event, TracingEvent::NewEvent { .. })));
+/// ```
+#[derive(Debug)]
+pub struct LayeredSender<S, F = fn(TracingEvent)> {
+    local: S,
+    sender: TracingEventSender<F>,
+    /// Mapping from sender span IDs to local span IDs.
+    local_ids: Mutex<BTreeMap<RawSpanId, Id>>,
+    /// Set when exiting or closing a local span. The local subscriber may close spans
+    /// via the default dispatcher (e.g., this is how the `Registry` works), i.e., `LayeredSender`
+    /// itself; such nested calls use local span IDs and must be forwarded directly
+    /// to the local subscriber.
+    local_close: ReentrancyGuard,
+}
+
+impl<S, F> LayeredSender<S, F>
+where
+    S: Subscriber,
+    F: Fn(TracingEvent) + 'static,
+{
+    /// Creates a subscriber teeing tracing data to the `local` subscriber and
+    /// the specified `sender`. This allows configuring the sender, e.g. to emit
+    /// [heartbeats](TracingEventSender::with_heartbeat()).
+    pub fn new(local: S, sender: TracingEventSender<F>) -> Self {
+        Self {
+            local,
+            sender,
+            local_ids: Mutex::default(),
+            local_close: ReentrancyGuard::default(),
+        }
+    }
+
+    /// Returns a reference to the local subscriber.
+    pub fn local(&self) -> &S {
+        &self.local
+    }
+
+    /// Returns a reference to the sender, e.g. to [flush](TracingEventSender::flush()) it.
+    pub fn sender(&self) -> &TracingEventSender<F> {
+        &self.sender
+    }
+
+    fn local_id(&self, id: &Id) -> Option<Id> {
+        self.local_ids.lock().get(&id.into_u64()).cloned()
+    }
+
+    fn local_attributes<'a>(&self, attrs: &'a Attributes<'_>) -> Attributes<'a> {
+        let metadata = attrs.metadata();
+        if let Some(parent) = attrs.parent() {
+            if let Some(local_parent) = self.local_id(parent) {
+                return Attributes::child_of(local_parent, metadata, attrs.values());
+            }
+        }
+        if attrs.is_root() {
+            Attributes::new_root(metadata, attrs.values())
+        } else {
+            // Includes the case when the explicit parent is disabled for the local subscriber.
+            Attributes::new(metadata, attrs.values())
+        }
+    }
+}
+
+impl<F: Fn(TracingEvent) + 'static> TracingEventSender<F> {
+    /// Creates a subscriber teeing tracing data to the `local` subscriber and a sender
+    /// with the specified "on event" hook. See [`LayeredSender`] for details.
+    pub fn layered<S: Subscriber>(local: S, on_event: F) -> LayeredSender<S, F> {
+        LayeredSender::new(local, Self::new(on_event))
+    }
+}
+
+impl<S, F> Subscriber for LayeredSender<S, F>
+where
+    S: Subscriber,
+    F: Fn(TracingEvent) + 'static,
+{
+    fn on_register_dispatch(&self, subscriber: &Dispatch) {
+        self.local.on_register_dispatch(subscriber);
+    }
+
+    fn register_callsite(&self, metadata: &'static Metadata<'static>) -> Interest {
+        self.local.register_callsite(metadata);
+        // The sender is interested in all call sites.
+        self.sender.register_callsite(metadata)
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        self.sender.enabled(metadata)
+    }
+
+    fn new_span(&self, span: &Attributes<'_>) -> Id {
+        let id = self.sender.new_span(span);
+        if self.local.enabled(span.metadata()) {
+            let local_id = self.local.new_span(&self.local_attributes(span));
+            self.local_ids.lock().insert(id.into_u64(), local_id);
+        }
+        id
+    }
+
+    fn record(&self, span: &Id, values: &Record<'_>) {
+        self.sender.record(span, values);
+        if let Some(local_id) = self.local_id(span) {
+            self.local.record(&local_id, values);
+        }
+    }
+
+    fn record_follows_from(&self, span: &Id, follows: &Id) {
+        self.sender.record_follows_from(span, follows);
+        if let (Some(local_id), Some(local_follows)) = (self.local_id(span), self.local_id(follows))
+        {
+            self.local.record_follows_from(&local_id, &local_follows);
+        }
+    }
+
+    fn event(&self, event: &Event<'_>) {
+        self.sender.event(event);
+        if event.parent().is_none() && self.local.enabled(event.metadata()) {
+            self.local.event(event);
+        }
+    }
+
+    fn enter(&self, span: &Id) {
+        self.sender.enter(span);
+        if let Some(local_id) = self.local_id(span) {
+            self.local.enter(&local_id);
+        }
+    }
+
+    fn exit(&self, span: &Id) {
+        self.sender.exit(span);
+        if let Some(local_id) = self.local_id(span) {
+            let _scope = self.local_close.enter();
+            self.local.exit(&local_id);
+        }
+    }
+
+    fn clone_span(&self, span: &Id) -> Id {
+        if let Some(local_id) = self.local_id(span) {
+            self.local.clone_span(&local_id);
+        }
+        self.sender.clone_span(span)
+    }
+
+    fn try_close(&self, span: Id) -> bool {
+        let Some(_scope) = self.local_close.enter() else {
+            // Nested call from the local subscriber closing a span; `span` is a local ID.
+            return self.local.try_close(span);
+        };
+        let local_id = self.local_id(&span);
+        let is_closed = self.sender.try_close(span.clone());
+        if is_closed {
+            self.local_ids.lock().remove(&span.into_u64());
+        }
+        if let Some(local_id) = local_id {
+            self.local.try_close(local_id);
+        }
+        is_closed
+    }
+}
@@ -0,0 +1,216 @@
+//! Size limits for values recorded by `TracingEventSender`.
+
+use crate::{alloc::String, TracedValue, TracedValues};
+
+/// Size limits for [`TracedValue`]s recorded by a [`TracingEventSender`].
+///
+/// Values exceeding a limit are truncated, and [`Self::TRUNCATION_MARKER`] is appended to them,
+/// so that the receiving side can distinguish truncated values from the original ones.
+/// The marker is not counted towards the limit. By default, no limits are set.
+///
+/// [`TracingEventSender`]: crate::TracingEventSender
+///
+/// # Examples
+///
+/// ```
+/// # use std::sync::mpsc;
+/// # use tracing_tunnel::{TracedValue, TracingEvent, TracingEventSender, ValueLimits};
+/// let (events_sx, events_rx) = mpsc::channel();
+/// let limits = ValueLimits::default().with_max_str_len(5);
+/// let sender = TracingEventSender::new(move |event| {
+///     events_sx.send(event).ok();
+/// });
+/// let sender = sender.with_value_limits(limits);
+/// tracing::subscriber::with_default(sender, || {
+///     tracing::info!(name = "truncated string", "hello");
+/// });
+///
+/// let values = events_rx.iter().find_map(|event| match event {
+///     TracingEvent::NewEvent { values, .. } => Some(values),
+///     _ => None,
+/// });
+/// let name = values.unwrap()["name"].as_str().unwrap().to_owned();
+/// assert_eq!(name, format!("trunc{}", ValueLimits::TRUNCATION_MARKER));
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ValueLimits {
+    str_len: Option<usize>,
+    debug_len: Option<usize>,
+    error_depth: Option<usize>,
+}
+
+impl ValueLimits {
+    /// Marker appended to truncated strings, [`Debug`](core::fmt::Debug) outputs
+    /// and error messages.
+    pub const TRUNCATION_MARKER: &'static str = "…[truncated]";
+
+    /// Sets the maximum length of [string values](TracedValue::String) in bytes.
+    /// Strings are truncated at the `char` boundary.
+    #[must_use]
+    pub fn with_max_str_len(mut self, len: usize) -> Self {
+        self.str_len = Some(len);
+        self
+    }
+
+    /// Sets the maximum length of [`Debug`](core::fmt::Debug) representations
+    /// of [objects](TracedValue::Object) in bytes. Representations are truncated
+    /// at the `char` boundary.
+    #[must_use]
+    pub fn with_max_debug_len(mut self, len: usize) -> Self {
+        self.debug_len = Some(len);
+        self
+    }
+
+    /// Sets the maximum number of errors in the [source] chain of [error values],
+    /// including the top-level error. If the chain is longer, its tail is dropped,
+    /// and the marker is appended to the message of the last retained error.
+    ///
+    /// [source]: std::error::Error::source()
+    /// [error values]: TracedValue::Error
+    ///
+    /// # Panics
+    ///
+    /// Panics if `depth` is zero.
+    #[must_use]
+    pub fn with_max_error_depth(mut self, depth: usize) -> Self {
+        assert!(depth > 0, "error chain depth must be positive");
+        self.error_depth = Some(depth);
+        self
+    }
+
+    fn is_empty(&self) -> bool {
+        self.str_len.is_none() && self.debug_len.is_none() && self.error_depth.is_none()
+    }
+
+    /// Truncates the provided values according to these limits. Returns the number
+    /// of truncated values.
+    pub(super) fn apply<S: AsRef<str>>(&self, values: &mut TracedValues<S>) -> u32 {
+        if self.is_empty() {
+            return 0;
+        }
+        let mut truncated_count = 0;
+        for value in values.values_mut() {
+            if self.apply_to_value(value) {
+                truncated_count += 1;
+            }
+        }
+        truncated_count
+    }
+
+    fn apply_to_value(&self, value: &mut TracedValue) -> bool {
+        match value {
+            TracedValue::String(s) => self.str_len.is_some_and(|len| truncate(s, len)),
+            TracedValue::Object(obj) => self.debug_len.is_some_and(|len| truncate(&mut obj.0, len)),
+            #[cfg(feature = "std")]
+            TracedValue::Error(err) => self.error_depth.is_some_and(|depth| {
+                let mut err = err;
+                for _ in 1..depth {
+                    match &mut err.source {
+                        Some(source) => err = source,
+                        None => return false,
+                    }
+                }
+                if err.source.take().is_some() {
+                    err.message.push_str(Self::TRUNCATION_MARKER);
+                    true
+                } else {
+                    false
+                }
+            }),
+            _ => false,
+        }
+    }
+}
+
+/// Truncates `s` to at most `max_len` bytes and appends the truncation marker. Returns `false`
+/// if `s` fits into the limit.
+fn truncate(s: &mut String, max_len: usize) -> bool {
+    if s.len() <= max_len {
+        return false;
+    }
+    let mut len = max_len;
+    while !s.is_char_boundary(len) {
+        len -= 1;
+    }
+    s.truncate(len);
+    s.push_str(ValueLimits::TRUNCATION_MARKER);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alloc::ToOwned;
+
+    #[test]
+    fn truncating_strings() {
+        let mut s = "hello".to_owned();
+        assert!(!truncate(&mut s, 5));
+        assert_eq!(s, "hello");
+        assert!(truncate(&mut s, 3));
+        assert_eq!(s, "hel…[truncated]");
+
+        let mut s = "тест".to_owned();
+        assert!(truncate(&mut s, 3)); // cuts the second char in half
+        assert_eq!(s, "т…[truncated]");
+    }
+
+    #[test]
+    fn applying_limits() {
+        let limits = ValueLimits::default()
+            .with_max_str_len(4)
+            .with_max_debug_len(2);
+        let mut values: TracedValues<&str> = [
+            ("short", TracedValue::from("test")),
+            ("long", TracedValue::from("test string")),
+            ("debug", TracedValue::debug(&[1, 2, 3])),
+            ("int", TracedValue::from(42_u64)),
+        ]
+        .into_iter()
+        .collect();
+
+        assert_eq!(limits.apply(&mut values), 2);
+        assert_eq!(values["short"], "test");
+        assert_eq!(values["long"], "test…[truncated]");
+        let debug = values["debug"].as_debug_str().unwrap();
+        assert_eq!(debug, "[1…[truncated]");
+        assert_eq!(values["int"], 42_u64);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn truncating_error_chains() {
+        use crate::TracedError;
+
+        fn error_chain(depth: usize) -> TracedError {
+            let mut err = TracedError {
+                message: "root cause".to_owned(),
+                source: None,
+            };
+            for i in 1..depth {
+                err = TracedError {
+                    message: crate::alloc::format!("err #{i}"),
+                    source: Some(Box::new(err)),
+                };
+            }
+            err
+        }
+
+        let limits = ValueLimits::default().with_max_error_depth(2);
+        let mut values: TracedValues<&str> = [
+            ("short", TracedValue::Error(error_chain(2))),
+            ("long", TracedValue::Error(error_chain(4))),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(limits.apply(&mut values), 1);
+
+        let TracedValue::Error(err) = &values["long"] else {
+            panic!("unexpected value: {:?}", values["long"]);
+        };
+        assert_eq!(err.message, "err #3");
+        let source = err.source.as_ref().unwrap();
+        assert_eq!(source.message, "err #2…[truncated]");
+        assert!(source.source.is_none());
+    }
+}
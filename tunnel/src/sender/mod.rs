@@ -0,0 +1,730 @@
+//! Client-side subscriber.
+
+use tracing_core::{
+    span::{Attributes, Id, Record},
+    Event, Interest, Metadata, Subscriber,
+};
+
+use core::{
+    mem,
+    num::NonZeroU32,
+    sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering},
+};
+
+use self::sync::{Mutex, MutexGuard, ReentrancyGuard};
+use crate::{
+    alloc::Vec, CallSiteData, MetadataId, RawSpanId, SequencedEvent, TracedValue, TracedValues,
+    TracingEvent,
+};
+
+mod layered;
+mod limits;
+#[cfg(feature = "std")]
+mod queue;
+mod routing;
+mod sync;
+#[cfg(feature = "wasm-web")]
+mod wasm;
+
+#[cfg(feature = "std")]
+pub use self::queue::{DropPolicy, EventQueue};
+pub use self::{layered::LayeredSender, limits::ValueLimits, routing::TargetRoutes};
+
+#[cfg(feature = "std")]
+type SpanRefsMap = std::collections::HashMap<RawSpanId, SpanRef>;
+#[cfg(not(feature = "std"))]
+type SpanRefsMap = crate::alloc::BTreeMap<RawSpanId, SpanRef>;
+
+/// Reference count for a span created by a [`TracingEventSender`].
+#[derive(Debug, Clone, Copy)]
+struct SpanRef {
+    count: usize,
+    /// Was the span created from inside the "on event" hook? Such spans are not sent
+    /// to the receiver, and neither are their lifecycle events.
+    is_from_hook: bool,
+}
+
+/// Reference counts for spans created by a [`TracingEventSender`].
+#[derive(Debug, Default)]
+struct SpanRefs {
+    refs: Mutex<SpanRefsMap>,
+    /// Number of live spans created from inside the hook; allows to skip locking `refs`
+    /// for span entries / exits in the common case.
+    hook_spans: AtomicUsize,
+}
+
+impl SpanRefs {
+    fn lock(&self) -> MutexGuard<'_, SpanRefsMap> {
+        self.refs.lock()
+    }
+
+    fn on_new_span(&self, id: RawSpanId, is_from_hook: bool) {
+        if is_from_hook {
+            self.hook_spans.fetch_add(1, Ordering::SeqCst);
+        }
+        let span_ref = SpanRef {
+            count: 1,
+            is_from_hook,
+        };
+        self.lock().insert(id, span_ref);
+    }
+
+    fn is_from_hook(&self, id: RawSpanId) -> bool {
+        if self.hook_spans.load(Ordering::SeqCst) == 0 {
+            return false;
+        }
+        self.lock()
+            .get(&id)
+            .is_some_and(|span_ref| span_ref.is_from_hook)
+    }
+
+    /// Returns `true` if the span was created from inside the hook.
+    fn on_span_cloned(&self, id: RawSpanId) -> bool {
+        let mut refs = self.lock();
+        let Some(span_ref) = refs.get_mut(&id) else {
+            return false;
+        };
+        span_ref.count += 1;
+        span_ref.is_from_hook
+    }
+
+    /// Returns the updated reference count, or `None` if the span is unknown.
+    fn on_span_dropped(&self, id: RawSpanId) -> Option<SpanRef> {
+        let mut refs = self.lock();
+        let span_ref = refs.get_mut(&id)?;
+        span_ref.count -= 1;
+        let span_ref = *span_ref;
+        if span_ref.count == 0 {
+            refs.remove(&id);
+            if span_ref.is_from_hook {
+                self.hook_spans.fetch_sub(1, Ordering::SeqCst);
+            }
+        }
+        Some(span_ref)
+    }
+}
+
+impl TracingEvent {
+    fn new_span(
+        span: &Attributes<'_>,
+        metadata_id: MetadataId,
+        id: RawSpanId,
+        generation: Option<u32>,
+    ) -> Self {
+        Self::NewSpan {
+            id,
+            parent_id: span.parent().map(Id::into_u64),
+            is_root: span.is_root(),
+            metadata_id,
+            values: TracedValues::from_values(span.values()),
+            generation,
+        }
+    }
+
+    fn values_recorded(id: RawSpanId, values: &Record<'_>) -> Self {
+        Self::ValuesRecorded {
+            id,
+            values: TracedValues::from_record(values),
+        }
+    }
+
+    fn new_event(event: &Event<'_>, metadata_id: MetadataId) -> Self {
+        Self::NewEvent {
+            metadata_id,
+            parent: event.parent().map(Id::into_u64),
+            is_root: event.is_root(),
+            values: TracedValues::from_event(event),
+        }
+    }
+
+    /// Converts a [`Self::NewEvent`] into a [`Self::SimpleEvent`] if possible.
+    fn into_simple(self) -> Self {
+        if let Self::NewEvent {
+            metadata_id,
+            parent: None,
+            is_root: false,
+            values,
+        } = &self
+        {
+            if let (1, Some(TracedValue::Object(message))) = (values.len(), values.get("message")) {
+                return Self::SimpleEvent {
+                    metadata_id: *metadata_id,
+                    message: message.as_ref().into(),
+                };
+            }
+        }
+        self
+    }
+}
+
+/// Tracing [`Subscriber`] that converts tracing events into (de)serializable [presentation]
+/// that can be sent elsewhere using a customizable hook.
+///
+/// As an example, this subscriber is used in the [Tardigrade client library] to send
+/// workflow traces to the host via a WASM import function.
+///
+/// # Heartbeats
+///
+/// The sender can optionally emit [`TracingEvent::Heartbeat`]s after every N events
+/// (see [`Self::with_heartbeat()`]), or on demand using [`Self::send_heartbeat()`].
+/// A heartbeat contains the number of events emitted so far and the number of events
+/// reported as dropped (see [`Self::with_dropped_events()`]). This allows the host
+/// to detect wedged or disconnected senders, and gaps in the event stream;
+/// see [`TracingEventReceiver::last_heartbeat()`].
+///
+/// [`TracingEventReceiver::last_heartbeat()`]: crate::TracingEventReceiver::last_heartbeat()
+///
+/// # Flushing and shutdown
+///
+/// The sender can emit a [`TracingEvent::Flush`] marker on demand using [`Self::flush()`],
+/// and a [`TracingEvent::Shutdown`] marker when it is dropped if [enabled](Self::with_shutdown_on_drop()).
+/// These markers allow the host to learn that the event stream is complete (e.g., to finalize
+/// the persisted receiver state) without relying on timeouts; see
+/// [`TracingEventReceiver::last_flush()`].
+///
+/// [`TracingEventReceiver::last_flush()`]: crate::TracingEventReceiver::last_flush()
+///
+/// # Span references
+///
+/// The sender emits [`TracingEvent::SpanCloned`] and [`TracingEvent::SpanDropped`] events
+/// each time a span handle is cloned or dropped (e.g., when a span is moved into a spawned task),
+/// so that the receiver can track span lifetimes. The sender also counts span references itself,
+/// and [`Subscriber::try_close()`] returns `true` once the last reference to a span is dropped.
+/// Without the `std` crate feature, reference counts are protected by a spin lock.
+///
+/// ```
+/// # use tracing_core::Dispatch;
+/// # use tracing_tunnel::TracingEventSender;
+/// let dispatch = Dispatch::new(TracingEventSender::new(|_| { /* do nothing */ }));
+/// tracing::dispatcher::with_default(&dispatch, || {
+///     let span = tracing::info_span!("task");
+///     let id = span.id().unwrap();
+///     let cloned_id = dispatch.clone_span(&id);
+///     assert!(!dispatch.try_close(dispatch.clone_span(&id)));
+///     drop(span); // `cloned_id` still holds a reference to the span
+///     assert!(dispatch.try_close(cloned_id));
+/// });
+/// ```
+///
+/// # Queueing
+///
+/// By default, the "on event" hook is called synchronously on the thread emitting tracing events.
+/// Alternatively, events can be pushed into a bounded [`EventQueue`] using [`Self::queued()`]
+/// and delivered by a background thread or by polling, with a configurable [`DropPolicy`]
+/// for a full queue. Dropped events are reported via [`TracingEvent::EventsDropped`].
+///
+/// # Sequence numbers
+///
+/// Transports between the sender and the receiver may lose or reorder events (e.g., if events
+/// are sent as UDP datagrams). To detect this, a sender can be created with [`Self::sequenced()`];
+/// it wraps each emitted event into a [`SequencedEvent`] with a monotonically increasing
+/// sequence number. See [`TracingEventReceiver::try_receive_sequenced()`] for how
+/// sequence numbers are verified on the receiver side.
+///
+/// [`TracingEventReceiver::try_receive_sequenced()`]: crate::TracingEventReceiver::try_receive_sequenced()
+///
+/// # Generations
+///
+/// Span IDs are assigned sequentially by each sender instance. If the sender is restarted
+/// while a long-lived receiver still tracks spans created by the previous instance, span IDs
+/// will be reused. To detect this, the sender can be assigned a [generation](Self::with_generation())
+/// which is included into [`TracingEvent::NewSpan`]s; see [`ReceiveError::StaleSpan`]
+/// for details on handling on the receiver side.
+///
+/// [`ReceiveError::StaleSpan`]: crate::ReceiveError::StaleSpan
+///
+/// # Reentrancy
+///
+/// If the "on event" hook itself emits tracing events (e.g., logs transport errors), these events
+/// could be sent to the hook again, leading to infinite recursion or deadlocks. (`tracing` only
+/// prevents this for [scoped default subscribers]; the sender may be installed globally
+/// or called explicitly.) To prevent this, the sender drops spans and events created from inside
+/// the hook, together with lifecycle events (entries, exits, etc.) for such spans, and meta events
+/// emitted by [`Self::flush()`] and [`Self::send_heartbeat()`]; the number of dropped events
+/// is available via [`Self::suppressed_events()`]. Other events emitted
+/// from inside the hook are sent after the hook returns:
+///
+/// - Call sites, since call sites are registered only once.
+/// - Lifecycle events for spans created outside the hook (e.g., if the hook clones
+///   and drops the current span), since the receiver would otherwise lose track of these spans.
+///   Entries / exits and clones / drops of the same span performed inside the hook cancel
+///   each other out. Thus, the hook should not perform other lifecycle actions on such spans
+///   (e.g., recording values) on each call, since that would lead to infinite recursion.
+///
+/// The hook is tracked per sender, so events emitted from inside the hook via *other* senders
+/// (e.g., if the hook forwards events to another sender) are not affected. With the `std` crate
+/// feature, the hook is additionally tracked per thread, so events emitted concurrently
+/// on other threads are not affected. Without `std`, events emitted via the sender on any thread
+/// while its hook is executing are handled as described above.
+///
+/// ```
+/// # use std::sync::{Arc, Mutex, OnceLock, Weak};
+/// # use tracing_tunnel::{TracingEvent, TracingEventSender};
+/// type BoxedSender = TracingEventSender<Box<dyn Fn(TracingEvent) + Send + Sync>>;
+///
+/// let events = Arc::new(Mutex::new(vec![]));
+/// let events_ = Arc::clone(&events);
+/// let sender_cell = Arc::new(OnceLock::<Weak<BoxedSender>>::new());
+/// let sender_in_hook = Arc::clone(&sender_cell);
+/// let sender: BoxedSender = TracingEventSender::new(Box::new(move |event| {
+///     if let Some(sender) = sender_in_hook.get().and_then(Weak::upgrade) {
+///         sender.flush(); // would recurse without the guard
+///     }
+///     events_.lock().unwrap().push(event);
+/// }));
+/// let sender = Arc::new(sender);
+/// sender_cell.set(Arc::downgrade(&sender)).unwrap();
+///
+/// tracing::subscriber::with_default(Arc::clone(&sender), || {
+///     tracing::info!("hello");
+/// });
+/// // 1 call site and 1 event are sent.
+/// assert_eq!(events.lock().unwrap().len(), 2);
+/// // Flush markers emitted by the hook on each call are suppressed.
+/// assert_eq!(sender.suppressed_events(), 2);
+/// ```
+///
+/// [scoped default subscribers]: tracing_core::dispatcher::with_default()
+///
+/// # Value limits
+///
+/// Recorded values may be arbitrarily large (e.g., long strings or `Debug` outputs of large
+/// objects). The sender can truncate such values according to [`ValueLimits`] set
+/// with [`Self::with_value_limits()`]. Truncated values are marked with
+/// [`ValueLimits::TRUNCATION_MARKER`]; the number of truncated values is available via
+/// [`Self::truncated_values()`].
+///
+/// # Examples
+///
+/// See [crate-level docs](index.html) for an example of usage.
+///
+/// [presentation]: TracingEvent
+/// [Tardigrade client library]: https://github.com/slowli/tardigrade
+#[derive(Debug)]
+pub struct TracingEventSender<F = fn(TracingEvent)> {
+    next_span_id: AtomicU32,
+    sequence: AtomicU32,
+    heartbeat_interval: Option<NonZeroU32>,
+    dropped_events: Option<&'static AtomicU32>,
+    generation: Option<u32>,
+    /// Hook emitting the shutdown marker on drop. Stored as a function pointer, since `Drop`
+    /// cannot be implemented with additional bounds on `F`.
+    shutdown_hook: Option<fn(&F, TracingEvent)>,
+    span_refs: SpanRefs,
+    reentrancy: ReentrancyGuard,
+    suppressed_events: AtomicU32,
+    /// Call sites registered and span lifecycle events emitted from inside the hook.
+    pending_events: Mutex<Vec<TracingEvent>>,
+    has_pending_events: AtomicBool,
+    value_limits: ValueLimits,
+    truncated_values: AtomicU32,
+    /// Wire format version supported by the receiving side.
+    receiver_version: u32,
+    on_event: F,
+}
+
+impl<F: Fn(TracingEvent) + 'static> TracingEventSender<F> {
+    /// Minimum wire format version supporting [`TracingEvent::SimpleEvent`]s.
+    const SIMPLE_EVENTS_VERSION: u32 = 2;
+
+    /// Creates a subscriber with the specified "on event" hook.
+    pub fn new(on_event: F) -> Self {
+        Self {
+            next_span_id: AtomicU32::new(1), // 0 is invalid span ID
+            sequence: AtomicU32::new(0),
+            heartbeat_interval: None,
+            dropped_events: None,
+            generation: None,
+            shutdown_hook: None,
+            span_refs: SpanRefs::default(),
+            reentrancy: ReentrancyGuard::default(),
+            suppressed_events: AtomicU32::new(0),
+            pending_events: Mutex::default(),
+            has_pending_events: AtomicBool::new(false),
+            value_limits: ValueLimits::default(),
+            truncated_values: AtomicU32::new(0),
+            receiver_version: 1,
+            on_event,
+        }
+    }
+
+    /// Enables emitting a [`TracingEvent::Heartbeat`] after each `interval` events.
+    #[must_use]
+    pub fn with_heartbeat(mut self, interval: NonZeroU32) -> Self {
+        self.heartbeat_interval = Some(interval);
+        self
+    }
+
+    /// Sets the counter of dropped events, which will be reported in heartbeats. The counter
+    /// should be incremented by the "on event" hook each time it fails to deliver an event
+    /// (e.g., because of a full buffer).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::{num::NonZeroU32, sync::{atomic::{AtomicU32, Ordering}, mpsc}};
+    /// # use tracing_tunnel::{TracingEvent, TracingEventSender};
+    /// static DROPPED_EVENTS: AtomicU32 = AtomicU32::new(0);
+    ///
+    /// let (events_sx, events_rx) = mpsc::sync_channel(10);
+    /// let subscriber = TracingEventSender::new(move |event| {
+    ///     if events_sx.try_send(event).is_err() {
+    ///         DROPPED_EVENTS.fetch_add(1, Ordering::Relaxed);
+    ///     }
+    /// });
+    /// let subscriber = subscriber
+    ///     .with_heartbeat(NonZeroU32::new(100).unwrap())
+    ///     .with_dropped_events(&DROPPED_EVENTS);
+    /// ```
+    #[must_use]
+    pub fn with_dropped_events(mut self, counter: &'static AtomicU32) -> Self {
+        self.dropped_events = Some(counter);
+        self
+    }
+
+    /// Sets the generation of this sender included into [`TracingEvent::NewSpan`]s.
+    /// The generation should be changed (e.g., incremented) each time the sender is restarted
+    /// while the receiver is retained, so that the receiver can detect stale span references.
+    /// By default, the generation is not set.
+    #[must_use]
+    pub fn with_generation(mut self, generation: u32) -> Self {
+        self.generation = Some(generation);
+        self
+    }
+
+    /// Sets size limits for recorded values. See [the type-level docs](#value-limits) for details.
+    #[must_use]
+    pub fn with_value_limits(mut self, limits: ValueLimits) -> Self {
+        self.value_limits = limits;
+        self
+    }
+
+    /// Sets the [wire format version](TracingEvent::WIRE_FORMAT_VERSION) supported
+    /// by the receiving side, e.g., communicated by the host when establishing the tunnel.
+    /// The sender only emits events supported by this version. By default, the receiving side
+    /// is assumed to support only version 1.
+    ///
+    /// Starting from version 2, events with the contextual parent and a single `message` value
+    /// (e.g., `tracing::info!("started")`) are emitted as compact [`TracingEvent::SimpleEvent`]s
+    /// instead of [`TracingEvent::NewEvent`]s, which significantly reduces the serialized size
+    /// of such events.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::sync::mpsc;
+    /// # use assert_matches::assert_matches;
+    /// # use tracing_tunnel::{TracingEvent, TracingEventSender};
+    /// let (events_sx, events_rx) = mpsc::channel();
+    /// let sender = TracingEventSender::new(move |event| {
+    ///     events_sx.send(event).ok();
+    /// });
+    /// // The version supported by the receiver, e.g., obtained during a handshake.
+    /// let receiver_version = TracingEvent::WIRE_FORMAT_VERSION;
+    /// let sender = sender.with_receiver_version(receiver_version);
+    /// tracing::subscriber::with_default(sender, || {
+    ///     tracing::info!("hello");
+    /// });
+    ///
+    /// let events: Vec<_> = events_rx.iter().collect();
+    /// assert_matches!(
+    ///     events.last(),
+    ///     Some(TracingEvent::SimpleEvent { message, .. }) if message == "hello"
+    /// );
+    /// ```
+    #[must_use]
+    pub fn with_receiver_version(mut self, version: u32) -> Self {
+        self.receiver_version = version;
+        self
+    }
+
+    /// Enables emitting a [`TracingEvent::Shutdown`] marker when this sender is dropped
+    /// (e.g., when the [`Dispatch`] wrapping the sender is dropped).
+    ///
+    /// [`Dispatch`]: tracing_core::Dispatch
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::sync::mpsc;
+    /// # use assert_matches::assert_matches;
+    /// # use tracing_tunnel::{TracingEvent, TracingEventSender};
+    /// let (events_sx, events_rx) = mpsc::channel();
+    /// let sender = TracingEventSender::new(move |event| {
+    ///     events_sx.send(event).ok();
+    /// });
+    /// let sender = sender.with_shutdown_on_drop();
+    /// tracing::subscriber::with_default(sender, || {
+    ///     tracing::info!("hello");
+    /// });
+    ///
+    /// let events: Vec<_> = events_rx.iter().collect();
+    /// assert_matches!(events.last(), Some(TracingEvent::Shutdown { sequence: 2 }));
+    /// ```
+    #[must_use]
+    pub fn with_shutdown_on_drop(mut self) -> Self {
+        self.shutdown_hook = Some(|on_event, event| on_event(event));
+        self
+    }
+
+    /// Emits a [`TracingEvent::Flush`] marker signalling that all preceding events
+    /// were emitted. This can be used to mark the end of a logical unit of work
+    /// (e.g., a workflow step) in the event stream.
+    pub fn flush(&self) {
+        let sequence = self.sequence.load(Ordering::SeqCst);
+        self.send_meta(TracingEvent::Flush { sequence });
+    }
+
+    /// Emits a [`TracingEvent::Heartbeat`] immediately. This can be used to emit heartbeats
+    /// periodically (e.g., on a timer) regardless of the number of emitted events.
+    pub fn send_heartbeat(&self) {
+        let sequence = self.sequence.load(Ordering::SeqCst);
+        self.send_meta(self.heartbeat(sequence));
+    }
+
+    fn heartbeat(&self, sequence: u32) -> TracingEvent {
+        let dropped_events = self
+            .dropped_events
+            .map_or(0, |counter| counter.load(Ordering::Relaxed));
+        TracingEvent::Heartbeat {
+            sequence,
+            dropped_events,
+        }
+    }
+
+    /// Sends a meta event, which does not affect the sequence number.
+    fn send_meta(&self, event: TracingEvent) {
+        let Some(hook_scope) = self.reentrancy.enter() else {
+            self.on_reentrant_event(event);
+            return;
+        };
+        (self.on_event)(event);
+        drop(hook_scope);
+        self.send_pending_events();
+    }
+
+    /// Returns the number of events emitted from inside the "on event" hook, which were dropped
+    /// to prevent infinite recursion. See [the type-level docs](#reentrancy) for details.
+    pub fn suppressed_events(&self) -> u32 {
+        self.suppressed_events.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of values truncated because of the [value limits](Self::with_value_limits()).
+    pub fn truncated_values(&self) -> u32 {
+        self.truncated_values.load(Ordering::Relaxed)
+    }
+
+    fn apply_value_limits<S: AsRef<str>>(&self, values: &mut TracedValues<S>) {
+        let truncated_count = self.value_limits.apply(values);
+        if truncated_count > 0 {
+            self.truncated_values
+                .fetch_add(truncated_count, Ordering::Relaxed);
+        }
+    }
+
+    fn metadata_id(metadata: &'static Metadata<'static>) -> MetadataId {
+        metadata as *const _ as MetadataId
+    }
+
+    /// Returns `false` if the event was suppressed because it was emitted from inside the hook.
+    fn send(&self, event: TracingEvent) -> bool {
+        let Some(hook_scope) = self.reentrancy.enter() else {
+            return self.on_reentrant_event(event);
+        };
+
+        (self.on_event)(event);
+        let sequence = self.sequence.fetch_add(1, Ordering::SeqCst).wrapping_add(1);
+        if let Some(interval) = self.heartbeat_interval {
+            if sequence % interval == 0 {
+                // The hook scope is still active, so the heartbeat is sent directly.
+                (self.on_event)(self.heartbeat(sequence));
+            }
+        }
+
+        drop(hook_scope);
+        self.send_pending_events();
+        true
+    }
+
+    /// Sends an event related to a span, unless the span was created from inside the hook.
+    fn send_for_span(&self, is_from_hook: bool, event: TracingEvent) {
+        if is_from_hook {
+            self.suppressed_events.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.send(event);
+        }
+    }
+
+    fn send_pending_events(&self) {
+        if self.has_pending_events.swap(false, Ordering::SeqCst) {
+            let events = mem::take(&mut *self.pending_events.lock());
+            for event in events {
+                self.send(event);
+            }
+        }
+    }
+
+    fn on_reentrant_event(&self, event: TracingEvent) -> bool {
+        let paired_event = match &event {
+            // Call sites are only registered once, and lifecycle events relate to spans
+            // known to the receiver, so neither can be dropped.
+            TracingEvent::NewCallSite { .. }
+            | TracingEvent::FollowsFrom { .. }
+            | TracingEvent::SpanEntered { .. }
+            | TracingEvent::SpanCloned { .. }
+            | TracingEvent::ValuesRecorded { .. } => None,
+            TracingEvent::SpanExited { id } => Some(TracingEvent::SpanEntered { id: *id }),
+            TracingEvent::SpanDropped { id } => Some(TracingEvent::SpanCloned { id: *id }),
+            _ => {
+                self.suppressed_events.fetch_add(1, Ordering::Relaxed);
+                return false;
+            }
+        };
+
+        // Entries / exits and clones / drops performed inside the hook (e.g., by `Span::in_scope()`
+        // or `Span::current()`) cancel each other out; otherwise, sending them would invoke
+        // the hook again, ad infinitum.
+        let mut pending_events = self.pending_events.lock();
+        if let Some(paired_event) = paired_event {
+            let paired_pos = pending_events
+                .iter()
+                .rposition(|pending| *pending == paired_event);
+            if let Some(pos) = paired_pos {
+                pending_events.remove(pos);
+                return true;
+            }
+        }
+        pending_events.push(event);
+        self.has_pending_events.store(true, Ordering::SeqCst);
+        true
+    }
+}
+
+/// Constructors for sequenced senders.
+impl TracingEventSender {
+    /// Creates a subscriber wrapping each emitted event into a [`SequencedEvent`] before passing
+    /// it to the `on_event` hook. Sequence numbers start from 0 and are incremented
+    /// for each event, including meta events (heartbeats, flush and shutdown markers);
+    /// they wrap on overflow.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::sync::mpsc;
+    /// # use tracing_tunnel::{SequencedEvent, TracingEventSender};
+    /// let (events_sx, events_rx) = mpsc::channel();
+    /// let sender = TracingEventSender::sequenced(move |event: SequencedEvent| {
+    ///     events_sx.send(event).ok();
+    /// });
+    /// tracing::subscriber::with_default(sender, || {
+    ///     tracing::info!("hello");
+    /// });
+    ///
+    /// let sequences: Vec<_> = events_rx.iter().map(|event| event.sequence).collect();
+    /// assert_eq!(sequences, [0, 1]); // call site + event
+    /// ```
+    pub fn sequenced(
+        on_event: impl Fn(SequencedEvent) + Send + Sync + 'static,
+    ) -> TracingEventSender<impl Fn(TracingEvent) + Send + Sync> {
+        let next_sequence = AtomicU32::new(0);
+        TracingEventSender::new(move |event| {
+            let sequence = next_sequence.fetch_add(1, Ordering::SeqCst);
+            on_event(SequencedEvent { sequence, event });
+        })
+    }
+}
+
+impl<F> Drop for TracingEventSender<F> {
+    fn drop(&mut self) {
+        if let Some(shutdown_hook) = self.shutdown_hook {
+            let sequence = *self.sequence.get_mut();
+            if let Some(_hook_scope) = self.reentrancy.enter() {
+                shutdown_hook(&self.on_event, TracingEvent::Shutdown { sequence });
+            }
+        }
+    }
+}
+
+impl<F: Fn(TracingEvent) + 'static> Subscriber for TracingEventSender<F> {
+    fn register_callsite(&self, metadata: &'static Metadata<'static>) -> Interest {
+        let id = Self::metadata_id(metadata);
+        self.send(TracingEvent::NewCallSite {
+            id,
+            data: CallSiteData::from(metadata),
+        });
+        Interest::always()
+    }
+
+    fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, span: &Attributes<'_>) -> Id {
+        let metadata_id = Self::metadata_id(span.metadata());
+        let span_id = u64::from(self.next_span_id.fetch_add(1, Ordering::SeqCst));
+        let mut event = TracingEvent::new_span(span, metadata_id, span_id, self.generation);
+        if let TracingEvent::NewSpan { values, .. } = &mut event {
+            self.apply_value_limits(values);
+        }
+        // The span ID is not exposed until this method returns, so registering the span
+        // after sending is fine.
+        let is_sent = self.send(event);
+        self.span_refs.on_new_span(span_id, !is_sent);
+        Id::from_u64(span_id)
+    }
+
+    fn record(&self, span: &Id, values: &Record<'_>) {
+        let id = span.into_u64();
+        let mut event = TracingEvent::values_recorded(id, values);
+        if let TracingEvent::ValuesRecorded { values, .. } = &mut event {
+            self.apply_value_limits(values);
+        }
+        self.send_for_span(self.span_refs.is_from_hook(id), event);
+    }
+
+    fn record_follows_from(&self, span: &Id, follows: &Id) {
+        let (id, follows_from) = (span.into_u64(), follows.into_u64());
+        let is_from_hook =
+            self.span_refs.is_from_hook(id) || self.span_refs.is_from_hook(follows_from);
+        self.send_for_span(is_from_hook, TracingEvent::FollowsFrom { id, follows_from });
+    }
+
+    fn event(&self, event: &Event<'_>) {
+        let metadata_id = Self::metadata_id(event.metadata());
+        let mut event = TracingEvent::new_event(event, metadata_id);
+        if let TracingEvent::NewEvent { values, .. } = &mut event {
+            self.apply_value_limits(values);
+        }
+        if self.receiver_version >= Self::SIMPLE_EVENTS_VERSION {
+            event = event.into_simple();
+        }
+        self.send(event);
+    }
+
+    fn enter(&self, span: &Id) {
+        let id = span.into_u64();
+        let is_from_hook = self.span_refs.is_from_hook(id);
+        self.send_for_span(is_from_hook, TracingEvent::SpanEntered { id });
+    }
+
+    fn exit(&self, span: &Id) {
+        let id = span.into_u64();
+        let is_from_hook = self.span_refs.is_from_hook(id);
+        self.send_for_span(is_from_hook, TracingEvent::SpanExited { id });
+    }
+
+    fn clone_span(&self, span: &Id) -> Id {
+        let id = span.into_u64();
+        let is_from_hook = self.span_refs.on_span_cloned(id);
+        self.send_for_span(is_from_hook, TracingEvent::SpanCloned { id });
+        span.clone()
+    }
+
+    fn try_close(&self, span: Id) -> bool {
+        let id = span.into_u64();
+        let span_ref = self.span_refs.on_span_dropped(id);
+        let is_from_hook = span_ref.is_some_and(|span_ref| span_ref.is_from_hook);
+        self.send_for_span(is_from_hook, TracingEvent::SpanDropped { id });
+        span_ref.is_some_and(|span_ref| span_ref.count == 0)
+    }
+}
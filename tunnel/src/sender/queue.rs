@@ -0,0 +1,277 @@
+//! Bounded event queue for `TracingEventSender`.
+
+use std::{
+    collections::VecDeque,
+    num::NonZeroUsize,
+    sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError},
+    thread,
+};
+
+use super::TracingEventSender;
+use crate::TracingEvent;
+
+/// Policy applied by an [`EventQueue`] when an event is pushed into a full queue.
+///
+/// Only data-bearing events ([`NewEvent`], [`SimpleEvent`] and [`ValuesRecorded`]) can be dropped.
+/// Other events (e.g., new call sites, spans and span lifecycle events) are necessary
+/// for the receiver to keep its state consistent, so they are never dropped; with
+/// the [`DropNewest`](Self::DropNewest) or [`DropOldest`](Self::DropOldest) policies,
+/// they are queued even if this makes the queue exceed its capacity.
+///
+/// [`NewEvent`]: TracingEvent::NewEvent
+/// [`SimpleEvent`]: TracingEvent::SimpleEvent
+/// [`ValuesRecorded`]: TracingEvent::ValuesRecorded
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DropPolicy {
+    /// Drop the pushed event if it is droppable.
+    #[default]
+    DropNewest,
+    /// Drop the oldest queued droppable event to make room for the pushed event.
+    /// If there are no such events, the pushed event is dropped if it is droppable.
+    DropOldest,
+    /// Block the emitting thread until the queue has room for the pushed event.
+    ///
+    /// This policy must only be used if the queue is flushed from another thread
+    /// (e.g., using [`EventQueue::spawn_flusher()`]); otherwise, pushing an event
+    /// into a full queue will block forever.
+    Block,
+}
+
+#[derive(Debug, Default)]
+struct QueueState {
+    events: VecDeque<TracingEvent>,
+    /// Number of events dropped since the last flush.
+    pending_dropped: u32,
+    /// Total number of dropped events.
+    total_dropped: u64,
+    is_closed: bool,
+}
+
+#[derive(Debug)]
+struct QueueInner {
+    state: Mutex<QueueState>,
+    not_empty: Condvar,
+    not_full: Condvar,
+    capacity: usize,
+    policy: DropPolicy,
+}
+
+impl QueueInner {
+    fn lock(&self) -> MutexGuard<'_, QueueState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Bounded queue decoupling emitting tracing events by a [`TracingEventSender`]
+/// from delivering them.
+///
+/// The queue is filled by a sender created with [`TracingEventSender::queued()`]. Queued events
+/// can be delivered either on a background thread (see [`Self::spawn_flusher()`]),
+/// or explicitly using [`Self::poll_flush()`], e.g., in single-threaded WASM environments.
+/// If the queue is full, the pushed event is handled according to the [`DropPolicy`].
+///
+/// Dropped events are reported to the flushed events consumer using
+/// [`TracingEvent::EventsDropped`] meta events. Such an event is inserted before
+/// the events delivered by a flush if any events were dropped since the previous flush.
+///
+/// The queue is cheaply cloneable; all clones refer to the same queue.
+///
+/// # Examples
+///
+/// ```
+/// # use std::num::NonZeroUsize;
+/// # use tracing_tunnel::{DropPolicy, EventQueue, TracingEvent, TracingEventSender};
+/// let capacity = NonZeroUsize::new(1_024).unwrap();
+/// let queue = EventQueue::new(capacity, DropPolicy::DropOldest);
+/// let subscriber = TracingEventSender::queued(&queue);
+/// tracing::subscriber::with_default(subscriber, || {
+///     tracing::info!(answer = 42, "computed");
+/// });
+///
+/// // Events are delivered only when the queue is flushed.
+/// let mut events = vec![];
+/// queue.poll_flush(|event| events.push(event));
+/// assert!(events
+///     .iter()
+///     .any(|event| matches!(event, TracingEvent::NewEvent { .. })));
+/// ```
+#[derive(Debug, Clone)]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+pub struct EventQueue {
+    inner: Arc<QueueInner>,
+}
+
+impl EventQueue {
+    /// Creates a queue with the specified capacity and drop policy.
+    pub fn new(capacity: NonZeroUsize, policy: DropPolicy) -> Self {
+        Self {
+            inner: Arc::new(QueueInner {
+                state: Mutex::new(QueueState::default()),
+                not_empty: Condvar::new(),
+                not_full: Condvar::new(),
+                capacity: capacity.get(),
+                policy,
+            }),
+        }
+    }
+
+    /// Checks whether the event may be dropped without breaking the receiver state.
+    fn is_droppable(event: &TracingEvent) -> bool {
+        matches!(
+            event,
+            TracingEvent::NewEvent { .. }
+                | TracingEvent::SimpleEvent { .. }
+                | TracingEvent::ValuesRecorded { .. }
+        )
+    }
+
+    /// Pushes an event into the queue, applying the [`DropPolicy`] if the queue is full.
+    /// Events pushed into a [closed](Self::close()) queue are dropped.
+    pub fn push(&self, event: TracingEvent) {
+        let mut state = self.inner.lock();
+        while state.events.len() >= self.inner.capacity {
+            match self.inner.policy {
+                DropPolicy::DropNewest => {
+                    if Self::is_droppable(&event) {
+                        Self::on_dropped(&mut state);
+                        return;
+                    }
+                    break;
+                }
+                DropPolicy::DropOldest => {
+                    let oldest_droppable = state.events.iter().position(Self::is_droppable);
+                    if let Some(idx) = oldest_droppable {
+                        state.events.remove(idx);
+                        Self::on_dropped(&mut state);
+                    } else if Self::is_droppable(&event) {
+                        Self::on_dropped(&mut state);
+                        return;
+                    } else {
+                        break;
+                    }
+                }
+                DropPolicy::Block => {
+                    if state.is_closed {
+                        break;
+                    }
+                    state = self
+                        .inner
+                        .not_full
+                        .wait(state)
+                        .unwrap_or_else(PoisonError::into_inner);
+                }
+            }
+        }
+
+        if state.is_closed {
+            Self::on_dropped(&mut state);
+        } else {
+            state.events.push_back(event);
+            self.inner.not_empty.notify_one();
+        }
+    }
+
+    fn on_dropped(state: &mut QueueState) {
+        state.pending_dropped = state.pending_dropped.saturating_add(1);
+        state.total_dropped += 1;
+    }
+
+    /// Returns the number of currently queued events.
+    pub fn len(&self) -> usize {
+        self.inner.lock().events.len()
+    }
+
+    /// Checks whether the queue is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the total number of events dropped by this queue.
+    pub fn dropped_events(&self) -> u64 {
+        self.inner.lock().total_dropped
+    }
+
+    fn take_events(state: &mut QueueState) -> impl Iterator<Item = TracingEvent> {
+        let dropped_event = if state.pending_dropped > 0 {
+            let count = state.pending_dropped;
+            state.pending_dropped = 0;
+            Some(TracingEvent::EventsDropped { count })
+        } else {
+            None
+        };
+        let events = state.events.drain(..).collect::<Vec<_>>();
+        dropped_event.into_iter().chain(events)
+    }
+
+    /// Delivers all queued events to `sink` on the current thread, preceded by
+    /// a [`TracingEvent::EventsDropped`] event if any events were dropped since
+    /// the previous flush. Returns the number of delivered events.
+    ///
+    /// The queue is not locked while events are delivered, so `sink` may emit tracing events
+    /// itself (they will be queued until the next flush).
+    pub fn poll_flush(&self, mut sink: impl FnMut(TracingEvent)) -> usize {
+        let events = {
+            let mut state = self.inner.lock();
+            let events = Self::take_events(&mut state);
+            self.inner.not_full.notify_all();
+            events
+        };
+        let mut count = 0;
+        for event in events {
+            sink(event);
+            count += 1;
+        }
+        count
+    }
+
+    /// Spawns a background thread delivering queued events to `sink` as soon as they
+    /// are available. The thread terminates after the queue is [closed](Self::close())
+    /// and all queued events are delivered.
+    pub fn spawn_flusher(
+        &self,
+        mut sink: impl FnMut(TracingEvent) + Send + 'static,
+    ) -> thread::JoinHandle<()> {
+        let inner = Arc::clone(&self.inner);
+        thread::spawn(move || loop {
+            let (events, is_closed) = {
+                let mut state = inner.lock();
+                while state.events.is_empty() && state.pending_dropped == 0 && !state.is_closed {
+                    state = inner
+                        .not_empty
+                        .wait(state)
+                        .unwrap_or_else(PoisonError::into_inner);
+                }
+                let events = Self::take_events(&mut state);
+                inner.not_full.notify_all();
+                (events, state.is_closed)
+            };
+            events.for_each(&mut sink);
+            if is_closed {
+                break;
+            }
+        })
+    }
+
+    /// Closes the queue. Events pushed into a closed queue are dropped, and senders blocked
+    /// because of [`DropPolicy::Block`] are unblocked. Already queued events can still
+    /// be flushed.
+    pub fn close(&self) {
+        let mut state = self.inner.lock();
+        state.is_closed = true;
+        self.inner.not_empty.notify_all();
+        self.inner.not_full.notify_all();
+    }
+}
+
+/// Constructors for queued senders.
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+impl TracingEventSender {
+    /// Creates a subscriber pushing tracing events into the specified bounded `queue`
+    /// instead of delivering them synchronously on the emitting thread.
+    /// See [`EventQueue`] docs for details.
+    pub fn queued(queue: &EventQueue) -> TracingEventSender<impl Fn(TracingEvent) + Send + Sync> {
+        let queue = queue.clone();
+        TracingEventSender::new(move |event| queue.push(event))
+    }
+}
@@ -0,0 +1,247 @@
+//! Routing of tracing events to different hooks based on the event target.
+
+use core::fmt;
+
+use super::{sync::Mutex, TracingEventSender};
+use crate::{
+    alloc::{BTreeMap, Box, String, Vec},
+    MetadataId, RawSpanId, TracingEvent,
+};
+
+type Hook = Box<dyn Fn(TracingEvent) + Send + Sync>;
+
+#[derive(Debug, Clone, Copy)]
+struct SpanRoute {
+    route: usize,
+    ref_count: usize,
+}
+
+/// Routes of [`TracingEvent`]s to different hooks based on the tracing target
+/// of the corresponding call site. Used in [`TracingEventSender::routed()`].
+///
+/// This allows multiplexing different kinds of structured output through a single subscriber;
+/// e.g., events with the `metrics` target can be sent to a metrics channel, and all other
+/// events to the trace channel.
+///
+/// # Routing rules
+///
+/// - A route [added](Self::with_route()) with a target prefix matches call sites with
+///   the target equal to the prefix or nested in it (e.g., the `metrics` prefix matches
+///   `metrics` and `metrics::http` targets, but not `metrics_exporter`). Routes are checked
+///   in the order they were added; if no route matches, the default hook is used.
+/// - [`NewCallSite`](TracingEvent::NewCallSite) events are routed based on their target,
+///   and spans and events are routed together with their call sites. Span-related events
+///   (e.g., [`SpanEntered`](TracingEvent::SpanEntered)) are routed together with the span.
+/// - Since each hook only receives a part of spans, spans and events with an explicit parent
+///   from another route are sent as explicit roots (i.e., with `is_root` set),
+///   and [`FollowsFrom`](TracingEvent::FollowsFrom) relations between spans from different
+///   routes are dropped.
+/// - Meta events (heartbeats, flush and shutdown markers, and reports on dropped events)
+///   are sent to all hooks. Note that [heartbeat](TracingEvent::Heartbeat) sequence numbers
+///   count events across all routes.
+///
+/// # Examples
+///
+/// ```
+/// # use std::sync::mpsc;
+/// # use tracing_tunnel::{TargetRoutes, TracingEvent, TracingEventSender};
+/// let (traces_sx, traces_rx) = mpsc::channel();
+/// let (metrics_sx, metrics_rx) = mpsc::channel();
+/// let routes = TargetRoutes::new(move |event| {
+///     traces_sx.send(event).ok();
+/// });
+/// let routes = routes.with_route("metrics", move |event| {
+///     metrics_sx.send(event).ok();
+/// });
+/// tracing::subscriber::with_default(TracingEventSender::routed(routes), || {
+///     tracing::info_span!("compute").in_scope(|| {
+///         tracing::info!(target: "metrics::compute", latency_ms = 15);
+///         tracing::info!("computed");
+///     });
+/// });
+///
+/// let metrics: Vec<_> = metrics_rx.iter().collect();
+/// // Call site + event; the parent span is not sent to the metrics hook.
+/// assert_eq!(metrics.len(), 2);
+/// let traces: Vec<_> = traces_rx.iter().collect();
+/// assert!(traces
+///     .iter()
+///     .any(|event| matches!(event, TracingEvent::NewSpan { .. })));
+/// ```
+pub struct TargetRoutes {
+    routes: Vec<(String, Hook)>,
+    default_hook: Hook,
+    call_sites: Mutex<BTreeMap<MetadataId, usize>>,
+    spans: Mutex<BTreeMap<RawSpanId, SpanRoute>>,
+}
+
+impl fmt::Debug for TargetRoutes {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let prefixes: Vec<_> = self.routes.iter().map(|(prefix, _)| prefix).collect();
+        formatter
+            .debug_struct("TargetRoutes")
+            .field("prefixes", &prefixes)
+            .field("call_sites", &self.call_sites)
+            .field("spans", &self.spans)
+            .finish_non_exhaustive()
+    }
+}
+
+impl TargetRoutes {
+    /// Creates routes with the specified default hook, which receives events not matching
+    /// any other route.
+    pub fn new(default_hook: impl Fn(TracingEvent) + Send + Sync + 'static) -> Self {
+        Self {
+            routes: Vec::new(),
+            default_hook: Box::new(default_hook),
+            call_sites: Mutex::default(),
+            spans: Mutex::default(),
+        }
+    }
+
+    /// Adds a route for the specified target prefix. See [the type-level docs](Self#routing-rules)
+    /// for details on matching.
+    #[must_use]
+    pub fn with_route(
+        mut self,
+        target_prefix: impl Into<String>,
+        hook: impl Fn(TracingEvent) + Send + Sync + 'static,
+    ) -> Self {
+        self.routes.push((target_prefix.into(), Box::new(hook)));
+        self
+    }
+
+    fn matches_target(prefix: &str, target: &str) -> bool {
+        target
+            .strip_prefix(prefix)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+    }
+
+    /// Returns the index of the route for `target`; the default route has the last index.
+    fn route_for_target(&self, target: &str) -> usize {
+        self.routes
+            .iter()
+            .position(|(prefix, _)| Self::matches_target(prefix, target))
+            .unwrap_or_else(|| self.default_route())
+    }
+
+    fn default_route(&self) -> usize {
+        self.routes.len()
+    }
+
+    fn call_site_route(&self, id: MetadataId) -> usize {
+        let call_sites = self.call_sites.lock();
+        call_sites
+            .get(&id)
+            .copied()
+            .unwrap_or_else(|| self.default_route())
+    }
+
+    fn span_route(&self, id: RawSpanId) -> Option<usize> {
+        self.spans.lock().get(&id).map(|span| span.route)
+    }
+
+    fn hook(&self, route: usize) -> &Hook {
+        self.routes
+            .get(route)
+            .map_or(&self.default_hook, |(_, hook)| hook)
+    }
+
+    fn send(&self, event: TracingEvent) {
+        let route = match &event {
+            TracingEvent::NewCallSite { id, data } => {
+                let route = self.route_for_target(&data.target);
+                self.call_sites.lock().insert(*id, route);
+                route
+            }
+            TracingEvent::NewSpan {
+                id, metadata_id, ..
+            } => {
+                let route = self.call_site_route(*metadata_id);
+                let span = SpanRoute {
+                    route,
+                    ref_count: 1,
+                };
+                self.spans.lock().insert(*id, span);
+                route
+            }
+            TracingEvent::NewEvent { metadata_id, .. }
+            | TracingEvent::SimpleEvent { metadata_id, .. } => self.call_site_route(*metadata_id),
+
+            TracingEvent::SpanEntered { id }
+            | TracingEvent::SpanExited { id }
+            | TracingEvent::ValuesRecorded { id, .. } => {
+                self.span_route(*id).unwrap_or_else(|| self.default_route())
+            }
+            TracingEvent::SpanCloned { id } => {
+                let mut spans = self.spans.lock();
+                spans.get_mut(id).map_or(self.default_route(), |span| {
+                    span.ref_count += 1;
+                    span.route
+                })
+            }
+            TracingEvent::SpanDropped { id } => {
+                let mut spans = self.spans.lock();
+                match spans.get_mut(id) {
+                    Some(span) if span.ref_count > 1 => {
+                        span.ref_count -= 1;
+                        span.route
+                    }
+                    Some(_) => spans.remove(id).unwrap().route,
+                    None => self.default_route(),
+                }
+            }
+            TracingEvent::FollowsFrom { id, follows_from } => {
+                let route = self.span_route(*id);
+                if route != self.span_route(*follows_from) {
+                    return; // the relation cannot be represented in a single route
+                }
+                route.unwrap_or_else(|| self.default_route())
+            }
+
+            TracingEvent::Heartbeat { .. }
+            | TracingEvent::EventsDropped { .. }
+            | TracingEvent::Flush { .. }
+            | TracingEvent::Shutdown { .. } => {
+                for (_, hook) in &self.routes {
+                    hook(event.clone());
+                }
+                (self.default_hook)(event);
+                return;
+            }
+        };
+        self.send_to_route(route, event);
+    }
+
+    fn send_to_route(&self, route: usize, mut event: TracingEvent) {
+        let (parent, is_root) = match &mut event {
+            TracingEvent::NewSpan {
+                parent_id, is_root, ..
+            } => (parent_id, is_root),
+            TracingEvent::NewEvent {
+                parent, is_root, ..
+            } => (parent, is_root),
+            _ => {
+                self.hook(route)(event);
+                return;
+            }
+        };
+        if let Some(parent_id) = *parent {
+            if self.span_route(parent_id) != Some(route) {
+                // `None` alone would mean the contextual parent on the receiver side.
+                *parent = None;
+                *is_root = true;
+            }
+        }
+        self.hook(route)(event);
+    }
+}
+
+/// Constructors for routed senders.
+impl TracingEventSender {
+    /// Creates a subscriber routing emitted events to different hooks based on
+    /// the event target. See [`TargetRoutes`] for details.
+    pub fn routed(routes: TargetRoutes) -> TracingEventSender<impl Fn(TracingEvent) + Send + Sync> {
+        TracingEventSender::new(move |event| routes.send(event))
+    }
+}
@@ -0,0 +1,235 @@
+//! Portable synchronization primitives used by the sender.
+//!
+//! - [`Mutex`]: with the `std` feature, this is a thin wrapper around `std::sync::Mutex`;
+//!   otherwise, it is a simple spin lock based on `core` atomics.
+//! - [`ReentrancyGuard`]: tracks whether the hook of a specific sender is being executed.
+//!   With the `std` feature, this is tracked per thread; otherwise, the guard tracks whether
+//!   *any* thread is inside the hook.
+
+#[cfg(feature = "std")]
+mod imp {
+    use std::sync::{self, PoisonError};
+
+    pub(crate) use std::sync::MutexGuard;
+
+    #[derive(Debug, Default)]
+    pub(crate) struct Mutex<T>(sync::Mutex<T>);
+
+    impl<T> Mutex<T> {
+        /// Locks the mutex. Unlike with `std::sync::Mutex`, poisoning is ignored.
+        pub(crate) fn lock(&self) -> MutexGuard<'_, T> {
+            self.0.lock().unwrap_or_else(PoisonError::into_inner)
+        }
+    }
+}
+
+#[cfg(not(feature = "std"))]
+mod imp {
+    use core::{
+        cell::UnsafeCell,
+        fmt, hint, ops,
+        sync::atomic::{AtomicBool, Ordering},
+    };
+
+    #[derive(Debug, Default)]
+    pub(crate) struct Mutex<T> {
+        is_locked: AtomicBool,
+        value: UnsafeCell<T>,
+    }
+
+    // SAFETY: access to `value` is synchronized by `is_locked`, similar to `std::sync::Mutex`.
+    unsafe impl<T: Send> Sync for Mutex<T> {}
+
+    impl<T> Mutex<T> {
+        pub(crate) fn lock(&self) -> MutexGuard<'_, T> {
+            while self
+                .is_locked
+                .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+                .is_err()
+            {
+                while self.is_locked.load(Ordering::Relaxed) {
+                    hint::spin_loop();
+                }
+            }
+            MutexGuard { mutex: self }
+        }
+    }
+
+    pub(crate) struct MutexGuard<'a, T> {
+        mutex: &'a Mutex<T>,
+    }
+
+    impl<T: fmt::Debug> fmt::Debug for MutexGuard<'_, T> {
+        fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+            fmt::Debug::fmt(&**self, formatter)
+        }
+    }
+
+    impl<T> ops::Deref for MutexGuard<'_, T> {
+        type Target = T;
+
+        fn deref(&self) -> &T {
+            // SAFETY: the guard has exclusive access to the value while the lock is held.
+            unsafe { &*self.mutex.value.get() }
+        }
+    }
+
+    impl<T> ops::DerefMut for MutexGuard<'_, T> {
+        fn deref_mut(&mut self) -> &mut T {
+            // SAFETY: the guard has exclusive access to the value while the lock is held.
+            unsafe { &mut *self.mutex.value.get() }
+        }
+    }
+
+    impl<T> Drop for MutexGuard<'_, T> {
+        fn drop(&mut self) {
+            self.mutex.is_locked.store(false, Ordering::Release);
+        }
+    }
+}
+
+pub(crate) use self::imp::{Mutex, MutexGuard};
+
+#[cfg(feature = "std")]
+mod reentrancy {
+    use std::{
+        cell::RefCell,
+        sync::atomic::{AtomicU64, Ordering},
+        vec::Vec,
+    };
+
+    static NEXT_GUARD_ID: AtomicU64 = AtomicU64::new(0);
+
+    std::thread_local! {
+        /// IDs of guards entered on the current thread.
+        static ENTERED_GUARDS: RefCell<Vec<u64>> = const { RefCell::new(Vec::new()) };
+    }
+
+    /// Reentrancy guard identified by a unique ID, so that guards of different senders
+    /// are independent.
+    #[derive(Debug)]
+    pub(crate) struct ReentrancyGuard(u64);
+
+    impl Default for ReentrancyGuard {
+        fn default() -> Self {
+            Self(NEXT_GUARD_ID.fetch_add(1, Ordering::Relaxed))
+        }
+    }
+
+    impl ReentrancyGuard {
+        /// Returns `None` if the current thread has already entered this guard.
+        pub(crate) fn enter(&self) -> Option<HookScope> {
+            let was_entered = ENTERED_GUARDS.with(|guards| {
+                let mut guards = guards.borrow_mut();
+                let was_entered = guards.contains(&self.0);
+                if !was_entered {
+                    guards.push(self.0);
+                }
+                was_entered
+            });
+            // `then_some()` would create and immediately drop the scope, resetting the flag.
+            if was_entered {
+                None
+            } else {
+                Some(HookScope(self.0))
+            }
+        }
+    }
+
+    #[derive(Debug)]
+    pub(crate) struct HookScope(u64);
+
+    impl Drop for HookScope {
+        fn drop(&mut self) {
+            ENTERED_GUARDS.with(|guards| guards.borrow_mut().retain(|&id| id != self.0));
+        }
+    }
+}
+
+#[cfg(not(feature = "std"))]
+mod reentrancy {
+    use core::sync::atomic::{AtomicBool, Ordering};
+
+    /// Reentrancy guard shared by all threads, so entering it on one thread blocks
+    /// entering it on other threads until the scope is dropped.
+    #[derive(Debug, Default)]
+    pub(crate) struct ReentrancyGuard(AtomicBool);
+
+    impl ReentrancyGuard {
+        /// Returns `None` if the hook is already being executed.
+        pub(crate) fn enter(&self) -> Option<HookScope<'_>> {
+            let was_in_hook = self.0.swap(true, Ordering::Acquire);
+            if was_in_hook {
+                None
+            } else {
+                Some(HookScope(&self.0))
+            }
+        }
+    }
+
+    #[derive(Debug)]
+    pub(crate) struct HookScope<'a>(&'a AtomicBool);
+
+    impl Drop for HookScope<'_> {
+        fn drop(&mut self) {
+            self.0.store(false, Ordering::Release);
+        }
+    }
+}
+
+pub(crate) use self::reentrancy::ReentrancyGuard;
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn mutex_synchronizes_access() {
+        const THREADS: usize = 4;
+        const INCREMENTS: usize = 1_000;
+
+        let counter = Mutex::<usize>::default();
+        thread::scope(|scope| {
+            for _ in 0..THREADS {
+                scope.spawn(|| {
+                    for _ in 0..INCREMENTS {
+                        *counter.lock() += 1;
+                    }
+                });
+            }
+        });
+        assert_eq!(*counter.lock(), THREADS * INCREMENTS);
+    }
+
+    #[test]
+    fn reentrancy_guard() {
+        let guard = ReentrancyGuard::default();
+        let scope = guard.enter().unwrap();
+        assert!(guard.enter().is_none());
+        drop(scope);
+        let _scope = guard.enter().unwrap();
+
+        let other_guard = ReentrancyGuard::default();
+        let _other_scope = other_guard.enter().unwrap();
+    }
+
+    #[test]
+    fn reentrancy_guards_are_independent() {
+        let guard = ReentrancyGuard::default();
+        let other_guard = ReentrancyGuard::default();
+        let scope = guard.enter().unwrap();
+        let other_scope = other_guard.enter().unwrap();
+        assert!(guard.enter().is_none());
+        assert!(other_guard.enter().is_none());
+
+        drop(scope);
+        assert!(other_guard.enter().is_none());
+        let _scope = guard.enter().unwrap();
+        drop(other_scope);
+        let _other_scope = other_guard.enter().unwrap();
+    }
+}
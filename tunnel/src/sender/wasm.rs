@@ -0,0 +1,154 @@
+//! `TracingEventSender` constructors for browser environments.
+
+use js_sys::{Function, Reflect};
+use wasm_bindgen::{prelude::wasm_bindgen, JsCast, JsValue};
+
+use std::cell::RefCell;
+
+use super::TracingEventSender;
+use crate::TracingEvent;
+
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_namespace = console, js_name = error)]
+    fn console_error(message: &str);
+}
+
+thread_local! {
+    // JS values are not `Send` / `Sync`, so we cannot store them in the sender directly.
+    // Instead, hooks are stored in a thread-local registry and are referenced by the index.
+    static HOOKS: RefCell<Vec<JsHook>> = const { RefCell::new(Vec::new()) };
+}
+
+/// JS function together with the value of `this` it should be called with.
+#[derive(Debug, Clone)]
+struct JsHook {
+    function: Function,
+    this: JsValue,
+}
+
+impl JsHook {
+    fn register(self) -> usize {
+        HOOKS.with(|hooks| {
+            let mut hooks = hooks.borrow_mut();
+            hooks.push(self);
+            hooks.len() - 1
+        })
+    }
+
+    fn into_sender(self) -> TracingEventSender<impl Fn(TracingEvent) + Send + Sync> {
+        let hook_idx = self.register();
+        TracingEventSender::new(move |event| send_to_hook(hook_idx, &event))
+    }
+}
+
+/// Sends an event to the JS hook. This function never panics; all errors are reported
+/// to the browser console, and the corresponding event is dropped.
+fn send_to_hook(hook_idx: usize, event: &TracingEvent) {
+    let payload = match serde_json::to_string(event) {
+        Ok(payload) => payload,
+        Err(err) => {
+            console_error(&format!("Failed serializing tracing event: {err}"));
+            return;
+        }
+    };
+
+    // `try_with` / `try_borrow` guard against the thread-local being destroyed and against
+    // the hook being registered from within itself, respectively.
+    let hook = HOOKS
+        .try_with(|hooks| {
+            let hooks = hooks.try_borrow().ok()?;
+            hooks.get(hook_idx).cloned()
+        })
+        .ok()
+        .flatten();
+    let Some(hook) = hook else {
+        // Can happen if the event was emitted from another thread.
+        console_error("Tracing event hook is not available in the current thread");
+        return;
+    };
+    let payload = JsValue::from_str(&payload);
+    if let Err(err) = hook.function.call1(&hook.this, &payload) {
+        console_error(&format!("Tracing event hook failed: {err:?}"));
+    }
+}
+
+/// Constructors for `wasm32-unknown-unknown` browser targets.
+#[cfg_attr(docsrs, doc(cfg(feature = "wasm-web")))]
+impl TracingEventSender {
+    /// Creates a subscriber invoking the specified JS function for each tracing event.
+    /// The function is called with a single argument: a JSON string with the serialized
+    /// [`TracingEvent`].
+    ///
+    /// The returned subscriber is `Send + Sync`, but the JS function can only be called
+    /// in the thread that has created the subscriber. Events emitted in other threads
+    /// are dropped.
+    ///
+    /// # Error handling
+    ///
+    /// The subscriber never panics. If an event cannot be serialized or the JS function
+    /// throws an exception, the error is reported to the browser console using `console.error`,
+    /// and the event is dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use js_sys::Function;
+    /// # use tracing_tunnel::TracingEventSender;
+    /// # use wasm_bindgen::prelude::*;
+    /// #[wasm_bindgen]
+    /// pub fn init_tracing(callback: Function) {
+    ///     let subscriber = TracingEventSender::js_callback(callback);
+    ///     tracing::subscriber::set_global_default(subscriber).ok();
+    /// }
+    /// ```
+    pub fn js_callback(
+        callback: Function,
+    ) -> TracingEventSender<impl Fn(TracingEvent) + Send + Sync> {
+        let hook = JsHook {
+            function: callback,
+            this: JsValue::NULL,
+        };
+        hook.into_sender()
+    }
+
+    /// Creates a subscriber posting each tracing event to the specified `target` using
+    /// its `postMessage()` method. The `target` may be a `Worker`, a `MessagePort`,
+    /// or the global scope of a dedicated worker (e.g., [`js_sys::global()`]
+    /// if the code runs in a worker). Each message is a JSON string with the serialized
+    /// [`TracingEvent`].
+    ///
+    /// The same considerations regarding threads and error handling as
+    /// for [`Self::js_callback()`] apply.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `target` does not have a `postMessage()` method.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use tracing_tunnel::TracingEventSender;
+    /// # use wasm_bindgen::prelude::*;
+    /// // Assumes that the code runs in a dedicated worker.
+    /// #[wasm_bindgen]
+    /// pub fn init_tracing() -> Result<(), JsValue> {
+    ///     let subscriber = TracingEventSender::post_message(&js_sys::global())?;
+    ///     tracing::subscriber::set_global_default(subscriber).ok();
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn post_message(
+        target: &JsValue,
+    ) -> Result<TracingEventSender<impl Fn(TracingEvent) + Send + Sync>, JsValue> {
+        let post_message = Reflect::get(target, &JsValue::from_str("postMessage"))?;
+        let post_message: Function = post_message
+            .dyn_into()
+            .map_err(|_| JsValue::from_str("`postMessage` is not a function"))?;
+        let hook = JsHook {
+            function: post_message,
+            this: target.clone(),
+        };
+        Ok(hook.into_sender())
+    }
+}
@@ -0,0 +1,618 @@
+//! [`proptest`] strategies generating valid sequences of [`TracingEvent`]s, e.g. to fuzz
+//! [`TracingEventReceiver`] or wrappers around it.
+//!
+//! The core type of this module is [`TraceFixture`], a sequence of events respecting
+//! the invariants upheld by [`TracingEventSender`]:
+//!
+//! - Call sites are defined before being referenced.
+//! - Spans are only referenced while they are alive, i.e., between their creation and the drop
+//!   of their last reference.
+//! - Spans are exited in the reverse order of entering, and an entered span is not dropped.
+//! - Span and event values correspond to fields of their call sites.
+//! - Heartbeat sequence numbers correspond to the number of preceding non-heartbeat events.
+//!
+//! Fixtures are generated from a sequence of abstract operations which are then interpreted
+//! into events; operations that would violate invariants are skipped. Thus, fixtures shrink well:
+//! removing an operation always produces a valid event sequence.
+//!
+//! [`TracingEventReceiver`]: crate::TracingEventReceiver
+//! [`TracingEventSender`]: crate::TracingEventSender
+//!
+//! # Examples
+//!
+//! ```
+//! use proptest::prelude::*;
+//! use tracing_tunnel::{strategies::{TraceConfig, TraceFixture}, TracingEvent};
+//!
+//! proptest! {
+//!     # /*
+//!     #[test]
+//!     # */
+//!     fn spans_are_balanced(fixture in any::<TraceFixture>()) {
+//!         // In real tests, events would be passed to a `TracingEventReceiver`
+//!         // or a wrapper around it.
+//!         let events = fixture.events();
+//!         let entered = events
+//!             .iter()
+//!             .filter(|event| matches!(event, TracingEvent::SpanEntered { .. }))
+//!             .count();
+//!         let exited = events
+//!             .iter()
+//!             .filter(|event| matches!(event, TracingEvent::SpanExited { .. }))
+//!             .count();
+//!         prop_assert_eq!(entered, exited);
+//!     }
+//! }
+//! # spans_are_balanced();
+//!
+//! // Generation can be configured:
+//! let mut config = TraceConfig::default();
+//! config.max_operations = 16;
+//! config.complete = false;
+//! let strategy = any_with::<TraceFixture>(config);
+//! ```
+
+use proptest::{
+    collection, option,
+    prelude::*,
+    sample::{select, Index},
+    strategy::BoxedStrategy,
+};
+
+use std::borrow::Cow;
+
+use crate::{
+    CallSiteData, CallSiteKind, MetadataId, RawSpanId, TracedError, TracedValue, TracedValues,
+    TracingEvent, TracingLevel,
+};
+
+/// Configuration of [`TraceFixture`] generation. Used as [`Arbitrary`] parameters
+/// for `TraceFixture`.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct TraceConfig {
+    /// Maximum number of call sites for spans and, separately, for events. Default value is 4.
+    pub max_call_sites: usize,
+    /// Maximum number of fields in a call site. Default value is 4.
+    pub max_fields: usize,
+    /// Maximum number of abstract operations (such as creating or entering a span) interpreted
+    /// into events. Default value is 64.
+    pub max_operations: usize,
+    /// Whether to exit all entered spans and drop all alive spans at the end of the trace.
+    /// Default value is `true`.
+    pub complete: bool,
+}
+
+impl Default for TraceConfig {
+    fn default() -> Self {
+        Self {
+            max_call_sites: 4,
+            max_fields: 4,
+            max_operations: 64,
+            complete: true,
+        }
+    }
+}
+
+/// Valid sequence of [`TracingEvent`]s. See [module-level docs](self) for the upheld invariants.
+#[derive(Debug, Clone)]
+pub struct TraceFixture {
+    events: Vec<TracingEvent>,
+}
+
+impl TraceFixture {
+    /// Returns events in this fixture.
+    pub fn events(&self) -> &[TracingEvent] {
+        &self.events
+    }
+
+    /// Converts this fixture into events.
+    pub fn into_events(self) -> Vec<TracingEvent> {
+        self.events
+    }
+}
+
+impl Arbitrary for TraceFixture {
+    type Parameters = TraceConfig;
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with(config: Self::Parameters) -> Self::Strategy {
+        trace_fixture(&config).boxed()
+    }
+}
+
+/// Creates a strategy generating [`TraceFixture`]s with the specified configuration.
+pub fn trace_fixture(config: &TraceConfig) -> impl Strategy<Value = TraceFixture> {
+    let max_call_sites = config.max_call_sites.max(1);
+    let span_call_sites = collection::vec(
+        call_site_data(CallSiteKind::Span, config.max_fields),
+        1..=max_call_sites,
+    );
+    let event_call_sites = collection::vec(
+        call_site_data(CallSiteKind::Event, config.max_fields),
+        1..=max_call_sites,
+    );
+    let operations = collection::vec(operation(config.max_fields), 0..=config.max_operations);
+    let complete = config.complete;
+
+    (span_call_sites, event_call_sites, operations).prop_map(
+        move |(span_call_sites, event_call_sites, operations)| {
+            let mut builder = TraceBuilder::new(span_call_sites, event_call_sites);
+            for operation in operations {
+                builder.apply(operation);
+            }
+            if complete {
+                builder.complete();
+            }
+            TraceFixture {
+                events: builder.events,
+            }
+        },
+    )
+}
+
+/// Creates a strategy generating [`CallSiteData`] of the specified kind with up to `max_fields`
+/// fields. Event call sites always contain the `message` field (not counted towards
+/// `max_fields`).
+pub fn call_site_data(
+    kind: CallSiteKind,
+    max_fields: usize,
+) -> impl Strategy<Value = CallSiteData> {
+    let levels = select(
+        &[
+            TracingLevel::Error,
+            TracingLevel::Warn,
+            TracingLevel::Info,
+            TracingLevel::Debug,
+            TracingLevel::Trace,
+        ][..],
+    );
+    (
+        "[a-z_]{1,10}",
+        "[a-z]{1,6}(::[a-z]{1,6}){0,2}",
+        levels,
+        option::of("[a-z]{1,6}(::[a-z]{1,6}){0,2}"),
+        option::of(("[a-z]{1,6}/[a-z]{1,6}\\.rs", 1_u32..10_000)),
+        collection::btree_set("[a-z]{1,6}", 0..=max_fields),
+    )
+        .prop_map(
+            move |(name, target, level, module_path, location, fields)| {
+                let mut fields: Vec<_> = fields
+                    .into_iter()
+                    .filter(|field| field != "message")
+                    .map(Cow::Owned)
+                    .collect();
+                if matches!(kind, CallSiteKind::Event) {
+                    fields.insert(0, Cow::Borrowed("message"));
+                }
+                let (file, line) = location.unzip();
+                CallSiteData {
+                    kind,
+                    name: Cow::Owned(name),
+                    target: Cow::Owned(target),
+                    level,
+                    module_path: module_path.map(Cow::Owned),
+                    file: file.map(Cow::Owned),
+                    line,
+                    fields,
+                }
+            },
+        )
+}
+
+/// Creates a strategy generating [`TracedValue`]s of all supported kinds. Floating-point values
+/// are always finite.
+pub fn traced_value() -> impl Strategy<Value = TracedValue> {
+    let error = (".{0,16}", collection::vec(".{0,16}", 0..=2)).prop_map(|(message, sources)| {
+        let source = sources.into_iter().rev().fold(None, |source, message| {
+            Some(Box::new(TracedError { message, source }))
+        });
+        TracedValue::Error(TracedError { message, source })
+    });
+
+    prop_oneof![
+        any::<bool>().prop_map(TracedValue::Bool),
+        any::<i64>().prop_map(|value| TracedValue::Int(value.into())),
+        any::<u64>().prop_map(|value| TracedValue::UInt(value.into())),
+        (prop::num::f64::NORMAL | prop::num::f64::ZERO).prop_map(TracedValue::Float),
+        ".{0,16}".prop_map(TracedValue::String),
+        ".{0,16}".prop_map(|s| TracedValue::debug(&s)),
+        collection::vec(any::<u8>(), 0..16).prop_map(TracedValue::Bytes),
+        error,
+    ]
+}
+
+/// Abstract operation interpreted into zero or more [`TracingEvent`]s.
+#[derive(Debug, Clone)]
+enum Operation {
+    NewSpan {
+        call_site: Index,
+        parent: Option<Index>,
+        values: Vec<(Index, TracedValue)>,
+    },
+    FollowsFrom {
+        span: Index,
+        follows_from: Index,
+    },
+    Enter(Index),
+    Exit,
+    Clone(Index),
+    Drop(Index),
+    Record {
+        span: Index,
+        values: Vec<(Index, TracedValue)>,
+    },
+    NewEvent {
+        call_site: Index,
+        parent: Option<Index>,
+        values: Vec<(Index, TracedValue)>,
+    },
+    Heartbeat,
+}
+
+fn operation(max_fields: usize) -> impl Strategy<Value = Operation> {
+    let values = move || collection::vec((any::<Index>(), traced_value()), 0..=max_fields);
+    let new_span = (any::<Index>(), option::of(any::<Index>()), values()).prop_map(
+        |(call_site, parent, values)| Operation::NewSpan {
+            call_site,
+            parent,
+            values,
+        },
+    );
+    let new_event = (any::<Index>(), option::of(any::<Index>()), values()).prop_map(
+        |(call_site, parent, values)| Operation::NewEvent {
+            call_site,
+            parent,
+            values,
+        },
+    );
+    let follows_from = any::<(Index, Index)>()
+        .prop_map(|(span, follows_from)| Operation::FollowsFrom { span, follows_from });
+    let record =
+        (any::<Index>(), values()).prop_map(|(span, values)| Operation::Record { span, values });
+
+    prop_oneof![
+        4 => new_span,
+        4 => new_event,
+        4 => any::<Index>().prop_map(Operation::Enter),
+        3 => Just(Operation::Exit),
+        2 => any::<Index>().prop_map(Operation::Clone),
+        3 => any::<Index>().prop_map(Operation::Drop),
+        2 => record,
+        1 => follows_from,
+        1 => Just(Operation::Heartbeat),
+    ]
+}
+
+#[derive(Debug)]
+struct AliveSpan {
+    id: RawSpanId,
+    call_site_idx: usize,
+    ref_count: usize,
+}
+
+/// Interpreter of [`Operation`]s.
+#[derive(Debug)]
+struct TraceBuilder {
+    /// Span call sites followed by event call sites.
+    call_sites: Vec<CallSiteData>,
+    span_call_site_count: usize,
+    metadata_ids: Vec<Option<MetadataId>>,
+    next_metadata_id: MetadataId,
+    next_span_id: RawSpanId,
+    alive_spans: Vec<AliveSpan>,
+    entered_span_ids: Vec<RawSpanId>,
+    sequence: u32,
+    events: Vec<TracingEvent>,
+}
+
+impl TraceBuilder {
+    fn new(span_call_sites: Vec<CallSiteData>, event_call_sites: Vec<CallSiteData>) -> Self {
+        let span_call_site_count = span_call_sites.len();
+        let mut call_sites = span_call_sites;
+        call_sites.extend(event_call_sites);
+        Self {
+            metadata_ids: vec![None; call_sites.len()],
+            call_sites,
+            span_call_site_count,
+            next_metadata_id: 0,
+            next_span_id: 1,
+            alive_spans: vec![],
+            entered_span_ids: vec![],
+            sequence: 0,
+            events: vec![],
+        }
+    }
+
+    fn push_event(&mut self, event: TracingEvent) {
+        if !matches!(event, TracingEvent::Heartbeat { .. }) {
+            self.sequence = self.sequence.wrapping_add(1);
+        }
+        self.events.push(event);
+    }
+
+    fn metadata_id(&mut self, call_site_idx: usize) -> MetadataId {
+        if let Some(id) = self.metadata_ids[call_site_idx] {
+            return id;
+        }
+        let id = self.next_metadata_id;
+        self.next_metadata_id += 1;
+        self.metadata_ids[call_site_idx] = Some(id);
+        self.push_event(TracingEvent::NewCallSite {
+            id,
+            data: self.call_sites[call_site_idx].clone(),
+        });
+        id
+    }
+
+    fn select_span(&self, idx: Index) -> Option<usize> {
+        if self.alive_spans.is_empty() {
+            None
+        } else {
+            Some(idx.index(self.alive_spans.len()))
+        }
+    }
+
+    fn select_parent(&self, parent: Option<Index>) -> Option<RawSpanId> {
+        let idx = self.select_span(parent?)?;
+        Some(self.alive_spans[idx].id)
+    }
+
+    fn values(
+        &self,
+        call_site_idx: usize,
+        values: Vec<(Index, TracedValue)>,
+    ) -> TracedValues<String> {
+        let fields = &self.call_sites[call_site_idx].fields;
+        let mut traced_values = TracedValues::new();
+        if !fields.is_empty() {
+            for (field_idx, value) in values {
+                let field = &fields[field_idx.index(fields.len())];
+                traced_values.insert(field.clone().into_owned(), value);
+            }
+        }
+        traced_values
+    }
+
+    fn apply(&mut self, operation: Operation) {
+        match operation {
+            Operation::NewSpan {
+                call_site,
+                parent,
+                values,
+            } => {
+                let call_site_idx = call_site.index(self.span_call_site_count);
+                let metadata_id = self.metadata_id(call_site_idx);
+                let parent_id = self.select_parent(parent);
+                let values = self.values(call_site_idx, values);
+                let id = self.next_span_id;
+                self.next_span_id += 1;
+                self.alive_spans.push(AliveSpan {
+                    id,
+                    call_site_idx,
+                    ref_count: 1,
+                });
+                self.push_event(TracingEvent::NewSpan {
+                    id,
+                    parent_id,
+                    is_root: false,
+                    metadata_id,
+                    values,
+                    generation: None,
+                });
+            }
+
+            Operation::FollowsFrom { span, follows_from } => {
+                if let (Some(span), Some(follows_from)) =
+                    (self.select_span(span), self.select_span(follows_from))
+                {
+                    let id = self.alive_spans[span].id;
+                    let follows_from = self.alive_spans[follows_from].id;
+                    self.push_event(TracingEvent::FollowsFrom { id, follows_from });
+                }
+            }
+
+            Operation::Enter(span) => {
+                let Some(span) = self.select_span(span) else {
+                    return;
+                };
+                let id = self.alive_spans[span].id;
+                if !self.entered_span_ids.contains(&id) {
+                    self.entered_span_ids.push(id);
+                    self.push_event(TracingEvent::SpanEntered { id });
+                }
+            }
+            Operation::Exit => {
+                if let Some(id) = self.entered_span_ids.pop() {
+                    self.push_event(TracingEvent::SpanExited { id });
+                }
+            }
+
+            Operation::Clone(span) => {
+                if let Some(span) = self.select_span(span) {
+                    let span = &mut self.alive_spans[span];
+                    span.ref_count += 1;
+                    let id = span.id;
+                    self.push_event(TracingEvent::SpanCloned { id });
+                }
+            }
+            Operation::Drop(span) => {
+                if let Some(span) = self.select_span(span) {
+                    self.drop_span(span);
+                }
+            }
+
+            Operation::Record { span, values } => {
+                if let Some(span) = self.select_span(span) {
+                    let AliveSpan {
+                        id, call_site_idx, ..
+                    } = self.alive_spans[span];
+                    let values = self.values(call_site_idx, values);
+                    self.push_event(TracingEvent::ValuesRecorded { id, values });
+                }
+            }
+
+            Operation::NewEvent {
+                call_site,
+                parent,
+                values,
+            } => {
+                let event_call_site_count = self.call_sites.len() - self.span_call_site_count;
+                let call_site_idx =
+                    self.span_call_site_count + call_site.index(event_call_site_count);
+                let metadata_id = self.metadata_id(call_site_idx);
+                let parent = self.select_parent(parent);
+                let values = self.values(call_site_idx, values);
+                self.push_event(TracingEvent::NewEvent {
+                    metadata_id,
+                    parent,
+                    is_root: false,
+                    values,
+                });
+            }
+
+            Operation::Heartbeat => {
+                self.push_event(TracingEvent::Heartbeat {
+                    sequence: self.sequence,
+                    dropped_events: 0,
+                });
+            }
+        }
+    }
+
+    fn drop_span(&mut self, idx: usize) {
+        let span = &mut self.alive_spans[idx];
+        let id = span.id;
+        if span.ref_count == 1 && self.entered_span_ids.contains(&id) {
+            return; // The entered span holds the last reference
+        }
+        span.ref_count -= 1;
+        if span.ref_count == 0 {
+            self.alive_spans.remove(idx);
+        }
+        self.push_event(TracingEvent::SpanDropped { id });
+    }
+
+    fn complete(&mut self) {
+        while let Some(id) = self.entered_span_ids.pop() {
+            self.push_event(TracingEvent::SpanExited { id });
+        }
+        while !self.alive_spans.is_empty() {
+            self.drop_span(self.alive_spans.len() - 1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::test_runner::TestRunner;
+
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn check_invariants(events: &[TracingEvent], is_complete: bool) {
+        let mut call_sites = HashMap::new();
+        let mut alive_spans = HashMap::new();
+        let mut entered_span_ids = vec![];
+        let mut sequence = 0_u32;
+
+        for event in events {
+            match event {
+                TracingEvent::NewCallSite { id, data } => {
+                    assert!(call_sites.insert(*id, data).is_none());
+                }
+                TracingEvent::NewSpan {
+                    id,
+                    parent_id,
+                    metadata_id,
+                    values,
+                    ..
+                } => {
+                    let call_site = call_sites[metadata_id];
+                    assert!(matches!(call_site.kind, CallSiteKind::Span));
+                    assert_values(call_site, values);
+                    assert!(parent_id.map_or(true, |id| alive_spans.contains_key(&id)));
+                    assert!(alive_spans.insert(*id, (*metadata_id, 1)).is_none());
+                }
+                TracingEvent::FollowsFrom { id, follows_from } => {
+                    assert!(alive_spans.contains_key(id));
+                    assert!(alive_spans.contains_key(follows_from));
+                }
+                TracingEvent::SpanEntered { id } => {
+                    assert!(alive_spans.contains_key(id));
+                    entered_span_ids.push(*id);
+                }
+                TracingEvent::SpanExited { id } => {
+                    assert_eq!(entered_span_ids.pop(), Some(*id));
+                }
+                TracingEvent::SpanCloned { id } => {
+                    alive_spans.get_mut(id).unwrap().1 += 1;
+                }
+                TracingEvent::SpanDropped { id } => {
+                    let ref_count = &mut alive_spans.get_mut(id).unwrap().1;
+                    *ref_count -= 1;
+                    if *ref_count == 0 {
+                        assert!(!entered_span_ids.contains(id));
+                        alive_spans.remove(id);
+                    }
+                }
+                TracingEvent::ValuesRecorded { id, values } => {
+                    let (metadata_id, _) = alive_spans[id];
+                    assert_values(call_sites[&metadata_id], values);
+                }
+                TracingEvent::NewEvent {
+                    metadata_id,
+                    parent,
+                    values,
+                    ..
+                } => {
+                    let call_site = call_sites[metadata_id];
+                    assert!(matches!(call_site.kind, CallSiteKind::Event));
+                    assert_values(call_site, values);
+                    assert!(parent.map_or(true, |id| alive_spans.contains_key(&id)));
+                }
+                TracingEvent::SimpleEvent { metadata_id, .. } => {
+                    let call_site = call_sites[metadata_id];
+                    assert!(matches!(call_site.kind, CallSiteKind::Event));
+                }
+                TracingEvent::Heartbeat { sequence: seq, .. } => {
+                    assert_eq!(*seq, sequence);
+                    continue;
+                }
+                TracingEvent::EventsDropped { .. }
+                | TracingEvent::Flush { .. }
+                | TracingEvent::Shutdown { .. } => unreachable!("not generated"),
+            }
+            sequence += 1;
+        }
+
+        if is_complete {
+            assert!(alive_spans.is_empty());
+            assert!(entered_span_ids.is_empty());
+        }
+    }
+
+    fn assert_values(call_site: &CallSiteData, values: &TracedValues<String>) {
+        for (name, _) in values {
+            assert!(call_site.fields.iter().any(|field| field == name));
+        }
+    }
+
+    #[test]
+    fn generated_traces_uphold_invariants() {
+        let mut runner = TestRunner::deterministic();
+        for complete in [false, true] {
+            let config = TraceConfig {
+                complete,
+                ..TraceConfig::default()
+            };
+            runner
+                .run(&trace_fixture(&config), |fixture| {
+                    check_invariants(fixture.events(), complete);
+                    Ok(())
+                })
+                .unwrap();
+        }
+    }
+}
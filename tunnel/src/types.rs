@@ -48,7 +48,7 @@ impl From<Level> for TracingLevel {
 }
 
 /// Kind of [`CallSiteData`] location: either a span, or an event.
-#[derive(Debug, Clone, Copy, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum CallSiteKind {
     /// Call site is a span.
@@ -60,7 +60,7 @@ pub enum CallSiteKind {
 /// Data for a single tracing call site: either a span definition, or an event definition.
 ///
 /// This corresponds to [`Metadata`] from the `tracing-core` library, but is (de)serializable.
-#[derive(Debug, Clone, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct CallSiteData {
     /// Kind of the call site.
     pub kind: CallSiteKind,
@@ -109,6 +109,80 @@ impl From<&Metadata<'static>> for CallSiteData {
     }
 }
 
+impl CallSiteData {
+    /// Computes a content-addressed identifier of this call site, i.e., a hash of all its data.
+    /// Unlike [`MetadataId`]s, which are assigned per process, the hash is stable across
+    /// recompilations of the sender (as long as the call site data is unchanged), platforms
+    /// and crate versions.
+    ///
+    /// The hash is a 64-bit FNV-1a digest; it is not cryptographically secure.
+    pub fn content_hash(&self) -> u64 {
+        let mut hasher = ContentHasher::default();
+        hasher.write(&[match self.kind {
+            CallSiteKind::Span => 0,
+            CallSiteKind::Event => 1,
+        }]);
+        hasher.write_str(&self.name);
+        hasher.write_str(&self.target);
+        hasher.write(&[self.level as u8]);
+        hasher.write_opt_str(self.module_path.as_deref());
+        hasher.write_opt_str(self.file.as_deref());
+        match self.line {
+            None => hasher.write(&[0]),
+            Some(line) => {
+                hasher.write(&[1]);
+                hasher.write(&line.to_le_bytes());
+            }
+        }
+        hasher.write(&(self.fields.len() as u64).to_le_bytes());
+        for field in &self.fields {
+            hasher.write_str(field);
+        }
+        hasher.0
+    }
+}
+
+/// 64-bit FNV-1a hasher with length-prefixed strings, used in [`CallSiteData::content_hash()`].
+/// Unlike `DefaultHasher`, its output is guaranteed to be stable.
+#[derive(Debug)]
+struct ContentHasher(u64);
+
+impl Default for ContentHasher {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl ContentHasher {
+    const PRIME: u64 = 0x0100_0000_01b3;
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ u64::from(byte)).wrapping_mul(Self::PRIME);
+        }
+    }
+
+    fn write_str(&mut self, s: &str) {
+        self.write(&(s.len() as u64).to_le_bytes());
+        self.write(s.as_bytes());
+    }
+
+    fn write_opt_str(&mut self, s: Option<&str>) {
+        match s {
+            None => self.write(&[0]),
+            Some(s) => {
+                self.write(&[1]);
+                self.write_str(s);
+            }
+        }
+    }
+}
+
+#[allow(clippy::trivially_copy_pass_by_ref)] // required by `serde`
+pub(crate) fn is_false(value: &bool) -> bool {
+    !*value
+}
+
 /// Event produced during tracing.
 ///
 /// These events are emitted by a [`TracingEventSender`] and then consumed
@@ -116,7 +190,7 @@ impl From<&Metadata<'static>> for CallSiteData {
 ///
 /// [`TracingEventSender`]: crate::TracingEventSender
 /// [`TracingEventReceiver`]: crate::TracingEventReceiver
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 #[non_exhaustive]
 pub enum TracingEvent {
@@ -133,13 +207,25 @@ pub enum TracingEvent {
     NewSpan {
         /// Unique ID of the span that will be used to refer to it in the following events.
         id: RawSpanId,
-        /// Parent span ID. `None` means using the contextual parent (i.e., the current span).
+        /// Parent span ID. `None` means using the contextual parent (i.e., the current span),
+        /// unless `is_root` is set.
         #[serde(default, skip_serializing_if = "Option::is_none")]
         parent_id: Option<RawSpanId>,
+        /// Whether the span is an explicit root, i.e., has no parent regardless of the context.
+        /// Only applicable if `parent_id` is `None`.
+        #[serde(default, skip_serializing_if = "is_false")]
+        is_root: bool,
         /// ID of the span metadata.
         metadata_id: MetadataId,
         /// Values associated with the span.
         values: TracedValues<String>,
+        /// Generation of the sender that created the span, e.g. incremented each time
+        /// the sender is restarted. Allows the receiver to detect stale span references.
+        /// See [`TracingEventSender::with_generation()`] for details.
+        ///
+        /// [`TracingEventSender::with_generation()`]: crate::TracingEventSender::with_generation()
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        generation: Option<u32>,
     },
     /// New "follows from" relation between spans.
     FollowsFrom {
@@ -180,15 +266,99 @@ pub enum TracingEvent {
     NewEvent {
         /// ID of the event metadata.
         metadata_id: MetadataId,
-        /// Parent span ID. `None` means using the contextual parent (i.e., the current span).
+        /// Parent span ID. `None` means using the contextual parent (i.e., the current span),
+        /// unless `is_root` is set.
         #[serde(default, skip_serializing_if = "Option::is_none")]
         parent: Option<RawSpanId>,
+        /// Whether the event is an explicit root, i.e., has no parent regardless of the context.
+        /// Only applicable if `parent` is `None`.
+        #[serde(default, skip_serializing_if = "is_false")]
+        is_root: bool,
         /// Values associated with the event.
         values: TracedValues<String>,
     },
+    /// New event with the contextual parent and a single `message` value. This is a compact
+    /// form of [`Self::NewEvent`] for the common case of events without additional fields.
+    ///
+    /// Introduced in the [wire format version](Self::WIRE_FORMAT_VERSION) 2. Emitted
+    /// by a [`TracingEventSender`] only if the receiving side
+    /// [supports](crate::TracingEventSender::with_receiver_version()) this version.
+    /// [`TracingEventReceiver`] handles this event in the same way as the equivalent
+    /// [`Self::NewEvent`].
+    ///
+    /// [`TracingEventSender`]: crate::TracingEventSender
+    /// [`TracingEventReceiver`]: crate::TracingEventReceiver
+    SimpleEvent {
+        /// ID of the event metadata.
+        metadata_id: MetadataId,
+        /// Event message.
+        message: String,
+    },
+
+    /// Liveness heartbeat. Emitted by a [`TracingEventSender`] if [heartbeats are enabled].
+    ///
+    /// [`TracingEventSender`]: crate::TracingEventSender
+    /// [heartbeats are enabled]: crate::TracingEventSender::with_heartbeat()
+    Heartbeat {
+        /// Number of (non-heartbeat) events emitted by the sender before this heartbeat,
+        /// wrapping on overflow.
+        sequence: u32,
+        /// Total number of events reported as dropped by the sender.
+        dropped_events: u32,
+    },
+
+    /// Report about events dropped by the sender, e.g., because of a full [`EventQueue`].
+    /// Like heartbeats, these meta events are not counted in heartbeat sequence numbers.
+    ///
+    /// [`EventQueue`]: crate::EventQueue
+    EventsDropped {
+        /// Number of events dropped since the previous report.
+        count: u32,
+    },
+
+    /// Flush marker emitted by a [`TracingEventSender`] on [explicit flush]. Signals
+    /// that all events preceding the marker were emitted. Like heartbeats, flush markers
+    /// are not counted in heartbeat sequence numbers.
+    ///
+    /// [`TracingEventSender`]: crate::TracingEventSender
+    /// [explicit flush]: crate::TracingEventSender::flush()
+    Flush {
+        /// Number of (non-meta) events emitted by the sender before the marker,
+        /// wrapping on overflow.
+        sequence: u32,
+    },
+
+    /// Shutdown marker emitted by a [`TracingEventSender`] when it is dropped if
+    /// [enabled](crate::TracingEventSender::with_shutdown_on_drop()). Signals that the event
+    /// stream is complete; the sender will not emit any more events.
+    ///
+    /// [`TracingEventSender`]: crate::TracingEventSender
+    Shutdown {
+        /// Number of (non-meta) events emitted by the sender before the marker,
+        /// wrapping on overflow.
+        sequence: u32,
+    },
 }
 
 impl TracingEvent {
+    /// Version of the wire format of events, i.e., their serialized presentation.
+    /// The version is incremented on breaking changes to the format (e.g., renaming fields)
+    /// and on additions that older receivers cannot handle (e.g., new event variants emitted
+    /// by senders), but not on backward-compatible additions (e.g., optional fields).
+    /// See [`Self::json_schema()`] for the format definition.
+    ///
+    /// The receiving side can communicate the version it supports to the sender, e.g.,
+    /// when establishing the tunnel. The sender only emits events supported by this version;
+    /// see [`TracingEventSender::with_receiver_version()`] for details.
+    ///
+    /// # Versions
+    ///
+    /// - **1.** The initial version.
+    /// - **2.** Adds [`Self::SimpleEvent`].
+    ///
+    /// [`TracingEventSender::with_receiver_version()`]: crate::TracingEventSender::with_receiver_version()
+    pub const WIRE_FORMAT_VERSION: u32 = 2;
+
     /// Normalizes a captured sequence of events so that it does not contain information that
     /// changes between program runs (e.g., metadata IDs) or due to minor refactoring
     /// (source code lines). Normalized events can be used for snapshot testing
@@ -218,7 +388,8 @@ impl TracingEvent {
                     }
                 }
                 TracingEvent::NewSpan { metadata_id, .. }
-                | TracingEvent::NewEvent { metadata_id, .. } => {
+                | TracingEvent::NewEvent { metadata_id, .. }
+                | TracingEvent::SimpleEvent { metadata_id, .. } => {
                     let new_metadata_id = metadata_id_mapping.len() as MetadataId;
                     *metadata_id = *metadata_id_mapping
                         .entry(*metadata_id)
@@ -229,3 +400,23 @@ impl TracingEvent {
         }
     }
 }
+
+/// [`TracingEvent`] together with its sequence number assigned by the sender.
+///
+/// Sequenced events are produced by a [`TracingEventSender`] created
+/// with [`TracingEventSender::sequenced()`]. Sequence numbers are assigned to all events
+/// (including meta events like heartbeats) starting from 0 and wrapping on overflow.
+/// They allow a [`TracingEventReceiver`] to detect gaps and reordering introduced
+/// by lossy transports; see [`TracingEventReceiver::try_receive_sequenced()`].
+///
+/// [`TracingEventSender`]: crate::TracingEventSender
+/// [`TracingEventSender::sequenced()`]: crate::TracingEventSender::sequenced()
+/// [`TracingEventReceiver`]: crate::TracingEventReceiver
+/// [`TracingEventReceiver::try_receive_sequenced()`]: crate::TracingEventReceiver::try_receive_sequenced()
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SequencedEvent {
+    /// Sequence number of the event.
+    pub sequence: u32,
+    /// Wrapped event.
+    pub event: TracingEvent,
+}
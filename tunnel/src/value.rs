@@ -2,9 +2,16 @@
 
 use serde::{Deserialize, Serialize};
 
-use core::{borrow::Borrow, fmt};
+use core::{
+    borrow::Borrow,
+    fmt,
+    num::{
+        NonZeroI128, NonZeroI16, NonZeroI32, NonZeroI64, NonZeroI8, NonZeroIsize, NonZeroU128,
+        NonZeroU16, NonZeroU32, NonZeroU64, NonZeroU8, NonZeroUsize,
+    },
+};
 
-use crate::alloc::{format, String, ToOwned};
+use crate::alloc::{format, String, ToOwned, Vec};
 
 #[cfg(feature = "std")]
 mod error {
@@ -13,7 +20,7 @@ mod error {
     use std::{error, fmt};
 
     /// (De)serializable presentation for an error recorded as a value in a tracing span or event.
-    #[derive(Debug, Clone, Serialize, Deserialize)]
+    #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
     #[non_exhaustive]
     #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
     pub struct TracedError {
@@ -52,9 +59,9 @@ pub use self::error::TracedError;
 
 /// Opaque wrapper for a [`Debug`](fmt::Debug)gable object recorded as a value
 /// in a tracing span or event.
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(transparent)]
-pub struct DebugObject(String);
+pub struct DebugObject(pub(crate) String);
 
 impl fmt::Debug for DebugObject {
     fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -70,7 +77,12 @@ impl AsRef<str> for DebugObject {
 }
 
 /// Value recorded in a tracing span or event.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+///
+/// Values can be compared with each other and with primitive values. Because of
+/// [floating-point values](Self::Float), values only implement [`PartialEq`], but not
+/// `Eq` or `Hash`; e.g., `Float(f64::NAN)` is not equal to itself. Values of different
+/// variants are never equal, even if they represent the same number (e.g., `Int(1)` and `UInt(1)`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 #[non_exhaustive]
 pub enum TracedValue {
@@ -86,6 +98,11 @@ pub enum TracedValue {
     String(String),
     /// Opaque object implementing the [`Debug`](fmt::Debug) trait.
     Object(DebugObject),
+    /// Byte slice (e.g., `payload = &buf[..]`) recorded via [`Visit::record_bytes()`].
+    /// Serialized as a base64 string (standard alphabet with padding).
+    ///
+    /// [`Visit::record_bytes()`]: tracing_core::field::Visit::record_bytes()
+    Bytes(#[serde(with = "crate::bytes")] Vec<u8>),
     /// Opaque error.
     #[cfg(feature = "std")]
     #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
@@ -93,7 +110,8 @@ pub enum TracedValue {
 }
 
 impl TracedValue {
-    #[doc(hidden)] // public for testing purposes
+    /// Creates an [object](Self::Object) from the [`Debug`](fmt::Debug) representation
+    /// of the `object`, e.g., in a custom [`Visit`](tracing_core::field::Visit) implementation.
     pub fn debug(object: &dyn fmt::Debug) -> Self {
         Self::Object(DebugObject(format!("{object:?}")))
     }
@@ -128,6 +146,12 @@ impl TracedValue {
         str::from_value(self)
     }
 
+    /// Returns value as a byte slice, or `None` if it's not [`Self::Bytes`].
+    #[inline]
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        <[u8]>::from_value(self)
+    }
+
     /// Checks whether this value is a [`DebugObject`] with the same [`Debug`](fmt::Debug)
     /// output as the provided `object`.
     pub fn is_debug(&self, object: &dyn fmt::Debug) -> bool {
@@ -146,12 +170,70 @@ impl TracedValue {
         }
     }
 
+    /// Creates an [error](Self::Error) from the `err` and its chain of sources, e.g.,
+    /// in a custom [`Visit`](tracing_core::field::Visit) implementation.
     #[cfg(feature = "std")]
-    pub(crate) fn error(err: &(dyn std::error::Error + 'static)) -> Self {
+    #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+    pub fn error(err: &(dyn std::error::Error + 'static)) -> Self {
         Self::Error(TracedError::new(err))
     }
 }
 
+#[cfg(feature = "json")]
+#[cfg_attr(docsrs, doc(cfg(feature = "json")))]
+impl TracedValue {
+    /// Converts this value to a JSON value.
+    ///
+    /// - Integers are converted to JSON numbers if they fit into `i64` / `u64`,
+    ///   and to strings otherwise.
+    /// - Non-finite floating-point values are converted to `null`.
+    /// - [`Debug`](fmt::Debug) objects are converted to their string representation.
+    /// - Bytes are converted to base64 strings, same as in the serialized presentation.
+    /// - Errors are converted to objects with the `message` and (optional) `source` fields.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use serde_json::json;
+    /// # use tracing_tunnel::TracedValue;
+    /// let value = TracedValue::from(42_i64);
+    /// assert_eq!(value.to_json(), json!(42));
+    /// let value = TracedValue::from(u128::MAX);
+    /// assert_eq!(value.to_json(), json!(u128::MAX.to_string()));
+    /// let value = TracedValue::debug(&Some(1));
+    /// assert_eq!(value.to_json(), json!("Some(1)"));
+    /// ```
+    pub fn to_json(&self) -> serde_json::Value {
+        use serde_json::Value as Json;
+
+        match self {
+            Self::Bool(value) => Json::Bool(*value),
+            Self::Int(value) => {
+                i64::try_from(*value).map_or_else(|_| Json::String(value.to_string()), Json::from)
+            }
+            Self::UInt(value) => {
+                u64::try_from(*value).map_or_else(|_| Json::String(value.to_string()), Json::from)
+            }
+            Self::Float(value) => {
+                serde_json::Number::from_f64(*value).map_or(Json::Null, Json::Number)
+            }
+            Self::String(value) => Json::String(value.clone()),
+            Self::Object(value) => Json::String(value.0.clone()),
+            Self::Bytes(bytes) => Json::String(crate::bytes::encode(bytes)),
+            Self::Error(err) => Self::error_to_json(err),
+        }
+    }
+
+    fn error_to_json(err: &TracedError) -> serde_json::Value {
+        let mut map = serde_json::Map::new();
+        map.insert("message".into(), err.message.clone().into());
+        if let Some(source) = &err.source {
+            map.insert("source".into(), Self::error_to_json(source));
+        }
+        map.into()
+    }
+}
+
 /// Fallible conversion from a [`TracedValue`] reference.
 pub trait FromTracedValue<'a> {
     /// Output of the conversion.
@@ -171,6 +253,29 @@ impl<'a> FromTracedValue<'a> for str {
     }
 }
 
+impl<'a> FromTracedValue<'a> for [u8] {
+    type Output = &'a [u8];
+
+    fn from_value(value: &'a TracedValue) -> Option<Self::Output> {
+        match value {
+            TracedValue::Bytes(bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+}
+
+impl From<Vec<u8>> for TracedValue {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self::Bytes(bytes)
+    }
+}
+
+impl From<&[u8]> for TracedValue {
+    fn from(bytes: &[u8]) -> Self {
+        Self::Bytes(bytes.to_vec())
+    }
+}
+
 macro_rules! impl_value_conversions {
     (TracedValue :: $variant:ident ($source:ty)) => {
         impl From<$source> for TracedValue {
@@ -239,15 +344,105 @@ macro_rules! impl_value_conversions {
             }
         }
     };
+
+    // `usize` / `isize` do not implement `From` conversion to 128-bit integers.
+    (TracedValue :: $variant:ident ($source:ty, cast as $field_ty:ty)) => {
+        impl From<$source> for TracedValue {
+            fn from(value: $source) -> Self {
+                Self::$variant(value as $field_ty)
+            }
+        }
+
+        impl PartialEq<$source> for TracedValue {
+            fn eq(&self, other: &$source) -> bool {
+                match self {
+                    Self::$variant(value) => *value == *other as $field_ty,
+                    _ => false,
+                }
+            }
+        }
+
+        impl PartialEq<TracedValue> for $source {
+            fn eq(&self, other: &TracedValue) -> bool {
+                other == self
+            }
+        }
+
+        impl FromTracedValue<'_> for $source {
+            type Output = Self;
+
+            fn from_value(value: &TracedValue) -> Option<Self::Output> {
+                match value {
+                    TracedValue::$variant(value) => (*value).try_into().ok(),
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+macro_rules! impl_non_zero_conversions {
+    ($($source:ty => $int:ty),+ $(,)?) => {
+        $(
+        impl From<$source> for TracedValue {
+            fn from(value: $source) -> Self {
+                value.get().into()
+            }
+        }
+
+        impl PartialEq<$source> for TracedValue {
+            fn eq(&self, other: &$source) -> bool {
+                *self == other.get()
+            }
+        }
+
+        impl PartialEq<TracedValue> for $source {
+            fn eq(&self, other: &TracedValue) -> bool {
+                other == self
+            }
+        }
+
+        impl FromTracedValue<'_> for $source {
+            type Output = Self;
+
+            fn from_value(value: &TracedValue) -> Option<Self::Output> {
+                <$int>::from_value(value).and_then(<$source>::new)
+            }
+        }
+        )+
+    };
 }
 
 impl_value_conversions!(TracedValue::Bool(bool));
 impl_value_conversions!(TracedValue::Int(i128));
+impl_value_conversions!(TracedValue::Int(i8 as i128));
+impl_value_conversions!(TracedValue::Int(i16 as i128));
+impl_value_conversions!(TracedValue::Int(i32 as i128));
 impl_value_conversions!(TracedValue::Int(i64 as i128));
+impl_value_conversions!(TracedValue::Int(isize, cast as i128));
 impl_value_conversions!(TracedValue::UInt(u128));
+impl_value_conversions!(TracedValue::UInt(u8 as u128));
+impl_value_conversions!(TracedValue::UInt(u16 as u128));
+impl_value_conversions!(TracedValue::UInt(u32 as u128));
 impl_value_conversions!(TracedValue::UInt(u64 as u128));
+impl_value_conversions!(TracedValue::UInt(usize, cast as u128));
 impl_value_conversions!(TracedValue::Float(f64));
 
+impl_non_zero_conversions!(
+    NonZeroI8 => i8,
+    NonZeroI16 => i16,
+    NonZeroI32 => i32,
+    NonZeroI64 => i64,
+    NonZeroI128 => i128,
+    NonZeroIsize => isize,
+    NonZeroU8 => u8,
+    NonZeroU16 => u16,
+    NonZeroU32 => u32,
+    NonZeroU64 => u64,
+    NonZeroU128 => u128,
+    NonZeroUsize => usize,
+);
+
 impl PartialEq<str> for TracedValue {
     fn eq(&self, other: &str) -> bool {
         match self {
@@ -23,6 +23,8 @@ use crate::{
 /// Functionally this collection is similar to a `HashMap<S, TracedValue>`,
 /// with the key difference being that the order of [iteration](Self::iter()) is the insertion order.
 /// If a value is updated, including via [`Extend`] etc., it preserves its old placement.
+///
+/// Like with maps, comparing values for equality does not take the order of values into account.
 #[derive(Clone)]
 pub struct TracedValues<S> {
     // Using `Vec` for entries is inefficient for random access, but seems acceptable given that
@@ -47,6 +49,15 @@ impl<S: AsRef<str>> fmt::Debug for TracedValues<S> {
     }
 }
 
+impl<S: AsRef<str>, T: AsRef<str>> PartialEq<TracedValues<T>> for TracedValues<S> {
+    fn eq(&self, other: &TracedValues<T>) -> bool {
+        self.len() == other.len()
+            && self
+                .iter()
+                .all(|(name, value)| other.get(name) == Some(value))
+    }
+}
+
 impl<S: From<&'static str> + AsRef<str>> TracedValues<S> {
     /// Creates traced values from the specified value set.
     pub fn from_values(values: &ValueSet<'_>) -> Self {
@@ -110,6 +121,11 @@ impl<S: AsRef<str>> TracedValues<S> {
         }
     }
 
+    #[cfg(feature = "sender")]
+    pub(crate) fn values_mut(&mut self) -> impl Iterator<Item = &mut TracedValue> + '_ {
+        self.inner.iter_mut().map(|(_, value)| value)
+    }
+
     /// Inserts a value with the specified name. If a value with the same name was present
     /// previously, it is overwritten. Returns the previous value with the specified name,
     /// if any.
@@ -185,7 +201,7 @@ impl<'a, S: AsRef<str>> Iterator for TracedValuesIter<'a, S> {
     }
 }
 
-impl<'a, S: AsRef<str>> DoubleEndedIterator for TracedValuesIter<'a, S> {
+impl<S: AsRef<str>> DoubleEndedIterator for TracedValuesIter<'_, S> {
     fn next_back(&mut self) -> Option<Self::Item> {
         self.inner
             .next_back()
@@ -193,7 +209,7 @@ impl<'a, S: AsRef<str>> DoubleEndedIterator for TracedValuesIter<'a, S> {
     }
 }
 
-impl<'a, S: AsRef<str>> ExactSizeIterator for TracedValuesIter<'a, S> {
+impl<S: AsRef<str>> ExactSizeIterator for TracedValuesIter<'_, S> {
     fn len(&self) -> usize {
         self.inner.len()
     }
@@ -295,6 +311,10 @@ impl<S: From<&'static str> + AsRef<str>> Visit for TracedValueVisitor<S> {
             .insert(field.name().into(), TracedValue::error(value));
     }
 
+    fn record_bytes(&mut self, field: &Field, value: &[u8]) {
+        self.values.insert(field.name().into(), value.into());
+    }
+
     fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
         self.values
             .insert(field.name().into(), TracedValue::debug(value));
@@ -2,20 +2,30 @@
 
 use assert_matches::assert_matches;
 use once_cell::sync::Lazy;
-use tracing_core::{Level, Subscriber};
+use tracing_core::{
+    callsite::DefaultCallsite,
+    field::{Field, FieldSet, Value},
+    metadata::Kind,
+    span::{Attributes, Id},
+    Level, Metadata, Subscriber,
+};
 use tracing_subscriber::{registry::LookupSpan, FmtSubscriber};
 
 use std::{
     borrow::Cow,
     collections::{HashMap, HashSet},
-    iter, thread,
+    error, fmt, iter, mem,
+    num::{NonZeroIsize, NonZeroU16, NonZeroU32, NonZeroUsize},
+    sync::{mpsc, Arc, Mutex, OnceLock, Weak},
+    thread,
 };
 
 mod fib;
 
 use tracing_tunnel::{
-    CallSiteKind, LocalSpans, PersistedMetadata, PersistedSpans, TracedValue, TracingEvent,
-    TracingEventReceiver, TracingLevel,
+    CallSiteKind, DispatchMode, DropPolicy, EventQueue, FromTracedValue, LocalSpans,
+    PersistedMetadata, PersistedSpans, TargetRoutes, TracedValue, TracedValues, TracingEvent,
+    TracingEventReceiver, TracingEventSender, TracingLevel, ValueLimits,
 };
 
 #[derive(Debug)]
@@ -45,26 +55,32 @@ fn resource_management_for_tracing_events() {
 }
 
 fn assert_span_management(events: &[TracingEvent]) {
-    let mut alive_spans = HashSet::new();
+    let mut alive_spans = HashMap::new();
     let mut open_spans = vec![];
     for event in events {
         match event {
             TracingEvent::NewSpan { id, .. } => {
-                assert!(alive_spans.insert(*id));
+                assert!(alive_spans.insert(*id, 1_usize).is_none());
+            }
+            TracingEvent::SpanCloned { id } => {
+                *alive_spans.get_mut(id).unwrap() += 1;
             }
-            TracingEvent::SpanCloned { .. } => unreachable!(),
             TracingEvent::SpanDropped { id } => {
-                assert!(!open_spans.contains(id));
-                assert!(alive_spans.remove(id));
+                let ref_count = alive_spans.get_mut(id).unwrap();
+                *ref_count -= 1;
+                if *ref_count == 0 {
+                    assert!(!open_spans.contains(id));
+                    alive_spans.remove(id);
+                }
             }
 
             TracingEvent::SpanEntered { id } => {
-                assert!(alive_spans.contains(id));
+                assert!(alive_spans.contains_key(id));
                 assert!(!open_spans.contains(id));
                 open_spans.push(*id);
             }
             TracingEvent::SpanExited { id } => {
-                assert!(alive_spans.contains(id));
+                assert!(alive_spans.contains_key(id));
                 let popped_span = open_spans.pop();
                 assert_eq!(popped_span, Some(*id));
             }
@@ -267,6 +283,255 @@ fn persisting_spans_with_reset_local_spans() {
     test_persisting_spans(true);
 }
 
+#[test]
+fn comparing_integer_values_of_various_types() {
+    let warn_event = EVENTS
+        .short
+        .iter()
+        .find_map(|event| match event {
+            TracingEvent::NewEvent { values, .. } if values.get("count").is_some() => Some(values),
+            _ => None,
+        })
+        .unwrap();
+    let count = &warn_event["count"];
+
+    assert_eq!(*count, 5_usize);
+    assert_eq!(*count, 5_u32);
+    assert_eq!(*count, 5_u8);
+    assert_eq!(*count, NonZeroUsize::new(5).unwrap());
+    assert_ne!(*count, 5_i32); // signedness is not coerced
+    assert_eq!(count.as_uint(), Some(5));
+    assert_eq!(usize::from_value(count), Some(5));
+    assert_eq!(NonZeroU16::from_value(count), NonZeroU16::new(5));
+
+    let small_value = TracedValue::from(300_u16);
+    assert_eq!(u8::from_value(&small_value), None);
+    assert_eq!(u16::from_value(&small_value), Some(300));
+    let zero = TracedValue::from(0_isize);
+    assert_eq!(zero, 0_i8);
+    assert_eq!(NonZeroIsize::from_value(&zero), None);
+}
+
+#[test]
+fn heartbeats() {
+    Lazy::force(&EVENTS);
+    let (events_sx, events_rx) = mpsc::channel();
+    let sender = TracingEventSender::new(move |event| {
+        events_sx.send(event).unwrap();
+    });
+    let sender = sender.with_heartbeat(NonZeroU32::new(5).unwrap());
+    tracing::subscriber::with_default(sender, || fib::fib(5));
+    let events: Vec<_> = events_rx.iter().collect();
+
+    let heartbeats: Vec<_> = events
+        .iter()
+        .filter_map(|event| match event {
+            TracingEvent::Heartbeat { sequence, .. } => Some(*sequence),
+            _ => None,
+        })
+        .collect();
+    let event_count = events.len() - heartbeats.len();
+    assert_eq!(heartbeats.len(), event_count / 5);
+    for (i, &sequence) in heartbeats.iter().enumerate() {
+        assert_eq!(sequence as usize, (i + 1) * 5);
+    }
+
+    // Drop some events and check that the receiver detects the gap.
+    // The position of the dropped event relative to heartbeats depends on the number
+    // of call sites registered in the process, so we just drop the first entered span.
+    let mut receiver = TracingEventReceiver::default();
+    let mut is_dropped = false;
+    for event in events {
+        if !is_dropped && matches!(event, TracingEvent::SpanEntered { .. }) {
+            is_dropped = true;
+            continue;
+        }
+        receiver.try_receive(event).ok();
+    }
+    let heartbeat = receiver.last_heartbeat().unwrap();
+    assert_eq!(heartbeat.sequence as usize, heartbeats.len() * 5);
+    assert!(heartbeat.missing_events > 0);
+    assert_eq!(heartbeat.dropped_events, 0);
+}
+
+fn values_recorded(id: u64) -> TracingEvent {
+    TracingEvent::ValuesRecorded {
+        id,
+        values: TracedValues::new(),
+    }
+}
+
+fn test_queue_drop_policy(policy: DropPolicy, expected_ids: &[u64]) {
+    let queue = EventQueue::new(NonZeroUsize::new(4).unwrap(), policy);
+    for id in 1..=10 {
+        queue.push(values_recorded(id));
+    }
+    assert_eq!(queue.len(), 4);
+    assert_eq!(queue.dropped_events(), 6);
+
+    let mut events = vec![];
+    assert_eq!(queue.poll_flush(|event| events.push(event)), 5);
+    assert!(queue.is_empty());
+    assert_eq!(events[0], TracingEvent::EventsDropped { count: 6 });
+    let ids: Vec<_> = events[1..]
+        .iter()
+        .map(|event| match event {
+            TracingEvent::ValuesRecorded { id, .. } => *id,
+            _ => panic!("unexpected event: {event:?}"),
+        })
+        .collect();
+    assert_eq!(ids, expected_ids);
+
+    // Dropped events are reported only once.
+    queue.push(values_recorded(11));
+    events.clear();
+    queue.poll_flush(|event| events.push(event));
+    assert_eq!(events, [values_recorded(11)]);
+    assert_eq!(queue.dropped_events(), 6);
+
+    // Structural events are never dropped, even if the queue is full.
+    for id in 1..=6 {
+        queue.push(TracingEvent::SpanEntered { id });
+    }
+    queue.push(values_recorded(12));
+    assert_eq!(queue.len(), 6);
+    assert_eq!(queue.dropped_events(), 7);
+}
+
+fn test_queue_overflow_with_receiver(policy: DropPolicy) {
+    Lazy::force(&EVENTS);
+    let queue = EventQueue::new(NonZeroUsize::new(4).unwrap(), policy);
+    let sender = TracingEventSender::queued(&queue);
+    tracing::subscriber::with_default(sender, || fib::fib(10));
+    assert!(queue.dropped_events() > 0);
+
+    let mut events = vec![];
+    queue.poll_flush(|event| events.push(event));
+    assert_span_management(&events);
+    let mut receiver = TracingEventReceiver::default();
+    for event in events {
+        receiver.try_receive(event).unwrap();
+    }
+    assert_eq!(receiver.counters().errors, 0);
+}
+
+#[test]
+fn queue_overflow_does_not_break_receiver() {
+    test_queue_overflow_with_receiver(DropPolicy::DropNewest);
+    test_queue_overflow_with_receiver(DropPolicy::DropOldest);
+}
+
+#[test]
+fn queue_with_drop_newest_policy() {
+    test_queue_drop_policy(DropPolicy::DropNewest, &[1, 2, 3, 4]);
+}
+
+#[test]
+fn queue_with_drop_oldest_policy() {
+    test_queue_drop_policy(DropPolicy::DropOldest, &[7, 8, 9, 10]);
+}
+
+#[test]
+fn queue_with_blocking_policy() {
+    Lazy::force(&EVENTS);
+    let queue = EventQueue::new(NonZeroUsize::new(2).unwrap(), DropPolicy::Block);
+    let (events_sx, events_rx) = mpsc::channel();
+    let flusher = queue.spawn_flusher(move |event| events_sx.send(event).unwrap());
+
+    let sender = TracingEventSender::queued(&queue);
+    tracing::subscriber::with_default(sender, || fib::fib(5));
+    queue.close();
+    flusher.join().unwrap();
+
+    assert_eq!(queue.dropped_events(), 0);
+    let events: Vec<_> = events_rx.iter().collect();
+    assert_span_management(&events);
+    assert!(!events
+        .iter()
+        .any(|event| matches!(event, TracingEvent::EventsDropped { .. })));
+
+    // Events pushed into a closed queue are dropped.
+    queue.push(TracingEvent::SpanEntered { id: 1 });
+    assert!(queue.is_empty());
+    assert_eq!(queue.dropped_events(), 1);
+}
+
+#[test]
+fn receiving_dropped_events_reports() {
+    let mut receiver = TracingEventReceiver::default();
+    receiver.receive(TracingEvent::EventsDropped { count: 3 });
+    receiver.receive(TracingEvent::EventsDropped { count: 2 });
+
+    let counters = receiver.counters();
+    assert_eq!(counters.events_dropped, 2);
+    assert_eq!(counters.reported_dropped_events, 5);
+    assert_eq!(counters.errors, 0);
+    // Reports do not affect the heartbeat sequence.
+    receiver.receive(TracingEvent::Heartbeat {
+        sequence: 0,
+        dropped_events: 0,
+    });
+    receiver.receive(TracingEvent::EventsDropped { count: 1 });
+    assert_eq!(receiver.last_heartbeat().unwrap().events_since, 0);
+}
+
+#[test]
+fn flush_and_shutdown_markers() {
+    Lazy::force(&EVENTS);
+
+    let events = Arc::new(Mutex::new(vec![]));
+    let events_in_hook = Arc::clone(&events);
+    let sender = TracingEventSender::new(move |event| {
+        events_in_hook.lock().unwrap().push(event);
+    });
+    let sender = Arc::new(sender.with_shutdown_on_drop());
+    tracing::subscriber::with_default(Arc::clone(&sender), || fib::fib(3));
+    sender.flush();
+    tracing::subscriber::with_default(Arc::clone(&sender), || fib::fib(2));
+    drop(sender);
+
+    let events = mem::take(&mut *events.lock().unwrap());
+    let sequence = events
+        .iter()
+        .filter(|event| {
+            !matches!(
+                event,
+                TracingEvent::Flush { .. } | TracingEvent::Shutdown { .. }
+            )
+        })
+        .count();
+    assert_matches!(
+        events.last(),
+        Some(TracingEvent::Shutdown { sequence: seq }) if *seq as usize == sequence
+    );
+
+    let flushes = Arc::new(Mutex::new(vec![]));
+    let flushes_in_hook = Arc::clone(&flushes);
+    let mut receiver = TracingEventReceiver::builder()
+        .with_flush_hook(move |status| flushes_in_hook.lock().unwrap().push(*status))
+        .build();
+    receiver.receive(TracingEvent::Heartbeat {
+        sequence: 0,
+        dropped_events: 0,
+    });
+    for event in events {
+        receiver.receive(event);
+    }
+
+    let flushes = flushes.lock().unwrap();
+    assert_eq!(flushes.len(), 2);
+    assert!(!flushes[0].is_final);
+    assert!(flushes[0].sequence > 0 && (flushes[0].sequence as usize) < sequence);
+    assert!(flushes[1].is_final);
+    assert_eq!(flushes[1].sequence as usize, sequence);
+    assert_eq!(receiver.last_flush(), Some(flushes[1]));
+    let counters = receiver.counters();
+    assert_eq!((counters.flushes, counters.shutdowns), (1, 1));
+    // Markers do not affect the heartbeat sequence.
+    let heartbeat = receiver.last_heartbeat().unwrap();
+    assert_eq!(heartbeat.events_since as usize, sequence);
+}
+
 #[test]
 #[allow(clippy::needless_collect)] // necessary for threads to be concurrent
 fn concurrent_senders() {
@@ -323,3 +588,702 @@ fn assert_valid_refs(events: &[TracingEvent]) {
         }
     }
 }
+
+type BoxedSender = TracingEventSender<Box<dyn Fn(TracingEvent) + Send + Sync>>;
+
+#[test]
+fn sender_hook_emitting_events_via_another_sender() {
+    let inner_events = Arc::new(Mutex::new(vec![]));
+    let inner_events_ = Arc::clone(&inner_events);
+    let inner_sender = Arc::new(TracingEventSender::new(move |event| {
+        inner_events_.lock().unwrap().push(event);
+    }));
+    let inner_sender_ = Arc::clone(&inner_sender);
+    let outer_sender = TracingEventSender::new(move |event| {
+        if let TracingEvent::SpanExited { id } = event {
+            // Emulate the hook forwarding events to another sender.
+            inner_sender_.exit(&Id::from_u64(id));
+            inner_sender_.flush();
+        }
+    });
+    let outer_sender = Arc::new(outer_sender);
+
+    tracing::subscriber::with_default(Arc::clone(&outer_sender), || {
+        tracing::info_span!("forwarded").in_scope(|| {});
+    });
+    assert_eq!(outer_sender.suppressed_events(), 0);
+    assert_eq!(inner_sender.suppressed_events(), 0);
+    let inner_events = inner_events.lock().unwrap();
+    assert_matches!(
+        inner_events.as_slice(),
+        [
+            TracingEvent::SpanExited { id: 1 },
+            TracingEvent::Flush { sequence: 1 }
+        ]
+    );
+}
+
+static HOOK_CALL_SITE: DefaultCallsite = DefaultCallsite::new(HOOK_METADATA);
+static HOOK_METADATA: &Metadata<'static> = &Metadata::new(
+    "hook",
+    "tracing_tunnel",
+    Level::INFO,
+    Some("hook.rs"),
+    Some(1),
+    Some("hook"),
+    FieldSet::new(&[], tracing_core::identify_callsite!(&HOOK_CALL_SITE)),
+    Kind::SPAN,
+);
+
+fn sender_with_hook(
+    hook: impl Fn(&BoxedSender, &TracingEvent) + Send + Sync + 'static,
+) -> (Arc<BoxedSender>, Arc<Mutex<Vec<TracingEvent>>>) {
+    let events = Arc::new(Mutex::new(vec![]));
+    let events_in_hook = Arc::clone(&events);
+    let sender_in_hook = Arc::new(OnceLock::<Weak<BoxedSender>>::new());
+    let sender_cell = Arc::clone(&sender_in_hook);
+    let hook = move |event| {
+        if let Some(sender) = sender_in_hook.get().and_then(Weak::upgrade) {
+            // Emulate the hook calling the sender explicitly.
+            hook(&sender, &event);
+        }
+        events_in_hook.lock().unwrap().push(event);
+    };
+    let sender: Arc<BoxedSender> = Arc::new(TracingEventSender::new(Box::new(hook)));
+    sender_cell.set(Arc::downgrade(&sender)).unwrap();
+    (sender, events)
+}
+
+#[test]
+fn events_emitted_from_sender_hook_are_suppressed() {
+    Lazy::force(&EVENTS);
+
+    let (sender, events) = sender_with_hook(|sender, _| {
+        let values: [(&Field, Option<&dyn Value>); 0] = [];
+        let values = HOOK_METADATA.fields().value_set(&values);
+        let span = sender.new_span(&Attributes::new_root(HOOK_METADATA, &values));
+        sender.enter(&span);
+        sender.exit(&span);
+        let span_clone = sender.clone_span(&span);
+        assert!(!sender.try_close(span_clone));
+        assert!(sender.try_close(span));
+        sender.flush();
+    });
+
+    tracing::subscriber::with_default(Arc::clone(&sender), || {
+        tracing::info_span!("reentrancy").in_scope(|| {});
+    });
+    let events = mem::take(&mut *events.lock().unwrap());
+    // The span, its 5 lifecycle events and the flush marker are suppressed per hook call.
+    assert_eq!(sender.suppressed_events() as usize, events.len() * 7);
+    let span_events: Vec<_> = events
+        .iter()
+        .filter(|event| !matches!(event, TracingEvent::NewCallSite { .. }))
+        .collect();
+    assert_matches!(
+        span_events.as_slice(),
+        [
+            TracingEvent::NewSpan { id, .. },
+            TracingEvent::SpanEntered { id: entered_id },
+            TracingEvent::SpanExited { id: exited_id },
+            TracingEvent::SpanDropped { id: dropped_id },
+        ] if [entered_id, exited_id, dropped_id] == [id; 3]
+    );
+
+    let mut receiver = TracingEventReceiver::default();
+    for event in events {
+        receiver.try_receive(event).unwrap();
+    }
+}
+
+#[test]
+fn span_lifecycle_events_emitted_from_sender_hook_are_sent() {
+    let span_clone = Mutex::new(None);
+    let (sender, events) = sender_with_hook(move |sender, event| {
+        let outer_span = Id::from_u64(1);
+        match event {
+            TracingEvent::SpanEntered { id: 1 } => {
+                *span_clone.lock().unwrap() = Some(sender.clone_span(&outer_span));
+            }
+            TracingEvent::SpanExited { id: 1 } => {
+                let span_clone = span_clone.lock().unwrap().take().unwrap();
+                assert!(!sender.try_close(span_clone));
+            }
+            _ => { /* do nothing */ }
+        }
+        // Emulate `Span::current().in_scope(..)`; these events cancel each other out.
+        if matches!(event, TracingEvent::NewCallSite { .. }) {
+            return;
+        }
+        let current = sender.clone_span(&outer_span);
+        sender.enter(&current);
+        sender.exit(&current);
+        sender.try_close(current);
+    });
+
+    tracing::subscriber::with_default(Arc::clone(&sender), || {
+        tracing::info_span!("reentrancy").in_scope(|| {});
+    });
+    assert_eq!(sender.suppressed_events(), 0);
+    let events = mem::take(&mut *events.lock().unwrap());
+    let span_events: Vec<_> = events
+        .iter()
+        .filter(|event| !matches!(event, TracingEvent::NewCallSite { .. }))
+        .collect();
+    assert_matches!(
+        span_events.as_slice(),
+        [
+            TracingEvent::NewSpan { id: 1, .. },
+            TracingEvent::SpanEntered { id: 1 },
+            TracingEvent::SpanCloned { id: 1 },
+            TracingEvent::SpanExited { id: 1 },
+            TracingEvent::SpanDropped { id: 1 },
+            TracingEvent::SpanDropped { id: 1 },
+        ]
+    );
+
+    let mut receiver = TracingEventReceiver::default();
+    for event in events {
+        receiver.try_receive(event).unwrap();
+    }
+}
+
+#[test]
+fn truncating_values_in_sender() {
+    #[derive(Debug)]
+    struct WrappedError(fmt::Error);
+
+    impl fmt::Display for WrappedError {
+        fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+            formatter.write_str("wrapped error")
+        }
+    }
+
+    impl error::Error for WrappedError {
+        fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+            Some(&self.0)
+        }
+    }
+
+    Lazy::force(&EVENTS);
+
+    let (events_sx, events_rx) = mpsc::channel();
+    let limits = ValueLimits::default()
+        .with_max_str_len(8)
+        .with_max_debug_len(10)
+        .with_max_error_depth(1);
+    let sender = TracingEventSender::new(move |event| {
+        events_sx.send(event).ok();
+    });
+    let sender = Arc::new(sender.with_value_limits(limits));
+
+    tracing::subscriber::with_default(Arc::clone(&sender), || {
+        let span = tracing::info_span!("truncated", payload = ?[0_u8; 32], short = "ok");
+        span.record("short", "long enough");
+        let err = WrappedError(fmt::Error);
+        tracing::warn!(err = &err as &dyn error::Error, "failed");
+    });
+    assert_eq!(sender.truncated_values(), 3);
+
+    let mut receiver = TracingEventReceiver::default();
+    for event in events_rx.try_iter() {
+        match &event {
+            TracingEvent::NewSpan { values, .. } => {
+                assert_eq!(
+                    values["payload"].as_debug_str(),
+                    Some("[0, 0, 0, …[truncated]")
+                );
+                assert_eq!(values["short"], "ok");
+            }
+            TracingEvent::ValuesRecorded { values, .. } => {
+                assert_eq!(values["short"], "long eno…[truncated]");
+            }
+            TracingEvent::NewEvent { values, .. } => {
+                let TracedValue::Error(err) = &values["err"] else {
+                    panic!("unexpected value: {:?}", values["err"]);
+                };
+                assert_eq!(err.message, "wrapped error…[truncated]");
+                assert!(err.source.is_none());
+            }
+            _ => { /* Do nothing */ }
+        }
+        receiver.try_receive(event).unwrap();
+    }
+}
+
+#[test]
+fn attaching_root_spans_to_local_parent() {
+    use tracing_core::span::Attributes;
+    use tracing_subscriber::{
+        layer::{Context, SubscriberExt},
+        Layer, Registry,
+    };
+
+    type SpanParents = Arc<Mutex<Vec<(&'static str, Option<&'static str>)>>>;
+
+    Lazy::force(&EVENTS);
+
+    struct ParentsLayer(SpanParents);
+
+    impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for ParentsLayer {
+        fn on_new_span(&self, _: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+            let span = ctx.span(id).unwrap();
+            let parent_name = span.parent().map(|parent| parent.name());
+            self.0.lock().unwrap().push((span.name(), parent_name));
+        }
+    }
+
+    let parents = SpanParents::default();
+    let subscriber = Registry::default().with(ParentsLayer(Arc::clone(&parents)));
+    tracing::subscriber::with_default(subscriber, || {
+        let invocation_span = tracing::info_span!("invocation");
+        let mut receiver = TracingEventReceiver::default();
+        receiver.set_root_parent(invocation_span.id());
+        for event in EVENTS.short.clone() {
+            receiver.receive(event);
+        }
+    });
+
+    let parents = parents.lock().unwrap();
+    assert_eq!(
+        parents.as_slice(),
+        [
+            ("invocation", None),
+            ("fib", Some("invocation")),
+            ("compute", Some("fib")),
+        ]
+    );
+}
+
+#[test]
+fn attaching_root_spans_to_parents_in_multiple_dispatches() {
+    use tracing_core::{span::Attributes, Dispatch};
+    use tracing_subscriber::{
+        layer::{Context, SubscriberExt},
+        Layer, Registry,
+    };
+
+    type SpanLog = Arc<Mutex<Vec<String>>>;
+
+    Lazy::force(&EVENTS);
+
+    struct LogLayer(SpanLog);
+
+    impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for LogLayer {
+        fn on_new_span(&self, _: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+            let span = ctx.span(id).unwrap();
+            let parent_name = span.parent().map_or("-", |parent| parent.name());
+            let entry = format!("new {} < {parent_name}", span.name());
+            self.0.lock().unwrap().push(entry);
+        }
+
+        fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+            let name = ctx.span(&id).unwrap().name();
+            self.0.lock().unwrap().push(format!("close {name}"));
+        }
+    }
+
+    let logs = [SpanLog::default(), SpanLog::default()];
+    let dispatches = logs.clone().map(|log| {
+        let subscriber = Registry::default().with(LogLayer(log));
+        Dispatch::new(subscriber)
+    });
+    // The parent span is only created in the second dispatch.
+    let parent =
+        tracing::dispatcher::with_default(&dispatches[1], || tracing::info_span!("invocation"));
+    let mut receiver = TracingEventReceiver::builder()
+        .with_dispatches(dispatches)
+        .build();
+    receiver.set_root_parents([None, parent.id()]);
+    // The receiver keeps the parent alive after its handle is dropped.
+    drop(parent);
+    assert_eq!(logs[1].lock().unwrap().len(), 1);
+
+    for event in EVENTS.short.clone() {
+        receiver.receive(event);
+    }
+    receiver.set_root_parent(None);
+
+    let first_log = logs[0].lock().unwrap();
+    assert_eq!(first_log[0], "new fib < -");
+    assert!(!first_log.iter().any(|entry| entry.contains("invocation")));
+    let second_log = logs[1].lock().unwrap();
+    assert_eq!(second_log[1], "new fib < invocation");
+    assert_eq!(second_log.last().unwrap(), "close invocation");
+}
+
+#[test]
+fn comparing_wire_types() {
+    let events = &EVENTS.short;
+    let json = serde_json::to_string(events).unwrap();
+    let restored: Vec<TracingEvent> = serde_json::from_str(&json).unwrap();
+    assert_eq!(restored, *events);
+
+    let call_sites: HashSet<_> = events
+        .iter()
+        .filter_map(|event| match event {
+            TracingEvent::NewCallSite { data, .. } => Some(data),
+            _ => None,
+        })
+        .collect();
+    let call_site_count = events
+        .iter()
+        .filter(|event| matches!(event, TracingEvent::NewCallSite { .. }))
+        .count();
+    assert_eq!(call_sites.len(), call_site_count);
+
+    let values: TracedValues<String> = [
+        ("num".to_owned(), TracedValue::from(42_u64)),
+        ("s".to_owned(), TracedValue::from("test")),
+    ]
+    .into_iter()
+    .collect();
+    let reordered: TracedValues<&str> = [("s", "test".into()), ("num", 42_u64.into())]
+        .into_iter()
+        .collect();
+    assert_eq!(values, reordered);
+    assert_ne!(values["num"], TracedValue::from(42_i64));
+    assert_ne!(TracedValue::from(f64::NAN), TracedValue::from(f64::NAN));
+}
+
+#[test]
+fn receiving_events_in_detached_mode() {
+    use tracing_core::{span::Attributes, Event};
+    use tracing_subscriber::{
+        layer::{Context, SubscriberExt},
+        Layer, Registry,
+    };
+
+    #[derive(Debug, Default)]
+    struct Recorded {
+        span_parents: Vec<(&'static str, Option<&'static str>)>,
+        event_parents: Vec<Option<&'static str>>,
+        entered_spans: Vec<&'static str>,
+    }
+
+    struct RecordingLayer(Arc<Mutex<Recorded>>);
+
+    impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for RecordingLayer {
+        fn on_new_span(&self, _: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+            let span = ctx.span(id).unwrap();
+            let parent_name = span.parent().map(|parent| parent.name());
+            let mut recorded = self.0.lock().unwrap();
+            recorded.span_parents.push((span.name(), parent_name));
+        }
+
+        fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+            let parent_name = ctx.event_span(event).map(|span| span.name());
+            self.0.lock().unwrap().event_parents.push(parent_name);
+        }
+
+        fn on_enter(&self, id: &Id, ctx: Context<'_, S>) {
+            let name = ctx.span(id).unwrap().name();
+            self.0.lock().unwrap().entered_spans.push(name);
+        }
+    }
+
+    Lazy::force(&EVENTS);
+
+    let recorded = Arc::<Mutex<Recorded>>::default();
+    let subscriber = Registry::default().with(RecordingLayer(Arc::clone(&recorded)));
+    tracing::subscriber::with_default(subscriber, || {
+        let _entered = tracing::info_span!("host").entered();
+        let mut receiver = TracingEventReceiver::builder()
+            .with_dispatch_mode(DispatchMode::Detached)
+            .build();
+        for event in EVENTS.short.clone() {
+            receiver.receive(event);
+            let current = tracing::Span::current();
+            assert_eq!(current.metadata().unwrap().name(), "host");
+        }
+    });
+
+    let recorded = recorded.lock().unwrap();
+    assert_eq!(
+        recorded.span_parents,
+        [("host", None), ("fib", None), ("compute", Some("fib"))]
+    );
+    assert_eq!(recorded.entered_spans, ["host"]);
+    let fib_event_count = recorded
+        .event_parents
+        .iter()
+        .filter(|&&parent| parent == Some("fib"))
+        .count();
+    assert_eq!(fib_event_count, 2); // warning + result
+    let compute_event_count = recorded
+        .event_parents
+        .iter()
+        .filter(|&&parent| parent == Some("compute"))
+        .count();
+    assert_eq!(compute_event_count, recorded.event_parents.len() - 2);
+}
+
+#[test]
+fn simple_events_are_handled_transparently() {
+    fn record_events<F: Fn(TracingEvent) + Send + Sync + 'static>(sender: TracingEventSender<F>) {
+        tracing::subscriber::with_default(sender, || {
+            let span = tracing::info_span!("greet");
+            let _entered = span.enter();
+            tracing::info!("hello");
+            tracing::info!(answer = 42, "with fields");
+            tracing::warn!(parent: &span, "explicit parent");
+        });
+    }
+
+    fn event_values(events: &[TracingEvent]) -> Vec<&TracedValues<String>> {
+        let values = events.iter().filter_map(|event| match event {
+            TracingEvent::NewEvent { values, .. } => Some(values),
+            _ => None,
+        });
+        values.collect()
+    }
+
+    Lazy::force(&EVENTS);
+
+    let (events_sx, events_rx) = mpsc::channel();
+    record_events(TracingEventSender::new(move |event| {
+        events_sx.send(event).ok();
+    }));
+    let plain_events: Vec<_> = events_rx.iter().collect();
+    assert!(plain_events
+        .iter()
+        .all(|event| !matches!(event, TracingEvent::SimpleEvent { .. })));
+
+    let (events_sx, events_rx) = mpsc::channel();
+    let sender = TracingEventSender::new(move |event| {
+        events_sx.send(event).ok();
+    });
+    record_events(sender.with_receiver_version(TracingEvent::WIRE_FORMAT_VERSION));
+    let compact_events: Vec<_> = events_rx.iter().collect();
+    let simple_events: Vec<_> = compact_events
+        .iter()
+        .filter_map(|event| match event {
+            TracingEvent::SimpleEvent { message, .. } => Some(message.as_str()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(simple_events, ["hello"]);
+    assert_eq!(event_values(&compact_events).len(), 2);
+
+    let plain_size = serde_json::to_string(&plain_events).unwrap().len();
+    let compact_size = serde_json::to_string(&compact_events).unwrap().len();
+    assert!(compact_size < plain_size, "{compact_size} >= {plain_size}");
+
+    // Relay simple events via a receiver to a sender emitting only `NewEvent`s.
+    let (events_sx, events_rx) = mpsc::channel();
+    let sender = TracingEventSender::new(move |event| {
+        events_sx.send(event).ok();
+    });
+    tracing::subscriber::with_default(sender, || {
+        let mut receiver = TracingEventReceiver::default();
+        for event in compact_events {
+            receiver.try_receive(event).unwrap();
+        }
+        assert_eq!(receiver.counters().new_events, 3);
+    });
+    let relayed_events: Vec<_> = events_rx.iter().collect();
+    assert_eq!(event_values(&relayed_events), event_values(&plain_events));
+}
+
+#[test]
+fn byte_values_are_relayed() {
+    fn payloads(events: &[TracingEvent]) -> Vec<&TracedValue> {
+        let payloads = events.iter().filter_map(|event| match event {
+            TracingEvent::NewEvent { values, .. } => values.get("payload"),
+            _ => None,
+        });
+        payloads.collect()
+    }
+
+    Lazy::force(&EVENTS);
+
+    let (events_sx, events_rx) = mpsc::channel();
+    let sender = TracingEventSender::new(move |event| {
+        events_sx.send(event).ok();
+    });
+    tracing::subscriber::with_default(sender, || {
+        let payload = b"\xde\xad\xbe\xef";
+        tracing::info!(payload = &payload[..], "sent payload");
+        tracing::info!(payload = ?&payload[..], "sent payload via Debug");
+    });
+    let events: Vec<_> = events_rx.iter().collect();
+    let sent_payloads = payloads(&events);
+    assert_eq!(sent_payloads[0].as_bytes(), Some(&b"\xde\xad\xbe\xef"[..]));
+    assert_eq!(
+        sent_payloads[1].as_debug_str(),
+        Some("[222, 173, 190, 239]")
+    );
+
+    let (relayed_sx, relayed_rx) = mpsc::channel();
+    let sender = TracingEventSender::new(move |event| {
+        relayed_sx.send(event).ok();
+    });
+    tracing::subscriber::with_default(sender, || {
+        let mut receiver = TracingEventReceiver::default();
+        for event in events.clone() {
+            receiver.try_receive(event).unwrap();
+        }
+    });
+    let relayed_events: Vec<_> = relayed_rx.iter().collect();
+    assert_eq!(payloads(&relayed_events), sent_payloads);
+}
+
+#[test]
+fn layered_sender_tees_data_to_local_subscriber() {
+    use tracing_core::{span::Attributes, Event};
+    use tracing_subscriber::{
+        filter::LevelFilter,
+        layer::{Context, SubscriberExt},
+        Layer, Registry,
+    };
+
+    #[derive(Debug, Default)]
+    struct Recorded {
+        span_parents: Vec<(&'static str, Option<&'static str>)>,
+        event_parents: Vec<Option<&'static str>>,
+        closed_spans: Vec<&'static str>,
+    }
+
+    struct RecordingLayer(Arc<Mutex<Recorded>>);
+
+    impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for RecordingLayer {
+        fn on_new_span(&self, _: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+            let span = ctx.span(id).unwrap();
+            let parent_name = span.parent().map(|parent| parent.name());
+            let mut recorded = self.0.lock().unwrap();
+            recorded.span_parents.push((span.name(), parent_name));
+        }
+
+        fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+            let parent_name = ctx.event_span(event).map(|span| span.name());
+            self.0.lock().unwrap().event_parents.push(parent_name);
+        }
+
+        fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+            let name = ctx.span(&id).unwrap().name();
+            self.0.lock().unwrap().closed_spans.push(name);
+        }
+    }
+
+    Lazy::force(&EVENTS);
+
+    let recorded = Arc::<Mutex<Recorded>>::default();
+    let local = Registry::default()
+        .with(LevelFilter::INFO)
+        .with(RecordingLayer(Arc::clone(&recorded)));
+    let (events_sx, events_rx) = mpsc::channel();
+    let subscriber = TracingEventSender::layered(local, move |event| {
+        events_sx.send(event).ok();
+    });
+    tracing::subscriber::with_default(subscriber, || {
+        let outer = tracing::info_span!("outer");
+        let _entered = outer.enter();
+        tracing::debug_span!("inner").in_scope(|| {
+            tracing::info!("contextual");
+            tracing::debug!("filtered");
+        });
+        tracing::info_span!(parent: &outer, "explicit_child").in_scope(|| {
+            tracing::info!(parent: &outer, "explicit");
+        });
+    });
+
+    let recorded = recorded.lock().unwrap();
+    assert_eq!(
+        recorded.span_parents,
+        [("outer", None), ("explicit_child", Some("outer"))]
+    );
+    // The event with the explicit parent is not relayed to the local subscriber.
+    assert_eq!(recorded.event_parents, [Some("outer")]);
+    assert_eq!(recorded.closed_spans, ["explicit_child", "outer"]);
+
+    let events: Vec<_> = events_rx.iter().collect();
+    let span_count = events
+        .iter()
+        .filter(|event| matches!(event, TracingEvent::NewSpan { .. }))
+        .count();
+    assert_eq!(span_count, 3);
+    let event_count = events
+        .iter()
+        .filter(|event| matches!(event, TracingEvent::NewEvent { .. }))
+        .count();
+    assert_eq!(event_count, 3);
+    assert_valid_refs(&events);
+}
+
+#[test]
+fn routing_events_by_target() {
+    Lazy::force(&EVENTS);
+
+    let (traces_sx, traces_rx) = mpsc::channel();
+    let (metrics_sx, metrics_rx) = mpsc::channel();
+    let routes = TargetRoutes::new(move |event| {
+        traces_sx.send(event).ok();
+    })
+    .with_route("metrics", move |event| {
+        metrics_sx.send(event).ok();
+    });
+    let sender = Arc::new(TracingEventSender::routed(routes));
+    tracing::subscriber::with_default(Arc::clone(&sender), || {
+        let span = tracing::info_span!("request");
+        span.in_scope(|| {
+            tracing::info!("started");
+            tracing::info!(target: "metrics::http", parent: &span, latency_ms = 10);
+            let batch = tracing::info_span!(target: "metrics", "batch", size = 2);
+            batch.follows_from(&span);
+            batch.in_scope(|| {
+                tracing::info!(target: "metrics", count = 1);
+                tracing::info!(target: "metrics_exporter", "not a metric");
+            });
+        });
+    });
+    sender.flush();
+    drop(sender);
+
+    let traces: Vec<_> = traces_rx.iter().collect();
+    let metrics: Vec<_> = metrics_rx.iter().collect();
+    for events in [&traces, &metrics] {
+        assert_valid_refs(events);
+        assert_matches!(events.last(), Some(TracingEvent::Flush { .. }));
+        let mut receiver = TracingEventReceiver::default();
+        for event in events.iter().cloned() {
+            receiver.try_receive(event).unwrap();
+        }
+    }
+
+    let trace_targets: HashSet<_> = traces
+        .iter()
+        .filter_map(|event| match event {
+            TracingEvent::NewCallSite { data, .. } => Some(data.target.as_ref()),
+            _ => None,
+        })
+        .collect();
+    assert!(!trace_targets
+        .iter()
+        .any(|target| target.starts_with("metrics::")));
+    assert!(trace_targets.contains("metrics_exporter"));
+
+    let metric_events: Vec<_> = metrics
+        .iter()
+        .filter_map(|event| match event {
+            TracingEvent::NewEvent {
+                parent,
+                is_root,
+                values,
+                ..
+            } => Some((*parent, *is_root, values)),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(metric_events.len(), 2);
+    // The explicit parent from the trace route is replaced with an explicit root.
+    assert_eq!(metric_events[0].0, None);
+    assert!(metric_events[0].1);
+    assert_eq!(metric_events[0].2["latency_ms"], 10_i64);
+    // The contextual parent is retained.
+    assert_eq!(metric_events[1].0, None);
+    assert!(!metric_events[1].1);
+    assert_eq!(metric_events[1].2["count"], 1_i64);
+    assert!(!metrics
+        .iter()
+        .any(|event| matches!(event, TracingEvent::FollowsFrom { .. })));
+}